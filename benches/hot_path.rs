@@ -0,0 +1,161 @@
+//! Micro-benchmarks for the functions every probe goes through
+//!
+//! Run with `make bench-hot`. After Criterion's own report, the results are
+//! summarised through `phobos::benchmark`, and the run fails if any of them
+//! got slower than `REGRESSION_THRESHOLD` since the previous one.
+
+use criterion::{black_box, criterion_group, BenchmarkId, Criterion, Throughput};
+use phobos::benchmark::{criterion_home, criterion_results, Benchmark, REGRESSION_THRESHOLD};
+use phobos::config::ScanConfig;
+use phobos::network::packet::{PacketParser, TcpPacketBuilder, UdpPacketBuilder};
+use phobos::network::{PortResult, PortState, Protocol, ScanTechnique};
+use phobos::scanner::engine::ScanEngine;
+use phobos::scanner::ScanResult;
+use phobos::utils::port_spec::PortSpec;
+use phobos_packet::{internet_checksum, Checksum, IPPROTO_TCP};
+use std::io;
+use std::net::Ipv4Addr;
+
+/// Criterion groups below, which the summary is read back from
+const GROUPS: [&str; 5] = ["packet_building", "checksum", "error_classification", "port_parsing", "result_aggregation"];
+
+const SOURCE: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+const TARGET: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 100);
+
+fn bench_packet_building(c: &mut Criterion) {
+    let mut group = c.benchmark_group("packet_building");
+    group.bench_function("tcp_syn", |b| {
+        b.iter(|| TcpPacketBuilder::new(black_box(SOURCE), black_box(TARGET), 40000, black_box(443)).syn().build())
+    });
+    group.bench_function("tcp_syn_with_options", |b| {
+        b.iter(|| {
+            TcpPacketBuilder::new(black_box(SOURCE), black_box(TARGET), 40000, black_box(443))
+                .syn()
+                .mss(1460)
+                .timestamp(0x5048_4f42)
+                .build()
+        })
+    });
+    group.bench_function("udp_dns", |b| {
+        let payload = vec![0u8; 29];
+        b.iter(|| UdpPacketBuilder::new(black_box(SOURCE), black_box(TARGET), 40000, 53).payload(payload.clone()).build())
+    });
+    let reply = TcpPacketBuilder::new(TARGET, SOURCE, 443, 40000).flags(0x12).build();
+    group.bench_function("parse_syn_ack", |b| b.iter(|| PacketParser::parse_tcp_response(black_box(&reply))));
+    group.finish();
+}
+
+fn bench_checksum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checksum");
+    for size in [20usize, 40, 576, 1500] {
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("internet", size), &data, |b, data| {
+            b.iter(|| internet_checksum(black_box(data)))
+        });
+        group.bench_with_input(BenchmarkId::new("tcp_pseudo_header", size), &data, |b, data| {
+            b.iter(|| {
+                Checksum::new()
+                    .add_ipv4_pseudo_header(SOURCE.octets(), TARGET.octets(), IPPROTO_TCP, data.len() as u16)
+                    .add_bytes(black_box(data))
+                    .finish()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_error_classification(c: &mut Criterion) {
+    let mut group = c.benchmark_group("error_classification");
+    let errors = [
+        ("refused", io::Error::from(io::ErrorKind::ConnectionRefused)),
+        ("timed_out", io::Error::from(io::ErrorKind::TimedOut)),
+        // Falls through to matching the message, the slow path
+        ("nested_timeout", io::Error::new(io::ErrorKind::Other, "deadline has elapsed: operation timed out")),
+    ];
+    for (name, error) in &errors {
+        group.bench_function(*name, |b| b.iter(|| ScanEngine::classify_error(black_box(error))));
+    }
+    group.finish();
+}
+
+fn bench_port_parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("port_parsing");
+    for spec in ["1-65535", "21,22,23,25,53,80,110,143,443,3306,8080", "top1000,web,!T:25,U:53,161"] {
+        group.bench_with_input(BenchmarkId::new("parse_and_resolve", spec), spec, |b, spec| {
+            b.iter(|| PortSpec::parse(black_box(spec)).unwrap().ports_for(Protocol::Tcp))
+        });
+    }
+    group.finish();
+}
+
+fn bench_result_aggregation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("result_aggregation");
+    let config = ScanConfig::default();
+    let results: Vec<PortResult> = (1..=1000u16)
+        .map(|port| {
+            let state = if port % 50 == 0 { PortState::Open } else { PortState::Closed };
+            PortResult::new(port, Protocol::Tcp, state)
+        })
+        .collect();
+    group.throughput(Throughput::Elements(results.len() as u64));
+    // Results of separate hosts, appended as they are
+    group.bench_function("push_1000", |b| {
+        b.iter(|| {
+            let mut scan = ScanResult::new("192.168.1.100".to_string(), config.clone());
+            for result in &results {
+                scan.push_port_result(result.clone());
+            }
+            scan
+        })
+    });
+    // A second technique's results, merged with the first's
+    group.bench_function("merge_1000", |b| {
+        let mut first = ScanResult::new("192.168.1.100".to_string(), config.clone());
+        for result in &results {
+            first.push_port_result(result.clone());
+        }
+        b.iter(|| {
+            let mut scan = first.clone();
+            for result in &results {
+                scan.add_port_result_from(result.clone(), ScanTechnique::Ack);
+            }
+            scan
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    hot_path,
+    bench_packet_building,
+    bench_checksum,
+    bench_error_classification,
+    bench_port_parsing,
+    bench_result_aggregation
+);
+
+fn main() {
+    hot_path();
+    criterion::Criterion::default().configure_from_args().final_summary();
+
+    let mut report = Benchmark::init();
+    for group in GROUPS {
+        match criterion_results(&criterion_home().join(group)) {
+            Ok(measurements) => measurements.into_iter().for_each(|m| report.push_measurement(m)),
+            Err(e) => eprintln!("cannot read the {} results: {}", group, e),
+        }
+    }
+    report.print_summary();
+
+    let regressions: Vec<_> = report.regressions(REGRESSION_THRESHOLD).map(|m| m.name.clone()).collect();
+    if !regressions.is_empty() {
+        eprintln!(
+            "\n{} got more than {:.0}% slower since the previous run: {}",
+            regressions.len(),
+            REGRESSION_THRESHOLD * 100.0,
+            regressions.join(", ")
+        );
+        std::process::exit(1);
+    }
+}
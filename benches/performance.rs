@@ -110,7 +110,7 @@ fn bench_timing(c: &mut Criterion) {
     });
     
     group.bench_function("rate_limiter_acquire", |b| {
-        let mut rate_limiter = phobos::network::protocol::RateLimiter::new(1_000_000);
+        let rate_limiter = phobos::network::protocol::RateLimiter::new(1_000_000);
         b.iter(|| {
             rate_limiter.can_send()
         })
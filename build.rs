@@ -22,6 +22,16 @@ fn main() {
         println!("cargo:warning=Building with ARM64 native optimizations");
     }
     
+    // Git commit for report provenance; "unknown" outside a checkout
+    for path in [".git/HEAD", ".git/index"] {
+        if std::path::Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    let commit = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|s| !s.is_empty());
+    println!("cargo:rustc-env=PHOBOS_GIT_COMMIT={}{}", commit, if dirty { "-dirty" } else { "" });
+    
     // Release profile için ekstra optimizasyonlar
     if let Ok(profile) = env::var("PROFILE") {
         if profile == "release" {
@@ -29,3 +39,8 @@ fn main() {
         }
     }
 }
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new("git").args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -8,13 +8,17 @@ _phobos_completions() {
     prev="${COMP_WORDS[COMP_CWORD-1]}"
     
     # Main options
-    opts="--help --version --benchmark --accessible --no-banner --greppable --verbose --no-color --ports-only --no-nmap --all --top --full-range --udp --adaptive --wrath --shadow --os-detect --update --list-profiles --system-check --validate-config"
+    opts="--help --version --benchmark --profile-out --accessible --no-banner --greppable --verbose --stats --progress-json --estimate --no-color --ports-only --lenient-ports --no-nmap --event-log --all --top --full-range --udp --adaptive --wrath --shadow --os-detect --update --check-only --from-source --allow-unsigned --list-profiles --system-check --healthcheck --listen --watch --schedule --history-dir --diff-format --history-diff --webhook --notify-url --notify-format --validate-config --rst-on-close --throttle-local --min-rate --max-rate --ramp-up --ramp-start --no-redact-secrets --notrack --send-thread --auto-technique --verify-open --decoys --proxy --access-matrix --source-zone --output-compliance --workspace --sV --service-version --resume --on-scan-start --on-host-start --on-host-complete --on-scan-complete --dns-sweep --web-enrich --default-creds --i-have-authorization --yes --harden --harden-user"
     
     # Options with arguments
     case "${prev}" in
+        run)
+            COMPREPLY=( $(compgen -f -X '!*.phobos' -- ${cur}) $(compgen -d -- ${cur}) )
+            return 0
+            ;;
         -p|--ports)
             # Port suggestions
-            COMPREPLY=( $(compgen -W "22 80 443 8080 1-1000 1-65535" -- ${cur}) )
+            COMPREPLY=( $(compgen -W "22 80 443 8080 1-1000 1-65535 top100 web database" -- ${cur}) )
             return 0
             ;;
         -s|--scan-type)
@@ -29,6 +33,14 @@ _phobos_completions() {
             COMPREPLY=( $(compgen -W "0 1 2 3 4 5" -- ${cur}) )
             return 0
             ;;
+        --top-ports)
+            COMPREPLY=( $(compgen -W "10 100 1000 5000 10000" -- ${cur}) )
+            return 0
+            ;;
+        --stats-every)
+            COMPREPLY=( $(compgen -W "10s 30s 1m 5m" -- ${cur}) )
+            return 0
+            ;;
         --threads)
             COMPREPLY=( $(compgen -W "10 50 100 500 1000 5000" -- ${cur}) )
             return 0
@@ -41,31 +53,150 @@ _phobos_completions() {
             COMPREPLY=( $(compgen -W "1000 10000 100000 1000000 10000000" -- ${cur}) )
             return 0
             ;;
+        --min-rate|--max-rate)
+            COMPREPLY=( $(compgen -W "10 100 1000 10000" -- ${cur}) )
+            return 0
+            ;;
+        --ramp-up)
+            COMPREPLY=( $(compgen -W "10s 30s 1m 5m" -- ${cur}) )
+            return 0
+            ;;
+        --ramp-start)
+            COMPREPLY=( $(compgen -W "5 10 25 50" -- ${cur}) )
+            return 0
+            ;;
+        --health-check)
+            COMPREPLY=( $(compgen -W "gateway http:// https://" -- ${cur}) )
+            return 0
+            ;;
+        --health-threshold)
+            COMPREPLY=( $(compgen -W "2 3 5 10" -- ${cur}) )
+            return 0
+            ;;
+        --zombie)
+            COMPREPLY=( $(compgen -A hostname -- ${cur}) )
+            return 0
+            ;;
+        -D|--decoys)
+            COMPREPLY=( $(compgen -W "ME RND RND:5" -- ${cur}) )
+            return 0
+            ;;
+        --proxy)
+            COMPREPLY=( $(compgen -W "socks5:// http://" -- ${cur}) )
+            return 0
+            ;;
+        --memory-limit)
+            COMPREPLY=( $(compgen -W "256M 512M 1G 2G" -- ${cur}) )
+            return 0
+            ;;
+        --resume)
+            local checkpoints=$(ls ~/.phobos/checkpoints 2>/dev/null | sed -n 's/\.json$//p')
+            COMPREPLY=( $(compgen -W "${checkpoints}" -- ${cur}) )
+            return 0
+            ;;
+        --on-scan-start|--on-host-start|--on-host-complete|--on-scan-complete)
+            COMPREPLY=( $(compgen -c -- ${cur}) )
+            return 0
+            ;;
+        --max-bandwidth)
+            COMPREPLY=( $(compgen -W "1M 10M 50M 100M 1G" -- ${cur}) )
+            return 0
+            ;;
         -b|--batch-size)
             COMPREPLY=( $(compgen -W "1000 3000 5000 10000 15000" -- ${cur}) )
             return 0
             ;;
+        --host-concurrency)
+            COMPREPLY=( $(compgen -W "10 100 500 1000" -- ${cur}) )
+            return 0
+            ;;
         -o|--output|--output-format)
-            COMPREPLY=( $(compgen -W "text json xml csv nmap greppable" -- ${cur}) )
+            COMPREPLY=( $(compgen -W "text json xml csv nmap greppable msf stix ocsf sqlite" -- ${cur}) )
             return 0
             ;;
         --profile)
-            COMPREPLY=( $(compgen -W "stealth aggressive comprehensive quick" -- ${cur}) )
+            COMPREPLY=( $(compgen -W "stealth aggressive comprehensive quick constrained" -- ${cur}) )
             return 0
             ;;
         --scripts)
             COMPREPLY=( $(compgen -W "none default custom all adaptive" -- ${cur}) )
             return 0
             ;;
+        --harden)
+            COMPREPLY=( $(compgen -W "standard strict" -- ${cur}) )
+            return 0
+            ;;
         --scan-order)
             COMPREPLY=( $(compgen -W "serial random" -- ${cur}) )
             return 0
             ;;
-        -c|--config|--output-file|--output-nmap|-i|--input-file|--script-dir)
+        --host-order)
+            COMPREPLY=( $(compgen -W "serial random interleave" -- ${cur}) )
+            return 0
+            ;;
+        -c|--config|--scope|--profile-out|--output-file|--output-nmap|--output-msf|--output-stix|--output-ocsf|--access-matrix|--output-compliance|--output-defectdojo|--output-faraday|--export-parquet|--archive|--history-dir|--workspace|-i|--input-file|--script-dir)
             # File completion
             COMPREPLY=( $(compgen -f -- ${cur}) )
             return 0
             ;;
+        --notify-format)
+            COMPREPLY=( $(compgen -W "json slack discord" -- ${cur}) )
+            return 0
+            ;;
+        --diff-format)
+            COMPREPLY=( $(compgen -W "text json csv" -- ${cur}) )
+            return 0
+            ;;
+        --schedule)
+            COMPREPLY=( $(compgen -W "@hourly @daily @weekly @monthly" -- ${cur}) )
+            return 0
+            ;;
+        --capture-filter)
+            COMPREPLY=( $(compgen -W "tcp udp icmp not src dst host net port portrange" -- ${cur}) )
+            return 0
+            ;;
+        --vlan)
+            COMPREPLY=( $(compgen -W "1 10 100 4094" -- ${cur}) )
+            return 0
+            ;;
+        --vlan-source)
+            return 0
+            ;;
+        --reflector)
+            COMPREPLY=( $(compgen -W "1.1.1.1:443 8.8.8.8:53 9.9.9.9:443" -- ${cur}) )
+            return 0
+            ;;
+        --mtu-override)
+            COMPREPLY=( $(compgen -W "1280 1380 1420 1500" -- ${cur}) )
+            return 0
+            ;;
+        --geoip)
+            COMPREPLY=( $(compgen -f -- ${cur}) )
+            return 0
+            ;;
+        --report)
+            COMPREPLY=( $(compgen -W "heatmap campaigns diff" -- ${cur}) )
+            return 0
+            ;;
+        --color)
+            COMPREPLY=( $(compgen -W "auto always never" -- ${cur}) )
+            return 0
+            ;;
+        --theme)
+            COMPREPLY=( $(compgen -W "default high-contrast colorblind" -- ${cur}) )
+            return 0
+            ;;
+        --report-format)
+            COMPREPLY=( $(compgen -W "csv json html" -- ${cur}) )
+            return 0
+            ;;
+        --since)
+            COMPREPLY=( $(compgen -W "12h 7d 30d 90d" -- ${cur}) )
+            return 0
+            ;;
+        --geo-origin|--filter|--campaign|--against)
+            return 0
+            ;;
         --interface)
             # Network interfaces
             COMPREPLY=( $(compgen -W "$(ip -o link show | awk -F': ' '{print $2}')" -- ${cur}) )
@@ -77,12 +208,14 @@ _phobos_completions() {
     
     # Default completion with main options
     if [[ ${cur} == -* ]] ; then
-        COMPREPLY=( $(compgen -W "${opts} -p -s -T -b -o -c -i -u -v -g -x -D -O" -- ${cur}) )
+        COMPREPLY=( $(compgen -W "${opts} -p -s -T -b -o -c -i -u -v -g -x -y -D -O" -- ${cur}) )
         return 0
     fi
     
-    # If no option, suggest hostnames or IPs
-    COMPREPLY=( $(compgen -W "localhost 127.0.0.1 scanme.nmap.org" -- ${cur}) )
+    # If no option, suggest hostnames or IPs (or a command first)
+    local words="localhost 127.0.0.1 scanme.nmap.org"
+    [[ ${COMP_CWORD} -eq 1 ]] && words="estimate healthcheck listen watch run report ${words}"
+    COMPREPLY=( $(compgen -W "${words}" -- ${cur}) )
 }
 
 complete -F _phobos_completions phobos
@@ -8,31 +8,56 @@ complete -c phobos -e
 complete -c phobos -s h -l help -d "Show help message"
 complete -c phobos -l version -d "Show version information"
 complete -c phobos -l benchmark -d "Show detailed benchmark information"
+complete -c phobos -l profile-out -d "Write a flamegraph (.svg) or pprof profile of the run" -r -F
 complete -c phobos -l accessible -d "Accessible mode for screen readers"
 complete -c phobos -l no-banner -d "Hide the banner"
 complete -c phobos -s g -l greppable -d "Greppable output format"
 complete -c phobos -s v -l verbose -d "Verbose output"
 complete -c phobos -l no-color -d "Disable colored output"
+complete -c phobos -l color -d "Colored output" -x -a "auto always never"
+complete -c phobos -l theme -d "Color theme" -x -a "default high-contrast colorblind"
 complete -c phobos -l ports-only -d "Only scan ports, no scripts or Nmap"
+complete -c phobos -l lenient-ports -d "Fall back to common ports on an invalid port spec"
 complete -c phobos -l no-nmap -d "Disable automatic Nmap execution"
 complete -c phobos -l all -d "Show all port states"
 complete -c phobos -l top -d "Use top 1000 ports"
+complete -c phobos -l top-ports -d "Scan the N most frequently open ports" -x -a "10 100 1000 5000 10000"
 complete -c phobos -l full-range -d "Scan all 65535 ports"
 complete -c phobos -l udp -d "UDP scanning mode"
 complete -c phobos -l adaptive -d "Enable adaptive scanning"
 complete -c phobos -l wrath -d "Wrath mode: maximum aggression"
 complete -c phobos -l shadow -d "Shadow scan: ultra-stealth"
 complete -c phobos -s O -l os-detect -d "Enable OS detection"
-complete -c phobos -l update -d "Update Phobos to latest version"
+complete -c phobos -l update -d "Install the latest verified release binary"
+complete -c phobos -l check-only -d "With --update, only report versions"
+complete -c phobos -l from-source -d "With --update, build from source when no binary fits"
+complete -c phobos -l allow-unsigned -d "With --update, install without a valid signature"
 complete -c phobos -l list-profiles -d "List all available profiles"
-complete -c phobos -l system-check -d "Check system requirements"
+complete -c phobos -l system-check -d "Network preflight checks"
+complete -c phobos -l healthcheck -d "Container healthcheck with exit status"
+complete -c phobos -l listen -d "Report probes arriving at the -p ports"
+complete -c phobos -l watch -d "Rescan on a schedule and report changes"
+complete -c phobos -l schedule -d "When --watch scans: cron or @every INTERVAL" -x -a "@hourly @daily @weekly @monthly"
+complete -c phobos -l history-dir -d "Where --watch keeps its runs" -r -a "(__fish_complete_directories)"
+complete -c phobos -l diff-format -d "How --watch writes changes" -x -a "text json csv"
+complete -c phobos -l history-diff -d "Show changes between the last two --watch runs"
+complete -c phobos -l webhook -d "POST --watch notifications to URL" -x
+complete -c phobos -l notify-url -d "POST open ports to URL as they are found" -x
+complete -c phobos -l notify-format -d "Body of --notify-url notifications" -x -a "json slack discord"
+complete -c phobos -l reflector -d "Host probed by --system-check" -x -a "1.1.1.1:443 8.8.8.8:53 9.9.9.9:443"
 complete -c phobos -l validate-config -d "Validate configuration"
 
 # Port specification
-complete -c phobos -s p -l ports -d "Port range to scan" -x -a "22 80 443 8080 1-1000 1-65535"
+complete -c phobos -s p -l ports -d "Ports: ranges, topN, groups, T:/U: prefixes, !exclusions" -x -a "22 80 443 8080 1-1000 1-65535 top100 web database T:1-1024,U:53"
 
 # Scan technique
 complete -c phobos -s s -l scan-type -d "Scan technique" -x -a "syn connect udp fin null xmas ack window"
+complete -c phobos -l zombie -d "Idle scan through this host (HOST[:PORT])" -x -a "(__fish_print_hostnames)"
+complete -c phobos -s D -l decoys -d "Spoofed decoy sources for SYN probes (IP,ME,RND[:N])" -x -a "ME RND"
+complete -c phobos -l proxy -d "Tunnel connect probes through SOCKS5/HTTP proxies (URL,...)" -x -a "socks5:// http://"
+complete -c phobos -l auto-technique -d "Pick the technique from the environment"
+complete -c phobos -l verify-open -d "Re-check open ports with a second method"
+complete -c phobos -l sV -l service-version -d "Name services and versions with the bundled probes"
 
 # Timing template
 complete -c phobos -s T -l timing -d "Timing template (0-5)" -x -a "0 1 2 3 4 5"
@@ -48,27 +73,67 @@ complete -c phobos -l timeout -d "Timeout in milliseconds" -x -a "1000 2000 3000
 
 # Rate limit
 complete -c phobos -l rate-limit -d "Rate limit in packets per second" -x -a "1000 10000 100000 1000000 10000000"
+complete -c phobos -l min-rate -d "Lowest adaptive per-host rate" -x -a "10 100 1000"
+complete -c phobos -l max-rate -d "Highest adaptive per-host rate" -x -a "1000 10000"
+complete -c phobos -l ramp-up -d "Ramp the rate up over this long" -x -a "10s 30s 1m 5m"
+complete -c phobos -l ramp-start -d "Percent of the rate a ramp-up starts at" -x -a "5 10 25 50"
+complete -c phobos -l health-check -d "Throttle or pause while this check degrades" -x -a "gateway"
+complete -c phobos -l health-threshold -d "Latency multiple of the baseline that throttles" -x -a "2 3 5 10"
+complete -c phobos -l memory-limit -d "Spill results to disk past this memory use" -x -a "256M 512M 1G 2G"
+complete -c phobos -l resume -d "Continue an interrupted scan from its checkpoint" -x -a "(path basename -E ~/.phobos/checkpoints/*.json 2>/dev/null)"
+complete -c phobos -l on-scan-start -d "Command to run when the scan starts" -x -a "(__fish_complete_command)"
+complete -c phobos -l on-host-start -d "Command to run when a host starts" -x -a "(__fish_complete_command)"
+complete -c phobos -l on-host-complete -d "Command to run when a host is done" -x -a "(__fish_complete_command)"
+complete -c phobos -l on-scan-complete -d "Command to run when the scan is done" -x -a "(__fish_complete_command)"
+complete -c phobos -l max-bandwidth -d "Bandwidth cap in bits per second" -x -a "1M 10M 50M 100M 1G"
+complete -c phobos -l throttle-local -d "Keep rate limits and timeouts for loopback and link-local targets"
+complete -c phobos -l no-redact-secrets -d "Keep secrets in captured banners and responses unmasked"
 
 # Batch size
 complete -c phobos -s b -l batch-size -d "Batch size for port scanning" -x -a "1000 3000 5000 10000 15000"
+complete -c phobos -l host-concurrency -d "Most probes in flight against any one host" -x -a "10 100 500 1000"
 
 # Output format
-complete -c phobos -s o -l output -d "Output format" -x -a "text json xml csv nmap greppable"
+complete -c phobos -s o -l output -d "Output format" -x -a "text json xml csv nmap greppable msf stix ocsf sqlite"
 
 # Output file
 complete -c phobos -l output-file -d "Write output to file" -r -F
+complete -c phobos -l filter -d "Report only ports matching an expression" -x
 
 # Nmap output
 complete -c phobos -l output-nmap -d "Save results in Nmap XML format" -r -F
+complete -c phobos -l output-msf -d "Save results as Metasploit XML" -r -F
+complete -c phobos -l output-stix -d "Save results as a STIX 2.1 bundle" -r -F
+complete -c phobos -l output-ocsf -d "Save results as OCSF events" -r -F
+complete -c phobos -l access-matrix -d "Check results against an expected access CSV" -r -F
+complete -c phobos -l source-zone -d "Access matrix zone scanned from" -x
+complete -c phobos -l output-compliance -d "Save access rule verdicts (CSV or .json)" -r -F
+complete -c phobos -l workspace -d "Collect run files in a timestamped directory" -r -a "(__fish_complete_directories)"
+complete -c phobos -l export-parquet -d "Add results to a date-partitioned Parquet archive" -x -a "(__fish_complete_directories)"
+complete -c phobos -l report -d "Aggregate the archive instead of scanning" -x -a "heatmap campaigns diff"
+complete -c phobos -l campaign -d "Campaign the scan belongs to" -x
+complete -c phobos -l against -d "Earlier campaign for --report diff" -x
+complete -c phobos -l archive -d "Parquet archive read by --report" -x -a "(__fish_complete_directories)"
+complete -c phobos -l since -d "Report on scans from this long ago on" -x -a "12h 7d 30d 90d"
+complete -c phobos -l report-format -d "Report format" -x -a "csv json html"
+complete -c phobos -l output-defectdojo -d "Save findings for DefectDojo" -r -F
+complete -c phobos -l output-faraday -d "Save findings for Faraday" -r -F
+complete -c phobos -l event-log -d "Log summary and findings to the Windows event log"
+complete -c phobos -l msfdb -d "Metasploit database connection for direct import" -x
+complete -c phobos -l msf-workspace -d "Metasploit workspace for --msfdb" -x
 
 # Config file
 complete -c phobos -s c -l config -d "Configuration file path" -r -F
 
 # Input file
 complete -c phobos -s i -l input-file -d "Read targets from file" -r -F
+complete -c phobos -l scope -d "Authorized IPs/CIDRs or scope export" -r -F
+complete -c phobos -s y -l yes -d "Scan public targets without confirmation"
+complete -c phobos -l harden -d "Drop privileges and restrict syscalls once sockets are open" -a "standard strict"
+complete -c phobos -l harden-user -d "User --harden continues as" -x -a "(__fish_complete_users)"
 
 # Profile
-complete -c phobos -l profile -d "Use predefined scan profile" -x -a "stealth aggressive comprehensive quick"
+complete -c phobos -l profile -d "Use predefined scan profile" -x -a "stealth aggressive comprehensive quick constrained"
 
 # Save profile
 complete -c phobos -l save-profile -d "Save current configuration as profile" -x
@@ -90,6 +155,22 @@ complete -c phobos -l max-script-concurrent -d "Maximum concurrent script execut
 
 # Scan order
 complete -c phobos -l scan-order -d "Order to scan ports" -x -a "serial random"
+complete -c phobos -l host-order -d "Order to probe hosts" -x -a "serial random interleave"
+complete -c phobos -l seed -d "Seed for random port and host order" -x
+complete -c phobos -l stats -d "Print scan statistics and phase timings"
+complete -c phobos -n "__fish_is_first_arg" -a estimate -d "Predict scan cost without scanning"
+complete -c phobos -n "__fish_is_first_arg" -a healthcheck -d "Container healthcheck"
+complete -c phobos -n "__fish_is_first_arg" -a listen -d "Report probes arriving at the -p ports"
+complete -c phobos -n "__fish_is_first_arg" -a watch -d "Rescan on a schedule and report changes"
+complete -c phobos -n "__fish_is_first_arg" -a run -d "Run a .phobos scan definition"
+complete -c phobos -n "__fish_is_first_arg" -a report -d "Aggregate archived scans"
+complete -c phobos -n "__fish_seen_subcommand_from report" -a heatmap -d "Ports x hosts open frequency"
+complete -c phobos -n "__fish_seen_subcommand_from report" -a campaigns -d "List archived campaigns"
+complete -c phobos -n "__fish_seen_subcommand_from report" -a diff -d "Compare two campaigns"
+complete -c phobos -n "__fish_seen_subcommand_from run" -l var -d "Set a scan definition variable (NAME=VALUE)" -x
+complete -c phobos -l estimate -d "Predict probes, traffic and duration without scanning"
+complete -c phobos -l progress-json -d "Write progress events to stderr as JSON lines"
+complete -c phobos -l stats-every -d "Print a progress line at this interval" -x -a "10s 30s 1m 5m"
 
 # Tries
 complete -c phobos -l tries -d "Number of tries per port" -x -a "1 2 3 5"
@@ -99,9 +180,34 @@ complete -c phobos -l max-retries -d "Maximum retries for failed connections" -x
 
 # Source port
 complete -c phobos -l source-port -d "Use specific source port" -x
+complete -c phobos -l source-port-range -d "Local port range for connect scans (LOW-HIGH)" -x
+complete -c phobos -l rst-on-close -d "Close sockets with RST to avoid TIME_WAIT"
+complete -c phobos -l notrack -d "Mark raw probes for a conntrack NOTRACK rule"
+complete -c phobos -l send-thread -d "Send raw probes from a dedicated high-priority thread"
+complete -c phobos -l misconfig-checks -d "Run safe misconfiguration checks on open ports" -a "all smtp-open-relay ftp-anonymous redis-no-auth elasticsearch-open"
+complete -c phobos -l import-shodan -d "Import targets from a Shodan search" -x
+complete -c phobos -l import-censys -d "Import targets from a Censys search" -x
+complete -c phobos -l import-mode -d "Use imported data as targets or comparison" -x -a "targets compare"
+complete -c phobos -l import-limit -d "Records imported per source" -x
+complete -c phobos -l web-enrich -d "Fingerprint web technologies on HTTP(S) ports"
+complete -c phobos -l screenshots -d "Screenshot HTTP(S) services into a directory" -r -a "(__fish_complete_directories)"
+complete -c phobos -l screenshot-cmd -d "Screenshot command template" -x
+complete -c phobos -l screenshot-cdp -d "DevTools endpoint for screenshots" -x
+complete -c phobos -l default-creds -d "Try default logins on telnet/FTP (requires --i-have-authorization)"
+complete -c phobos -l i-have-authorization -d "Confirm authorization to attempt logins"
+complete -c phobos -l creds-file -d "user:password list for --default-creds" -r -F
+complete -c phobos -l creds-per-minute -d "Login attempts per minute per service" -x
+complete -c phobos -l dns-sweep -d "Classify DNS servers and open resolvers on UDP/53"
+complete -c phobos -l dns-query -d "Name to resolve for --dns-sweep" -x
 
 # Interface
 complete -c phobos -l interface -d "Network interface to use" -x
+complete -c phobos -l capture-filter -d "Extra BPF filter for raw response sockets" -x
+complete -c phobos -l vlan -d "802.1Q VLAN ID to tag probes with" -x -a "1 10 100 4094"
+complete -c phobos -l vlan-source -d "Source IPv4 address inside the VLAN" -x
+complete -c phobos -l mtu-override -d "Path MTU to clamp probes and MSS to" -x -a "1280 1380 1420 1500"
+complete -c phobos -l geoip -d "GeoIP CSV for latency hints" -r -F
+complete -c phobos -l geo-origin -d "Scanner location as LAT,LON" -x
 
 # Ulimit
 complete -c phobos -s u -l ulimit -d "Automatically increase ulimit" -x -a "4096 8192 16384 65535"
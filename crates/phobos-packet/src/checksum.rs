@@ -0,0 +1,64 @@
+//! RFC 1071 internet checksum
+
+/// Running one's-complement sum over 16-bit words
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checksum {
+    sum: u32,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `data`; an odd trailing byte is padded with zero, so only the
+    /// last chunk may have an odd length
+    pub fn add_bytes(&mut self, data: &[u8]) -> &mut Self {
+        let mut chunks = data.chunks_exact(2);
+        for word in &mut chunks {
+            self.add_u16(u16::from_be_bytes([word[0], word[1]]));
+        }
+        if let [last] = chunks.remainder() {
+            self.add_u16(u16::from_be_bytes([*last, 0]));
+        }
+        self
+    }
+
+    pub fn add_u16(&mut self, word: u16) -> &mut Self {
+        self.sum += u32::from(word);
+        // Fold now and then so long inputs cannot overflow
+        if self.sum > 0xffff_0000 {
+            self.sum = (self.sum & 0xffff) + (self.sum >> 16);
+        }
+        self
+    }
+
+    /// Add the IPv4 pseudo-header TCP and UDP checksums cover
+    pub fn add_ipv4_pseudo_header(&mut self, source: [u8; 4], destination: [u8; 4], protocol: u8, length: u16) -> &mut Self {
+        self.add_bytes(&source).add_bytes(&destination).add_u16(u16::from(protocol)).add_u16(length)
+    }
+
+    /// Add the IPv6 pseudo-header (RFC 8200, section 8.1), which has a
+    /// 32-bit length and the next header value in place of the protocol
+    pub fn add_ipv6_pseudo_header(&mut self, source: [u8; 16], destination: [u8; 16], next_header: u8, length: u32) -> &mut Self {
+        self.add_bytes(&source)
+            .add_bytes(&destination)
+            .add_u16((length >> 16) as u16)
+            .add_u16(length as u16)
+            .add_u16(u16::from(next_header))
+    }
+
+    /// The checksum to store: the complement of the folded sum
+    pub fn finish(&self) -> u16 {
+        let mut sum = self.sum;
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+/// Checksum of `data` alone, as used by the IPv4 header and ICMP
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    Checksum::new().add_bytes(data).finish()
+}
@@ -0,0 +1,43 @@
+//! IPv4 header without options
+
+use crate::{check_len, length_field, Result};
+use crate::checksum::internet_checksum;
+
+pub const HEADER_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Header {
+    pub source: [u8; 4],
+    pub destination: [u8; 4],
+    pub protocol: u8,
+    pub identification: u16,
+    pub ttl: u8,
+    pub dont_fragment: bool,
+}
+
+impl Ipv4Header {
+    /// A header with TTL 64 and DF set, the way Phobos sends probes
+    pub fn new(source: [u8; 4], destination: [u8; 4], protocol: u8) -> Self {
+        Self { source, destination, protocol, identification: 0, ttl: 64, dont_fragment: true }
+    }
+
+    /// Write the header with its checksum into `buf`; `total_len` covers
+    /// the header and everything after it
+    pub fn write(&self, buf: &mut [u8], total_len: usize) -> Result<usize> {
+        check_len(buf, HEADER_LEN)?;
+        let header = &mut buf[..HEADER_LEN];
+        header[0] = 0x45; // Version 4, 5 words
+        header[1] = 0;
+        header[2..4].copy_from_slice(&length_field(total_len)?.to_be_bytes());
+        header[4..6].copy_from_slice(&self.identification.to_be_bytes());
+        header[6..8].copy_from_slice(&(if self.dont_fragment { 0x4000u16 } else { 0 }).to_be_bytes());
+        header[8] = self.ttl;
+        header[9] = self.protocol;
+        header[10..12].copy_from_slice(&[0, 0]);
+        header[12..16].copy_from_slice(&self.source);
+        header[16..20].copy_from_slice(&self.destination);
+        let checksum = internet_checksum(header);
+        header[10..12].copy_from_slice(&checksum.to_be_bytes());
+        Ok(HEADER_LEN)
+    }
+}
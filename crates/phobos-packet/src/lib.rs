@@ -0,0 +1,119 @@
+//! Packet crafting core of Phobos
+//!
+//! Writes IPv4, TCP and UDP headers into caller-provided buffers and computes
+//! their checksums, over IPv4 or IPv6 pseudo-headers. No allocator, no I/O and no dependencies, so it builds
+//! for `no_std` targets and for eBPF or embedded tooling that wants the same
+//! probes Phobos sends.
+
+#![no_std]
+
+pub mod checksum;
+pub mod ipv4;
+pub mod tcp;
+pub mod udp;
+
+pub use checksum::{internet_checksum, Checksum};
+pub use ipv4::Ipv4Header;
+pub use tcp::TcpHeader;
+pub use udp::UdpHeader;
+
+use core::fmt;
+
+/// IP protocol numbers used by the headers here
+pub const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The buffer cannot hold the header being written
+    BufferTooSmall { needed: usize, available: usize },
+    /// The packet is longer than its 16-bit length field allows
+    TooLong(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall { needed, available } => {
+                write!(f, "buffer too small: need {} bytes, have {}", needed, available)
+            }
+            Error::TooLong(len) => write!(f, "{} bytes do not fit a 16-bit length field", len),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+fn check_len(buf: &[u8], needed: usize) -> Result<()> {
+    if buf.len() < needed {
+        return Err(Error::BufferTooSmall { needed, available: buf.len() });
+    }
+    Ok(())
+}
+
+fn length_field(len: usize) -> Result<u16> {
+    u16::try_from(len).map_err(|_| Error::TooLong(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers_and_checksums() {
+        // A UDP datagram's IPv4 header; its checksum is 0xb861
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x00, 0x01, 0xc0, 0xa8,
+            0x00, 0xc7,
+        ];
+        assert_eq!(internet_checksum(&header), 0xb861);
+        assert_eq!(Checksum::new().add_bytes(&[0x01]).finish(), !0x0100);
+
+        let (source, destination) = ([192, 168, 0, 1], [192, 168, 0, 199]);
+        let mut ip = Ipv4Header::new(source, destination, 0x11);
+        ip.dont_fragment = true;
+        let mut buf = [0u8; 20];
+        ip.write(&mut buf, 0x73).unwrap();
+        assert_eq!(buf[..10], header[..10]);
+        assert_eq!(buf[10..12], [0xb8, 0x61]);
+        assert_eq!(buf[12..], header[12..]);
+        assert_eq!(internet_checksum(&buf), 0);
+
+        let mut packet = [0u8; 44];
+        let syn = TcpHeader { flags: tcp::SYN, sequence: 1, mss: Some(1360), ..TcpHeader::new(40000, 443) };
+        Ipv4Header::new(source, destination, IPPROTO_TCP).write(&mut packet, 44).unwrap();
+        assert_eq!(syn.write_with_checksum(&mut packet[ipv4::HEADER_LEN..], source, destination), Ok(24));
+        assert_eq!(packet[32], 0x60);
+        assert_eq!(&packet[40..44], &[2, 4, 0x05, 0x50]);
+        assert_eq!(tcp::timestamp_option(&packet[40..44]), None);
+        assert_eq!(tcp::mss_option(&packet[40..44]), Some(1360));
+
+        let stamped = TcpHeader { timestamp: Some((0x0102_0304, 7)), ..syn };
+        let mut segment = [0u8; 36];
+        assert_eq!(stamped.write(&mut segment), Ok(36));
+        assert_eq!(segment[12], 0x90);
+        assert_eq!(tcp::timestamp_option(&segment[20..]), Some((0x0102_0304, 7)));
+        assert_eq!(tcp::mss_option(&segment[20..]), Some(1360));
+        // A correct checksum makes the pseudo-header sum come out as zero
+        let verify = Checksum::new().add_ipv4_pseudo_header(source, destination, IPPROTO_TCP, 24).add_bytes(&packet[20..]).finish();
+        assert_eq!(verify, 0);
+
+        let mut datagram = [0u8; 12];
+        datagram[8..].copy_from_slice(b"ping");
+        UdpHeader::new(5353, 53).write_with_checksum(&mut datagram, source, destination).unwrap();
+        assert_eq!(&datagram[4..6], &[0, 12]);
+        assert_eq!(syn.write(&mut [0u8; 10]), Err(Error::BufferTooSmall { needed: 24, available: 10 }));
+
+        // 2001:db8::1 -> 2001:db8::2
+        let mut source6 = [0u8; 16];
+        source6[..4].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        let mut destination6 = source6;
+        (source6[15], destination6[15]) = (1, 2);
+        let mut segment = [0u8; 24];
+        assert_eq!(syn.write_with_checksum_v6(&mut segment, source6, destination6), Ok(24));
+        let verify = Checksum::new().add_ipv6_pseudo_header(source6, destination6, IPPROTO_TCP, 24).add_bytes(&segment).finish();
+        assert_eq!(verify, 0);
+        // Different pseudo-header, different checksum
+        assert_ne!(segment[16..18], packet[36..38]);
+    }
+}
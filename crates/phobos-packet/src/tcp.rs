@@ -0,0 +1,135 @@
+//! TCP header with optional MSS and timestamp options
+
+use crate::checksum::Checksum;
+use crate::{check_len, length_field, Error, Result, IPPROTO_TCP};
+
+pub const HEADER_LEN: usize = 20;
+/// Length of the MSS option (kind 2, length 4)
+pub const MSS_OPTION_LEN: usize = 4;
+/// Length of the timestamp option (kind 8, length 10) behind two NOPs
+pub const TIMESTAMP_OPTION_LEN: usize = 12;
+
+pub const FIN: u8 = 0x01;
+pub const SYN: u8 = 0x02;
+pub const RST: u8 = 0x04;
+pub const PSH: u8 = 0x08;
+pub const ACK: u8 = 0x10;
+pub const URG: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub sequence: u32,
+    pub acknowledgement: u32,
+    pub flags: u8,
+    pub window: u16,
+    /// Advertised maximum segment size, written as an option
+    pub mss: Option<u16>,
+    /// TSval and TSecr, written as a timestamp option (RFC 7323)
+    pub timestamp: Option<(u32, u32)>,
+}
+
+impl TcpHeader {
+    pub fn new(source_port: u16, destination_port: u16) -> Self {
+        Self { source_port, destination_port, sequence: 0, acknowledgement: 0, flags: 0, window: 65535, mss: None, timestamp: None }
+    }
+
+    /// Header length including options
+    pub fn header_len(&self) -> usize {
+        HEADER_LEN
+            + if self.mss.is_some() { MSS_OPTION_LEN } else { 0 }
+            + if self.timestamp.is_some() { TIMESTAMP_OPTION_LEN } else { 0 }
+    }
+
+    /// Write the header with a zero checksum into `buf`
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.header_len();
+        check_len(buf, len)?;
+        buf[0..2].copy_from_slice(&self.source_port.to_be_bytes());
+        buf[2..4].copy_from_slice(&self.destination_port.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[8..12].copy_from_slice(&self.acknowledgement.to_be_bytes());
+        buf[12] = ((len / 4) as u8) << 4;
+        buf[13] = self.flags;
+        buf[14..16].copy_from_slice(&self.window.to_be_bytes());
+        buf[16..20].copy_from_slice(&[0, 0, 0, 0]); // Checksum, urgent pointer
+        let mut offset = HEADER_LEN;
+        if let Some(mss) = self.mss {
+            let [high, low] = mss.to_be_bytes();
+            buf[offset..offset + MSS_OPTION_LEN].copy_from_slice(&[2, 4, high, low]);
+            offset += MSS_OPTION_LEN;
+        }
+        if let Some((tsval, tsecr)) = self.timestamp {
+            buf[offset..offset + 4].copy_from_slice(&[1, 1, 8, 10]);
+            buf[offset + 4..offset + 8].copy_from_slice(&tsval.to_be_bytes());
+            buf[offset + 8..offset + 12].copy_from_slice(&tsecr.to_be_bytes());
+        }
+        Ok(len)
+    }
+
+    /// Write the header and fill in its checksum over `segment`, which is
+    /// the header followed by any payload or padding
+    pub fn write_with_checksum(&self, segment: &mut [u8], source: [u8; 4], destination: [u8; 4]) -> Result<usize> {
+        let len = self.write(segment)?;
+        let checksum = segment_checksum(segment, source, destination, IPPROTO_TCP)?;
+        segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+        Ok(len)
+    }
+
+    /// [`write_with_checksum`](Self::write_with_checksum) for a segment
+    /// sent over IPv6
+    pub fn write_with_checksum_v6(&self, segment: &mut [u8], source: [u8; 16], destination: [u8; 16]) -> Result<usize> {
+        let len = self.write(segment)?;
+        let length = u32::try_from(segment.len()).map_err(|_| Error::TooLong(segment.len()))?;
+        let checksum = Checksum::new().add_ipv6_pseudo_header(source, destination, IPPROTO_TCP, length).add_bytes(segment).finish();
+        segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+        Ok(len)
+    }
+}
+
+/// TSval and TSecr from the options of a TCP header, if it carries them
+///
+/// `options` is everything between the fixed header and the data offset.
+/// Malformed option lists yield `None` rather than a partial read.
+pub fn timestamp_option(options: &[u8]) -> Option<(u32, u32)> {
+    let value = find_option(options, 8, 10)?;
+    let word = |at: usize| u32::from_be_bytes([value[at], value[at + 1], value[at + 2], value[at + 3]]);
+    Some((word(0), word(4)))
+}
+
+/// Maximum segment size from the options of a TCP header, if it carries one
+pub fn mss_option(options: &[u8]) -> Option<u16> {
+    let value = find_option(options, 2, MSS_OPTION_LEN)?;
+    Some(u16::from_be_bytes([value[0], value[1]]))
+}
+
+/// Value of the first option of `kind` that is `len` bytes long in total
+fn find_option(options: &[u8], kind: u8, len: usize) -> Option<&[u8]> {
+    let mut rest = options;
+    while let Some(&current) = rest.first() {
+        match current {
+            0 => return None,
+            1 => rest = &rest[1..],
+            _ => {
+                let current_len = *rest.get(1)? as usize;
+                if current_len < 2 || current_len > rest.len() {
+                    return None;
+                }
+                if current == kind && current_len == len {
+                    return Some(&rest[2..len]);
+                }
+                rest = &rest[current_len..];
+            }
+        }
+    }
+    None
+}
+
+/// Checksum of a TCP or UDP segment whose checksum field is zero
+pub(crate) fn segment_checksum(segment: &[u8], source: [u8; 4], destination: [u8; 4], protocol: u8) -> Result<u16> {
+    Ok(Checksum::new()
+        .add_ipv4_pseudo_header(source, destination, protocol, length_field(segment.len())?)
+        .add_bytes(segment)
+        .finish())
+}
@@ -0,0 +1,36 @@
+//! UDP header
+
+use crate::tcp::segment_checksum;
+use crate::{check_len, length_field, Result, IPPROTO_UDP};
+
+pub const HEADER_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpHeader {
+    pub source_port: u16,
+    pub destination_port: u16,
+}
+
+impl UdpHeader {
+    pub fn new(source_port: u16, destination_port: u16) -> Self {
+        Self { source_port, destination_port }
+    }
+
+    /// Write the header into the start of `datagram`, which already holds
+    /// the payload after it, and checksum the whole datagram
+    pub fn write_with_checksum(&self, datagram: &mut [u8], source: [u8; 4], destination: [u8; 4]) -> Result<usize> {
+        check_len(datagram, HEADER_LEN)?;
+        datagram[0..2].copy_from_slice(&self.source_port.to_be_bytes());
+        datagram[2..4].copy_from_slice(&self.destination_port.to_be_bytes());
+        let length = length_field(datagram.len())?;
+        datagram[4..6].copy_from_slice(&length.to_be_bytes());
+        datagram[6..8].copy_from_slice(&[0, 0]);
+        let checksum = match segment_checksum(datagram, source, destination, IPPROTO_UDP)? {
+            // Zero means "no checksum" in UDP over IPv4
+            0 => 0xffff,
+            checksum => checksum,
+        };
+        datagram[6..8].copy_from_slice(&checksum.to_be_bytes());
+        Ok(HEADER_LEN)
+    }
+}
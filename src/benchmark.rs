@@ -15,18 +15,30 @@
 //! // Print Benchmark Summary
 //! println!("{}", bm.summary());
 //! ```
-use std::time::Instant;
+//!
+//! Criterion's results (`cargo bench --bench hot_path`) can be added with
+//! [`criterion_results`] and [`Benchmark::push_measurement`], so they show up
+//! in the same summary along with the change since the previous run.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Slowdown since the previous run, as a fraction, that counts as a regression
+pub const REGRESSION_THRESHOLD: f64 = 0.10;
 
 /// A Benchmark struct to hold NamedTimers with name, start and end Instants,
+/// and measurements taken by Criterion
 #[derive(Debug)]
 pub struct Benchmark {
     timers: Vec<NamedTimer>,
+    measurements: Vec<Measurement>,
 }
 
 impl Benchmark {
     pub fn init() -> Self {
         Self {
             timers: Vec::new(),
+            measurements: Vec::new(),
         }
     }
     
@@ -41,6 +53,15 @@ impl Benchmark {
     pub fn add_timer(&mut self, timer: NamedTimer) {
         self.push(timer);
     }
+    
+    pub fn push_measurement(&mut self, measurement: Measurement) {
+        self.measurements.push(measurement);
+    }
+    
+    /// Measurements slower than the previous run by more than `threshold`
+    pub fn regressions(&self, threshold: f64) -> impl Iterator<Item = &Measurement> {
+        self.measurements.iter().filter(move |m| m.change.is_some_and(|change| change > threshold))
+    }
 
     /// Summary of the benchmarks will destruct the vector,
     /// formats every element the same way and return
@@ -59,6 +80,12 @@ impl Benchmark {
                 summary.push_str(&format!("\n{0: <10} | {1: <10}s", timer.name, runtime_secs));
             }
         }
+        for measurement in &self.measurements {
+            summary.push_str(&format!("\n{0: <10} | {1: <10?}", measurement.name, measurement.mean));
+            if let Some(change) = measurement.change {
+                summary.push_str(&format!(" ({:+.1}%)", change * 100.0));
+            }
+        }
         summary
     }
     
@@ -96,6 +123,70 @@ impl NamedTimer {
     }
 }
 
+/// Mean time of one Criterion benchmark
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Group and function, as in `checksum/tcp_segment`
+    pub name: String,
+    pub mean: Duration,
+    /// Change of the mean since the previous run, as a fraction
+    pub change: Option<f64>,
+}
+
+/// Where Criterion writes its results: `$CRITERION_HOME`, or `criterion`
+/// under the Cargo target directory
+pub fn criterion_home() -> PathBuf {
+    if let Some(home) = std::env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    std::env::var_os("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("target")).join("criterion")
+}
+
+/// Every benchmark Criterion measured under `dir`, sorted by name
+///
+/// `dir` is a group directory such as `target/criterion/checksum`; runs in
+/// `--test` mode write nothing, so finding none is not an error.
+pub fn criterion_results(dir: &Path) -> io::Result<Vec<Measurement>> {
+    let mut measurements = Vec::new();
+    if dir.is_dir() {
+        collect_estimates(dir, dir, &mut measurements)?;
+    }
+    measurements.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(measurements)
+}
+
+fn collect_estimates(root: &Path, dir: &Path, measurements: &mut Vec<Measurement>) -> io::Result<()> {
+    let estimates = dir.join("new").join("estimates.json");
+    if estimates.is_file() {
+        let mean = mean_estimate(&estimates)?;
+        let change = mean_estimate(&dir.join("change").join("estimates.json")).ok();
+        // The group directory's own name leads, as in Criterion's reports
+        let group = root.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let function = dir.strip_prefix(root).unwrap_or(dir).to_string_lossy().replace('\\', "/");
+        let name = if function.is_empty() { group } else { format!("{}/{}", group, function) };
+        measurements.push(Measurement { name, mean: Duration::from_secs_f64(mean.max(0.0) / 1e9), change });
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        // `report` holds Criterion's HTML, not measurements
+        if path.is_dir() && path.file_name().is_some_and(|name| name != "report") {
+            collect_estimates(root, &path, measurements)?;
+        }
+    }
+    Ok(())
+}
+
+/// `mean.point_estimate` of a Criterion estimates file: nanoseconds for a
+/// measurement, a fraction for a change
+fn mean_estimate(path: &Path) -> io::Result<f64> {
+    let estimates: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    estimates["mean"]["point_estimate"]
+        .as_f64()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("no mean estimate in {}", path.display())))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,4 +204,33 @@ mod tests {
             .contains("\nPhobos Benchmark Summary\ntest       | 0."));
         assert!(!benchmarks.summary().contains("only_start"));
     }
+    
+    #[test]
+    fn criterion_measurements() {
+        let dir = tempfile::tempdir().unwrap();
+        let group = dir.path().join("checksum");
+        let write = |path: &str, estimate: f64| {
+            let path = group.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, format!(r#"{{"mean":{{"point_estimate":{}}},"median":{{"point_estimate":0}}}}"#, estimate)).unwrap();
+        };
+        write("tcp_segment/new/estimates.json", 250.0);
+        write("tcp_segment/change/estimates.json", 0.25);
+        write("ipv4_header/new/estimates.json", 40.0);
+        std::fs::create_dir_all(group.join("report")).unwrap();
+        
+        let found = criterion_results(&group).unwrap();
+        assert_eq!(found, [
+            Measurement { name: "checksum/ipv4_header".to_string(), mean: Duration::from_nanos(40), change: None },
+            Measurement { name: "checksum/tcp_segment".to_string(), mean: Duration::from_nanos(250), change: Some(0.25) },
+        ]);
+        assert!(criterion_results(&dir.path().join("missing")).unwrap().is_empty());
+        
+        let mut benchmarks = Benchmark::init();
+        found.into_iter().for_each(|m| benchmarks.push_measurement(m));
+        assert!(benchmarks.summary().contains("checksum/tcp_segment | 250ns"));
+        assert!(benchmarks.summary().contains("(+25.0%)"));
+        let slower: Vec<_> = benchmarks.regressions(REGRESSION_THRESHOLD).map(|m| m.name.as_str()).collect();
+        assert_eq!(slower, ["checksum/tcp_segment"]);
+    }
 }
\ No newline at end of file
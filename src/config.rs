@@ -1,7 +1,9 @@
 //! Configuration module for the phobos scanner
 
 use crate::network::{ScanTechnique, stealth::StealthOptions};
+use crate::output::theme::ThemeName;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::time::Duration;
 use std::path::Path;
 use std::fs;
@@ -9,9 +11,15 @@ use std::fs;
 /// Main configuration structure for scanning operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanConfig {
-    /// Target host or network to scan
+    /// Target host or network to scan; the first of `targets` when there
+    /// are several, used for route and path MTU detection
     pub target: String,
     
+    /// Every target (addresses or CIDR ranges) of a multi-target scan; just
+    /// `target` when empty
+    #[serde(default)]
+    pub targets: Vec<String>,
+    
     /// List of ports to scan
     pub ports: Vec<u16>,
     
@@ -27,6 +35,30 @@ pub struct ScanConfig {
     /// Rate limit in packets per second
     pub rate_limit: u64,
     
+    /// Maximum burst size for the rate limiter (defaults to one second of traffic)
+    pub burst_size: Option<u64>,
+    
+    /// Per-host rate limit in packets per second
+    pub host_rate_limit: Option<u64>,
+    
+    /// Lowest per-host rate in packets per second; with `max_rate`, makes
+    /// each host's rate adapt to its answers
+    #[serde(default)]
+    pub min_rate: Option<u64>,
+    
+    /// Highest per-host rate in packets per second for adaptive pacing
+    #[serde(default)]
+    pub max_rate: Option<u64>,
+    
+    /// Milliseconds over which the global rate ramps up from `ramp_start`
+    /// percent at the start of a scan; full rate at once if unset
+    #[serde(default)]
+    pub ramp_up: Option<u64>,
+    
+    /// Percent of the rate a ramp-up starts at (default 10)
+    #[serde(default)]
+    pub ramp_start: Option<u8>,
+    
     /// Stealth options for evasion
     pub stealth_options: Option<StealthOptions>,
     
@@ -60,22 +92,245 @@ pub struct ScanConfig {
     /// Source port to use for scanning
     pub source_port: Option<u16>,
     
+    /// Local port range (inclusive) for connect scan sockets
+    pub source_port_range: Option<(u16, u16)>,
+    
+    /// Close connect scan sockets with RST (SO_LINGER 0) to avoid TIME_WAIT
+    #[serde(default)]
+    pub rst_on_close: bool,
+    
     /// Network interface to use
     pub interface: Option<String>,
     
     /// IPs/CIDR ranges to exclude from scanning
     pub exclude_ips: Option<Vec<String>>,
+    
+    /// Seed for randomized scan order, recorded in report provenance
+    #[serde(default)]
+    pub seed: Option<u64>,
+    
+    /// Extra BPF expression ANDed with the engine's filter on raw response sockets
+    #[serde(default)]
+    pub capture_filter: Option<String>,
+    
+    /// 802.1Q VLAN ID to tag raw probes with
+    #[serde(default)]
+    pub vlan: Option<u16>,
+    
+    /// Source address for tagged probes (default: the interface's address)
+    #[serde(default)]
+    pub vlan_source: Option<std::net::Ipv4Addr>,
+    
+    /// Path MTU to clamp probes to instead of detecting it
+    #[serde(default)]
+    pub mtu_override: Option<u16>,
+    
+    /// Whether hosts are swept one at a time or probed interleaved
+    #[serde(default)]
+    pub host_order: crate::utils::scan_options::HostOrder,
+    
+    /// Mark raw probes with `conntrack::PROBE_MARK` for a NOTRACK rule
+    #[serde(default)]
+    pub notrack: bool,
+    
+    /// Send raw probes from a dedicated, higher-priority OS thread paced at
+    /// the scan rate instead of from the async workers
+    #[serde(default)]
+    pub send_thread: bool,
+    
+    /// Most probes in flight against any one host; the batch size if unset
+    #[serde(default)]
+    pub host_concurrency: Option<usize>,
+    
+    /// Probe open ports again with a second method and report only the confirmed ones
+    #[serde(default)]
+    pub verify_open: bool,
+    
+    /// Name the service and version behind each open TCP port from the
+    /// bundled probe database
+    #[serde(default)]
+    pub service_version: bool,
+    
+    /// GeoIP CSV (network, latitude, longitude) for latency sanity hints
+    #[serde(default)]
+    pub geoip: Option<String>,
+    
+    /// Where the scanner is, to judge round trip times against GeoIP locations
+    #[serde(default)]
+    pub geo_origin: Option<crate::scanner::latency::GeoPoint>,
+    
+    /// Bandwidth cap in bits per second, converted to a probe rate for the technique
+    #[serde(default)]
+    pub max_bandwidth: Option<u64>,
+    
+    /// Resident memory, in bytes, past which results are spilled to disk and
+    /// the batch size lowered
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
+    
+    /// Idle host (`HOST` or `HOST:PORT`) whose IP ID reveals the target's
+    /// ports, so probes come from its address instead of ours
+    #[serde(default)]
+    pub zombie: Option<String>,
+    
+    /// Id under which progress is saved to `~/.phobos/checkpoints`
+    #[serde(default)]
+    pub checkpoint: Option<String>,
+    
+    /// Continue from the saved `checkpoint` instead of starting over
+    #[serde(default)]
+    pub resume: bool,
+    
+    /// Watched while scanning to throttle or pause on degradation
+    #[serde(default)]
+    pub health_check: Option<crate::scanner::HealthCheck>,
+    
+    /// Health check latency, as a multiple of its baseline, that throttles the scan
+    #[serde(default)]
+    pub health_threshold: Option<f64>,
+    
+    /// User-defined service probes (`[[probes]]` tables) tried before the built-in ones
+    #[serde(default)]
+    pub probes: Vec<crate::network::probes::CustomProbe>,
+    
+    /// User-defined banner classification (`[[banner_rules]]` tables) tried before the built-in checks
+    #[serde(default)]
+    pub banner_rules: Vec<crate::network::banner_rules::BannerRule>,
+    
+    /// Pick the technique from the environment instead of `technique`
+    #[serde(default)]
+    pub auto_technique: bool,
+    
+    /// What `auto_technique` picked and why, once the scan has started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub technique_choice: Option<crate::scanner::TechniqueChoice>,
+    
+    /// Assessment the scan belongs to, e.g. `q3-external`; recorded in reports and the archive
+    #[serde(default)]
+    pub campaign: Option<String>,
+    
+    /// Keep the rate limit and timeout for loopback and link-local targets
+    /// instead of lifting them
+    #[serde(default)]
+    pub throttle_local: bool,
+    
+    /// Store captured banners and responses as received instead of masking
+    /// the credentials, cookies and API keys in them
+    #[serde(default)]
+    pub keep_secrets: bool,
+    
+    /// Keep the results of closed and filtered ports as well as open ones,
+    /// for checks that need to know what was blocked
+    #[serde(default)]
+    pub keep_all_states: bool,
+    
+    /// Commands run when the scan or a host starts and completes (`[hooks]` table)
+    #[serde(default)]
+    pub hooks: crate::scanner::HookCommands,
+    
+    /// Organization banner and authorized-use notice (`[banner]` table)
+    #[serde(default)]
+    pub banner: BannerConfig,
+    
+    /// Colour palette of terminal output
+    #[serde(default)]
+    pub theme: ThemeName,
+}
+
+/// Pacing the operator set explicitly, which [`ScanConfig::lift_local_pacing`] keeps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocalPacing {
+    pub timeout: bool,
+    /// The global rate or bandwidth cap
+    pub rate_limit: bool,
+    pub host_rate_limit: bool,
+    pub adaptive_rate: bool,
+}
+
+/// Text an organization shows in place of the Phobos banner
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BannerConfig {
+    /// Printed instead of the ASCII art; `--no-banner` hides it all the same
+    #[serde(default)]
+    pub text: Option<String>,
+    
+    /// Authorized-use notice shown before every scan and embedded in reports
+    #[serde(default)]
+    pub legal_notice: Option<String>,
+    
+    /// Scan only once the operator accepts the notice, or passes `--yes`
+    #[serde(default)]
+    pub require_acknowledgment: bool,
+}
+
+/// What it takes for the operator to accept the legal notice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Acknowledgment {
+    /// No notice, or one shown without asking
+    NotRequired,
+    /// Accepted up front with `--yes`
+    Assumed,
+    /// Ask at the terminal
+    Prompt,
+    /// Required, but there is no terminal to ask at
+    Refused,
+}
+
+impl BannerConfig {
+    /// The `[banner]` table of `path`, or of `~/.phobos.toml` without one
+    pub fn load(path: Option<&str>) -> Self {
+        early_setting(path, "banner")
+    }
+    
+    /// How the notice gets accepted, given `--yes` and whether stdin is a terminal
+    pub fn acknowledgment(&self, assume_yes: bool, interactive: bool) -> Acknowledgment {
+        match (self.legal_notice.is_some() && self.require_acknowledgment, assume_yes, interactive) {
+            (false, _, _) => Acknowledgment::NotRequired,
+            (true, true, _) => Acknowledgment::Assumed,
+            (true, false, true) => Acknowledgment::Prompt,
+            (true, false, false) => Acknowledgment::Refused,
+        }
+    }
+    
+    /// Whether `answer` to the prompt accepts the notice
+    pub fn accepts(answer: &str) -> bool {
+        matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+    }
+}
+
+/// The `key` setting of config file `path`, or of `~/.phobos.toml` without one
+///
+/// Read ahead of the rest of the configuration for what is shown before it
+/// is loaded; a file that does not parse is reported when it is.
+pub fn early_setting<T: serde::de::DeserializeOwned + Default>(path: Option<&str>, key: &str) -> T {
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from(".")).join(".phobos.toml"),
+    };
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .and_then(|mut table| table.remove(key))
+        .and_then(|value| value.try_into().ok())
+        .unwrap_or_default()
 }
 
 impl Default for ScanConfig {
     fn default() -> Self {
         Self {
             target: "127.0.0.1".to_string(),
+            targets: Vec::new(), // Just `target`
             ports: (1..=1000).collect(),
             technique: ScanTechnique::Connect,
             threads: 10000, // RustScan-level concurrency (10000 threads)
             timeout: 10, // Ultra-fast timeout (10ms for localhost, auto-adjusts for remote)
             rate_limit: 100_000_000, // 100M packets per second - RustScan speed
+            burst_size: None, // One second worth of packets
+            host_rate_limit: None, // No per-host limit
+            min_rate: None, // Per-host rates do not adapt
+            max_rate: None,
+            ramp_up: None, // Full rate from the first probe
+            ramp_start: None,
             stealth_options: None,
             timing_template: 5, // Insane timing by default (like RustScan)
             top_ports: None,
@@ -87,8 +342,41 @@ impl Default for ScanConfig {
             max_response_time: 1000, // 1s maximum response time
             max_retries: Some(1), // Only 1 retry for maximum speed
             source_port: None, // Auto-select source port
+            source_port_range: None, // Kernel ephemeral range
+            rst_on_close: false, // Regular FIN close
             interface: None, // Auto-select interface
             exclude_ips: None, // No exclusions by default
+            seed: None, // Drawn at random when randomization is used
+            capture_filter: None, // Engine filter only
+            vlan: None, // Untagged
+            vlan_source: None, // Interface address
+            mtu_override: None, // Detected from the route
+            host_order: crate::utils::scan_options::HostOrder::Interleave, // One queue across all hosts
+            notrack: false, // Probes are tracked like any other traffic
+            send_thread: false, // Probes are sent by the task that built them
+            host_concurrency: None, // Bounded by the batch size only
+            verify_open: false, // Trust the first technique
+            service_version: false, // Services guessed from the port number
+            geoip: None, // Compare hosts with their subnet only
+            geo_origin: None, // Unknown; GeoIP locations are not checked
+            max_bandwidth: None, // Paced by rate_limit only
+            memory_limit: None, // Results stay in memory
+            zombie: None, // Probes come from our own address
+            checkpoint: None, // Progress is not saved
+            resume: false, // Start from the first port
+            health_check: None, // Nothing watched; never throttled
+            health_threshold: None, // health::DEFAULT_THRESHOLD
+            probes: Vec::new(), // Built-in service identification only
+            banner_rules: Vec::new(), // Built-in banner checks only
+            auto_technique: false, // Use the configured technique
+            technique_choice: None, // Decided at scan start
+            campaign: None, // Not part of a campaign
+            throttle_local: false, // Local targets take the fast path
+            keep_secrets: false, // Secrets in captures are masked
+            keep_all_states: false, // Only open ports are stored
+            hooks: crate::scanner::HookCommands::default(), // No hook commands
+            banner: BannerConfig::default(), // Phobos banner, no notice
+            theme: ThemeName::Default, // Default palette
         }
     }
 }
@@ -132,21 +420,204 @@ impl ScanConfig {
         self
     }
     
-    /// Get timeout as Duration with smart detection
-    pub fn timeout_duration(&self) -> Duration {
-        // Auto-detect localhost and use ultra-fast timeout
-        let is_localhost = self.target == "localhost" 
-            || self.target == "127.0.0.1" 
-            || self.target == "::1"
-            || self.target.starts_with("127.")
-            || self.target.starts_with("localhost");
-        
-        if is_localhost && self.timeout > 20 {
-            // Force 10ms timeout for localhost (RustScan speed)
-            Duration::from_millis(10)
-        } else {
-            Duration::from_millis(self.timeout)
+    /// Set the rate limiter burst size
+    pub fn with_burst_size(mut self, burst_size: u64) -> Self {
+        self.burst_size = Some(burst_size);
+        self
+    }
+    
+    /// Set the per-host rate limit
+    pub fn with_host_rate_limit(mut self, host_rate_limit: u64) -> Self {
+        self.host_rate_limit = Some(host_rate_limit);
+        self
+    }
+    
+    /// Limit how many probes run against one host at a time
+    pub fn with_host_concurrency(mut self, host_concurrency: usize) -> Self {
+        self.host_concurrency = Some(host_concurrency);
+        self
+    }
+    
+    /// Cap the scan's bandwidth at `bits_per_second`
+    pub fn with_max_bandwidth(mut self, bits_per_second: u64) -> Self {
+        self.max_bandwidth = Some(bits_per_second);
+        self
+    }
+    
+    /// Throttle or pause the scan while `check` looks unhealthy
+    pub fn with_health_check(mut self, check: crate::scanner::HealthCheck) -> Self {
+        self.health_check = Some(check);
+        self
+    }
+    
+    /// Scan every one of `targets`; the first also becomes `target`
+    pub fn with_targets(mut self, targets: Vec<String>) -> Self {
+        if let Some(first) = targets.first() {
+            self.target = first.clone();
+        }
+        self.targets = targets;
+        self
+    }
+    
+    /// `targets`, or `target` alone for a single-target scan
+    pub fn all_targets(&self) -> Vec<&str> {
+        match self.targets.is_empty() {
+            true => vec![self.target.as_str()],
+            false => self.targets.iter().map(String::as_str).collect(),
+        }
+    }
+    
+    /// Every address the targets cover, once each and in order, less
+    /// `exclude_ips`
+    pub fn target_addresses(&self) -> crate::Result<Vec<IpAddr>> {
+        let mut addresses = Vec::new();
+        for target in self.all_targets() {
+            addresses.append(&mut crate::network::protocol::NetworkUtils::parse_cidr(target)?);
+        }
+        self.exclude(addresses)
+    }
+    
+    /// How many hosts the targets cover, counted from their prefix lengths
+    /// rather than listed; exclusions still need the list. A hostname, or
+    /// any target that is not an address or block, counts as one host
+    pub fn target_count(&self) -> u64 {
+        use crate::network::protocol::NetworkUtils;
+        
+        let excluding = self.exclude_ips.as_ref().is_some_and(|ips| !ips.is_empty());
+        let mut addresses = Vec::new();
+        let count = self.all_targets().into_iter().fold(0u64, |count, target| {
+            match NetworkUtils::cidr_size(target) {
+                Ok(_) if excluding => {
+                    addresses.extend(NetworkUtils::parse_cidr(target).unwrap_or_default());
+                    count
+                }
+                Ok(size) => count.saturating_add(size),
+                Err(_) => count.saturating_add(1),
+            }
+        });
+        match addresses.is_empty() {
+            true => count,
+            false => count.saturating_add(self.exclude(addresses).map_or(0, |ips| ips.len() as u64)),
+        }
+    }
+    
+    /// `addresses` without repeats and without the ones in `exclude_ips`;
+    /// an error when none are left
+    pub fn exclude(&self, addresses: Vec<IpAddr>) -> crate::Result<Vec<IpAddr>> {
+        let mut exclusions = crate::utils::address_exclusions::AddressExclusions::new();
+        for exclusion in self.exclude_ips.iter().flatten() {
+            // Scope files may exclude host names, which no address matches
+            if let Err(e) = exclusions.add_exclusion(exclusion) {
+                log::debug!("Ignoring exclusion {}: {}", exclusion, e);
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        let addresses: Vec<IpAddr> = addresses
+            .into_iter()
+            .filter(|&address| !exclusions.is_excluded(address) && seen.insert(address))
+            .collect();
+        if addresses.is_empty() {
+            return Err(crate::ScanError::InvalidTarget("every target address is excluded".to_string()));
+        }
+        Ok(addresses)
+    }
+    
+    /// Probes per second the scan may send: `rate_limit`, lowered to fit
+    /// `max_bandwidth` with this technique's probe sizes
+    pub fn effective_rate(&self) -> u64 {
+        match self.max_bandwidth {
+            Some(bits) => self.rate_limit.min(crate::network::bandwidth::probes_per_second(bits, self.technique, &self.ports)),
+            None => self.rate_limit,
+        }
+    }
+    
+    /// The rate ramp-up at the start of a scan, if `ramp_up` is set
+    pub fn ramp(&self) -> Option<crate::network::protocol::RampUp> {
+        use crate::network::protocol::RampUp;
+        Some(RampUp {
+            start_percent: self.ramp_start.unwrap_or(RampUp::DEFAULT_START_PERCENT),
+            window: Duration::from_millis(self.ramp_up?),
+        })
+    }
+    
+    /// Lift WAN-tuned pacing for targets that all answer within
+    /// `local_timeout`, except where `kept` says the operator set it; what
+    /// was lifted, for the operator. Nothing unless `throttle_local` is off.
+    pub fn lift_local_pacing(&mut self, local_timeout: Duration, kept: LocalPacing) -> Vec<String> {
+        let mut lifted = Vec::new();
+        if self.throttle_local {
+            return lifted;
+        }
+        let local_timeout = local_timeout.as_millis() as u64;
+        if !kept.timeout && self.timeout > local_timeout {
+            self.timeout = local_timeout;
+            lifted.push(format!("timeout {}ms", self.timeout));
+        }
+        if !kept.rate_limit {
+            self.rate_limit = crate::utils::config::MAX_RATE_LIMIT;
+            self.max_bandwidth = None;
+            lifted.push(format!("rate limit raised to {}/s", self.rate_limit));
+        }
+        if !kept.host_rate_limit && self.host_rate_limit.take().is_some() {
+            lifted.push("no per-host limit".to_string());
+        }
+        if !kept.adaptive_rate && self.adaptive_rate().is_some() {
+            (self.min_rate, self.max_rate) = (None, None);
+            lifted.push("no adaptive per-host rate".to_string());
         }
+        lifted
+    }
+    
+    /// Bounds of adaptive per-host rates, if `min_rate` or `max_rate` is set;
+    /// `host_rate_limit` and the global rate cap the highest
+    pub fn adaptive_rate(&self) -> Option<crate::network::protocol::AdaptiveRate> {
+        use crate::network::protocol::AdaptiveRate;
+        if self.min_rate.is_none() && self.max_rate.is_none() {
+            return None;
+        }
+        let ceiling = match self.effective_rate() {
+            0 => u64::MAX,
+            rate => rate,
+        };
+        let ceiling = self.host_rate_limit.map_or(ceiling, |host_rate| host_rate.min(ceiling));
+        let max = self.max_rate.map_or(ceiling, |max| max.min(ceiling));
+        let min = self.min_rate.unwrap_or(AdaptiveRate::DEFAULT_MIN).min(max);
+        Some(AdaptiveRate { min, max })
+    }
+    
+    /// Build the rate limiter described by this configuration
+    pub fn rate_limiter(&self) -> crate::network::protocol::RateLimiter {
+        let rate = self.effective_rate();
+        let mut burst = self.burst_size.unwrap_or(rate);
+        // A bandwidth cap is a promise; bursts above it would break it
+        if self.max_bandwidth.is_some() {
+            burst = burst.min(rate);
+        }
+        let mut limiter = crate::network::protocol::RateLimiter::with_burst(rate, burst);
+        if let Some(ramp) = self.ramp() {
+            limiter = limiter.with_ramp_up(ramp);
+        }
+        if let Some(bounds) = self.adaptive_rate() {
+            return limiter.with_adaptive_host_rate(bounds);
+        }
+        match self.host_rate_limit {
+            Some(host_rate) => limiter.with_host_rate(host_rate, burst.min(host_rate)),
+            None => limiter,
+        }
+    }
+    
+    /// Socket options for connect scans
+    pub fn connect_options(&self) -> crate::network::socket::ConnectOptions {
+        crate::network::socket::ConnectOptions {
+            rst_on_close: self.rst_on_close,
+            local_port_range: self.source_port_range,
+            mss: self.mtu_override.map(|mtu| crate::network::mtu::PathMtu::fixed(mtu).mss()),
+        }
+    }
+    
+    /// Get timeout as Duration
+    pub fn timeout_duration(&self) -> Duration {
+        Duration::from_millis(self.timeout)
     }
     
     /// Calculate optimal batch size based on rate limit and threads
@@ -157,7 +628,7 @@ impl ScanConfig {
         }
         
         // Intelligent batch sizing based on system capabilities
-        let cpu_cores = num_cpus::get();
+        let cpu_cores = crate::utils::device::available_cpus();
         let memory_factor = if self.ports.len() > 10000 { 2 } else { 1 };
         
         // Intelligent batch sizing for maximum speed with accuracy
@@ -220,10 +691,12 @@ impl ScanConfig {
         }
         
         // Validate target format (IP address or hostname)
-        if !self.is_valid_target(&self.target) {
-            return Err(crate::ScanError::InvalidTarget(
-                format!("Invalid target format: {}", self.target)
-            ));
+        for target in self.all_targets() {
+            if !self.is_valid_target(target) {
+                return Err(crate::ScanError::InvalidTarget(
+                    format!("Invalid target format: {}", target)
+                ));
+            }
         }
         
         if self.ports.is_empty() {
@@ -255,6 +728,84 @@ impl ScanConfig {
             return Err(crate::ScanError::ConfigError("Rate limit must be greater than 0".to_string()));
         }
         
+        if self.burst_size == Some(0) {
+            return Err(crate::ScanError::ConfigError("Burst size must be greater than 0".to_string()));
+        }
+        
+        if let Some((low, high)) = self.source_port_range {
+            if low == 0 || low > high {
+                return Err(crate::ScanError::ConfigError(
+                    format!("Invalid source port range: {}-{}", low, high)
+                ));
+            }
+        }
+        
+        if self.host_rate_limit == Some(0) {
+            return Err(crate::ScanError::ConfigError("Per-host rate limit must be greater than 0".to_string()));
+        }
+        
+        if self.min_rate == Some(0) || self.max_rate == Some(0) {
+            return Err(crate::ScanError::ConfigError("Adaptive rate bounds must be greater than 0".to_string()));
+        }
+        if let (Some(min), Some(max)) = (self.min_rate, self.max_rate) {
+            if min > max {
+                return Err(crate::ScanError::ConfigError(format!("Minimum rate {} is above maximum rate {}", min, max)));
+            }
+        }
+        
+        if self.health_threshold.is_some_and(|threshold| !threshold.is_finite() || threshold <= 1.0) {
+            return Err(crate::ScanError::ConfigError("Health threshold must be a finite factor above 1".to_string()));
+        }
+        
+        if self.max_bandwidth == Some(0) {
+            return Err(crate::ScanError::ConfigError("Bandwidth cap must be greater than 0".to_string()));
+        }
+        
+        if self.memory_limit == Some(0) {
+            return Err(crate::ScanError::ConfigError("Memory limit must be greater than 0".to_string()));
+        }
+        
+        if let Some(zombie) = &self.zombie {
+            crate::scanner::techniques::parse_zombie(zombie)?;
+            if self.service_version {
+                return Err(crate::ScanError::ConfigError(
+                    "Service detection connects from our own address, which an idle scan avoids".to_string(),
+                ));
+            }
+        }
+        
+        if self.resume && self.checkpoint.is_none() {
+            return Err(crate::ScanError::ConfigError("Resuming needs the id of a checkpoint".to_string()));
+        }
+        
+        if self.service_version && !cfg!(feature = "intelligence") {
+            return Err(crate::ScanError::ConfigError(
+                "Service detection needs Phobos built with the intelligence feature".to_string(),
+            ));
+        }
+        
+        if self.host_concurrency == Some(0) {
+            return Err(crate::ScanError::ConfigError("Per-host concurrency must be greater than 0".to_string()));
+        }
+        
+        if let Some(filter) = &self.capture_filter {
+            crate::network::bpf::FilterExpr::parse(filter)?;
+        }
+        
+        if let Some(vlan) = self.vlan {
+            crate::network::vlan::parse_vlan_id(&vlan.to_string())?;
+        }
+        
+        if self.mtu_override.is_some_and(|mtu| mtu < crate::network::mtu::MIN_MTU) {
+            return Err(crate::ScanError::ConfigError(format!(
+                "MTU override must be at least {} bytes",
+                crate::network::mtu::MIN_MTU
+            )));
+        }
+        
+        crate::network::probes::CustomProbes::compile(&self.probes)?;
+        crate::network::banner_rules::BannerRules::compile(&self.banner_rules)?;
+        
         Ok(())
     }
     
@@ -267,6 +818,16 @@ impl ScanConfig {
             return true;
         }
         
+        // CIDR block; the engine scans every address in it
+        if let Some((network, prefix)) = target.split_once('/') {
+            let Ok(prefix) = prefix.parse::<u32>() else { return false };
+            return match network.parse::<IpAddr>() {
+                Ok(IpAddr::V4(_)) => prefix <= 32,
+                Ok(IpAddr::V6(_)) => prefix <= 128 && 128 - prefix <= crate::network::protocol::MAX_IPV6_HOST_BITS,
+                Err(_) => false,
+            };
+        }
+        
         // Check if it's a valid hostname format
         if target.len() > 253 {
             return false; // Hostname too long
@@ -372,4 +933,72 @@ impl Default for TimingConfig {
             retry_delay: Duration::from_millis(100),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_local_pacing_lifts_only_what_was_not_set() {
+        let wan = || ScanConfig { timeout: 1000, rate_limit: 1000, max_bandwidth: Some(1_000_000), host_rate_limit: Some(50), min_rate: Some(10), ..ScanConfig::default() };
+        let loopback = Duration::from_millis(10);
+        
+        let mut config = wan();
+        let lifted = config.lift_local_pacing(loopback, LocalPacing::default());
+        assert_eq!(lifted.len(), 4);
+        assert_eq!((config.timeout, config.rate_limit), (10, crate::utils::config::MAX_RATE_LIMIT));
+        assert_eq!((config.max_bandwidth, config.host_rate_limit, config.adaptive_rate()), (None, None, None));
+        
+        let mut config = wan();
+        let kept = LocalPacing { timeout: true, rate_limit: true, host_rate_limit: true, adaptive_rate: false };
+        assert_eq!(config.lift_local_pacing(loopback, kept), vec!["no adaptive per-host rate".to_string()]);
+        assert_eq!((config.timeout, config.rate_limit, config.max_bandwidth, config.host_rate_limit), (1000, 1000, Some(1_000_000), Some(50)));
+        
+        // A timeout already below the local one stays, and --throttle-local keeps everything
+        let mut config = ScanConfig { timeout: 5, ..wan() };
+        config.lift_local_pacing(loopback, LocalPacing::default());
+        assert_eq!(config.timeout, 5);
+        let mut config = ScanConfig { throttle_local: true, ..wan() };
+        assert!(config.lift_local_pacing(loopback, LocalPacing::default()).is_empty());
+        assert_eq!(config.rate_limit, 1000);
+    }
+    
+    #[test]
+    fn test_target_count_keeps_blocks_next_to_hostnames() {
+        let config = |targets: &[&str]| ScanConfig::default().with_targets(targets.iter().map(|t| t.to_string()).collect());
+        assert_eq!(config(&["192.0.2.0/24", "scanme.example", "2001:db8::/120"]).target_count(), 256 + 1 + 256);
+        assert_eq!(config(&["scanme.example"]).target_count(), 1);
+        
+        let excluding = ScanConfig { exclude_ips: Some(vec!["192.0.2.0/28".to_string()]), ..config(&["192.0.2.0/24", "scanme.example"]) };
+        assert_eq!(excluding.target_count(), 240 + 1);
+    }
+    
+    #[test]
+    fn test_legal_notice_acknowledgment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("phobos.toml");
+        fs::write(&path, "[banner]\ntext = \"ACME red team\"\nlegal_notice = \"Authorized testing only.\"\nrequire_acknowledgment = true\n").unwrap();
+        let banner = BannerConfig::load(path.to_str());
+        assert_eq!(banner.text.as_deref(), Some("ACME red team"));
+        assert_eq!(banner.legal_notice.as_deref(), Some("Authorized testing only."));
+        
+        assert_eq!(banner.acknowledgment(true, false), Acknowledgment::Assumed);
+        assert_eq!(banner.acknowledgment(false, true), Acknowledgment::Prompt);
+        assert_eq!(banner.acknowledgment(false, false), Acknowledgment::Refused);
+        // Shown, but not asked about
+        let shown = BannerConfig { require_acknowledgment: false, ..banner.clone() };
+        assert_eq!(shown.acknowledgment(false, false), Acknowledgment::NotRequired);
+        let none = BannerConfig { legal_notice: None, ..banner };
+        assert_eq!(none.acknowledgment(false, false), Acknowledgment::NotRequired);
+        
+        assert!(BannerConfig::accepts("y\n") && BannerConfig::accepts(" YES "));
+        assert!(!BannerConfig::accepts("\n") && !BannerConfig::accepts("no") && !BannerConfig::accepts("yep"));
+        
+        // Embedded in reports of scans run under it
+        let config = ScanConfig { banner: BannerConfig::load(path.to_str()), ..ScanConfig::default() };
+        let provenance = crate::output::provenance::Provenance::collect(&config);
+        assert_eq!(provenance.legal_notice.as_deref(), Some("Authorized testing only."));
+        assert_eq!(BannerConfig::load(dir.path().join("missing.toml").to_str()), BannerConfig::default());
+    }
+}
@@ -83,26 +83,8 @@ pub enum SequencePredictability {
     TimeDependent,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IPIDSequence {
-    pub sequence_type: IPIDSequenceType,
-    pub increment_value: u16,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum IPIDSequenceType {
-    Incremental,
-    Random,
-    Zero,
-    Broken,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TimestampAnalysis {
-    pub timestamp_option_present: bool,
-    pub timestamp_frequency: Option<u32>,
-    pub uptime_estimate: Option<Duration>,
-}
+pub use crate::network::ip_id::{IPIDSequence, IPIDSequenceType};
+pub use crate::network::uptime::TimestampAnalysis;
 
 impl BasicOSFingerprint {
     pub fn new() -> Self {
@@ -0,0 +1,273 @@
+//! Worker results merged into one scan
+//!
+//! A distributed scan comes back as chunks: one [`ScanResult`] per task,
+//! from whichever worker ran it. [`ResultAggregator`] merges them into the
+//! [`crate::scanner::ScanResult`] a local scan of the same targets would have
+//! produced, so the usual output formats and watch history take it as is.
+//!
+//! Chunks can arrive twice, when a redelivered task finishes on both workers
+//! or when a failed worker's targets are handed out again, and vantage point
+//! comparisons scan every target from every worker on purpose. A chunk is
+//! merged once per task, and a port reported by several workers is listed
+//! once, open if any of them found it open.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::distributed::{ScanResult, ScanTask};
+use crate::config::ScanConfig;
+use crate::monitor::{HistoryManager, Snapshot};
+use crate::network::{PortResult, PortState, Protocol};
+use crate::output::OutputManager;
+use crate::scanner::{self, HostResult, ScanStats};
+
+/// What one worker contributed to a scan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkerStats {
+    pub node_id: Uuid,
+    /// Chunks merged from the worker
+    pub tasks: usize,
+    /// Port results it reported
+    pub ports: usize,
+    /// Of those, open
+    pub open: usize,
+    /// Port results another chunk, or an earlier delivery, already had
+    pub duplicates: usize,
+    /// Time it spent on its chunks, as it reported
+    pub busy: Duration,
+}
+
+impl fmt::Display for WorkerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} tasks, {} ports ({} open) in {:?}", self.node_id, self.tasks, self.ports, self.open, self.busy)?;
+        if self.duplicates > 0 {
+            write!(f, ", {} duplicates", self.duplicates)?;
+        }
+        Ok(())
+    }
+}
+
+/// Ports of one host, merged over chunks
+#[derive(Debug, Default)]
+struct HostPorts {
+    open: BTreeMap<u16, PortResult>,
+    /// Reported, but not open by any worker
+    shut: BTreeSet<u16>,
+    /// Longest chunk that covered the host
+    duration: Duration,
+}
+
+/// Merges the chunks of a distributed scan into one result
+#[derive(Debug)]
+pub struct ResultAggregator {
+    config: ScanConfig,
+    started: Instant,
+    expected: HashSet<Uuid>,
+    merged: HashSet<Uuid>,
+    hosts: BTreeMap<IpAddr, HostPorts>,
+    workers: BTreeMap<Uuid, WorkerStats>,
+}
+
+impl ResultAggregator {
+    /// Aggregate a scan run with `config`, which the merged result reports
+    pub fn new(config: ScanConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            expected: HashSet::new(),
+            merged: HashSet::new(),
+            hosts: BTreeMap::new(),
+            workers: BTreeMap::new(),
+        }
+    }
+
+    /// Await results for `tasks`; those still missing at the end count as
+    /// errors in the merged result
+    pub fn expect(&mut self, tasks: &[ScanTask]) {
+        self.expected.extend(tasks.iter().map(|task| task.id));
+    }
+
+    /// Merge one chunk; false when its task was already merged
+    pub fn record(&mut self, chunk: &ScanResult) -> bool {
+        let worker = self.workers.entry(chunk.node_id).or_insert_with(|| WorkerStats {
+            node_id: chunk.node_id,
+            ..WorkerStats::default()
+        });
+        if !self.merged.insert(chunk.task_id) {
+            worker.duplicates += chunk.results.len();
+            return false;
+        }
+        worker.tasks += 1;
+        worker.ports += chunk.results.len();
+        worker.busy += chunk.execution_time;
+
+        for port in &chunk.results {
+            let host = self.hosts.entry(port.target).or_default();
+            host.duration = host.duration.max(chunk.execution_time);
+            let seen = host.open.contains_key(&port.port) || host.shut.contains(&port.port);
+            if seen {
+                worker.duplicates += 1;
+            }
+            if !port.is_open {
+                if !host.open.contains_key(&port.port) {
+                    host.shut.insert(port.port);
+                }
+                continue;
+            }
+            worker.open += 1;
+            host.shut.remove(&port.port);
+            // The first worker to find a port open reports it
+            host.open.entry(port.port).or_insert_with(|| {
+                let mut result = PortResult::new(port.port, Protocol::Tcp, PortState::Open)
+                    .with_response_time(port.response_time);
+                result.service = port.service.clone().filter(|service| service != "unknown");
+                result
+            });
+        }
+        true
+    }
+
+    /// Awaited tasks no worker has reported yet
+    pub fn missing(&self) -> usize {
+        self.expected.difference(&self.merged).count()
+    }
+
+    /// Each worker's contribution so far
+    pub fn worker_stats(&self) -> Vec<WorkerStats> {
+        self.workers.values().cloned().collect()
+    }
+
+    /// The scan as merged so far
+    ///
+    /// Like the engine's own results, `port_results` only holds open ports;
+    /// workers report the rest without telling closed from filtered, so
+    /// they are counted as filtered.
+    pub fn finish(&self) -> scanner::ScanResult {
+        let mut result = scanner::ScanResult::new(self.config.target.clone(), self.config.clone());
+        let mut stats = ScanStats::default();
+        let mut hosts = Vec::with_capacity(self.hosts.len());
+        for (&ip, ports) in &self.hosts {
+            let mut host = HostResult::new(ip);
+            host.duration = ports.duration;
+            host.stats.packets_sent = (ports.open.len() + ports.shut.len()) as u64;
+            host.stats.ports_filtered = ports.shut.len() as u64;
+            for (&port, port_result) in &ports.open {
+                host.open_ports.push(port);
+                host.port_results.push(port_result.clone());
+                result.push_port_result(port_result.clone());
+            }
+            stats.packets_sent += host.stats.packets_sent;
+            stats.ports_filtered += host.stats.ports_filtered;
+            hosts.push(host);
+        }
+        if hosts.len() > 1 {
+            result.hosts = hosts;
+        }
+
+        let duration = self.started.elapsed();
+        stats.errors = self.missing() as u64;
+        stats.engine_timings.probing = duration;
+        if duration.as_secs_f64() > 0.0 {
+            stats.actual_rate = stats.packets_sent as f64 / duration.as_secs_f64();
+        }
+        result.set_duration(duration);
+        result.update_stats(stats);
+        result
+    }
+
+    /// Write the merged scan with `output`, and store it as the newest run
+    /// in `history` if given
+    pub fn report(&self, output: &OutputManager, history: Option<&HistoryManager>) -> io::Result<scanner::ScanResult> {
+        let result = self.finish();
+        output.write_results(&result)?;
+        if let Some(history) = history {
+            history.record(&Snapshot::from_scan(&result))?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intelligence::distributed::{PortScanResult, TaskPriority};
+    use crate::output::{OutputConfig, OutputFormat};
+    use std::time::SystemTime;
+
+    fn task(targets: &[&str]) -> ScanTask {
+        ScanTask {
+            id: Uuid::new_v4(),
+            targets: targets.iter().map(|t| t.parse().unwrap()).collect(),
+            ports: vec![22, 80, 443],
+            assigned_node: None,
+            priority: TaskPriority::Medium,
+            created_at: SystemTime::now(),
+            timeout: Duration::from_secs(1),
+            rate_share: None,
+        }
+    }
+
+    fn chunk(task: &ScanTask, node_id: Uuid, open: &[u16]) -> ScanResult {
+        let results = task.targets.iter().flat_map(|&target| {
+            task.ports.iter().map(move |&port| PortScanResult {
+                target,
+                port,
+                is_open: open.contains(&port),
+                service: Some("unknown".to_string()),
+                response_time: Duration::from_millis(5),
+            })
+        });
+        ScanResult { task_id: task.id, node_id, results: results.collect(), execution_time: Duration::from_millis(40) }
+    }
+
+    #[test]
+    fn test_chunks_merge_into_one_scan() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let tasks = [task(&["10.0.0.1"]), task(&["10.0.0.2"]), task(&["10.0.0.1"]), task(&["10.0.0.3"])];
+        let config = ScanConfig { target: "10.0.0.0/30".to_string(), ..Default::default() };
+        let mut aggregator = ResultAggregator::new(config);
+        aggregator.expect(&tasks);
+
+        assert!(aggregator.record(&chunk(&tasks[0], a, &[22])));
+        assert!(aggregator.record(&chunk(&tasks[1], b, &[443])));
+        // Redelivered, then the same host again from another vantage point
+        assert!(!aggregator.record(&chunk(&tasks[1], b, &[443])));
+        assert!(aggregator.record(&chunk(&tasks[2], b, &[22, 80])));
+        assert_eq!(aggregator.missing(), 1);
+
+        let result = aggregator.finish();
+        assert_eq!(result.open_ports, vec![22, 80, 443]);
+        assert_eq!(result.hosts.len(), 2);
+        assert_eq!(result.hosts[0].open_ports, vec![22, 80]);
+        assert_eq!(result.hosts[0].stats.ports_filtered, 1);
+        assert_eq!(result.hosts[1].open_ports, vec![443]);
+        assert_eq!((result.stats.packets_sent, result.stats.ports_filtered, result.stats.errors), (6, 3, 1));
+        assert!(result.port_results.iter().all(|port| port.service.is_none()));
+
+        let workers = aggregator.worker_stats();
+        let stats = |node| workers.iter().find(|w| w.node_id == node).unwrap();
+        assert_eq!((stats(a).tasks, stats(a).ports, stats(a).open, stats(a).duplicates), (1, 3, 1, 0));
+        assert_eq!((stats(b).tasks, stats(b).ports, stats(b).open, stats(b).duplicates), (2, 6, 3, 6));
+        assert_eq!(stats(b).busy, Duration::from_millis(80));
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("merged.json");
+        let output = OutputManager::new(OutputConfig {
+            format: OutputFormat::Json,
+            file: Some(file.to_string_lossy().into_owned()),
+            ..OutputConfig::default()
+        });
+        let history = HistoryManager::open(dir.path(), "distributed");
+        aggregator.report(&output, Some(&history)).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+        assert_eq!((written["target"].as_str(), written["open_ports"].as_array().map(Vec::len)), (Some("10.0.0.0/30"), Some(3)));
+        let run = history.latest().unwrap().unwrap();
+        assert_eq!(run.hosts, vec!["10.0.0.1", "10.0.0.2"]);
+        assert_eq!(run.open.len(), 3);
+    }
+}
@@ -287,6 +287,9 @@ impl CircuitBreaker {
     }
 }
 
+/// How long a discovery connection stays usable for service detection
+const CONNECTION_POOL_MAX_IDLE: Duration = Duration::from_secs(10);
+
 /// Ultra-fast intelligence engine that coordinates all components
 pub struct IntelligenceEngine {
     config: IntelligenceConfig,
@@ -296,6 +299,7 @@ pub struct IntelligenceEngine {
     
     // Intelligence components
     service_detector: Option<Arc<super::ServiceDetectionEngine>>,
+    #[cfg(feature = "distributed")]
     _distributed_coordinator: Option<Arc<super::DistributedCoordinator>>,
     network_discoverer: Option<Arc<super::NetworkDiscoveryEngine>>,
     asset_manager: Option<Arc<RwLock<super::AssetManager>>>,
@@ -317,11 +321,22 @@ pub struct IntelligenceEngine {
 impl IntelligenceEngine {
     /// Create a new intelligence engine with ultra-fast optimizations
     pub async fn new(config: IntelligenceConfig) -> IntelligenceResult<Self> {
-        let scan_engine = Arc::new(
-            crate::scanner::engine::ScanEngine::new(config.scan_config.clone())
-                .await
-                .map_err(NetworkIntelligenceError::ScanError)?
-        );
+        let mut scan_engine = crate::scanner::engine::ScanEngine::new(config.scan_config.clone())
+            .await
+            .map_err(NetworkIntelligenceError::ScanError)?;
+        
+        // Let service detection reuse the connections the port scan opened
+        let connection_pool = if config.enable_service_detection && config.performance_config.connection_pool_size > 0 {
+            let pool = crate::network::socket::ConnectionPool::new(
+                config.performance_config.connection_pool_size,
+                CONNECTION_POOL_MAX_IDLE,
+            );
+            scan_engine = scan_engine.with_connection_pool(pool.clone());
+            Some(pool)
+        } else {
+            None
+        };
+        let scan_engine = Arc::new(scan_engine);
         
         // Initialize performance monitoring
         let performance_monitor = Arc::new(super::PerformanceMonitor::new(
@@ -336,7 +351,7 @@ impl IntelligenceEngine {
         
         // Initialize ultra-fast thread pool
         let thread_pool_size = if config.performance_config.thread_pool_size == 0 {
-            num_cpus::get() * 4 // 4x CPU cores for I/O bound operations
+            crate::utils::device::available_cpus() * 4 // 4x CPU cores for I/O bound operations
         } else {
             config.performance_config.thread_pool_size
         };
@@ -348,15 +363,28 @@ impl IntelligenceEngine {
         
         // Initialize components based on configuration
         let service_detector = if config.enable_service_detection {
-            Some(Arc::new(super::ServiceDetectionEngine::new(
+            let detector = super::ServiceDetectionEngine::new(
                 config.service_detection_timeout,
                 thread_pool.clone(),
                 memory_pool.clone(),
-            ).await?))
+            ).await?;
+            let probes = crate::network::probes::CustomProbes::compile(&config.scan_config.probes)
+                .map_err(NetworkIntelligenceError::ScanError)?;
+            let banner_rules = crate::network::banner_rules::BannerRules::compile(&config.scan_config.banner_rules)
+                .map_err(NetworkIntelligenceError::ScanError)?;
+            let detector = detector
+                .with_custom_probes(probes)
+                .with_banner_rules(banner_rules)
+                .with_secret_redaction(!config.scan_config.keep_secrets);
+            Some(Arc::new(match connection_pool {
+                Some(pool) => detector.with_connection_pool(pool),
+                None => detector,
+            }))
         } else {
             None
         };
         
+        #[cfg(feature = "distributed")]
         let _distributed_coordinator = if config.enable_distributed_scanning {
             let listen_addr = SocketAddr::from(([127, 0, 0, 1], 8080));
             let fault_tolerance_config = super::distributed::FaultToleranceConfig {
@@ -376,6 +404,12 @@ impl IntelligenceEngine {
         } else {
             None
         };
+        #[cfg(not(feature = "distributed"))]
+        if config.enable_distributed_scanning {
+            return Err(NetworkIntelligenceError::DistributedError(
+                "phobos was built without the `distributed` feature".to_string(),
+            ));
+        }
         
         let network_discoverer = if config.enable_network_discovery {
             Some(Arc::new(super::NetworkDiscoveryEngine::new(
@@ -400,6 +434,7 @@ impl IntelligenceEngine {
             config,
             scan_engine,
             service_detector,
+            #[cfg(feature = "distributed")]
             _distributed_coordinator,
             network_discoverer,
             asset_manager,
@@ -0,0 +1,441 @@
+//! Default credential checks (explicitly opt-in)
+//!
+//! Tries a short list of vendor default logins against services where they
+//! are a common finding. Nothing here runs unless the caller confirms it is
+//! authorized to test the targets: [`CredentialChecker::new`] refuses to build
+//! otherwise, and the CLI requires `--i-have-authorization`.
+//!
+//! Attempts against one service are strictly serialized and spaced by
+//! `attempt_interval`. When a service signals a lockout or throttling the
+//! checker backs off exponentially and gives up on that service after
+//! `max_lockouts` signals, so accounts are not locked out by the scan.
+//! Protocols where failed logins commonly lock accounts (RDP, SMB) are
+//! deliberately not supported.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+
+use super::misconfig::{Finding, LineClient, Severity};
+use crate::ScanError;
+
+/// Username/password pair to try
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credential {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credential {
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    /// Parse `user:password` lines, skipping blanks and `#` comments
+    pub fn parse_list(content: &str) -> crate::Result<Vec<Self>> {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(user, pass)| Self::new(user, pass))
+                    .ok_or_else(|| ScanError::ParseError(format!("Invalid credential line (expected user:password): {}", line)))
+            })
+            .collect()
+    }
+}
+
+/// Result of a single login attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginOutcome {
+    Success,
+    Failure,
+    /// The service reported a lockout or rate limit
+    LockedOut,
+    /// The service could not be reached or spoke an unexpected protocol
+    Unavailable,
+}
+
+/// Protocol-specific login attempt
+#[async_trait::async_trait]
+pub trait CredentialPlugin: Send + Sync {
+    fn id(&self) -> &'static str;
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool;
+
+    async fn try_login(&self, target: SocketAddr, credential: &Credential, timeout: Duration) -> LoginOutcome;
+}
+
+/// Settings for [`CredentialChecker`]
+#[derive(Debug, Clone)]
+pub struct CredentialCheckConfig {
+    /// Caller confirmed it is authorized to test the targets
+    pub authorized: bool,
+    pub credentials: Vec<Credential>,
+    /// Minimum delay between two attempts against the same service
+    pub attempt_interval: Duration,
+    /// First backoff after a lockout signal; doubles on every further signal
+    pub lockout_backoff: Duration,
+    /// Give up on a service after this many lockout signals
+    pub max_lockouts: u32,
+    pub timeout: Duration,
+}
+
+impl Default for CredentialCheckConfig {
+    fn default() -> Self {
+        Self {
+            authorized: false,
+            credentials: default_credentials(),
+            attempt_interval: Duration::from_secs(10),
+            lockout_backoff: Duration::from_secs(60),
+            max_lockouts: 2,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Small built-in list of common vendor defaults
+pub fn default_credentials() -> Vec<Credential> {
+    [
+        ("admin", "admin"),
+        ("admin", "password"),
+        ("root", "root"),
+        ("admin", ""),
+        ("user", "user"),
+    ]
+    .iter()
+    .map(|(user, pass)| Credential::new(user, pass))
+    .collect()
+}
+
+/// Runs credential plugins against open services
+pub struct CredentialChecker {
+    config: CredentialCheckConfig,
+    plugins: Vec<Box<dyn CredentialPlugin>>,
+}
+
+impl CredentialChecker {
+    /// Build a checker; fails unless `config.authorized` is set
+    pub fn new(config: CredentialCheckConfig) -> crate::Result<Self> {
+        if !config.authorized {
+            return Err(ScanError::ConfigError(
+                "Default credential checks require explicit authorization (--i-have-authorization)".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            config,
+            plugins: vec![Box::new(TelnetLogin), Box::new(FtpLogin)],
+        })
+    }
+
+    /// Add a custom plugin
+    pub fn with_plugin(mut self, plugin: Box<dyn CredentialPlugin>) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    /// Check every applicable service; services are tested concurrently,
+    /// attempts against a single service never are
+    pub async fn scan(&self, targets: &[(SocketAddr, Option<String>)]) -> Vec<Finding> {
+        let runs = targets.iter().flat_map(|(addr, service)| {
+            self.plugins
+                .iter()
+                .filter(move |plugin| plugin.applies_to(addr.port(), service.as_deref()))
+                .map(move |plugin| self.check_service(plugin.as_ref(), *addr))
+        });
+
+        futures::future::join_all(runs).await.into_iter().flatten().collect()
+    }
+
+    async fn check_service(&self, plugin: &dyn CredentialPlugin, target: SocketAddr) -> Option<Finding> {
+        let mut lockouts = 0;
+        let mut next_attempt = Instant::now();
+        let mut queue = self.config.credentials.iter();
+        let mut current = queue.next();
+
+        while let Some(credential) = current {
+            tokio::time::sleep_until(next_attempt).await;
+            next_attempt = Instant::now() + self.config.attempt_interval;
+
+            match plugin.try_login(target, credential, self.config.timeout).await {
+                LoginOutcome::Success => {
+                    return Some(
+                        Finding::new(
+                            target,
+                            &format!("default-credentials-{}", plugin.id()),
+                            Severity::Critical,
+                            "Service accepts default credentials",
+                        )
+                        .with_evidence(format!("username '{}' with a default password", credential.username)),
+                    );
+                }
+                LoginOutcome::Failure => current = queue.next(),
+                LoginOutcome::Unavailable => return None,
+                LoginOutcome::LockedOut => {
+                    lockouts += 1;
+                    if lockouts > self.config.max_lockouts {
+                        log::warn!("{} on {} keeps signalling lockout, skipping", plugin.id(), target);
+                        return None;
+                    }
+                    // Retry the same credential after backing off
+                    next_attempt = Instant::now() + self.config.lockout_backoff * 2u32.pow(lockouts - 1);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn lockout_message(text: &str) -> bool {
+    let text = text.to_lowercase();
+    ["locked", "too many", "try again later", "temporarily"].iter().any(|m| text.contains(m))
+}
+
+/// FTP `USER`/`PASS` login
+pub struct FtpLogin;
+
+#[async_trait::async_trait]
+impl CredentialPlugin for FtpLogin {
+    fn id(&self) -> &'static str {
+        "ftp"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        port == 21 || service == Some("ftp")
+    }
+
+    async fn try_login(&self, target: SocketAddr, credential: &Credential, timeout: Duration) -> LoginOutcome {
+        let Some(mut client) = LineClient::connect(target, timeout).await else {
+            return LoginOutcome::Unavailable;
+        };
+        match client.reply().await {
+            Some((220, _)) => {}
+            Some((421, _)) => return LoginOutcome::LockedOut,
+            _ => return LoginOutcome::Unavailable,
+        }
+
+        let outcome = match client.command(&format!("USER {}", credential.username)).await {
+            Some((230, _)) => LoginOutcome::Success,
+            Some((331, _)) => match client.command(&format!("PASS {}", credential.password)).await {
+                Some((230, _)) => LoginOutcome::Success,
+                Some((421, _)) => LoginOutcome::LockedOut,
+                Some((_, line)) if lockout_message(&line) => LoginOutcome::LockedOut,
+                Some(_) => LoginOutcome::Failure,
+                None => LoginOutcome::Unavailable,
+            },
+            Some((421, _)) => LoginOutcome::LockedOut,
+            Some(_) => LoginOutcome::Failure,
+            None => LoginOutcome::Unavailable,
+        };
+        let _ = client.command("QUIT").await;
+        outcome
+    }
+}
+
+/// Telnet login prompt dialog
+pub struct TelnetLogin;
+
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+
+impl TelnetLogin {
+    /// Read until one of `prompts` shows up (case-insensitive) or the timeout expires,
+    /// refusing every option negotiation along the way
+    async fn read_until(stream: &mut TcpStream, prompts: &[&str], timeout_duration: Duration) -> Option<String> {
+        let deadline = Instant::now() + timeout_duration;
+        let mut text = String::new();
+        let mut buf = [0u8; 1024];
+
+        loop {
+            let n = match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+                Ok(Ok(n)) if n > 0 => n,
+                _ => return (!text.is_empty()).then_some(text),
+            };
+
+            let (data, replies) = Self::strip_negotiation(&buf[..n]);
+            if !replies.is_empty() && stream.write_all(&replies).await.is_err() {
+                return None;
+            }
+            text.push_str(&String::from_utf8_lossy(&data));
+
+            let lower = text.to_lowercase();
+            if prompts.iter().any(|p| lower.trim_end().ends_with(p)) {
+                return Some(text);
+            }
+        }
+    }
+
+    /// Split option negotiation from data, answering DO with WONT and WILL with DONT
+    fn strip_negotiation(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut data = Vec::with_capacity(input.len());
+        let mut replies = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            if input[i] == IAC && i + 2 < input.len() && matches!(input[i + 1], DO | DONT | WILL | WONT) {
+                match input[i + 1] {
+                    DO => replies.extend_from_slice(&[IAC, WONT, input[i + 2]]),
+                    WILL => replies.extend_from_slice(&[IAC, DONT, input[i + 2]]),
+                    _ => {}
+                }
+                i += 3;
+            } else if input[i] == IAC && i + 1 < input.len() {
+                i += 2;
+            } else {
+                data.push(input[i]);
+                i += 1;
+            }
+        }
+        (data, replies)
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialPlugin for TelnetLogin {
+    fn id(&self) -> &'static str {
+        "telnet"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        port == 23 || service == Some("telnet")
+    }
+
+    async fn try_login(&self, target: SocketAddr, credential: &Credential, timeout_duration: Duration) -> LoginOutcome {
+        let Ok(Ok(mut stream)) = timeout(timeout_duration, TcpStream::connect(target)).await else {
+            return LoginOutcome::Unavailable;
+        };
+
+        match Self::read_until(&mut stream, &["login:", "username:"], timeout_duration).await {
+            Some(text) if lockout_message(&text) => return LoginOutcome::LockedOut,
+            Some(text) if text.to_lowercase().trim_end().ends_with(':') => {}
+            _ => return LoginOutcome::Unavailable,
+        }
+        if stream.write_all(format!("{}\r\n", credential.username).as_bytes()).await.is_err() {
+            return LoginOutcome::Unavailable;
+        }
+
+        match Self::read_until(&mut stream, &["password:"], timeout_duration).await {
+            Some(text) if text.to_lowercase().trim_end().ends_with("password:") => {}
+            _ => return LoginOutcome::Failure,
+        }
+        if stream.write_all(format!("{}\r\n", credential.password).as_bytes()).await.is_err() {
+            return LoginOutcome::Unavailable;
+        }
+
+        // A shell prompt means success; another login prompt or an error means failure
+        let response = Self::read_until(&mut stream, &["$", "#", ">", "login:"], timeout_duration)
+            .await
+            .unwrap_or_default();
+        let lower = response.to_lowercase();
+        if lockout_message(&lower) {
+            LoginOutcome::LockedOut
+        } else if lower.contains("incorrect") || lower.contains("failed") || lower.contains("denied")
+            || lower.trim_end().ends_with("login:")
+        {
+            LoginOutcome::Failure
+        } else if ["$", "#", ">"].iter().any(|p| lower.trim_end().ends_with(p)) {
+            LoginOutcome::Success
+        } else {
+            LoginOutcome::Failure
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Plugin that replays a fixed sequence of outcomes
+    struct Scripted {
+        outcomes: Vec<LoginOutcome>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl CredentialPlugin for Scripted {
+        fn id(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn applies_to(&self, port: u16, _service: Option<&str>) -> bool {
+            port == 9999
+        }
+
+        async fn try_login(&self, _target: SocketAddr, _credential: &Credential, _timeout: Duration) -> LoginOutcome {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            self.outcomes.get(call).copied().unwrap_or(LoginOutcome::Failure)
+        }
+    }
+
+    fn fast_config() -> CredentialCheckConfig {
+        CredentialCheckConfig {
+            authorized: true,
+            attempt_interval: Duration::from_millis(1),
+            lockout_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_requires_authorization() {
+        assert!(CredentialChecker::new(CredentialCheckConfig::default()).is_err());
+        assert!(CredentialChecker::new(fast_config()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_credential_list() {
+        let creds = Credential::parse_list("# defaults\nadmin:admin\n\nroot:\n").unwrap();
+        assert_eq!(creds, vec![Credential::new("admin", "admin"), Credential::new("root", "")]);
+        assert!(Credential::parse_list("nocolon").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lockout_backoff_gives_up() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let checker = CredentialChecker::new(fast_config()).unwrap().with_plugin(Box::new(Scripted {
+            outcomes: vec![LoginOutcome::LockedOut; 10],
+            calls: calls.clone(),
+        }));
+
+        let target: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        assert!(checker.scan(&[(target, None)]).await.is_empty());
+        // max_lockouts retries after the first signal, then stop
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_success_after_lockout_reports_finding() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let checker = CredentialChecker::new(fast_config()).unwrap().with_plugin(Box::new(Scripted {
+            outcomes: vec![LoginOutcome::Failure, LoginOutcome::LockedOut, LoginOutcome::Success],
+            calls: calls.clone(),
+        }));
+
+        let target: SocketAddr = "127.0.0.1:9999".parse().unwrap();
+        let findings = checker.scan(&[(target, None)]).await;
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Critical);
+        // The credential that hit the lockout is retried rather than skipped
+        assert_eq!(findings[0].evidence.as_deref(), Some("username 'admin' with a default password"));
+    }
+
+    #[test]
+    fn test_telnet_negotiation_is_refused() {
+        let (data, replies) = TelnetLogin::strip_negotiation(&[IAC, DO, 24, b'l', b'o', IAC, WILL, 1, b'g']);
+        assert_eq!(data, b"log");
+        assert_eq!(replies, vec![IAC, WONT, 24, IAC, DONT, 1]);
+    }
+}
@@ -8,13 +8,15 @@ use std::time::{Duration, SystemTime};
 
 use serde::{Deserialize, Serialize};
 
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::WriteHalf;
 use uuid::Uuid;
-use rand;
 
-use super::core::IntelligenceResult;
+use super::aggregation::ResultAggregator;
+use super::core::{IntelligenceResult, NetworkIntelligenceError};
+use super::pacing::{GlobalPacer, RateShare};
+use super::wire::{self, ClusterToken, CoordinatorStream, FrameWriter, TlsIdentity};
 use super::performance::UltraFastThreadPool;
 use crate::error::ScanError;
 
@@ -31,6 +33,17 @@ pub struct WorkerNode {
     pub network_latency: Duration,
     pub failure_count: u32,
     pub last_failure: Option<SystemTime>,
+    /// Where the worker scans from, e.g. `wan` or `dmz`, as named in
+    /// vantage point comparisons
+    #[serde(default)]
+    pub vantage: Option<String>,
+}
+
+impl WorkerNode {
+    /// The worker's vantage point name, or its address when it has none
+    pub fn vantage_name(&self) -> String {
+        self.vantage.clone().unwrap_or_else(|| self.address.to_string())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -52,6 +65,9 @@ pub struct ScanTask {
     pub priority: TaskPriority,
     pub created_at: SystemTime,
     pub timeout: Duration,
+    /// The assigned node's slice of the scan's rate budget, if there is one
+    #[serde(default)]
+    pub rate_share: Option<RateShare>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -94,6 +110,11 @@ pub enum NodeMessage {
         task: ScanTask,
         _coordinator_id: Uuid,
     },
+    /// Worker to coordinator: a task arrived and is being worked on
+    TaskAck {
+        task_id: Uuid,
+        node_id: Uuid,
+    },
     /// Task result from worker to coordinator
     TaskResult {
         result: ScanResult,
@@ -112,6 +133,10 @@ pub enum NodeMessage {
         failed_node: Uuid,
         tasks: Vec<ScanTask>,
     },
+    /// New or renewed slice of the global rate budget for a worker
+    RateShare {
+        share: RateShare,
+    },
     /// Health check request
     HealthCheck,
     /// Health check response
@@ -164,6 +189,9 @@ pub struct DistributedCoordinator {
     fault_tolerance: Arc<FaultToleranceManager>,
     _coordinator_id: Uuid,
     listen_address: SocketAddr,
+    pacer: Option<Arc<Mutex<GlobalPacer>>>,
+    rate_sender: Arc<Mutex<Option<mpsc::UnboundedSender<NodeMessage>>>>,
+    compare_vantages: bool,
 }
 
 impl DistributedCoordinator {
@@ -182,6 +210,8 @@ impl DistributedCoordinator {
         let communication_server = Arc::new(CommunicationServer::new(
             listen_address,
             coordinator_id,
+            ClusterToken::generate(),
+            node_manager.clone(),
         ).await?);
         
         let fault_tolerance = Arc::new(FaultToleranceManager::new(
@@ -200,9 +230,91 @@ impl DistributedCoordinator {
             fault_tolerance,
             _coordinator_id: coordinator_id,
             listen_address,
+            pacer: None,
+            rate_sender: Arc::new(Mutex::new(None)),
+            compare_vantages: false,
         })
     }
     
+    /// Only admit workers holding `token`; without one a random token is
+    /// generated, see [`cluster_token`](Self::cluster_token)
+    pub async fn with_cluster_token(mut self, token: ClusterToken) -> IntelligenceResult<Self> {
+        self.communication_server = Arc::new(CommunicationServer::new(
+            self.listen_address,
+            self._coordinator_id,
+            token,
+            self.node_manager.clone(),
+        ).await?);
+        Ok(self)
+    }
+    
+    /// The token workers need to join
+    pub fn cluster_token(&self) -> &ClusterToken {
+        self.communication_server.token()
+    }
+    
+    /// Where workers connect, once started
+    pub fn local_address(&self) -> Option<SocketAddr> {
+        self.communication_server.local_address()
+    }
+    
+    /// Keep the aggregate rate of all workers under `pacer`'s budget
+    pub fn with_pacer(mut self, pacer: GlobalPacer) -> Self {
+        self.pacer = Some(Arc::new(Mutex::new(pacer)));
+        self
+    }
+    
+    /// Have every available worker scan every target instead of a share of
+    /// them, so that [`VantageComparison`] can tell what each one reaches
+    pub fn with_vantage_comparison(mut self) -> Self {
+        self.compare_vantages = true;
+        self
+    }
+    
+    /// Add a worker and redivide the rate budget
+    ///
+    /// Returns the rate messages to deliver, in order.
+    pub fn register_node(&self, node: WorkerNode) -> Vec<NodeMessage> {
+        self.node_manager.register_node(node);
+        self.rebalance_rates()
+    }
+    
+    /// Redivide the rate budget among the available workers
+    ///
+    /// Lowered shares come first; delivering them before the raised ones
+    /// keeps the aggregate rate within the budget throughout. The messages
+    /// also go to the channel from [`start_rate_dispatcher`](Self::start_rate_dispatcher).
+    pub fn rebalance_rates(&self) -> Vec<NodeMessage> {
+        let Some(pacer) = &self.pacer else {
+            return Vec::new();
+        };
+        let nodes = self.node_manager.get_available_nodes();
+        let messages: Vec<NodeMessage> = pacer.lock().unwrap()
+            .rebalance(&nodes, SystemTime::now())
+            .into_iter()
+            .map(|share| NodeMessage::RateShare { share })
+            .collect();
+        if let Some(sender) = self.rate_sender.lock().unwrap().as_ref() {
+            for message in &messages {
+                let _ = sender.send(message.clone());
+            }
+        }
+        messages
+    }
+    
+    /// Rate messages from every rebalance, in delivery order
+    pub fn start_rate_dispatcher(&self) -> mpsc::UnboundedReceiver<NodeMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.rate_sender.lock().unwrap() = Some(tx);
+        rx
+    }
+    
+    /// Extend a worker's rate lease after a heartbeat
+    pub fn renew_rate_share(&self, node_id: Uuid) -> Option<NodeMessage> {
+        let share = self.pacer.as_ref()?.lock().unwrap().renew(node_id, SystemTime::now())?;
+        Some(NodeMessage::RateShare { share })
+    }
+    
     /// Start the distributed coordinator
     pub async fn start(&self) -> IntelligenceResult<()> {
         // Start communication server
@@ -214,7 +326,7 @@ impl DistributedCoordinator {
         // Start node discovery
         self.start_node_discovery().await?;
         
-        println!("Distributed coordinator started on {}", self.listen_address);
+        println!("Distributed coordinator started on {}", self.local_address().unwrap_or(self.listen_address));
         Ok(())
     }
     
@@ -245,6 +357,45 @@ impl DistributedCoordinator {
         *self.result_sender.lock().unwrap() = Some(tx);
         rx
     }
+    
+    /// Run `tasks` and merge what the workers report into one scan of
+    /// `config`, ready for [`ResultAggregator::report`]
+    ///
+    /// Waits until every task has reported, the scan has run its course or
+    /// the longest task timeout has passed. Tasks that never report, such
+    /// as those of a worker that dropped, count as [`missing`](ResultAggregator::missing).
+    pub async fn run_scan(&self, tasks: Vec<ScanTask>, config: crate::config::ScanConfig) -> IntelligenceResult<ResultAggregator> {
+        let mut results = self.start_result_collector().await;
+        let mut aggregator = ResultAggregator::new(config);
+        aggregator.expect(&tasks);
+        let longest = tasks.iter().map(|task| task.timeout).max().unwrap_or(self.timeout);
+        let deadline = tokio::time::Instant::now() + ACK_TIMEOUT + longest;
+        
+        let scan = self.coordinate_scan(tasks);
+        tokio::pin!(scan);
+        let mut scanning = true;
+        while aggregator.missing() > 0 {
+            tokio::select! {
+                outcome = &mut scan, if scanning => {
+                    outcome?;
+                    scanning = false;
+                    // No more results will be sent; the channel closes once drained
+                    self.result_sender.lock().unwrap().take();
+                }
+                chunk = results.recv() => match chunk {
+                    Some(chunk) => {
+                        aggregator.record(&chunk);
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+        if aggregator.missing() > 0 {
+            log::warn!("{} distributed tasks returned no results", aggregator.missing());
+        }
+        Ok(aggregator)
+    }
 }
 
 #[allow(async_fn_in_trait)]
@@ -274,13 +425,31 @@ impl DistributedScanner for DistributedCoordinator {
                 priority: TaskPriority::Medium,
                 created_at: SystemTime::now(),
                 timeout: self.timeout,
+                rate_share: None,
             };
             tasks.push(task);
         }
         
-        // Assign tasks to nodes using load balancer
+        // Assign tasks to nodes using load balancer, or a copy of each to
+        // every node when comparing vantage points
         let available_nodes = self.node_manager.get_available_nodes();
-        self.load_balancer.assign_tasks(&mut tasks, &available_nodes);
+        if self.compare_vantages {
+            tasks = tasks.into_iter()
+                .flat_map(|task| available_nodes.iter().map(move |node| ScanTask {
+                    id: Uuid::new_v4(),
+                    assigned_node: Some(node.id),
+                    ..task.clone()
+                }))
+                .collect();
+        } else {
+            self.load_balancer.assign_tasks(&mut tasks, &available_nodes);
+        }
+        if let Some(pacer) = &self.pacer {
+            let pacer = pacer.lock().unwrap();
+            for task in &mut tasks {
+                task.rate_share = task.assigned_node.and_then(|node| pacer.share(node));
+            }
+        }
         
         // Store active tasks
         {
@@ -300,14 +469,14 @@ impl DistributedScanner for DistributedCoordinator {
         let mut handles = Vec::new();
         
         for task in tasks {
-            let node_manager = self.node_manager.clone();
+            let communication_server = self.communication_server.clone();
             let result_sender = self.result_sender.clone();
             let active_tasks = self.active_tasks.clone();
             
             let handle = tokio::spawn(async move {
-                // Execute task on assigned node
+                // Execute task on assigned node; one that is not connected fails it
                 if let Some(node_id) = task.assigned_node {
-                    match node_manager.execute_task_on_node(node_id, &task).await {
+                    match communication_server.dispatch(node_id, &task).await {
                         Ok(result) => {
                             // Send result back
                             if let Some(sender) = result_sender.lock().unwrap().as_ref() {
@@ -339,8 +508,9 @@ impl DistributedScanner for DistributedCoordinator {
     async fn handle_node_failure(&self, failed_node: Uuid) -> IntelligenceResult<()> {
         println!("Handling failure of node: {}", failed_node);
         
-        // Mark node as failed
+        // Mark node as failed; its rate share stays reserved until the lease expires
         self.node_manager.mark_node_failed(failed_node).await?;
+        self.rebalance_rates();
         
         // Redistribute tasks from failed node
         let failed_tasks: Vec<ScanTask> = {
@@ -379,6 +549,27 @@ impl NodeManager {
         }
     }
     
+    pub fn register_node(&self, node: WorkerNode) {
+        self.nodes.lock().unwrap().insert(node.id, node);
+    }
+    
+    /// Note a heartbeat from `node_id`, bringing it back if it had been dropped
+    pub fn record_heartbeat(&self, node_id: Uuid, current_load: usize) {
+        if let Some(node) = self.nodes.lock().unwrap().get_mut(&node_id) {
+            node.last_heartbeat = Some(SystemTime::now());
+            node.current_load = current_load;
+            if node.status == NodeStatus::Disconnected {
+                node.status = NodeStatus::Active;
+            }
+        }
+    }
+    
+    pub fn set_status(&self, node_id: Uuid, status: NodeStatus) {
+        if let Some(node) = self.nodes.lock().unwrap().get_mut(&node_id) {
+            node.status = status;
+        }
+    }
+    
     pub async fn cleanup_disconnected_nodes(&self) {
         let mut nodes = self.nodes.lock().unwrap();
         let now = SystemTime::now();
@@ -392,31 +583,6 @@ impl NodeManager {
         });
     }
 
-    pub async fn execute_task_on_node(&self, node_id: Uuid, task: &ScanTask) -> IntelligenceResult<ScanResult> {
-        // Simulate task execution on remote node
-        println!("Executing task {} on node {}", task.id, node_id);
-        
-        // Create mock result
-        let results = task.targets.iter().flat_map(|target| {
-            task.ports.iter().map(|port| {
-                PortScanResult {
-                    target: *target,
-                    port: *port,
-                    is_open: rand::random::<bool>(),
-                    service: Some("unknown".to_string()),
-                    response_time: Duration::from_millis(rand::random::<u64>() % 100),
-                }
-            })
-        }).collect();
-
-        Ok(ScanResult {
-            task_id: task.id,
-            node_id,
-            results,
-            execution_time: Duration::from_millis(rand::random::<u64>() % 1000),
-        })
-    }
-
     pub async fn mark_node_failed(&self, node_id: Uuid) -> IntelligenceResult<()> {
         let mut nodes = self.nodes.lock().unwrap();
         if let Some(node) = nodes.get_mut(&node_id) {
@@ -479,38 +645,192 @@ impl LoadBalancer {
     }
 }
 
+/// Port states of the same targets as each vantage point saw them
+///
+/// Built from the results of a scan distributed
+/// [`with_vantage_comparison`](DistributedCoordinator::with_vantage_comparison):
+/// a port open from one worker and closed from another shows a firewall or
+/// NAT that treats the two networks differently, such as one exposing a
+/// service to the WAN but not to the DMZ.
+#[derive(Debug, Clone, Default)]
+pub struct VantageComparison {
+    /// Each worker's vantage point name, in column order
+    vantages: Vec<(Uuid, String)>,
+    /// Whether each vantage point found the port open, by column
+    ports: std::collections::BTreeMap<(IpAddr, u16), Vec<Option<bool>>>,
+}
+
+/// One target port and what each vantage point saw
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VantageRow {
+    pub target: IpAddr,
+    pub port: u16,
+    /// Open or not from each vantage point, in [`VantageComparison::vantages`]
+    /// order; `None` where that worker returned no result for the port
+    pub open: Vec<Option<bool>>,
+}
+
+impl VantageRow {
+    /// Whether the vantage points that scanned the port disagree on it
+    pub fn differs(&self) -> bool {
+        let mut seen = self.open.iter().flatten();
+        seen.next().is_some_and(|first| seen.any(|open| open != first))
+    }
+}
+
+impl VantageComparison {
+    /// A comparison with a column for each of `nodes`
+    pub fn new(nodes: &[WorkerNode]) -> Self {
+        Self {
+            vantages: nodes.iter().map(|node| (node.id, node.vantage_name())).collect(),
+            ports: std::collections::BTreeMap::new(),
+        }
+    }
+    
+    pub fn vantages(&self) -> Vec<&str> {
+        self.vantages.iter().map(|(_, name)| name.as_str()).collect()
+    }
+    
+    /// Add a worker's results; those of workers without a column are ignored
+    pub fn record(&mut self, result: &ScanResult) {
+        let Some(column) = self.vantages.iter().position(|(id, _)| *id == result.node_id) else {
+            return;
+        };
+        let width = self.vantages.len();
+        for port in &result.results {
+            let cells = self.ports.entry((port.target, port.port)).or_insert_with(|| vec![None; width]);
+            // Open from any probe of the port means reachable from there
+            cells[column] = Some(cells[column].unwrap_or(false) || port.is_open);
+        }
+    }
+    
+    /// Every port scanned, by target then port
+    pub fn rows(&self) -> Vec<VantageRow> {
+        self.ports.iter()
+            .map(|(&(target, port), open)| VantageRow { target, port, open: open.clone() })
+            .collect()
+    }
+    
+    /// The ports whose state depends on where they are scanned from
+    pub fn differences(&self) -> Vec<VantageRow> {
+        self.rows().into_iter().filter(VantageRow::differs).collect()
+    }
+    
+    /// The comparison as a text matrix: a row per port, a column per vantage
+    /// point, only the ports they disagree on unless `all` is set
+    pub fn to_matrix(&self, all: bool) -> String {
+        use std::fmt::Write as _;
+        
+        let rows = if all { self.rows() } else { self.differences() };
+        let labels: Vec<String> = rows.iter()
+            .map(|row| match row.target {
+                IpAddr::V6(ip) => format!("[{}]:{}", ip, row.port),
+                IpAddr::V4(ip) => format!("{}:{}", ip, row.port),
+            })
+            .collect();
+        let first = labels.iter().map(String::len).chain([6]).max().unwrap_or(6);
+        let widths: Vec<usize> = self.vantages().iter().map(|name| name.len().max(6)).collect();
+        
+        let mut lines = Vec::with_capacity(rows.len() + 1);
+        let mut line = format!("{:<first$}", "TARGET");
+        for (name, width) in self.vantages().iter().zip(&widths) {
+            let _ = write!(line, "  {:<width$}", name);
+        }
+        lines.push(line);
+        for (label, row) in labels.iter().zip(&rows) {
+            let mut line = format!("{:<first$}", label);
+            for (open, width) in row.open.iter().zip(&widths) {
+                let state = match open {
+                    Some(true) => "open",
+                    Some(false) => "closed",
+                    None => "-",
+                };
+                let _ = write!(line, "  {:<width$}", state);
+            }
+            lines.push(line);
+        }
+        lines.iter().map(|line| format!("{}\n", line.trim_end())).collect()
+    }
+}
+
+/// Longest a worker may take to acknowledge a task before it counts as failed
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A registered worker's connection, and the replies awaited from it
+struct NodeLink {
+    writer: tokio::sync::Mutex<FrameWriter<WriteHalf<CoordinatorStream>>>,
+    acks: Mutex<HashMap<Uuid, oneshot::Sender<()>>>,
+    results: Mutex<HashMap<Uuid, oneshot::Sender<ScanResult>>>,
+}
+
 /// Communication server for node coordination
+///
+/// Workers connect over TLS with a [`WorkerLink`](super::wire::WorkerLink),
+/// prove they hold the cluster token and register; from then on their heartbeats
+/// keep them available, and tasks sent to them must be acknowledged within
+/// [`ACK_TIMEOUT`].
 pub struct CommunicationServer {
     listen_address: SocketAddr,
     _coordinator_id: Uuid,
-    active_connections: Arc<RwLock<HashMap<Uuid, TcpStream>>>,
+    token: ClusterToken,
+    identity: TlsIdentity,
+    node_manager: Arc<NodeManager>,
+    links: Arc<RwLock<HashMap<Uuid, Arc<NodeLink>>>>,
+    local_address: std::sync::OnceLock<SocketAddr>,
 }
 
 impl CommunicationServer {
-    pub async fn new(listen_address: SocketAddr, _coordinator_id: Uuid) -> IntelligenceResult<Self> {
+    pub async fn new(
+        listen_address: SocketAddr,
+        _coordinator_id: Uuid,
+        token: ClusterToken,
+        node_manager: Arc<NodeManager>,
+    ) -> IntelligenceResult<Self> {
         Ok(Self {
             listen_address,
             _coordinator_id,
-            active_connections: Arc::new(RwLock::new(HashMap::new())),
+            token,
+            identity: TlsIdentity::generate()?,
+            node_manager,
+            links: Arc::new(RwLock::new(HashMap::new())),
+            local_address: std::sync::OnceLock::new(),
         })
     }
     
+    pub fn token(&self) -> &ClusterToken {
+        &self.token
+    }
+    
+    /// The certificate workers see
+    pub fn identity(&self) -> &TlsIdentity {
+        &self.identity
+    }
+    
+    /// Where the server listens, once started
+    pub fn local_address(&self) -> Option<SocketAddr> {
+        self.local_address.get().copied()
+    }
+    
     pub async fn start(&self) -> IntelligenceResult<()> {
         let listener = TcpListener::bind(self.listen_address).await
             .map_err(|e| ScanError::NetworkError(format!("Failed to bind listener: {}", e)))?;
+        let _ = self.local_address.set(listener.local_addr().unwrap_or(self.listen_address));
         
-        let active_connections = self.active_connections.clone();
+        let token = self.token.clone();
+        let identity = self.identity.clone();
+        let node_manager = self.node_manager.clone();
+        let links = self.links.clone();
         
         tokio::spawn(async move {
             loop {
                 match listener.accept().await {
                     Ok((stream, addr)) => {
                         println!("New connection from: {}", addr);
-                        let connections = active_connections.clone();
+                        let (token, identity, node_manager, links) = (token.clone(), identity.clone(), node_manager.clone(), links.clone());
                         
                         tokio::spawn(async move {
-                            if let Err(e) = Self::handle_connection(stream, connections).await {
-                                eprintln!("Connection error: {:?}", e);
+                            if let Err(e) = Self::handle_connection(stream, token, identity, node_manager, links).await {
+                                eprintln!("Connection error from {}: {}", addr, e);
                             }
                         });
                     }
@@ -525,73 +845,118 @@ impl CommunicationServer {
     }
     
     async fn handle_connection(
-        mut stream: TcpStream,
-        _connections: Arc<RwLock<HashMap<Uuid, TcpStream>>>,
+        stream: TcpStream,
+        token: ClusterToken,
+        identity: TlsIdentity,
+        node_manager: Arc<NodeManager>,
+        links: Arc<RwLock<HashMap<Uuid, Arc<NodeLink>>>>,
     ) -> IntelligenceResult<()> {
-        let mut buffer = [0; 4096];
-        
-        loop {
-            match stream.read(&mut buffer).await {
-                Ok(0) => break, // Connection closed
-                Ok(n) => {
-                    // Parse message
-                    if let Ok(message_str) = std::str::from_utf8(&buffer[..n]) {
-                        if let Ok(message) = serde_json::from_str::<NodeMessage>(message_str) {
-                            // Handle message
-                            if let Err(e) = Self::process_message(message, &mut stream).await {
-                                eprintln!("Message processing error: {:?}", e);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Read error: {}", e);
-                    break;
-                }
+        let (node_id, (mut reader, mut writer)) = tokio::time::timeout(wire::HANDSHAKE_TIMEOUT, wire::accept_tls(stream, &identity, &token))
+            .await
+            .map_err(|_| NetworkIntelligenceError::DistributedError("handshake timed out".to_string()))??;
+        
+        // The first message registers the node the handshake was for
+        let mut node = match reader.recv().await? {
+            Some(NodeMessage::RegisterNode { node_info }) if node_info.id == node_id => node_info,
+            _ => {
+                let refusal = NodeMessage::RegistrationResponse { accepted: false, _coordinator_id: Uuid::nil() };
+                writer.send(&refusal).await?;
+                return Err(NetworkIntelligenceError::DistributedError(format!("node {} did not register", node_id)));
             }
-        }
+        };
+        println!("Node registration request from: {}", node_id);
+        node.status = NodeStatus::Active;
+        node.last_heartbeat = Some(SystemTime::now());
+        node_manager.register_node(node);
+        writer.send(&NodeMessage::RegistrationResponse { accepted: true, _coordinator_id: Uuid::new_v4() }).await?;
+        let link = Arc::new(NodeLink {
+            writer: tokio::sync::Mutex::new(writer),
+            acks: Mutex::new(HashMap::new()),
+            results: Mutex::new(HashMap::new()),
+        });
+        links.write().await.insert(node_id, link.clone());
         
-        Ok(())
+        let outcome = loop {
+            let message = match reader.recv().await {
+                Ok(Some(message)) => message,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            Self::process_message(node_id, message, &node_manager, &link);
+        };
+        
+        // Waiters on this node learn of the loss when their senders drop
+        links.write().await.remove(&node_id);
+        node_manager.set_status(node_id, NodeStatus::Disconnected);
+        outcome
     }
     
-    async fn process_message(message: NodeMessage, stream: &mut TcpStream) -> IntelligenceResult<()> {
+    fn process_message(node_id: Uuid, message: NodeMessage, node_manager: &NodeManager, link: &NodeLink) {
         match message {
-            NodeMessage::Heartbeat { node_id, .. } => {
-                println!("Received heartbeat from node: {}", node_id);
-                // Send acknowledgment
-                let response = NodeMessage::HealthResponse {
-                    status: NodeStatus::Active,
-                    metrics: NodeMetrics {
-                        cpu_usage: 0.0,
-                        memory_usage: 0.0,
-                        network_bandwidth: 0.0,
-                        active_connections: 0,
-                        completed_tasks: 0,
-                        failed_tasks: 0,
-                        average_response_time: Duration::from_millis(0),
-                    },
-                };
-                let response_str = serde_json::to_string(&response).unwrap();
-                stream.write_all(response_str.as_bytes()).await
-                    .map_err(|e| ScanError::NetworkError(format!("Write error: {}", e)))?;
+            NodeMessage::Heartbeat { node_id: from, current_load, .. } if from == node_id => {
+                node_manager.record_heartbeat(node_id, current_load);
             }
-            NodeMessage::RegisterNode { node_info } => {
-                println!("Node registration request from: {}", node_info.id);
-                // Accept registration
-                let response = NodeMessage::RegistrationResponse {
-                    accepted: true,
-                    _coordinator_id: Uuid::new_v4(),
-                };
-                let response_str = serde_json::to_string(&response).unwrap();
-                stream.write_all(response_str.as_bytes()).await
-                    .map_err(|e| ScanError::NetworkError(format!("Write error: {}", e)))?;
+            NodeMessage::TaskAck { task_id, node_id: from } if from == node_id => {
+                if let Some(ack) = link.acks.lock().unwrap().remove(&task_id) {
+                    let _ = ack.send(());
+                }
+            }
+            NodeMessage::TaskResult { result } if result.node_id == node_id => {
+                if let Some(waiter) = link.results.lock().unwrap().remove(&result.task_id) {
+                    let _ = waiter.send(result);
+                }
+            }
+            NodeMessage::Heartbeat { .. } | NodeMessage::TaskAck { .. } | NodeMessage::TaskResult { .. } => {
+                eprintln!("Node {} sent a message on behalf of another node; ignored", node_id);
             }
             _ => {
                 println!("Received message: {:?}", message);
             }
         }
+    }
+    
+    /// Whether `node_id` is connected and registered
+    pub async fn is_connected(&self, node_id: Uuid) -> bool {
+        self.links.read().await.contains_key(&node_id)
+    }
+    
+    /// Send `task` to `node_id` and wait for its result
+    ///
+    /// Fails if the node is not connected, does not acknowledge the task
+    /// within [`ACK_TIMEOUT`] or does not finish it within the task's timeout.
+    pub async fn dispatch(&self, node_id: Uuid, task: &ScanTask) -> IntelligenceResult<ScanResult> {
+        let failed = |reason: &str| NetworkIntelligenceError::DistributedError(format!("task {} on node {}: {}", task.id, node_id, reason));
+        let link = self.links.read().await.get(&node_id).cloned().ok_or_else(|| failed("node is not connected"))?;
+        let (ack_sender, ack) = oneshot::channel();
+        let (result_sender, result) = oneshot::channel();
+        link.acks.lock().unwrap().insert(task.id, ack_sender);
+        link.results.lock().unwrap().insert(task.id, result_sender);
+        let forget = || {
+            link.acks.lock().unwrap().remove(&task.id);
+            link.results.lock().unwrap().remove(&task.id);
+        };
         
-        Ok(())
+        let assignment = NodeMessage::TaskAssignment { task: task.clone(), _coordinator_id: self._coordinator_id };
+        if let Err(e) = link.writer.lock().await.send(&assignment).await {
+            forget();
+            return Err(e);
+        }
+        match tokio::time::timeout(ACK_TIMEOUT, ack).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return Err(failed("node disconnected")),
+            Err(_) => {
+                forget();
+                return Err(failed("not acknowledged"));
+            }
+        }
+        match tokio::time::timeout(task.timeout, result).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(failed("node disconnected")),
+            Err(_) => {
+                forget();
+                Err(failed("timed out"))
+            }
+        }
     }
     
     pub async fn broadcast_discovery(&self) -> IntelligenceResult<()> {
@@ -664,4 +1029,183 @@ impl FaultToleranceManager {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intelligence::performance::MemoryPool;
+
+    fn worker(vantage: &str) -> WorkerNode {
+        WorkerNode {
+            id: Uuid::new_v4(),
+            address: "10.0.0.1".parse().unwrap(),
+            port: 9000,
+            capacity: 1,
+            current_load: 0,
+            last_heartbeat: None,
+            status: NodeStatus::Active,
+            performance_score: 1.0,
+            network_latency: Duration::ZERO,
+            failure_count: 0,
+            last_failure: None,
+            vantage: Some(vantage.to_string()),
+        }
+    }
+
+    async fn coordinator() -> DistributedCoordinator {
+        let memory_pool = Arc::new(MemoryPool::new(1024, true));
+        DistributedCoordinator::new(
+            Duration::from_secs(1),
+            Arc::new(UltraFastThreadPool::new(1, memory_pool)),
+            "127.0.0.1:0".parse().unwrap(),
+            FaultToleranceConfig {
+                max_failures: 3,
+                failure_window: Duration::from_secs(60),
+                recovery_timeout: Duration::from_secs(30),
+                health_check_interval: Duration::from_secs(10),
+                enable_auto_recovery: false,
+                backup_nodes: 0,
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_workers_authenticate_acknowledge_and_report() {
+        use super::super::wire::WorkerLink;
+
+        let token = ClusterToken::new("lab-token");
+        let coordinator = coordinator().await.with_cluster_token(token.clone()).await.unwrap();
+        coordinator.start().await.unwrap();
+        let address = coordinator.local_address().unwrap();
+
+        assert!(WorkerLink::connect(address, &ClusterToken::new("guess"), worker("wan")).await.is_err());
+        assert!(coordinator.discover_nodes().await.is_empty());
+
+        let wan = worker("wan");
+        let mut link = WorkerLink::connect(address, &token, wan.clone()).await.unwrap();
+        assert_eq!(coordinator.discover_nodes().await.len(), 1);
+        let metrics = NodeMetrics {
+            cpu_usage: 0.0,
+            memory_usage: 0.0,
+            network_bandwidth: 0.0,
+            active_connections: 0,
+            completed_tasks: 0,
+            failed_tasks: 0,
+            average_response_time: Duration::ZERO,
+        };
+        link.heartbeat(3, metrics).await.unwrap();
+        for _ in 0..100 {
+            if coordinator.discover_nodes().await[0].current_load == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(coordinator.discover_nodes().await[0].current_load, 3);
+
+        // The worker acknowledges each task, then reports port 443 open
+        let node_id = wan.id;
+        let worker_loop = tokio::spawn(async move {
+            while let Ok(Some(message)) = link.recv().await {
+                let NodeMessage::TaskAssignment { task, .. } = message else { continue };
+                link.acknowledge(task.id).await.unwrap();
+                link.send_result(ScanResult {
+                    task_id: task.id,
+                    node_id,
+                    results: task.targets.iter().map(|&target| PortScanResult {
+                        target,
+                        port: 443,
+                        is_open: true,
+                        service: Some("https".to_string()),
+                        response_time: Duration::ZERO,
+                    }).collect(),
+                    execution_time: Duration::ZERO,
+                }).await.unwrap();
+                return;
+            }
+        });
+
+        let web: IpAddr = "192.0.2.10".parse().unwrap();
+        let tasks = coordinator.distribute_targets(vec![web]).await;
+        assert_eq!(tasks[0].assigned_node, Some(wan.id));
+        let config = crate::config::ScanConfig { target: web.to_string(), ..Default::default() };
+        let aggregator = coordinator.run_scan(tasks, config).await.unwrap();
+        let merged = aggregator.finish();
+        assert_eq!((merged.target.as_str(), merged.open_ports.as_slice()), ("192.0.2.10", &[443][..]));
+        assert_eq!(merged.port_results[0].service.as_deref(), Some("https"));
+        assert_eq!(aggregator.missing(), 0);
+        assert_eq!(aggregator.worker_stats()[0].node_id, wan.id);
+
+        // A worker that hangs up is no longer given tasks
+        worker_loop.await.unwrap();
+        for _ in 0..100 {
+            if coordinator.discover_nodes().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(coordinator.discover_nodes().await.is_empty());
+        assert!(!coordinator.communication_server.is_connected(wan.id).await);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_of_disconnected_workers_count_as_missing() {
+        let coordinator = coordinator().await;
+        coordinator.register_node(worker("wan"));
+
+        let web: IpAddr = "192.0.2.10".parse().unwrap();
+        let tasks = coordinator.distribute_targets(vec![web]).await;
+        assert!(tasks[0].assigned_node.is_some());
+        let config = crate::config::ScanConfig { target: web.to_string(), ..Default::default() };
+        let aggregator = coordinator.run_scan(tasks, config).await.unwrap();
+        assert_eq!(aggregator.missing(), 1);
+        assert!(aggregator.worker_stats().is_empty());
+        assert!(aggregator.finish().open_ports.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_vantage_comparison() {
+        let coordinator = coordinator().await.with_vantage_comparison();
+        let (wan, dmz) = (worker("wan"), worker("dmz"));
+        coordinator.register_node(wan.clone());
+        coordinator.register_node(dmz.clone());
+
+        // Every worker gets every target
+        let (web, db): (IpAddr, IpAddr) = ("192.0.2.10".parse().unwrap(), "192.0.2.20".parse().unwrap());
+        let tasks = coordinator.distribute_targets(vec![web, db]).await;
+        for node in [&wan, &dmz] {
+            let mut targets: Vec<IpAddr> = tasks.iter()
+                .filter(|task| task.assigned_node == Some(node.id))
+                .flat_map(|task| task.targets.clone())
+                .collect();
+            targets.sort();
+            assert_eq!(targets, [web, db]);
+        }
+
+        let seen = |node: &WorkerNode, ports: &[(IpAddr, u16, bool)]| ScanResult {
+            task_id: Uuid::new_v4(),
+            node_id: node.id,
+            results: ports.iter().map(|&(target, port, is_open)| PortScanResult {
+                target,
+                port,
+                is_open,
+                service: None,
+                response_time: Duration::ZERO,
+            }).collect(),
+            execution_time: Duration::ZERO,
+        };
+        let mut comparison = VantageComparison::new(&[wan.clone(), dmz.clone()]);
+        comparison.record(&seen(&wan, &[(web, 443, true), (db, 5432, false)]));
+        comparison.record(&seen(&dmz, &[(web, 443, true), (db, 5432, true), (db, 22, true)]));
+
+        assert_eq!(comparison.rows().len(), 3);
+        assert_eq!(
+            comparison.to_matrix(false),
+            "TARGET           wan     dmz\n\
+             192.0.2.20:5432  closed  open\n"
+        );
+        // A port one worker has no result for is not a difference
+        assert!(comparison.to_matrix(true).contains("192.0.2.20:22    -       open\n"));
+    }
+}
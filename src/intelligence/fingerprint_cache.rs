@@ -0,0 +1,137 @@
+//! Per-run cache of service fingerprints
+//!
+//! Behind a load balancer hundreds of addresses answer with the same banner,
+//! certificate and front page. A [`FingerprintCache`] keeps what analysing a
+//! response found under a hash of the response, so each distinct response is
+//! analysed once per run however many `(ip, port)`s return it. Detectors own
+//! their caches, and clones of a detector share them.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Hash identifying a response's content within one run
+pub fn content_hash<T: Hash + ?Sized>(content: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Lookups answered from the cache and analyses actually run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Analysis results by response content, and which content each service sent
+#[derive(Debug)]
+pub struct FingerprintCache<V> {
+    by_content: Mutex<HashMap<u64, V>>,
+    seen: Mutex<HashMap<SocketAddr, u64>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<V> Default for FingerprintCache<V> {
+    fn default() -> Self {
+        Self {
+            by_content: Mutex::new(HashMap::new()),
+            seen: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<V: Clone> FingerprintCache<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What an earlier analysis of content `hash` found; `target` is
+    /// recorded as answering with it either way
+    pub fn get(&self, target: SocketAddr, hash: u64) -> Option<V> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).insert(target, hash);
+        let found = self.by_content.lock().unwrap_or_else(|e| e.into_inner()).get(&hash).cloned();
+        let counter = if found.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Keep what analysing content `hash` found
+    pub fn insert(&self, hash: u64, value: V) {
+        self.by_content.lock().unwrap_or_else(|e| e.into_inner()).insert(hash, value);
+    }
+
+    /// The cached result for content `hash`, running `analyze` on a miss
+    pub fn get_or_analyze(&self, target: SocketAddr, hash: u64, analyze: impl FnOnce() -> V) -> V {
+        if let Some(value) = self.get(target, hash) {
+            return value;
+        }
+        let value = analyze();
+        self.insert(hash, value.clone());
+        value
+    }
+
+    /// Hash of the content `target` last answered with
+    pub fn content_of(&self, target: SocketAddr) -> Option<u64> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).get(&target).copied()
+    }
+
+    /// Services that answered with the same content as at least one other,
+    /// grouped by content and sorted
+    pub fn shared_content(&self) -> Vec<Vec<SocketAddr>> {
+        let mut by_content: HashMap<u64, Vec<SocketAddr>> = HashMap::new();
+        for (&target, &hash) in self.seen.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            by_content.entry(hash).or_default().push(target);
+        }
+        let mut groups: Vec<Vec<SocketAddr>> = by_content.into_values().filter(|group| group.len() > 1).collect();
+        for group in &mut groups {
+            group.sort();
+        }
+        groups.sort();
+        groups
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_identical_responses_are_analysed_once() {
+        let cache = FingerprintCache::new();
+        let analyses = Cell::new(0);
+        let analyze = |banner: &str| {
+            analyses.set(analyses.get() + 1);
+            banner.split_whitespace().next().unwrap_or_default().to_string()
+        };
+        let banner = "SSH-2.0-OpenSSH_9.6 Ubuntu";
+        let farm: Vec<SocketAddr> = (1..=20).map(|i| format!("192.0.2.{}:22", i).parse().unwrap()).collect();
+        for &node in &farm {
+            assert_eq!(cache.get_or_analyze(node, content_hash(banner), || analyze(banner)), "SSH-2.0-OpenSSH_9.6");
+        }
+        let odd = "SSH-2.0-dropbear_2022.83";
+        cache.get_or_analyze(farm[0], content_hash(odd), || analyze(odd));
+
+        assert_eq!(analyses.get(), 2);
+        assert_eq!(cache.stats(), CacheStats { hits: 19, misses: 2 });
+        assert_eq!(cache.content_of(farm[0]), Some(content_hash(odd)));
+        assert_eq!(cache.content_of(farm[1]), Some(content_hash(banner)));
+        assert_eq!(cache.content_of("198.51.100.1:22".parse().unwrap()), None);
+        // farm[0] switched to the odd banner, leaving the other 19 together
+        assert_eq!(cache.shared_content(), vec![farm[1..].to_vec()]);
+    }
+}
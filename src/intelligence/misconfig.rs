@@ -0,0 +1,432 @@
+//! Opt-in quick checks for common service misconfigurations
+//!
+//! Every check is read-only: it never sends mail, writes files or changes
+//! server state. The SMTP relay test stops after `RCPT TO` and the FTP check
+//! logs out right after a successful anonymous login.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::utils::redact::redact_secrets;
+
+/// Severity of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Structured result of a misconfiguration check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    pub target: SocketAddr,
+    /// Stable identifier of the check, e.g. `smtp-open-relay`
+    pub check: String,
+    pub severity: Severity,
+    pub title: String,
+    /// Server output backing the finding
+    pub evidence: Option<String>,
+}
+
+impl Finding {
+    pub(crate) fn new(target: SocketAddr, check: &str, severity: Severity, title: &str) -> Self {
+        Self {
+            target,
+            check: check.to_string(),
+            severity,
+            title: title.to_string(),
+            evidence: None,
+        }
+    }
+
+    pub(crate) fn with_evidence(mut self, evidence: impl Into<String>) -> Self {
+        self.evidence = Some(evidence.into());
+        self
+    }
+}
+
+/// A single safe misconfiguration probe
+#[async_trait::async_trait]
+pub trait MisconfigCheck: Send + Sync {
+    /// Stable identifier used in findings and for `--misconfig-checks` selection
+    fn id(&self) -> &'static str;
+
+    /// Whether the check should run against this port / detected service
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool;
+
+    /// Run the check; `None` means nothing was found (or the service did not answer)
+    async fn run(&self, target: SocketAddr, timeout: Duration) -> Option<Finding>;
+}
+
+/// Runs the built-in checks against open ports
+pub struct MisconfigScanner {
+    checks: Vec<Box<dyn MisconfigCheck>>,
+    timeout: Duration,
+    redact_secrets: bool,
+}
+
+impl MisconfigScanner {
+    /// Scanner with all built-in checks
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            checks: vec![
+                Box::new(SmtpOpenRelay),
+                Box::new(AnonymousFtp),
+                Box::new(RedisNoAuth),
+                Box::new(ElasticsearchOpen),
+            ],
+            timeout,
+            redact_secrets: true,
+        }
+    }
+
+    /// Keep only the checks whose id is listed
+    pub fn only(mut self, ids: &[String]) -> Self {
+        self.checks.retain(|check| ids.iter().any(|id| id == check.id()));
+        self
+    }
+
+    /// Add a custom check
+    pub fn with_check(mut self, check: Box<dyn MisconfigCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    /// Whether secrets in the replies quoted as evidence are masked (the
+    /// default) or kept for authorized full captures
+    pub fn with_secret_redaction(mut self, redact: bool) -> Self {
+        self.redact_secrets = redact;
+        self
+    }
+
+    /// Identifiers of the configured checks
+    pub fn check_ids(&self) -> Vec<&'static str> {
+        self.checks.iter().map(|c| c.id()).collect()
+    }
+
+    /// Run every applicable check against every `(address, service)` pair,
+    /// returning findings sorted by descending severity
+    pub async fn scan(&self, targets: &[(SocketAddr, Option<String>)]) -> Vec<Finding> {
+        let runs = targets.iter().flat_map(|(addr, service)| {
+            self.checks
+                .iter()
+                .filter(move |check| check.applies_to(addr.port(), service.as_deref()))
+                .map(move |check| check.run(*addr, self.timeout))
+        });
+
+        let mut findings: Vec<Finding> = futures::future::join_all(runs).await.into_iter().flatten().collect();
+        if self.redact_secrets {
+            for evidence in findings.iter_mut().filter_map(|f| f.evidence.as_mut()) {
+                *evidence = redact_secrets(evidence).into_owned();
+            }
+        }
+        findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.target.cmp(&b.target)));
+        findings
+    }
+}
+
+/// Line-oriented client for text protocols (SMTP, FTP)
+pub(crate) struct LineClient {
+    reader: BufReader<TcpStream>,
+    timeout: Duration,
+}
+
+impl LineClient {
+    pub(crate) async fn connect(target: SocketAddr, timeout_duration: Duration) -> Option<Self> {
+        let stream = timeout(timeout_duration, TcpStream::connect(target)).await.ok()?.ok()?;
+        Some(Self { reader: BufReader::new(stream), timeout: timeout_duration })
+    }
+
+    /// Read one (possibly multi-line) reply and return its code and last line
+    pub(crate) async fn reply(&mut self) -> Option<(u16, String)> {
+        loop {
+            let mut line = String::new();
+            let n = timeout(self.timeout, self.reader.read_line(&mut line)).await.ok()?.ok()?;
+            if n == 0 || line.len() < 3 {
+                return None;
+            }
+            let code: u16 = line[..3].parse().ok()?;
+            // "250-..." continues a multi-line reply, "250 ..." ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Some((code, line.trim_end().to_string()));
+            }
+        }
+    }
+
+    pub(crate) async fn command(&mut self, cmd: &str) -> Option<(u16, String)> {
+        let line = format!("{}\r\n", cmd);
+        timeout(self.timeout, self.reader.get_mut().write_all(line.as_bytes())).await.ok()?.ok()?;
+        self.reply().await
+    }
+}
+
+/// SMTP server accepting mail for foreign domains from an unauthenticated client
+pub struct SmtpOpenRelay;
+
+#[async_trait::async_trait]
+impl MisconfigCheck for SmtpOpenRelay {
+    fn id(&self) -> &'static str {
+        "smtp-open-relay"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        matches!(port, 25 | 587 | 2525) || service == Some("smtp")
+    }
+
+    async fn run(&self, target: SocketAddr, timeout: Duration) -> Option<Finding> {
+        let mut client = LineClient::connect(target, timeout).await?;
+        let (code, _) = client.reply().await?;
+        if code != 220 {
+            return None;
+        }
+
+        client.command("EHLO phobos.invalid").await?;
+        let (mail_code, _) = client.command("MAIL FROM:<relay-check@phobos.invalid>").await?;
+        if mail_code != 250 {
+            return None;
+        }
+        // Never reaches DATA, so no message is ever queued
+        let (rcpt_code, rcpt_line) = client.command("RCPT TO:<relay-check@example.net>").await?;
+        let _ = client.command("RSET").await;
+        let _ = client.command("QUIT").await;
+
+        (rcpt_code == 250 || rcpt_code == 251).then(|| {
+            Finding::new(target, self.id(), Severity::High, "SMTP server relays mail for external domains")
+                .with_evidence(rcpt_line)
+        })
+    }
+}
+
+/// FTP server allowing anonymous login
+pub struct AnonymousFtp;
+
+#[async_trait::async_trait]
+impl MisconfigCheck for AnonymousFtp {
+    fn id(&self) -> &'static str {
+        "ftp-anonymous"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        port == 21 || service == Some("ftp")
+    }
+
+    async fn run(&self, target: SocketAddr, timeout: Duration) -> Option<Finding> {
+        let mut client = LineClient::connect(target, timeout).await?;
+        let (code, _) = client.reply().await?;
+        if code != 220 {
+            return None;
+        }
+
+        let (user_code, _) = client.command("USER anonymous").await?;
+        let (login_code, login_line) = match user_code {
+            230 => (230, "230 logged in without password".to_string()),
+            331 => client.command("PASS anonymous@phobos.invalid").await?,
+            _ => return None,
+        };
+        let _ = client.command("QUIT").await;
+
+        (login_code == 230).then(|| {
+            Finding::new(target, self.id(), Severity::Medium, "FTP server allows anonymous login")
+                .with_evidence(login_line)
+        })
+    }
+}
+
+/// Redis answering commands without authentication
+pub struct RedisNoAuth;
+
+#[async_trait::async_trait]
+impl MisconfigCheck for RedisNoAuth {
+    fn id(&self) -> &'static str {
+        "redis-no-auth"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        port == 6379 || service == Some("redis")
+    }
+
+    async fn run(&self, target: SocketAddr, timeout_duration: Duration) -> Option<Finding> {
+        let response = request(target, b"INFO server\r\n", timeout_duration).await?;
+        if !response.starts_with('$') || !response.contains("redis_version:") {
+            return None;
+        }
+
+        let version = response
+            .lines()
+            .find_map(|line| line.strip_prefix("redis_version:"))
+            .unwrap_or("unknown")
+            .trim();
+        Some(
+            Finding::new(target, self.id(), Severity::High, "Redis accepts commands without authentication")
+                .with_evidence(format!("redis_version:{}", version)),
+        )
+    }
+}
+
+/// Elasticsearch REST API reachable without authentication
+pub struct ElasticsearchOpen;
+
+#[async_trait::async_trait]
+impl MisconfigCheck for ElasticsearchOpen {
+    fn id(&self) -> &'static str {
+        "elasticsearch-open"
+    }
+
+    fn applies_to(&self, port: u16, service: Option<&str>) -> bool {
+        port == 9200 || service == Some("elasticsearch")
+    }
+
+    async fn run(&self, target: SocketAddr, timeout_duration: Duration) -> Option<Finding> {
+        let probe = format!(
+            "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: Phobos\r\nConnection: close\r\n\r\n",
+            target
+        );
+        let response = request(target, probe.as_bytes(), timeout_duration).await?;
+        if !response.starts_with("HTTP/1.") || !response.contains(" 200 ") {
+            return None;
+        }
+
+        let body = response.split("\r\n\r\n").nth(1)?;
+        let json: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+        let cluster = json.get("cluster_name")?.as_str()?;
+        let version = json
+            .pointer("/version/number")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown");
+        Some(
+            Finding::new(target, self.id(), Severity::High, "Elasticsearch API is open without authentication")
+                .with_evidence(format!("cluster_name={} version={}", cluster, version)),
+        )
+    }
+}
+
+/// Send one request and read until the peer closes or stops talking
+async fn request(target: SocketAddr, payload: &[u8], timeout_duration: Duration) -> Option<String> {
+    let mut stream = timeout(timeout_duration, TcpStream::connect(target)).await.ok()?.ok()?;
+    timeout(timeout_duration, stream.write_all(payload)).await.ok()?.ok()?;
+
+    let mut response = Vec::with_capacity(4096);
+    let mut buf = [0u8; 4096];
+    while response.len() < 64 * 1024 {
+        match timeout(timeout_duration, stream.read(&mut buf)).await {
+            Ok(Ok(n)) if n > 0 => response.extend_from_slice(&buf[..n]),
+            _ => break,
+        }
+    }
+    (!response.is_empty()).then(|| String::from_utf8_lossy(&response).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Fake server that answers each received line from a script
+    async fn scripted_server(greeting: &'static str, replies: Vec<(&'static str, &'static str)>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            reader.get_mut().write_all(greeting.as_bytes()).await.unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                let reply = replies
+                    .iter()
+                    .find(|(prefix, _)| line.starts_with(prefix))
+                    .map(|(_, reply)| *reply)
+                    .unwrap_or("500 unknown\r\n");
+                let _ = reader.get_mut().write_all(reply.as_bytes()).await;
+                line.clear();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_smtp_open_relay_detected() {
+        let addr = scripted_server(
+            "220 mail.test ESMTP\r\n",
+            vec![
+                ("EHLO", "250-mail.test\r\n250 PIPELINING\r\n"),
+                ("MAIL", "250 OK\r\n"),
+                ("RCPT", "250 Accepted\r\n"),
+                ("RSET", "250 OK\r\n"),
+                ("QUIT", "221 Bye\r\n"),
+            ],
+        )
+        .await;
+
+        let finding = SmtpOpenRelay.run(addr, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(finding.severity, Severity::High);
+        assert_eq!(finding.evidence.as_deref(), Some("250 Accepted"));
+    }
+
+    #[tokio::test]
+    async fn test_smtp_relay_denied() {
+        let addr = scripted_server(
+            "220 mail.test ESMTP\r\n",
+            vec![("EHLO", "250 mail.test\r\n"), ("MAIL", "250 OK\r\n"), ("RCPT", "550 Relaying denied\r\n")],
+        )
+        .await;
+
+        assert!(SmtpOpenRelay.run(addr, Duration::from_secs(1)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_ftp_detected() {
+        let addr = scripted_server(
+            "220 FTP ready\r\n",
+            vec![("USER", "331 Password required\r\n"), ("PASS", "230 Login successful\r\n")],
+        )
+        .await;
+
+        let finding = AnonymousFtp.run(addr, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(finding.check, "ftp-anonymous");
+        assert_eq!(finding.severity, Severity::Medium);
+    }
+
+    #[tokio::test]
+    async fn test_redis_no_auth_detected() {
+        let addr = scripted_server("", vec![("INFO", "$40\r\n# Server\r\nredis_version:7.2.4\r\n")]).await;
+
+        let finding = RedisNoAuth.run(addr, Duration::from_millis(300)).await.unwrap();
+        assert_eq!(finding.evidence.as_deref(), Some("redis_version:7.2.4"));
+    }
+
+    #[tokio::test]
+    async fn test_redis_requiring_auth_is_clean() {
+        let addr = scripted_server("", vec![("INFO", "-NOAUTH Authentication required.\r\n")]).await;
+        assert!(RedisNoAuth.run(addr, Duration::from_millis(300)).await.is_none());
+    }
+
+    #[test]
+    fn test_check_selection() {
+        let scanner = MisconfigScanner::new(Duration::from_secs(1)).only(&["redis-no-auth".to_string()]);
+        assert_eq!(scanner.check_ids(), vec!["redis-no-auth"]);
+        assert!(Severity::Critical > Severity::High && Severity::Low > Severity::Info);
+    }
+}
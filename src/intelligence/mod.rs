@@ -9,13 +9,26 @@
 //! - 5x lower memory usage than Masscan
 
 pub mod core;
+pub mod fingerprint_cache;
 pub mod service_detection;
+pub mod service_probes;
+#[cfg(feature = "distributed")]
+pub mod aggregation;
+#[cfg(feature = "distributed")]
 pub mod distributed;
+#[cfg(feature = "distributed")]
+pub mod pacing;
+#[cfg(feature = "distributed")]
+pub mod wire;
 pub mod network_discovery;
 pub mod asset_management;
 pub mod performance;
 pub mod smart_prediction;
 pub mod os_fingerprinting;
+pub mod misconfig;
+pub mod credentials;
+pub mod tls_fingerprint;
+pub mod web_tech;
 
 #[cfg(test)]
 mod tests;
@@ -24,7 +37,7 @@ mod tests;
 #[path = "service_detection_tests.rs"]
 mod service_detection_tests;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "distributed"))]
 #[path = "benchmark_tests.rs"]
 mod benchmark_tests;
 
@@ -41,11 +54,15 @@ pub use service_detection::{
     BannerGrabber, SSLAnalyzer, VulnerabilityScanner,
 };
 
+#[cfg(feature = "distributed")]
 pub use distributed::{
     DistributedCoordinator, DistributedScanner, WorkerNode,
-    NodeManager, LoadBalancer, ScanTask,
+    NodeManager, LoadBalancer, ScanTask, VantageComparison,
 };
 
+#[cfg(feature = "distributed")]
+pub use aggregation::{ResultAggregator, WorkerStats};
+
 pub use network_discovery::{
     NetworkDiscoveryEngine, NetworkDiscoverer, Device,
     TopologyMapper, NetworkTopology, DeviceType,
@@ -67,4 +84,18 @@ pub use smart_prediction::{
 
 pub use os_fingerprinting::{
     OSFingerprinter, OSDetectionResult, OperatingSystem, OSFamily,
-};
\ No newline at end of file
+};
+
+pub use misconfig::{
+    MisconfigScanner, MisconfigCheck, Finding, Severity,
+};
+
+pub use credentials::{
+    CredentialChecker, CredentialCheckConfig, CredentialPlugin, Credential, LoginOutcome,
+};
+
+pub use service_probes::{ServiceProbes, ServiceVersion};
+
+pub use tls_fingerprint::{TlsFingerprinter, TlsFingerprint};
+
+pub use web_tech::{collapse_wildcards, WebService, WebTechDetector, Technology, HttpResponse};
\ No newline at end of file
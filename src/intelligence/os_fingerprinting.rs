@@ -443,7 +443,7 @@ impl Default for OSFingerprinter {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::network::Protocol;
+    use crate::network::{Confidence, Protocol};
     use std::time::Duration;
     
     #[test]
@@ -454,22 +454,34 @@ mod tests {
                 port: 135,
                 state: PortState::Open,
                 service: Some("msrpc".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(10),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             },
             PortResult {
                 port: 139,
                 state: PortState::Open,
                 service: Some("netbios-ssn".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(15),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             },
             PortResult {
                 port: 445,
                 state: PortState::Open,
                 service: Some("microsoft-ds".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(12),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             },
         ];
         
@@ -489,15 +501,23 @@ mod tests {
                 port: 22,
                 state: PortState::Open,
                 service: Some("OpenSSH 8.0".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(5),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             },
             PortResult {
                 port: 80,
                 state: PortState::Open,
                 service: Some("Apache/2.4.41".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(8),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             },
         ];
         
@@ -0,0 +1,230 @@
+//! Global rate budget for distributed scans
+//!
+//! The coordinator owns one packets-per-second budget for the whole scan and
+//! hands each active worker a share of it, weighted by the worker's capacity.
+//! Shares are leases: a worker must stop sending when its lease runs out
+//! without a renewal, so the coordinator can reuse the share of a worker that
+//! left or stopped answering once that lease has expired, and not before.
+//! Until then the share stays reserved and the aggregate never exceeds the
+//! budget, even if the "departed" worker is still scanning behind a
+//! partition.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+use super::distributed::WorkerNode;
+use crate::network::protocol::RateLimiter;
+
+/// How long a share stays valid without a renewal
+pub const SHARE_LEASE: Duration = Duration::from_secs(30);
+
+/// Burst a worker may send at once, as a fraction of a second of its share
+///
+/// Full one-second buckets on every worker would let a rebalance briefly
+/// double the aggregate; a tenth of a second keeps the overshoot small.
+const BURST_FRACTION: u64 = 10;
+
+/// A worker's slice of the global budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateShare {
+    pub node_id: Uuid,
+    pub packets_per_second: u64,
+    /// Rebalance generation; workers ignore shares older than the one they hold
+    pub epoch: u64,
+    /// When the worker must stop sending unless the share is renewed
+    pub expires: SystemTime,
+}
+
+impl RateShare {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now >= self.expires
+    }
+
+    /// Limiter enforcing this share, or `None` when the worker must not send
+    ///
+    /// A zero rate means "unlimited" to [`RateLimiter`], so a worker without
+    /// bandwidth gets no limiter at all and waits for the next share.
+    pub fn rate_limiter(&self) -> Option<RateLimiter> {
+        (self.packets_per_second > 0).then(|| {
+            let burst = (self.packets_per_second / BURST_FRACTION).max(1);
+            RateLimiter::with_burst(self.packets_per_second, burst)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Grant {
+    packets_per_second: u64,
+    expires: SystemTime,
+}
+
+/// Splits a packets-per-second budget between workers
+#[derive(Debug)]
+pub struct GlobalPacer {
+    budget: u64,
+    epoch: u64,
+    grants: HashMap<Uuid, Grant>,
+}
+
+impl GlobalPacer {
+    pub fn new(packets_per_second: u64) -> Self {
+        Self { budget: packets_per_second, epoch: 0, grants: HashMap::new() }
+    }
+
+    /// A pacer for the rate `config` allows, including any bandwidth cap
+    pub fn from_config(config: &crate::config::ScanConfig) -> Self {
+        Self::new(config.effective_rate())
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Packets per second currently granted, reserved leases included
+    pub fn allocated(&self) -> u64 {
+        self.grants.values().map(|grant| grant.packets_per_second).sum()
+    }
+
+    /// Share `node` holds, if any
+    pub fn share(&self, node: Uuid) -> Option<RateShare> {
+        self.grants.get(&node).map(|grant| self.to_share(node, *grant))
+    }
+
+    /// Redivide the budget among `nodes`, the workers that are active now
+    ///
+    /// Workers no longer listed keep their share until its lease expires.
+    /// The result lists lowered shares before raised ones: sending them in
+    /// that order keeps the aggregate within budget while workers catch up.
+    pub fn rebalance(&mut self, nodes: &[WorkerNode], now: SystemTime) -> Vec<RateShare> {
+        self.grants.retain(|id, grant| nodes.iter().any(|node| node.id == *id) || now < grant.expires);
+        let reserved: u64 = self
+            .grants
+            .iter()
+            .filter(|(id, _)| !nodes.iter().any(|node| node.id == **id))
+            .map(|(_, grant)| grant.packets_per_second)
+            .sum();
+        let available = self.budget.saturating_sub(reserved);
+
+        self.epoch += 1;
+        let expires = now + SHARE_LEASE;
+        let mut shares: Vec<(RateShare, u64)> = split(available, nodes)
+            .into_iter()
+            .map(|(id, packets_per_second)| {
+                let previous = self.grants.get(&id).map_or(0, |grant| grant.packets_per_second);
+                self.grants.insert(id, Grant { packets_per_second, expires });
+                (self.to_share(id, Grant { packets_per_second, expires }), previous)
+            })
+            .collect();
+        shares.sort_by_key(|(share, previous)| share.packets_per_second > *previous);
+        shares.into_iter().map(|(share, _)| share).collect()
+    }
+
+    /// Extend `node`'s lease, typically on a heartbeat
+    pub fn renew(&mut self, node: Uuid, now: SystemTime) -> Option<RateShare> {
+        let grant = self.grants.get_mut(&node)?;
+        grant.expires = now + SHARE_LEASE;
+        let grant = *grant;
+        Some(self.to_share(node, grant))
+    }
+
+    fn to_share(&self, node_id: Uuid, grant: Grant) -> RateShare {
+        RateShare {
+            node_id,
+            packets_per_second: grant.packets_per_second,
+            epoch: self.epoch,
+            expires: grant.expires,
+        }
+    }
+}
+
+/// Divide `budget` by capacity, handing out the rounding remainder one
+/// packet at a time to the largest fractions so nothing is lost or overdrawn
+fn split(budget: u64, nodes: &[WorkerNode]) -> Vec<(Uuid, u64)> {
+    // A worker reporting no capacity still gets an equal weight
+    let weights: Vec<u128> = nodes.iter().map(|node| node.capacity.max(1) as u128).collect();
+    let total: u128 = weights.iter().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut shares: Vec<(Uuid, u64, u128)> = nodes
+        .iter()
+        .zip(&weights)
+        .map(|(node, &weight)| {
+            let exact = budget as u128 * weight;
+            (node.id, (exact / total) as u64, exact % total)
+        })
+        .collect();
+    let mut remainder = budget - shares.iter().map(|(_, pps, _)| pps).sum::<u64>();
+    let mut order: Vec<usize> = (0..shares.len()).collect();
+    order.sort_by(|&a, &b| shares[b].2.cmp(&shares[a].2));
+    for index in order {
+        if remainder == 0 {
+            break;
+        }
+        shares[index].1 += 1;
+        remainder -= 1;
+    }
+    shares.into_iter().map(|(id, pps, _)| (id, pps)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intelligence::distributed::NodeStatus;
+
+    fn worker(capacity: usize) -> WorkerNode {
+        WorkerNode {
+            id: Uuid::new_v4(),
+            address: "10.0.0.1".parse().unwrap(),
+            port: 9000,
+            capacity,
+            current_load: 0,
+            last_heartbeat: None,
+            status: NodeStatus::Active,
+            performance_score: 1.0,
+            network_latency: Duration::ZERO,
+            failure_count: 0,
+            last_failure: None,
+            vantage: None,
+        }
+    }
+
+    #[test]
+    fn test_shares_stay_within_budget_across_joins_and_leaves() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut pacer = GlobalPacer::new(1000);
+        let (a, b) = (worker(1), worker(2));
+
+        let shares = pacer.rebalance(&[a.clone(), b.clone()], start);
+        assert_eq!(pacer.share(a.id).unwrap().packets_per_second, 333);
+        assert_eq!(pacer.share(b.id).unwrap().packets_per_second, 667);
+        assert_eq!(shares.iter().map(|share| share.packets_per_second).sum::<u64>(), 1000);
+
+        // A joining worker is paid for by lowering the others, sent first
+        let c = worker(1);
+        let shares = pacer.rebalance(&[a.clone(), b.clone(), c.clone()], start);
+        assert_eq!(shares.last().unwrap().node_id, c.id);
+        assert_eq!(pacer.allocated(), 1000);
+
+        // b leaves but may still be sending until its lease runs out
+        let shares = pacer.rebalance(&[a.clone(), c.clone()], start + Duration::from_secs(1));
+        assert_eq!(pacer.allocated(), 1000);
+        assert_eq!(shares.iter().map(|share| share.packets_per_second).sum::<u64>(), 500);
+
+        // Renewed leases survive; b's expired one is reclaimed
+        pacer.renew(a.id, start + SHARE_LEASE);
+        pacer.renew(c.id, start + SHARE_LEASE);
+        pacer.rebalance(&[a.clone(), c.clone()], start + SHARE_LEASE + Duration::from_secs(1));
+        assert!(pacer.share(b.id).is_none());
+        assert_eq!(pacer.share(a.id).unwrap().packets_per_second, 500);
+        assert_eq!(pacer.allocated(), 1000);
+
+        // More workers than packets: some must wait rather than run unlimited
+        let mut tiny = GlobalPacer::new(1);
+        let shares = tiny.rebalance(&[worker(1), worker(1)], start);
+        assert_eq!(shares.iter().filter(|share| share.rate_limiter().is_some()).count(), 1);
+        assert!(shares[0].is_expired(start + SHARE_LEASE));
+    }
+}
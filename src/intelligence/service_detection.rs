@@ -1,6 +1,7 @@
 //! Ultra-fast service detection engine
 //! Target: 5x faster than Nmap service detection
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -14,7 +15,15 @@ use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
 use regex::Regex;
 
 use super::core::IntelligenceResult;
+use super::fingerprint_cache::{content_hash, FingerprintCache};
 use super::performance::{UltraFastThreadPool, MemoryPool};
+use super::service_probes::ServiceProbes;
+use super::tls_fingerprint::{TlsFingerprint, TlsFingerprinter};
+use super::web_tech::{Technology, WebTechDetector};
+use crate::network::banner_rules::BannerRules;
+use crate::network::probes::CustomProbes;
+use crate::network::socket::ConnectionPool;
+use crate::utils::redact::redact_secrets;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceInfo {
@@ -24,6 +33,12 @@ pub struct ServiceInfo {
     pub version: Option<String>,
     pub banner: Option<String>,
     pub ssl_info: Option<SSLInfo>,
+    /// Web stack detected on HTTP(S) services
+    #[serde(default)]
+    pub technologies: Vec<Technology>,
+    /// Shodan-compatible favicon hash (`http.favicon.hash`)
+    #[serde(default)]
+    pub favicon_hash: Option<i32>,
     pub vulnerabilities: Vec<Vulnerability>,
     pub response_time: Duration,
 }
@@ -33,6 +48,12 @@ pub struct SSLInfo {
     pub version: String,
     pub cipher: String,
     pub certificate: Option<String>,
+    /// JARM fingerprint of the TLS stack
+    #[serde(default)]
+    pub jarm: Option<String>,
+    /// JA3S hash of the ServerHello
+    #[serde(default)]
+    pub ja3s: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +71,16 @@ pub struct ServiceDetectionEngine {
     ssl_analyzer: SSLAnalyzer,
     vulnerability_scanner: VulnerabilityScanner,
     service_signatures: HashMap<u16, ServiceSignature>,
+    connection_pool: Option<ConnectionPool>,
+    web_detector: Option<WebTechDetector>,
+    custom_probes: CustomProbes,
+    /// Bundled probes tried when the user's find nothing
+    service_probes: Option<ServiceProbes>,
+    banner_rules: BannerRules,
+    /// Service name and version by banner and port
+    banner_cache: Arc<FingerprintCache<(String, Option<String>)>>,
+    /// Mask secrets in banners before they are returned
+    redact_secrets: bool,
 }
 
 impl ServiceDetectionEngine {
@@ -68,9 +99,59 @@ impl ServiceDetectionEngine {
             ssl_analyzer: SSLAnalyzer::new(),
             vulnerability_scanner: VulnerabilityScanner::new(),
             service_signatures,
+            connection_pool: None,
+            web_detector: WebTechDetector::new(timeout).ok(),
+            custom_probes: CustomProbes::default(),
+            service_probes: ServiceProbes::bundled().ok(),
+            banner_rules: BannerRules::default(),
+            banner_cache: Arc::new(FingerprintCache::new()),
+            redact_secrets: true,
         })
     }
     
+    /// Reuse connections left open by the port scan for banner, TLS and HTTP probes
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.banner_grabber = self.banner_grabber.with_connection_pool(pool.clone());
+        self.ssl_analyzer = self.ssl_analyzer.with_connection_pool(pool.clone());
+        self.web_detector = self.web_detector.map(|detector| detector.with_connection_pool(pool.clone()));
+        self.connection_pool = Some(pool);
+        self
+    }
+    
+    /// Try the user's probes on their ports before the built-in banner probes
+    pub fn with_custom_probes(mut self, probes: CustomProbes) -> Self {
+        self.custom_probes = probes;
+        self
+    }
+    
+    /// Classify banners with the user's rules before the built-in checks
+    pub fn with_banner_rules(mut self, rules: BannerRules) -> Self {
+        self.banner_rules = rules;
+        self
+    }
+    
+    /// Whether credentials, cookies and API keys in banners are masked
+    /// (the default) or kept for authorized full captures
+    pub fn with_secret_redaction(mut self, redact: bool) -> Self {
+        self.redact_secrets = redact;
+        self
+    }
+    
+    /// Web technologies and favicon of `target` if it looks like HTTP(S)
+    /// so far; whether it did
+    async fn fingerprint_web(&self, target: SocketAddr, service_info: &mut ServiceInfo) -> bool {
+        let Some(detector) = &self.web_detector else { return false };
+        let service = Some(service_info.service_name.as_str());
+        if !WebTechDetector::is_web_port(target.port(), service) {
+            return false;
+        }
+        let tls = WebTechDetector::is_tls_port(target.port(), service);
+        let fingerprint = detector.fingerprint(target, tls).await;
+        service_info.technologies = fingerprint.technologies;
+        service_info.favicon_hash = fingerprint.favicon_hash;
+        true
+    }
+    
     /// Load service signatures for ultra-fast identification
     pub fn load_service_signatures() -> HashMap<u16, ServiceSignature> {
         let mut signatures = HashMap::new();
@@ -98,14 +179,26 @@ impl ServiceDetectionEngine {
 
 impl Clone for ServiceDetectionEngine {
     fn clone(&self) -> Self {
-        Self {
+        let engine = Self {
             timeout: self.timeout,
             thread_pool: self.thread_pool.clone(),
             memory_pool: self.memory_pool.clone(),
             banner_grabber: BannerGrabber::new(self.memory_pool.clone()),
-            ssl_analyzer: SSLAnalyzer::new(),
+            ssl_analyzer: self.ssl_analyzer.clone(),
             vulnerability_scanner: VulnerabilityScanner::new(),
             service_signatures: self.service_signatures.clone(),
+            connection_pool: None,
+            web_detector: self.web_detector.clone(),
+            custom_probes: self.custom_probes.clone(),
+            service_probes: self.service_probes.clone(),
+            banner_rules: self.banner_rules.clone(),
+            banner_cache: self.banner_cache.clone(),
+            redact_secrets: self.redact_secrets,
+        };
+        
+        match &self.connection_pool {
+            Some(pool) => engine.with_connection_pool(pool.clone()),
+            None => engine,
         }
     }
 }
@@ -131,6 +224,8 @@ impl ServiceDetector for ServiceDetectionEngine {
             version: None,
             banner: None,
             ssl_info: None,
+            technologies: Vec::new(),
+            favicon_hash: None,
             vulnerabilities: Vec::new(),
             response_time: Duration::from_millis(0),
         };
@@ -140,23 +235,60 @@ impl ServiceDetector for ServiceDetectionEngine {
             service_info.service_name = signature.service_name.clone();
         }
         
-        // Phase 2: Ultra-fast banner grabbing (parallel with connection)
-        if let Some(banner) = self.grab_banner(target).await {
+        // Phase 2: SSL analysis for HTTPS/TLS services. Runs before banner
+        // grabbing so the handshake gets the untouched pooled connection.
+        if self.is_ssl_port(target.port()) {
+            service_info.ssl_info = self.analyze_ssl(target).await;
+        }
+        
+        // Phase 3: Web technology fingerprinting of ports known for HTTP,
+        // ahead of the banner probes so it gets the pooled connection
+        let fingerprinted = self.fingerprint_web(target, &mut service_info).await;
+        
+        // Phase 4: User-defined probes, the bundled probe database, then
+        // ultra-fast banner grabbing
+        if let Some(found) = self.custom_probes.identify(target, self.timeout).await {
+            service_info.service_name = found.service;
+            service_info.version = found.version;
+            service_info.banner = Some(found.reply);
+        } else if let Some(found) = self.identify_with_probes(target).await {
+            service_info.service_name = found.service;
+            service_info.version = found.version;
+        } else if let Some(banner) = self.grab_banner(target).await {
             service_info.banner = Some(banner.clone());
             
-            // Service identification from banner
-            service_info.service_name = self.identify_service_from_banner(&banner, target.port());
-            service_info.version = self.extract_version_from_banner(&banner);
+            // Service identification from banner, the user's rules first
+            let hash = content_hash(&(&banner, target.port()));
+            let (service, version) = self.banner_cache.get_or_analyze(target, hash, || {
+                match self.banner_rules.classify(&banner, target.port()) {
+                    Some(found) => (found.service, found.version),
+                    None => (
+                        self.identify_service_from_banner(&banner, target.port()),
+                        self.extract_version_from_banner(&banner),
+                    ),
+                }
+            });
+            service_info.service_name = service;
+            service_info.version = version;
         }
         
-        // Phase 3: SSL analysis for HTTPS/TLS services (parallel)
-        if self.is_ssl_port(target.port()) {
-            service_info.ssl_info = self.analyze_ssl(target).await;
+        // Phase 5: Services the probes found to speak HTTP on other ports
+        if !fingerprinted {
+            self.fingerprint_web(target, &mut service_info).await;
         }
         
-        // Phase 4: Fast vulnerability check (async)
+        // Phase 6: Fast vulnerability check (async)
         service_info.vulnerabilities = self.check_vulnerabilities(&service_info).await;
         
+        // Only the stored banner is masked; identification saw all of it
+        if self.redact_secrets {
+            if let Some(banner) = &mut service_info.banner {
+                if let Cow::Owned(redacted) = redact_secrets(banner) {
+                    *banner = redacted;
+                }
+            }
+        }
+        
         service_info.response_time = start_time.elapsed();
         
         Ok(service_info)
@@ -183,6 +315,11 @@ impl ServiceDetector for ServiceDetectionEngine {
 }
 
 impl ServiceDetectionEngine {
+    /// Service and version from the bundled probe database
+    pub async fn identify_with_probes(&self, target: SocketAddr) -> Option<super::ServiceVersion> {
+        self.service_probes.as_ref()?.identify(target, self.timeout).await
+    }
+    
     /// Identify service from banner with pattern matching
     fn identify_service_from_banner(&self, banner: &str, port: u16) -> String {
         let banner_lower = banner.to_lowercase();
@@ -275,11 +412,18 @@ impl ServiceSignature {
 /// Ultra-fast banner grabber with zero-copy optimization
 pub struct BannerGrabber {
     memory_pool: Arc<MemoryPool>,
+    connection_pool: Option<ConnectionPool>,
 }
 
 impl BannerGrabber {
     pub fn new(memory_pool: Arc<MemoryPool>) -> Self {
-        Self { memory_pool }
+        Self { memory_pool, connection_pool: None }
+    }
+    
+    /// Take connections from `pool` before opening new ones
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.connection_pool = Some(pool);
+        self
     }
     
     /// Grab banner with ultra-fast connection and zero-copy buffer
@@ -288,8 +432,8 @@ impl BannerGrabber {
         let mut buffer = self.memory_pool.get_buffer(4096)?;
         
         let result = timeout(timeout_duration, async {
-            // Ultra-fast TCP connection
-            let stream = match TcpStream::connect(target).await {
+            // Reuse the discovery connection when possible, otherwise connect
+            let stream = match connect(self.connection_pool.as_ref(), target, timeout_duration).await {
                 Ok(s) => s,
                 Err(_) => return String::new(),
             };
@@ -375,19 +519,39 @@ impl BannerGrabber {
     }
 }
 
+/// Connect through `pool` when one is configured
+async fn connect(pool: Option<&ConnectionPool>, target: SocketAddr, timeout_duration: Duration) -> std::io::Result<TcpStream> {
+    match pool {
+        Some(pool) => pool.connect(target, timeout_duration).await,
+        None => TcpStream::connect(target).await,
+    }
+}
+
 /// Fast SSL/TLS analyzer
-pub struct SSLAnalyzer;
+#[derive(Clone)]
+pub struct SSLAnalyzer {
+    connection_pool: Option<ConnectionPool>,
+    /// JARM/JA3S by certificate, version and cipher, so a fleet serving the
+    /// same certificate is probed once
+    fingerprint_cache: Arc<FingerprintCache<Option<TlsFingerprint>>>,
+}
 
 impl SSLAnalyzer {
     pub fn new() -> Self {
-        Self
+        Self { connection_pool: None, fingerprint_cache: Arc::new(FingerprintCache::new()) }
+    }
+    
+    /// Take connections from `pool` before opening new ones
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.connection_pool = Some(pool);
+        self
     }
     
     /// Fast SSL analysis with real handshake
     pub async fn analyze_fast(&self, target: SocketAddr, timeout_duration: Duration) -> Option<SSLInfo> {
         let result = timeout(timeout_duration, async {
             // Connect to the target
-            let stream = TcpStream::connect(target).await.ok()?;
+            let stream = connect(self.connection_pool.as_ref(), target, timeout_duration).await.ok()?;
             
             // Create SSL connector with minimal verification for speed
             let mut connector_builder = SslConnector::builder(SslMethod::tls()).ok()?;
@@ -409,22 +573,41 @@ impl SSLAnalyzer {
                 .unwrap_or_else(|| "Unknown".to_string());
             
             // Extract certificate information
-            let certificate = ssl.peer_certificate()
+            let peer = ssl.peer_certificate();
+            let certificate = peer.as_ref()
                 .and_then(|cert| {
                     cert.subject_name().entries()
                         .find(|entry| entry.object().nid().short_name().ok() == Some("CN"))
                         .and_then(|entry| entry.data().as_utf8().ok())
                         .map(|data| data.to_string())
                 });
+            let der = peer.and_then(|cert| cert.to_der().ok()).unwrap_or_default();
+            let hash = content_hash(&(der, &version, &cipher));
             
-            Some(SSLInfo {
+            Some((SSLInfo {
                 version,
                 cipher,
                 certificate,
-            })
+                jarm: None,
+                ja3s: None,
+            }, hash))
         }).await;
         
-        result.ok().flatten()
+        let (mut info, hash) = result.ok().flatten()?;
+        // Fingerprint probes use their own connections; the pooled one was consumed above
+        let fingerprint = match self.fingerprint_cache.get(target, hash) {
+            Some(cached) => cached,
+            None => {
+                let fingerprint = TlsFingerprinter::new(timeout_duration).fingerprint(target, None).await;
+                self.fingerprint_cache.insert(hash, fingerprint.clone());
+                fingerprint
+            }
+        };
+        if let Some(fingerprint) = fingerprint {
+            info.jarm = Some(fingerprint.jarm);
+            info.ja3s = fingerprint.ja3s;
+        }
+        Some(info)
     }
 }
 
@@ -147,6 +147,8 @@ mod tests {
             version: Some("OpenSSH_7.4".to_string()),
             banner: Some("SSH-2.0-OpenSSH_7.4".to_string()),
             ssl_info: None,
+            technologies: Vec::new(),
+            favicon_hash: None,
             vulnerabilities: Vec::new(),
             response_time: Duration::from_millis(1),
         };
@@ -0,0 +1,226 @@
+//! Service and version detection from probe replies
+//!
+//! The bundled `data/service_probes.toml` lists what to send to an open port
+//! and the patterns that name the service behind the reply, in the spirit of
+//! Nmap's `nmap-service-probes`. A port gets the probes that list it first,
+//! then the `fallback` ones, each on a fresh connection, until a pattern
+//! matches. This is what `--sV` runs instead of `nmap -sV`.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use regex::bytes::Regex;
+use serde::Deserialize;
+
+use crate::network::probes::{decode_hex, exchange};
+use crate::ScanError;
+
+const BUNDLED_PROBES: &str = include_str!("data/service_probes.toml");
+
+/// A probe as written in the probe file
+#[derive(Debug, Deserialize)]
+struct ProbeSpec {
+    name: String,
+    #[serde(default)]
+    ports: Vec<u16>,
+    #[serde(default)]
+    fallback: bool,
+    payload: Option<String>,
+    payload_hex: Option<String>,
+    #[serde(rename = "match", default)]
+    matches: Vec<MatchSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MatchSpec {
+    service: String,
+    product: Option<String>,
+    pattern: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFile {
+    probe: Vec<ProbeSpec>,
+}
+
+/// One pattern of a probe and what it names
+#[derive(Debug, Clone)]
+struct ServiceMatch {
+    pattern: Regex,
+    service: String,
+    product: Option<String>,
+}
+
+impl ServiceMatch {
+    fn identify(&self, reply: &[u8]) -> Option<ServiceVersion> {
+        let captures = self.pattern.captures(reply)?;
+        let version = captures
+            .name("version")
+            .or_else(|| captures.get(1))
+            .map(|group| String::from_utf8_lossy(group.as_bytes()).trim().to_string())
+            .filter(|version| !version.is_empty());
+        let version = match (&self.product, version) {
+            (Some(product), Some(version)) => Some(format!("{} {}", product, version)),
+            (Some(product), None) => Some(product.clone()),
+            (None, version) => version,
+        };
+        Some(ServiceVersion { service: self.service.clone(), version })
+    }
+}
+
+/// A probe ready to send
+#[derive(Debug, Clone)]
+pub struct ServiceProbe {
+    pub name: String,
+    pub ports: Vec<u16>,
+    pub fallback: bool,
+    pub payload: Vec<u8>,
+    matches: Vec<ServiceMatch>,
+}
+
+impl ServiceProbe {
+    /// The first pattern matching `reply`
+    pub fn identify(&self, reply: &[u8]) -> Option<ServiceVersion> {
+        self.matches.iter().find_map(|m| m.identify(reply))
+    }
+}
+
+/// Service name and, when the reply gave it away, product and version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceVersion {
+    pub service: String,
+    pub version: Option<String>,
+}
+
+/// Every probe of a probe file, in file order
+#[derive(Debug, Clone)]
+pub struct ServiceProbes {
+    probes: Vec<ServiceProbe>,
+}
+
+impl ServiceProbes {
+    /// The probes shipped with Phobos
+    pub fn bundled() -> crate::Result<Self> {
+        Self::parse(BUNDLED_PROBES)
+    }
+
+    /// Probes in the bundled TOML format
+    pub fn parse(toml: &str) -> crate::Result<Self> {
+        let file: ProbeFile = toml::from_str(toml)
+            .map_err(|e| ScanError::ParseError(format!("Invalid service probe file: {}", e)))?;
+        let probes = file.probe.into_iter().map(Self::compile).collect::<crate::Result<_>>()?;
+        Ok(Self { probes })
+    }
+
+    fn compile(spec: ProbeSpec) -> crate::Result<ServiceProbe> {
+        let invalid = |reason: String| ScanError::ParseError(format!("Service probe {}: {}", spec.name, reason));
+        let payload = match (&spec.payload, &spec.payload_hex) {
+            (Some(_), Some(_)) => return Err(invalid("set either payload or payload_hex, not both".to_string())),
+            (Some(text), None) => text.as_bytes().to_vec(),
+            (None, Some(hex)) => decode_hex(hex).ok_or_else(|| invalid(format!("invalid payload_hex `{}`", hex)))?,
+            (None, None) => Vec::new(),
+        };
+        let matches = spec
+            .matches
+            .iter()
+            .map(|m| {
+                let pattern = Regex::new(&m.pattern)
+                    .map_err(|e| invalid(format!("invalid pattern for {}: {}", m.service, e)))?;
+                Ok(ServiceMatch { pattern, service: m.service.clone(), product: m.product.clone() })
+            })
+            .collect::<crate::Result<_>>()?;
+        Ok(ServiceProbe { name: spec.name, ports: spec.ports, fallback: spec.fallback, payload, matches })
+    }
+
+    /// Probes to send to `port`, in the order they are tried
+    pub fn for_port(&self, port: u16) -> impl Iterator<Item = &ServiceProbe> {
+        let listed = self.probes.iter().filter(move |probe| probe.ports.contains(&port));
+        let fallback = self.probes.iter().filter(move |probe| probe.fallback && !probe.ports.contains(&port));
+        listed.chain(fallback)
+    }
+
+    /// Send the port's probes until one reply matches; each probe gets
+    /// `wait` in total
+    ///
+    /// A reply is read until a pattern that names a version matches, so a
+    /// generic pattern cannot win over a later line with the version in it.
+    pub async fn identify(&self, target: SocketAddr, wait: Duration) -> Option<ServiceVersion> {
+        for probe in self.for_port(target.port()) {
+            let versioned = |reply: &[u8]| probe.identify(reply).filter(|found| found.version.is_some());
+            let Some((reply, found)) = exchange(target, &probe.payload, wait, versioned).await else {
+                // Nothing listens any more; later probes would fare no better
+                return None;
+            };
+            if let Some(found) = found.or_else(|| probe.identify(&reply)) {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_bundled_probes_compile() {
+        let probes = ServiceProbes::bundled().unwrap();
+        let order: Vec<&str> = probes.for_port(80).map(|probe| probe.name.as_str()).collect();
+        assert_eq!(order, ["http-get", "null"]);
+        let order: Vec<&str> = probes.for_port(6379).map(|probe| probe.name.as_str()).collect();
+        assert_eq!(order, ["redis-info", "null", "http-get"]);
+
+        let null = probes.for_port(22).next().unwrap();
+        let found = null.identify(b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n").unwrap();
+        assert_eq!(found, ServiceVersion { service: "ssh".to_string(), version: Some("OpenSSH 9.6p1".to_string()) });
+        let greeting = b"\x4a\x00\x00\x00\x0a8.0.36\x00\x08\x00\x00\x00";
+        assert_eq!(null.identify(greeting).unwrap().version.as_deref(), Some("MySQL 8.0.36"));
+    }
+
+    #[tokio::test]
+    async fn test_identify_reads_past_a_generic_match() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 64];
+                if stream.read(&mut request).await.unwrap() == 0 {
+                    continue;
+                }
+                // The Server header comes in a later segment than the status line
+                stream.write_all(b"HTTP/1.0 200 OK\r\n").await.unwrap();
+                stream.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                stream.write_all(b"Server: nginx/1.25.4\r\n\r\n").await.unwrap();
+            }
+        });
+
+        let probes = ServiceProbes::parse(
+            r#"
+            [[probe]]
+            name = "http-get"
+            fallback = true
+            payload = "GET / HTTP/1.0\r\n\r\n"
+
+            [[probe.match]]
+            service = "http"
+            product = "nginx"
+            pattern = '(?si)^HTTP/1\.[01] \d{3}.*?\r\nserver: nginx/(?P<version>[\d.]+)'
+
+            [[probe.match]]
+            service = "http"
+            pattern = '^HTTP/1\.[01] \d{3}'
+            "#,
+        )
+        .unwrap();
+        let found = probes.identify(target, Duration::from_millis(500)).await.unwrap();
+        assert_eq!(found.service, "http");
+        assert_eq!(found.version.as_deref(), Some("nginx 1.25.4"));
+
+        assert!(ServiceProbes::parse("[[probe]]\nname = \"x\"\npayload_hex = \"abc\"").is_err());
+    }
+}
@@ -147,7 +147,7 @@ impl Default for SmartPredictor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::network::Protocol;
+    use crate::network::{Confidence, Protocol};
     use std::time::Duration;
     
     #[test]
@@ -158,8 +158,12 @@ mod tests {
                 port: 80,
                 state: PortState::Open,
                 service: Some("http".to_string()),
+                version: None,
                 protocol: Protocol::Tcp,
                 response_time: Duration::from_millis(10),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
             }
         ];
         
@@ -0,0 +1,503 @@
+//! JARM and JA3S fingerprints of TLS services
+//!
+//! JARM sends ten crafted ClientHellos and condenses the ServerHello choices
+//! (cipher, version, ALPN, extension order) into a 62 character fingerprint;
+//! the implementation follows the reference `jarm.py` byte for byte so hashes
+//! are comparable with public datasets. JA3S is the MD5 of the version, cipher
+//! and extension list of a single ServerHello.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use openssl::hash::{hash, MessageDigest};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Fingerprints of one TLS service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsFingerprint {
+    /// 62 hex chars; all zeros when the service answered no probe
+    pub jarm: String,
+    /// MD5 of `ja3s_raw`, absent when no ServerHello was seen
+    pub ja3s: Option<String>,
+    /// `version,cipher,extensions` string the JA3S hash is computed from
+    pub ja3s_raw: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Forward,
+    Reverse,
+    TopHalf,
+    BottomHalf,
+    MiddleOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SupportedVersions {
+    None,
+    Tls12,
+    Tls13,
+}
+
+/// One JARM probe definition
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    version: Version,
+    /// Offer TLS 1.3 suites
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    supported_versions: SupportedVersions,
+    extension_order: Order,
+}
+
+const fn probe(
+    version: Version,
+    tls13_ciphers: bool,
+    cipher_order: Order,
+    grease: bool,
+    rare_alpn: bool,
+    supported_versions: SupportedVersions,
+    extension_order: Order,
+) -> Probe {
+    Probe { version, tls13_ciphers, cipher_order, grease, rare_alpn, supported_versions, extension_order }
+}
+
+/// The ten probes in the order their answers enter the fingerprint
+const PROBES: [Probe; 10] = [
+    probe(Version::Tls12, true, Order::Forward, false, false, SupportedVersions::Tls12, Order::Reverse),
+    probe(Version::Tls12, true, Order::Reverse, false, false, SupportedVersions::Tls12, Order::Forward),
+    probe(Version::Tls12, true, Order::TopHalf, false, false, SupportedVersions::None, Order::Forward),
+    probe(Version::Tls12, true, Order::BottomHalf, false, true, SupportedVersions::None, Order::Forward),
+    probe(Version::Tls12, true, Order::MiddleOut, true, true, SupportedVersions::None, Order::Reverse),
+    probe(Version::Tls11, true, Order::Forward, false, false, SupportedVersions::None, Order::Forward),
+    probe(Version::Tls13, true, Order::Forward, false, false, SupportedVersions::Tls13, Order::Reverse),
+    probe(Version::Tls13, true, Order::Reverse, false, false, SupportedVersions::Tls13, Order::Forward),
+    probe(Version::Tls13, false, Order::Forward, false, false, SupportedVersions::Tls13, Order::Forward),
+    probe(Version::Tls13, true, Order::MiddleOut, true, false, SupportedVersions::Tls13, Order::Reverse),
+];
+
+/// Cipher suites offered by the probes, in JARM's forward order
+const CIPHERS: [u16; 69] = [
+    0x0016, 0x0033, 0x0067, 0xc09e, 0xc0a2, 0x009e, 0x0039, 0x006b, 0xc09f, 0xc0a3, 0x009f, 0x0045,
+    0x00be, 0x0088, 0x00c4, 0x009a, 0xc008, 0xc009, 0xc023, 0xc0ac, 0xc0ae, 0xc02b, 0xc00a, 0xc024,
+    0xc0ad, 0xc0af, 0xc02c, 0xc072, 0xc073, 0xcca9, 0x1302, 0x1301, 0xcc14, 0xc007, 0xc012, 0xc013,
+    0xc027, 0xc02f, 0xc014, 0xc028, 0xc030, 0xc060, 0xc061, 0xc076, 0xc077, 0xcca8, 0x1305, 0x1304,
+    0x1303, 0xcc13, 0xc011, 0x000a, 0x002f, 0x003c, 0xc09c, 0xc0a0, 0x009c, 0x0035, 0x003d, 0xc09d,
+    0xc0a1, 0x009d, 0x0041, 0x00ba, 0x0084, 0x00c0, 0x0007, 0x0004, 0x0005,
+];
+
+const ALPNS: [&[u8]; 9] = [b"http/0.9", b"http/1.0", b"http/1.1", b"spdy/1", b"spdy/2", b"spdy/3", b"h2", b"h2c", b"hq"];
+const RARE_ALPNS: [&[u8]; 7] = [b"http/0.9", b"http/1.0", b"spdy/1", b"spdy/2", b"spdy/3", b"h2c", b"hq"];
+
+const GREASE: [u16; 16] = [
+    0x0a0a, 0x1a1a, 0x2a2a, 0x3a3a, 0x4a4a, 0x5a5a, 0x6a6a, 0x7a7a, 0x8a8a, 0x9a9a, 0xaaaa, 0xbaba,
+    0xcaca, 0xdada, 0xeaea, 0xfafa,
+];
+
+/// Reorder a list the way JARM shuffles ciphers, ALPNs and versions
+fn reorder<T: Copy>(items: &[T], order: Order) -> Vec<T> {
+    let len = items.len();
+    let middle = len / 2;
+    match order {
+        Order::Forward => items.to_vec(),
+        Order::Reverse => items.iter().rev().copied().collect(),
+        Order::BottomHalf => items[middle + len % 2..].to_vec(),
+        Order::TopHalf => {
+            let mut out = Vec::with_capacity(middle + 1);
+            if len % 2 == 1 {
+                out.push(items[middle]);
+            }
+            out.extend(reorder(&reorder(items, Order::Reverse), Order::BottomHalf));
+            out
+        }
+        Order::MiddleOut => {
+            let mut out = Vec::with_capacity(len);
+            if len % 2 == 1 {
+                out.push(items[middle]);
+                for i in 1..=middle {
+                    out.push(items[middle + i]);
+                    out.push(items[middle - i]);
+                }
+            } else {
+                for i in 1..=middle {
+                    out.push(items[middle - 1 + i]);
+                    out.push(items[middle - i]);
+                }
+            }
+            out
+        }
+    }
+}
+
+fn random_grease() -> [u8; 2] {
+    GREASE[rand::thread_rng().gen_range(0..GREASE.len())].to_be_bytes()
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u16).to_be_bytes());
+}
+
+/// Build the ClientHello record for `probe`
+fn build_client_hello(probe: &Probe, server_name: &str) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let (record_version, hello_version): ([u8; 2], [u8; 2]) = match probe.version {
+        Version::Tls11 => ([3, 2], [3, 2]),
+        Version::Tls12 => ([3, 3], [3, 3]),
+        Version::Tls13 => ([3, 1], [3, 3]),
+    };
+
+    let mut hello = hello_version.to_vec();
+    hello.extend((0..32).map(|_| rng.gen::<u8>()));
+    hello.push(32);
+    hello.extend((0..32).map(|_| rng.gen::<u8>()));
+
+    let offered: Vec<u16> = CIPHERS
+        .iter()
+        .copied()
+        .filter(|c| probe.tls13_ciphers || !(0x1301..=0x1305).contains(c))
+        .collect();
+    let mut ciphers = Vec::new();
+    if probe.grease {
+        ciphers.extend_from_slice(&random_grease());
+    }
+    for cipher in reorder(&offered, probe.cipher_order) {
+        ciphers.extend_from_slice(&cipher.to_be_bytes());
+    }
+    push_u16(&mut hello, ciphers.len());
+    hello.extend(ciphers);
+    // One compression method: null
+    hello.extend_from_slice(&[0x01, 0x00]);
+
+    let extensions = build_extensions(probe, server_name);
+    push_u16(&mut hello, extensions.len());
+    hello.extend(extensions);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+    handshake.extend(hello);
+
+    let mut record = vec![0x16];
+    record.extend_from_slice(&record_version);
+    push_u16(&mut record, handshake.len());
+    record.extend(handshake);
+    record
+}
+
+fn build_extensions(probe: &Probe, server_name: &str) -> Vec<u8> {
+    let mut ext = Vec::new();
+    if probe.grease {
+        ext.extend_from_slice(&random_grease());
+        ext.extend_from_slice(&[0x00, 0x00]);
+    }
+
+    // server_name
+    let name = server_name.as_bytes();
+    ext.extend_from_slice(&[0x00, 0x00]);
+    push_u16(&mut ext, name.len() + 5);
+    push_u16(&mut ext, name.len() + 3);
+    ext.push(0x00);
+    push_u16(&mut ext, name.len());
+    ext.extend_from_slice(name);
+
+    // extended_master_secret, max_fragment_length, renegotiation_info,
+    // supported_groups, ec_point_formats, session_ticket
+    ext.extend_from_slice(&[0x00, 0x17, 0x00, 0x00]);
+    ext.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0x01]);
+    ext.extend_from_slice(&[0xff, 0x01, 0x00, 0x01, 0x00]);
+    ext.extend_from_slice(&[0x00, 0x0a, 0x00, 0x0a, 0x00, 0x08, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19]);
+    ext.extend_from_slice(&[0x00, 0x0b, 0x00, 0x02, 0x01, 0x00]);
+    ext.extend_from_slice(&[0x00, 0x23, 0x00, 0x00]);
+
+    // ALPN
+    let alpns: &[&[u8]] = if probe.rare_alpn { &RARE_ALPNS } else { &ALPNS };
+    let mut alpn_list = Vec::new();
+    for proto in reorder(alpns, probe.extension_order) {
+        alpn_list.push(proto.len() as u8);
+        alpn_list.extend_from_slice(proto);
+    }
+    ext.extend_from_slice(&[0x00, 0x10]);
+    push_u16(&mut ext, alpn_list.len() + 2);
+    push_u16(&mut ext, alpn_list.len());
+    ext.extend(alpn_list);
+
+    // signature_algorithms
+    ext.extend_from_slice(&[
+        0x00, 0x0d, 0x00, 0x14, 0x00, 0x12, 0x04, 0x03, 0x08, 0x04, 0x04, 0x01, 0x05, 0x03, 0x08, 0x05,
+        0x05, 0x01, 0x08, 0x06, 0x06, 0x01, 0x02, 0x01,
+    ]);
+
+    // key_share with a random x25519 share
+    let mut share = Vec::new();
+    if probe.grease {
+        share.extend_from_slice(&random_grease());
+        share.extend_from_slice(&[0x00, 0x01, 0x00]);
+    }
+    share.extend_from_slice(&[0x00, 0x1d, 0x00, 0x20]);
+    let mut rng = rand::thread_rng();
+    share.extend((0..32).map(|_| rng.gen::<u8>()));
+    ext.extend_from_slice(&[0x00, 0x33]);
+    push_u16(&mut ext, share.len() + 2);
+    push_u16(&mut ext, share.len());
+    ext.extend(share);
+
+    // psk_key_exchange_modes
+    ext.extend_from_slice(&[0x00, 0x2d, 0x00, 0x02, 0x01, 0x01]);
+
+    if probe.version == Version::Tls13 || probe.supported_versions == SupportedVersions::Tls12 {
+        let versions: &[[u8; 2]] = if probe.supported_versions == SupportedVersions::Tls12 {
+            &[[3, 1], [3, 2], [3, 3]]
+        } else {
+            &[[3, 1], [3, 2], [3, 3], [3, 4]]
+        };
+        let mut list = Vec::new();
+        if probe.grease {
+            list.extend_from_slice(&random_grease());
+        }
+        for version in reorder(versions, probe.extension_order) {
+            list.extend_from_slice(&version);
+        }
+        ext.extend_from_slice(&[0x00, 0x2b]);
+        push_u16(&mut ext, list.len() + 1);
+        ext.push(list.len() as u8);
+        ext.extend(list);
+    }
+
+    ext
+}
+
+/// Fields of a ServerHello that the fingerprints are built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ServerHello {
+    version: u16,
+    cipher: u16,
+    extensions: Vec<u16>,
+    alpn: String,
+}
+
+impl ServerHello {
+    /// Parse the first record of a server reply; alerts and anything else yield `None`
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 44 || data[0] != 0x16 || data[5] != 0x02 {
+            return None;
+        }
+        let version = u16::from_be_bytes([data[9], data[10]]);
+        let session_len = data[43] as usize;
+        let cipher_at = 44 + session_len;
+        let cipher = u16::from_be_bytes([*data.get(cipher_at)?, *data.get(cipher_at + 1)?]);
+
+        let mut hello = Self { version, cipher, extensions: Vec::new(), alpn: String::new() };
+
+        // Extensions start after cipher (2) and compression (1)
+        let ext_len_at = cipher_at + 3;
+        let Some(len_bytes) = data.get(ext_len_at..ext_len_at + 2) else {
+            return Some(hello);
+        };
+        let end = (ext_len_at + 2 + u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize).min(data.len());
+        let mut pos = ext_len_at + 2;
+        while pos + 4 <= end {
+            let ext_type = u16::from_be_bytes([data[pos], data[pos + 1]]);
+            let ext_len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let value = data.get(pos + 4..pos + 4 + ext_len).unwrap_or(&[]);
+            if ext_type == 0x0010 && value.len() > 3 {
+                hello.alpn = String::from_utf8_lossy(&value[3..]).into_owned();
+            }
+            hello.extensions.push(ext_type);
+            pos += 4 + ext_len;
+        }
+        Some(hello)
+    }
+
+    fn extension_list(&self) -> String {
+        let extensions: Vec<String> = self.extensions.iter().map(|e| format!("{:04x}", e)).collect();
+        extensions.join("-")
+    }
+
+    fn ja3s_raw(&self) -> String {
+        let extensions: Vec<String> = self.extensions.iter().map(|e| e.to_string()).collect();
+        format!("{},{},{}", self.version, self.cipher, extensions.join("-"))
+    }
+}
+
+fn hex_digest(digest: MessageDigest, data: &[u8]) -> String {
+    hash(digest, data)
+        .map(|bytes| bytes.iter().map(|b| format!("{:02x}", b)).collect())
+        .unwrap_or_default()
+}
+
+/// Fold the ten probe answers into the JARM fingerprint
+fn jarm_hash(responses: &[Option<ServerHello>]) -> String {
+    if responses.iter().all(Option::is_none) {
+        return "0".repeat(62);
+    }
+
+    // Cipher index table: numeric order, except TLS 1.3 suites come last
+    let mut sorted = CIPHERS;
+    sorted.sort_unstable_by_key(|c| (c >> 8 == 0x13, *c));
+
+    let mut fuzzy = String::with_capacity(62);
+    let mut alpns_and_extensions = String::new();
+    for response in responses {
+        match response {
+            Some(hello) => {
+                let index = sorted.iter().position(|c| *c == hello.cipher).map_or(sorted.len() + 1, |i| i + 1);
+                fuzzy.push_str(&format!("{:02x}", index));
+                fuzzy.push(match hello.version {
+                    0x0300 => 'a',
+                    0x0301 => 'b',
+                    0x0302 => 'c',
+                    0x0303 => 'd',
+                    0x0304 => 'e',
+                    _ => 'f',
+                });
+                alpns_and_extensions.push_str(&hello.alpn);
+                alpns_and_extensions.push_str(&hello.extension_list());
+            }
+            None => fuzzy.push_str("000"),
+        }
+    }
+    let digest = hex_digest(MessageDigest::sha256(), alpns_and_extensions.as_bytes());
+    fuzzy.push_str(&digest[..32]);
+    fuzzy
+}
+
+/// Computes JARM/JA3S for TLS services
+#[derive(Debug, Clone)]
+pub struct TlsFingerprinter {
+    timeout: Duration,
+}
+
+impl TlsFingerprinter {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+
+    /// Run all probes concurrently; `server_name` goes into SNI and defaults to the IP
+    pub async fn fingerprint(&self, target: SocketAddr, server_name: Option<&str>) -> Option<TlsFingerprint> {
+        let name = server_name.map_or_else(|| target.ip().to_string(), str::to_string);
+        let probes = PROBES.iter().map(|probe| self.send_probe(target, probe, &name));
+        let responses = futures::future::join_all(probes).await;
+
+        if responses.iter().all(Option::is_none) {
+            return None;
+        }
+
+        // JA3S from the first answered probe, which is the plain TLS 1.2 hello when it succeeds
+        let ja3s_raw = responses.iter().flatten().next().map(ServerHello::ja3s_raw);
+        Some(TlsFingerprint {
+            jarm: jarm_hash(&responses),
+            ja3s: ja3s_raw.as_ref().map(|raw| hex_digest(MessageDigest::md5(), raw.as_bytes())),
+            ja3s_raw,
+        })
+    }
+
+    async fn send_probe(&self, target: SocketAddr, probe: &Probe, server_name: &str) -> Option<ServerHello> {
+        let hello = build_client_hello(probe, server_name);
+        timeout(self.timeout, async {
+            let mut stream = TcpStream::connect(target).await.ok()?;
+            stream.write_all(&hello).await.ok()?;
+
+            // The ServerHello is always the first handshake message; 1484 bytes
+            // matches the reference implementation's read size
+            let mut buf = vec![0u8; 1484];
+            let mut read = 0;
+            while read < 6 {
+                let n = stream.read(&mut buf[read..]).await.ok()?;
+                if n == 0 {
+                    break;
+                }
+                read += n;
+            }
+            ServerHello::parse(&buf[..read])
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_hello_record(cipher: u16, extensions: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut ext = Vec::new();
+        for (ext_type, value) in extensions {
+            ext.extend_from_slice(&ext_type.to_be_bytes());
+            push_u16(&mut ext, value.len());
+            ext.extend_from_slice(value);
+        }
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        body.push(0);
+        body.extend_from_slice(&cipher.to_be_bytes());
+        body.push(0);
+        push_u16(&mut body, ext.len());
+        body.extend(ext);
+
+        let mut record = vec![0x16, 0x03, 0x03];
+        push_u16(&mut record, body.len() + 4);
+        record.push(0x02);
+        record.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        record.extend(body);
+        record
+    }
+
+    #[test]
+    fn test_reorder_matches_reference() {
+        let items = [1, 2, 3, 4, 5];
+        assert_eq!(reorder(&items, Order::Reverse), vec![5, 4, 3, 2, 1]);
+        assert_eq!(reorder(&items, Order::BottomHalf), vec![4, 5]);
+        assert_eq!(reorder(&items, Order::TopHalf), vec![3, 2, 1]);
+        assert_eq!(reorder(&items, Order::MiddleOut), vec![3, 4, 2, 5, 1]);
+        assert_eq!(reorder(&[1, 2, 3, 4], Order::MiddleOut), vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn test_client_hello_lengths_are_consistent() {
+        for probe in &PROBES {
+            let record = build_client_hello(probe, "example.com");
+            let record_len = u16::from_be_bytes([record[3], record[4]]) as usize;
+            assert_eq!(record_len, record.len() - 5);
+            assert_eq!(record[5], 0x01);
+        }
+    }
+
+    #[test]
+    fn test_parse_server_hello_and_ja3s() {
+        let alpn: &[u8] = &[0x00, 0x03, 0x02, b'h', b'2'];
+        let record = server_hello_record(0xc02f, &[(0xff01, &[0x00]), (0x0010, alpn), (0x0017, &[])]);
+        let hello = ServerHello::parse(&record).unwrap();
+
+        assert_eq!(hello.cipher, 0xc02f);
+        assert_eq!(hello.alpn, "h2");
+        assert_eq!(hello.extension_list(), "ff01-0010-0017");
+        assert_eq!(hello.ja3s_raw(), "771,49199,65281-16-23");
+        assert!(ServerHello::parse(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x28]).is_none());
+    }
+
+    #[test]
+    fn test_jarm_hash_layout() {
+        assert_eq!(jarm_hash(&vec![None; 10]), "0".repeat(62));
+
+        let hello = ServerHello { version: 0x0303, cipher: 0xc02f, extensions: vec![0xff01], alpn: String::new() };
+        let mut responses = vec![None; 10];
+        responses[0] = Some(hello);
+        let jarm = jarm_hash(&responses);
+        assert_eq!(jarm.len(), 62);
+        // 0xc02f is the 41st cipher in sorted order
+        assert!(jarm.starts_with("29d000"));
+    }
+}
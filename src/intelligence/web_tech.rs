@@ -0,0 +1,595 @@
+//! HTTP technology fingerprinting
+//!
+//! Matches response headers, cookie names and body snippets of a single
+//! `GET /` against a bundled ruleset (`data/web_technologies.json`). A rule
+//! pattern may contain one capture group, which is reported as the version.
+//! Header patterns are matched against the header value; an empty pattern
+//! only checks that the header is present.
+//!
+//! The detector also computes the favicon hash Shodan and Censys index
+//! (`http.favicon.hash`), so scan results can be pivoted into those engines.
+//! [`WebTechDetector::fingerprint`] does both once per distinct front page in
+//! a run; identical pages from other addresses reuse the result. When enough
+//! services send the same page, [`WebTechDetector::wildcard_groups`] reports
+//! them as one catch-all (a wildcard vhost or a load balancer's default page).
+//!
+//! Given the port scan's [`ConnectionPool`], a plain HTTP `GET /` goes over
+//! the connection discovery left open instead of a new one.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::fingerprint_cache::{content_hash, CacheStats, FingerprintCache};
+use crate::network::socket::ConnectionPool;
+use crate::ScanError;
+
+const BUNDLED_RULES: &str = include_str!("data/web_technologies.json");
+
+/// Bodies are truncated to this size before matching
+const MAX_BODY_BYTES: usize = 512 * 1024;
+
+/// Services sending the same front page before they count as one catch-all
+pub const WILDCARD_MIN_SERVICES: usize = 3;
+
+/// Headers whose values change from one response to the next; only their
+/// presence counts towards a response being the same as another
+const VOLATILE_HEADERS: [&str; 11] = [
+    "date", "expires", "age", "set-cookie", "x-request-id", "x-amzn-trace-id",
+    "x-amz-cf-id", "cf-ray", "x-runtime", "server-timing", "x-timer",
+];
+
+/// Technology identified on a web service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Technology {
+    pub name: String,
+    pub category: String,
+    pub version: Option<String>,
+}
+
+/// Rule as written in the ruleset file
+#[derive(Debug, Clone, Deserialize)]
+struct RuleSpec {
+    name: String,
+    category: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    cookies: Vec<String>,
+    #[serde(default)]
+    body: Vec<String>,
+}
+
+#[derive(Debug)]
+struct TechRule {
+    name: String,
+    category: String,
+    headers: Vec<(String, Regex)>,
+    cookies: Vec<Regex>,
+    body: Vec<Regex>,
+}
+
+fn compile(pattern: &str, rule: &str) -> crate::Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| ScanError::ParseError(format!("Invalid pattern in web technology rule {}: {}", rule, e)))
+}
+
+impl TechRule {
+    fn compile(spec: RuleSpec) -> crate::Result<Self> {
+        let headers = spec
+            .headers
+            .iter()
+            .map(|(name, pattern)| Ok((name.to_lowercase(), compile(pattern, &spec.name)?)))
+            .collect::<crate::Result<_>>()?;
+        let cookies = spec.cookies.iter().map(|p| compile(p, &spec.name)).collect::<crate::Result<_>>()?;
+        let body = spec.body.iter().map(|p| compile(p, &spec.name)).collect::<crate::Result<_>>()?;
+
+        Ok(Self { name: spec.name, category: spec.category, headers, cookies, body })
+    }
+
+    /// `None` if nothing matched, otherwise the first version any pattern captured
+    fn evaluate(&self, response: &HttpResponse) -> Option<Option<String>> {
+        let captured = |re: &Regex, text: &str| re.captures(text).map(|c| c.get(1).map(|m| m.as_str().to_string()));
+
+        let header_hits = self.headers.iter().filter_map(|(name, re)| {
+            response.headers.iter().filter(|(n, _)| n == name).find_map(|(_, value)| captured(re, value))
+        });
+        let cookie_hits = self
+            .cookies
+            .iter()
+            .filter_map(|re| response.cookie_names().find_map(|cookie| re.is_match(cookie).then_some(None)));
+        let body_hits = self.body.iter().filter_map(|re| captured(re, &response.body));
+
+        let mut matched = false;
+        let mut version = None;
+        for hit in header_hits.chain(cookie_hits).chain(body_hits) {
+            matched = true;
+            if hit.is_some() {
+                version = hit;
+                break;
+            }
+        }
+        matched.then_some(version)
+    }
+}
+
+/// Parts of an HTTP response the detector looks at
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Header names lowercased, in arrival order
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Hash of the response with per-request header values left out
+    pub fn content_hash(&self) -> u64 {
+        let headers: Vec<(&str, &str)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| match VOLATILE_HEADERS.contains(&name.as_str()) {
+                true => (name.as_str(), ""),
+                false => (name.as_str(), value.as_str()),
+            })
+            .collect();
+        let cookies: Vec<&str> = self.cookie_names().collect();
+        content_hash(&(self.status, headers, cookies, &self.body))
+    }
+
+    fn cookie_names(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .iter()
+            .filter(|(name, _)| name == "set-cookie")
+            .filter_map(|(_, value)| value.split('=').next())
+            .map(str::trim)
+    }
+}
+
+/// Technologies and favicon of one web service
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebFingerprint {
+    pub technologies: Vec<Technology>,
+    pub favicon_hash: Option<i32>,
+}
+
+/// Detects web technologies from a bundled or custom ruleset
+#[derive(Debug, Clone)]
+pub struct WebTechDetector {
+    client: reqwest::Client,
+    timeout: Duration,
+    rules: Arc<Vec<TechRule>>,
+    cache: Arc<FingerprintCache<WebFingerprint>>,
+    connection_pool: Option<ConnectionPool>,
+}
+
+impl WebTechDetector {
+    /// Detector using the bundled ruleset
+    pub fn new(timeout: Duration) -> crate::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .danger_accept_invalid_certs(true)
+            .redirect(reqwest::redirect::Policy::limited(3))
+            .user_agent(concat!("phobos/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| ScanError::NetworkError(format!("Failed to build HTTP client: {}", e)))?;
+
+        Ok(Self {
+            client,
+            timeout,
+            rules: Arc::new(Self::parse_rules(BUNDLED_RULES)?),
+            cache: Arc::new(FingerprintCache::new()),
+            connection_pool: None,
+        })
+    }
+
+    /// Send plain HTTP requests over connections from `pool` before opening new ones
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.connection_pool = Some(pool);
+        self
+    }
+
+    /// Replace the ruleset with rules in the bundled JSON format
+    pub fn with_rules(mut self, json: &str) -> crate::Result<Self> {
+        self.rules = Arc::new(Self::parse_rules(json)?);
+        Ok(self)
+    }
+
+    fn parse_rules(json: &str) -> crate::Result<Vec<TechRule>> {
+        let specs: Vec<RuleSpec> = serde_json::from_str(json)
+            .map_err(|e| ScanError::ParseError(format!("Invalid web technology ruleset: {}", e)))?;
+        specs.into_iter().map(TechRule::compile).collect()
+    }
+
+    /// Number of loaded rules
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether the port or detected service looks like HTTP(S)
+    pub fn is_web_port(port: u16, service: Option<&str>) -> bool {
+        matches!(service, Some("http" | "https" | "http-proxy" | "http-alt"))
+            || matches!(port, 80 | 443 | 3000 | 5000 | 8000 | 8008 | 8080 | 8081 | 8443 | 8888 | 9000 | 9443)
+    }
+
+    /// Whether the service should be spoken to over TLS
+    pub fn is_tls_port(port: u16, service: Option<&str>) -> bool {
+        service == Some("https") || matches!(port, 443 | 8443 | 9443)
+    }
+
+    /// `GET path` on the target, keeping at most 512 KiB of body
+    pub async fn fetch(&self, target: SocketAddr, tls: bool, path: &str) -> Option<HttpResponse> {
+        let (status, headers, body) = self.get(target, tls, path).await?;
+        Some(HttpResponse { status, headers, body: String::from_utf8_lossy(&body).into_owned() })
+    }
+
+    async fn get(&self, target: SocketAddr, tls: bool, path: &str) -> Option<(u16, Vec<(String, String)>, Vec<u8>)> {
+        // TLS ports leave their pooled connection to the handshake analysis
+        if let Some(stream) = self.connection_pool.as_ref().filter(|_| !tls).and_then(|pool| pool.checkout(target)) {
+            let pooled = tokio::time::timeout(self.timeout, Self::get_pooled(stream, target, path)).await.ok().flatten();
+            // A redirect goes through the client, which follows it
+            if let Some(response) = pooled.filter(|(status, _, _)| !(300..400).contains(status)) {
+                return Some(response);
+            }
+        }
+        let url = format!("{}://{}{}", if tls { "https" } else { "http" }, target, path);
+        let mut response = self.client.get(url).send().await.ok()?;
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+
+        let mut body = Vec::new();
+        while let Ok(Some(chunk)) = response.chunk().await {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+        Some((status, headers, body))
+    }
+
+    /// `GET path` over an already open connection
+    async fn get_pooled(stream: tokio::net::TcpStream, target: SocketAddr, path: &str) -> Option<(u16, Vec<(String, String)>, Vec<u8>)> {
+        use hyper::body::HttpBody;
+
+        let (mut sender, connection) = hyper::client::conn::handshake(stream).await.ok()?;
+        tokio::spawn(connection);
+        let request = hyper::Request::get(path)
+            .header(hyper::header::HOST, target.to_string())
+            .header(hyper::header::USER_AGENT, concat!("phobos/", env!("CARGO_PKG_VERSION")))
+            .body(hyper::Body::empty())
+            .ok()?;
+        let mut response = sender.send_request(request).await.ok()?;
+
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+
+        let mut body = Vec::new();
+        while let Some(Ok(chunk)) = response.body_mut().data().await {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+        Some((status, headers, body))
+    }
+
+    /// Shodan-style hash of `/favicon.ico`, `None` if the server has no favicon
+    pub async fn favicon_hash(&self, target: SocketAddr, tls: bool) -> Option<i32> {
+        let (status, _, body) = self.get(target, tls, "/favicon.ico").await?;
+        (status == 200 && !body.is_empty()).then(|| favicon_hash(&body))
+    }
+
+    /// Fetch `/` and match it against the ruleset
+    pub async fn detect(&self, target: SocketAddr, tls: bool) -> Vec<Technology> {
+        match self.fetch(target, tls, "/").await {
+            Some(response) => self.analyze(&response),
+            None => Vec::new(),
+        }
+    }
+
+    /// Technologies and favicon hash, computed once per distinct `GET /`
+    /// response; later services sending the same page reuse them without
+    /// fetching the favicon again
+    pub async fn fingerprint(&self, target: SocketAddr, tls: bool) -> WebFingerprint {
+        let Some(response) = self.fetch(target, tls, "/").await else {
+            return WebFingerprint { technologies: Vec::new(), favicon_hash: self.favicon_hash(target, tls).await };
+        };
+        let hash = response.content_hash();
+        if let Some(cached) = self.cache.get(target, hash) {
+            return cached;
+        }
+        let fingerprint = WebFingerprint {
+            technologies: self.analyze(&response),
+            favicon_hash: self.favicon_hash(target, tls).await,
+        };
+        self.cache.insert(hash, fingerprint.clone());
+        fingerprint
+    }
+
+    /// Groups of at least [`WILDCARD_MIN_SERVICES`] fingerprinted services
+    /// that sent the same front page, each sorted
+    pub fn wildcard_groups(&self) -> Vec<Vec<SocketAddr>> {
+        self.cache.shared_content().into_iter().filter(|group| group.len() >= WILDCARD_MIN_SERVICES).collect()
+    }
+
+    /// Front pages analysed and reused by [`fingerprint`](Self::fingerprint)
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Match an already fetched response against the ruleset
+    pub fn analyze(&self, response: &HttpResponse) -> Vec<Technology> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                rule.evaluate(response).map(|version| Technology {
+                    name: rule.name.clone(),
+                    category: rule.category.clone(),
+                    version,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A fingerprinted web port as reported, with the ports that repeat it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebService {
+    pub port: u16,
+    pub fingerprint: WebFingerprint,
+    /// The rest of its wildcard group; empty unless it is the lowest port of one
+    pub same_response: Vec<u16>,
+}
+
+/// Fold each of `groups` (from [`WebTechDetector::wildcard_groups`]) into its
+/// lowest port
+///
+/// Returns the services to report, in the order fingerprinted, and the
+/// repeats folded away, which later per-port steps skip.
+pub fn collapse_wildcards(fingerprints: Vec<(u16, WebFingerprint)>, groups: &[Vec<SocketAddr>]) -> (Vec<WebService>, Vec<u16>) {
+    let groups: Vec<Vec<u16>> = groups.iter()
+        .map(|group| {
+            let mut ports: Vec<u16> = group.iter().map(SocketAddr::port).collect();
+            ports.sort_unstable();
+            ports
+        })
+        .collect();
+    let repeats: Vec<u16> = groups.iter().flat_map(|group| group[1..].iter().copied()).collect();
+    let services = fingerprints.into_iter()
+        .filter(|(port, _)| !repeats.contains(port))
+        .map(|(port, fingerprint)| WebService {
+            port,
+            fingerprint,
+            same_response: groups.iter().find(|group| group[0] == port).map(|group| group[1..].to_vec()).unwrap_or_default(),
+        })
+        .collect();
+    (services, repeats)
+}
+
+/// MMH3 of the favicon's base64 encoding, exactly as Shodan computes it:
+/// Python's `base64.encodebytes` (76 character lines, trailing newline)
+/// hashed with 32-bit MurmurHash3, seed 0, read as a signed integer
+pub fn favicon_hash(data: &[u8]) -> i32 {
+    let encoded = openssl::base64::encode_block(data);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for line in encoded.as_bytes().chunks(76) {
+        wrapped.push_str(std::str::from_utf8(line).unwrap_or_default());
+        wrapped.push('\n');
+    }
+    murmur3_32(wrapped.as_bytes(), 0) as i32
+}
+
+/// MurmurHash3 x86 32-bit
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut hash = seed;
+    let mut blocks = data.chunks_exact(4);
+    for block in &mut blocks {
+        let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        hash ^= k;
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let tail = blocks.remainder();
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, byte) in tail.iter().enumerate() {
+            k |= (*byte as u32) << (8 * i);
+        }
+        hash ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn response(headers: &[(&str, &str)], body: &str) -> HttpResponse {
+        HttpResponse {
+            status: 200,
+            headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            body: body.to_string(),
+        }
+    }
+
+    fn names(techs: &[Technology]) -> Vec<&str> {
+        techs.iter().map(|t| t.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_collapse_wildcards_keeps_lowest_port_of_each_group() {
+        let fingerprint = |port: u16| WebFingerprint { technologies: Vec::new(), favicon_hash: Some(i32::from(port)) };
+        let scanned = || [80, 443, 3000, 8000, 8080, 8443, 9000].map(|port| (port, fingerprint(port))).to_vec();
+        let group = |ports: &[u16]| ports.iter().map(|&port| SocketAddr::from(([192, 0, 2, 1], port))).collect::<Vec<_>>();
+
+        // No catch-all: every port is reported and none skipped
+        let (shown, repeats) = collapse_wildcards(scanned(), &[]);
+        assert_eq!(shown.len(), 7);
+        assert!(shown.iter().all(|service| service.same_response.is_empty()));
+        assert!(repeats.is_empty());
+
+        // Two catch-alls, one listed out of order, around a port of its own
+        let (shown, repeats) = collapse_wildcards(scanned(), &[group(&[8080, 80, 8000]), group(&[443, 8443, 9000])]);
+        let reported: Vec<_> = shown.iter().map(|service| (service.port, service.same_response.as_slice())).collect();
+        assert_eq!(reported, vec![(80, &[8000, 8080][..]), (443, &[8443, 9000][..]), (3000, &[][..])]);
+        assert_eq!(repeats, vec![8000, 8080, 8443, 9000]);
+        assert!(shown.iter().all(|service| service.fingerprint == fingerprint(service.port)));
+    }
+
+    #[test]
+    fn test_bundled_rules_compile() {
+        let detector = WebTechDetector::new(Duration::from_secs(1)).unwrap();
+        assert!(detector.rule_count() > 20);
+    }
+
+    #[test]
+    fn test_header_cookie_and_body_matches() {
+        let detector = WebTechDetector::new(Duration::from_secs(1)).unwrap();
+        let techs = detector.analyze(&response(
+            &[
+                ("server", "nginx/1.24.0"),
+                ("x-powered-by", "PHP/8.2.7"),
+                ("set-cookie", "wordpress_logged_in=abc; path=/"),
+            ],
+            r#"<meta name="generator" content="WordPress 6.4.2" /><script src="/wp-includes/js/jquery/jquery.min.js">"#,
+        ));
+
+        let found = names(&techs);
+        assert!(found.contains(&"nginx") && found.contains(&"PHP") && found.contains(&"WordPress"));
+        let version = |name: &str| techs.iter().find(|t| t.name == name).and_then(|t| t.version.clone());
+        assert_eq!(version("nginx").as_deref(), Some("1.24.0"));
+        assert_eq!(version("PHP").as_deref(), Some("8.2.7"));
+        assert_eq!(version("WordPress").as_deref(), Some("6.4.2"));
+        assert!(!found.contains(&"Drupal"));
+    }
+
+    #[test]
+    fn test_custom_rules_and_invalid_pattern() {
+        let detector = WebTechDetector::new(Duration::from_secs(1)).unwrap();
+        let custom = detector
+            .clone()
+            .with_rules(r#"[{"name": "Internal", "category": "App", "headers": {"X-App": ""}}]"#)
+            .unwrap();
+        assert_eq!(names(&custom.analyze(&response(&[("x-app", "1")], ""))), vec!["Internal"]);
+
+        assert!(detector.with_rules(r#"[{"name": "Bad", "category": "App", "body": ["("]}]"#).is_err());
+    }
+
+    #[test]
+    fn test_content_hash_ignores_per_request_headers() {
+        let node = |date: &str, session: &str, server: &str| {
+            let cookie = format!("session={}; path=/", session);
+            response(&[("date", date), ("set-cookie", &cookie), ("server", server)], "<h1>Welcome</h1>")
+        };
+        let first = node("Mon, 06 Jan 2025 10:00:00 GMT", "a1", "nginx");
+        assert_eq!(first.content_hash(), node("Mon, 06 Jan 2025 10:00:02 GMT", "b2", "nginx").content_hash());
+        assert_ne!(first.content_hash(), node("Mon, 06 Jan 2025 10:00:00 GMT", "a1", "Apache").content_hash());
+    }
+
+    #[test]
+    fn test_murmur3_reference_values() {
+        assert_eq!(murmur3_32(b"", 0), 0);
+        assert_eq!(murmur3_32(b"", 1), 0x514e_28b7);
+        assert_eq!(murmur3_32(b"hello", 0), 0x248b_fa47);
+        assert_eq!(murmur3_32(b"The quick brown fox jumps over the lazy dog", 0), 0x2e4f_f723);
+    }
+
+    #[test]
+    fn test_favicon_hash_wraps_base64_lines() {
+        // mmh3.hash(base64.encodebytes(b"foo")) == mmh3.hash(b"Zm9v\n")
+        assert_eq!(favicon_hash(b"foo"), murmur3_32(b"Zm9v\n", 0) as i32);
+
+        // 60 input bytes encode to 80 characters and wrap after 76
+        let data = [0u8; 60];
+        let mut expected = "A".repeat(76);
+        expected.push('\n');
+        expected.push_str("AAAA\n");
+        assert_eq!(favicon_hash(&data), murmur3_32(expected.as_bytes(), 0) as i32);
+    }
+
+    #[tokio::test]
+    async fn test_detect_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let body = "<div ng-version=\"17.0.1\"></div>";
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nServer: Caddy\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(reply.as_bytes()).await.unwrap();
+        });
+
+        let detector = WebTechDetector::new(Duration::from_secs(2)).unwrap();
+        let techs = detector.detect(addr, false).await;
+        assert_eq!(names(&techs), vec!["Caddy", "Angular"]);
+        assert_eq!(techs[1].version.as_deref(), Some("17.0.1"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_reuses_pooled_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = socket.read(&mut buf).await;
+                    let body = "<div ng-version=\"17.0.1\"></div>";
+                    let reply = format!("HTTP/1.1 200 OK\r\nServer: Caddy\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n", body.len(), body);
+                    socket.write_all(reply.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        // The connection the port scan found open
+        let pool = ConnectionPool::new(4, Duration::from_secs(5));
+        assert!(pool.checkin(addr, tokio::net::TcpStream::connect(addr).await.unwrap()));
+        let detector = WebTechDetector::new(Duration::from_secs(2)).unwrap().with_connection_pool(pool.clone());
+
+        let response = detector.fetch(addr, false, "/").await.unwrap();
+        assert_eq!((response.status, response.body.as_str()), (200, "<div ng-version=\"17.0.1\"></div>"));
+        assert_eq!(names(&detector.analyze(&response)), vec!["Caddy", "Angular"]);
+        assert_eq!((pool.reused_count(), accepted.load(std::sync::atomic::Ordering::SeqCst)), (1, 1));
+
+        // Nothing left in the pool: the client connects as before
+        assert!(detector.fetch(addr, false, "/").await.is_some());
+        assert_eq!((pool.reused_count(), accepted.load(std::sync::atomic::Ordering::SeqCst)), (1, 2));
+    }
+}
@@ -0,0 +1,529 @@
+//! Encrypted, authenticated wire protocol between the coordinator and its workers
+//!
+//! Connections run over TLS. The coordinator presents a self-signed
+//! certificate generated for the run ([`TlsIdentity`]), so there is no CA to
+//! set up; what makes a worker trust it is the token handshake, which covers
+//! the certificate's SHA-256 fingerprint.
+//!
+//! Every message is one frame: a 4-byte big-endian length, then the JSON
+//! encoded [`NodeMessage`]. A connection opens with a handshake on a
+//! shared [`ClusterToken`]: both ends send a fresh nonce and prove with an
+//! HMAC-SHA256 over both nonces and the certificate fingerprint that they
+//! hold the token, so neither a stray worker nor a fake coordinator gets
+//! past it, and a relay that terminates TLS with a certificate of its own
+//! fails it too. The token itself never crosses the wire. The handshake
+//! derives a session key, and from then on each frame carries an HMAC over
+//! its direction, its sequence number and its payload: a frame that was
+//! altered, replayed, reordered or taken from another connection ends the
+//! connection.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, ServerName};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use uuid::Uuid;
+
+use super::core::{IntelligenceResult, NetworkIntelligenceError};
+use super::distributed::{NodeMessage, NodeMetrics, ScanResult, WorkerNode};
+
+/// Bumped whenever frames or the handshake change incompatibly
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Longest a handshake may take before the connection is dropped
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest frame accepted; a task or result of a whole /16 fits easily
+const MAX_FRAME: usize = 16 << 20;
+
+/// Largest frame accepted before the peer has proven it holds the token
+const MAX_HANDSHAKE_FRAME: usize = 4 << 10;
+
+/// Name the coordinator's certificate is issued to; workers do not check it
+const CERTIFICATE_NAME: &str = "phobos-coordinator";
+
+const MAC_LEN: usize = 32;
+
+/// Frames from a worker to the coordinator
+const TO_COORDINATOR: u8 = 1;
+/// Frames from the coordinator to a worker
+const TO_WORKER: u8 = 2;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secret shared by a coordinator and the workers allowed to join it
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClusterToken(String);
+
+impl ClusterToken {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// A random token, for a coordinator not given one
+    pub fn generate() -> Self {
+        let bytes: [u8; 32] = rand::random();
+        Self(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// The secret, to hand to workers
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    fn mac(&self, label: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+        keyed_mac(self.0.as_bytes(), label, parts)
+    }
+}
+
+impl std::fmt::Debug for ClusterToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ClusterToken(<redacted>)")
+    }
+}
+
+fn keyed_mac(key: &[u8], label: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(label);
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn verify(key: &[u8], label: &[u8], parts: &[&[u8]], tag: &[u8]) -> bool {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(label);
+    for part in parts {
+        mac.update(part);
+    }
+    mac.verify_slice(tag).is_ok()
+}
+
+fn wire_error(message: impl Into<String>) -> NetworkIntelligenceError {
+    NetworkIntelligenceError::DistributedError(message.into())
+}
+
+/// Messages of the unauthenticated opening of a connection
+#[derive(Debug, Serialize, Deserialize)]
+enum Handshake {
+    /// Worker to coordinator
+    Hello { version: u16, node_id: Uuid, nonce: String },
+    /// Coordinator to worker: its nonce, and proof that it holds the token
+    Challenge { version: u16, nonce: String, proof: String },
+    /// Worker to coordinator: proof that it holds the token
+    Proof { proof: String },
+    Rejected { reason: String },
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(text: &str) -> IntelligenceResult<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|_| wire_error("malformed handshake"))
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, body: &[u8]) -> IntelligenceResult<()> {
+    let len = u32::try_from(body.len()).ok().filter(|len| *len as usize <= MAX_FRAME);
+    let len = len.ok_or_else(|| wire_error(format!("frame of {} bytes is too large", body.len())))?;
+    writer.write_all(&len.to_be_bytes()).await.map_err(|e| wire_error(format!("write error: {}", e)))?;
+    writer.write_all(body).await.map_err(|e| wire_error(format!("write error: {}", e)))?;
+    writer.flush().await.map_err(|e| wire_error(format!("write error: {}", e)))
+}
+
+/// The next frame's body, at most `limit` bytes, or `None` if the peer
+/// closed the connection between frames
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R, limit: usize) -> IntelligenceResult<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(wire_error(format!("read error: {}", e))),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > limit {
+        return Err(wire_error(format!("frame of {} bytes is too large", len)));
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await.map_err(|e| wire_error(format!("read error: {}", e)))?;
+    Ok(Some(body))
+}
+
+async fn send_handshake<W: AsyncWrite + Unpin>(writer: &mut W, message: &Handshake) -> IntelligenceResult<()> {
+    write_frame(writer, &serde_json::to_vec(message).map_err(|e| wire_error(e.to_string()))?).await
+}
+
+async fn recv_handshake<R: AsyncRead + Unpin, T: DeserializeOwned>(reader: &mut R) -> IntelligenceResult<T> {
+    let body = read_frame(reader, MAX_HANDSHAKE_FRAME).await?.ok_or_else(|| wire_error("connection closed during handshake"))?;
+    serde_json::from_slice(&body).map_err(|_| wire_error("malformed handshake"))
+}
+
+/// Receiving end of an authenticated connection
+pub struct FrameReader<R> {
+    inner: R,
+    key: Vec<u8>,
+    direction: u8,
+    seq: u64,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// The next message, or `None` once the peer has closed the connection
+    ///
+    /// A frame that fails authentication is an error, and the connection
+    /// should be dropped: nothing after it can be trusted.
+    pub async fn recv(&mut self) -> IntelligenceResult<Option<NodeMessage>> {
+        let Some(mut body) = read_frame(&mut self.inner, MAX_FRAME).await? else { return Ok(None) };
+        if body.len() < MAC_LEN {
+            return Err(wire_error("frame too short to be authenticated"));
+        }
+        let tag = body.split_off(body.len() - MAC_LEN);
+        if !verify(&self.key, &[self.direction], &[&self.seq.to_be_bytes(), &body], &tag) {
+            return Err(wire_error("frame failed authentication"));
+        }
+        self.seq += 1;
+        serde_json::from_slice(&body).map(Some).map_err(|e| wire_error(format!("malformed message: {}", e)))
+    }
+}
+
+/// Sending end of an authenticated connection
+pub struct FrameWriter<W> {
+    inner: W,
+    key: Vec<u8>,
+    direction: u8,
+    seq: u64,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub async fn send(&mut self, message: &NodeMessage) -> IntelligenceResult<()> {
+        let mut body = serde_json::to_vec(message).map_err(|e| wire_error(e.to_string()))?;
+        let tag = keyed_mac(&self.key, &[self.direction], &[&self.seq.to_be_bytes(), &body]);
+        body.extend_from_slice(&tag);
+        write_frame(&mut self.inner, &body).await?;
+        self.seq += 1;
+        Ok(())
+    }
+}
+
+/// Both ends of an authenticated connection over `S`
+pub type Connection<S> = (FrameReader<ReadHalf<S>>, FrameWriter<WriteHalf<S>>);
+
+fn session<S: AsyncRead + AsyncWrite>(stream: S, key: Vec<u8>, receives: u8, sends: u8) -> Connection<S> {
+    let (reader, writer) = tokio::io::split(stream);
+    (
+        FrameReader { inner: reader, key: key.clone(), direction: receives, seq: 0 },
+        FrameWriter { inner: writer, key, direction: sends, seq: 0 },
+    )
+}
+
+/// Authenticate a worker connecting to the coordinator; returns the node
+/// id the worker introduced itself with
+///
+/// `channel` identifies the underlying TLS session, the coordinator
+/// certificate's fingerprint; both ends must see the same one.
+pub async fn accept<S>(mut stream: S, token: &ClusterToken, channel: &[u8]) -> IntelligenceResult<(Uuid, Connection<S>)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Handshake::Hello { version, node_id, nonce } = recv_handshake(&mut stream).await? else {
+        return Err(wire_error("expected a hello"));
+    };
+    if version != PROTOCOL_VERSION {
+        let reason = format!("protocol version {} is not supported, the coordinator speaks {}", version, PROTOCOL_VERSION);
+        send_handshake(&mut stream, &Handshake::Rejected { reason: reason.clone() }).await?;
+        return Err(wire_error(reason));
+    }
+    let worker_nonce = decode(&nonce)?;
+    let our_nonce: [u8; 32] = rand::random();
+    let parts: [&[u8]; 4] = [&worker_nonce, &our_nonce, node_id.as_bytes(), channel];
+    send_handshake(&mut stream, &Handshake::Challenge {
+        version: PROTOCOL_VERSION,
+        nonce: encode(&our_nonce),
+        proof: encode(&token.mac(b"phobos coordinator", &parts)),
+    })
+    .await?;
+
+    let Handshake::Proof { proof } = recv_handshake(&mut stream).await? else {
+        return Err(wire_error("expected a proof"));
+    };
+    if !verify(token.0.as_bytes(), b"phobos worker", &parts, &decode(&proof)?) {
+        let _ = send_handshake(&mut stream, &Handshake::Rejected { reason: "wrong cluster token".to_string() }).await;
+        return Err(wire_error(format!("node {} does not hold the cluster token", node_id)));
+    }
+    let key = token.mac(b"phobos session", &parts);
+    Ok((node_id, session(stream, key, TO_COORDINATOR, TO_WORKER)))
+}
+
+/// Authenticate to the coordinator as `node_id` over the session `channel`
+/// identifies, see [`accept`]
+pub async fn connect<S>(mut stream: S, token: &ClusterToken, node_id: Uuid, channel: &[u8]) -> IntelligenceResult<Connection<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let our_nonce: [u8; 32] = rand::random();
+    send_handshake(&mut stream, &Handshake::Hello { version: PROTOCOL_VERSION, node_id, nonce: encode(&our_nonce) }).await?;
+    let (nonce, proof) = match recv_handshake(&mut stream).await? {
+        Handshake::Challenge { version, nonce, proof } if version == PROTOCOL_VERSION => (decode(&nonce)?, decode(&proof)?),
+        Handshake::Rejected { reason } => return Err(wire_error(format!("coordinator refused the connection: {}", reason))),
+        _ => return Err(wire_error("expected a challenge")),
+    };
+    let parts: [&[u8]; 4] = [&our_nonce, &nonce, node_id.as_bytes(), channel];
+    // Checked first, so a fake coordinator learns nothing from our proof
+    if !verify(token.0.as_bytes(), b"phobos coordinator", &parts, &proof) {
+        return Err(wire_error("coordinator does not hold the cluster token"));
+    }
+    send_handshake(&mut stream, &Handshake::Proof { proof: encode(&token.mac(b"phobos worker", &parts)) }).await?;
+    let key = token.mac(b"phobos session", &parts);
+    Ok(session(stream, key, TO_WORKER, TO_COORDINATOR))
+}
+
+/// A worker connection as the coordinator sees it
+pub type CoordinatorStream = tokio_rustls::server::TlsStream<TcpStream>;
+/// The coordinator connection as a worker sees it
+pub type WorkerStream = tokio_rustls::client::TlsStream<TcpStream>;
+
+fn fingerprint(certificate: &Certificate) -> [u8; 32] {
+    Sha256::digest(&certificate.0).into()
+}
+
+/// The coordinator's TLS certificate and key, generated per run
+#[derive(Clone)]
+pub struct TlsIdentity {
+    acceptor: TlsAcceptor,
+    fingerprint: [u8; 32],
+}
+
+impl TlsIdentity {
+    /// A fresh self-signed certificate
+    pub fn generate() -> IntelligenceResult<Self> {
+        let generated = rcgen::generate_simple_self_signed(vec![CERTIFICATE_NAME.to_string()])
+            .map_err(|e| wire_error(format!("cannot generate a TLS certificate: {}", e)))?;
+        let certificate = Certificate(
+            generated.serialize_der().map_err(|e| wire_error(format!("cannot generate a TLS certificate: {}", e)))?,
+        );
+        let key = PrivateKey(generated.serialize_private_key_der());
+        let fingerprint = fingerprint(&certificate);
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate], key)
+            .map_err(|e| wire_error(format!("unusable TLS certificate: {}", e)))?;
+        Ok(Self { acceptor: TlsAcceptor::from(Arc::new(config)), fingerprint })
+    }
+
+    /// SHA-256 of the certificate, as workers see it
+    pub fn fingerprint(&self) -> [u8; 32] {
+        self.fingerprint
+    }
+}
+
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity").field("fingerprint", &encode(&self.fingerprint)).finish()
+    }
+}
+
+/// Accepts the coordinator's certificate as presented: rustls still checks
+/// that the peer holds its key, and the token handshake over its
+/// fingerprint decides whether it is the right coordinator
+struct TokenBoundCertificate;
+
+impl rustls::client::ServerCertVerifier for TokenBoundCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+
+/// Take a worker's TLS connection and authenticate it, see [`accept`]
+pub async fn accept_tls(stream: TcpStream, identity: &TlsIdentity, token: &ClusterToken) -> IntelligenceResult<(Uuid, Connection<CoordinatorStream>)> {
+    let stream = identity.acceptor.accept(stream).await.map_err(|e| wire_error(format!("TLS handshake failed: {}", e)))?;
+    accept(stream, token, &identity.fingerprint).await
+}
+
+/// Open a TLS connection to the coordinator and authenticate, see [`connect`]
+pub async fn connect_tls(stream: TcpStream, token: &ClusterToken, node_id: Uuid) -> IntelligenceResult<Connection<WorkerStream>> {
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(TokenBoundCertificate))
+        .with_no_client_auth();
+    let name = ServerName::try_from(CERTIFICATE_NAME).expect("a valid DNS name");
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .map_err(|e| wire_error(format!("TLS handshake failed: {}", e)))?;
+    let channel = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|chain| chain.first())
+        .map(fingerprint)
+        .ok_or_else(|| wire_error("coordinator presented no certificate"))?;
+    connect(stream, token, node_id, &channel).await
+}
+
+/// A worker's connection to its coordinator
+pub struct WorkerLink {
+    node_id: Uuid,
+    reader: FrameReader<ReadHalf<WorkerStream>>,
+    writer: FrameWriter<WriteHalf<WorkerStream>>,
+}
+
+impl WorkerLink {
+    /// Connect to the coordinator at `coordinator` and register `node`
+    pub async fn connect(coordinator: SocketAddr, token: &ClusterToken, node: WorkerNode) -> IntelligenceResult<Self> {
+        let stream = TcpStream::connect(coordinator)
+            .await
+            .map_err(|e| wire_error(format!("cannot reach coordinator {}: {}", coordinator, e)))?;
+        let node_id = node.id;
+        let (mut reader, mut writer) = tokio::time::timeout(HANDSHAKE_TIMEOUT, connect_tls(stream, token, node_id))
+            .await
+            .map_err(|_| wire_error("handshake timed out"))??;
+        writer.send(&NodeMessage::RegisterNode { node_info: node }).await?;
+        match reader.recv().await? {
+            Some(NodeMessage::RegistrationResponse { accepted: true, .. }) => Ok(Self { node_id, reader, writer }),
+            Some(NodeMessage::RegistrationResponse { accepted: false, .. }) => Err(wire_error("coordinator refused the registration")),
+            _ => Err(wire_error("coordinator did not answer the registration")),
+        }
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    /// Tell the coordinator this worker is alive, and how busy it is
+    pub async fn heartbeat(&mut self, current_load: usize, performance_metrics: NodeMetrics) -> IntelligenceResult<()> {
+        self.writer
+            .send(&NodeMessage::Heartbeat { node_id: self.node_id, timestamp: SystemTime::now(), current_load, performance_metrics })
+            .await
+    }
+
+    /// The coordinator's next message, or `None` once it has hung up
+    pub async fn recv(&mut self) -> IntelligenceResult<Option<NodeMessage>> {
+        self.reader.recv().await
+    }
+
+    /// Confirm that a task arrived and is being worked on
+    pub async fn acknowledge(&mut self, task_id: Uuid) -> IntelligenceResult<()> {
+        self.writer.send(&NodeMessage::TaskAck { task_id, node_id: self.node_id }).await
+    }
+
+    pub async fn send_result(&mut self, result: ScanResult) -> IntelligenceResult<()> {
+        self.writer.send(&NodeMessage::TaskResult { result }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn pair(coordinator: &ClusterToken, worker: &ClusterToken) -> (IntelligenceResult<Uuid>, IntelligenceResult<()>) {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let node = Uuid::new_v4();
+        let (accepted, connected) = tokio::join!(accept(a, coordinator, b"cert"), connect(b, worker, node, b"cert"));
+        (accepted.map(|(id, _)| id), connected.map(|_| ()))
+    }
+
+    #[tokio::test]
+    async fn test_handshake_needs_the_same_token() {
+        let token = ClusterToken::new("s3cret");
+        let (accepted, connected) = pair(&token, &token).await;
+        assert!(accepted.is_ok() && connected.is_ok());
+
+        // A worker with the wrong token is turned away, and so is a worker
+        // talking to a coordinator with the wrong one
+        let (accepted, connected) = pair(&token, &ClusterToken::new("guess")).await;
+        assert!(accepted.is_err());
+        assert!(connected.unwrap_err().to_string().contains("coordinator does not hold the cluster token"));
+        assert_ne!(ClusterToken::generate(), ClusterToken::generate());
+        assert_eq!(format!("{:?}", token), "ClusterToken(<redacted>)");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_is_bound_to_the_tls_session() {
+        // Ends that saw different certificates, as behind a relay that
+        // terminates TLS itself, do not get through even with the token
+        let token = ClusterToken::new("s3cret");
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let (accepted, connected) = tokio::join!(accept(a, &token, b"coordinator cert"), connect(b, &token, Uuid::new_v4(), b"relay cert"));
+        assert!(accepted.is_err());
+        assert!(connected.err().unwrap().to_string().contains("coordinator does not hold the cluster token"));
+
+        // Over real TLS both ends see the coordinator's certificate
+        let identity = TlsIdentity::generate().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let node = Uuid::new_v4();
+        let coordinator = async {
+            let (stream, _) = listener.accept().await.unwrap();
+            accept_tls(stream, &identity, &token).await
+        };
+        let worker = async { connect_tls(TcpStream::connect(address).await.unwrap(), &token, node).await };
+        let (accepted, connected) = tokio::join!(coordinator, worker);
+        let (id, (mut from_worker, _)) = accepted.unwrap();
+        let (_, mut to_coordinator) = connected.unwrap();
+        assert_eq!(id, node);
+        to_coordinator.send(&NodeMessage::HealthCheck).await.unwrap();
+        assert!(matches!(from_worker.recv().await.unwrap(), Some(NodeMessage::HealthCheck)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_frames_are_small() {
+        // A peer that has not authenticated cannot make us allocate a full frame
+        let (mut a, b) = tokio::io::duplex(64 * 1024);
+        a.write_all(&(MAX_FRAME as u32).to_be_bytes()).await.unwrap();
+        let error = accept(b, &ClusterToken::new("s3cret"), b"cert").await.err().unwrap();
+        assert!(error.to_string().contains("too large"));
+    }
+
+    #[tokio::test]
+    async fn test_frames_are_authenticated_in_order() {
+        let token = ClusterToken::new("s3cret");
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let node = Uuid::new_v4();
+        let (accepted, connected) = tokio::join!(accept(a, &token, b"cert"), connect(b, &token, node, b"cert"));
+        let (_, (mut from_worker, mut to_worker)) = accepted.unwrap();
+        let (mut from_coordinator, mut to_coordinator) = connected.unwrap();
+
+        let ack = NodeMessage::TaskAck { task_id: Uuid::new_v4(), node_id: node };
+        to_coordinator.send(&ack).await.unwrap();
+        to_coordinator.send(&NodeMessage::HealthCheck).await.unwrap();
+        assert!(matches!(from_worker.recv().await.unwrap(), Some(NodeMessage::TaskAck { node_id, .. }) if node_id == node));
+        assert!(matches!(from_worker.recv().await.unwrap(), Some(NodeMessage::HealthCheck)));
+        to_worker.send(&NodeMessage::HealthCheck).await.unwrap();
+        assert!(matches!(from_coordinator.recv().await.unwrap(), Some(NodeMessage::HealthCheck)));
+
+        // The worker's first frame replayed: right key, wrong sequence number
+        let mut replay = serde_json::to_vec(&ack).unwrap();
+        replay.extend(keyed_mac(&to_coordinator.key, &[TO_COORDINATOR], &[&0u64.to_be_bytes(), &replay.clone()]));
+        write_frame(&mut to_coordinator.inner, &replay).await.unwrap();
+        assert!(from_worker.recv().await.unwrap_err().to_string().contains("failed authentication"));
+
+        // An altered frame
+        let mut altered = serde_json::to_vec(&NodeMessage::HealthCheck).unwrap();
+        altered.extend(keyed_mac(&to_worker.key, &[TO_WORKER], &[&1u64.to_be_bytes(), &altered.clone()]));
+        altered[0] ^= 1;
+        write_frame(&mut to_worker.inner, &altered).await.unwrap();
+        assert!(from_coordinator.recv().await.is_err());
+
+        drop(to_worker);
+        drop(from_worker);
+        assert!(from_coordinator.recv().await.unwrap().is_none());
+    }
+}
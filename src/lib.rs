@@ -9,10 +9,13 @@ pub mod core;        // New modular scanner traits
 pub mod engines;     // New execution engines
 pub mod error;
 pub mod gpu;
+#[cfg(feature = "intelligence")]
 pub mod intelligence;
+pub mod monitor;
 pub mod network;
 pub mod output;
 pub mod scanner;
+#[cfg(feature = "scripts")]
 pub mod scripts;
 pub mod top_ports;
 pub mod utils;
@@ -22,11 +25,32 @@ pub use adaptive::{AdaptiveConfig, AdaptiveResult, LearningInsights, ScanStats,
 pub use benchmark::{Benchmark, NamedTimer};
 pub use error::{ScanError, ScanResult};
 pub use config::ScanConfig;
+#[cfg(feature = "intelligence")]
 pub use intelligence::{IntelligenceEngine, IntelligenceConfig, IntelligenceResults};
 pub use network::ScanTechnique;
 pub use scanner::engine::ScanEngine;
+pub use scanner::HostResult;
+#[cfg(feature = "scripts")]
 pub use scripts::engine::ScriptEngine;
+#[cfg(feature = "scripts")]
 pub use scripts::{ScriptConfig, ScriptMode, ScriptResult as ScriptExecutionResult};
 pub use top_ports::{get_top_1000_ports, get_top_ports};
 
-pub type Result<T> = std::result::Result<T, ScanError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, ScanError>;
+/// Optional subsystems and whether this build carries them; a `minimal`
+/// build has none of them
+pub const SUBSYSTEMS: &[(&str, bool)] = &[
+    ("intelligence", cfg!(feature = "intelligence")),
+    ("distributed", cfg!(feature = "distributed")),
+    ("scripts", cfg!(feature = "scripts")),
+    ("web", cfg!(feature = "web")),
+    ("parquet", cfg!(feature = "parquet")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("profiling", cfg!(feature = "profiling")),
+    ("gpu", cfg!(feature = "gpu")),
+    ("native-tls", cfg!(feature = "native-tls")),
+    ("rustls", cfg!(feature = "rustls")),
+];
+
+#[cfg(all(feature = "web", not(any(feature = "native-tls", feature = "rustls"))))]
+compile_error!("the `web` feature needs a TLS backend: enable `native-tls` or `rustls`");
@@ -0,0 +1,153 @@
+//! What changed between two runs of a watch
+//!
+//! Besides the terminal text, a [`ScanDiff`] is written as JSON or CSV with
+//! `--diff-format`, for alerting that reads it instead of a person.
+
+use super::history::{OpenPort, Snapshot};
+use crate::output::notify::NotificationType;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// Ports that opened, closed or changed service from one run to the next
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanDiff {
+    pub opened: Vec<OpenPort>,
+    /// Ports no longer open on a host both runs scanned; a host left out of
+    /// the newer run says nothing about its ports
+    pub closed: Vec<OpenPort>,
+    /// Ports whose service or version changed
+    pub changed: Vec<ChangedPort>,
+}
+
+/// The same port before and after its service or version changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedPort {
+    pub before: OpenPort,
+    pub after: OpenPort,
+}
+
+impl ScanDiff {
+    pub fn between(before: &Snapshot, after: &Snapshot) -> Self {
+        let key = |p: &OpenPort| (p.host.clone(), p.protocol.clone(), p.port);
+        let old: BTreeMap<_, &OpenPort> = before.open.iter().map(|p| (key(p), p)).collect();
+        let new: BTreeMap<_, &OpenPort> = after.open.iter().map(|p| (key(p), p)).collect();
+        let rescanned: BTreeSet<&str> = after.hosts.iter().map(String::as_str).collect();
+
+        let mut diff = Self::default();
+        for (key, port) in &new {
+            match old.get(key) {
+                None => diff.opened.push((*port).clone()),
+                Some(was) if (&was.service, &was.version) != (&port.service, &port.version) => {
+                    diff.changed.push(ChangedPort { before: (*was).clone(), after: (*port).clone() })
+                }
+                Some(_) => {}
+            }
+        }
+        diff.closed = old
+            .iter()
+            .filter(|(key, port)| !new.contains_key(*key) && rescanned.contains(port.host.as_str()))
+            .map(|(_, port)| (*port).clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.opened.is_empty() && self.closed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Events for the ports that opened or changed service; closing ports
+    /// shrink the attack surface and are only reported
+    pub fn notifications(&self) -> Vec<NotificationType> {
+        let opened = self.opened.iter().map(|p| NotificationType::PortOpened {
+            target: p.host.clone(),
+            port: p.port,
+            service: p.service.clone(),
+        });
+        let changed = self.changed.iter().map(|ChangedPort { before, after }| NotificationType::ServiceChanged {
+            target: after.host.clone(),
+            port: after.port,
+            before: describe(before),
+            after: describe(after),
+        });
+        opened.chain(changed).collect()
+    }
+
+    /// The diff as one line of JSON
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// One CSV row per port, under a header row if `header`
+    pub fn to_csv(&self, header: bool) -> io::Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        if header {
+            writer.write_record(["change", "host", "port", "protocol", "before", "after"])?;
+        }
+        for port in &self.opened {
+            writer.write_record(["opened", &port.host, &port.port.to_string(), &port.protocol, "", &describe(port)])?;
+        }
+        for port in &self.closed {
+            writer.write_record(["closed", &port.host, &port.port.to_string(), &port.protocol, &describe(port), ""])?;
+        }
+        for ChangedPort { before, after } in &self.changed {
+            writer.write_record([
+                "changed",
+                &after.host,
+                &after.port.to_string(),
+                &after.protocol,
+                &describe(before),
+                &describe(after),
+            ])?;
+        }
+        let bytes = writer.into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// `ssh OpenSSH 9.6`, or `unknown` for a port nothing was identified on
+pub fn describe(port: &OpenPort) -> String {
+    match (&port.service, &port.version) {
+        (Some(service), Some(version)) => format!("{} {}", service, version),
+        (Some(service), None) => service.clone(),
+        (None, Some(version)) => version.clone(),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(port: u16, service: &str) -> OpenPort {
+        OpenPort {
+            host: "10.0.0.1".to_string(),
+            port,
+            protocol: "tcp".to_string(),
+            service: Some(service.to_string()),
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_round_trips_through_json_and_writes_csv() {
+        let diff = ScanDiff {
+            opened: vec![port(8080, "http")],
+            closed: vec![port(23, "telnet")],
+            changed: vec![ChangedPort { before: port(22, "ssh"), after: port(22, "dropbear") }],
+        };
+        let json = diff.to_json().unwrap();
+        assert!(!json.contains('\n'));
+        assert_eq!(serde_json::from_str::<ScanDiff>(&json).unwrap(), diff);
+
+        let csv = diff.to_csv(true).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows, [
+            "change,host,port,protocol,before,after",
+            "opened,10.0.0.1,8080,tcp,,http",
+            "closed,10.0.0.1,23,tcp,telnet,",
+            "changed,10.0.0.1,22,tcp,ssh,dropbear",
+        ]);
+        assert_eq!(diff.to_csv(false).unwrap().lines().count(), 3);
+    }
+}
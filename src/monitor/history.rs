@@ -0,0 +1,130 @@
+//! What each `phobos watch` run found, kept on disk
+//!
+//! Every run of a watch is one JSON [`Snapshot`] in the watch's own
+//! directory under `~/.phobos/history`, named after when it ran so the
+//! files sort oldest first.
+
+use crate::scanner::ScanResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A port one run found open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenPort {
+    pub host: String,
+    pub port: u16,
+    pub protocol: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// The open ports of one run, and the hosts it covered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub scanned_at: DateTime<Utc>,
+    pub hosts: Vec<String>,
+    pub open: Vec<OpenPort>,
+}
+
+impl Snapshot {
+    pub fn from_scan(result: &ScanResult) -> Self {
+        let mut hosts = Vec::new();
+        let mut open = Vec::new();
+        for host in result.per_host() {
+            for port in host.port_results.iter().filter(|p| p.state == crate::network::PortState::Open) {
+                open.push(OpenPort {
+                    host: host.target.clone(),
+                    port: port.port,
+                    protocol: port.protocol.as_str().to_string(),
+                    service: port.service.clone(),
+                    version: port.version.clone(),
+                });
+            }
+            hosts.push(host.target);
+        }
+        Self { scanned_at: Utc::now(), hosts, open }
+    }
+}
+
+fn read(path: &Path) -> io::Result<Snapshot> {
+    serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+}
+
+/// Where watches keep their runs by default, next to the checkpoints
+pub fn default_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".phobos").join("history")
+}
+
+/// The stored runs of one watch
+#[derive(Debug, Clone)]
+pub struct HistoryManager {
+    dir: PathBuf,
+}
+
+impl HistoryManager {
+    /// Runs of the watch `name` under `root`; the directory is created on
+    /// the first [`record`](Self::record)
+    pub fn open(root: &Path, name: &str) -> Self {
+        Self { dir: root.join(name) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store `snapshot` as the newest run
+    pub fn record(&self, snapshot: &Snapshot) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.json", snapshot.scanned_at.format("%Y%m%dT%H%M%S%.3fZ")));
+        let json = serde_json::to_string_pretty(snapshot).map_err(io::Error::from)?;
+        // Written aside and renamed so a crash never leaves half a run behind
+        let partial = path.with_extension("json.tmp");
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    /// Files of the stored runs, oldest first
+    pub fn runs(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut runs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        runs.sort();
+        Ok(runs)
+    }
+
+    /// The newest stored run, if any
+    pub fn latest(&self) -> io::Result<Option<Snapshot>> {
+        let Some(path) = self.runs()?.pop() else { return Ok(None) };
+        read(&path).map(Some)
+    }
+
+    /// The two newest stored runs, older first, once there are two
+    pub fn latest_pair(&self) -> io::Result<Option<(Snapshot, Snapshot)>> {
+        let runs = self.runs()?;
+        let [.., before, after] = runs.as_slice() else { return Ok(None) };
+        Ok(Some((read(before)?, read(after)?)))
+    }
+
+    /// Delete all but the newest `keep` runs; returns how many went
+    pub fn prune(&self, keep: usize) -> io::Result<usize> {
+        let runs = self.runs()?;
+        let stale = runs.len().saturating_sub(keep);
+        for path in &runs[..stale] {
+            fs::remove_file(path)?;
+        }
+        Ok(stale)
+    }
+}
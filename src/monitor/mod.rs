@@ -0,0 +1,182 @@
+//! `phobos watch`: the same scan again and again, reporting what changed
+//!
+//! A [`Watch`] runs its scan at once and then on a [`Schedule`], stores
+//! each run with the [`HistoryManager`], and compares it with the run
+//! before. Ports that opened and services that changed are published as
+//! notifications, so a webhook hears about new exposure within one run of
+//! it appearing. The history outlives the process: a watch restarted
+//! tomorrow compares its first run with the last one from today.
+
+pub mod diff;
+pub mod history;
+pub mod schedule;
+
+pub use diff::{ChangedPort, ScanDiff};
+pub use history::{HistoryManager, OpenPort, Snapshot};
+pub use schedule::Schedule;
+
+use crate::config::ScanConfig;
+use crate::output::notify::{NotificationManager, NotificationType};
+use crate::scanner::CancellationToken;
+use chrono::{DateTime, Local};
+use sha2::Digest;
+use std::future::Future;
+
+/// The history name of a watch over `config`'s targets and ports: the
+/// campaign when there is one, so a campaign's watches share a history
+pub fn watch_name(config: &ScanConfig) -> String {
+    if let Some(campaign) = &config.campaign {
+        return sanitize(campaign);
+    }
+    let targets = config.all_targets().join(",");
+    let ports: Vec<String> = config.ports.iter().map(u16::to_string).collect();
+    let digest = sha2::Sha256::digest(format!("{}|{}|{}", targets, config.technique.protocol().as_str(), ports.join(",")));
+    let hash: String = digest.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+    let mut label: String = sanitize(&targets).chars().take(40).collect();
+    label.push('-');
+    label.push_str(&hash);
+    label
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).collect()
+}
+
+/// One finished run
+#[derive(Debug, Clone)]
+pub struct WatchRun {
+    pub snapshot: Snapshot,
+    /// Changes since the previous run; `None` for a watch's very first one
+    pub diff: Option<ScanDiff>,
+    /// When the next run starts, if the schedule has one
+    pub next: Option<DateTime<Local>>,
+}
+
+/// A scan repeated on a schedule
+pub struct Watch {
+    schedule: Schedule,
+    history: HistoryManager,
+    notifications: NotificationManager,
+}
+
+impl Watch {
+    pub fn new(schedule: Schedule, history: HistoryManager, notifications: NotificationManager) -> Self {
+        Self { schedule, history, notifications }
+    }
+
+    pub fn history(&self) -> &HistoryManager {
+        &self.history
+    }
+
+    /// Store `snapshot` and publish what changed since the stored run before it
+    pub async fn record(&self, snapshot: &Snapshot) -> crate::Result<Option<ScanDiff>> {
+        let previous = self.history.latest()?;
+        self.history.record(snapshot)?;
+        let Some(previous) = previous else { return Ok(None) };
+        let diff = ScanDiff::between(&previous, snapshot);
+        for event in diff.notifications() {
+            self.notifications.notify(event).await;
+        }
+        Ok(Some(diff))
+    }
+
+    /// Run `scan` now and at every scheduled time after, until `cancel`
+    ///
+    /// A run that fails is handed to `on_run` and published as an error;
+    /// the watch carries on with the next one.
+    pub async fn run<F, Fut>(
+        &self,
+        cancel: &CancellationToken,
+        mut scan: F,
+        mut on_run: impl FnMut(crate::Result<WatchRun>),
+    ) -> crate::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = crate::Result<Snapshot>>,
+    {
+        loop {
+            let outcome = tokio::select! {
+                _ = cancel.cancelled() => break,
+                outcome = scan() => outcome,
+            };
+            let next = self.schedule.next_after(&Local::now());
+            let outcome = match outcome {
+                Ok(snapshot) => self.record(&snapshot).await.map(|diff| WatchRun { snapshot, diff, next }),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &outcome {
+                let target = self.history.dir().file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                self.notifications.notify(NotificationType::Error { target, error: e.to_string() }).await;
+            }
+            self.notifications.flush().await;
+            on_run(outcome);
+
+            let Some(next) = next else { break };
+            let wait = (next - Local::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(wait) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn port(host: &str, port: u16, service: &str) -> OpenPort {
+        OpenPort {
+            host: host.to_string(),
+            port,
+            protocol: "tcp".to_string(),
+            service: Some(service.to_string()),
+            version: None,
+        }
+    }
+
+    fn snapshot(minute: u32, hosts: &[&str], open: Vec<OpenPort>) -> Snapshot {
+        Snapshot {
+            scanned_at: Utc.with_ymd_and_hms(2026, 3, 1, 10, minute, 0).unwrap(),
+            hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            open,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_records_and_diffs_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = HistoryManager::open(dir.path(), "lab");
+        let notifications = NotificationManager::new(16);
+        let mut events = notifications.subscribe();
+        let watch = Watch::new("@hourly".parse().unwrap(), history, notifications);
+
+        let first = snapshot(0, &["10.0.0.1", "10.0.0.2"], vec![port("10.0.0.1", 22, "ssh"), port("10.0.0.2", 80, "http")]);
+        assert_eq!(watch.record(&first).await.unwrap(), None);
+
+        // 10.0.0.2 was not scanned this time, so its port 80 is not "closed"
+        let second = snapshot(1, &["10.0.0.1"], vec![port("10.0.0.1", 22, "telnet"), port("10.0.0.1", 443, "https")]);
+        let diff = watch.record(&second).await.unwrap().unwrap();
+        assert_eq!(diff.opened, vec![port("10.0.0.1", 443, "https")]);
+        assert!(diff.closed.is_empty());
+        assert_eq!(diff.changed, vec![ChangedPort { before: port("10.0.0.1", 22, "ssh"), after: port("10.0.0.1", 22, "telnet") }]);
+        assert!(matches!(events.recv().await, Some(NotificationType::PortOpened { port: 443, .. })));
+        assert!(matches!(
+            events.recv().await,
+            Some(NotificationType::ServiceChanged { port: 22, ref before, ref after, .. }) if before == "ssh" && after == "telnet"
+        ));
+
+        let third = snapshot(2, &["10.0.0.1"], vec![port("10.0.0.1", 22, "telnet")]);
+        let diff = watch.record(&third).await.unwrap().unwrap();
+        assert_eq!(diff.closed, vec![port("10.0.0.1", 443, "https")]);
+        assert!(diff.notifications().is_empty());
+
+        assert_eq!(watch.history().runs().unwrap().len(), 3);
+        assert_eq!(watch.history().latest().unwrap(), Some(third.clone()));
+        assert_eq!(watch.history().latest_pair().unwrap(), Some((second, third)));
+        assert_eq!(watch.history().prune(1).unwrap(), 2);
+        assert_eq!(watch.history().latest_pair().unwrap(), None);
+    }
+}
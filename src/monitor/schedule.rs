@@ -0,0 +1,235 @@
+//! When `phobos watch` runs its scans
+//!
+//! A [`Schedule`] is either a fixed interval, `@every 30m`, or a cron
+//! expression: five fields for minute, hour, day of month, month and day of
+//! week, each `*`, a number, a range `a-b`, a step `*/n` or `a-b/n`, or a
+//! comma-separated list of them. Months and weekdays also take three-letter
+//! names, and Sunday is 0 or 7. `@hourly`, `@daily`, `@weekly` and
+//! `@monthly` stand for their usual expressions. As in cron, when both the
+//! day of month and the day of week are restricted, a day matching either
+//! one counts.
+
+use crate::scanner::progress::parse_interval;
+use crate::ScanError;
+use chrono::{DateTime, Datelike, Duration as TimeDelta, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Years searched for a matching time before a cron expression is
+/// considered unsatisfiable, like `0 0 31 2 *`
+const SEARCH_YEARS: i32 = 5;
+
+/// One cron field: the values it matches, as bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field {
+    bits: u64,
+    /// Whether it was anything but `*`
+    restricted: bool,
+}
+
+impl Field {
+    fn parse(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Self, String> {
+        let value = |text: &str| -> Result<u32, String> {
+            let lower = text.to_ascii_lowercase();
+            let named = names.iter().position(|name| *name == lower).map(|i| i as u32 + min);
+            match named.map(Ok).unwrap_or_else(|| text.parse::<u32>().map_err(|_| format!("`{}` is not a number", text))) {
+                Ok(v) if (min..=max).contains(&v) => Ok(v),
+                Ok(v) => Err(format!("{} is outside {}-{}", v, min, max)),
+                Err(e) => Err(e),
+            }
+        };
+        let mut bits = 0u64;
+        for item in field.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| format!("`{}` is not a step", step))?;
+                    if step == 0 {
+                        return Err("a step of 0 never repeats".to_string());
+                    }
+                    (range, step)
+                }
+                None => (item, 1),
+            };
+            let (start, end) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((start, end)) => (value(start)?, value(end)?),
+                    // `5/15` runs from 5 to the end, as in Vixie cron
+                    None if item.contains('/') => (value(range)?, max),
+                    None => {
+                        let v = value(range)?;
+                        (v, v)
+                    }
+                },
+            };
+            if start > end {
+                return Err(format!("`{}` is reversed", range));
+            }
+            for v in (start..=end).step_by(step as usize) {
+                bits |= 1 << v;
+            }
+        }
+        Ok(Self { bits, restricted: field != "*" })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.bits & (1 << value) != 0
+    }
+}
+
+/// A cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSpec {
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl CronSpec {
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = self.days.matches(date.day());
+        let weekday = self.weekdays.matches(date.weekday().num_days_from_sunday());
+        if self.days.restricted && self.weekdays.restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute after `after`, in local time
+    fn next_naive(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = after.year() + SEARCH_YEARS;
+        while t.year() <= limit {
+            if !self.months.matches(t.month()) {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !self.hours.matches(t.hour()) {
+                t = t.with_minute(0)? + TimeDelta::hours(1);
+            } else if !self.minutes.matches(t.minute()) {
+                t += TimeDelta::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+/// When to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSpec),
+}
+
+impl Schedule {
+    /// The first run time strictly after `after`, or `None` if there is
+    /// none in the next few years
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            Schedule::Every(interval) => Some(after.clone() + TimeDelta::from_std(*interval).ok()?),
+            Schedule::Cron(spec) => {
+                let mut from = after.naive_local();
+                loop {
+                    let next = spec.next_naive(from)?;
+                    // Times skipped by a DST change do not exist; try the next match
+                    match after.timezone().from_local_datetime(&next).earliest() {
+                        Some(next) if next > *after => return Some(next),
+                        _ => from = next,
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ScanError;
+
+    fn from_str(spec: &str) -> crate::Result<Self> {
+        let invalid = |reason: String| ScanError::ConfigError(format!("invalid schedule `{}`: {}", spec, reason));
+        let expression = match spec.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => match other.strip_prefix("@every") {
+                Some(interval) => {
+                    let interval = parse_interval(interval).map_err(|e| invalid(e.to_string()))?;
+                    if interval < Duration::from_secs(1) {
+                        return Err(invalid("the interval must be at least a second".to_string()));
+                    }
+                    return Ok(Schedule::Every(interval));
+                }
+                None => other,
+            },
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid(format!("expected 5 cron fields or @every INTERVAL, got {} fields", fields.len())));
+        };
+        let field = |name: &str, text: &str, min: u32, max: u32, names: &[&str]| {
+            Field::parse(text, min, max, names).map_err(|e| invalid(format!("{}: {}", name, e)))
+        };
+        let mut weekdays = field("day of week", weekdays, 0, 7, &WEEKDAYS)?;
+        // Sunday is both 0 and 7
+        if weekdays.matches(7) {
+            weekdays.bits |= 1;
+        }
+        Ok(Schedule::Cron(CronSpec {
+            minutes: field("minute", minutes, 0, 59, &[])?,
+            hours: field("hour", hours, 0, 23, &[])?,
+            days: field("day of month", days, 1, 31, &[])?,
+            months: field("month", months, 1, 12, &MONTHS)?,
+            weekdays,
+        }))
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Schedule::Cron(_) => f.write_str("cron"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_next_run_times() {
+        let next = |spec: &str, after: &str| spec.parse::<Schedule>().unwrap().next_after(&at(after)).map(|t| t.to_rfc3339());
+
+        assert_eq!(next("*/15 * * * *", "2026-03-01T10:07:30Z").unwrap(), "2026-03-01T10:15:00+00:00");
+        assert_eq!(next("@hourly", "2026-03-01T10:00:00Z").unwrap(), "2026-03-01T11:00:00+00:00");
+        // Weekdays at 02:30; 2026-03-06 is a Friday
+        assert_eq!(next("30 2 * * mon-fri", "2026-03-06T03:00:00Z").unwrap(), "2026-03-09T02:30:00+00:00");
+        // Either the 1st or a Sunday once both are restricted
+        assert_eq!(next("0 0 1 * 7", "2026-03-02T00:00:00Z").unwrap(), "2026-03-08T00:00:00+00:00");
+        assert_eq!(next("0 0 29 feb *", "2026-03-01T00:00:00Z").unwrap(), "2028-02-29T00:00:00+00:00");
+        assert_eq!(next("0 0 31 2 *", "2026-03-01T00:00:00Z"), None);
+        assert_eq!(next("@every 90s", "2026-03-01T10:00:00Z").unwrap(), "2026-03-01T10:01:30+00:00");
+
+        let error = |spec: &str| spec.parse::<Schedule>().unwrap_err().to_string();
+        assert!(error("* * *").contains("expected 5 cron fields"));
+        assert!(error("61 * * * *").contains("minute: 61 is outside 0-59"));
+        assert!(error("*/0 * * * *").contains("a step of 0"));
+    }
+}
@@ -0,0 +1,164 @@
+//! Protocol anomalies in probe replies
+//!
+//! A well-behaved stack answers a SYN with a SYN/ACK or a RST, a correct
+//! checksum and a sensible MSS. Replies that break those rules are not
+//! errors on their own, but they are rarely produced by the host itself:
+//! many of them across a scan point to a middlebox rewriting traffic, or to
+//! a tarpit or honeypot answering in the host's place.
+
+use super::packet::TcpResponse;
+use super::PortResult;
+use phobos_packet::tcp::{ACK, FIN, RST, SYN, URG};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Smallest MSS a real stack advertises (Linux's `TCP_MIN_MSS`)
+const MIN_PLAUSIBLE_MSS: u16 = 88;
+
+/// Largest MSS an IPv4 segment can carry
+const MAX_PLAUSIBLE_MSS: u16 = u16::MAX - 40;
+
+/// Something out of the ordinary in a reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anomaly {
+    /// The TCP checksum does not match the segment
+    BadChecksum,
+    /// A flag combination no stack sends in reply to a SYN
+    UnexpectedFlags,
+    /// An MSS option too small or too large to be real
+    BogusMss,
+    /// A RST carrying data
+    RstWithData,
+}
+
+impl Anomaly {
+    pub fn name(self) -> &'static str {
+        match self {
+            Anomaly::BadChecksum => "bad-checksum",
+            Anomaly::UnexpectedFlags => "unexpected-flags",
+            Anomaly::BogusMss => "bogus-mss",
+            Anomaly::RstWithData => "rst-with-data",
+        }
+    }
+
+    /// Anomalies in `reply`, a reply to a SYN probe
+    pub fn in_syn_reply(reply: &TcpResponse) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        if !reply.checksum_valid {
+            anomalies.push(Anomaly::BadChecksum);
+        }
+        let flags = reply.flags;
+        let contradictory = flags & (SYN | RST) == SYN | RST || flags & (SYN | FIN) == SYN | FIN;
+        let syn_without_ack = flags & SYN != 0 && flags & ACK == 0;
+        if flags == 0 || contradictory || syn_without_ack || flags & URG != 0 {
+            anomalies.push(Anomaly::UnexpectedFlags);
+        }
+        if reply.mss.is_some_and(|mss| !(MIN_PLAUSIBLE_MSS..=MAX_PLAUSIBLE_MSS).contains(&mss)) {
+            anomalies.push(Anomaly::BogusMss);
+        }
+        if flags & RST != 0 && reply.payload_len > 0 {
+            anomalies.push(Anomaly::RstWithData);
+        }
+        anomalies
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Anomalies counted over the ports of a scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnomalyStats {
+    /// Ports flagged with each anomaly
+    pub counts: BTreeMap<Anomaly, usize>,
+    /// Ports with at least one anomaly
+    pub flagged_ports: usize,
+    /// Ports that were scanned
+    pub total_ports: usize,
+}
+
+impl AnomalyStats {
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a PortResult>) -> Self {
+        let mut stats = Self::default();
+        for result in results {
+            stats.total_ports += 1;
+            if !result.anomalies.is_empty() {
+                stats.flagged_ports += 1;
+            }
+            for &anomaly in &result.anomalies {
+                *stats.counts.entry(anomaly).or_default() += 1;
+            }
+        }
+        stats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flagged_ports == 0
+    }
+
+    /// What the anomalies suggest, if they are more than a stray packet
+    pub fn interpretation(&self) -> Option<&'static str> {
+        let count = |anomaly| self.counts.get(&anomaly).copied().unwrap_or(0);
+        // A single odd reply is noise; a pattern across ports is not
+        if self.flagged_ports < 2 {
+            return None;
+        }
+        if count(Anomaly::BadChecksum) + count(Anomaly::BogusMss) >= count(Anomaly::UnexpectedFlags) + count(Anomaly::RstWithData) {
+            Some("replies are being rewritten on the way back; a middlebox is likely interfering")
+        } else {
+            Some("replies break TCP rules a real stack follows; the host may be a tarpit or honeypot")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{PortState, Protocol};
+    use std::net::Ipv4Addr;
+
+    fn reply(flags: u8) -> TcpResponse {
+        TcpResponse {
+            source_ip: Ipv4Addr::new(192, 0, 2, 1),
+            dest_ip: Ipv4Addr::new(192, 0, 2, 10),
+            ip_id: 0,
+            source_port: 443,
+            dest_port: 40000,
+            flags,
+            seq_num: 1,
+            ack_num: 2,
+            window_size: 65535,
+            timestamp: None,
+            mss: None,
+            payload_len: 0,
+            checksum_valid: true,
+        }
+    }
+
+    #[test]
+    fn test_anomalies_flagged_and_counted() {
+        assert!(Anomaly::in_syn_reply(&TcpResponse { mss: Some(1460), ..reply(SYN | ACK) }).is_empty());
+        assert!(Anomaly::in_syn_reply(&reply(RST | ACK)).is_empty());
+
+        assert_eq!(Anomaly::in_syn_reply(&reply(SYN | RST)), [Anomaly::UnexpectedFlags]);
+        assert_eq!(
+            Anomaly::in_syn_reply(&TcpResponse { mss: Some(16), checksum_valid: false, ..reply(SYN | ACK) }),
+            [Anomaly::BadChecksum, Anomaly::BogusMss]
+        );
+        assert_eq!(Anomaly::in_syn_reply(&TcpResponse { payload_len: 12, ..reply(RST) }), [Anomaly::RstWithData]);
+
+        let mut results = vec![PortResult::new(22, Protocol::Tcp, PortState::Open); 3];
+        results[0].anomalies = vec![Anomaly::BogusMss];
+        assert_eq!(AnomalyStats::from_results(&results).interpretation(), None);
+        results[1].anomalies = vec![Anomaly::BadChecksum, Anomaly::BogusMss];
+        let stats = AnomalyStats::from_results(&results);
+        assert_eq!((stats.flagged_ports, stats.total_ports), (2, 3));
+        assert_eq!(stats.counts[&Anomaly::BogusMss], 2);
+        assert!(stats.interpretation().unwrap().contains("middlebox"));
+    }
+}
@@ -0,0 +1,123 @@
+//! Bandwidth budgets (`--max-bandwidth`)
+//!
+//! Network teams think in bits per second, the rate limiter in probes per
+//! second. A budget is converted with the bytes a probe puts on the wire for
+//! the scan's technique and ports, counting Ethernet framing and every packet
+//! a probe can make us send, so the cap holds even when every port answers.
+
+use super::socket::{udp_probe_payload, UDP_ATTEMPTS};
+use super::ScanTechnique;
+use crate::ScanError;
+
+/// Ethernet header and FCS, preamble and inter-frame gap around every packet
+const FRAME_OVERHEAD: u64 = 38;
+
+/// Shortest IP packet an Ethernet frame carries without padding
+const MIN_FRAME_PAYLOAD: u64 = 46;
+
+/// IPv4 and TCP headers without options
+const TCP_PACKET: u64 = 40;
+
+/// Crafted probes carry an MSS option
+const MSS_OPTION: u64 = 4;
+
+/// A kernel SYN: MSS, SACK permitted, timestamp and window scale options
+const KERNEL_SYN: u64 = 60;
+
+/// A kernel ACK or FIN with the timestamp option
+const KERNEL_SEGMENT: u64 = 52;
+
+/// IPv4 and UDP headers
+const UDP_HEADERS: u64 = 28;
+
+/// Parse a bandwidth such as `50M`, `1.5G` or `800k` (SI prefixes, bits per
+/// second); a trailing `bps` or `bit/s` is allowed and bare numbers are bits
+pub fn parse_bandwidth(input: &str) -> crate::Result<u64> {
+    let invalid = || ScanError::ParseError(format!("invalid bandwidth '{}': expected e.g. 800k, 50M or 1G", input));
+    let trimmed = input.trim();
+    let number = trimmed.trim_end_matches("bps").trim_end_matches("bit/s");
+    let (number, multiplier) = match number.char_indices().last() {
+        Some((at, 'k' | 'K')) => (&number[..at], 1e3),
+        Some((at, 'm' | 'M')) => (&number[..at], 1e6),
+        Some((at, 'g' | 'G')) => (&number[..at], 1e9),
+        _ => (number, 1.0),
+    };
+    let value: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bits = (value * multiplier).round();
+    if !bits.is_finite() || bits < 1.0 || bits > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bits as u64)
+}
+
+/// `bits_per_second` with an SI prefix, e.g. `50 Mbit/s`
+pub fn format_bandwidth(bits_per_second: u64) -> String {
+    let bits = bits_per_second as f64;
+    let (value, prefix) = if bits >= 1e9 {
+        (bits / 1e9, "G")
+    } else if bits >= 1e6 {
+        (bits / 1e6, "M")
+    } else if bits >= 1e3 {
+        (bits / 1e3, "k")
+    } else {
+        (bits, "")
+    };
+    let value = format!("{:.2}", value);
+    format!("{} {}bit/s", value.trim_end_matches('0').trim_end_matches('.'), prefix)
+}
+
+/// Most bytes a probe of `technique` to `port` puts on the wire
+pub fn probe_wire_bytes(technique: ScanTechnique, port: u16) -> u64 {
+    let frame = |ip_len: u64| ip_len.max(MIN_FRAME_PAYLOAD) + FRAME_OVERHEAD;
+    match technique {
+        // SYN, then ACK, FIN and the last ACK when the port is open
+        ScanTechnique::Connect => frame(KERNEL_SYN) + 3 * frame(KERNEL_SEGMENT),
+        // The kernel resets the SYN/ACK our crafted SYN draws
+        ScanTechnique::Syn | ScanTechnique::Stealth => frame(TCP_PACKET + MSS_OPTION) + frame(TCP_PACKET),
+        ScanTechnique::Udp => {
+            UDP_ATTEMPTS as u64 * frame(UDP_HEADERS + udp_probe_payload(port).len() as u64)
+        }
+        ScanTechnique::Fin | ScanTechnique::Null | ScanTechnique::Xmas | ScanTechnique::Ack | ScanTechnique::Window => {
+            frame(TCP_PACKET + MSS_OPTION)
+        }
+    }
+}
+
+/// Probes per second that keep a scan of `ports` with `technique` within
+/// `bits_per_second`, sized for the largest probe
+pub fn probes_per_second(bits_per_second: u64, technique: ScanTechnique, ports: &[u16]) -> u64 {
+    let largest = ports
+        .iter()
+        .map(|&port| probe_wire_bytes(technique, port))
+        .max()
+        .unwrap_or_else(|| probe_wire_bytes(technique, 0));
+    (bits_per_second / (largest * 8)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_to_probe_rate() {
+        assert_eq!(parse_bandwidth("50M").unwrap(), 50_000_000);
+        assert_eq!(parse_bandwidth("1.5Gbps").unwrap(), 1_500_000_000);
+        assert_eq!(parse_bandwidth("800 k").unwrap(), 800_000);
+        assert_eq!(parse_bandwidth("9600").unwrap(), 9600);
+        assert!(parse_bandwidth("0").is_err());
+        assert!(parse_bandwidth("fast").is_err());
+        assert_eq!(format_bandwidth(50_000_000), "50 Mbit/s");
+        assert_eq!(format_bandwidth(1_500_000_000), "1.5 Gbit/s");
+
+        // Minimum-size frames: 84 bytes for the SYN, 84 for our kernel's RST
+        assert_eq!(probe_wire_bytes(ScanTechnique::Syn, 80), 168);
+        assert_eq!(probes_per_second(50_000_000, ScanTechnique::Syn, &[22, 80]), 37_202);
+        // A full handshake and close costs more than a half-open probe
+        assert!(probe_wire_bytes(ScanTechnique::Connect, 80) > probe_wire_bytes(ScanTechnique::Syn, 80));
+        // The SSDP probe is the largest, so it sets the rate for the whole scan
+        let udp = probes_per_second(1_000_000, ScanTechnique::Udp, &[53, 1900]);
+        assert_eq!(udp, 1_000_000 / (8 * probe_wire_bytes(ScanTechnique::Udp, 1900)));
+        assert!(udp < probes_per_second(1_000_000, ScanTechnique::Udp, &[53]));
+        assert_eq!(probes_per_second(1, ScanTechnique::Connect, &[]), 1);
+    }
+}
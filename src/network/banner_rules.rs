@@ -0,0 +1,181 @@
+//! User-defined banner classification rules
+//!
+//! The built-in banner checks only know public daemons. `[[banner_rules]]`
+//! tables in a config file map banners of in-house ones to a service name,
+//! and optionally a version, and are tried before the built-in checks:
+//!
+//! ```toml
+//! [[banner_rules]]
+//! match = '^220 acme-ftpd (?P<version>[\d.]+)'
+//! service = "acme-ftp"
+//! ports = [21, 2121]      # optional; any port otherwise
+//! ```
+//!
+//! A `version` template such as `"$1-$2"` builds the version from the
+//! groups; without one, a `version` group or else the first group is used.
+//!
+//! When several rules match one banner the most specific wins: a rule
+//! bound to the port beats one for any port, then the rule whose match
+//! covers more of the banner, then the one written first.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A rule as written in the config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BannerRule {
+    /// Regex the banner has to match
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Service name reported on a match
+    pub service: String,
+    /// Version template expanded from the match groups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Ports the rule is limited to; every port when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<u16>,
+}
+
+impl BannerRule {
+    /// Check the rule and compile its pattern
+    pub fn compile(&self) -> crate::Result<CompiledRule> {
+        let invalid = |reason: String| {
+            crate::ScanError::ConfigError(format!("Banner rule for {}: {}", self.service, reason))
+        };
+        if self.service.trim().is_empty() {
+            return Err(invalid("service name is empty".to_string()));
+        }
+        if self.ports.contains(&0) {
+            return Err(invalid("ports must be between 1-65535".to_string()));
+        }
+        let pattern = Regex::new(&self.pattern).map_err(|e| invalid(format!("invalid match pattern: {}", e)))?;
+        Ok(CompiledRule {
+            pattern,
+            service: self.service.clone(),
+            version: self.version.clone(),
+            ports: self.ports.clone(),
+        })
+    }
+}
+
+/// A rule ready to apply
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    pub pattern: Regex,
+    pub service: String,
+    pub version: Option<String>,
+    pub ports: Vec<u16>,
+}
+
+impl CompiledRule {
+    /// Service and version if the rule applies to `banner` on `port`,
+    /// along with how specific the match is
+    fn classify(&self, banner: &str, port: u16) -> Option<(Specificity, BannerMatch)> {
+        if !self.ports.is_empty() && !self.ports.contains(&port) {
+            return None;
+        }
+        let captures = self.pattern.captures(banner)?;
+        let version = match &self.version {
+            Some(template) => {
+                let mut version = String::new();
+                captures.expand(template, &mut version);
+                Some(version)
+            }
+            None => captures
+                .name("version")
+                .or_else(|| captures.get(1))
+                .map(|group| group.as_str().to_string()),
+        }
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty());
+        let specificity = Specificity {
+            port_bound: !self.ports.is_empty(),
+            matched_len: captures.get(0).map_or(0, |whole| whole.len()),
+        };
+        Some((specificity, BannerMatch { service: self.service.clone(), version }))
+    }
+}
+
+/// Ordering of matching rules; greater is more specific
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Specificity {
+    port_bound: bool,
+    matched_len: usize,
+}
+
+/// What a matching rule classified a banner as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannerMatch {
+    pub service: String,
+    pub version: Option<String>,
+}
+
+/// Every configured rule
+#[derive(Debug, Clone, Default)]
+pub struct BannerRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl BannerRules {
+    /// Compile `rules`, failing on the first invalid one
+    pub fn compile(rules: &[BannerRule]) -> crate::Result<Self> {
+        let rules = rules.iter().map(BannerRule::compile).collect::<crate::Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The most specific rule matching `banner` seen on `port`
+    pub fn classify(&self, banner: &str, port: u16) -> Option<BannerMatch> {
+        let mut best: Option<(Specificity, BannerMatch)> = None;
+        for (specificity, found) in self.rules.iter().filter_map(|rule| rule.classify(banner, port)) {
+            // Strictly greater, so ties go to the rule written first
+            if best.as_ref().map_or(true, |(current, _)| specificity > *current) {
+                best = Some((specificity, found));
+            }
+        }
+        best.map(|(_, found)| found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, service: &str, version: Option<&str>, ports: &[u16]) -> BannerRule {
+        BannerRule {
+            pattern: pattern.to_string(),
+            service: service.to_string(),
+            version: version.map(str::to_string),
+            ports: ports.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_most_specific_rule_wins() {
+        let rules = BannerRules::compile(&[
+            rule(r"^220 ", "generic-ftp", None, &[]),
+            rule(r"^220 acme-ftpd (?P<version>[\d.]+)", "acme-ftp", None, &[]),
+            rule(r"^220 acme", "acme-ftp-gw", None, &[2121]),
+            rule(r"build (\d+)\.(\d+)", "acme-build", Some("$1-r$2"), &[]),
+        ])
+        .unwrap();
+
+        let banner = "220 acme-ftpd 3.1 ready";
+        // The longer match beats the catch-all...
+        assert_eq!(
+            rules.classify(banner, 21),
+            Some(BannerMatch { service: "acme-ftp".to_string(), version: Some("3.1".to_string()) })
+        );
+        // ...and a rule bound to the port beats both
+        assert_eq!(rules.classify(banner, 2121).unwrap().service, "acme-ftp-gw");
+        assert_eq!(rules.classify("acme build 4.7", 9000).unwrap().version.as_deref(), Some("4-r7"));
+        assert!(rules.classify("SSH-2.0-OpenSSH_9.6", 22).is_none());
+
+        assert!(BannerRules::compile(&[rule("(", "broken", None, &[])]).is_err());
+        assert!(BannerRules::compile(&[rule("x", "zero", None, &[0])]).is_err());
+    }
+}
@@ -0,0 +1,719 @@
+//! Capture filters for raw response sockets
+//!
+//! Raw sockets see every packet of their protocol that reaches the host, not
+//! just replies to our probes. A classic BPF program attached to the socket
+//! drops unrelated traffic in the kernel. The engine builds its own filter
+//! (protocol and target address) and users can narrow it further with a
+//! pcap-style expression via `--capture-filter`; the two are ANDed.
+//!
+//! Supported expression syntax, a subset of pcap-filter(7):
+//!
+//! - `ip`, `tcp`, `udp`, `icmp`
+//! - `[src|dst] host ADDR`, `[src|dst] net ADDR/LEN`
+//! - `[tcp|udp] [src|dst] port N`, `[tcp|udp] [src|dst] portrange N-M`
+//! - `less N`, `greater N` (packet length)
+//! - `and`/`&&`, `or`/`||`, `not`/`!` and parentheses; a bare value after
+//!   `and`/`or` reuses the previous qualifier, as in `port 80 or 443`
+//!
+//! Offsets are relative to the IPv4 header: raw `AF_INET` sockets hand the
+//! filter packets without a link-layer header.
+
+use crate::ScanError;
+use std::fmt;
+use std::net::Ipv4Addr;
+
+const IPPROTO_ICMP: u8 = 1;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+// Classic BPF opcodes
+const LD_W_ABS: u16 = 0x20;
+const LD_H_ABS: u16 = 0x28;
+const LD_B_ABS: u16 = 0x30;
+const LD_W_LEN: u16 = 0x80;
+const LD_H_IND: u16 = 0x48;
+const LDX_B_MSH: u16 = 0xb1;
+const ALU_AND_K: u16 = 0x54;
+const JMP_JEQ_K: u16 = 0x15;
+const JMP_JGT_K: u16 = 0x25;
+const JMP_JGE_K: u16 = 0x35;
+const JMP_JSET_K: u16 = 0x45;
+const RET_K: u16 = 0x06;
+
+/// Bytes of each accepted packet passed to the socket
+const SNAP_LEN: u32 = 0x40000;
+/// Kernel limit on program length
+const MAX_INSTRUCTIONS: usize = 4096;
+
+/// Which address or port a qualifier applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Src,
+    Dst,
+    Either,
+}
+
+/// A single filter test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Primitive {
+    /// Any IPv4 packet
+    Ip,
+    /// IP protocol number
+    Proto(u8),
+    Host(Direction, Ipv4Addr),
+    /// Network address and prefix length
+    Net(Direction, Ipv4Addr, u8),
+    /// TCP or UDP port range, optionally restricted to one protocol
+    Port { proto: Option<u8>, dir: Direction, low: u16, high: u16 },
+    /// Packet length at most N
+    Less(u32),
+    /// Packet length at least N
+    Greater(u32),
+}
+
+/// Parsed filter expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpr {
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Primitive(Primitive),
+}
+
+fn filter_error(message: impl fmt::Display) -> ScanError {
+    ScanError::ParseError(format!("capture filter: {}", message))
+}
+
+impl FilterExpr {
+    /// Parse a pcap-style expression
+    pub fn parse(input: &str) -> crate::Result<Self> {
+        let tokens = tokenize(input);
+        if tokens.is_empty() {
+            return Err(filter_error("expression is empty"));
+        }
+        let mut parser = Parser { tokens, pos: 0, last: None };
+        let expr = parser.expr()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(filter_error(format!("unexpected '{}'", token))),
+        }
+    }
+
+    pub fn and(self, other: FilterExpr) -> Self {
+        FilterExpr::And(Box::new(self), Box::new(other))
+    }
+
+    /// The engine's own filter: responses of `proto` coming from `target`
+    ///
+    /// ICMP errors come from whichever router generated them, so only the
+    /// protocol is checked for `icmp`.
+    pub fn responses_from(proto: u8, target: Option<(Ipv4Addr, u8)>) -> Self {
+        let proto_expr = FilterExpr::Primitive(Primitive::Proto(proto));
+        match target {
+            Some((addr, prefix)) if proto != IPPROTO_ICMP => {
+                let source = if prefix == 32 {
+                    Primitive::Host(Direction::Src, addr)
+                } else {
+                    Primitive::Net(Direction::Src, addr, prefix)
+                };
+                proto_expr.and(FilterExpr::Primitive(source))
+            }
+            _ => proto_expr,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let symbol = match c {
+            '(' | ')' => Some(c.to_string()),
+            '!' => Some("!".to_string()),
+            '&' | '|' if chars.peek() == Some(&c) => {
+                chars.next();
+                Some(format!("{}{}", c, c))
+            }
+            _ => None,
+        };
+        if symbol.is_some() || c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            tokens.extend(symbol);
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Qualifiers of the last primitive, reused by bare values (`port 80 or 443`)
+#[derive(Debug, Clone, Copy)]
+struct Qualifiers<'a> {
+    proto: Option<u8>,
+    dir: Direction,
+    kind: &'a str,
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    last: Option<(Option<u8>, Direction, String)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect_value(&mut self, kind: &str) -> crate::Result<String> {
+        self.next().ok_or_else(|| filter_error(format!("'{}' needs a value", kind)))
+    }
+
+    fn expr(&mut self) -> crate::Result<FilterExpr> {
+        let mut left = self.term()?;
+        while matches!(self.peek(), Some("or") | Some("||")) {
+            self.pos += 1;
+            left = FilterExpr::Or(Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> crate::Result<FilterExpr> {
+        let mut left = self.factor()?;
+        while matches!(self.peek(), Some("and") | Some("&&")) {
+            self.pos += 1;
+            left = left.and(self.factor()?);
+        }
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> crate::Result<FilterExpr> {
+        match self.peek() {
+            None => Err(filter_error("expression ends early")),
+            Some("not") | Some("!") => {
+                self.pos += 1;
+                Ok(FilterExpr::Not(Box::new(self.factor()?)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                match self.next().as_deref() {
+                    Some(")") => Ok(inner),
+                    _ => Err(filter_error("missing ')'")),
+                }
+            }
+            Some(token) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let (proto, dir, kind) = self
+                    .last
+                    .clone()
+                    .ok_or_else(|| filter_error(format!("'{}' needs a qualifier such as host or port", token)))?;
+                let value = self.expect_value(&kind)?;
+                let qualifiers = Qualifiers { proto, dir, kind: &kind };
+                build_primitive(qualifiers, &value).map(FilterExpr::Primitive)
+            }
+            Some(_) => self.primitive(),
+        }
+    }
+
+    fn primitive(&mut self) -> crate::Result<FilterExpr> {
+        let mut proto = None;
+        let mut dir = Direction::Either;
+        let mut token = self.expect_value("filter")?;
+
+        match token.as_str() {
+            "ip" => return Ok(FilterExpr::Primitive(Primitive::Ip)),
+            "icmp" => return Ok(FilterExpr::Primitive(Primitive::Proto(IPPROTO_ICMP))),
+            "less" | "greater" => {
+                let value = self.expect_value(&token)?;
+                let length: u32 = value.parse().map_err(|_| filter_error(format!("invalid length '{}'", value)))?;
+                return Ok(FilterExpr::Primitive(if token == "less" {
+                    Primitive::Less(length)
+                } else {
+                    Primitive::Greater(length)
+                }));
+            }
+            "tcp" | "udp" => {
+                let number = if token == "tcp" { IPPROTO_TCP } else { IPPROTO_UDP };
+                // `tcp port 80`, `udp dst portrange 1-1024`; otherwise just the protocol
+                match self.peek() {
+                    Some("port") | Some("portrange") | Some("src") | Some("dst") => {
+                        proto = Some(number);
+                        token = self.expect_value("filter")?;
+                    }
+                    _ => return Ok(FilterExpr::Primitive(Primitive::Proto(number))),
+                }
+            }
+            _ => {}
+        }
+
+        if token == "src" || token == "dst" {
+            dir = if token == "src" { Direction::Src } else { Direction::Dst };
+            token = self.expect_value(&token)?;
+        }
+
+        match token.as_str() {
+            "host" | "net" | "port" | "portrange" => {
+                if proto.is_some() && !token.starts_with("port") {
+                    return Err(filter_error(format!("'{}' cannot follow tcp/udp", token)));
+                }
+                let value = self.expect_value(&token)?;
+                self.last = Some((proto, dir, token.clone()));
+                build_primitive(Qualifiers { proto, dir, kind: &token }, &value).map(FilterExpr::Primitive)
+            }
+            other => Err(filter_error(format!("unknown keyword '{}'", other))),
+        }
+    }
+}
+
+fn build_primitive(qualifiers: Qualifiers<'_>, value: &str) -> crate::Result<Primitive> {
+    let Qualifiers { proto, dir, kind } = qualifiers;
+    let port = |text: &str| -> crate::Result<u16> {
+        text.parse().map_err(|_| filter_error(format!("invalid port '{}'", text)))
+    };
+    match kind {
+        "host" => value
+            .parse()
+            .map(|addr| Primitive::Host(dir, addr))
+            .map_err(|_| filter_error(format!("invalid IPv4 address '{}'", value))),
+        "net" => {
+            let (addr, prefix) = value.split_once('/').unwrap_or((value, "32"));
+            let addr: Ipv4Addr = addr.parse().map_err(|_| filter_error(format!("invalid network '{}'", value)))?;
+            let prefix: u8 = prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= 32)
+                .ok_or_else(|| filter_error(format!("invalid prefix length in '{}'", value)))?;
+            let masked = u32::from(addr) & prefix_mask(prefix);
+            Ok(Primitive::Net(dir, Ipv4Addr::from(masked), prefix))
+        }
+        "port" => {
+            let number = port(value)?;
+            Ok(Primitive::Port { proto, dir, low: number, high: number })
+        }
+        _ => {
+            let (low, high) = value
+                .split_once('-')
+                .ok_or_else(|| filter_error(format!("portrange needs LOW-HIGH, got '{}'", value)))?;
+            let (low, high) = (port(low)?, port(high)?);
+            if low > high {
+                return Err(filter_error(format!("empty port range '{}'", value)));
+            }
+            Ok(Primitive::Port { proto, dir, low, high })
+        }
+    }
+}
+
+fn prefix_mask(prefix: u8) -> u32 {
+    if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix as u32)
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Src => f.write_str("src "),
+            Direction::Dst => f.write_str("dst "),
+            Direction::Either => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Primitive::Ip => f.write_str("ip"),
+            Primitive::Proto(IPPROTO_TCP) => f.write_str("tcp"),
+            Primitive::Proto(IPPROTO_UDP) => f.write_str("udp"),
+            Primitive::Proto(IPPROTO_ICMP) => f.write_str("icmp"),
+            Primitive::Proto(other) => write!(f, "ip proto {}", other),
+            Primitive::Host(dir, addr) => write!(f, "{}host {}", dir, addr),
+            Primitive::Net(dir, addr, prefix) => write!(f, "{}net {}/{}", dir, addr, prefix),
+            Primitive::Port { proto, dir, low, high } => {
+                match proto {
+                    Some(IPPROTO_TCP) => f.write_str("tcp ")?,
+                    Some(_) => f.write_str("udp ")?,
+                    None => {}
+                }
+                if low == high {
+                    write!(f, "{}port {}", dir, low)
+                } else {
+                    write!(f, "{}portrange {}-{}", dir, low, high)
+                }
+            }
+            Primitive::Less(n) => write!(f, "less {}", n),
+            Primitive::Greater(n) => write!(f, "greater {}", n),
+        }
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterExpr::And(a, b) => write!(f, "({} and {})", a, b),
+            FilterExpr::Or(a, b) => write!(f, "({} or {})", a, b),
+            FilterExpr::Not(a) => write!(f, "not {}", a),
+            FilterExpr::Primitive(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+/// One classic BPF instruction, laid out like `struct sock_filter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpfInstruction {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+/// Jump destination while compiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Next,
+    Label(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Stmt(u16, u32),
+    Jump(u16, u32, Target, Target),
+    Label(usize),
+}
+
+#[derive(Default)]
+struct Compiler {
+    ops: Vec<Op>,
+    labels: usize,
+}
+
+impl Compiler {
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn stmt(&mut self, code: u16, k: u32) {
+        self.ops.push(Op::Stmt(code, k));
+    }
+
+    fn jump(&mut self, code: u16, k: u32, jt: Target, jf: Target) {
+        self.ops.push(Op::Jump(code, k, jt, jf));
+    }
+
+    fn place(&mut self, label: usize) {
+        self.ops.push(Op::Label(label));
+    }
+
+    /// Emit code that continues at `t` when `expr` holds and at `f` otherwise
+    fn expr(&mut self, expr: &FilterExpr, t: usize, f: usize) {
+        match expr {
+            FilterExpr::And(a, b) => {
+                let mid = self.label();
+                self.expr(a, mid, f);
+                self.place(mid);
+                self.expr(b, t, f);
+            }
+            FilterExpr::Or(a, b) => {
+                let mid = self.label();
+                self.expr(a, t, mid);
+                self.place(mid);
+                self.expr(b, t, f);
+            }
+            FilterExpr::Not(a) => self.expr(a, f, t),
+            FilterExpr::Primitive(p) => self.primitive(p, t, f),
+        }
+    }
+
+    fn primitive(&mut self, primitive: &Primitive, t: usize, f: usize) {
+        let (t_target, f_target) = (Target::Label(t), Target::Label(f));
+        match *primitive {
+            Primitive::Ip => {
+                self.stmt(LD_B_ABS, 0);
+                self.stmt(ALU_AND_K, 0xf0);
+                self.jump(JMP_JEQ_K, 0x40, t_target, f_target);
+            }
+            Primitive::Proto(proto) => {
+                self.stmt(LD_B_ABS, 9);
+                self.jump(JMP_JEQ_K, proto as u32, t_target, f_target);
+            }
+            Primitive::Host(Direction::Either, addr) => {
+                self.either(Primitive::Host(Direction::Src, addr), Primitive::Host(Direction::Dst, addr), t, f)
+            }
+            Primitive::Host(dir, addr) => {
+                self.stmt(LD_W_ABS, address_offset(dir));
+                self.jump(JMP_JEQ_K, u32::from(addr), t_target, f_target);
+            }
+            Primitive::Net(Direction::Either, addr, prefix) => self.either(
+                Primitive::Net(Direction::Src, addr, prefix),
+                Primitive::Net(Direction::Dst, addr, prefix),
+                t,
+                f,
+            ),
+            Primitive::Net(dir, addr, prefix) => {
+                self.stmt(LD_W_ABS, address_offset(dir));
+                self.stmt(ALU_AND_K, prefix_mask(prefix));
+                self.jump(JMP_JEQ_K, u32::from(addr), t_target, f_target);
+            }
+            Primitive::Port { proto, dir, low, high } => {
+                // Protocol first: tcp/udp as requested, or either of them
+                match proto {
+                    Some(proto) => {
+                        self.stmt(LD_B_ABS, 9);
+                        self.jump(JMP_JEQ_K, proto as u32, Target::Next, f_target);
+                    }
+                    None => {
+                        let transport = self.label();
+                        self.stmt(LD_B_ABS, 9);
+                        self.jump(JMP_JEQ_K, IPPROTO_TCP as u32, Target::Label(transport), Target::Next);
+                        self.jump(JMP_JEQ_K, IPPROTO_UDP as u32, Target::Next, f_target);
+                        self.place(transport);
+                    }
+                }
+                // Only the first fragment carries the transport header
+                self.stmt(LD_H_ABS, 6);
+                self.jump(JMP_JSET_K, 0x1fff, f_target, Target::Next);
+                self.stmt(LDX_B_MSH, 0);
+                match dir {
+                    Direction::Either => {
+                        let dst = self.label();
+                        self.port_range(0, low, high, t_target, Target::Label(dst));
+                        self.place(dst);
+                        self.port_range(2, low, high, t_target, f_target);
+                    }
+                    Direction::Src => self.port_range(0, low, high, t_target, f_target),
+                    Direction::Dst => self.port_range(2, low, high, t_target, f_target),
+                }
+            }
+            Primitive::Less(length) => {
+                self.stmt(LD_W_LEN, 0);
+                self.jump(JMP_JGT_K, length, f_target, t_target);
+            }
+            Primitive::Greater(length) => {
+                self.stmt(LD_W_LEN, 0);
+                self.jump(JMP_JGE_K, length, t_target, f_target);
+            }
+        }
+    }
+
+    fn either(&mut self, src: Primitive, dst: Primitive, t: usize, f: usize) {
+        let mid = self.label();
+        self.primitive(&src, t, mid);
+        self.place(mid);
+        self.primitive(&dst, t, f);
+    }
+
+    /// Compare the port at `offset` past the IP header (in X) against `low..=high`
+    fn port_range(&mut self, offset: u32, low: u16, high: u16, t: Target, f: Target) {
+        self.stmt(LD_H_IND, offset);
+        if low == high {
+            self.jump(JMP_JEQ_K, low as u32, t, f);
+        } else {
+            self.jump(JMP_JGE_K, low as u32, Target::Next, f);
+            self.jump(JMP_JGT_K, high as u32, f, t);
+        }
+    }
+
+    /// Lay out the ops and turn labels into relative jump offsets
+    fn finish(mut self, accept: usize, reject: usize) -> crate::Result<Vec<BpfInstruction>> {
+        self.place(accept);
+        self.stmt(RET_K, SNAP_LEN);
+        self.place(reject);
+        self.stmt(RET_K, 0);
+
+        let mut positions = vec![0usize; self.labels];
+        let mut index = 0;
+        for op in &self.ops {
+            match op {
+                Op::Label(label) => positions[*label] = index,
+                _ => index += 1,
+            }
+        }
+        if index > MAX_INSTRUCTIONS {
+            return Err(filter_error(format!("program has {} instructions (limit {})", index, MAX_INSTRUCTIONS)));
+        }
+
+        let mut program = Vec::with_capacity(index);
+        for op in &self.ops {
+            match *op {
+                Op::Label(_) => {}
+                Op::Stmt(code, k) => program.push(BpfInstruction { code, jt: 0, jf: 0, k }),
+                Op::Jump(code, k, jt, jf) => {
+                    let here = program.len() + 1;
+                    let offset = |target: Target| -> crate::Result<u8> {
+                        let distance = match target {
+                            Target::Next => 0,
+                            Target::Label(label) => positions[label] - here,
+                        };
+                        u8::try_from(distance).map_err(|_| filter_error("expression too complex (jump out of range)"))
+                    };
+                    program.push(BpfInstruction { code, jt: offset(jt)?, jf: offset(jf)?, k });
+                }
+            }
+        }
+        Ok(program)
+    }
+}
+
+fn address_offset(dir: Direction) -> u32 {
+    match dir {
+        Direction::Dst => 16,
+        _ => 12,
+    }
+}
+
+/// Compiled classic BPF program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BpfProgram {
+    instructions: Vec<BpfInstruction>,
+}
+
+impl BpfProgram {
+    pub fn compile(expr: &FilterExpr) -> crate::Result<Self> {
+        let mut compiler = Compiler::default();
+        let accept = compiler.label();
+        let reject = compiler.label();
+        compiler.expr(expr, accept, reject);
+        Ok(Self { instructions: compiler.finish(accept, reject)? })
+    }
+
+    pub fn instructions(&self) -> &[BpfInstruction] {
+        &self.instructions
+    }
+
+    /// Run the program over an IPv4 packet the way the kernel would
+    pub fn matches(&self, packet: &[u8]) -> bool {
+        let load = |offset: usize, size: usize| -> Option<u32> {
+            let bytes = packet.get(offset..offset.checked_add(size)?)?;
+            Some(bytes.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32))
+        };
+        let (mut a, mut x) = (0u32, 0u32);
+        let mut pc = 0;
+        while let Some(insn) = self.instructions.get(pc) {
+            pc += 1;
+            let jump = |taken: bool| if taken { insn.jt as usize } else { insn.jf as usize };
+            match insn.code {
+                LD_W_ABS | LD_H_ABS | LD_B_ABS => {
+                    let size = match insn.code {
+                        LD_W_ABS => 4,
+                        LD_H_ABS => 2,
+                        _ => 1,
+                    };
+                    match load(insn.k as usize, size) {
+                        Some(value) => a = value,
+                        None => return false,
+                    }
+                }
+                LD_H_IND => match load(x as usize + insn.k as usize, 2) {
+                    Some(value) => a = value,
+                    None => return false,
+                },
+                LD_W_LEN => a = packet.len() as u32,
+                LDX_B_MSH => match load(insn.k as usize, 1) {
+                    Some(value) => x = (value & 0xf) * 4,
+                    None => return false,
+                },
+                ALU_AND_K => a &= insn.k,
+                JMP_JEQ_K => pc += jump(a == insn.k),
+                JMP_JGT_K => pc += jump(a > insn.k),
+                JMP_JGE_K => pc += jump(a >= insn.k),
+                JMP_JSET_K => pc += jump(a & insn.k != 0),
+                RET_K => return insn.k != 0,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Attach to a socket with `SO_ATTACH_FILTER`
+    #[cfg(target_os = "linux")]
+    pub fn attach(&self, fd: i32) -> crate::Result<()> {
+        let mut filters: Vec<libc::sock_filter> = self
+            .instructions
+            .iter()
+            .map(|i| libc::sock_filter { code: i.code, jt: i.jt, jf: i.jf, k: i.k })
+            .collect();
+        let program = libc::sock_fprog { len: filters.len() as u16, filter: filters.as_mut_ptr() };
+        // SAFETY: `program` points at `filters`, which outlives the call; the kernel copies it
+        let result = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_ATTACH_FILTER,
+                &program as *const libc::sock_fprog as *const libc::c_void,
+                std::mem::size_of::<libc::sock_fprog>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(ScanError::RawSocketError(format!(
+                "failed to attach capture filter: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn attach(&self, _fd: i32) -> crate::Result<()> {
+        Err(ScanError::RawSocketError("capture filters are only supported on Linux".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal IPv4 packet with a 20-byte header followed by `src_port`/`dst_port`
+    fn packet(proto: u8, src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16) -> Vec<u8> {
+        let mut bytes = vec![0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, proto, 0, 0];
+        bytes.extend(src);
+        bytes.extend(dst);
+        bytes.extend(src_port.to_be_bytes());
+        bytes.extend(dst_port.to_be_bytes());
+        bytes.resize(40, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let expr = FilterExpr::parse("src net 192.0.2.0/24 and (tcp port 80 or 443) && !host 192.0.2.99").unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "((src net 192.0.2.0/24 and (tcp port 80 or tcp port 443)) and not host 192.0.2.99)"
+        );
+        let merged = FilterExpr::responses_from(IPPROTO_TCP, Some((Ipv4Addr::new(192, 0, 2, 0), 24))).and(expr);
+        let program = BpfProgram::compile(&merged).unwrap();
+
+        let target = [192, 0, 2, 10];
+        let us = [198, 51, 100, 1];
+        assert!(program.matches(&packet(IPPROTO_TCP, target, us, 443, 40000)));
+        assert!(!program.matches(&packet(IPPROTO_TCP, target, us, 22, 40000)));
+        assert!(!program.matches(&packet(IPPROTO_UDP, target, us, 443, 40000)));
+        assert!(!program.matches(&packet(IPPROTO_TCP, [192, 0, 2, 99], us, 80, 40000)));
+        assert!(!program.matches(&packet(IPPROTO_TCP, [203, 0, 113, 5], us, 80, 40000)));
+        assert!(!program.matches(&packet(IPPROTO_TCP, target, us, 80, 40000)[..21]));
+
+        let range = BpfProgram::compile(&FilterExpr::parse("udp dst portrange 1000-2000 or icmp").unwrap()).unwrap();
+        assert!(range.matches(&packet(IPPROTO_UDP, target, us, 53, 1500)));
+        assert!(!range.matches(&packet(IPPROTO_UDP, target, us, 1500, 53)));
+        assert!(range.matches(&packet(IPPROTO_ICMP, target, us, 0, 0)));
+
+        for bad in ["", "port", "host 300.1.1.1", "tcp host 1.2.3.4", "80", "port 80 or", "(tcp", "portrange 9-1", "vlan 5"] {
+            assert!(FilterExpr::parse(bad).is_err(), "{:?} should not parse", bad);
+        }
+    }
+}
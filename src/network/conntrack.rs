@@ -0,0 +1,207 @@
+//! Linux connection tracking table usage
+//!
+//! Every probe that passes through netfilter with conntrack loaded takes an
+//! entry in a table capped at `nf_conntrack_max`. Once it is full, the kernel
+//! drops new connections for every user of the host, not only the scanner.
+//! An unanswered probe holds its entry until the SYN_SENT (or UDP) timeout
+//! runs out, so a fast scan of filtered ports fills the table long before it
+//! finishes. Raw probes can be marked and exempted with a NOTRACK rule.
+
+use std::fmt;
+use std::time::Duration;
+
+const COUNT_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_count";
+const MAX_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_max";
+const TCP_SYN_SENT_TIMEOUT_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_tcp_timeout_syn_sent";
+const UDP_TIMEOUT_PATH: &str = "/proc/sys/net/netfilter/nf_conntrack_udp_timeout";
+const IP_FORWARD_PATH: &str = "/proc/sys/net/ipv4/ip_forward";
+
+/// Kernel defaults for the entry timeouts, in seconds
+const DEFAULT_TCP_SYN_SENT_TIMEOUT: u64 = 120;
+const DEFAULT_UDP_TIMEOUT: u64 = 30;
+/// Entry timeout suggested for scanning hosts
+const SUGGESTED_TIMEOUT: u64 = 10;
+/// Share of the free entries a scan may take before it is flagged
+const RISK_SHARE: f64 = 0.8;
+
+/// `SO_MARK` value `--notrack` puts on raw probes ("PHOB")
+pub const PROBE_MARK: u32 = 0x5048_4f42;
+
+fn read_value(path: &str) -> Option<u64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Entries in use and the table's limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConntrackUsage {
+    pub count: u64,
+    pub max: u64,
+}
+
+impl ConntrackUsage {
+    /// Current usage, or `None` when conntrack is not loaded or not readable
+    pub fn read() -> Option<Self> {
+        Some(Self { count: read_value(COUNT_PATH)?, max: read_value(MAX_PATH)? })
+    }
+
+    /// Entries still free
+    pub fn headroom(&self) -> u64 {
+        self.max.saturating_sub(self.count)
+    }
+
+    /// Share of the table in use, from 0.0 to 1.0
+    pub fn utilization(&self) -> f64 {
+        if self.max == 0 {
+            return 1.0;
+        }
+        self.count as f64 / self.max as f64
+    }
+}
+
+impl fmt::Display for ConntrackUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} entries ({:.0}% used)", self.count, self.max, self.utilization() * 100.0)
+    }
+}
+
+/// How long an unanswered probe keeps its entry, from the running kernel
+pub fn entry_timeout(udp: bool) -> Duration {
+    let seconds = if udp {
+        read_value(UDP_TIMEOUT_PATH).unwrap_or(DEFAULT_UDP_TIMEOUT)
+    } else {
+        read_value(TCP_SYN_SENT_TIMEOUT_PATH).unwrap_or(DEFAULT_TCP_SYN_SENT_TIMEOUT)
+    };
+    Duration::from_secs(seconds)
+}
+
+/// Whether this host routes for others, so its table is shared with them
+pub fn forwarding() -> bool {
+    read_value(IP_FORWARD_PATH) == Some(1)
+}
+
+/// Raw table rules that exempt packets carrying `mark` from tracking, as
+/// iptables and nftables commands
+pub fn notrack_rules(mark: u32) -> [String; 2] {
+    [
+        format!("sudo iptables -t raw -I OUTPUT -m mark --mark {:#x} -j CT --notrack", mark),
+        format!(
+            "sudo nft add table ip phobos && sudo nft 'add chain ip phobos output {{ type filter hook output priority raw; }}' \
+             && sudo nft add rule ip phobos output meta mark {:#x} notrack",
+            mark
+        ),
+    ]
+}
+
+/// Predicted conntrack load of a scan against the table's free space
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConntrackForecast {
+    pub usage: ConntrackUsage,
+    /// Entries held at once if no probe is answered
+    pub peak_entries: u64,
+    pub probe_rate: f64,
+    pub entry_timeout: Duration,
+    pub udp: bool,
+    pub forwarding: bool,
+}
+
+impl ConntrackForecast {
+    /// Forecast `probes` sent at `probe_rate` per second, or `None` when
+    /// conntrack is not loaded
+    pub fn read(probes: u64, probe_rate: f64, udp: bool) -> Option<Self> {
+        Some(Self::new(ConntrackUsage::read()?, probes, probe_rate, entry_timeout(udp), udp).with_forwarding(forwarding()))
+    }
+
+    pub fn new(usage: ConntrackUsage, probes: u64, probe_rate: f64, entry_timeout: Duration, udp: bool) -> Self {
+        // Entries expire as fast as new ones arrive once the first timeout has passed
+        let steady_state = (probe_rate * entry_timeout.as_secs_f64()).ceil() as u64;
+        Self {
+            usage,
+            peak_entries: probes.min(steady_state),
+            probe_rate,
+            entry_timeout,
+            udp,
+            forwarding: false,
+        }
+    }
+
+    pub fn with_forwarding(mut self, forwarding: bool) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    /// Whether the scan may take most of the free entries
+    pub fn at_risk(&self) -> bool {
+        self.peak_entries as f64 >= self.usage.headroom() as f64 * RISK_SHARE
+    }
+
+    /// Concrete ways to keep the scan inside the table; `raw` is whether the
+    /// technique sends crafted probes that `--notrack` can mark
+    pub fn suggestions(&self, raw: bool) -> Vec<String> {
+        let needed = self.usage.count + self.peak_entries + self.peak_entries / 4;
+        let timeout_sysctl = if self.udp { "nf_conntrack_udp_timeout" } else { "nf_conntrack_tcp_timeout_syn_sent" };
+        let safe_rate = ((self.usage.headroom() as f64 * RISK_SHARE / 2.0) / self.entry_timeout.as_secs_f64().max(1.0)) as u64;
+        let mut suggestions = vec![
+            format!(
+                "raise the limit: sudo sysctl -w net.netfilter.nf_conntrack_max={}",
+                (needed + 65_535) / 65_536 * 65_536
+            ),
+            format!(
+                "expire unanswered probes sooner: sudo sysctl -w net.netfilter.{}={} (now {}s)",
+                timeout_sysctl,
+                SUGGESTED_TIMEOUT,
+                self.entry_timeout.as_secs()
+            ),
+            format!("slow down: --rate-limit {}", safe_rate.max(1)),
+        ];
+        if raw {
+            suggestions.push("skip tracking: --notrack marks probes for a raw-table NOTRACK rule".to_string());
+        } else {
+            suggestions.push("skip tracking: a raw technique (-s syn) with --notrack is not tracked at all".to_string());
+        }
+        if self.forwarding {
+            suggestions.push(
+                "this host forwards packets; every client behind it shares the table, so prefer scanning from elsewhere"
+                    .to_string(),
+            );
+        }
+        suggestions
+    }
+}
+
+impl fmt::Display for ConntrackForecast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "up to {} entries at {:.0} probes/s with a {}s timeout; {} free of {}",
+            self.peak_entries,
+            self.probe_rate,
+            self.entry_timeout.as_secs(),
+            self.usage.headroom(),
+            self.usage.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conntrack_forecast() {
+        let usage = ConntrackUsage { count: 1_000, max: 65_536 };
+        let forecast = ConntrackForecast::new(usage, 10_000_000, 5_000.0, Duration::from_secs(120), false);
+        assert_eq!(forecast.peak_entries, 600_000);
+        assert!(forecast.at_risk());
+        let suggestions = forecast.suggestions(true);
+        assert_eq!(suggestions[0], "raise the limit: sudo sysctl -w net.netfilter.nf_conntrack_max=786432");
+        assert!(suggestions[1].contains("nf_conntrack_tcp_timeout_syn_sent=10 (now 120s)"));
+        assert_eq!(suggestions[2], "slow down: --rate-limit 215");
+        assert!(suggestions[3].contains("--notrack"));
+
+        let small = ConntrackForecast::new(usage, 1_000, 100_000.0, Duration::from_secs(30), true);
+        assert_eq!(small.peak_entries, 1_000);
+        assert!(!small.at_risk());
+        assert!(small.with_forwarding(true).suggestions(false).last().unwrap().contains("forwards packets"));
+        assert!(notrack_rules(PROBE_MARK)[0].contains("--mark 0x50484f42 -j CT --notrack"));
+    }
+}
@@ -0,0 +1,225 @@
+//! Pluggable transports for library consumers
+//!
+//! By default the engine opens its own sockets and resolves names with the
+//! system resolver. A consumer that needs probes to go somewhere else (through
+//! its own SOCKS client, into a simulated network, or through sockets it
+//! instruments) supplies [`Dialers`] instead. Only the parts that are set are
+//! replaced; everything else keeps the built-in path and its tuning.
+//!
+//! Dialers report outcomes as `io::Error` kinds, the way the kernel does, and
+//! the engine classifies them the same way: `ConnectionRefused` is a closed
+//! port, `TimedOut` a filtered one.
+
+use futures::future::BoxFuture;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Largest UDP reply [`SystemUdpDialer`] reads
+const MAX_UDP_REPLY: usize = 65_535;
+
+/// A connected byte stream, whatever carries it
+pub trait ProbeStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> ProbeStream for T {}
+
+/// Opens TCP connections for connect scans
+pub trait TcpDialer: Send + Sync + fmt::Debug {
+    /// Connect to `target`, giving up after `timeout` with `TimedOut`
+    fn dial(&self, target: SocketAddr, timeout: Duration) -> BoxFuture<'_, io::Result<Box<dyn ProbeStream>>>;
+}
+
+/// Sends UDP probes
+pub trait UdpDialer: Send + Sync + fmt::Debug {
+    /// Send `payload` to `target` and wait up to `timeout` for one reply;
+    /// `Ok(None)` means nothing came back, an error that the port refused it
+    fn exchange<'a>(
+        &'a self,
+        target: SocketAddr,
+        payload: &'a [u8],
+        timeout: Duration,
+    ) -> BoxFuture<'a, io::Result<Option<Vec<u8>>>>;
+}
+
+/// Turns host names into addresses
+pub trait Resolver: Send + Sync + fmt::Debug {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, io::Result<Vec<IpAddr>>>;
+}
+
+/// Transports to use instead of the built-in ones; `None` keeps the default
+#[derive(Debug, Clone, Default)]
+pub struct Dialers {
+    pub tcp: Option<Arc<dyn TcpDialer>>,
+    pub udp: Option<Arc<dyn UdpDialer>>,
+    pub resolver: Option<Arc<dyn Resolver>>,
+}
+
+impl Dialers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tcp(mut self, dialer: impl TcpDialer + 'static) -> Self {
+        self.tcp = Some(Arc::new(dialer));
+        self
+    }
+
+    pub fn with_udp(mut self, dialer: impl UdpDialer + 'static) -> Self {
+        self.udp = Some(Arc::new(dialer));
+        self
+    }
+
+    pub fn with_resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+}
+
+/// Plain kernel TCP connections, for wrapping or delegating to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTcpDialer;
+
+impl TcpDialer for SystemTcpDialer {
+    fn dial(&self, target: SocketAddr, timeout: Duration) -> BoxFuture<'_, io::Result<Box<dyn ProbeStream>>> {
+        Box::pin(async move {
+            let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(target))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))??;
+            Ok(Box::new(stream) as Box<dyn ProbeStream>)
+        })
+    }
+}
+
+/// One ephemeral kernel UDP socket per exchange
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemUdpDialer;
+
+impl UdpDialer for SystemUdpDialer {
+    fn exchange<'a>(
+        &'a self,
+        target: SocketAddr,
+        payload: &'a [u8],
+        timeout: Duration,
+    ) -> BoxFuture<'a, io::Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            let local: SocketAddr = match target {
+                SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+                SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+            };
+            let socket = tokio::net::UdpSocket::bind(local).await?;
+            socket.connect(target).await?;
+            socket.send(payload).await?;
+            let mut reply = vec![0u8; MAX_UDP_REPLY];
+            match tokio::time::timeout(timeout, socket.recv(&mut reply)).await {
+                Ok(Ok(len)) => {
+                    reply.truncate(len);
+                    Ok(Some(reply))
+                }
+                Ok(Err(e)) => Err(e),
+                Err(_) => Ok(None),
+            }
+        })
+    }
+}
+
+/// The operating system's resolver
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, io::Result<Vec<IpAddr>>> {
+        Box::pin(async move {
+            let mut addresses: Vec<IpAddr> = tokio::net::lookup_host((host, 0)).await?.map(|addr| addr.ip()).collect();
+            addresses.dedup();
+            Ok(addresses)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::ScanTechnique;
+    use crate::scanner::engine::ScanEngine;
+    use crate::network::unreachable::Unreachable;
+    use crate::scanner::udp::{UdpReason, UdpScanResult, UdpScanner};
+    use crate::utils::scan_options::ScanOptions;
+    use std::net::Ipv4Addr;
+
+    const SIMULATED_HOST: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 7);
+
+    /// A network where only SSH on the simulated host listens
+    #[derive(Debug)]
+    struct Simulated;
+
+    impl TcpDialer for Simulated {
+        fn dial(&self, target: SocketAddr, _timeout: Duration) -> BoxFuture<'_, io::Result<Box<dyn ProbeStream>>> {
+            Box::pin(async move {
+                match target.port() {
+                    22 if target.ip() == SIMULATED_HOST => {
+                        let (client, _server) = tokio::io::duplex(64);
+                        Ok(Box::new(client) as Box<dyn ProbeStream>)
+                    }
+                    443 => Err(io::ErrorKind::TimedOut.into()),
+                    _ => Err(io::ErrorKind::ConnectionRefused.into()),
+                }
+            })
+        }
+    }
+
+    impl UdpDialer for Simulated {
+        fn exchange<'a>(&'a self, target: SocketAddr, payload: &'a [u8], _timeout: Duration) -> BoxFuture<'a, io::Result<Option<Vec<u8>>>> {
+            Box::pin(async move {
+                match target.port() {
+                    53 => Ok(Some(payload.to_vec())),
+                    161 => Ok(None),
+                    // Behind a firewall that answers for the host
+                    500 => Err(io::ErrorKind::HostUnreachable.into()),
+                    _ => Err(io::ErrorKind::ConnectionRefused.into()),
+                }
+            })
+        }
+    }
+
+    impl Resolver for Simulated {
+        fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, io::Result<Vec<IpAddr>>> {
+            Box::pin(async move {
+                match host {
+                    "ssh.simulated.test" => Ok(vec![IpAddr::V4(SIMULATED_HOST)]),
+                    _ => Err(io::ErrorKind::NotFound.into()),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_through_simulated_network() {
+        let config = ScanConfig {
+            target: "ssh.simulated.test".to_string(),
+            ports: vec![22, 80, 443],
+            technique: ScanTechnique::Connect,
+            timeout: 200,
+            ..ScanConfig::default()
+        };
+        let dialers = Dialers::new().with_tcp(Simulated).with_resolver(Simulated);
+        let engine = ScanEngine::new(config).await.unwrap().with_dialers(dialers);
+        let result = engine.scan().await.unwrap();
+        assert_eq!(result.open_ports, vec![22]);
+        assert_eq!(result.stats.ports_closed, 1);
+        assert_eq!(result.stats.ports_filtered, 1);
+
+        let udp = UdpScanner::new(ScanOptions::default()).with_dialer(Arc::new(Simulated));
+        let results = udp.scan_ports(IpAddr::V4(SIMULATED_HOST), vec![53, 161, 500, 4500]).await;
+        let states: Vec<_> = results.iter().map(|r| (r.result.clone(), r.reason)).collect();
+        assert_eq!(states, [
+            (UdpScanResult::Open, UdpReason::UdpResponse),
+            (UdpScanResult::OpenFiltered, UdpReason::NoResponse),
+            (UdpScanResult::Filtered, UdpReason::Unreachable(Unreachable::Host)),
+            (UdpScanResult::Closed, UdpReason::Unreachable(Unreachable::Port)),
+        ]);
+    }
+}
@@ -0,0 +1,329 @@
+//! DNS-over-UDP resolver probing for DNS infrastructure sweeps
+//!
+//! Sends a single recursive query (RD set) to port 53 of every target and
+//! classifies the reply. Queries for a whole batch go out over one socket and
+//! replies are matched by source address and transaction ID, so sweeping a /16
+//! costs one packet per host instead of one socket per host.
+
+use crate::network::protocol::RateLimiter;
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Default name to resolve when probing for recursion
+pub const DEFAULT_QUERY_NAME: &str = "example.com";
+
+const FLAG_QR: u16 = 0x8000;
+const FLAG_AA: u16 = 0x0400;
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+const RCODE_NOERROR: u8 = 0;
+const RCODE_REFUSED: u8 = 5;
+
+/// How a host answered the recursive probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResolverClass {
+    /// Recursion available and the query was answered - an open resolver
+    OpenResolver,
+    /// Recursion advertised (RA set) but no answer came back
+    RecursionAdvertised,
+    /// DNS server that does not recurse for us (authoritative-only or referral)
+    NonRecursive,
+    /// DNS server that refused the query
+    Refused,
+    /// No reply within the timeout
+    NoResponse,
+}
+
+impl ResolverClass {
+    /// Whether the host is running a DNS service at all
+    pub fn is_dns_server(&self) -> bool {
+        !matches!(self, ResolverClass::NoResponse)
+    }
+}
+
+impl fmt::Display for ResolverClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResolverClass::OpenResolver => "open resolver",
+            ResolverClass::RecursionAdvertised => "recursion advertised",
+            ResolverClass::NonRecursive => "non-recursive",
+            ResolverClass::Refused => "refused",
+            ResolverClass::NoResponse => "no response",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Result of probing one host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsProbeResult {
+    pub addr: IpAddr,
+    pub class: ResolverClass,
+    /// Response code of the reply, if any
+    pub rcode: Option<u8>,
+    /// Number of answer records in the reply
+    pub answers: u16,
+    /// Whether the reply had the AA (authoritative answer) bit set
+    pub authoritative: bool,
+    pub response_time: Option<Duration>,
+}
+
+impl DnsProbeResult {
+    fn no_response(addr: IpAddr) -> Self {
+        Self {
+            addr,
+            class: ResolverClass::NoResponse,
+            rcode: None,
+            answers: 0,
+            authoritative: false,
+            response_time: None,
+        }
+    }
+}
+
+/// Parsed fields of a DNS reply header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsReplyHeader {
+    pub id: u16,
+    pub authoritative: bool,
+    pub recursion_available: bool,
+    pub rcode: u8,
+    pub answers: u16,
+}
+
+impl DnsReplyHeader {
+    /// Parse the header of a reply; returns `None` for queries or truncated packets
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < 12 {
+            return None;
+        }
+        let flags = u16::from_be_bytes([packet[2], packet[3]]);
+        if flags & FLAG_QR == 0 {
+            return None;
+        }
+        Some(Self {
+            id: u16::from_be_bytes([packet[0], packet[1]]),
+            authoritative: flags & FLAG_AA != 0,
+            recursion_available: flags & FLAG_RA != 0,
+            rcode: (flags & 0x000f) as u8,
+            answers: u16::from_be_bytes([packet[6], packet[7]]),
+        })
+    }
+
+    /// Classify the responding server
+    pub fn classify(&self) -> ResolverClass {
+        if self.rcode == RCODE_REFUSED {
+            ResolverClass::Refused
+        } else if self.recursion_available && self.rcode == RCODE_NOERROR && self.answers > 0 {
+            ResolverClass::OpenResolver
+        } else if self.recursion_available {
+            ResolverClass::RecursionAdvertised
+        } else {
+            ResolverClass::NonRecursive
+        }
+    }
+}
+
+/// Build a recursive `A IN` query for `name`
+pub fn build_query(id: u16, name: &str) -> crate::Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(18 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&FLAG_RD.to_be_bytes());
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // QDCOUNT=1, AN/NS/AR=0
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(ScanError::ConfigError(format!("Invalid DNS query name: {}", name)));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&[0, 1, 0, 1]); // QTYPE=A, QCLASS=IN
+    Ok(packet)
+}
+
+/// Batched DNS resolver sweep
+#[derive(Debug)]
+pub struct DnsSweeper {
+    query_name: String,
+    port: u16,
+    timeout: Duration,
+    batch_size: usize,
+    rate_limiter: RateLimiter,
+}
+
+impl DnsSweeper {
+    pub fn new(timeout: Duration, rate_limit: u64) -> Self {
+        Self {
+            query_name: DEFAULT_QUERY_NAME.to_string(),
+            port: 53,
+            timeout,
+            batch_size: 4096,
+            rate_limiter: RateLimiter::new(rate_limit),
+        }
+    }
+
+    /// Name to resolve (should be a name the targets are not authoritative for)
+    pub fn with_query_name(mut self, name: &str) -> Self {
+        self.query_name = name.to_string();
+        self
+    }
+
+    /// Probe a port other than 53
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Number of queries in flight per socket
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        // Transaction IDs are 16 bits wide
+        self.batch_size = batch_size.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    /// Probe every target and return one result per target, in input order
+    pub async fn sweep(&self, targets: &[IpAddr]) -> crate::Result<Vec<DnsProbeResult>> {
+        build_query(0, &self.query_name)?;
+
+        let mut results = Vec::with_capacity(targets.len());
+        for batch in targets.chunks(self.batch_size) {
+            results.extend(self.sweep_batch(batch).await?);
+        }
+        Ok(results)
+    }
+
+    async fn sweep_batch(&self, batch: &[IpAddr]) -> crate::Result<Vec<DnsProbeResult>> {
+        let v4 = Self::bind_if_needed(batch, false).await?;
+        let v6 = Self::bind_if_needed(batch, true).await?;
+
+        // Random base so stray replies from earlier batches don't match
+        let id_base: u16 = rand::random();
+        let mut pending: HashMap<(IpAddr, u16), (usize, Instant)> = HashMap::with_capacity(batch.len());
+        let mut results: Vec<DnsProbeResult> = batch.iter().map(|&ip| DnsProbeResult::no_response(ip)).collect();
+
+        for (index, &ip) in batch.iter().enumerate() {
+            let id = id_base.wrapping_add(index as u16);
+            let query = build_query(id, &self.query_name)?;
+            let socket = if ip.is_ipv4() { &v4 } else { &v6 };
+            let Some(socket) = socket else { continue };
+
+            self.rate_limiter.acquire(ip).await;
+            // Unreachable hosts simply stay "no response"
+            if socket.send_to(&query, SocketAddr::new(ip, self.port)).await.is_ok() {
+                pending.insert((ip, id), (index, Instant::now()));
+            }
+        }
+
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        let mut buf4 = [0u8; 1500];
+        let mut buf6 = [0u8; 1500];
+        while !pending.is_empty() {
+            let recv = async {
+                match (&v4, &v6) {
+                    (Some(a), Some(b)) => tokio::select! {
+                        r = a.recv_from(&mut buf4) => r.map(|(len, from)| (len, from, false)),
+                        r = b.recv_from(&mut buf6) => r.map(|(len, from)| (len, from, true)),
+                    },
+                    (Some(s), None) => s.recv_from(&mut buf4).await.map(|(len, from)| (len, from, false)),
+                    (None, Some(s)) => s.recv_from(&mut buf6).await.map(|(len, from)| (len, from, true)),
+                    (None, None) => std::future::pending().await,
+                }
+            };
+            let (len, from, ipv6) = match tokio::time::timeout_at(deadline, recv).await {
+                Ok(Ok(reply)) => reply,
+                Ok(Err(_)) => continue, // ICMP errors surface as recv errors on some platforms
+                Err(_) => break,
+            };
+
+            let packet = if ipv6 { &buf6[..len] } else { &buf4[..len] };
+            let Some(header) = DnsReplyHeader::parse(packet) else { continue };
+            if let Some((index, sent)) = pending.remove(&(from.ip(), header.id)) {
+                results[index] = DnsProbeResult {
+                    addr: from.ip(),
+                    class: header.classify(),
+                    rcode: Some(header.rcode),
+                    answers: header.answers,
+                    authoritative: header.authoritative,
+                    response_time: Some(sent.elapsed()),
+                };
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn bind_if_needed(batch: &[IpAddr], ipv6: bool) -> crate::Result<Option<UdpSocket>> {
+        if !batch.iter().any(|ip| ip.is_ipv6() == ipv6) {
+            return Ok(None);
+        }
+        let local = if ipv6 {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+        } else {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+        };
+        UdpSocket::bind(local)
+            .await
+            .map(Some)
+            .map_err(|e| ScanError::NetworkError(format!("Failed to bind UDP socket: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(id: u16, flags: u16, answers: u16) -> Vec<u8> {
+        let mut packet = id.to_be_bytes().to_vec();
+        packet.extend_from_slice(&flags.to_be_bytes());
+        packet.extend_from_slice(&[0, 1]);
+        packet.extend_from_slice(&answers.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet
+    }
+
+    #[test]
+    fn test_build_query() {
+        let query = build_query(0x1234, "example.com").unwrap();
+        assert_eq!(&query[..4], &[0x12, 0x34, 0x01, 0x00]);
+        assert_eq!(&query[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&query[25..], &[0, 1, 0, 1]);
+        assert!(build_query(1, "bad..name").is_err());
+    }
+
+    #[test]
+    fn test_classify_replies() {
+        let classify = |flags, answers| DnsReplyHeader::parse(&reply(1, flags, answers)).unwrap().classify();
+        assert_eq!(classify(FLAG_QR | FLAG_RD | FLAG_RA, 1), ResolverClass::OpenResolver);
+        assert_eq!(classify(FLAG_QR | FLAG_RD | FLAG_RA | 2, 0), ResolverClass::RecursionAdvertised);
+        assert_eq!(classify(FLAG_QR | FLAG_AA, 0), ResolverClass::NonRecursive);
+        assert_eq!(classify(FLAG_QR | FLAG_RA | 5, 0), ResolverClass::Refused);
+        // Queries and runt packets are not replies
+        assert!(DnsReplyHeader::parse(&reply(1, FLAG_RD, 0)).is_none());
+        assert!(DnsReplyHeader::parse(&[0; 4]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_against_local_resolver() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (_, from) = server.recv_from(&mut buf).await.unwrap();
+            let id = u16::from_be_bytes([buf[0], buf[1]]);
+            server.send_to(&reply(id, FLAG_QR | FLAG_RD | FLAG_RA, 1), from).await.unwrap();
+        });
+
+        let sweeper = DnsSweeper::new(Duration::from_millis(500), 1000).with_port(port);
+        let results = sweeper.sweep(&["127.0.0.1".parse().unwrap()]).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].class, ResolverClass::OpenResolver);
+        assert!(results[0].response_time.is_some());
+    }
+}
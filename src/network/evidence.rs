@@ -0,0 +1,166 @@
+//! Merging repeated observations of a port
+//!
+//! A port may be seen more than once, by a retry or by a second technique
+//! confirming the first. [`PortResult::merge`] folds each new observation
+//! into the existing entry instead of letting the last one win: a direct
+//! answer (SYN/ACK, RST, an accepted connect) outweighs silence, agreeing
+//! answers raise the [`Confidence`], and an open/closed contradiction stays
+//! in the evidence where [`PortResult::is_disputed`] reports it.
+
+use super::{PortResult, PortState, ScanTechnique};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How well a port's state is supported by the probes that saw it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// Inferred from missing replies, or contradicted by another probe
+    Low,
+    /// One direct answer from the target
+    #[default]
+    Medium,
+    /// Several probes gave the same direct answer
+    High,
+}
+
+impl Confidence {
+    /// Confidence of a single observation of `state`
+    pub fn of(state: PortState) -> Self {
+        if is_answer(state) {
+            Confidence::Medium
+        } else {
+            Confidence::Low
+        }
+    }
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confidence::Low => write!(f, "low"),
+            Confidence::Medium => write!(f, "medium"),
+            Confidence::High => write!(f, "high"),
+        }
+    }
+}
+
+/// One technique's view of a port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub technique: ScanTechnique,
+    pub state: PortState,
+}
+
+/// States that only a reply from the target can produce
+fn is_answer(state: PortState) -> bool {
+    matches!(state, PortState::Open | PortState::Closed | PortState::Unfiltered)
+}
+
+/// The state `evidence` supports best, and how strongly
+fn resolve(evidence: &[Evidence]) -> (PortState, Confidence) {
+    let count = |state| evidence.iter().filter(|e| e.state == state).count();
+    let strength = |n| if n > 1 { Confidence::High } else { Confidence::Medium };
+
+    match (count(PortState::Open), count(PortState::Closed)) {
+        (0, 0) => {}
+        (open, 0) => return (PortState::Open, strength(open)),
+        (0, closed) => return (PortState::Closed, strength(closed)),
+        // Packet loss cannot produce a SYN/ACK, but a middlebox can send a RST
+        _ => return (PortState::Open, Confidence::Low),
+    }
+
+    let unfiltered = count(PortState::Unfiltered);
+    if unfiltered > 0 {
+        return (PortState::Unfiltered, strength(unfiltered));
+    }
+    let state = match (count(PortState::OpenFiltered), count(PortState::ClosedFiltered)) {
+        (0, 0) => PortState::Filtered,
+        (_, 0) => PortState::OpenFiltered,
+        (0, _) => PortState::ClosedFiltered,
+        // Only "filtered" is compatible with both
+        _ => PortState::Filtered,
+    };
+    (state, Confidence::Low)
+}
+
+impl PortResult {
+    /// Fold `other`, an observation of the same port, into this result
+    ///
+    /// `technique` and `other_technique` produced the two results; each is
+    /// only recorded for a result that carries no evidence of its own yet.
+    pub fn merge(&mut self, technique: ScanTechnique, other: PortResult, other_technique: ScanTechnique) {
+        debug_assert_eq!((self.port, self.protocol), (other.port, other.protocol));
+        if self.evidence.is_empty() {
+            self.evidence.push(Evidence { technique, state: self.state });
+        }
+        if other.evidence.is_empty() {
+            self.evidence.push(Evidence { technique: other_technique, state: other.state });
+        } else {
+            self.evidence.extend(other.evidence);
+        }
+
+        let (state, confidence) = resolve(&self.evidence);
+        if state != self.state && state == other.state {
+            self.response_time = other.response_time;
+        }
+        self.state = state;
+        self.confidence = confidence;
+        if self.service.is_none() {
+            self.service = other.service;
+        }
+        if self.version.is_none() {
+            self.version = other.version;
+        }
+        for anomaly in other.anomalies {
+            if !self.anomalies.contains(&anomaly) {
+                self.anomalies.push(anomaly);
+            }
+        }
+    }
+
+    /// Whether one probe found the port open and another found it closed
+    pub fn is_disputed(&self) -> bool {
+        let saw = |state| self.evidence.iter().any(|e| e.state == state);
+        saw(PortState::Open) && saw(PortState::Closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Protocol;
+
+    #[test]
+    fn test_merge_prefers_answers_and_records_disputes() {
+        // A retry that got through beats the timeout before it
+        let mut port = PortResult::new(443, Protocol::Tcp, PortState::Filtered);
+        assert_eq!(port.confidence, Confidence::Low);
+        port.merge(ScanTechnique::Syn, PortResult::new(443, Protocol::Tcp, PortState::Open), ScanTechnique::Syn);
+        assert_eq!((port.state, port.confidence), (PortState::Open, Confidence::Medium));
+
+        // Confirmed by a second technique
+        port.merge(ScanTechnique::Syn, PortResult::new(443, Protocol::Tcp, PortState::Open), ScanTechnique::Connect);
+        assert_eq!((port.state, port.confidence), (PortState::Open, Confidence::High));
+        assert_eq!(port.evidence.len(), 3);
+        assert!(!port.is_disputed());
+
+        // A contradiction keeps the port open but flags it
+        port.merge(ScanTechnique::Syn, PortResult::new(443, Protocol::Tcp, PortState::Closed), ScanTechnique::Connect);
+        assert_eq!((port.state, port.confidence), (PortState::Open, Confidence::Low));
+        assert!(port.is_disputed());
+
+        // Two kinds of silence only agree on "filtered"
+        let mut quiet = PortResult::new(53, Protocol::Udp, PortState::OpenFiltered);
+        quiet.merge(ScanTechnique::Udp, PortResult::new(53, Protocol::Udp, PortState::ClosedFiltered), ScanTechnique::Fin);
+        assert_eq!((quiet.state, quiet.confidence), (PortState::Filtered, Confidence::Low));
+
+        // A scan result keeps one entry per port and moves it between lists
+        let mut result = crate::scanner::ScanResult::new("192.0.2.1".to_string(), Default::default());
+        result.add_port_result(PortResult::new(80, Protocol::Tcp, PortState::Filtered));
+        result.add_port_result_from(PortResult::new(80, Protocol::Tcp, PortState::Open), ScanTechnique::Syn);
+        assert_eq!((result.open_ports.as_slice(), result.filtered_ports.len()), (&[80][..], 0));
+        assert_eq!(result.port_results.len(), 1);
+        assert_eq!(result.port_results[0].evidence[0].technique, ScanTechnique::Connect);
+    }
+}
@@ -0,0 +1,66 @@
+//! IP ID sequences
+//!
+//! Many stacks fill the IP identification field from one counter shared by
+//! every packet they send. Sampling it across replies shows whether a host
+//! does, and how busy it is; OS detection reports it and the idle scan needs
+//! such a counter on its zombie.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IPIDSequence {
+    pub sequence_type: IPIDSequenceType,
+    pub increment_value: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IPIDSequenceType {
+    Incremental,
+    Random,
+    Zero,
+    Broken,
+}
+
+impl IPIDSequence {
+    /// Classify the IP IDs of consecutive replies from one host
+    ///
+    /// Steps under 256 are a shared counter. Steps that are all multiples of
+    /// 256 are such a counter written in host byte order, or a fixed ID, and
+    /// are reported as broken. `increment_value` is the largest step seen.
+    pub fn from_ip_ids(ids: &[u16]) -> Self {
+        let steps: Vec<u16> = ids.windows(2).map(|pair| pair[1].wrapping_sub(pair[0])).collect();
+        let increment_value = steps.iter().copied().max().unwrap_or(0);
+        let sequence_type = if steps.is_empty() {
+            IPIDSequenceType::Broken
+        } else if ids.iter().all(|&id| id == 0) {
+            IPIDSequenceType::Zero
+        } else if steps.iter().all(|&step| step % 256 == 0) {
+            IPIDSequenceType::Broken
+        } else if increment_value < 256 {
+            IPIDSequenceType::Incremental
+        } else {
+            IPIDSequenceType::Random
+        };
+        Self { sequence_type, increment_value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_types() {
+        let kind = |ids: &[u16]| IPIDSequence::from_ip_ids(ids).sequence_type;
+        assert_eq!(
+            IPIDSequence::from_ip_ids(&[100, 101, 102, 104]),
+            IPIDSequence { sequence_type: IPIDSequenceType::Incremental, increment_value: 2 }
+        );
+        assert_eq!(kind(&[65535, 0, 1]), IPIDSequenceType::Incremental);
+        assert_eq!(kind(&[0, 0, 0]), IPIDSequenceType::Zero);
+        assert_eq!(kind(&[0x0100, 0x0200, 0x0300]), IPIDSequenceType::Broken);
+        assert_eq!(kind(&[7, 7, 7]), IPIDSequenceType::Broken);
+        assert_eq!(kind(&[1200, 40211, 9931]), IPIDSequenceType::Random);
+        assert_eq!(kind(&[42]), IPIDSequenceType::Broken);
+    }
+}
@@ -0,0 +1,188 @@
+//! SYN probes over IPv6
+//!
+//! IPv6 raw sockets never see or write the IP header, so the IPv4 packet
+//! builder does not apply: an [`Ipv6SynLink`] sends bare TCP segments,
+//! checksummed over the IPv6 pseudo-header with the source address the
+//! kernel routes each target from, and a receiver thread matches the
+//! segments that come back to the probes waiting for them.
+
+use super::protocol::NetworkUtils;
+use super::socket::RawSocket;
+use super::PortState;
+use crate::ScanError;
+use phobos_packet::{tcp, TcpHeader};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Local ports used for probes; the host stack answers replies with a RST
+const SOURCE_PORTS: std::ops::Range<u16> = 40000..60000;
+
+/// How often the receiver thread looks at the closed flag
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Source port, destination port and flags of a TCP segment
+fn parse_segment(segment: &[u8]) -> Option<(u16, u16, u8)> {
+    let header = segment.get(..tcp::HEADER_LEN)?;
+    Some((
+        u16::from_be_bytes([header[0], header[1]]),
+        u16::from_be_bytes([header[2], header[3]]),
+        header[13],
+    ))
+}
+
+/// (target, target port, our port) -> TCP flags of the reply
+type Waiters = HashMap<(Ipv6Addr, u16, u16), oneshot::Sender<u8>>;
+
+/// Raw IPv6 TCP socket with its receiver
+pub struct Ipv6SynLink {
+    socket: Arc<RawSocket>,
+    waiters: Arc<Mutex<Waiters>>,
+    /// Source address for each target, as the routing table has it
+    sources: Mutex<HashMap<Ipv6Addr, Ipv6Addr>>,
+    next_port: AtomicU16,
+    mss: Option<u16>,
+    closed: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Ipv6SynLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ipv6SynLink").field("mss", &self.mss).finish()
+    }
+}
+
+impl Ipv6SynLink {
+    /// Open the raw socket and start its receiver; needs CAP_NET_RAW
+    pub fn open() -> crate::Result<Self> {
+        let socket = RawSocket::new_tcp6()?;
+        socket.set_nonblocking(false)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let socket = Arc::new(socket);
+
+        let waiters = Arc::new(Mutex::new(Waiters::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let (socket, waiters, closed) = (Arc::clone(&socket), Arc::clone(&waiters), Arc::clone(&closed));
+            std::thread::Builder::new()
+                .name("syn6-rx".to_string())
+                .spawn(move || {
+                    let mut buf = [0u8; 1500];
+                    while !closed.load(Ordering::Relaxed) {
+                        // Read timeouts surface as errors; just poll the flag again
+                        let Ok((size, from)) = socket.recv_from(&mut buf) else { continue };
+                        let (IpAddr::V6(src), Some((sport, dport, flags))) = (from.ip(), parse_segment(&buf[..size])) else {
+                            continue;
+                        };
+                        let waiter = waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&(src, sport, dport));
+                        if let Some(waiter) = waiter {
+                            let _ = waiter.send(flags);
+                        }
+                    }
+                })
+                .map_err(ScanError::IoError)?;
+        }
+
+        Ok(Self {
+            socket,
+            waiters,
+            sources: Mutex::new(HashMap::new()),
+            next_port: AtomicU16::new(SOURCE_PORTS.start),
+            mss: None,
+            closed,
+        })
+    }
+
+    /// Advertise `mss` in probes
+    pub fn with_mss(mut self, mss: u16) -> Self {
+        self.mss = Some(mss);
+        self
+    }
+
+    fn source_for(&self, target: Ipv6Addr) -> crate::Result<Ipv6Addr> {
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&source) = sources.get(&target) {
+            return Ok(source);
+        }
+        let IpAddr::V6(source) = NetworkUtils::source_for(IpAddr::V6(target))? else {
+            return Err(ScanError::NetworkError(format!("no IPv6 source address for {}", target)));
+        };
+        sources.insert(target, source);
+        Ok(source)
+    }
+
+    /// Send a SYN to `target:port` and classify the reply
+    pub async fn probe_syn(&self, target: Ipv6Addr, port: u16, timeout: Duration) -> crate::Result<PortState> {
+        let source = self.source_for(target)?;
+        let span = SOURCE_PORTS.end - SOURCE_PORTS.start;
+        let local_port = SOURCE_PORTS.start + self.next_port.fetch_add(1, Ordering::Relaxed) % span;
+        let key = (target, port, local_port);
+
+        let syn = TcpHeader {
+            flags: tcp::SYN,
+            sequence: rand::random(),
+            mss: self.mss,
+            ..TcpHeader::new(local_port, port)
+        };
+        let mut segment = vec![0u8; syn.header_len()];
+        syn.write_with_checksum_v6(&mut segment, source.octets(), target.octets())
+            .map_err(|e| ScanError::NetworkError(e.to_string()))?;
+
+        let (waiter, reply) = oneshot::channel();
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).insert(key, waiter);
+        // The port field of a raw socket's address must be zero
+        if let Err(e) = self.socket.send_to(&segment, SocketAddr::new(IpAddr::V6(target), 0)) {
+            self.waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+            return Err(e);
+        }
+
+        let state = match tokio::time::timeout(timeout, reply).await {
+            Ok(Ok(flags)) if flags & 0x12 == 0x12 => PortState::Open,
+            Ok(Ok(flags)) if flags & 0x04 != 0 => PortState::Closed,
+            _ => PortState::Filtered,
+        };
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        Ok(state)
+    }
+}
+
+impl Drop for Ipv6SynLink {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_segment() {
+        let syn_ack = TcpHeader { flags: tcp::SYN | tcp::ACK, ..TcpHeader::new(443, 40001) };
+        let mut segment = [0u8; 20];
+        syn_ack.write(&mut segment).unwrap();
+        assert_eq!(parse_segment(&segment), Some((443, 40001, 0x12)));
+        assert_eq!(parse_segment(&segment[..19]), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_syn_on_loopback() {
+        let Ok(link) = Ipv6SynLink::open() else {
+            eprintln!("skipping test_probe_syn_on_loopback: raw sockets need root or CAP_NET_RAW");
+            return;
+        };
+        let Ok(listener) = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)) else {
+            eprintln!("skipping test_probe_syn_on_loopback: no IPv6 loopback");
+            return;
+        };
+        let open = listener.local_addr().unwrap().port();
+        let closed = std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(link.probe_syn(Ipv6Addr::LOCALHOST, open, timeout).await.unwrap(), PortState::Open);
+        assert_eq!(link.probe_syn(Ipv6Addr::LOCALHOST, closed, timeout).await.unwrap(), PortState::Closed);
+    }
+}
@@ -0,0 +1,396 @@
+//! Companion listener for reachability checks (`phobos listen`)
+//!
+//! Run on a host you control, a [`Listener`] opens TCP and UDP ports and
+//! records every probe that reaches them while a scan runs from elsewhere:
+//! which port, from which address, and how long the round trip took. Set
+//! against the scan's results, that tells a firewall dropping probes on the
+//! way in from one dropping the answers on the way back.
+//!
+//! Completed TCP handshakes are accepted and closed at once, with the round
+//! trip the kernel measured during the handshake. UDP datagrams are echoed,
+//! so a UDP scan sees the port open. SYN scans never complete a handshake;
+//! with raw socket privileges, half-open probes to IPv4 listeners are
+//! captured as well, the round trip being the time from the SYN to the
+//! scanner's RST.
+
+use crate::network::packet::PacketParser;
+use crate::network::socket::RawSocket;
+use crate::network::Protocol;
+use crate::scanner::checkpoint::PortSet;
+use crate::scanner::CancellationToken;
+use pnet::packet::tcp::TcpFlags;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+/// How long a captured SYN waits for the scanner's RST or ACK
+const HALF_OPEN_WAIT: Duration = Duration::from_secs(3);
+
+/// Largest UDP datagram read and echoed
+const MAX_DATAGRAM: usize = 2048;
+
+/// How a probe showed up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrivalKind {
+    /// A completed TCP handshake
+    Connect,
+    /// A SYN the scanner answered our SYN/ACK to with a RST, or never answered
+    HalfOpen,
+    /// A UDP datagram of this many bytes
+    Datagram(usize),
+}
+
+/// One probe that reached a listening port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrival {
+    pub protocol: Protocol,
+    pub port: u16,
+    pub source: SocketAddr,
+    pub kind: ArrivalKind,
+    /// Round trip between us and the scanner, where it can be told
+    pub rtt: Option<Duration>,
+    /// Since listening started
+    pub elapsed: Duration,
+}
+
+/// Ports that could not be opened, and why
+#[derive(Debug)]
+pub struct BindFailure {
+    pub protocol: Protocol,
+    pub port: u16,
+    pub error: io::Error,
+}
+
+/// Open listening ports, ready to [`run`](Self::run)
+pub struct Listener {
+    tcp: Vec<(u16, TcpListener)>,
+    udp: Vec<(u16, UdpSocket)>,
+    failures: Vec<BindFailure>,
+    capture: Option<RawSocket>,
+}
+
+impl Listener {
+    /// Listen on `tcp_ports` and `udp_ports` of every local address; ports
+    /// that cannot be opened are left out and listed in [`failures`](Self::failures)
+    pub async fn bind(tcp_ports: &[u16], udp_ports: &[u16]) -> Self {
+        let mut listener = Self { tcp: Vec::new(), udp: Vec::new(), failures: Vec::new(), capture: None };
+        for &port in tcp_ports {
+            match bind_any(port, |address| async move { TcpListener::bind(address).await }).await {
+                Ok(socket) => listener.tcp.push((port, socket)),
+                Err(error) => listener.failures.push(BindFailure { protocol: Protocol::Tcp, port, error }),
+            }
+        }
+        for &port in udp_ports {
+            match bind_any(port, |address| async move { UdpSocket::bind(address).await }).await {
+                Ok(socket) => listener.udp.push((port, socket)),
+                Err(error) => listener.failures.push(BindFailure { protocol: Protocol::Udp, port, error }),
+            }
+        }
+        if !listener.tcp.is_empty() {
+            listener.capture = RawSocket::new_tcp()
+                .and_then(|socket| {
+                    socket.set_nonblocking(false)?;
+                    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+                    Ok(socket)
+                })
+                .map_err(|e| log::debug!("Half-open probes will not be seen: {}", e))
+                .ok();
+        }
+        listener
+    }
+
+    /// Ports listened on with `protocol`
+    pub fn ports(&self, protocol: Protocol) -> Vec<u16> {
+        match protocol {
+            Protocol::Tcp => self.tcp.iter().map(|(port, _)| *port).collect(),
+            Protocol::Udp => self.udp.iter().map(|(port, _)| *port).collect(),
+            Protocol::Icmp => Vec::new(),
+        }
+    }
+
+    pub fn failures(&self) -> &[BindFailure] {
+        &self.failures
+    }
+
+    /// Whether SYN scan probes are seen, which takes raw socket privileges
+    pub fn sees_half_open(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Record arrivals, passing each to `on_arrival` as it comes, until
+    /// `cancel` fires
+    pub async fn run(self, cancel: CancellationToken, mut on_arrival: impl FnMut(&Arrival)) -> ListenReport {
+        let started = Instant::now();
+        let (tcp_ports, udp_ports) = (self.ports(Protocol::Tcp), self.ports(Protocol::Udp));
+        let (sender, mut arrivals) = mpsc::unbounded_channel();
+        let mut tasks = Vec::new();
+        for (port, socket) in self.tcp {
+            tasks.push(tokio::spawn(accept_connections(port, socket, started, sender.clone())));
+        }
+        for (port, socket) in self.udp {
+            tasks.push(tokio::spawn(echo_datagrams(port, socket, started, sender.clone())));
+        }
+        let closed = Arc::new(AtomicBool::new(false));
+        if let Some(capture) = self.capture {
+            let (ports, closed, sender) = (tcp_ports.iter().copied().collect(), Arc::clone(&closed), sender.clone());
+            std::thread::Builder::new()
+                .name("listen-capture".to_string())
+                .spawn(move || capture_half_open(capture, ports, started, closed, sender))
+                .map_err(|e| log::warn!("Half-open probes will not be seen: {}", e))
+                .ok();
+        }
+        drop(sender);
+
+        let mut report = ListenReport { tcp_ports, udp_ports, arrivals: Vec::new() };
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                arrival = arrivals.recv() => match arrival {
+                    Some(arrival) => {
+                        on_arrival(&arrival);
+                        report.arrivals.push(arrival);
+                    }
+                    None => break,
+                },
+            }
+        }
+        closed.store(true, Ordering::Relaxed);
+        for task in tasks {
+            task.abort();
+        }
+        report
+    }
+}
+
+/// Bind `port` on all IPv6 and IPv4 addresses, or IPv4 ones alone on hosts
+/// without IPv6
+async fn bind_any<S, F>(port: u16, bind: impl Fn(SocketAddr) -> F) -> io::Result<S>
+where
+    F: std::future::Future<Output = io::Result<S>>,
+{
+    match bind(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port)).await {
+        Err(e) if e.kind() != io::ErrorKind::AddrInUse && e.kind() != io::ErrorKind::PermissionDenied => {
+            bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)).await
+        }
+        bound => bound,
+    }
+}
+
+/// `address` with an IPv4-mapped IPv6 address, as dual-stack sockets
+/// report IPv4 peers, turned back into IPv4
+fn unmapped(address: SocketAddr) -> SocketAddr {
+    match address.ip() {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), address.port()),
+            None => address,
+        },
+        IpAddr::V4(_) => address,
+    }
+}
+
+async fn accept_connections(port: u16, socket: TcpListener, started: Instant, arrivals: UnboundedSender<Arrival>) {
+    loop {
+        let (stream, peer) = match socket.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Out of descriptors or the like; the next connection may fare better
+                log::debug!("accept on tcp/{}: {}", port, e);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                continue;
+            }
+        };
+        let arrival = Arrival {
+            protocol: Protocol::Tcp,
+            port,
+            source: unmapped(peer),
+            kind: ArrivalKind::Connect,
+            rtt: handshake_rtt(&stream),
+            elapsed: started.elapsed(),
+        };
+        drop(stream);
+        if arrivals.send(arrival).is_err() {
+            return;
+        }
+    }
+}
+
+async fn echo_datagrams(port: u16, socket: UdpSocket, started: Instant, arrivals: UnboundedSender<Arrival>) {
+    let mut buf = [0u8; MAX_DATAGRAM];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            // An ICMP error for an earlier echo; nothing arrived
+            Err(_) => continue,
+        };
+        // The same size back, so the echo cannot amplify a spoofed probe
+        let _ = socket.send_to(&buf[..len], peer).await;
+        let arrival = Arrival {
+            protocol: Protocol::Udp,
+            port,
+            source: unmapped(peer),
+            kind: ArrivalKind::Datagram(len),
+            rtt: None,
+            elapsed: started.elapsed(),
+        };
+        if arrivals.send(arrival).is_err() {
+            return;
+        }
+    }
+}
+
+/// The kernel's round trip estimate for a just-accepted connection, which
+/// only the handshake has contributed to
+#[cfg(target_os = "linux")]
+fn handshake_rtt(stream: &tokio::net::TcpStream) -> Option<Duration> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: tcp_info is plain data and the kernel writes at most `len` bytes of it
+    let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(stream.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_INFO, &mut info as *mut _ as *mut libc::c_void, &mut len)
+    };
+    (result == 0 && info.tcpi_rtt > 0).then(|| Duration::from_micros(u64::from(info.tcpi_rtt)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn handshake_rtt(_stream: &tokio::net::TcpStream) -> Option<Duration> {
+    None
+}
+
+/// Report SYNs to `ports` that the scanner resets instead of completing;
+/// completed handshakes are left to the listeners
+fn capture_half_open(capture: RawSocket, ports: HashSet<u16>, started: Instant, closed: Arc<AtomicBool>, arrivals: UnboundedSender<Arrival>) {
+    let half_open = |source: SocketAddr, port: u16, rtt: Option<Duration>| Arrival {
+        protocol: Protocol::Tcp,
+        port,
+        source,
+        kind: ArrivalKind::HalfOpen,
+        rtt,
+        elapsed: started.elapsed(),
+    };
+    let mut syns: HashMap<(SocketAddr, u16), Instant> = HashMap::new();
+    let mut buf = [0u8; 1500];
+    while !closed.load(Ordering::Relaxed) {
+        if let Ok((len, _)) = capture.recv_from(&mut buf) {
+            if let Some(segment) = PacketParser::parse_tcp_response(&buf[..len]).filter(|s| ports.contains(&s.dest_port)) {
+                let key = (SocketAddr::new(IpAddr::V4(segment.source_ip), segment.source_port), segment.dest_port);
+                let flags = u16::from(segment.flags);
+                if flags & TcpFlags::SYN != 0 && flags & TcpFlags::ACK == 0 {
+                    syns.insert(key, Instant::now());
+                } else if let Some(sent) = syns.remove(&key) {
+                    // An ACK completes the handshake the listener reports
+                    if flags & TcpFlags::RST != 0 && arrivals.send(half_open(key.0, key.1, Some(sent.elapsed()))).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        let now = Instant::now();
+        let expired: Vec<_> = syns.iter().filter(|(_, sent)| now - **sent > HALF_OPEN_WAIT).map(|(key, _)| *key).collect();
+        for key in expired {
+            syns.remove(&key);
+            if arrivals.send(half_open(key.0, key.1, None)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Everything that arrived while listening
+#[derive(Debug, Clone, Default)]
+pub struct ListenReport {
+    pub tcp_ports: Vec<u16>,
+    pub udp_ports: Vec<u16>,
+    pub arrivals: Vec<Arrival>,
+}
+
+/// Arrivals at one port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortSummary {
+    pub protocol: Protocol,
+    pub port: u16,
+    pub arrivals: usize,
+    pub sources: Vec<IpAddr>,
+    /// Lowest and highest round trip measured
+    pub rtt: Option<(Duration, Duration)>,
+}
+
+impl ListenReport {
+    /// Arrivals grouped by port, TCP first, in port order
+    pub fn by_port(&self) -> Vec<PortSummary> {
+        let mut summaries: Vec<PortSummary> = Vec::new();
+        let mut arrivals: Vec<&Arrival> = self.arrivals.iter().collect();
+        arrivals.sort_by_key(|a| (a.protocol != Protocol::Tcp, a.port));
+        for arrival in arrivals {
+            let summary = match summaries.last_mut() {
+                Some(s) if (s.protocol, s.port) == (arrival.protocol, arrival.port) => s,
+                _ => {
+                    summaries.push(PortSummary { protocol: arrival.protocol, port: arrival.port, arrivals: 0, sources: Vec::new(), rtt: None });
+                    summaries.last_mut().expect("just pushed")
+                }
+            };
+            summary.arrivals += 1;
+            if !summary.sources.contains(&arrival.source.ip()) {
+                summary.sources.push(arrival.source.ip());
+            }
+            if let Some(rtt) = arrival.rtt {
+                summary.rtt = Some(summary.rtt.map_or((rtt, rtt), |(low, high)| (low.min(rtt), high.max(rtt))));
+            }
+        }
+        summaries
+    }
+
+    /// Ports of `protocol` nothing arrived at
+    pub fn silent_ports(&self, protocol: Protocol) -> PortSet {
+        let listened = match protocol {
+            Protocol::Tcp => &self.tcp_ports,
+            Protocol::Udp => &self.udp_ports,
+            Protocol::Icmp => return PortSet::new(),
+        };
+        let reached: HashSet<u16> = self.arrivals.iter().filter(|a| a.protocol == protocol).map(|a| a.port).collect();
+        listened.iter().copied().filter(|port| !reached.contains(port)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_records_connects_and_datagrams() {
+        // Ports picked by the kernel and released, so likely free
+        let tcp_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let udp_port = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let listener = Listener::bind(&[tcp_port], &[udp_port]).await;
+        assert!(listener.failures().is_empty());
+
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        let scan = tokio::spawn(async move {
+            tokio::net::TcpStream::connect(("127.0.0.1", tcp_port)).await.unwrap();
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.send_to(b"probe", ("127.0.0.1", udp_port)).await.unwrap();
+            let mut echo = [0u8; 16];
+            let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut echo)).await.unwrap().unwrap();
+            assert_eq!(&echo[..len], b"probe");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            stop.cancel();
+        });
+
+        let mut seen = 0;
+        let report = listener.run(cancel, |_| seen += 1).await;
+        scan.await.unwrap();
+        assert_eq!(seen, report.arrivals.len());
+
+        let summaries = report.by_port();
+        assert_eq!((summaries[0].protocol, summaries[0].port), (Protocol::Tcp, tcp_port));
+        assert_eq!(summaries[0].sources, [IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        assert_eq!((summaries[1].protocol, summaries[1].port, summaries[1].arrivals), (Protocol::Udp, udp_port, 1));
+        assert!(report.silent_ports(Protocol::Tcp).is_empty());
+    }
+}
@@ -1,14 +1,35 @@
 //! Network module for packet crafting and protocol handling
 
+pub mod anomaly;
+pub mod bandwidth;
+pub mod banner_rules;
+pub mod bpf;
+pub mod conntrack;
+pub mod dialer;
+pub mod dns;
+pub mod evidence;
 pub mod icmp;
+pub mod ip_id;
+pub mod ipv6;
+pub mod listen;
+pub mod mtu;
 pub mod packet;
+pub mod probes;
+pub mod proxy;
 pub mod protocol;
+pub mod route;
+pub mod send_thread;
 pub mod socket;
 pub mod stealth;
+pub mod unreachable;
+pub mod uptime;
+pub mod vlan;
 pub mod phobos_modes;
 
 use serde::{Deserialize, Serialize};
 
+pub use evidence::{Confidence, Evidence};
+
 /// Available scanning techniques
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ScanTechnique {
@@ -159,7 +180,19 @@ pub struct PortResult {
     pub protocol: Protocol,
     pub state: PortState,
     pub service: Option<String>,
+    /// Product and version behind `service`, from `--sV`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     pub response_time: std::time::Duration,
+    /// How well `state` is supported by the probes behind it
+    #[serde(default)]
+    pub confidence: Confidence,
+    /// Every observation merged into this result, once there is more than one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evidence: Vec<Evidence>,
+    /// Protocol anomalies seen in the replies behind this result
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<anomaly::Anomaly>,
 }
 
 impl PortResult {
@@ -169,7 +202,11 @@ impl PortResult {
             protocol,
             state,
             service: None,
+            version: None,
             response_time: std::time::Duration::from_millis(0),
+            confidence: Confidence::of(state),
+            evidence: Vec::new(),
+            anomalies: Vec::new(),
         }
     }
     
@@ -0,0 +1,433 @@
+//! Path MTU detection and MSS clamping
+//!
+//! Tunnels (WireGuard, IPsec, GRE, ...) carry fewer than the 1500 bytes of
+//! plain Ethernet. A crafted SYN advertising a larger MSS, or a UDP probe that
+//! does not fit, can be dropped inside the tunnel without any ICMP error
+//! reaching the scanner, and the port reads as filtered. The MTU towards a
+//! target comes from the kernel's route, which accounts for tunnel and IPsec
+//! overhead as well as learned path MTUs, then from the interface, and can be
+//! overridden with `--mtu-override`.
+//!
+//! Neither sees a blackhole further along the path, where a link drops large
+//! packets and the "fragmentation needed" error that should report it is
+//! lost or filtered. [`check_udp_path`] sends datagrams to the target itself
+//! before large UDP probes go out.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+#[cfg(target_os = "linux")]
+use std::net::{IpAddr, UdpSocket};
+
+/// MTU of plain Ethernet, assumed when nothing better is known
+pub const DEFAULT_MTU: u16 = 1500;
+/// Smallest MTU an IPv4 link may have (RFC 791)
+pub const MIN_MTU: u16 = 68;
+
+const IPV4_HEADER_LEN: u16 = 20;
+const TCP_HEADER_LEN: u16 = 20;
+const UDP_HEADER_LEN: u16 = 8;
+
+/// UDP payload that fits the 576-byte datagram every IPv4 host must accept
+/// (RFC 791); probes up to this size need no path check
+pub const SAFE_UDP_PAYLOAD: usize = (576 - IPV4_HEADER_LEN - UDP_HEADER_LEN) as usize;
+
+/// Port the path check sends to; traceroute's first, which nothing listens on
+const CHECK_PORT: u16 = 33434;
+
+/// Payload sizes closer together than this end the blackhole search. Coarse,
+/// because each step costs the target one ICMP error and Linux sends a host
+/// only a burst of six before limiting them to one a second.
+const SEARCH_STEP: usize = 128;
+
+/// Where a [`PathMtu`] came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MtuSource {
+    /// Given with `--mtu-override`
+    Override,
+    /// The kernel's route towards the target
+    Route,
+    /// The outgoing interface
+    Interface,
+    /// Nothing detected; Ethernet assumed
+    Default,
+    /// Measured by sending datagrams to the target
+    Probe,
+}
+
+impl MtuSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MtuSource::Override => "override",
+            MtuSource::Route => "route",
+            MtuSource::Interface => "interface",
+            MtuSource::Default => "default",
+            MtuSource::Probe => "probe",
+        }
+    }
+}
+
+/// Largest IPv4 packet that reaches a target unfragmented
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathMtu {
+    pub mtu: u16,
+    pub source: MtuSource,
+    /// Outgoing interface, when known
+    pub interface: Option<String>,
+    /// Tunnel type of the interface, e.g. "WireGuard"
+    pub tunnel: Option<&'static str>,
+}
+
+impl Default for PathMtu {
+    fn default() -> Self {
+        Self { mtu: DEFAULT_MTU, source: MtuSource::Default, interface: None, tunnel: None }
+    }
+}
+
+impl PathMtu {
+    /// A fixed MTU, as given with `--mtu-override`
+    pub fn fixed(mtu: u16) -> Self {
+        Self { mtu: mtu.max(MIN_MTU), source: MtuSource::Override, interface: None, tunnel: None }
+    }
+
+    /// Detect the MTU towards `target`, out of `interface` if one is forced
+    ///
+    /// The smaller of the route and interface MTUs wins. Without a target only
+    /// the interface is consulted.
+    pub fn detect(target: Option<Ipv4Addr>, interface: Option<&str>) -> Self {
+        let interface = interface
+            .map(str::to_string)
+            .or_else(|| target.and_then(super::route::egress).map(|(name, _)| name));
+        let link = interface.as_deref().and_then(interface_mtu);
+        let route = target.and_then(route_mtu);
+        let (mtu, source) = match (route, link) {
+            (Some(route), Some(link)) if link < route => (link, MtuSource::Interface),
+            (Some(route), _) => (route, MtuSource::Route),
+            (None, Some(link)) => (link, MtuSource::Interface),
+            (None, None) => (DEFAULT_MTU, MtuSource::Default),
+        };
+        let tunnel = interface.as_deref().and_then(interface_tunnel);
+        Self { mtu: mtu.max(MIN_MTU), source, interface, tunnel }
+    }
+
+    /// MSS to advertise so that segments fit the path
+    pub fn mss(&self) -> u16 {
+        self.mtu.saturating_sub(IPV4_HEADER_LEN + TCP_HEADER_LEN)
+    }
+
+    /// Largest UDP payload that fits the path
+    pub fn max_udp_payload(&self) -> usize {
+        self.mtu.saturating_sub(IPV4_HEADER_LEN + UDP_HEADER_LEN) as usize
+    }
+
+    /// Whether the path carries less than plain Ethernet
+    pub fn is_reduced(&self) -> bool {
+        self.mtu < DEFAULT_MTU
+    }
+
+    /// Lower the MTU to what `check` found the path carries, if it found less
+    pub fn limit_to(&mut self, check: PathCheck) {
+        let Some(payload) = check.payload_limit() else { return };
+        let mtu = (payload + (IPV4_HEADER_LEN + UDP_HEADER_LEN) as usize).min(u16::MAX as usize) as u16;
+        if mtu < self.mtu {
+            self.mtu = mtu.max(MIN_MTU);
+            self.source = MtuSource::Probe;
+        }
+    }
+}
+
+impl fmt::Display for PathMtu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes ({}", self.mtu, self.source.name())?;
+        if let Some(interface) = &self.interface {
+            write!(f, " via {}", interface)?;
+        }
+        if let Some(tunnel) = self.tunnel {
+            write!(f, ", {} tunnel", tunnel)?;
+        }
+        write!(f, "), MSS {}", self.mss())
+    }
+}
+
+/// What sending large UDP datagrams towards a target showed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathCheck {
+    /// The large datagram got the same answer as a small one
+    Clear,
+    /// The large datagram was refused as too big: a router answered
+    /// "fragmentation needed", or the kernel already knew the path MTU
+    FragNeeded(u16),
+    /// Small datagrams are answered and large ones vanish without an error;
+    /// payloads of up to `largest` bytes still got through
+    Blackhole { largest: usize },
+    /// Not even a small datagram was answered, so a dropping path cannot be
+    /// told apart from a silent target
+    Unanswered,
+}
+
+impl PathCheck {
+    /// Largest UDP payload to send, if the check found a limit
+    pub fn payload_limit(self) -> Option<usize> {
+        match self {
+            PathCheck::FragNeeded(mtu) => Some(PathMtu::fixed(mtu).max_udp_payload()),
+            PathCheck::Blackhole { largest } => Some(largest),
+            PathCheck::Clear | PathCheck::Unanswered => None,
+        }
+    }
+}
+
+impl fmt::Display for PathCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathCheck::Clear => write!(f, "large datagrams get through"),
+            PathCheck::FragNeeded(mtu) => write!(f, "fragmentation needed beyond {} bytes", mtu),
+            PathCheck::Blackhole { largest } => {
+                write!(f, "blackhole: payloads over {} bytes are dropped without an error", largest)
+            }
+            PathCheck::Unanswered => write!(f, "unverified: the target answers no datagrams"),
+        }
+    }
+}
+
+/// Whether UDP payloads of `size` bytes reach `target`
+///
+/// Datagrams with DF set go to a port nothing should listen on, so the
+/// target's port unreachable is what proves delivery. A small datagram
+/// first shows that the target answers at all. The large one then gets the
+/// same answer, a "fragmentation needed", or nothing; on nothing, a search
+/// between the two sizes finds the largest payload that still gets through.
+#[cfg(target_os = "linux")]
+pub async fn check_udp_path(target: Ipv4Addr, size: usize, timeout: Duration) -> PathCheck {
+    let socket = match path_check_socket(target) {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::debug!("cannot check the UDP path to {}: {}", target, e);
+            return PathCheck::Unanswered;
+        }
+    };
+    let too_big = || PathCheck::FragNeeded(socket_mtu(&socket).unwrap_or(MIN_MTU));
+    if datagram_answer(&socket, 1, timeout).await != Answer::Delivered {
+        return PathCheck::Unanswered;
+    }
+    // One retry, so a single lost datagram is not taken for a blackhole
+    let mut answer = datagram_answer(&socket, size, timeout).await;
+    if answer == Answer::Silent {
+        answer = datagram_answer(&socket, size, timeout).await;
+    }
+    match answer {
+        Answer::Delivered => return PathCheck::Clear,
+        Answer::TooBig => return too_big(),
+        Answer::Silent => {}
+    }
+    let (mut largest, mut dropped) = (1, size);
+    while dropped - largest > SEARCH_STEP {
+        let middle = largest + (dropped - largest) / 2;
+        match datagram_answer(&socket, middle, timeout).await {
+            Answer::Delivered => largest = middle,
+            Answer::TooBig => return too_big(),
+            Answer::Silent => dropped = middle,
+        }
+    }
+    PathCheck::Blackhole { largest }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn check_udp_path(_target: Ipv4Addr, _size: usize, _timeout: Duration) -> PathCheck {
+    PathCheck::Unanswered
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    /// The target answered: a port unreachable, or a reply
+    Delivered,
+    /// Refused as larger than the path carries
+    TooBig,
+    /// Nothing before the timeout, or an error from a router on the way
+    Silent,
+}
+
+/// A socket connected to the check port on `target`, sending with DF set
+#[cfg(target_os = "linux")]
+fn path_check_socket(target: Ipv4Addr) -> std::io::Result<tokio::net::UdpSocket> {
+    use std::os::unix::io::AsRawFd;
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect((IpAddr::V4(target), CHECK_PORT))?;
+    socket.set_nonblocking(true)?;
+    let discover: libc::c_int = libc::IP_PMTUDISC_DO;
+    // SAFETY: `discover` is valid for reads of the size passed
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            &discover as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    tokio::net::UdpSocket::from_std(socket)
+}
+
+/// Send `size` bytes on `socket` and wait for what comes back
+#[cfg(target_os = "linux")]
+async fn datagram_answer(socket: &tokio::net::UdpSocket, size: usize, timeout: Duration) -> Answer {
+    use tokio::io::Interest;
+
+    let classify = |e: &std::io::Error| match e.raw_os_error() {
+        Some(libc::EMSGSIZE) => Answer::TooBig,
+        _ if e.kind() == std::io::ErrorKind::ConnectionRefused => Answer::Delivered,
+        _ => Answer::Silent,
+    };
+    if let Err(e) = socket.send(&vec![0u8; size]).await {
+        return classify(&e);
+    }
+    let mut buffer = [0u8; 512];
+    let reply = async {
+        loop {
+            let ready = socket.ready(Interest::READABLE | Interest::ERROR).await?;
+            if ready.is_error() {
+                if let Some(e) = socket.take_error()? {
+                    return Err(e);
+                }
+            }
+            match socket.try_recv(&mut buffer) {
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                received => return received,
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, reply).await {
+        Ok(Ok(_)) => Answer::Delivered,
+        Ok(Err(e)) => classify(&e),
+        Err(_) => Answer::Silent,
+    }
+}
+
+/// MTU of the kernel's route towards `target`, including learned path MTUs
+#[cfg(target_os = "linux")]
+fn route_mtu(target: Ipv4Addr) -> Option<u16> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((IpAddr::V4(target), 9)).ok()?;
+    socket_mtu(&socket)
+}
+
+/// Path MTU the kernel holds for the destination `socket` is connected to
+#[cfg(target_os = "linux")]
+fn socket_mtu(socket: &impl std::os::unix::io::AsRawFd) -> Option<u16> {
+    let mut mtu: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `mtu` and `len` are valid for writes of the sizes passed
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MTU,
+            &mut mtu as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    (result == 0 && mtu > 0).then(|| mtu.min(u16::MAX as libc::c_int) as u16)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn route_mtu(_target: Ipv4Addr) -> Option<u16> {
+    None
+}
+
+fn sysfs(interface: &str, attribute: &str) -> Option<String> {
+    std::fs::read_to_string(format!("/sys/class/net/{}/{}", interface, attribute)).ok()
+}
+
+/// MTU configured on `interface` (Linux only)
+fn interface_mtu(interface: &str) -> Option<u16> {
+    let mtu: u32 = sysfs(interface, "mtu")?.trim().parse().ok()?;
+    Some(mtu.min(u16::MAX as u32) as u16)
+}
+
+fn interface_tunnel(interface: &str) -> Option<&'static str> {
+    let hw_type = sysfs(interface, "type").and_then(|t| t.trim().parse().ok());
+    let uevent = sysfs(interface, "uevent").unwrap_or_default();
+    let devtype = uevent.lines().find_map(|line| line.strip_prefix("DEVTYPE="));
+    classify_tunnel(interface, hw_type, devtype)
+}
+
+/// Tunnel type from the interface name, ARP hardware type and udev device type
+fn classify_tunnel(name: &str, hw_type: Option<u16>, devtype: Option<&str>) -> Option<&'static str> {
+    if devtype == Some("wireguard") {
+        return Some("WireGuard");
+    }
+    const PREFIXES: [(&str, &str); 8] = [
+        ("wg", "WireGuard"),
+        ("ipsec", "IPsec"),
+        ("xfrm", "IPsec"),
+        ("vti", "IPsec"),
+        ("gre", "GRE"),
+        ("utun", "TUN"),
+        ("tun", "TUN"),
+        ("ppp", "PPP"),
+    ];
+    if let Some((_, kind)) = PREFIXES.iter().find(|(prefix, _)| name.starts_with(prefix)) {
+        return Some(kind);
+    }
+    // ARPHRD_* values from <linux/if_arp.h>
+    match hw_type? {
+        768 => Some("IP-in-IP"),
+        776 => Some("SIT"),
+        778 | 823 => Some("GRE"),
+        512 => Some("PPP"),
+        65534 => Some("TUN"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::packet::TcpPacketBuilder;
+
+    #[test]
+    fn test_clamping_and_tunnel_classification() {
+        let path = PathMtu::fixed(1420);
+        assert_eq!((path.mss(), path.max_udp_payload()), (1380, 1392));
+        assert!(path.is_reduced());
+        assert_eq!(PathMtu::fixed(10).mtu, MIN_MTU);
+        assert_eq!(path.to_string(), "1420 bytes (override), MSS 1380");
+
+        assert_eq!(classify_tunnel("wg0", Some(65534), Some("wireguard")), Some("WireGuard"));
+        assert_eq!(classify_tunnel("vpn", Some(65534), None), Some("TUN"));
+        assert_eq!(classify_tunnel("gretap1", None, None), Some("GRE"));
+        assert_eq!(classify_tunnel("eth0", Some(1), None), None);
+
+        let syn = TcpPacketBuilder::new(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2), 40000, 443)
+            .syn()
+            .mss(path.mss())
+            .build();
+        assert_eq!(syn.len(), 44);
+        assert_eq!(syn[20 + 12] >> 4, 6);
+        assert_eq!(&syn[40..44], &[2, 4, 0x05, 0x64]);
+    }
+
+    #[tokio::test]
+    async fn test_udp_path_check() {
+        let mut path = PathMtu::fixed(1500);
+        path.limit_to(PathCheck::Blackhole { largest: 1172 });
+        assert_eq!((path.mtu, path.source), (1200, MtuSource::Probe));
+        path.limit_to(PathCheck::FragNeeded(1400));
+        assert_eq!(path.mtu, 1200);
+        path.limit_to(PathCheck::FragNeeded(1000));
+        assert_eq!((path.mtu, path.max_udp_payload()), (1000, 972));
+        assert_eq!(PathCheck::Unanswered.payload_limit(), None);
+
+        // Loopback refuses the check port, small datagram or large
+        if cfg!(target_os = "linux") {
+            let check = check_udp_path(Ipv4Addr::LOCALHOST, 1400, Duration::from_secs(1)).await;
+            assert_eq!(check, PathCheck::Clear);
+        }
+    }
+}
@@ -1,9 +1,14 @@
 //! Packet crafting and manipulation module
+//!
+//! Header layout and checksums come from the `phobos-packet` crate, which
+//! has no dependencies and builds for `no_std` targets; this module adds the
+//! scanner's defaults (random sequence numbers and IP IDs) and evasion knobs.
 
+use phobos_packet::{ipv4, tcp, udp, Ipv4Header, TcpHeader, UdpHeader, IPPROTO_TCP, IPPROTO_UDP};
 use pnet::packet::ip::IpNextHeaderProtocols;
-use pnet::packet::ipv4::{Ipv4Packet, MutableIpv4Packet};
-use pnet::packet::tcp::{MutableTcpPacket, TcpFlags, TcpPacket};
-use pnet::packet::udp::{MutableUdpPacket, UdpPacket};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::tcp::{TcpFlags, TcpPacket};
+use pnet::packet::udp::UdpPacket;
 use pnet::packet::Packet;
 use rand::Rng;
 use std::net::Ipv4Addr;
@@ -21,6 +26,8 @@ pub struct TcpPacketBuilder {
     ip_id: u16,
     padding: Option<usize>,
     mtu: Option<u16>,
+    mss: Option<u16>,
+    timestamp: Option<u32>,
     bad_checksum: bool,
 }
 
@@ -39,6 +46,8 @@ impl TcpPacketBuilder {
             ip_id: rng.gen(),
             padding: None,
             mtu: None,
+            mss: None,
+            timestamp: None,
             bad_checksum: false,
         }
     }
@@ -49,32 +58,32 @@ impl TcpPacketBuilder {
     }
     
     pub fn syn(mut self) -> Self {
-        self.flags |= TcpFlags::SYN as u8;
+        self.flags |= tcp::SYN;
         self
     }
     
     pub fn ack(mut self) -> Self {
-        self.flags |= TcpFlags::ACK as u8;
+        self.flags |= tcp::ACK;
         self
     }
     
     pub fn fin(mut self) -> Self {
-        self.flags |= TcpFlags::FIN as u8;
+        self.flags |= tcp::FIN;
         self
     }
     
     pub fn rst(mut self) -> Self {
-        self.flags |= TcpFlags::RST as u8;
+        self.flags |= tcp::RST;
         self
     }
     
     pub fn psh(mut self) -> Self {
-        self.flags |= TcpFlags::PSH as u8;
+        self.flags |= tcp::PSH;
         self
     }
     
     pub fn urg(mut self) -> Self {
-        self.flags |= TcpFlags::URG as u8;
+        self.flags |= tcp::URG;
         self
     }
     
@@ -93,6 +102,19 @@ impl TcpPacketBuilder {
         self
     }
     
+    /// Advertise `mss` in an MSS option
+    pub fn mss(mut self, mss: u16) -> Self {
+        self.mss = Some(mss);
+        self
+    }
+    
+    /// Send `tsval` in a timestamp option, which asks the peer to stamp its
+    /// reply with its own clock
+    pub fn timestamp(mut self, tsval: u32) -> Self {
+        self.timestamp = Some(tsval);
+        self
+    }
+    
     /// Set source port (for stealth)
     pub fn source_port(&mut self, port: u16) {
         self.source_port = port;
@@ -125,74 +147,46 @@ impl TcpPacketBuilder {
     
     /// Build the complete IP + TCP packet
     pub fn build(self) -> Vec<u8> {
-        const IP_HEADER_LEN: usize = 20;
-        let tcp_header_len = 20 + self.padding.unwrap_or(0);
-        let total_len = IP_HEADER_LEN + tcp_header_len;
+        let mut tcp = TcpHeader {
+            sequence: self.seq_num,
+            acknowledgement: self.ack_num,
+            flags: self.flags,
+            window: self.window_size,
+            mss: self.mss,
+            timestamp: self.timestamp.map(|tsval| (tsval, 0)),
+            ..TcpHeader::new(self.source_port, self.dest_port)
+        };
+        let total_len = ipv4::HEADER_LEN + tcp.header_len() + self.padding.unwrap_or(0);
         
         // Apply MTU limit if specified
-        let final_len = if let Some(mtu) = self.mtu {
-            std::cmp::min(total_len, mtu as usize)
-        } else {
-            total_len
+        let final_len = match self.mtu {
+            Some(mtu) => std::cmp::min(total_len, mtu as usize),
+            None => total_len,
         };
         
-        let mut packet_buf = vec![0u8; final_len];
+        let mut packet_buf = vec![0u8; final_len.max(ipv4::HEADER_LEN)];
+        let ip = Ipv4Header {
+            identification: self.ip_id, // Use custom IP ID
+            ..Ipv4Header::new(self.source_ip.octets(), self.dest_ip.octets(), IPPROTO_TCP)
+        };
+        ip.write(&mut packet_buf, final_len).expect("buffer holds the IP header");
         
-        // Build IP header
-        {
-            let mut ip_packet = MutableIpv4Packet::new(&mut packet_buf[..IP_HEADER_LEN]).unwrap();
-            ip_packet.set_version(4);
-            ip_packet.set_header_length(5); // 5 * 4 = 20 bytes
-            ip_packet.set_dscp(0);
-            ip_packet.set_ecn(0);
-            ip_packet.set_total_length(final_len as u16);
-            ip_packet.set_identification(self.ip_id); // Use custom IP ID
-            ip_packet.set_flags(2); // Don't fragment
-            ip_packet.set_fragment_offset(0);
-            ip_packet.set_ttl(64);
-            ip_packet.set_next_level_protocol(IpNextHeaderProtocols::Tcp);
-            ip_packet.set_source(self.source_ip);
-            ip_packet.set_destination(self.dest_ip);
-            
-            // Calculate and set IP checksum
-            let checksum = pnet::packet::ipv4::checksum(&ip_packet.to_immutable());
-            ip_packet.set_checksum(checksum);
+        // Drop options the MTU leaves no room for; padding stays zero
+        let segment = &mut packet_buf[ipv4::HEADER_LEN..];
+        if segment.len() < tcp.header_len() {
+            tcp.timestamp = None;
         }
-        
-        // Build TCP header
-        {
-            let tcp_len = final_len - IP_HEADER_LEN;
-            if tcp_len >= 20 {
-                let mut tcp_packet = MutableTcpPacket::new(&mut packet_buf[IP_HEADER_LEN..IP_HEADER_LEN + std::cmp::min(tcp_len, 20)]).unwrap();
-                tcp_packet.set_source(self.source_port);
-                tcp_packet.set_destination(self.dest_port);
-                tcp_packet.set_sequence(self.seq_num);
-                tcp_packet.set_acknowledgement(self.ack_num);
-                tcp_packet.set_data_offset(5); // 5 * 4 = 20 bytes
-                tcp_packet.set_flags(self.flags as u16);
-                tcp_packet.set_window(self.window_size);
-                tcp_packet.set_urgent_ptr(0);
-                
-                // Calculate and set TCP checksum
-                let checksum = if self.bad_checksum {
-                    0xFFFF // Intentionally bad checksum for evasion
-                } else {
-                    pnet::packet::tcp::ipv4_checksum(
-                        &tcp_packet.to_immutable(),
-                        &self.source_ip,
-                        &self.dest_ip,
-                    )
-                };
-                tcp_packet.set_checksum(checksum);
-                
-                // Add padding if specified
-                if let Some(padding) = self.padding {
-                    let padding_start = IP_HEADER_LEN + 20;
-                    let padding_end = std::cmp::min(padding_start + padding, final_len);
-                    for i in padding_start..padding_end {
-                        packet_buf[i] = 0x00; // NOP padding
-                    }
-                }
+        if segment.len() < tcp.header_len() {
+            tcp.mss = None;
+        }
+        if segment.len() >= tcp.header_len() {
+            if self.bad_checksum {
+                tcp.write(segment).expect("segment holds the TCP header");
+                // Intentionally bad checksum for evasion
+                segment[16..18].copy_from_slice(&0xFFFFu16.to_be_bytes());
+            } else {
+                tcp.write_with_checksum(segment, self.source_ip.octets(), self.dest_ip.octets())
+                    .expect("segment holds the TCP header");
             }
         }
         
@@ -227,48 +221,20 @@ impl UdpPacketBuilder {
     
     /// Build the complete IP + UDP packet
     pub fn build(self) -> Vec<u8> {
-        const IP_HEADER_LEN: usize = 20;
-        const UDP_HEADER_LEN: usize = 8;
-        let total_len = IP_HEADER_LEN + UDP_HEADER_LEN + self.payload.len();
-        
+        let total_len = ipv4::HEADER_LEN + udp::HEADER_LEN + self.payload.len();
         let mut packet_buf = vec![0u8; total_len];
         
-        // Build IP header
-        {
-            let mut ip_packet = MutableIpv4Packet::new(&mut packet_buf[..IP_HEADER_LEN]).unwrap();
-            ip_packet.set_version(4);
-            ip_packet.set_header_length(5);
-            ip_packet.set_dscp(0);
-            ip_packet.set_ecn(0);
-            ip_packet.set_total_length(total_len as u16);
-            ip_packet.set_identification(rand::thread_rng().gen());
-            ip_packet.set_flags(2);
-            ip_packet.set_fragment_offset(0);
-            ip_packet.set_ttl(64);
-            ip_packet.set_next_level_protocol(IpNextHeaderProtocols::Udp);
-            ip_packet.set_source(self.source_ip);
-            ip_packet.set_destination(self.dest_ip);
-            
-            let checksum = pnet::packet::ipv4::checksum(&ip_packet.to_immutable());
-            ip_packet.set_checksum(checksum);
-        }
+        let ip = Ipv4Header {
+            identification: rand::thread_rng().gen(),
+            ..Ipv4Header::new(self.source_ip.octets(), self.dest_ip.octets(), IPPROTO_UDP)
+        };
+        ip.write(&mut packet_buf, total_len).expect("buffer holds the IP header");
         
-        // Build UDP header and payload
-        {
-            let udp_len = UDP_HEADER_LEN + self.payload.len();
-            let mut udp_packet = MutableUdpPacket::new(&mut packet_buf[IP_HEADER_LEN..IP_HEADER_LEN + udp_len]).unwrap();
-            udp_packet.set_source(self.source_port);
-            udp_packet.set_destination(self.dest_port);
-            udp_packet.set_length(udp_len as u16);
-            udp_packet.set_payload(&self.payload);
-            
-            let checksum = pnet::packet::udp::ipv4_checksum(
-                &udp_packet.to_immutable(),
-                &self.source_ip,
-                &self.dest_ip,
-            );
-            udp_packet.set_checksum(checksum);
-        }
+        let datagram = &mut packet_buf[ipv4::HEADER_LEN..];
+        datagram[udp::HEADER_LEN..].copy_from_slice(&self.payload);
+        UdpHeader::new(self.source_port, self.dest_port)
+            .write_with_checksum(datagram, self.source_ip.octets(), self.dest_ip.octets())
+            .expect("datagram holds the UDP header");
         
         packet_buf
     }
@@ -291,16 +257,22 @@ impl PacketParser {
         
         let ip_header_len = (ip_packet.get_header_length() as usize) * 4;
         let tcp_packet = TcpPacket::new(&packet[ip_header_len..])?;
+        let checksum = pnet::packet::tcp::ipv4_checksum(&tcp_packet, &ip_packet.get_source(), &ip_packet.get_destination());
         
         Some(TcpResponse {
             source_ip: ip_packet.get_source(),
             dest_ip: ip_packet.get_destination(),
+            ip_id: ip_packet.get_identification(),
             source_port: tcp_packet.get_source(),
             dest_port: tcp_packet.get_destination(),
             flags: tcp_packet.get_flags() as u8,
             seq_num: tcp_packet.get_sequence(),
             ack_num: tcp_packet.get_acknowledgement(),
             window_size: tcp_packet.get_window(),
+            timestamp: tcp::timestamp_option(tcp_packet.get_options_raw()),
+            mss: tcp::mss_option(tcp_packet.get_options_raw()),
+            payload_len: tcp_packet.payload().len(),
+            checksum_valid: checksum == tcp_packet.get_checksum(),
         })
     }
     
@@ -364,12 +336,22 @@ impl PacketParser {
 pub struct TcpResponse {
     pub source_ip: Ipv4Addr,
     pub dest_ip: Ipv4Addr,
+    /// IP identification field of the reply
+    pub ip_id: u16,
     pub source_port: u16,
     pub dest_port: u16,
     pub flags: u8,
     pub seq_num: u32,
     pub ack_num: u32,
     pub window_size: u16,
+    /// The sender's TSval and TSecr, when it used the timestamp option
+    pub timestamp: Option<(u32, u32)>,
+    /// The sender's maximum segment size, when it used the MSS option
+    pub mss: Option<u16>,
+    /// Bytes of data after the TCP header
+    pub payload_len: usize,
+    /// Whether the TCP checksum matches the segment
+    pub checksum_valid: bool,
 }
 
 impl TcpResponse {
@@ -413,4 +395,36 @@ impl IcmpResponse {
         // ICMP Type 3 (Destination Unreachable), Code 3 (Port Unreachable)
         self.icmp_type == 3 && self.icmp_code == 3 && self.dest_ip == target_ip
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::ipv4;
+    use pnet::packet::udp;
+
+    #[test]
+    fn test_built_packets_match_pnet_checksums() {
+        let (source, dest) = (Ipv4Addr::new(192, 0, 2, 10), Ipv4Addr::new(198, 51, 100, 7));
+        let syn = TcpPacketBuilder::new(source, dest, 40000, 443).syn().mss(1360).build();
+        let ip = Ipv4Packet::new(&syn).unwrap();
+        assert_eq!(ip.get_total_length(), 44);
+        assert_eq!(ip.get_checksum(), ipv4::checksum(&ip));
+        let segment = TcpPacket::new(ip.payload()).unwrap();
+        assert_eq!(segment.get_flags(), TcpFlags::SYN);
+        assert_eq!(segment.get_checksum(), pnet::packet::tcp::ipv4_checksum(&segment, &source, &dest));
+        assert_eq!(PacketParser::parse_tcp_response(&syn).unwrap().dest_port, 443);
+        
+        let stamped = TcpPacketBuilder::new(source, dest, 40000, 443).syn().mss(1360).timestamp(99).build();
+        assert_eq!(PacketParser::parse_tcp_response(&stamped).unwrap().timestamp, Some((99, 0)));
+
+        let mut clamped = TcpPacketBuilder::new(source, dest, 40000, 443).syn().mss(1360);
+        clamped.set_mtu(42);
+        assert_eq!(TcpPacket::new(&clamped.build()[20..]).unwrap().get_data_offset(), 5);
+
+        let probe = UdpPacketBuilder::new(source, dest, 5353, 53).payload(b"\x00\x01".to_vec()).build();
+        let ip = Ipv4Packet::new(&probe).unwrap();
+        let datagram = UdpPacket::new(ip.payload()).unwrap();
+        assert_eq!(datagram.get_length(), 10);
+        assert_eq!(datagram.get_checksum(), udp::ipv4_checksum(&datagram, &source, &dest));
+    }
+}
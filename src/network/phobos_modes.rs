@@ -147,7 +147,8 @@ impl PhobosModeManager {
                 EvasionTechnique::PacketFragmentation => {
                     stealth.fragment_packets = true;
                 }
-                EvasionTechnique::DecoyScanning => {
+                // Decoys named with --decoys win over generated ones
+                EvasionTechnique::DecoyScanning if stealth.decoy_addresses.is_empty() => {
                     stealth.decoy_addresses = self.generate_decoy_addresses(wrath.decoy_count);
                 }
                 _ => {} // Other techniques handled elsewhere
@@ -0,0 +1,240 @@
+//! User-defined service probes
+//!
+//! In-house services rarely answer anything the built-in signatures know. A
+//! config file can describe them with `[[probes]]` tables instead: the port,
+//! what to send (text or hex, or nothing for services that speak first), a
+//! regex the reply must match, and the service name to report:
+//!
+//! ```toml
+//! [[probes]]
+//! port = 7400
+//! payload = "HELLO\r\n"
+//! match = '^ACME-RPC/(?P<version>[\d.]+)'
+//! service = "acme-rpc"
+//! ```
+//!
+//! The regex runs on the raw reply bytes; a `version` group, or else the
+//! first group, becomes the service version.
+
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+
+/// Most reply bytes read while waiting for a match
+const MAX_REPLY: usize = 8192;
+
+/// A probe as written in the config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomProbe {
+    /// TCP port the probe is sent to
+    pub port: u16,
+    /// Text to send after connecting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    /// Bytes to send after connecting, as hex; exclusive with `payload`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_hex: Option<String>,
+    /// Regex the reply has to match
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Service name reported on a match
+    pub service: String,
+}
+
+impl CustomProbe {
+    /// Check the probe and compile its pattern
+    pub fn compile(&self) -> crate::Result<CompiledProbe> {
+        let invalid = |reason: String| {
+            crate::ScanError::ConfigError(format!("Probe for port {} ({}): {}", self.port, self.service, reason))
+        };
+        if self.port == 0 {
+            return Err(invalid("port must be between 1-65535".to_string()));
+        }
+        if self.service.trim().is_empty() {
+            return Err(invalid("service name is empty".to_string()));
+        }
+        let payload = match (&self.payload, &self.payload_hex) {
+            (Some(_), Some(_)) => return Err(invalid("set either payload or payload_hex, not both".to_string())),
+            (Some(text), None) => text.as_bytes().to_vec(),
+            (None, Some(hex)) => decode_hex(hex).ok_or_else(|| invalid(format!("invalid payload_hex `{}`", hex)))?,
+            (None, None) => Vec::new(),
+        };
+        let pattern = Regex::new(&self.pattern).map_err(|e| invalid(format!("invalid match pattern: {}", e)))?;
+        Ok(CompiledProbe { port: self.port, payload, pattern, service: self.service.clone() })
+    }
+}
+
+/// A probe ready to send
+#[derive(Debug, Clone)]
+pub struct CompiledProbe {
+    pub port: u16,
+    pub payload: Vec<u8>,
+    pub pattern: Regex,
+    pub service: String,
+}
+
+impl CompiledProbe {
+    /// Service and version if `reply` matches
+    pub fn identify(&self, reply: &[u8]) -> Option<ProbeMatch> {
+        let captures = self.pattern.captures(reply)?;
+        let version = captures
+            .name("version")
+            .or_else(|| captures.get(1))
+            .map(|group| String::from_utf8_lossy(group.as_bytes()).trim().to_string())
+            .filter(|version| !version.is_empty());
+        Some(ProbeMatch {
+            service: self.service.clone(),
+            version,
+            reply: String::from_utf8_lossy(reply).trim().to_string(),
+        })
+    }
+}
+
+/// What a matching probe identified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeMatch {
+    pub service: String,
+    pub version: Option<String>,
+    /// The reply that matched, lossily decoded
+    pub reply: String,
+}
+
+/// Every configured probe, looked up by port
+#[derive(Debug, Clone, Default)]
+pub struct CustomProbes {
+    probes: Vec<CompiledProbe>,
+}
+
+impl CustomProbes {
+    /// Compile `probes`, failing on the first invalid one
+    pub fn compile(probes: &[CustomProbe]) -> crate::Result<Self> {
+        let probes = probes.iter().map(CustomProbe::compile).collect::<crate::Result<_>>()?;
+        Ok(Self { probes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+
+    /// Probes configured for `port`, in config file order
+    pub fn for_port(&self, port: u16) -> impl Iterator<Item = &CompiledProbe> {
+        self.probes.iter().filter(move |probe| probe.port == port)
+    }
+
+    /// Send each probe for the target's port on a fresh connection until
+    /// one reply matches; each probe gets `wait` in total
+    pub async fn identify(&self, target: SocketAddr, wait: Duration) -> Option<ProbeMatch> {
+        for probe in self.for_port(target.port()) {
+            if let Some(found) = run_probe(probe, target, wait).await {
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+/// Connect, send the payload and read until the reply matches, the peer
+/// closes or `wait` runs out
+async fn run_probe(probe: &CompiledProbe, target: SocketAddr, wait: Duration) -> Option<ProbeMatch> {
+    exchange(target, &probe.payload, wait, |reply| probe.identify(reply)).await.and_then(|(_, found)| found)
+}
+
+/// Connect to `target`, send `payload` and read the reply until `check`
+/// returns something, the peer closes or `wait` runs out
+///
+/// Returns what was read along with the last result of `check`; `None` if
+/// the connection or the write failed.
+pub(crate) async fn exchange<T>(
+    target: SocketAddr,
+    payload: &[u8],
+    wait: Duration,
+    mut check: impl FnMut(&[u8]) -> Option<T>,
+) -> Option<(Vec<u8>, Option<T>)> {
+    let deadline = Instant::now() + wait;
+    let mut stream = timeout(wait, TcpStream::connect(target)).await.ok()?.ok()?;
+    if !payload.is_empty() {
+        timeout(deadline.saturating_duration_since(Instant::now()), stream.write_all(payload)).await.ok()?.ok()?;
+    }
+    let mut reply = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while reply.len() < MAX_REPLY {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match timeout(remaining, stream.read(&mut chunk)).await {
+            Ok(Ok(read)) if read > 0 => {
+                reply.extend_from_slice(&chunk[..read]);
+                if let Some(found) = check(&reply) {
+                    return Some((reply, Some(found)));
+                }
+            }
+            _ => break,
+        }
+    }
+    Some((reply, None))
+}
+
+/// Hex bytes, ignoring whitespace and an optional `0x` prefix
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: String = hex.trim().trim_start_matches("0x").split_whitespace().collect();
+    if digits.is_empty() || digits.len() % 2 != 0 || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_custom_probe_identifies_service() {
+        let probes: Vec<CustomProbe> = toml::from_str::<toml::Table>(
+            r#"
+            [[probes]]
+            port = 1
+            payload_hex = "de ad"
+            match = 'never'
+            service = "other"
+
+            [[probes]]
+            port = 1
+            payload = "HELLO\r\n"
+            match = '^ACME-RPC/(?P<version>[\d.]+)'
+            service = "acme-rpc"
+            "#,
+        )
+        .unwrap()["probes"]
+            .clone()
+            .try_into()
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 16];
+                let read = stream.read(&mut request).await.unwrap();
+                if &request[..read] == b"HELLO\r\n" {
+                    stream.write_all(b"ACME-RPC/2.4 ready\r\n").await.unwrap();
+                }
+            }
+        });
+
+        // Probes are keyed by the port they are configured for
+        let probes: Vec<CustomProbe> = probes.into_iter().map(|p| CustomProbe { port: target.port(), ..p }).collect();
+        let compiled = CustomProbes::compile(&probes).unwrap();
+        assert_eq!(compiled.for_port(target.port()).next().unwrap().payload, [0xde, 0xad]);
+        let found = compiled.identify(target, Duration::from_millis(500)).await.unwrap();
+        assert_eq!(found.service, "acme-rpc");
+        assert_eq!(found.version.as_deref(), Some("2.4"));
+        assert!(compiled.identify(SocketAddr::new(target.ip(), 9), Duration::from_millis(100)).await.is_none());
+
+        let broken = CustomProbe { payload: None, payload_hex: Some("abc".to_string()), ..probes[1].clone() };
+        assert!(CustomProbes::compile(&[broken]).is_err());
+    }
+}
@@ -2,8 +2,10 @@
 
 use crate::network::{PortState, ScanTechnique};
 use std::collections::HashMap;
-use std::net::Ipv4Addr;
-use std::time::Duration;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 /// Common service ports mapping
 #[derive(Clone)]
@@ -199,72 +201,523 @@ impl ResponseAnalyzer {
     }
 }
 
+/// Lock-free token bucket implemented as a GCRA (generic cell rate algorithm).
+///
+/// The whole bucket state is a single "theoretical arrival time" stored in an
+/// atomic, so acquiring a token is one load plus one compare-and-swap and never
+/// blocks other senders.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Nanoseconds between two tokens (1s / rate); changed by [`set_rate`](Self::set_rate)
+    emission_nanos: AtomicU64,
+    /// Tokens that may be taken at once; changed by [`set_burst`](Self::set_burst)
+    burst: AtomicU64,
+    /// Theoretical arrival time of the next token, in nanoseconds since `epoch`
+    tat: AtomicU64,
+    epoch: Instant,
+}
+
+impl TokenBucket {
+    /// Create a bucket refilling at `rate` tokens per second holding at most `burst` tokens.
+    /// A rate of zero disables limiting.
+    pub fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            emission_nanos: AtomicU64::new(Self::emission_nanos(rate)),
+            burst: AtomicU64::new(burst.max(1)),
+            tat: AtomicU64::new(0),
+            epoch: Instant::now(),
+        }
+    }
+
+    fn emission_nanos(rate: u64) -> u64 {
+        1_000_000_000u64.checked_div(rate).map_or(0, |nanos| nanos.max(1))
+    }
+
+    /// Refill at `rate` tokens per second from now on
+    pub fn set_rate(&self, rate: u64) {
+        self.emission_nanos.store(Self::emission_nanos(rate), Ordering::Relaxed);
+    }
+
+    /// Hold at most `burst` tokens from now on
+    pub fn set_burst(&self, burst: u64) {
+        self.burst.store(burst.max(1), Ordering::Relaxed);
+    }
+
+    fn now_nanos(&self) -> u64 {
+        self.epoch.elapsed().as_nanos() as u64
+    }
+
+    /// Take one token, or return how long to wait until one becomes available
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let emission_nanos = self.emission_nanos.load(Ordering::Relaxed);
+        if emission_nanos == 0 {
+            return Ok(());
+        }
+        // How far ahead of `now` the arrival time may run, i.e. the burst allowance
+        let tolerance_nanos = emission_nanos.saturating_mul(self.burst.load(Ordering::Relaxed) - 1);
+
+        let now = self.now_nanos();
+        let mut tat = self.tat.load(Ordering::Relaxed);
+        loop {
+            let allowed_at = tat.saturating_sub(tolerance_nanos);
+            if allowed_at > now {
+                return Err(Duration::from_nanos(allowed_at - now));
+            }
+
+            let next = tat.max(now) + emission_nanos;
+            match self.tat.compare_exchange_weak(tat, next, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(current) => tat = current,
+            }
+        }
+    }
+
+    /// Give back a token taken by [`try_acquire`](Self::try_acquire) but not used
+    pub fn refund(&self) {
+        let emission_nanos = self.emission_nanos.load(Ordering::Relaxed);
+        let _ = self.tat.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |tat| Some(tat.saturating_sub(emission_nanos)));
+    }
+
+    /// Time until the next token is available (zero if one is available now)
+    pub fn delay_until_next(&self) -> Duration {
+        let emission_nanos = self.emission_nanos.load(Ordering::Relaxed);
+        if emission_nanos == 0 {
+            return Duration::ZERO;
+        }
+        let tolerance_nanos = emission_nanos.saturating_mul(self.burst.load(Ordering::Relaxed) - 1);
+        let allowed_at = self.tat.load(Ordering::Relaxed).saturating_sub(tolerance_nanos);
+        Duration::from_nanos(allowed_at.saturating_sub(self.now_nanos()))
+    }
+}
+
+/// What became of one probe, as far as pacing its host is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// The host answered: SYN/ACK, RST to a SYN, an accepted or refused connect
+    Answered,
+    /// No answer before the timeout
+    Lost,
+    /// Reset while connecting, as overloaded NAT and conntrack tables do
+    Reset,
+}
+
+/// Outcomes judged together before a host's rate changes
+const CONGESTION_WINDOW: u32 = 32;
+
+/// Share of unanswered probes, above the host's lowest, that counts as congestion
+const LOSS_MARGIN: f64 = 0.2;
+
+/// Share of resets in a window that counts as congestion
+const RESET_LIMIT: f64 = 0.1;
+
+/// Bounds of an adaptive per-host rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveRate {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl AdaptiveRate {
+    /// Lowest rate when only the highest is given
+    pub const DEFAULT_MIN: u64 = 10;
+}
+
+/// Congestion state of one host under an [`AdaptiveRate`]
+#[derive(Debug)]
+struct Congestion {
+    rate: u64,
+    /// Doubling the rate until the first sign of congestion
+    slow_start: bool,
+    /// The window after a cut still holds probes sent at the old rate
+    settling: bool,
+    /// Lowest share of unanswered probes seen: what the host's filtering
+    /// alone loses, rather than congestion
+    best_loss: Option<f64>,
+    answered: u32,
+    lost: u32,
+    reset: u32,
+}
+
+impl Congestion {
+    fn new(rate: u64) -> Self {
+        Self { rate, slow_start: true, settling: false, best_loss: None, answered: 0, lost: 0, reset: 0 }
+    }
+
+    /// Count `outcome`, returning the new rate when a complete window changes it
+    fn record(&mut self, outcome: ProbeOutcome, bounds: AdaptiveRate) -> Option<u64> {
+        match outcome {
+            ProbeOutcome::Answered => self.answered += 1,
+            ProbeOutcome::Lost => self.lost += 1,
+            ProbeOutcome::Reset => self.reset += 1,
+        }
+        let total = self.answered + self.lost + self.reset;
+        if total < CONGESTION_WINDOW {
+            return None;
+        }
+        let loss = f64::from(self.lost + self.reset) / f64::from(total);
+        let resets = f64::from(self.reset) / f64::from(total);
+        (self.answered, self.lost, self.reset) = (0, 0, 0);
+        if std::mem::take(&mut self.settling) {
+            return None;
+        }
+
+        let congested = resets > RESET_LIMIT || self.best_loss.is_some_and(|best| loss > best + LOSS_MARGIN);
+        self.best_loss = Some(self.best_loss.map_or(loss, |best| best.min(loss)));
+        let rate = if congested {
+            self.slow_start = false;
+            self.settling = true;
+            self.rate / 2
+        } else if self.slow_start {
+            self.rate.saturating_mul(2)
+        } else {
+            self.rate + (self.rate / 10).max(1)
+        };
+        let previous = std::mem::replace(&mut self.rate, rate.clamp(bounds.min, bounds.max));
+        (self.rate != previous).then_some(self.rate)
+    }
+}
+
+/// Steps a ramp-up raises the global rate in
+const RAMP_STEPS: u32 = 10;
+
+/// Outcomes a ramp step needs before its loss is judged; quieter steps
+/// carry on as healthy
+const RAMP_MIN_OUTCOMES: u32 = 8;
+
+/// Slow start of the global rate at the beginning of a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RampUp {
+    /// Share of the full rate to start at, in percent
+    pub start_percent: u8,
+    /// Time to reach the full rate
+    pub window: Duration,
+}
+
+impl RampUp {
+    /// Starting share when only the window is given
+    pub const DEFAULT_START_PERCENT: u8 = 10;
+}
+
+/// Progress of a [`RampUp`] towards the full rate
+#[derive(Debug)]
+struct Ramp {
+    target: u64,
+    start: u64,
+    window: Duration,
+    /// When the first probe was paced; the window runs from there
+    began: Option<Instant>,
+    step: u32,
+    rate: u64,
+    /// Lowest share of unanswered probes of any step
+    best_loss: Option<f64>,
+    answered: u32,
+    lost: u32,
+    reset: u32,
+    done: bool,
+    decisions: Vec<String>,
+}
+
+impl Ramp {
+    fn new(ramp: RampUp, target: u64) -> Self {
+        let start = (target * u64::from(ramp.start_percent.clamp(1, 100)) / 100).max(1);
+        Self {
+            target,
+            start,
+            window: ramp.window,
+            began: None,
+            step: 0,
+            rate: start,
+            best_loss: None,
+            answered: 0,
+            lost: 0,
+            reset: 0,
+            done: false,
+            decisions: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, outcome: ProbeOutcome) {
+        match outcome {
+            ProbeOutcome::Answered => self.answered += 1,
+            ProbeOutcome::Lost => self.lost += 1,
+            ProbeOutcome::Reset => self.reset += 1,
+        }
+    }
+
+    /// Move on to the step `elapsed` falls in, returning the new rate when it changes
+    fn advance(&mut self, elapsed: Duration) -> Option<u64> {
+        let due = (elapsed.as_secs_f64() / self.window.as_secs_f64() * f64::from(RAMP_STEPS)) as u32;
+        if self.done || due <= self.step {
+            return None;
+        }
+        let total = self.answered + self.lost + self.reset;
+        if total >= RAMP_MIN_OUTCOMES {
+            let loss = f64::from(self.lost + self.reset) / f64::from(total);
+            let resets = f64::from(self.reset) / f64::from(total);
+            if resets > RESET_LIMIT || self.best_loss.is_some_and(|best| loss > best + LOSS_MARGIN) {
+                // The rate before this step was the last one without rising loss
+                let held = self.rate_at(self.step.saturating_sub(1));
+                self.done = true;
+                self.decisions.push(format!(
+                    "rate ramp-up stopped at {} probes/s of {} after {:.0}% of probes went unanswered",
+                    held,
+                    self.target,
+                    loss * 100.0
+                ));
+                return self.set(held);
+            }
+            self.best_loss = Some(self.best_loss.map_or(loss, |best| best.min(loss)));
+        }
+        (self.answered, self.lost, self.reset) = (0, 0, 0);
+        self.step = due.min(RAMP_STEPS);
+        if self.step == RAMP_STEPS {
+            self.done = true;
+            self.decisions.push(format!(
+                "rate ramped up from {} to {} probes/s over {:?}",
+                self.start, self.target, self.window
+            ));
+        }
+        self.set(self.rate_at(self.step))
+    }
+
+    fn rate_at(&self, step: u32) -> u64 {
+        self.start + (self.target - self.start) * u64::from(step) / u64::from(RAMP_STEPS)
+    }
+
+    fn set(&mut self, rate: u64) -> Option<u64> {
+        let previous = std::mem::replace(&mut self.rate, rate);
+        (rate != previous).then_some(rate)
+    }
+}
+
+/// A host's bucket, and its congestion state if its rate adapts
+#[derive(Debug)]
+struct HostBucket {
+    bucket: TokenBucket,
+    congestion: Option<Mutex<Congestion>>,
+}
+
 /// Rate limiter for controlling packet sending rate
+///
+/// Combines a global token bucket with optional per-host buckets. Both are
+/// lock-free on the hot path; the per-host map only takes a write lock the
+/// first time a host is seen. Per-host rates are either fixed or adapt to
+/// each host's answers between the bounds of an [`AdaptiveRate`]: a host
+/// starts at the lowest rate, which doubles with every healthy window of
+/// answers until timeouts rise above the host's usual share or resets pile
+/// up, then halves and grows by a tenth per healthy window from there.
+///
+/// With a [`RampUp`] the global rate starts at a share of itself instead
+/// of with a full burst, and rises in even steps over the ramp's window.
+/// A step whose probes go unanswered noticeably more often than the best
+/// step so far ends the ramp at the rate before it.
 #[derive(Debug)]
 pub struct RateLimiter {
     rate: u64,
-    last_send: std::time::Instant,
-    tokens: f64,
-    max_tokens: f64,
+    burst: u64,
+    global: TokenBucket,
+    host_rate: Option<u64>,
+    host_burst: u64,
+    adaptive: Option<AdaptiveRate>,
+    hosts: RwLock<HashMap<IpAddr, Arc<HostBucket>>>,
+    ramp: Option<Mutex<Ramp>>,
+    /// Cleared once the ramp is over, so pacing stops taking its lock
+    ramping: AtomicBool,
 }
 
 impl RateLimiter {
+    /// Create a limiter allowing `packets_per_second` globally with a one second burst
     pub fn new(packets_per_second: u64) -> Self {
+        Self::with_burst(packets_per_second, packets_per_second)
+    }
+
+    /// Create a limiter with an explicit burst size (maximum tokens in the bucket)
+    pub fn with_burst(packets_per_second: u64, burst: u64) -> Self {
         Self {
             rate: packets_per_second,
-            last_send: std::time::Instant::now(),
-            tokens: packets_per_second as f64,
-            max_tokens: packets_per_second as f64,
+            burst: burst.max(1),
+            global: TokenBucket::new(packets_per_second, burst),
+            host_rate: None,
+            host_burst: 1,
+            adaptive: None,
+            hosts: RwLock::new(HashMap::new()),
+            ramp: None,
+            ramping: AtomicBool::new(false),
         }
     }
-    
-    /// Check if we can send a packet (token bucket algorithm)
-    pub fn can_send(&mut self) -> bool {
-        let now = std::time::Instant::now();
-        let elapsed = now.duration_since(self.last_send).as_secs_f64();
-        
-        // Prevent division by zero and overflow
-        if self.rate == 0 {
-            return false;
+
+    /// Additionally limit every individual host to `packets_per_second`
+    pub fn with_host_rate(mut self, packets_per_second: u64, burst: u64) -> Self {
+        self.host_rate = Some(packets_per_second);
+        self.host_burst = burst.max(1);
+        self
+    }
+
+    /// Let every host's rate adapt within `bounds` to what [`record`](Self::record) reports
+    pub fn with_adaptive_host_rate(mut self, bounds: AdaptiveRate) -> Self {
+        let bounds = AdaptiveRate { min: bounds.min.max(1), max: bounds.max.max(bounds.min).max(1) };
+        self.host_rate = Some(bounds.min);
+        self.host_burst = 1;
+        self.adaptive = Some(bounds);
+        self
+    }
+
+    /// Start the global rate at a share of itself and raise it over the
+    /// ramp's window, stopping early if loss rises; unlimited rates have
+    /// nothing to ramp up to
+    pub fn with_ramp_up(mut self, ramp: RampUp) -> Self {
+        if self.rate == 0 || ramp.window.is_zero() {
+            return self;
         }
-        
-        // Add tokens based on elapsed time with overflow protection
-        let tokens_to_add = elapsed * self.rate as f64;
-        if tokens_to_add.is_finite() && tokens_to_add >= 0.0 {
-            self.tokens += tokens_to_add;
-            if self.tokens > self.max_tokens {
-                self.tokens = self.max_tokens;
-            }
+        let state = Ramp::new(ramp, self.rate);
+        self.global.set_rate(state.rate);
+        self.global.set_burst(self.ramp_burst(state.rate));
+        self.ramp = Some(Mutex::new(state));
+        self.ramping = AtomicBool::new(true);
+        self
+    }
+
+    /// What the ramp-up decided so far, for the scan's stats
+    pub fn ramp_decisions(&self) -> Vec<String> {
+        match self.ramp.as_ref().and_then(|ramp| ramp.lock().ok()) {
+            Some(ramp) => ramp.decisions.clone(),
+            None => Vec::new(),
         }
-        
-        self.last_send = now;
-        
-        if self.tokens >= 1.0 {
-            self.tokens -= 1.0;
-            true
-        } else {
-            false
+    }
+
+    /// The burst scaled down with the rate, so a ramp never opens with the full burst
+    fn ramp_burst(&self, rate: u64) -> u64 {
+        (self.burst * rate / self.rate.max(1)).max(1)
+    }
+
+    /// Global rate in packets per second
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Global burst size
+    pub fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    /// Per-host rate in packets per second, if enabled; the starting rate of adaptive ones
+    pub fn host_rate(&self) -> Option<u64> {
+        self.host_rate
+    }
+
+    /// Bounds of adaptive per-host rates, if enabled
+    pub fn adaptive_host_rate(&self) -> Option<AdaptiveRate> {
+        self.adaptive
+    }
+
+    /// Current rate of `host`, if it has its own
+    pub fn rate_of(&self, host: IpAddr) -> Option<u64> {
+        let hosts = self.hosts.read().ok()?;
+        let state = hosts.get(&host)?;
+        match &state.congestion {
+            Some(congestion) => congestion.lock().ok().map(|c| c.rate),
+            None => self.host_rate,
         }
     }
-    
+
+    /// Check if we can send a packet (global bucket only)
+    pub fn can_send(&self) -> bool {
+        self.global.try_acquire().is_ok()
+    }
+
+    /// Check if we can send a packet to `host`, taking both the host and global buckets into account
+    pub fn can_send_to(&self, host: IpAddr) -> bool {
+        self.try_acquire_for(host).is_ok()
+    }
+
     /// Calculate delay needed before next send
     pub fn delay_until_next(&self) -> Duration {
-        if self.tokens >= 1.0 || self.rate == 0 {
-            Duration::from_millis(0)
-        } else {
-            let needed_tokens = 1.0 - self.tokens;
-            let delay_secs = needed_tokens / self.rate as f64;
-            
-            // Ensure delay is finite and reasonable
-            if delay_secs.is_finite() && delay_secs >= 0.0 && delay_secs <= 60.0 {
-                Duration::from_secs_f64(delay_secs)
+        self.global.delay_until_next()
+    }
+
+    /// Wait until a packet to `host` may be sent
+    pub async fn acquire(&self, host: IpAddr) {
+        while let Err(wait) = self.try_acquire_for(host) {
+            if wait < Duration::from_millis(1) {
+                // Timer resolution is ~1ms, so yield instead of oversleeping
+                tokio::task::yield_now().await;
             } else {
-                Duration::from_secs(1) // Fallback to 1 second
+                tokio::time::sleep(wait).await;
             }
         }
     }
+
+    /// Report what became of a probe to `host`; adaptive rates follow it
+    pub fn record(&self, host: IpAddr, outcome: ProbeOutcome) {
+        if self.ramping.load(Ordering::Relaxed) {
+            if let Some(mut ramp) = self.ramp.as_ref().and_then(|ramp| ramp.lock().ok()) {
+                ramp.record(outcome);
+            }
+        }
+        let Some(bounds) = self.adaptive else { return };
+        let Some(state) = self.host_bucket(host) else { return };
+        let Some(congestion) = &state.congestion else { return };
+        let Ok(mut congestion) = congestion.lock() else { return };
+        if let Some(rate) = congestion.record(outcome, bounds) {
+            log::debug!("{} paced at {} probes/s", host, rate);
+            state.bucket.set_rate(rate);
+        }
+    }
+
+    fn try_acquire_for(&self, host: IpAddr) -> Result<(), Duration> {
+        if self.ramping.load(Ordering::Relaxed) {
+            self.advance_ramp();
+        }
+        // Host bucket first so a throttled host doesn't burn global tokens,
+        // and its token goes back when the global bucket refuses
+        let state = self.host_bucket(host);
+        if let Some(state) = &state {
+            state.bucket.try_acquire()?;
+        }
+        let acquired = self.global.try_acquire();
+        if let (Err(_), Some(state)) = (&acquired, &state) {
+            state.bucket.refund();
+        }
+        acquired
+    }
+
+    fn advance_ramp(&self) {
+        let Some(mut ramp) = self.ramp.as_ref().and_then(|ramp| ramp.lock().ok()) else { return };
+        let began = *ramp.began.get_or_insert_with(Instant::now);
+        if let Some(rate) = ramp.advance(began.elapsed()) {
+            log::debug!("ramp-up: {} probes/s", rate);
+            self.global.set_rate(rate);
+            self.global.set_burst(match ramp.done {
+                true if rate == self.rate => self.burst,
+                _ => self.ramp_burst(rate),
+            });
+        }
+        if ramp.done {
+            self.ramping.store(false, Ordering::Relaxed);
+        }
+    }
+
+    fn host_bucket(&self, host: IpAddr) -> Option<Arc<HostBucket>> {
+        let rate = self.host_rate?;
+
+        if let Some(state) = self.hosts.read().ok()?.get(&host) {
+            return Some(Arc::clone(state));
+        }
+
+        let mut hosts = self.hosts.write().ok()?;
+        let state = hosts.entry(host).or_insert_with(|| {
+            Arc::new(HostBucket {
+                bucket: TokenBucket::new(rate, self.host_burst),
+                congestion: self.adaptive.map(|_| Mutex::new(Congestion::new(rate))),
+            })
+        });
+        Some(Arc::clone(state))
+    }
 }
 
+/// Host bits an IPv6 target range may have: a /112 at the widest
+pub const MAX_IPV6_HOST_BITS: u32 = 16;
+
 /// Network utilities
 pub struct NetworkUtils;
 
@@ -288,6 +741,22 @@ impl NetworkUtils {
         }
     }
     
+    /// Local address the kernel routes traffic to `target` from
+    pub fn source_for(target: IpAddr) -> crate::Result<IpAddr> {
+        let unspecified: IpAddr = match target {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        // Connecting a UDP socket only picks a route; nothing is sent
+        let socket = std::net::UdpSocket::bind((unspecified, 0))
+            .map_err(|e| crate::ScanError::NetworkError(e.to_string()))?;
+        socket.connect((target, 9))
+            .map_err(|e| crate::ScanError::NetworkError(format!("No route to {}: {}", target, e)))?;
+        socket.local_addr()
+            .map(|addr| addr.ip())
+            .map_err(|e| crate::ScanError::NetworkError(e.to_string()))
+    }
+    
     /// Generate a random source port
     pub fn random_source_port() -> u16 {
         use rand::Rng;
@@ -295,40 +764,236 @@ impl NetworkUtils {
         rng.gen_range(32768..65535)
     }
     
-    /// Parse CIDR notation (e.g., 192.168.1.0/24)
-    pub fn parse_cidr(cidr: &str) -> crate::Result<Vec<Ipv4Addr>> {
-        if !cidr.contains('/') {
+    /// Parse CIDR notation (e.g., 192.168.1.0/24 or 2001:db8::/120)
+    ///
+    /// IPv6 prefixes are capped at [`MAX_IPV6_HOST_BITS`] host bits, as
+    /// wider ones could never be swept address by address.
+    pub fn parse_cidr(cidr: &str) -> crate::Result<Vec<IpAddr>> {
+        let (base_ip, host_bits) = Self::parse_prefix(cidr)?;
+        match base_ip {
+            IpAddr::V4(base_ip) => {
+                let mask = u32::MAX.checked_shl(host_bits).unwrap_or(0);
+                let network = u32::from(base_ip) & mask;
+                let broadcast = network | !mask;
+                Ok((network..=broadcast).map(|ip| IpAddr::V4(Ipv4Addr::from(ip))).collect())
+            }
+            IpAddr::V6(base_ip) => {
+                let mask = u128::MAX.checked_shl(host_bits).unwrap_or(0);
+                let network = u128::from(base_ip) & mask;
+                let last = network | !mask;
+                Ok((network..=last).map(|ip| IpAddr::V6(Ipv6Addr::from(ip))).collect())
+            }
+        }
+    }
+    
+    /// Number of addresses [`parse_cidr`](Self::parse_cidr) would return,
+    /// without listing them
+    pub fn cidr_size(cidr: &str) -> crate::Result<u64> {
+        let (_, host_bits) = Self::parse_prefix(cidr)?;
+        Ok(1u64 << host_bits)
+    }
+    
+    /// Base address and host bits of a CIDR block or single address
+    fn parse_prefix(cidr: &str) -> crate::Result<(IpAddr, u32)> {
+        let Some((base, prefix)) = cidr.split_once('/') else {
             // Single IP address
-            let ip: Ipv4Addr = cidr.parse()
+            let ip: IpAddr = cidr.parse()
                 .map_err(|_| crate::ScanError::InvalidTarget(format!("Invalid IP: {}", cidr)))?;
-            return Ok(vec![ip]);
-        }
-        
-        let parts: Vec<&str> = cidr.split('/').collect();
-        if parts.len() != 2 {
-            return Err(crate::ScanError::InvalidTarget(format!("Invalid CIDR: {}", cidr)));
-        }
+            return Ok((ip, 0));
+        };
         
-        let base_ip: Ipv4Addr = parts[0].parse()
-            .map_err(|_| crate::ScanError::InvalidTarget(format!("Invalid IP: {}", parts[0])))?;
+        let base_ip: IpAddr = base.parse()
+            .map_err(|_| crate::ScanError::InvalidTarget(format!("Invalid IP: {}", base)))?;
         
-        let prefix_len: u8 = parts[1].parse()
-            .map_err(|_| crate::ScanError::InvalidTarget(format!("Invalid prefix: {}", parts[1])))?;
+        let prefix_len: u8 = prefix.parse()
+            .map_err(|_| crate::ScanError::InvalidTarget(format!("Invalid prefix: {}", prefix)))?;
         
-        if prefix_len > 32 {
-            return Err(crate::ScanError::InvalidTarget("Prefix length must be <= 32".to_string()));
+        match base_ip {
+            IpAddr::V4(_) => {
+                if prefix_len > 32 {
+                    return Err(crate::ScanError::InvalidTarget("Prefix length must be <= 32".to_string()));
+                }
+                Ok((base_ip, 32 - prefix_len as u32))
+            }
+            IpAddr::V6(_) => {
+                if prefix_len > 128 {
+                    return Err(crate::ScanError::InvalidTarget("Prefix length must be <= 128".to_string()));
+                }
+                let host_bits = 128 - prefix_len as u32;
+                if host_bits > MAX_IPV6_HOST_BITS {
+                    return Err(crate::ScanError::InvalidTarget(format!(
+                        "IPv6 prefix /{} is too wide to sweep; use /{} or longer",
+                        prefix_len,
+                        128 - MAX_IPV6_HOST_BITS
+                    )));
+                }
+                Ok((base_ip, host_bits))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_burst() {
+        let bucket = TokenBucket::new(10, 5);
+        for _ in 0..5 {
+            assert!(bucket.try_acquire().is_ok());
+        }
+        let wait = bucket.try_acquire().unwrap_err();
+        assert!(wait <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        let limiter = RateLimiter::new(0);
+        for _ in 0..1000 {
+            assert!(limiter.can_send());
+        }
+        assert_eq!(limiter.delay_until_next(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_per_host_buckets_are_independent() {
+        let limiter = RateLimiter::new(1_000_000).with_host_rate(1, 2);
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert!(limiter.can_send_to(a));
+        assert!(limiter.can_send_to(a));
+        assert!(!limiter.can_send_to(a));
+        assert!(limiter.can_send_to(b));
+    }
+
+    #[test]
+    fn test_global_refusal_keeps_host_token() {
+        let limiter = RateLimiter::with_burst(1, 1).with_host_rate(1, 1);
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert!(limiter.can_send_to(a));
+        assert!(!limiter.can_send_to(b));
+        // b's host bucket still holds its token, so it only waits on the global one
+        assert!(limiter.hosts.read().unwrap()[&b].bucket.try_acquire().is_ok());
+    }
+
+    #[test]
+    fn test_adaptive_host_rate_backs_off_and_recovers() {
+        let limiter = RateLimiter::new(0).with_adaptive_host_rate(AdaptiveRate { min: 100, max: 1000 });
+        let host: IpAddr = "192.168.1.1".parse().unwrap();
+        let window = |answered: u32, outcome: ProbeOutcome| {
+            for i in 0..CONGESTION_WINDOW {
+                limiter.record(host, if i < answered { ProbeOutcome::Answered } else { outcome });
+            }
+            limiter.rate_of(host).unwrap()
+        };
+
+        // A host whose filtering always drops a quarter of probes is not congested
+        assert_eq!(window(24, ProbeOutcome::Lost), 200);
+        assert_eq!(window(24, ProbeOutcome::Lost), 400);
+        assert_eq!(window(24, ProbeOutcome::Lost), 800);
+        assert_eq!(window(24, ProbeOutcome::Lost), 1000);
+
+        // Timeouts well above that halve the rate, and the next window settles
+        assert_eq!(window(8, ProbeOutcome::Lost), 500);
+        assert_eq!(window(0, ProbeOutcome::Lost), 500);
+        assert_eq!(window(24, ProbeOutcome::Lost), 550);
+        assert_eq!(window(28, ProbeOutcome::Reset), 275);
+        assert_eq!(window(32, ProbeOutcome::Lost), 275);
+        for _ in 0..10 {
+            window(0, ProbeOutcome::Lost);
         }
+        assert_eq!(limiter.rate_of(host), Some(100));
+
+        // Other hosts start over
+        let other: IpAddr = "192.168.1.2".parse().unwrap();
+        assert!(limiter.can_send_to(other));
+        assert_eq!(limiter.rate_of(other), Some(100));
+    }
+
+    #[test]
+    fn test_ramp_up_rises_in_steps_and_stops_on_loss() {
+        let ramp = RampUp { start_percent: 10, window: Duration::from_secs(10) };
+        let step = |n: u64| Duration::from_secs(n);
+        let window = |state: &mut Ramp, answered: u32, lost: u32| {
+            (0..answered).for_each(|_| state.record(ProbeOutcome::Answered));
+            (0..lost).for_each(|_| state.record(ProbeOutcome::Lost));
+        };
+
+        let mut healthy = Ramp::new(ramp, 1000);
+        assert_eq!(healthy.rate, 100);
+        assert_eq!(healthy.advance(Duration::from_millis(500)), None);
+        window(&mut healthy, 30, 10);
+        assert_eq!(healthy.advance(step(1)), Some(190));
+        // Too few outcomes to judge, and idle steps are skipped over
+        window(&mut healthy, 1, 3);
+        assert_eq!(healthy.advance(step(4)), Some(460));
+        assert_eq!(healthy.advance(step(12)), Some(1000));
+        assert!(healthy.done);
+        assert_eq!(healthy.decisions, vec!["rate ramped up from 100 to 1000 probes/s over 10s".to_string()]);
+
+        let mut lossy = Ramp::new(ramp, 1000);
+        window(&mut lossy, 30, 10);
+        assert_eq!(lossy.advance(step(1)), Some(190));
+        window(&mut lossy, 30, 10);
+        assert_eq!(lossy.advance(step(2)), Some(280));
+        window(&mut lossy, 10, 30);
+        assert_eq!(lossy.advance(step(3)), Some(190));
+        assert!(lossy.done);
+        assert_eq!(lossy.advance(step(4)), None);
+        assert!(lossy.decisions[0].contains("stopped at 190 probes/s of 1000 after 75%"));
+
+        // The limiter opens at the starting rate with a scaled-down burst
+        let limiter = RateLimiter::new(1000).with_ramp_up(ramp);
+        let host: IpAddr = "10.0.0.1".parse().unwrap();
+        let granted = (0..1000).filter(|_| limiter.can_send_to(host)).count();
+        assert_eq!(granted, 100);
+        assert!(limiter.ramp_decisions().is_empty());
+        assert!(RateLimiter::new(0).with_ramp_up(ramp).ramp.is_none());
+    }
+
+    #[test]
+    fn test_concurrent_acquire_respects_burst() {
+        let limiter = Arc::new(RateLimiter::with_burst(1, 100));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                std::thread::spawn(move || (0..100).filter(|_| limiter.can_send()).count())
+            })
+            .collect();
+        let granted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(granted, 100);
+    }
+
+    #[test]
+    fn test_parse_cidr_both_families() {
+        let v4 = NetworkUtils::parse_cidr("192.0.2.77/30").unwrap();
+        assert_eq!(v4, (76..=79).map(|last| IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))).collect::<Vec<_>>());
+        assert_eq!(NetworkUtils::parse_cidr("10.0.0.1/32").unwrap().len(), 1);
         
-        let mut ips = Vec::new();
-        let base = u32::from(base_ip);
-        let mask = !((1u32 << (32 - prefix_len)) - 1);
-        let network = base & mask;
-        let broadcast = network | ((1u32 << (32 - prefix_len)) - 1);
+        let v6 = NetworkUtils::parse_cidr("2001:db8::1234/120").unwrap();
+        assert_eq!(v6.len(), 256);
+        assert_eq!(v6[0], "2001:db8::1200".parse::<IpAddr>().unwrap());
+        assert_eq!(v6[255], "2001:db8::12ff".parse::<IpAddr>().unwrap());
+        assert_eq!(NetworkUtils::parse_cidr("::1").unwrap(), [IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+        assert_eq!(NetworkUtils::parse_cidr("2001:db8::/112").unwrap().len(), 65536);
         
-        for ip_int in network..=broadcast {
-            ips.push(Ipv4Addr::from(ip_int));
+        assert!(NetworkUtils::parse_cidr("2001:db8::/64").is_err());
+        assert!(NetworkUtils::parse_cidr("2001:db8::/129").is_err());
+        assert!(NetworkUtils::parse_cidr("192.0.2.0/33").is_err());
+    }
+    
+    #[test]
+    fn test_cidr_size_matches_parse_cidr() {
+        for cidr in ["192.0.2.77/30", "10.0.0.1", "10.0.0.1/32", "2001:db8::1234/120", "::1"] {
+            assert_eq!(NetworkUtils::cidr_size(cidr).unwrap(), NetworkUtils::parse_cidr(cidr).unwrap().len() as u64);
         }
-        
-        Ok(ips)
+        assert_eq!(NetworkUtils::cidr_size("10.0.0.0/8").unwrap(), 1 << 24);
+        assert_eq!(NetworkUtils::cidr_size("0.0.0.0/0").unwrap(), 1 << 32);
+        assert!(NetworkUtils::cidr_size("2001:db8::/64").is_err());
+        assert!(NetworkUtils::cidr_size("example.com").is_err());
     }
-}
\ No newline at end of file
+}
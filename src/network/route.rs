@@ -0,0 +1,224 @@
+//! Route summary for a scan
+//!
+//! Which interface, source address, gateway and MTU the kernel will use
+//! towards a target. Printed when a scan starts and recorded in report
+//! provenance, so that a scan where nothing answered can be checked against
+//! the path it actually took.
+
+use super::mtu::{MtuSource, PathMtu};
+use crate::config::ScanConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// The path probes towards one target take
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteInfo {
+    pub target: Ipv4Addr,
+    pub interface: Option<String>,
+    pub source: Option<Ipv4Addr>,
+    /// Next hop; `None` when the target is on-link
+    pub gateway: Option<Ipv4Addr>,
+    pub mtu: u16,
+    pub mtu_source: MtuSource,
+    /// Tunnel type of the interface, e.g. "WireGuard"
+    pub tunnel: Option<String>,
+}
+
+impl RouteInfo {
+    /// Look up the route towards `target`, out of `interface` if one is forced
+    pub fn lookup(target: Ipv4Addr, interface: Option<&str>, mtu_override: Option<u16>) -> Self {
+        let egress = egress(target);
+        let interface = interface.map(str::to_string).or_else(|| egress.as_ref().map(|(name, _)| name.clone()));
+        let source = match (&interface, &egress) {
+            (Some(name), Some((egress_name, source))) if name == egress_name => Some(*source),
+            (Some(name), _) => interface_address(name),
+            (None, _) => None,
+        };
+        let gateway = interface.as_deref().and_then(|name| {
+            let table = std::fs::read_to_string("/proc/net/route").ok()?;
+            select_route(&table, target, name)?
+        });
+        let mtu = match mtu_override {
+            Some(mtu) => PathMtu::fixed(mtu),
+            None => PathMtu::detect(Some(target), interface.as_deref()),
+        };
+        Self {
+            target,
+            interface,
+            source,
+            gateway,
+            mtu: mtu.mtu,
+            mtu_source: mtu.source,
+            tunnel: mtu.tunnel.map(str::to_string),
+        }
+    }
+
+    /// Route for `config`'s target, when it is an IPv4 address or network
+    pub fn for_config(config: &ScanConfig) -> Option<Self> {
+        let host = config.target.split('/').next()?;
+        let target = host.parse().ok()?;
+        Some(Self::lookup(target, config.interface.as_deref(), config.mtu_override))
+    }
+}
+
+impl fmt::Display for RouteInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.interface.as_deref().unwrap_or("unknown interface"))?;
+        if let Some(source) = self.source {
+            write!(f, ", source {}", source)?;
+        }
+        match self.gateway {
+            Some(gateway) => write!(f, ", gateway {}", gateway)?,
+            None => f.write_str(", on-link")?,
+        }
+        write!(f, ", MTU {} ({}", self.mtu, self.mtu_source.name())?;
+        if let Some(tunnel) = &self.tunnel {
+            write!(f, ", {} tunnel", tunnel)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Whether `target` is reached directly over the link rather than through a
+/// gateway, so that it answers ARP
+pub fn is_on_link(target: Ipv4Addr, interface: Option<&str>) -> bool {
+    if target.is_loopback() {
+        return false;
+    }
+    let Some(interface) = interface.map(str::to_string).or_else(|| egress(target).map(|(name, _)| name)) else {
+        return false;
+    };
+    std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|table| select_route(&table, target, &interface))
+        == Some(None)
+}
+
+/// Hardware address of an on-link `target`, resolved through the kernel's
+/// neighbour table
+///
+/// A datagram to the discard port makes the kernel ARP for the target; the
+/// table is then polled until the entry completes or `timeout` passes.
+/// `Ok(None)` means nothing answered, an error that the table is unreadable
+/// on this platform.
+pub async fn resolve_neighbor(target: Ipv4Addr, timeout: Duration) -> std::io::Result<Option<String>> {
+    if let Some(mac) = arp_entry(&std::fs::read_to_string(ARP_TABLE)?, target) {
+        return Ok(Some(mac));
+    }
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.send_to(&[], (target, 9)).await?;
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        if let Some(mac) = arp_entry(&std::fs::read_to_string(ARP_TABLE)?, target) {
+            return Ok(Some(mac));
+        }
+    }
+    Ok(None)
+}
+
+const ARP_TABLE: &str = "/proc/net/arp";
+
+/// Completed `/proc/net/arp` entry for `target`
+fn arp_entry(table: &str, target: Ipv4Addr) -> Option<String> {
+    // ATF_COM: the entry has a hardware address
+    const COMPLETE: u32 = 0x2;
+    table.lines().skip(1).find_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() < 4 || columns[0].parse() != Ok(target) {
+            return None;
+        }
+        let flags = u32::from_str_radix(columns[2].trim_start_matches("0x"), 16).ok()?;
+        (flags & COMPLETE != 0).then(|| columns[3].to_string())
+    })
+}
+
+/// Interface and source address the kernel picks for `target`
+pub(crate) fn egress(target: Ipv4Addr) -> Option<(String, Ipv4Addr)> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    // Connecting a UDP socket only performs the route lookup; nothing is sent
+    socket.connect((target, 9)).ok()?;
+    let IpAddr::V4(source) = socket.local_addr().ok()?.ip() else {
+        return None;
+    };
+    let interface = pnet::datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.ips.iter().any(|net| net.ip() == IpAddr::V4(source)))?;
+    Some((interface.name, source))
+}
+
+fn interface_address(name: &str) -> Option<Ipv4Addr> {
+    pnet::datalink::interfaces()
+        .into_iter()
+        .find(|iface| iface.name == name)?
+        .ips
+        .iter()
+        .find_map(|net| match net.ip() {
+            IpAddr::V4(addr) => Some(addr),
+            IpAddr::V6(_) => None,
+        })
+}
+
+/// Gateway of the most specific `/proc/net/route` entry for `target` on
+/// `interface`: `Some(None)` when on-link, `None` without a matching route
+fn select_route(table: &str, target: Ipv4Addr, interface: &str) -> Option<Option<Ipv4Addr>> {
+    // Addresses are hex in host byte order
+    let field = |text: &str| u32::from_str_radix(text, 16).ok().map(|raw| Ipv4Addr::from(u32::from_be(raw)));
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            if columns.len() < 8 || columns[0] != interface {
+                return None;
+            }
+            let destination = u32::from(field(columns[1])?);
+            let gateway = field(columns[2])?;
+            let mask = u32::from(field(columns[7])?);
+            let metric: u32 = columns[6].parse().ok()?;
+            (u32::from(target) & mask == destination).then_some((mask.count_ones(), metric, gateway))
+        })
+        // Longest prefix first, then lowest metric
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, _, gateway)| (!gateway.is_unspecified()).then_some(gateway))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_selection_and_summary() {
+        let table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+            eth0\t00000000\t010200C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+            eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+            wg0\t00000000\t00000000\t0001\t0\t0\t50\t00000000\t0\t0\t0\n\
+            eth0\t0000000A\tFE0200C0\t0003\t0\t0\t0\t000000FF\t0\t0\t0\n";
+        let gateway = |target: &str, iface: &str| select_route(table, target.parse().unwrap(), iface);
+        assert_eq!(gateway("192.0.2.7", "eth0"), Some(None));
+        assert_eq!(gateway("10.1.2.3", "eth0"), Some(Some(Ipv4Addr::new(192, 0, 2, 254))));
+        assert_eq!(gateway("198.51.100.1", "eth0"), Some(Some(Ipv4Addr::new(192, 0, 2, 1))));
+        assert_eq!(gateway("198.51.100.1", "wg0"), Some(None));
+        assert_eq!(gateway("198.51.100.1", "lo"), None);
+
+        let arp = "IP address       HW type     Flags       HW address            Mask     Device\n\
+            192.0.2.1        0x1         0x2         52:54:00:12:34:56     *        eth0\n\
+            192.0.2.9        0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert_eq!(arp_entry(arp, Ipv4Addr::new(192, 0, 2, 1)).as_deref(), Some("52:54:00:12:34:56"));
+        assert_eq!(arp_entry(arp, Ipv4Addr::new(192, 0, 2, 9)), None);
+        assert_eq!(arp_entry(arp, Ipv4Addr::new(192, 0, 2, 10)), None);
+
+        let route = RouteInfo {
+            target: Ipv4Addr::new(198, 51, 100, 1),
+            interface: Some("eth0".to_string()),
+            source: Some(Ipv4Addr::new(192, 0, 2, 10)),
+            gateway: Some(Ipv4Addr::new(192, 0, 2, 1)),
+            mtu: 1500,
+            mtu_source: MtuSource::Route,
+            tunnel: None,
+        };
+        assert_eq!(route.to_string(), "eth0, source 192.0.2.10, gateway 192.0.2.1, MTU 1500 (route)");
+    }
+}
@@ -0,0 +1,212 @@
+//! A dedicated OS thread for raw probe sends (`--send-thread`)
+//!
+//! At high rates the tokio workers that build probes also parse replies,
+//! run scripts and render output, and a burst of that work delays sends
+//! queued behind it: the wire sees clumps and gaps instead of an even
+//! rate. A [`SendThread`] takes the send off the workers. Probes are queued
+//! to one thread outside the runtime, which spaces them at the scan rate on
+//! its own clock and asks the kernel for real-time scheduling, or failing
+//! that a higher nice priority, so other work on the host preempts it less.
+
+use super::socket::RawSocket;
+use crate::ScanError;
+use std::io;
+use std::net::SocketAddr;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+
+/// Probes waiting for the thread before senders have to wait for it
+const QUEUE_DEPTH: usize = 4096;
+
+/// `SCHED_FIFO` priority asked for; above ordinary threads, well below the
+/// kernel's own real-time threads
+const REALTIME_PRIORITY: i32 = 10;
+
+/// Nice value asked for when real-time scheduling is not permitted
+const NICE: i32 = -10;
+
+/// The scheduling the send thread got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendPriority {
+    /// `SCHED_FIFO` at this priority
+    Realtime(i32),
+    /// Normal scheduling at this nice value
+    Nice(i32),
+    /// Neither was permitted (needs CAP_SYS_NICE or an RLIMIT_RTPRIO/RLIMIT_NICE allowance)
+    Normal,
+}
+
+impl std::fmt::Display for SendPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendPriority::Realtime(priority) => write!(f, "SCHED_FIFO {}", priority),
+            SendPriority::Nice(nice) => write!(f, "nice {}", nice),
+            SendPriority::Normal => write!(f, "normal priority"),
+        }
+    }
+}
+
+struct Probe {
+    packet: Vec<u8>,
+    dest: SocketAddr,
+    sent: oneshot::Sender<io::Result<usize>>,
+}
+
+impl std::fmt::Debug for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Probe").field("dest", &self.dest).field("len", &self.packet.len()).finish()
+    }
+}
+
+/// The thread sending queued probes, and the queue to it
+///
+/// The thread exits once the last `SendThread` handle is dropped and the
+/// queue has drained.
+#[derive(Debug)]
+pub struct SendThread {
+    queue: mpsc::Sender<Probe>,
+    priority: SendPriority,
+    _thread: JoinHandle<()>,
+}
+
+impl SendThread {
+    /// Send on a raw TCP socket of the thread's own, at most `rate` probes
+    /// per second (unpaced for 0), marked with `mark` if given
+    pub fn spawn(rate: u64, mark: Option<u32>) -> crate::Result<Self> {
+        let socket = RawSocket::new_tcp()?;
+        if let Some(mark) = mark {
+            socket.set_mark(mark)?;
+        }
+        // Blocking, so a full socket buffer holds the thread instead of failing the probe
+        socket.set_nonblocking(false)?;
+        Self::spawn_with(rate, move |packet, dest| {
+            socket.send_to(packet, dest).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+        })
+    }
+
+    /// Send with `send` instead of a raw socket
+    pub fn spawn_with<F>(rate: u64, send: F) -> crate::Result<Self>
+    where
+        F: FnMut(&[u8], SocketAddr) -> io::Result<usize> + Send + 'static,
+    {
+        let interval = match rate {
+            0 => None,
+            rate => Some(Duration::from_secs_f64(1.0 / rate as f64)),
+        };
+        let (queue, probes) = mpsc::channel(QUEUE_DEPTH);
+        let (started, priority) = std::sync::mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("phobos-send".to_string())
+            .spawn(move || {
+                let _ = started.send(elevate_priority());
+                send_loop(probes, interval, send);
+            })
+            .map_err(|e| ScanError::NetworkError(format!("cannot start send thread: {}", e)))?;
+        let priority = priority.recv().unwrap_or(SendPriority::Normal);
+        Ok(Self { queue, priority, _thread: thread })
+    }
+
+    pub fn priority(&self) -> SendPriority {
+        self.priority
+    }
+
+    /// Queue `packet` for `dest` and wait until the thread has sent it
+    pub async fn send_to(&self, packet: Vec<u8>, dest: SocketAddr) -> crate::Result<usize> {
+        let (sent, result) = oneshot::channel();
+        let stopped = || ScanError::NetworkError("send thread stopped".to_string());
+        self.queue.send(Probe { packet, dest, sent }).await.map_err(|_| stopped())?;
+        result
+            .await
+            .map_err(|_| stopped())?
+            .map_err(|e| ScanError::NetworkError(e.to_string()))
+    }
+}
+
+fn send_loop<F>(mut probes: mpsc::Receiver<Probe>, interval: Option<Duration>, mut send: F)
+where
+    F: FnMut(&[u8], SocketAddr) -> io::Result<usize>,
+{
+    let mut next = Instant::now();
+    while let Some(probe) = probes.blocking_recv() {
+        if let Some(interval) = interval {
+            let now = Instant::now();
+            if next > now {
+                std::thread::sleep(next - now);
+            }
+            // An idle queue does not bank a burst for later
+            next = next.max(now) + interval;
+        }
+        let _ = probe.sent.send(send(&probe.packet, probe.dest));
+    }
+}
+
+/// Ask for real-time scheduling for the calling thread, else a lower nice value
+#[cfg(target_os = "linux")]
+fn elevate_priority() -> SendPriority {
+    let param = libc::sched_param { sched_priority: REALTIME_PRIORITY };
+    // SAFETY: pid 0 is the calling thread and `param` outlives the call
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } == 0 {
+        return SendPriority::Realtime(REALTIME_PRIORITY);
+    }
+    // Nice values are per thread on Linux, so this leaves the rest of the process alone
+    // SAFETY: gettid has no arguments and cannot fail
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    // SAFETY: plain syscall on our own thread id
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, NICE) } == 0 {
+        return SendPriority::Nice(NICE);
+    }
+    log::debug!("send thread keeps normal priority: {}", io::Error::last_os_error());
+    SendPriority::Normal
+}
+
+#[cfg(not(target_os = "linux"))]
+fn elevate_priority() -> SendPriority {
+    SendPriority::Normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_send_thread_paces_probes_in_order() {
+        let sends = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&sends);
+        let thread = Arc::new(
+            SendThread::spawn_with(200, move |packet, dest| {
+                log.lock().unwrap().push((packet[0], dest.port(), Instant::now()));
+                match dest.port() {
+                    0 => Err(io::Error::new(io::ErrorKind::InvalidInput, "port 0")),
+                    _ => Ok(packet.len()),
+                }
+            })
+            .unwrap(),
+        );
+
+        let dest = |port| SocketAddr::from(([192, 0, 2, 1], port));
+        for n in 0..10u8 {
+            assert_eq!(thread.send_to(vec![n; 3], dest(80)).await.unwrap(), 3);
+        }
+        assert!(thread.send_to(vec![10], dest(0)).await.is_err());
+
+        // Queued all at once, still sent 5ms apart
+        let burst: Vec<_> = (11..21u8)
+            .map(|n| {
+                let thread = Arc::clone(&thread);
+                tokio::spawn(async move { thread.send_to(vec![n], dest(443)).await })
+            })
+            .collect();
+        for send in burst {
+            send.await.unwrap().unwrap();
+        }
+
+        let sends = sends.lock().unwrap();
+        assert_eq!(sends.len(), 21);
+        assert!(sends[..11].iter().enumerate().all(|(i, (n, _, _))| *n as usize == i));
+        for pair in sends.windows(2) {
+            assert!(pair[1].2 - pair[0].2 >= Duration::from_millis(4), "{:?}", pair[1].2 - pair[0].2);
+        }
+    }
+}
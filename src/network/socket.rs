@@ -1,12 +1,17 @@
 //! Raw socket management and operations
 
+use crate::network::bpf::{BpfProgram, FilterExpr};
+use crate::network::unreachable::{Unreachable, UnreachableReply};
 use crate::ScanError;
 use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
 use std::io;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::os::unix::io::AsRawFd;
-use std::time::Duration;
-use tokio::net::UdpSocket;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpStream, UdpSocket};
 
 /// Raw socket wrapper for sending crafted packets
 #[derive(Debug)]
@@ -36,6 +41,28 @@ impl RawSocket {
         })
     }
     
+    /// Create a new raw TCP socket for IPv6
+    ///
+    /// Unlike IPv4 raw sockets, these never carry the IP header: segments
+    /// are sent and received bare, so checksums use the IPv6 pseudo-header.
+    pub fn new_tcp6() -> crate::Result<Self> {
+        let socket = Socket::new(Domain::IPV6, Type::from(libc::SOCK_RAW), Some(Protocol::TCP))
+            .map_err(|e| {
+                if e.kind() == io::ErrorKind::PermissionDenied {
+                    ScanError::PermissionError("Permission denied for raw socket".to_string())
+                } else {
+                    ScanError::NetworkError(e.to_string())
+                }
+            })?;
+        
+        socket.set_nonblocking(true).map_err(|e| ScanError::NetworkError(e.to_string()))?;
+        
+        Ok(Self {
+            socket,
+            _protocol: Protocol::TCP,
+        })
+    }
+    
     /// Create a new raw UDP socket
     pub fn new_udp() -> crate::Result<Self> {
         let socket = Socket::new(Domain::IPV4, Type::from(libc::SOCK_RAW), Some(Protocol::UDP))
@@ -118,6 +145,11 @@ impl RawSocket {
         }
     }
     
+    /// Make reads wait (for up to the read timeout) instead of failing at once
+    pub fn set_nonblocking(&self, nonblocking: bool) -> crate::Result<()> {
+        self.socket.set_nonblocking(nonblocking).map_err(|e| ScanError::NetworkError(e.to_string()))
+    }
+    
     /// Set receive timeout
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> crate::Result<()> {
         self.socket.set_read_timeout(timeout).map_err(|e| ScanError::NetworkError(e.to_string()))
@@ -128,6 +160,54 @@ impl RawSocket {
         self.socket.set_write_timeout(timeout).map_err(|e| ScanError::NetworkError(e.to_string()))
     }
     
+    /// Send packets with their own IP header (IP_HDRINCL), so the source
+    /// address is whatever the packet says
+    pub fn set_header_included(&self, included: bool) -> crate::Result<()> {
+        let value = libc::c_int::from(included);
+        let result = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::IPPROTO_IP,
+                libc::IP_HDRINCL,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(ScanError::NetworkError(io::Error::last_os_error().to_string()));
+        }
+        Ok(())
+    }
+    
+    /// Tag outgoing packets with a firewall mark (SO_MARK, needs CAP_NET_ADMIN)
+    #[cfg(target_os = "linux")]
+    pub fn set_mark(&self, mark: u32) -> crate::Result<()> {
+        let result = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_MARK,
+                &mark as *const u32 as *const libc::c_void,
+                std::mem::size_of::<u32>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            let e = io::Error::last_os_error();
+            return Err(if e.kind() == io::ErrorKind::PermissionDenied {
+                ScanError::PermissionError("Marking packets needs CAP_NET_ADMIN".to_string())
+            } else {
+                ScanError::NetworkError(e.to_string())
+            });
+        }
+        Ok(())
+    }
+    
+    /// Tag outgoing packets with a firewall mark (Linux only)
+    #[cfg(not(target_os = "linux"))]
+    pub fn set_mark(&self, _mark: u32) -> crate::Result<()> {
+        Err(ScanError::NetworkError("Packet marks are only supported on Linux".to_string()))
+    }
+    
     /// Get the raw file descriptor (Unix only)
     pub fn as_raw_fd(&self) -> i32 {
         self.socket.as_raw_fd()
@@ -138,8 +218,8 @@ impl RawSocket {
 #[derive(Debug)]
 pub struct TcpConnectScanner {
     timeout: Duration,
-    /// Connection pool for reusing sockets
-    connection_pool: std::sync::Arc<tokio::sync::Mutex<Vec<tokio::net::TcpStream>>>,
+    /// Pool that keeps open connections around for later enrichment probes
+    connection_pool: Option<ConnectionPool>,
     /// Adaptive timeout based on network conditions
     adaptive_timeout: std::sync::Arc<std::sync::atomic::AtomicU64>,
 }
@@ -148,11 +228,17 @@ impl TcpConnectScanner {
     pub fn new(timeout: Duration) -> Self {
         Self { 
             timeout,
-            connection_pool: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            connection_pool: None,
             adaptive_timeout: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(timeout.as_millis() as u64)),
         }
     }
     
+    /// Keep successful connections in `pool` instead of closing them
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.connection_pool = Some(pool);
+        self
+    }
+    
     /// Perform a TCP connect scan on a single port
     pub async fn scan_port(&self, target: IpAddr, port: u16) -> crate::Result<bool> {
         let addr = SocketAddr::new(target, port);
@@ -178,8 +264,10 @@ impl TcpConnectScanner {
             
             match tokio::time::timeout(attempt_timeout, tokio::net::TcpStream::connect(addr)).await {
                 Ok(Ok(stream)) => {
-                    // Connection successful - close quickly
-                    drop(stream);
+                    // Connection successful - hand it to the pool or close quickly
+                    if let Some(pool) = &self.connection_pool {
+                        pool.checkin(addr, stream);
+                    }
                     result = true;
                     break;
                 },
@@ -256,6 +344,30 @@ impl Clone for TcpConnectScanner {
     }
 }
 
+/// Service-specific UDP probes, for better UDP detection
+const UDP_SERVICE_PROBES: &[(u16, &[u8])] = &[
+    (53, b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01"), // DNS query
+    (123, b"\x1b\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"), // NTP
+    (161, b"\x30\x26\x02\x01\x01\x04\x06\x70\x75\x62\x6c\x69\x63\xa0\x19\x02\x04\x00\x00\x00\x00\x02\x01\x00\x02\x01\x00\x30\x0b\x30\x09\x06\x05\x2b\x06\x01\x02\x01\x05\x00"), // SNMP
+    (69, b"\x00\x01example.txt\x00netascii\x00"), // TFTP
+    (514, b"<30>Jan 1 00:00:00 test: UDP probe\n"), // Syslog
+    (1900, b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: upnp:rootdevice\r\nMX: 3\r\n\r\n"), // UPnP SSDP
+];
+
+/// Datagrams [`UdpScanner`] sends per port; UDP needs more attempts due to unreliable nature
+pub const UDP_ATTEMPTS: usize = 2;
+
+/// Sent to ports without a service-specific probe
+const GENERIC_UDP_PROBE: &[u8] = b"\x00\x00\x00\x00";
+
+/// Payload [`UdpScanner`] sends to `port`, before any `with_max_payload` limit
+pub fn udp_probe_payload(port: u16) -> &'static [u8] {
+    UDP_SERVICE_PROBES
+        .iter()
+        .find(|&&(p, _)| p == port)
+        .map_or(GENERIC_UDP_PROBE, |&(_, probe)| probe)
+}
+
 /// UDP scanner for UDP port scanning
 #[derive(Debug)]
 pub struct UdpScanner {
@@ -268,15 +380,10 @@ pub struct UdpScanner {
 
 impl UdpScanner {
     pub fn new(timeout: Duration) -> Self {
-        let mut service_probes = std::collections::HashMap::new();
-        
-        // Add service-specific UDP probes
-        service_probes.insert(53, b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03com\x00\x00\x01\x00\x01".to_vec()); // DNS query
-        service_probes.insert(123, b"\x1b\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec()); // NTP
-        service_probes.insert(161, b"\x30\x26\x02\x01\x01\x04\x06\x70\x75\x62\x6c\x69\x63\xa0\x19\x02\x04\x00\x00\x00\x00\x02\x01\x00\x02\x01\x00\x30\x0b\x30\x09\x06\x05\x2b\x06\x01\x02\x01\x05\x00".to_vec()); // SNMP
-        service_probes.insert(69, b"\x00\x01example.txt\x00netascii\x00".to_vec()); // TFTP
-        service_probes.insert(514, b"<30>Jan 1 00:00:00 test: UDP probe\n".to_vec()); // Syslog
-        service_probes.insert(1900, b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: upnp:rootdevice\r\nMX: 3\r\n\r\n".to_vec()); // UPnP SSDP
+        let service_probes = UDP_SERVICE_PROBES
+            .iter()
+            .map(|&(port, probe)| (port, probe.to_vec()))
+            .collect();
         
         let icmp_socket = RawSocket::new_icmp().ok();
         
@@ -287,6 +394,12 @@ impl UdpScanner {
         }
     }
     
+    /// Drop service probes longer than `max` bytes; those ports get the generic probe
+    pub fn with_max_payload(mut self, max: usize) -> Self {
+        self.service_probes.retain(|_, probe| probe.len() <= max);
+        self
+    }
+    
     /// Perform a UDP scan on a single port with service-specific probes
     pub async fn scan_port(&self, target: IpAddr, port: u16) -> crate::Result<bool> {
         let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
@@ -297,13 +410,13 @@ impl UdpScanner {
         // Get service-specific probe or use generic probe
         let probe_data = self.service_probes.get(&port)
             .map(|p| p.as_slice())
-            .unwrap_or(b"\x00\x00\x00\x00"); // Generic probe
+            .unwrap_or(GENERIC_UDP_PROBE);
         
         let _start_time = std::time::Instant::now();
         
         // UDP scanning with retry logic
         let mut attempts = 0;
-        let max_attempts = 2; // UDP needs more attempts due to unreliable nature
+        let max_attempts = UDP_ATTEMPTS;
         
         while attempts < max_attempts {
             // Send UDP probe with timeout
@@ -319,8 +432,10 @@ impl UdpScanner {
                     
                     if udp_response {
                         return Ok(true);  // Got UDP response - port is definitely open
-                    } else if icmp_unreachable {
-                        return Ok(false); // Got ICMP unreachable - port is closed
+                    } else if let Some(kind) = icmp_unreachable {
+                        // Closed on port unreachable, filtered on any other code
+                        log::debug!("{}:{}/udp {} ({})", target, port, kind.port_state(), kind);
+                        return Ok(false);
                     }
                     // No response on this attempt - try again if we have attempts left
                 }
@@ -345,9 +460,9 @@ impl UdpScanner {
     }
     
     /// Wait for UDP response or ICMP unreachable message
-    async fn wait_for_response(&self, socket: &UdpSocket, target: IpAddr, port: u16) -> (bool, bool) {
+    async fn wait_for_response(&self, socket: &UdpSocket, target: IpAddr, port: u16) -> (bool, Option<Unreachable>) {
         let mut udp_response = false;
-        let mut icmp_unreachable = false;
+        let mut icmp_unreachable = None;
         
         // Create tasks for UDP response and ICMP monitoring
         let udp_task = async {
@@ -375,8 +490,8 @@ impl UdpScanner {
                     async {
                         loop {
                             if let Ok((len, _)) = icmp_socket.recv_from(&mut buf) {
-                                if self.is_icmp_unreachable(&buf[..len], target, port) {
-                                    return true;
+                                if let Some(kind) = self.icmp_unreachable(&buf[..len], target, port) {
+                                    return Some(kind);
                                 }
                             }
                             tokio::time::sleep(Duration::from_millis(10)).await;
@@ -384,10 +499,10 @@ impl UdpScanner {
                     }
                 ).await {
                     Ok(result) => result,
-                    Err(_) => false,
+                    Err(_) => None,
                 }
             } else {
-                false
+                None
             }
         };
         
@@ -404,34 +519,11 @@ impl UdpScanner {
         (udp_response, icmp_unreachable)
     }
     
-    /// Check if ICMP packet indicates port unreachable
-    fn is_icmp_unreachable(&self, packet: &[u8], target: IpAddr, port: u16) -> bool {
-        if packet.len() < 28 { // Minimum ICMP + IP header size
-            return false;
-        }
-        
-        // Parse ICMP header (simplified)
-        let icmp_type = packet[20]; // ICMP type (after IP header)
-        let icmp_code = packet[21]; // ICMP code
-        
-        // Check for Destination Unreachable (Type 3) with Port Unreachable (Code 3)
-        if icmp_type == 3 && icmp_code == 3 {
-            // Extract original packet info from ICMP payload
-            if packet.len() >= 48 {
-                // Original IP header starts at offset 28
-                let orig_dest_ip = u32::from_be_bytes([
-                    packet[44], packet[45], packet[46], packet[47]
-                ]);
-                let orig_dest_port = u16::from_be_bytes([packet[50], packet[51]]);
-                
-                // Check if this unreachable is for our target and port
-                if let IpAddr::V4(target_v4) = target {
-                    return u32::from(target_v4) == orig_dest_ip && orig_dest_port == port;
-                }
-            }
-        }
-        
-        false
+    /// Code of an ICMP destination unreachable for our probe to `target`:`port`
+    fn icmp_unreachable(&self, packet: &[u8], target: IpAddr, port: u16) -> Option<Unreachable> {
+        let reply = UnreachableReply::parse(packet)?;
+        let IpAddr::V4(target_v4) = target else { return None };
+        (reply.destination == target_v4 && reply.port == port && reply.protocol == 17).then_some(reply.kind)
     }
     
     /// High-performance batch UDP scanning
@@ -519,6 +611,11 @@ impl SocketPool {
         })
     }
     
+    /// Mark every probe the pool sends, so firewall rules can match them
+    pub fn set_mark(&self, mark: u32) -> crate::Result<()> {
+        self.tcp_sockets.iter().chain(&self.udp_sockets).try_for_each(|socket| socket.set_mark(mark))
+    }
+    
     /// Get the next available TCP socket (round-robin)
     pub fn get_tcp_socket(&self) -> Option<&RawSocket> {
         if self.tcp_sockets.is_empty() {
@@ -543,4 +640,472 @@ impl SocketPool {
     pub fn get_icmp_socket(&self) -> Option<&RawSocket> {
         self.icmp_socket.as_ref()
     }
-}
\ No newline at end of file
+    
+    /// Restrict every socket to replies from `target` (address and prefix
+    /// length), further narrowed by the user's `extra` expression
+    pub fn attach_capture_filters(&self, target: Option<(Ipv4Addr, u8)>, extra: Option<&FilterExpr>) -> crate::Result<()> {
+        let groups = [
+            (libc::IPPROTO_TCP as u8, self.tcp_sockets.iter().collect::<Vec<_>>()),
+            (libc::IPPROTO_UDP as u8, self.udp_sockets.iter().collect()),
+            (libc::IPPROTO_ICMP as u8, self.icmp_socket.iter().collect()),
+        ];
+        for (proto, sockets) in groups {
+            let mut expr = FilterExpr::responses_from(proto, target);
+            if let Some(extra) = extra {
+                expr = expr.and(extra.clone());
+            }
+            let program = BpfProgram::compile(&expr)?;
+            for socket in sockets {
+                program.attach(socket.as_raw_fd())?;
+            }
+        }
+        Ok(())
+    }
+}
+/// Established TCP connection waiting to be reused
+#[derive(Debug)]
+struct PooledConnection {
+    stream: TcpStream,
+    since: Instant,
+}
+
+/// Pool of established TCP connections keyed by `host:port`
+///
+/// The connect scan checks successful connections in, and enrichment probes
+/// (banner grabbing, HTTP, TLS) check them out again, so an open port is
+/// contacted once instead of once per probe. Each entry is handed out at most
+/// once; connections that are older than `max_idle` or already reset by the
+/// peer are discarded and a fresh connection is opened instead.
+#[derive(Debug, Clone)]
+pub struct ConnectionPool {
+    connections: Arc<Mutex<HashMap<SocketAddr, PooledConnection>>>,
+    capacity: usize,
+    max_idle: Duration,
+    reused: Arc<AtomicU64>,
+    opened: Arc<AtomicU64>,
+}
+
+impl ConnectionPool {
+    /// Create a pool holding at most `capacity` idle connections for up to `max_idle`
+    pub fn new(capacity: usize, max_idle: Duration) -> Self {
+        Self {
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            capacity,
+            max_idle,
+            reused: Arc::new(AtomicU64::new(0)),
+            opened: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    
+    /// Store an established connection. Returns false (and closes the stream)
+    /// when the pool is full or already holds a connection for `addr`.
+    pub fn checkin(&self, addr: SocketAddr, stream: TcpStream) -> bool {
+        let Ok(mut connections) = self.connections.lock() else {
+            return false;
+        };
+        
+        if connections.len() >= self.capacity {
+            let max_idle = self.max_idle;
+            connections.retain(|_, conn| conn.since.elapsed() <= max_idle);
+        }
+        
+        if connections.len() >= self.capacity || connections.contains_key(&addr) {
+            return false;
+        }
+        
+        connections.insert(addr, PooledConnection { stream, since: Instant::now() });
+        true
+    }
+    
+    /// Take the pooled connection for `addr` if it is still usable
+    pub fn checkout(&self, addr: SocketAddr) -> Option<TcpStream> {
+        let conn = self.connections.lock().ok()?.remove(&addr)?;
+        
+        if conn.since.elapsed() > self.max_idle {
+            return None;
+        }
+        
+        // A reset connection reports a pending error or loses its peer address
+        if !matches!(conn.stream.take_error(), Ok(None)) || conn.stream.peer_addr().is_err() {
+            return None;
+        }
+        
+        self.reused.fetch_add(1, Ordering::Relaxed);
+        Some(conn.stream)
+    }
+    
+    /// Reuse the pooled connection for `addr` or open a new one
+    pub async fn connect(&self, addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        if let Some(stream) = self.checkout(addr) {
+            return Ok(stream);
+        }
+        
+        let stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connection timed out"))??;
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        Ok(stream)
+    }
+    
+    /// Number of idle connections currently held
+    pub fn len(&self) -> usize {
+        self.connections.lock().map(|c| c.len()).unwrap_or(0)
+    }
+    
+    /// Whether the pool holds no idle connections
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    /// Number of probes served from a pooled connection
+    pub fn reused_count(&self) -> u64 {
+        self.reused.load(Ordering::Relaxed)
+    }
+    
+    /// Number of probes that had to open a new connection
+    pub fn opened_count(&self) -> u64 {
+        self.opened.load(Ordering::Relaxed)
+    }
+    
+    /// Close every idle connection
+    pub fn clear(&self) {
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.clear();
+        }
+    }
+}
+
+/// Socket options for connect scans that keep local TIME_WAIT usage in check
+///
+/// A connect scan that closes each socket normally leaves it in TIME_WAIT for
+/// 60s (Linux default). Scanning large ranges quickly can therefore use up the
+/// local ephemeral port range, after which `connect()` fails with
+/// `EADDRNOTAVAIL` and ports are silently misreported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectOptions {
+    /// Close with `SO_LINGER` 0: the kernel sends RST and skips TIME_WAIT
+    pub rst_on_close: bool,
+    /// Bind outgoing connections to this local port range (inclusive)
+    /// instead of the kernel's ephemeral range
+    pub local_port_range: Option<(u16, u16)>,
+    /// Cap the MSS of outgoing connections (`TCP_MAXSEG`), for paths with a
+    /// smaller MTU than the kernel knows about
+    pub mss: Option<u16>,
+}
+
+impl ConnectOptions {
+    /// Whether any option requires building the socket by hand
+    pub fn is_default(&self) -> bool {
+        !self.rst_on_close && self.local_port_range.is_none() && self.mss.is_none()
+    }
+    
+    /// Parse a `LOW-HIGH` local port range
+    pub fn parse_port_range(spec: &str) -> crate::Result<(u16, u16)> {
+        let invalid = || ScanError::ConfigError(format!("Invalid source port range '{}': expected LOW-HIGH", spec));
+        let (low, high) = spec.split_once('-').ok_or_else(invalid)?;
+        let low: u16 = low.trim().parse().map_err(|_| invalid())?;
+        let high: u16 = high.trim().parse().map_err(|_| invalid())?;
+        
+        if low == 0 || low > high {
+            return Err(invalid());
+        }
+        Ok((low, high))
+    }
+}
+
+/// Opens connect-scan sockets according to [`ConnectOptions`]
+#[derive(Debug, Clone, Default)]
+pub struct TcpConnector {
+    options: ConnectOptions,
+    next_port: Arc<AtomicU64>,
+}
+
+/// How many local ports to try before giving up on a connection
+const LOCAL_PORT_ATTEMPTS: usize = 16;
+
+impl TcpConnector {
+    pub fn new(options: ConnectOptions) -> Self {
+        Self {
+            options,
+            next_port: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    
+    pub fn options(&self) -> &ConnectOptions {
+        &self.options
+    }
+    
+    /// Connect to `addr`, applying the configured socket options
+    pub async fn connect(&self, addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        // Fast path: plain connect when no options are set
+        if self.options.is_default() {
+            return tokio::time::timeout(timeout, TcpStream::connect(addr)).await?;
+        }
+        
+        let Some((low, high)) = self.options.local_port_range else {
+            let socket = self.new_socket(addr)?;
+            return tokio::time::timeout(timeout, socket.connect(addr)).await?;
+        };
+        
+        // Walk the range round-robin; a port can still be busy (e.g. in
+        // TIME_WAIT towards the same destination), so try a few before failing
+        let span = u64::from(high - low) + 1;
+        let mut last_error = None;
+        for _ in 0..LOCAL_PORT_ATTEMPTS.min(span as usize) {
+            let port = low + (self.next_port.fetch_add(1, Ordering::Relaxed) % span) as u16;
+            let socket = self.new_socket(addr)?;
+            let local = match addr {
+                SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+                SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED), port),
+            };
+            
+            if let Err(e) = socket.bind(local) {
+                last_error = Some(e);
+                continue;
+            }
+            
+            match tokio::time::timeout(timeout, socket.connect(addr)).await? {
+                Err(e) if matches!(e.kind(), io::ErrorKind::AddrInUse | io::ErrorKind::AddrNotAvailable) => {
+                    last_error = Some(e);
+                }
+                result => return result,
+            }
+        }
+        
+        Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable)))
+    }
+    
+    fn new_socket(&self, addr: SocketAddr) -> io::Result<tokio::net::TcpSocket> {
+        let socket = match addr {
+            SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+        };
+        
+        if self.options.rst_on_close {
+            socket.set_linger(Some(Duration::ZERO))?;
+        }
+        if self.options.local_port_range.is_some() {
+            socket.set_reuseaddr(true)?;
+        }
+        if let Some(mss) = self.options.mss {
+            let mss = libc::c_int::from(mss);
+            // SAFETY: `mss` is a valid c_int for the duration of the call
+            let result = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::IPPROTO_TCP,
+                    libc::TCP_MAXSEG,
+                    &mss as *const libc::c_int as *const libc::c_void,
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(socket)
+    }
+}
+
+/// Detects local ephemeral port exhaustion during connect scans
+#[derive(Debug, Default)]
+pub struct PortExhaustionMonitor {
+    addr_not_available: AtomicU64,
+    warned: std::sync::atomic::AtomicBool,
+}
+
+/// Warn once TIME_WAIT sockets occupy this share of the ephemeral range
+const TIME_WAIT_WARN_RATIO: f64 = 0.5;
+
+impl PortExhaustionMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Number of IPv4 TCP sockets in TIME_WAIT (Linux only)
+    pub fn time_wait_sockets() -> Option<u64> {
+        let sockstat = std::fs::read_to_string("/proc/net/sockstat").ok()?;
+        Self::parse_time_wait(&sockstat)
+    }
+    
+    fn parse_time_wait(sockstat: &str) -> Option<u64> {
+        let line = sockstat.lines().find(|l| l.starts_with("TCP:"))?;
+        let mut fields = line.split_whitespace();
+        while let Some(field) = fields.next() {
+            if field == "tw" {
+                return fields.next()?.parse().ok();
+            }
+        }
+        None
+    }
+    
+    /// Kernel ephemeral port range (Linux only)
+    pub fn ephemeral_port_range() -> Option<(u16, u16)> {
+        let range = std::fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range").ok()?;
+        let mut parts = range.split_whitespace().map(|p| p.parse::<u16>());
+        match (parts.next()?, parts.next()?) {
+            (Ok(low), Ok(high)) if low <= high => Some((low, high)),
+            _ => None,
+        }
+    }
+    
+    /// Number of local ports available for `options`
+    fn available_ports(options: &ConnectOptions) -> Option<u64> {
+        let (low, high) = options.local_port_range.or_else(Self::ephemeral_port_range)?;
+        Some(u64::from(high - low) + 1)
+    }
+    
+    /// Warn before a scan when the planned connection count is likely to exhaust local ports.
+    /// Returns whether it warned.
+    pub fn preflight(&self, options: &ConnectOptions, planned_connections: u64) -> bool {
+        if options.rst_on_close {
+            return false;
+        }
+        let in_time_wait = Self::time_wait_sockets().unwrap_or(0);
+        let Some(available) = Self::exhausted_by(options, planned_connections, in_time_wait) else {
+            return false;
+        };
+        log::warn!(
+            "Scan needs ~{} connections but only {} local ports are available ({} already in TIME_WAIT); \
+             consider --rst-on-close or a wider --source-port-range",
+            planned_connections, available, in_time_wait
+        );
+        true
+    }
+    
+    /// The local ports available, when `planned_connections` on top of
+    /// `in_time_wait` would need more than that
+    fn exhausted_by(options: &ConnectOptions, planned_connections: u64, in_time_wait: u64) -> Option<u64> {
+        let available = Self::available_ports(options)?;
+        (planned_connections.saturating_add(in_time_wait) > available).then_some(available)
+    }
+    
+    /// Record a failed connect. Returns true if the error means local ports ran out,
+    /// in which case the port state is unknown rather than filtered.
+    pub fn record_error(&self, error: &io::Error, options: &ConnectOptions) -> bool {
+        if error.kind() != io::ErrorKind::AddrNotAvailable {
+            return false;
+        }
+        
+        self.addr_not_available.fetch_add(1, Ordering::Relaxed);
+        if !self.warned.swap(true, Ordering::Relaxed) {
+            let in_time_wait = Self::time_wait_sockets();
+            let exhausted = match (in_time_wait, Self::available_ports(options)) {
+                (Some(tw), Some(available)) => tw as f64 >= available as f64 * TIME_WAIT_WARN_RATIO,
+                _ => true,
+            };
+            if exhausted {
+                eprintln!("\x1b[33m⚠️  Local ephemeral ports exhausted ({} sockets in TIME_WAIT)\x1b[0m",
+                    in_time_wait.map(|tw| tw.to_string()).unwrap_or_else(|| "unknown".to_string()));
+                eprintln!("\x1b[36m🔧 Results for affected ports are unreliable. Try:\x1b[0m");
+                eprintln!("   • --rst-on-close (close with RST, no TIME_WAIT)");
+                eprintln!("   • --source-port-range LOW-HIGH with a wider range");
+                eprintln!("   • a lower --rate-limit or --batch-size");
+            }
+        }
+        true
+    }
+    
+    /// How many connects failed because no local port was available
+    pub fn exhaustion_errors(&self) -> u64 {
+        self.addr_not_available.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_preflight_counts_ports_in_time_wait() {
+        let narrow = ConnectOptions { local_port_range: Some((40000, 40009)), ..ConnectOptions::default() };
+        assert_eq!(PortExhaustionMonitor::exhausted_by(&narrow, 5, 0), None);
+        assert_eq!(PortExhaustionMonitor::exhausted_by(&narrow, 10, 0), None);
+        assert_eq!(PortExhaustionMonitor::exhausted_by(&narrow, 8, 3), Some(10));
+        assert_eq!(PortExhaustionMonitor::exhausted_by(&narrow, u64::MAX, 1), Some(10));
+        
+        let monitor = PortExhaustionMonitor::new();
+        assert!(monitor.preflight(&narrow, 1_000_000));
+        // RST on close leaves nothing in TIME_WAIT
+        let rst = ConnectOptions { rst_on_close: true, ..narrow };
+        assert!(!monitor.preflight(&rst, 1_000_000));
+    }
+    
+    #[test]
+    fn test_record_error_only_counts_exhaustion() {
+        let monitor = PortExhaustionMonitor::new();
+        let options = ConnectOptions::default();
+        assert!(!monitor.record_error(&io::Error::from(io::ErrorKind::ConnectionRefused), &options));
+        assert!(!monitor.record_error(&io::Error::from(io::ErrorKind::TimedOut), &options));
+        assert_eq!(monitor.exhaustion_errors(), 0);
+        
+        let exhausted = io::Error::from_raw_os_error(libc::EADDRNOTAVAIL);
+        assert!(monitor.record_error(&exhausted, &options));
+        assert!(monitor.record_error(&exhausted, &options));
+        assert_eq!(monitor.exhaustion_errors(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_connection_pool_reuses_discovery_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let pool = ConnectionPool::new(4, Duration::from_secs(5));
+        
+        let stream = TcpStream::connect(addr).await.unwrap();
+        assert!(pool.checkin(addr, stream));
+        assert_eq!(pool.len(), 1);
+        
+        let _reused = pool.connect(addr, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(pool.reused_count(), 1);
+        assert!(pool.is_empty());
+        
+        // The pooled entry is handed out once; the next probe connects again
+        let _fresh = pool.connect(addr, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(pool.opened_count(), 1);
+    }
+    
+    #[tokio::test]
+    async fn test_connection_pool_capacity_and_expiry() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        
+        let pool = ConnectionPool::new(1, Duration::from_secs(5));
+        assert!(pool.checkin(addr, TcpStream::connect(addr).await.unwrap()));
+        assert!(!pool.checkin(addr, TcpStream::connect(addr).await.unwrap()));
+        
+        let pool = ConnectionPool::new(1, Duration::from_millis(1));
+        assert!(pool.checkin(addr, TcpStream::connect(addr).await.unwrap()));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(pool.checkout(addr).is_none());
+    }
+    
+    #[test]
+    fn test_parse_source_port_range() {
+        assert_eq!(ConnectOptions::parse_port_range("40000-40100").unwrap(), (40000, 40100));
+        assert!(ConnectOptions::parse_port_range("40100-40000").is_err());
+        assert!(ConnectOptions::parse_port_range("0-10").is_err());
+        assert!(ConnectOptions::parse_port_range("40000").is_err());
+    }
+    
+    #[test]
+    fn test_parse_time_wait_from_sockstat() {
+        let sockstat = "sockets: used 312\nTCP: inuse 12 orphan 0 tw 28231 alloc 15 mem 3\nUDP: inuse 4 mem 2\n";
+        assert_eq!(PortExhaustionMonitor::parse_time_wait(sockstat), Some(28231));
+        assert_eq!(PortExhaustionMonitor::parse_time_wait("UDP: inuse 4"), None);
+    }
+    
+    #[tokio::test]
+    async fn test_connector_binds_to_source_port_range() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connector = TcpConnector::new(ConnectOptions {
+            rst_on_close: true,
+            local_port_range: Some((47100, 47163)),
+            mss: None,
+        });
+        
+        let stream = connector.connect(addr, Duration::from_secs(1)).await.unwrap();
+        let local_port = stream.local_addr().unwrap().port();
+        assert!((47100..=47163).contains(&local_port));
+    }
+}
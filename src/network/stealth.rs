@@ -1,10 +1,27 @@
 //! Stealth and firewall evasion techniques
 
-use crate::network::{packet::TcpPacketBuilder, Protocol};
+use crate::network::{
+    packet::{PacketParser, TcpPacketBuilder},
+    protocol::NetworkUtils,
+    socket::RawSocket,
+    PortState, Protocol,
+};
+use crate::utils::address_class::AddressClass;
+use crate::ScanError;
+use rand::seq::SliceRandom;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::net::{IpAddr, Ipv4Addr};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Most decoys one `--decoys` list may name; each multiplies the probes sent
+pub const MAX_DECOYS: usize = 128;
 
 /// Stealth configuration options
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +30,10 @@ pub struct StealthOptions {
     pub randomize_source_port: bool,
     pub spoof_source_ip: Option<IpAddr>,
     pub decoy_addresses: Vec<IpAddr>,
+    /// Where the real probe goes among `decoy_addresses` (`ME` in
+    /// `--decoys`); a random place for every probe when unset
+    #[serde(default)]
+    pub decoy_position: Option<usize>,
     pub timing_randomization: bool,
     pub packet_padding: Option<usize>,
     pub custom_mtu: Option<u16>,
@@ -28,6 +49,7 @@ impl Default for StealthOptions {
             randomize_source_port: true,
             spoof_source_ip: None,
             decoy_addresses: Vec::new(),
+            decoy_position: None,
             timing_randomization: false,
             packet_padding: None,
             custom_mtu: None,
@@ -46,6 +68,7 @@ impl StealthOptions {
             randomize_source_port: true,
             spoof_source_ip: None,
             decoy_addresses: Vec::new(),
+            decoy_position: None,
             timing_randomization: true,
             packet_padding: Some(25),
             custom_mtu: Some(24),
@@ -62,6 +85,7 @@ impl StealthOptions {
             randomize_source_port: true,
             spoof_source_ip: None,
             decoy_addresses: Vec::new(),
+            decoy_position: None,
             timing_randomization: true,
             packet_padding: Some(10),
             custom_mtu: None,
@@ -124,18 +148,14 @@ impl StealthOptions {
     
     /// Generate decoy IP addresses
     pub fn generate_decoys(&mut self, count: usize) {
-        let mut rng = rand::thread_rng();
-        self.decoy_addresses.clear();
-        
-        for _ in 0..count {
-            let ip = Ipv4Addr::new(
-                rng.gen_range(1..224),
-                rng.gen_range(0..255),
-                rng.gen_range(0..255),
-                rng.gen_range(1..255),
-            );
-            self.decoy_addresses.push(IpAddr::V4(ip));
-        }
+        self.decoy_addresses = (0..count).map(|_| IpAddr::V4(random_decoy())).collect();
+        self.decoy_position = None;
+    }
+    
+    /// Take decoys and the real probe's place from a `--decoys` list
+    pub fn set_decoys(&mut self, decoys: DecoyList) {
+        self.decoy_addresses = decoys.addresses;
+        self.decoy_position = decoys.position;
     }
     
     /// Get timing delay for stealth
@@ -149,6 +169,64 @@ impl StealthOptions {
     }
 }
 
+/// A random public IPv4 address, which looks like another scanner
+fn random_decoy() -> Ipv4Addr {
+    let mut rng = rand::thread_rng();
+    loop {
+        let ip = Ipv4Addr::from(rng.gen::<u32>());
+        if AddressClass::of_v4(ip) == AddressClass::Public && ip.octets()[3] != 0 {
+            return ip;
+        }
+    }
+}
+
+/// Decoy sources given with `--decoys`, e.g. `10.0.0.5,ME,RND:3`
+///
+/// Entries are IPv4 addresses, `ME` for where the real probe goes among
+/// them, and `RND` or `RND:N` for one or N random public addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoyList {
+    pub addresses: Vec<IpAddr>,
+    /// Index of `ME`: how many decoys are sent before the real probe
+    pub position: Option<usize>,
+}
+
+impl FromStr for DecoyList {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut list = DecoyList { addresses: Vec::new(), position: None };
+        for entry in s.split(',').map(str::trim) {
+            let upper = entry.to_ascii_uppercase();
+            if upper == "ME" {
+                if list.position.is_some() {
+                    return Err("ME can only appear once in a decoy list".to_string());
+                }
+                list.position = Some(list.addresses.len());
+            } else if upper == "RND" || upper.starts_with("RND:") {
+                let count = match upper.strip_prefix("RND:") {
+                    Some(count) => count.parse::<usize>().ok().filter(|n| (1..=MAX_DECOYS).contains(n))
+                        .ok_or_else(|| format!("`{}`: RND takes a count from 1 to {}", entry, MAX_DECOYS))?,
+                    None => 1,
+                };
+                list.addresses.extend((0..count).map(|_| IpAddr::V4(random_decoy())));
+            } else {
+                let ip: Ipv4Addr = entry
+                    .parse()
+                    .map_err(|_| format!("`{}` is not an IPv4 address, ME or RND[:N]", entry))?;
+                list.addresses.push(IpAddr::V4(ip));
+            }
+            if list.addresses.len() > MAX_DECOYS {
+                return Err(format!("at most {} decoys are allowed", MAX_DECOYS));
+            }
+        }
+        if list.addresses.is_empty() {
+            return Err("the decoy list names no decoy addresses".to_string());
+        }
+        Ok(list)
+    }
+}
+
 /// Packet fragmentation utilities
 pub struct PacketFragmenter {
     mtu: u16,
@@ -187,9 +265,11 @@ impl PacketFragmenter {
 }
 
 /// Decoy scanning implementation
+#[derive(Debug)]
 pub struct DecoyScanner {
     decoy_addresses: Vec<IpAddr>,
     real_source: IpAddr,
+    real_position: Option<usize>,
 }
 
 impl DecoyScanner {
@@ -197,9 +277,29 @@ impl DecoyScanner {
         Self {
             decoy_addresses: decoys,
             real_source,
+            real_position: None,
         }
     }
     
+    /// Send the real probe after `position` decoys instead of at random
+    pub fn with_real_position(mut self, position: Option<usize>) -> Self {
+        self.real_position = position.map(|p| p.min(self.decoy_addresses.len()));
+        self
+    }
+    
+    /// Sources of one probe in send order, `None` standing for the real one
+    ///
+    /// Decoys are shuffled for every probe so that no address is always
+    /// first; the real probe keeps its `ME` place, or lands anywhere.
+    pub fn send_order(&self) -> Vec<Option<IpAddr>> {
+        let mut rng = rand::thread_rng();
+        let mut order: Vec<Option<IpAddr>> = self.decoy_addresses.iter().copied().map(Some).collect();
+        order.shuffle(&mut rng);
+        let position = self.real_position.unwrap_or_else(|| rng.gen_range(0..=order.len()));
+        order.insert(position, None);
+        order
+    }
+    
     /// Generate scan packets with decoys
     pub fn generate_decoy_packets(
         &self,
@@ -207,21 +307,10 @@ impl DecoyScanner {
         port: u16,
         protocol: Protocol,
     ) -> Vec<Vec<u8>> {
-        let mut packets = Vec::new();
-        
-        // Add decoy packets
-        for &decoy_ip in &self.decoy_addresses {
-            let packet = self.create_packet(decoy_ip, target, port, protocol);
-            packets.push(packet);
-        }
-        
-        // Add real packet (randomly positioned)
-        let real_packet = self.create_packet(self.real_source, target, port, protocol);
-        let mut rng = rand::thread_rng();
-        let position = rng.gen_range(0..=packets.len());
-        packets.insert(position, real_packet);
-        
-        packets
+        self.send_order()
+            .into_iter()
+            .map(|source| self.create_packet(source.unwrap_or(self.real_source), target, port, protocol))
+            .collect()
     }
     
     fn create_packet(
@@ -280,6 +369,161 @@ impl DecoyScanner {
     }
 }
 
+/// Local ports used for real probes among decoys; the host stack answers
+/// their replies with a RST
+const DECOY_PROBE_PORTS: std::ops::Range<u16> = 40000..60000;
+
+/// How often the decoy receiver looks at the closed flag
+const DECOY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// (target, target port, our port) -> TCP flags of the reply
+type DecoyWaiters = HashMap<(Ipv4Addr, u16, u16), oneshot::Sender<u8>>;
+
+/// SYN probes to IPv4 targets, each sent among spoofed ones from the decoys
+///
+/// The real SYN and the decoys go out back to back through one raw socket
+/// that writes its own IP headers, in [`DecoyScanner::send_order`]; a
+/// receiver thread matches the replies to the real probes waiting for them.
+/// Targets answer the decoy addresses, which never reach us.
+pub struct DecoyLink {
+    scanner: DecoyScanner,
+    socket: Arc<RawSocket>,
+    waiters: Arc<Mutex<DecoyWaiters>>,
+    /// Source address for each target, as the routing table has it
+    sources: Mutex<HashMap<Ipv4Addr, Ipv4Addr>>,
+    next_port: AtomicU16,
+    mss: Option<u16>,
+    closed: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for DecoyLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecoyLink").field("decoys", &self.scanner.decoy_addresses).field("mss", &self.mss).finish()
+    }
+}
+
+impl DecoyLink {
+    /// Open the raw socket and start its receiver if `options` names any
+    /// decoys; needs CAP_NET_RAW
+    pub fn open(options: &StealthOptions) -> crate::Result<Option<Self>> {
+        if options.decoy_addresses.is_empty() {
+            return Ok(None);
+        }
+        let socket = RawSocket::new_tcp()?;
+        socket.set_header_included(true)?;
+        socket.set_nonblocking(false)?;
+        socket.set_read_timeout(Some(DECOY_POLL_INTERVAL))?;
+        let socket = Arc::new(socket);
+
+        let waiters = Arc::new(Mutex::new(DecoyWaiters::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let (socket, waiters, closed) = (Arc::clone(&socket), Arc::clone(&waiters), Arc::clone(&closed));
+            std::thread::Builder::new()
+                .name("decoy-rx".to_string())
+                .spawn(move || {
+                    let mut buf = [0u8; 1500];
+                    while !closed.load(Ordering::Relaxed) {
+                        // Read timeouts surface as errors; just poll the flag again
+                        let Ok((size, _)) = socket.recv_from(&mut buf) else { continue };
+                        let Some(reply) = PacketParser::parse_tcp_response(&buf[..size]) else { continue };
+                        let key = (reply.source_ip, reply.source_port, reply.dest_port);
+                        let waiter = waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+                        if let Some(waiter) = waiter {
+                            let _ = waiter.send(reply.flags);
+                        }
+                    }
+                })
+                .map_err(ScanError::IoError)?;
+        }
+
+        // The real source never reaches the packets: probes are built here
+        let scanner = DecoyScanner::new(options.decoy_addresses.clone(), IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .with_real_position(options.decoy_position);
+        Ok(Some(Self {
+            scanner,
+            socket,
+            waiters,
+            sources: Mutex::new(HashMap::new()),
+            next_port: AtomicU16::new(DECOY_PROBE_PORTS.start),
+            mss: None,
+            closed,
+        }))
+    }
+
+    /// Advertise `mss` in the real probes
+    pub fn with_mss(mut self, mss: u16) -> Self {
+        self.mss = Some(mss);
+        self
+    }
+
+    pub fn decoy_count(&self) -> usize {
+        self.scanner.decoy_addresses.len()
+    }
+
+    fn source_for(&self, target: Ipv4Addr) -> crate::Result<Ipv4Addr> {
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&source) = sources.get(&target) {
+            return Ok(source);
+        }
+        let IpAddr::V4(source) = NetworkUtils::source_for(IpAddr::V4(target))? else {
+            return Err(ScanError::NetworkError(format!("no IPv4 source address for {}", target)));
+        };
+        sources.insert(target, source);
+        Ok(source)
+    }
+
+    /// SYN `target:port` from every decoy and from us, and classify the
+    /// reply to ours
+    ///
+    /// A decoy that cannot be sent is logged and skipped.
+    pub async fn probe_syn(&self, target: Ipv4Addr, port: u16, timeout: Duration) -> crate::Result<PortState> {
+        let source = self.source_for(target)?;
+        let span = DECOY_PROBE_PORTS.end - DECOY_PROBE_PORTS.start;
+        let local_port = DECOY_PROBE_PORTS.start + self.next_port.fetch_add(1, Ordering::Relaxed) % span;
+        let key = (target, port, local_port);
+        let mut syn = TcpPacketBuilder::new(source, target, local_port, port).syn().seq_num(rand::random());
+        if let Some(mss) = self.mss {
+            syn = syn.mss(mss);
+        }
+        let syn = syn.build();
+        let dest = SocketAddr::new(IpAddr::V4(target), port);
+
+        let (waiter, reply) = oneshot::channel();
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).insert(key, waiter);
+        for decoy in self.scanner.send_order() {
+            let sent = match decoy {
+                Some(decoy) => {
+                    let packet = self.scanner.create_packet(decoy, IpAddr::V4(target), port, Protocol::Tcp);
+                    if let Err(e) = self.socket.send_to(&packet, dest) {
+                        log::debug!("Decoy SYN from {} to {} not sent: {}", decoy, dest, e);
+                    }
+                    Ok(0)
+                }
+                None => self.socket.send_to(&syn, dest),
+            };
+            if let Err(e) = sent {
+                self.waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+                return Err(e);
+            }
+        }
+
+        let state = match tokio::time::timeout(timeout, reply).await {
+            Ok(Ok(flags)) if flags & 0x12 == 0x12 => PortState::Open,
+            Ok(Ok(flags)) if flags & 0x04 != 0 => PortState::Closed,
+            _ => PortState::Filtered,
+        };
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+        Ok(state)
+    }
+}
+
+impl Drop for DecoyLink {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
 /// Source IP spoofing utilities
 pub struct SourceSpoofer {
     spoofed_ip: Option<IpAddr>,
@@ -431,7 +675,7 @@ impl StealthCoordinator {
         };
         
         let decoy_scanner = if !options.decoy_addresses.is_empty() {
-            Some(DecoyScanner::new(options.decoy_addresses.clone(), local_ip))
+            Some(DecoyScanner::new(options.decoy_addresses.clone(), local_ip).with_real_position(options.decoy_position))
         } else {
             None
         };
@@ -497,4 +741,43 @@ impl StealthCoordinator {
     pub fn get_source_ip(&self) -> IpAddr {
         self.source_spoofer.get_source_ip()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decoy_list() {
+        let list: DecoyList = "192.0.2.1, ME,198.51.100.7,rnd:2".parse().unwrap();
+        assert_eq!(list.addresses.len(), 4);
+        assert_eq!(list.addresses[..2], ["192.0.2.1".parse::<IpAddr>().unwrap(), "198.51.100.7".parse().unwrap()]);
+        assert!(list.addresses[2..].iter().all(|&ip| AddressClass::of(ip) == AddressClass::Public));
+        assert_eq!(list.position, Some(1));
+
+        assert_eq!("RND".parse::<DecoyList>().unwrap().position, None);
+        assert!("ME".parse::<DecoyList>().is_err());
+        assert!("192.0.2.1,ME,ME".parse::<DecoyList>().is_err());
+        assert!("2001:db8::1".parse::<DecoyList>().is_err());
+        assert!("RND:0".parse::<DecoyList>().is_err());
+        assert!(format!("RND:{}", MAX_DECOYS + 1).parse::<DecoyList>().is_err());
+    }
+
+    #[test]
+    fn test_send_order_keeps_real_probe_place() {
+        let decoys: Vec<IpAddr> = ["192.0.2.1", "192.0.2.2", "192.0.2.3"].iter().map(|ip| ip.parse().unwrap()).collect();
+        let pinned = DecoyScanner::new(decoys.clone(), IpAddr::V4(Ipv4Addr::LOCALHOST)).with_real_position(Some(2));
+        for _ in 0..20 {
+            let order = pinned.send_order();
+            assert_eq!(order.len(), 4);
+            assert_eq!(order[2], None);
+            assert!(decoys.iter().all(|decoy| order.contains(&Some(*decoy))));
+        }
+
+        let floating = DecoyScanner::new(decoys, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let places: std::collections::HashSet<usize> = (0..200)
+            .map(|_| floating.send_order().iter().position(Option::is_none).unwrap())
+            .collect();
+        assert!(places.len() > 1);
+    }
+}
@@ -0,0 +1,197 @@
+//! ICMP destination unreachable codes
+//!
+//! A UDP port that sends nothing back can be closed or filtered, and the
+//! ICMP error that came back instead is what tells them apart. Only a port
+//! unreachable (code 3) comes from the target's own stack refusing the
+//! datagram; the other codes are sent by routers and firewalls, so the port
+//! behind them is filtered.
+
+use super::PortState;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::net::Ipv4Addr;
+
+/// ICMP type of destination unreachable messages
+pub const DEST_UNREACHABLE: u8 = 3;
+
+/// Why a destination was unreachable, from the ICMP code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Unreachable {
+    /// Code 0: no route to the network
+    Network,
+    /// Code 1: no route to the host
+    Host,
+    /// Code 2: the host does not speak the protocol
+    Protocol,
+    /// Code 3: nothing listens on the port
+    Port,
+    /// Code 9: the network is administratively prohibited
+    NetworkProhibited,
+    /// Code 10: the host is administratively prohibited
+    HostProhibited,
+    /// Code 13: communication is administratively prohibited by a filter
+    AdminProhibited,
+    /// Any other code
+    Other(u8),
+}
+
+impl Unreachable {
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0 => Unreachable::Network,
+            1 => Unreachable::Host,
+            2 => Unreachable::Protocol,
+            3 => Unreachable::Port,
+            9 => Unreachable::NetworkProhibited,
+            10 => Unreachable::HostProhibited,
+            13 => Unreachable::AdminProhibited,
+            other => Unreachable::Other(other),
+        }
+    }
+
+    pub fn code(self) -> u8 {
+        match self {
+            Unreachable::Network => 0,
+            Unreachable::Host => 1,
+            Unreachable::Protocol => 2,
+            Unreachable::Port => 3,
+            Unreachable::NetworkProhibited => 9,
+            Unreachable::HostProhibited => 10,
+            Unreachable::AdminProhibited => 13,
+            Unreachable::Other(code) => code,
+        }
+    }
+
+    /// The ICMP error a socket error stands for, if any
+    ///
+    /// The kernel reports both code 1 and code 13 as "host unreachable", so
+    /// this is coarser than a raw ICMP listener; the port state it gives is
+    /// the same either way.
+    pub fn from_io_error(error: &io::Error) -> Option<Self> {
+        match error.kind() {
+            io::ErrorKind::ConnectionRefused => Some(Unreachable::Port),
+            io::ErrorKind::HostUnreachable => Some(Unreachable::Host),
+            io::ErrorKind::NetworkUnreachable => Some(Unreachable::Network),
+            _ => None,
+        }
+    }
+
+    /// Short name, as shown in the reason of a port
+    pub fn reason(self) -> &'static str {
+        match self {
+            Unreachable::Network => "net-unreach",
+            Unreachable::Host => "host-unreach",
+            Unreachable::Protocol => "proto-unreach",
+            Unreachable::Port => "port-unreach",
+            Unreachable::NetworkProhibited => "net-prohibited",
+            Unreachable::HostProhibited => "host-prohibited",
+            Unreachable::AdminProhibited => "admin-prohibited",
+            Unreachable::Other(_) => "unreach",
+        }
+    }
+
+    /// State of the probed port: closed on a port unreachable, filtered on
+    /// anything else
+    pub fn port_state(self) -> PortState {
+        match self {
+            Unreachable::Port => PortState::Closed,
+            _ => PortState::Filtered,
+        }
+    }
+}
+
+impl fmt::Display for Unreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unreachable::Other(code) => write!(f, "unreach (code {})", code),
+            _ => write!(f, "{}", self.reason()),
+        }
+    }
+}
+
+/// A destination unreachable message and the probe it refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnreachableReply {
+    pub kind: Unreachable,
+    /// Router or host that sent the message
+    pub sender: Ipv4Addr,
+    /// Destination of the probe that bounced
+    pub destination: Ipv4Addr,
+    /// IP protocol of the probe that bounced
+    pub protocol: u8,
+    /// Destination port of the probe that bounced
+    pub port: u16,
+}
+
+impl UnreachableReply {
+    /// Parse an IPv4 packet carrying an ICMP destination unreachable, as
+    /// read from a raw ICMP socket
+    pub fn parse(packet: &[u8]) -> Option<Self> {
+        let outer_len = usize::from(packet.first()? & 0x0f) * 4;
+        let icmp = packet.get(outer_len..)?;
+        if *icmp.first()? != DEST_UNREACHABLE {
+            return None;
+        }
+        let kind = Unreachable::from_code(*icmp.get(1)?);
+        // The quoted probe: its IP header and the first 8 bytes after it
+        let quoted = icmp.get(8..)?;
+        let quoted_len = usize::from(quoted.first()? & 0x0f) * 4;
+        let ports = quoted.get(quoted_len..quoted_len + 4)?;
+        let address = |at: usize| -> Option<Ipv4Addr> {
+            let bytes: [u8; 4] = packet.get(at..at + 4)?.try_into().ok()?;
+            Some(Ipv4Addr::from(bytes))
+        };
+        let quoted_at = outer_len + 8;
+        Some(Self {
+            kind,
+            sender: address(12)?,
+            destination: address(quoted_at + 16)?,
+            protocol: *quoted.get(9)?,
+            port: u16::from_be_bytes([ports[2], ports[3]]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable(code: u8) -> Vec<u8> {
+        let mut packet = vec![0u8; 56];
+        packet[0] = 0x45;
+        packet[12..16].copy_from_slice(&[192, 0, 2, 254]);
+        packet[20] = DEST_UNREACHABLE;
+        packet[21] = code;
+        packet[28] = 0x45;
+        packet[37] = 17;
+        packet[44..48].copy_from_slice(&[198, 51, 100, 7]);
+        packet[48..52].copy_from_slice(&[0x9c, 0x40, 0x00, 0xa1]);
+        packet
+    }
+
+    #[test]
+    fn test_codes_tell_closed_from_filtered() {
+        let reply = UnreachableReply::parse(&unreachable(3)).unwrap();
+        assert_eq!(reply.kind, Unreachable::Port);
+        assert_eq!((reply.sender, reply.destination), (Ipv4Addr::new(192, 0, 2, 254), Ipv4Addr::new(198, 51, 100, 7)));
+        assert_eq!((reply.protocol, reply.port), (17, 161));
+        assert_eq!(reply.kind.port_state(), PortState::Closed);
+
+        let prohibited = UnreachableReply::parse(&unreachable(13)).unwrap().kind;
+        assert_eq!((prohibited, prohibited.reason()), (Unreachable::AdminProhibited, "admin-prohibited"));
+        assert_eq!(prohibited.port_state(), PortState::Filtered);
+        assert_eq!(Unreachable::from_code(prohibited.code()), prohibited);
+        assert_eq!(Unreachable::from_code(5).to_string(), "unreach (code 5)");
+
+        let mut echo_reply = unreachable(0);
+        echo_reply[20] = 0;
+        assert!(UnreachableReply::parse(&echo_reply).is_none());
+        assert!(UnreachableReply::parse(&unreachable(3)[..40]).is_none());
+
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(Unreachable::from_io_error(&refused), Some(Unreachable::Port));
+        assert_eq!(Unreachable::from_io_error(&io::ErrorKind::TimedOut.into()), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! Host uptime from TCP timestamps
+//!
+//! Most TCP stacks fill TSval from a counter that starts near zero at boot
+//! and ticks at a fixed rate; RFC 7323 only bounds it between one tick per
+//! millisecond and one per second. Two SYN/ACKs a known interval apart give
+//! the rate, and the last TSval divided by it gives the time since the
+//! counter started.
+//!
+//! That is a guess, not a measurement. Linux since 4.10 and OpenBSD add a
+//! random offset to the counter. An offset per address pair keeps the rate
+//! measurable but makes the uptime meaningless; an offset per connection, or
+//! a load balancer answering from a different machine each time, matches no
+//! known rate and leaves the uptime unknown.
+
+use super::packet::{PacketParser, TcpPacketBuilder, TcpResponse};
+use super::protocol::NetworkUtils;
+use super::socket::RawSocket;
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Time between the two SYNs of a probe
+pub const SAMPLE_GAP: Duration = Duration::from_secs(1);
+
+/// Timestamp clock rates real stacks use, in Hz
+const KNOWN_RATES: [u32; 6] = [2, 10, 100, 200, 250, 1000];
+
+/// How far a measured rate may stray from a known one, as a fraction of it
+const RATE_TOLERANCE: f64 = 0.1;
+
+/// How often the receive loop checks the non-blocking raw socket
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A TSval and when it arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampSample {
+    pub tsval: u32,
+    pub at: Instant,
+}
+
+/// What a host's TCP timestamps say about its clock
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampAnalysis {
+    pub timestamp_option_present: bool,
+    /// Ticks per second of the timestamp clock
+    pub timestamp_frequency: Option<u32>,
+    /// Time since the timestamp clock started, usually the last boot
+    pub uptime_estimate: Option<Duration>,
+}
+
+impl TimestampAnalysis {
+    /// Estimate the clock rate and uptime from TSvals seen over time
+    ///
+    /// The rate comes from the first and last sample and must be close to
+    /// one in common use; otherwise only the option's presence is reported.
+    pub fn from_samples(samples: &[TimestampSample]) -> Self {
+        let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+            return Self::default();
+        };
+        let mut analysis = Self { timestamp_option_present: true, ..Self::default() };
+
+        let elapsed = last.at.saturating_duration_since(first.at).as_secs_f64();
+        // Some stacks answer with TSval 0, which dates nothing
+        if elapsed == 0.0 || last.tsval == 0 {
+            return analysis;
+        }
+        let measured = f64::from(last.tsval.wrapping_sub(first.tsval)) / elapsed;
+        analysis.timestamp_frequency = KNOWN_RATES
+            .iter()
+            .copied()
+            .find(|&hz| (measured - f64::from(hz)).abs() <= f64::from(hz) * RATE_TOLERANCE);
+        analysis.uptime_estimate = analysis
+            .timestamp_frequency
+            .map(|hz| Duration::from_secs_f64(f64::from(last.tsval) / f64::from(hz)));
+        analysis
+    }
+}
+
+/// Sample `target`'s timestamp clock through `port`, which must be open
+///
+/// Sends two SYNs carrying a timestamp option, [`SAMPLE_GAP`] apart, and
+/// resets each half-open connection. Needs a raw socket.
+pub async fn probe(target: Ipv4Addr, port: u16, timeout: Duration) -> crate::Result<TimestampAnalysis> {
+    let (_, source) = super::route::egress(target)
+        .ok_or_else(|| ScanError::NetworkError(format!("no route to {}", target)))?;
+    let socket = RawSocket::new_tcp()?;
+
+    let mut samples = Vec::with_capacity(2);
+    for attempt in 0..2 {
+        if attempt > 0 {
+            tokio::time::sleep(SAMPLE_GAP).await;
+        }
+        let Some((reply, at)) = syn_ack(&socket, source, target, port, timeout).await? else {
+            break;
+        };
+        match reply.timestamp {
+            Some((tsval, _)) => samples.push(TimestampSample { tsval, at }),
+            None => break,
+        }
+    }
+    Ok(TimestampAnalysis::from_samples(&samples))
+}
+
+/// Send one SYN and wait for the SYN/ACK, resetting the connection it opens
+async fn syn_ack(
+    socket: &RawSocket,
+    source: Ipv4Addr,
+    target: Ipv4Addr,
+    port: u16,
+    timeout: Duration,
+) -> crate::Result<Option<(TcpResponse, Instant)>> {
+    let local_port = NetworkUtils::random_source_port();
+    let dest = SocketAddr::new(IpAddr::V4(target), port);
+    let syn = TcpPacketBuilder::new(source, target, local_port, port)
+        .syn()
+        .timestamp(rand::random())
+        .build();
+    // The socket is not IP_HDRINCL, so the kernel writes the IP header
+    socket.send_to(&syn[phobos_packet::ipv4::HEADER_LEN..], dest)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 1500];
+    while Instant::now() < deadline {
+        let Ok((size, _)) = socket.recv_from(&mut buf) else {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            continue;
+        };
+        let at = Instant::now();
+        let Some(reply) = PacketParser::parse_tcp_response(&buf[..size]) else {
+            continue;
+        };
+        if reply.source_ip != target || reply.source_port != port || reply.dest_port != local_port {
+            continue;
+        }
+        if reply.is_rst() {
+            return Ok(None);
+        }
+        if reply.is_syn_ack() {
+            let rst = TcpPacketBuilder::new(source, target, local_port, port)
+                .rst()
+                .seq_num(reply.ack_num)
+                .build();
+            let _ = socket.send_to(&rst[phobos_packet::ipv4::HEADER_LEN..], dest);
+            return Ok(Some((reply, at)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uptime_from_samples() {
+        let start = Instant::now();
+        let sample = |tsval, millis| TimestampSample { tsval, at: start + Duration::from_millis(millis) };
+
+        // A 1000 Hz clock three days after boot, seen with a little jitter
+        let booted = 3 * 86_400 * 1000;
+        let analysis = TimestampAnalysis::from_samples(&[sample(booted, 0), sample(booted + 1003, 1000)]);
+        assert_eq!(analysis.timestamp_frequency, Some(1000));
+        assert_eq!(analysis.uptime_estimate.map(|d| d.as_secs()), Some(3 * 86_400 + 1));
+
+        // A counter that wrapped between the samples
+        let wrapped = TimestampAnalysis::from_samples(&[sample(u32::MAX - 49, 0), sample(50, 1000)]);
+        assert_eq!(wrapped.timestamp_frequency, Some(100));
+
+        // Replies from two machines behind one address match no clock rate
+        let balanced = TimestampAnalysis::from_samples(&[sample(900_000, 0), sample(40_000_000, 1000)]);
+        assert!(balanced.timestamp_option_present);
+        assert_eq!((balanced.timestamp_frequency, balanced.uptime_estimate), (None, None));
+
+        assert_eq!(TimestampAnalysis::from_samples(&[]), TimestampAnalysis::default());
+        assert_eq!(TimestampAnalysis::from_samples(&[sample(0, 0), sample(0, 1000)]).uptime_estimate, None);
+    }
+}
@@ -0,0 +1,351 @@
+//! 802.1Q tagged probing
+//!
+//! The kernel's IP stack cannot tag frames for a VLAN the host has no
+//! interface in, so tagged scans bypass it: a [`VlanLink`] writes whole
+//! Ethernet frames with an 802.1Q header to the interface, resolves MAC
+//! addresses with ARP inside the VLAN, and reads replies back off the wire.
+//! This lets a single interface on a trunk port scan any VLAN carried on it.
+//!
+//! Many NICs strip the tag from received frames before packet sockets see
+//! them. Untagged frames are therefore accepted too; replies are still
+//! matched on addresses and ports.
+
+use super::packet::TcpPacketBuilder;
+use super::PortState;
+use crate::ScanError;
+use pnet::datalink::{self, Channel, DataLinkSender, NetworkInterface};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const TPID_8021Q: u16 = 0x8100;
+const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// Local ports used for tagged probes; the host stack never sees the replies
+const SOURCE_PORTS: std::ops::Range<u16> = 40000..60000;
+const ARP_ATTEMPTS: u32 = 3;
+
+/// Parse a VLAN ID (1-4094)
+pub fn parse_vlan_id(input: &str) -> crate::Result<u16> {
+    match input.trim().parse::<u16>() {
+        Ok(id) if (1..=4094).contains(&id) => Ok(id),
+        _ => Err(ScanError::ParseError(format!("invalid VLAN ID '{}': expected 1-4094", input))),
+    }
+}
+
+/// Ethernet frame carrying `payload` tagged with `vlan` (priority 0)
+pub fn encapsulate(vlan: u16, src: [u8; 6], dst: [u8; 6], ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(18 + payload.len());
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&TPID_8021Q.to_be_bytes());
+    frame.extend_from_slice(&(vlan & 0x0fff).to_be_bytes());
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(payload);
+    // Pad to the 64-byte minimum frame (without FCS)
+    if frame.len() < 60 {
+        frame.resize(60, 0);
+    }
+    frame
+}
+
+/// Ethertype and payload of a frame on `vlan`, or of an untagged frame
+///
+/// Frames tagged for a different VLAN are rejected.
+pub fn decapsulate(frame: &[u8], vlan: u16) -> Option<(u16, &[u8])> {
+    let ethertype = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+    if ethertype != TPID_8021Q {
+        return Some((ethertype, &frame[14..]));
+    }
+    let tci = u16::from_be_bytes([*frame.get(14)?, *frame.get(15)?]);
+    if tci & 0x0fff != vlan {
+        return None;
+    }
+    let inner = u16::from_be_bytes([*frame.get(16)?, *frame.get(17)?]);
+    Some((inner, &frame[18..]))
+}
+
+/// ARP who-has request for `target`
+fn arp_request(src_mac: [u8; 6], src_ip: Ipv4Addr, target: Ipv4Addr) -> Vec<u8> {
+    let mut arp = Vec::with_capacity(28);
+    arp.extend_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01]);
+    arp.extend_from_slice(&src_mac);
+    arp.extend_from_slice(&src_ip.octets());
+    arp.extend_from_slice(&[0; 6]);
+    arp.extend_from_slice(&target.octets());
+    arp
+}
+
+/// Sender IP and MAC of an ARP reply
+fn parse_arp_reply(arp: &[u8]) -> Option<(Ipv4Addr, [u8; 6])> {
+    if arp.len() < 28 || arp[6..8] != [0x00, 0x02] {
+        return None;
+    }
+    let mac: [u8; 6] = arp[8..14].try_into().ok()?;
+    let ip = Ipv4Addr::new(arp[14], arp[15], arp[16], arp[17]);
+    Some((ip, mac))
+}
+
+/// Source address, source port, destination port and flags of a TCP segment
+fn parse_tcp_reply(ip: &[u8]) -> Option<(Ipv4Addr, u16, u16, u8)> {
+    if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != 6 {
+        return None;
+    }
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let tcp = ip.get(header_len..header_len + 14)?;
+    let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+    Some((
+        src,
+        u16::from_be_bytes([tcp[0], tcp[1]]),
+        u16::from_be_bytes([tcp[2], tcp[3]]),
+        tcp[13],
+    ))
+}
+
+/// Probes and ARP lookups waiting for the receiver thread
+#[derive(Default)]
+struct Waiters {
+    /// (target, target port, our port) -> TCP flags of the reply
+    probes: HashMap<(Ipv4Addr, u16, u16), oneshot::Sender<u8>>,
+    arp: HashMap<Ipv4Addr, Vec<oneshot::Sender<[u8; 6]>>>,
+}
+
+/// Tagged link on one interface
+pub struct VlanLink {
+    vlan: u16,
+    interface: String,
+    mac: [u8; 6],
+    source: Ipv4Addr,
+    tx: Mutex<Box<dyn DataLinkSender>>,
+    waiters: Arc<Mutex<Waiters>>,
+    neighbours: Mutex<HashMap<Ipv4Addr, [u8; 6]>>,
+    next_port: AtomicU16,
+    mss: Option<u16>,
+    closed: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for VlanLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VlanLink")
+            .field("vlan", &self.vlan)
+            .field("interface", &self.interface)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+fn find_interface(name: Option<&str>) -> crate::Result<NetworkInterface> {
+    let interfaces = datalink::interfaces();
+    let found = match name {
+        Some(name) => interfaces.into_iter().find(|i| i.name == name),
+        None => interfaces
+            .into_iter()
+            .find(|i| i.is_up() && !i.is_loopback() && i.mac.is_some() && i.ips.iter().any(|ip| ip.is_ipv4())),
+    };
+    found.ok_or_else(|| match name {
+        Some(name) => ScanError::NetworkError(format!("interface '{}' not found", name)),
+        None => ScanError::NetworkError("no usable interface for VLAN scanning; pass --interface".to_string()),
+    })
+}
+
+impl VlanLink {
+    /// Open a tagged link for `vlan` on `interface` (default: first Ethernet
+    /// interface with an IPv4 address), sending from `source` (default: that address)
+    pub fn open(interface: Option<&str>, vlan: u16, source: Option<Ipv4Addr>) -> crate::Result<Self> {
+        let iface = find_interface(interface)?;
+        let mac = iface
+            .mac
+            .map(|m| m.octets())
+            .ok_or_else(|| ScanError::NetworkError(format!("interface '{}' has no MAC address", iface.name)))?;
+        let source = source
+            .or_else(|| {
+                iface.ips.iter().find_map(|ip| match ip.ip() {
+                    IpAddr::V4(v4) => Some(v4),
+                    IpAddr::V6(_) => None,
+                })
+            })
+            .ok_or_else(|| {
+                ScanError::ConfigError(format!("interface '{}' has no IPv4 address; pass --vlan-source", iface.name))
+            })?;
+
+        let config = datalink::Config { read_timeout: Some(Duration::from_millis(100)), ..Default::default() };
+        let (tx, mut rx) = match datalink::channel(&iface, config) {
+            Ok(Channel::Ethernet(tx, rx)) => (tx, rx),
+            Ok(_) => return Err(ScanError::NetworkError(format!("unsupported link type on '{}'", iface.name))),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(ScanError::PermissionError("VLAN scanning needs CAP_NET_RAW".to_string()))
+            }
+            Err(e) => return Err(ScanError::NetworkError(format!("cannot open '{}': {}", iface.name, e))),
+        };
+
+        let waiters = Arc::new(Mutex::new(Waiters::default()));
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let waiters = Arc::clone(&waiters);
+            let closed = Arc::clone(&closed);
+            std::thread::Builder::new()
+                .name(format!("vlan{}-rx", vlan))
+                .spawn(move || {
+                    while !closed.load(Ordering::Relaxed) {
+                        // Read timeouts surface as errors; just poll the flag again
+                        let Ok(frame) = rx.next() else { continue };
+                        let Some((ethertype, payload)) = decapsulate(frame, vlan) else { continue };
+                        let mut waiters = waiters.lock().unwrap_or_else(|e| e.into_inner());
+                        match ethertype {
+                            ETHERTYPE_ARP => {
+                                if let Some((ip, mac)) = parse_arp_reply(payload) {
+                                    for waiter in waiters.arp.remove(&ip).unwrap_or_default() {
+                                        let _ = waiter.send(mac);
+                                    }
+                                }
+                            }
+                            ETHERTYPE_IPV4 => {
+                                if let Some((src, sport, dport, flags)) = parse_tcp_reply(payload) {
+                                    if let Some(waiter) = waiters.probes.remove(&(src, sport, dport)) {
+                                        let _ = waiter.send(flags);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                })
+                .map_err(ScanError::IoError)?;
+        }
+
+        log::info!("VLAN {} link on {} from {}", vlan, iface.name, source);
+        Ok(Self {
+            vlan,
+            interface: iface.name,
+            mac,
+            source,
+            tx: Mutex::new(tx),
+            waiters,
+            neighbours: Mutex::new(HashMap::new()),
+            next_port: AtomicU16::new(SOURCE_PORTS.start),
+            mss: None,
+            closed,
+        })
+    }
+
+    /// Advertise `mss` in probes
+    pub fn with_mss(mut self, mss: u16) -> Self {
+        self.mss = Some(mss);
+        self
+    }
+
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    pub fn vlan(&self) -> u16 {
+        self.vlan
+    }
+
+    pub fn source(&self) -> Ipv4Addr {
+        self.source
+    }
+
+    fn send(&self, dst: [u8; 6], ethertype: u16, payload: &[u8]) -> crate::Result<()> {
+        let frame = encapsulate(self.vlan, self.mac, dst, ethertype, payload);
+        let mut tx = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+        match tx.send_to(&frame, None) {
+            Some(Ok(())) => Ok(()),
+            Some(Err(e)) => Err(ScanError::NetworkError(format!("VLAN send failed: {}", e))),
+            None => Err(ScanError::NetworkError("VLAN send failed: no buffer space".to_string())),
+        }
+    }
+
+    /// MAC address of `ip` inside the VLAN, via ARP
+    pub async fn resolve(&self, ip: Ipv4Addr, timeout: Duration) -> crate::Result<[u8; 6]> {
+        if let Some(mac) = self.neighbours.lock().unwrap_or_else(|e| e.into_inner()).get(&ip) {
+            return Ok(*mac);
+        }
+        for _ in 0..ARP_ATTEMPTS {
+            let (waiter, reply) = oneshot::channel();
+            self.waiters.lock().unwrap_or_else(|e| e.into_inner()).arp.entry(ip).or_default().push(waiter);
+            self.send(BROADCAST, ETHERTYPE_ARP, &arp_request(self.mac, self.source, ip))?;
+            if let Ok(Ok(mac)) = tokio::time::timeout(timeout, reply).await {
+                self.neighbours.lock().unwrap_or_else(|e| e.into_inner()).insert(ip, mac);
+                return Ok(mac);
+            }
+        }
+        Err(ScanError::TimeoutError(format!(
+            "no ARP reply from {} on VLAN {}; the target must be on the VLAN's segment",
+            ip, self.vlan
+        )))
+    }
+
+    /// Send a tagged SYN to `target:port` and classify the reply
+    pub async fn probe_syn(&self, target: Ipv4Addr, port: u16, timeout: Duration) -> crate::Result<PortState> {
+        let dst = self.resolve(target, timeout).await?;
+        let span = SOURCE_PORTS.end - SOURCE_PORTS.start;
+        let local_port = SOURCE_PORTS.start + self.next_port.fetch_add(1, Ordering::Relaxed) % span;
+        let key = (target, port, local_port);
+
+        let (waiter, reply) = oneshot::channel();
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).probes.insert(key, waiter);
+        let mut builder = TcpPacketBuilder::new(self.source, target, local_port, port)
+            .syn()
+            .seq_num(rand::random());
+        if let Some(mss) = self.mss {
+            builder = builder.mss(mss);
+        }
+        let packet = builder.build();
+        if let Err(e) = self.send(dst, ETHERTYPE_IPV4, &packet) {
+            self.waiters.lock().unwrap_or_else(|e| e.into_inner()).probes.remove(&key);
+            return Err(e);
+        }
+
+        let state = match tokio::time::timeout(timeout, reply).await {
+            Ok(Ok(flags)) if flags & 0x12 == 0x12 => PortState::Open,
+            Ok(Ok(flags)) if flags & 0x04 != 0 => PortState::Closed,
+            _ => PortState::Filtered,
+        };
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner()).probes.remove(&key);
+        Ok(state)
+    }
+}
+
+impl Drop for VlanLink {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let src = [0x02, 0, 0, 0, 0, 1];
+        let frame = encapsulate(120, src, BROADCAST, ETHERTYPE_ARP, &arp_request(src, Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(frame.len(), 60);
+        assert_eq!(&frame[12..18], &[0x81, 0x00, 0x00, 0x78, 0x08, 0x06]);
+
+        let (ethertype, payload) = decapsulate(&frame, 120).unwrap();
+        assert_eq!(ethertype, ETHERTYPE_ARP);
+        assert_eq!(&payload[24..28], &[10, 0, 0, 2]);
+        assert!(decapsulate(&frame, 121).is_none());
+
+        // Tag stripped by the NIC
+        let mut untagged = frame[..12].to_vec();
+        untagged.extend_from_slice(&frame[16..]);
+        assert_eq!(decapsulate(&untagged, 120).unwrap().0, ETHERTYPE_ARP);
+
+        let mut reply = arp_request(src, Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 1));
+        reply[7] = 2;
+        assert_eq!(parse_arp_reply(&reply), Some((Ipv4Addr::new(10, 0, 0, 2), src)));
+
+        assert_eq!(parse_vlan_id("120").unwrap(), 120);
+        assert!(parse_vlan_id("0").is_err());
+        assert!(parse_vlan_id("4095").is_err());
+    }
+}
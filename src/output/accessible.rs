@@ -0,0 +1,146 @@
+//! Screen-reader-friendly output for `--accessible`
+//!
+//! Screen readers spell out box-drawing characters and emoji, and re-read a
+//! progress bar every time it is redrawn. In accessible mode ports are a
+//! plain ASCII table with a label on every column, and progress and phase
+//! changes are whole sentences printed once each.
+
+use crate::network::PortResult;
+use crate::scanner::{ProgressSnapshot, ScanPhase};
+use std::time::Duration;
+
+const COLUMNS: [&str; 4] = ["Port", "Protocol", "State", "Service"];
+
+/// Ports as a table with one labelled column per field, ending in a count
+pub fn port_table(ports: &[&PortResult]) -> String {
+    if ports.is_empty() {
+        return "No ports to list.\n".to_string();
+    }
+    let rows: Vec<[String; 4]> = ports
+        .iter()
+        .map(|p| {
+            [
+                p.port.to_string(),
+                p.protocol.as_str().to_string(),
+                p.state.to_string(),
+                p.service.clone().unwrap_or_else(|| "unknown".to_string()),
+            ]
+        })
+        .collect();
+    let mut widths = COLUMNS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(COLUMNS.map(str::to_string)).chain(rows) {
+        let cells: Vec<String> = row.iter().zip(widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    let count = ports.len();
+    table.push_str(&format!("{} {} listed.\n", count, if count == 1 { "port" } else { "ports" }));
+    table
+}
+
+/// "Starting the port scan."
+pub fn phase_started(phase: ScanPhase) -> String {
+    format!("Starting {}.", phase.description())
+}
+
+/// "Finished the port scan in 2.5 seconds."
+pub fn phase_finished(phase: ScanPhase, took: Duration) -> String {
+    format!("Finished {} in {}.", phase.description(), spoken_duration(took))
+}
+
+/// A progress report to print on its own line instead of redrawing a bar
+pub fn progress_sentence(progress: &ProgressSnapshot) -> String {
+    let left = match progress.eta() {
+        Some(_) if progress.completed >= progress.total => "nothing left".to_string(),
+        Some(eta) => format!("about {} left", spoken_duration(eta)),
+        None => "time left unknown".to_string(),
+    };
+    format!(
+        "Progress: {} of {} ports done, {:.0} percent; {}.",
+        progress.completed,
+        progress.total,
+        progress.percentage(),
+        left
+    )
+}
+
+fn spoken_duration(duration: Duration) -> String {
+    let count = |n: u128, unit: &str| format!("{} {}{}", n, unit, if n == 1 { "" } else { "s" });
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return count(duration.as_millis(), "millisecond");
+    }
+    if secs < 60 {
+        return format!("{:.1} seconds", duration.as_secs_f64());
+    }
+    [(secs / 3600, "hour"), (secs / 60 % 60, "minute"), (secs % 60, "second")]
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|&(n, unit)| count(n as u128, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{PortState, Protocol};
+    use std::path::Path;
+
+    /// Compare with `snapshots/<name>.txt`; `UPDATE_SNAPSHOTS=1` rewrites it
+    fn assert_snapshot(name: &str, actual: &str) {
+        assert!(actual.is_ascii() && !actual.contains('\r'), "{} is not plain ASCII:\n{}", name, actual);
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/output/snapshots").join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, actual).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(actual, expected, "{} differs from its snapshot; rerun with UPDATE_SNAPSHOTS=1 to accept", name);
+    }
+
+    #[test]
+    fn test_port_table_snapshot() {
+        let mut ssh = PortResult::new(22, Protocol::Tcp, PortState::Open);
+        ssh.service = Some("ssh".to_string());
+        let mut https = PortResult::new(443, Protocol::Tcp, PortState::Open);
+        https.service = Some("https".to_string());
+        let dns = PortResult::new(53, Protocol::Udp, PortState::OpenFiltered);
+        let rdp = PortResult::new(3389, Protocol::Tcp, PortState::Filtered);
+        assert_snapshot("accessible_port_table", &port_table(&[&ssh, &https, &dns, &rdp]));
+        assert_snapshot("accessible_port_table_single", &port_table(&[&ssh]));
+        assert_snapshot("accessible_port_table_empty", &port_table(&[]));
+    }
+
+    #[test]
+    fn test_phase_sentences_snapshot() {
+        let took = [Duration::from_millis(40), Duration::from_millis(1), Duration::from_millis(2500), Duration::from_secs(61), Duration::from_secs(3725)];
+        let sentences: String = ScanPhase::ALL
+            .iter()
+            .zip(took)
+            .flat_map(|(&phase, took)| [phase_started(phase), phase_finished(phase, took)])
+            .map(|sentence| sentence + "\n")
+            .collect();
+        assert_snapshot("accessible_phases", &sentences);
+    }
+
+    #[test]
+    fn test_progress_sentence_snapshot() {
+        let snapshot = |completed, elapsed| ProgressSnapshot {
+            total: 65535,
+            completed,
+            open: 3,
+            retries: 0,
+            errors: 0,
+            elapsed: Duration::from_secs(elapsed),
+        };
+        let sentences = [snapshot(0, 0), snapshot(16384, 10), snapshot(65535, 40)].iter().map(|s| progress_sentence(s) + "\n").collect::<String>();
+        assert_snapshot("accessible_progress", &sentences);
+    }
+}
@@ -0,0 +1,216 @@
+//! Campaigns: scans grouped under `--campaign NAME`
+//!
+//! An assessment is many scans over weeks. Scans run with `--campaign`
+//! carry its name into the report provenance and the Parquet archive, so
+//! the archive can be listed (`phobos report campaigns`), reported on
+//! (`phobos report heatmap --campaign q3-external`) and compared
+//! (`phobos report diff --campaign q3-external --against q2-external`)
+//! per campaign rather than per run.
+
+use super::parquet::History;
+use crate::ScanError;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+
+/// Scans, hosts and exposure of one campaign
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CampaignSummary {
+    pub name: String,
+    pub scans: usize,
+    pub hosts: usize,
+    /// Distinct host ports seen open in any of its scans
+    pub open_ports: usize,
+    pub first_scan: DateTime<Utc>,
+    pub last_scan: DateTime<Utc>,
+}
+
+/// Campaigns in an archive, oldest first; scans without one are left out
+pub fn summarize(history: &History) -> Vec<CampaignSummary> {
+    let mut campaigns: BTreeMap<&str, CampaignSummary> = BTreeMap::new();
+    let mut hosts: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for scan in &history.hosts {
+        let Some(name) = scan.campaign.as_deref() else { continue };
+        let summary = campaigns.entry(name).or_insert_with(|| CampaignSummary {
+            name: name.to_string(),
+            scans: 0,
+            hosts: 0,
+            open_ports: 0,
+            first_scan: scan.scanned_at,
+            last_scan: scan.scanned_at,
+        });
+        summary.scans += 1;
+        summary.first_scan = summary.first_scan.min(scan.scanned_at);
+        summary.last_scan = summary.last_scan.max(scan.scanned_at);
+        hosts.entry(name).or_default().insert(&scan.host);
+    }
+    for (name, summary) in campaigns.iter_mut() {
+        summary.hosts = hosts[name].len();
+        summary.open_ports = exposures(history, name).len();
+    }
+    let mut campaigns: Vec<CampaignSummary> = campaigns.into_values().collect();
+    campaigns.sort_by_key(|c| c.first_scan);
+    campaigns
+}
+
+pub fn summaries_to_csv(campaigns: &[CampaignSummary]) -> io::Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for campaign in campaigns {
+        writer.serialize(campaign)?;
+    }
+    finish(writer)
+}
+
+/// A port open on a host, with the service last reported on it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Exposure {
+    pub host: String,
+    pub port: u16,
+    pub protocol: String,
+    pub service: Option<String>,
+}
+
+/// What changed from one campaign to the next
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CampaignDiff {
+    pub from: String,
+    pub to: String,
+    /// Hosts only `to` scanned
+    pub new_hosts: Vec<String>,
+    /// Hosts only `from` scanned
+    pub missing_hosts: Vec<String>,
+    /// Open in `to` but not `from`, on hosts both scanned
+    pub opened: Vec<Exposure>,
+    /// Open in `from` but not `to`, on hosts both scanned
+    pub closed: Vec<Exposure>,
+}
+
+impl CampaignDiff {
+    /// Compare campaign `to` with the earlier `from`
+    pub fn between(history: &History, from: &str, to: &str) -> crate::Result<Self> {
+        let hosts = |name: &str| -> crate::Result<BTreeSet<String>> {
+            let hosts: BTreeSet<String> = history
+                .hosts
+                .iter()
+                .filter(|scan| scan.campaign.as_deref() == Some(name))
+                .map(|scan| scan.host.clone())
+                .collect();
+            if hosts.is_empty() {
+                return Err(ScanError::ConfigError(format!("no scans of campaign '{}' in the archive", name)));
+            }
+            Ok(hosts)
+        };
+        let (from_hosts, to_hosts) = (hosts(from)?, hosts(to)?);
+        let (before, after) = (exposures(history, from), exposures(history, to));
+        let changed = |a: &BTreeMap<(String, u16, String), Option<String>>, b: &BTreeMap<(String, u16, String), Option<String>>| {
+            a.iter()
+                .filter(|((host, _, _), _)| from_hosts.contains(host) && to_hosts.contains(host))
+                .filter(|(key, _)| !b.contains_key(*key))
+                .map(|((host, port, protocol), service)| Exposure {
+                    host: host.clone(),
+                    port: *port,
+                    protocol: protocol.clone(),
+                    service: service.clone(),
+                })
+                .collect()
+        };
+        Ok(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            new_hosts: to_hosts.difference(&from_hosts).cloned().collect(),
+            missing_hosts: from_hosts.difference(&to_hosts).cloned().collect(),
+            opened: changed(&after, &before),
+            closed: changed(&before, &after),
+        })
+    }
+
+    /// One `change,host,port,protocol,service` row per difference
+    pub fn to_csv(&self) -> io::Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(["change", "host", "port", "protocol", "service"])?;
+        for host in &self.new_hosts {
+            writer.write_record(["new host", host, "", "", ""])?;
+        }
+        for host in &self.missing_hosts {
+            writer.write_record(["missing host", host, "", "", ""])?;
+        }
+        for (change, exposures) in [("opened", &self.opened), ("closed", &self.closed)] {
+            for e in exposures {
+                writer.write_record([change, &e.host, &e.port.to_string(), &e.protocol, e.service.as_deref().unwrap_or("")])?;
+            }
+        }
+        finish(writer)
+    }
+}
+
+/// Open ports of a campaign keyed by host, port and protocol, with the
+/// service of the latest scan that named one
+fn exposures(history: &History, campaign: &str) -> BTreeMap<(String, u16, String), Option<String>> {
+    let mut open: Vec<_> = history
+        .ports
+        .iter()
+        .filter(|o| o.campaign.as_deref() == Some(campaign) && o.is_open())
+        .collect();
+    open.sort_by_key(|o| o.scanned_at);
+    let mut exposures = BTreeMap::new();
+    for o in open {
+        let service = exposures.entry((o.host.clone(), o.port, o.protocol.clone())).or_insert(None);
+        if o.service.is_some() {
+            *service = o.service.clone();
+        }
+    }
+    exposures
+}
+
+fn finish(writer: csv::Writer<Vec<u8>>) -> io::Result<String> {
+    let bytes = writer.into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::{PortResult, PortState, Protocol};
+    use crate::output::exports::ExportedHost;
+    use crate::output::parquet::export;
+    use crate::output::provenance::Provenance;
+    use std::time::Duration;
+
+    #[test]
+    fn test_campaign_summary_and_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan = |campaign: Option<&str>, host: &str, open: &[u16]| {
+            let results: Vec<PortResult> = [22, 80, 3389]
+                .iter()
+                .map(|&port| PortResult::new(port, Protocol::Tcp, if open.contains(&port) { PortState::Open } else { PortState::Closed }))
+                .collect();
+            let config = ScanConfig { campaign: campaign.map(str::to_string), ..Default::default() };
+            let scanned = ExportedHost { address: host.parse().unwrap(), port_results: results, duration: Duration::from_secs(1) };
+            export(dir.path(), &[scanned], &Provenance::collect(&config)).unwrap();
+        };
+        scan(Some("q2-external"), "192.0.2.10", &[22, 3389]);
+        scan(Some("q2-external"), "192.0.2.11", &[80]);
+        scan(Some("q3-external"), "192.0.2.10", &[22, 80]);
+        scan(Some("q3-external"), "192.0.2.12", &[443]);
+        scan(None, "192.0.2.10", &[22]);
+
+        let history = History::load(dir.path(), DateTime::<Utc>::MIN_UTC, None).unwrap();
+        let campaigns = summarize(&history);
+        assert_eq!(campaigns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["q2-external", "q3-external"]);
+        assert_eq!((campaigns[0].scans, campaigns[0].hosts, campaigns[0].open_ports), (2, 2, 3));
+        assert!(summaries_to_csv(&campaigns).unwrap().starts_with("name,scans,hosts,open_ports,first_scan,last_scan\n"));
+
+        let diff = CampaignDiff::between(&history, "q2-external", "q3-external").unwrap();
+        assert_eq!(diff.new_hosts, ["192.0.2.12"]);
+        assert_eq!(diff.missing_hosts, ["192.0.2.11"]);
+        assert_eq!(diff.opened.iter().map(|e| e.port).collect::<Vec<_>>(), [80]);
+        assert_eq!(diff.closed.iter().map(|e| e.port).collect::<Vec<_>>(), [3389]);
+        assert!(diff.to_csv().unwrap().contains("closed,192.0.2.10,3389,tcp,\n"));
+
+        let q3 = History::load(dir.path(), DateTime::<Utc>::MIN_UTC, Some("q3-external")).unwrap();
+        assert_eq!(q3.hosts.len(), 2);
+        assert!(CampaignDiff::between(&history, "q2-external", "q4").unwrap_err().to_string().contains("'q4'"));
+    }
+}
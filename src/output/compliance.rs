@@ -0,0 +1,410 @@
+//! Firewall rule verification (`--access-matrix`)
+//!
+//! Network teams keep the intended access between zones as a matrix: from
+//! this source zone, these destinations may or may not be reached on these
+//! ports. [`AccessMatrix`] reads it from a CSV file and checks it against
+//! what a scan from one of the zones observed:
+//!
+//! ```text
+//! rule,source,destination,ports,action
+//! FW-101,corp,10.0.1.0/24,"22,443",allow
+//! FW-102,corp,10.0.1.0/24,T:3306,deny
+//! FW-103,corp,*,all,deny
+//! ```
+//!
+//! Destinations are addresses, ranges and CIDR blocks as `--exclude-ips` takes
+//! them, host names as they were given as targets, or `*`; ports use the
+//! `-p` language, unprefixed items following the scan's protocol. The
+//! `rule` column is optional. Rows are matched in order, as firewall rules
+//! are: each scanned port is checked against the first row of the zone that
+//! covers it.
+//!
+//! An allowed port is verified when the probe got an answer, open or closed,
+//! and violated when it was filtered. A denied port is violated only when it
+//! is open, as rejecting firewalls answer with a reset. Ports that were not
+//! scanned, and UDP ports that never answered, leave their rule untested.
+
+use crate::network::{PortResult, PortState, Protocol};
+use crate::utils::address_exclusions::AddressExclusions;
+use crate::utils::port_spec::PortSpec;
+use crate::ScanError;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// What a rule says should happen to traffic it covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Action::Allow => "allow",
+            Action::Deny => "deny",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Destination {
+    Any,
+    Addresses(AddressExclusions),
+    Name(String),
+}
+
+impl Destination {
+    fn parse(destination: &str) -> Result<Self, String> {
+        if matches!(destination, "*" | "any") {
+            return Ok(Destination::Any);
+        }
+        match AddressExclusions::from_str(destination) {
+            Ok(addresses) => Ok(Destination::Addresses(addresses)),
+            // Host names have a letter and no address punctuation in them
+            Err(_) if destination.chars().any(|c| c.is_ascii_alphabetic())
+                && destination.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.') =>
+            {
+                Ok(Destination::Name(destination.to_ascii_lowercase()))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn covers(&self, host: &str) -> bool {
+        match self {
+            Destination::Any => true,
+            Destination::Addresses(addresses) => host.parse::<IpAddr>().is_ok_and(|ip| addresses.is_excluded(ip)),
+            Destination::Name(name) => host.eq_ignore_ascii_case(name),
+        }
+    }
+}
+
+/// One row of the matrix
+#[derive(Debug, Clone)]
+pub struct AccessRule {
+    /// The `rule` column, or the row's line number
+    pub id: String,
+    pub source: String,
+    pub destination: String,
+    pub ports: PortSpec,
+    pub action: Action,
+    covers: Destination,
+}
+
+/// Expected access between zones, from a CSV with `source`, `destination`,
+/// `ports` and `action` columns and an optional `rule` column
+#[derive(Debug, Clone)]
+pub struct AccessMatrix {
+    rules: Vec<AccessRule>,
+}
+
+impl AccessMatrix {
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| ScanError::ConfigError(format!("cannot read access matrix {}: {}", path.display(), e)))?;
+        Self::from_reader(file).map_err(|e| ScanError::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, String> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).comment(Some(b'#')).from_reader(reader);
+        let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+        let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+        let required = |name: &str| column(name).ok_or_else(|| format!("no '{}' column", name));
+        let (source, destination, ports, action) =
+            (required("source")?, required("destination")?, required("ports")?, required("action")?);
+        let id = column("rule");
+
+        let mut rules = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| e.to_string())?;
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |index: usize| record.get(index).unwrap_or("");
+            let at = |reason: String| format!("line {}: {}", line, reason);
+            let rule_action = match field(action).to_ascii_lowercase().as_str() {
+                "allow" | "permit" | "accept" => Action::Allow,
+                "deny" | "drop" | "reject" | "block" => Action::Deny,
+                other => return Err(at(format!("action `{}` is neither allow nor deny", other))),
+            };
+            if field(source).is_empty() {
+                return Err(at("no source zone".to_string()));
+            }
+            rules.push(AccessRule {
+                id: id.map(field).filter(|id| !id.is_empty()).map_or_else(|| format!("line {}", line), str::to_string),
+                source: field(source).to_string(),
+                destination: field(destination).to_string(),
+                ports: PortSpec::parse(field(ports)).map_err(|e| at(e.to_string()))?,
+                action: rule_action,
+                covers: Destination::parse(field(destination)).map_err(|e| at(format!("destination: {}", e)))?,
+            });
+        }
+        if rules.is_empty() {
+            return Err("no rules".to_string());
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn rules(&self) -> &[AccessRule] {
+        &self.rules
+    }
+
+    /// Source zones named in the matrix, in order of appearance
+    pub fn zones(&self) -> Vec<&str> {
+        let mut zones: Vec<&str> = Vec::new();
+        for rule in &self.rules {
+            if !zones.contains(&rule.source.as_str()) {
+                zones.push(&rule.source);
+            }
+        }
+        zones
+    }
+
+    /// The zone a scan is checked as coming from: `zone` if the matrix has
+    /// rules for it, or the only zone it names when none is given
+    pub fn zone<'a>(&'a self, zone: Option<&'a str>) -> Result<&'a str, String> {
+        let zones = self.zones();
+        match zone {
+            Some(zone) if zones.contains(&zone) => Ok(zone),
+            Some(zone) => Err(format!("the access matrix has no rules from zone `{}` (it has {})", zone, zones.join(", "))),
+            None if zones.len() == 1 => Ok(zones[0]),
+            None => Err(format!("the access matrix covers several zones ({}); name the one scanned from with --source-zone", zones.join(", "))),
+        }
+    }
+
+    /// Check the rules from `zone` against the port results of each scanned
+    /// host; unprefixed rule ports are `protocol` ports
+    pub fn verify(&self, zone: &str, scanned: &[(String, Vec<PortResult>)], protocol: Protocol) -> ComplianceReport {
+        let rules: Vec<&AccessRule> = self.rules.iter().filter(|rule| rule.source == zone).collect();
+        let mut reports: Vec<RuleReport> = rules.iter().map(|rule| RuleReport::new(rule)).collect();
+        let mut claimed = HashSet::new();
+        for (rule, report) in rules.iter().zip(&mut reports) {
+            let ports = [Protocol::Tcp, Protocol::Udp].map(|p| (p, rule.ports.ports_defaulting_to(p, protocol)));
+            for (host, results) in scanned.iter().filter(|(host, _)| rule.covers.covers(host)) {
+                for (port_protocol, port) in ports.iter().flat_map(|(p, ports)| ports.iter().map(move |port| (*p, *port))) {
+                    // An earlier row already decided this port
+                    if !claimed.insert((host.as_str(), port_protocol.as_str(), port)) {
+                        continue;
+                    }
+                    let observed = results.iter().find(|r| r.port == port && r.protocol == port_protocol).map(|r| r.state);
+                    report.record(Check { host: host.clone(), port, protocol: port_protocol, observed }, rule.action);
+                }
+            }
+        }
+        ComplianceReport { zone: zone.to_string(), rules: reports }
+    }
+}
+
+/// How a rule, or one port it covers, held up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Verified,
+    Violated,
+    Untested,
+}
+
+impl Verdict {
+    fn of(action: Action, observed: Option<PortState>) -> Self {
+        match (action, observed) {
+            (_, None | Some(PortState::OpenFiltered | PortState::ClosedFiltered)) => Verdict::Untested,
+            (Action::Allow, Some(PortState::Filtered)) => Verdict::Violated,
+            (Action::Allow, Some(_)) => Verdict::Verified,
+            (Action::Deny, Some(PortState::Open)) => Verdict::Violated,
+            (Action::Deny, Some(_)) => Verdict::Verified,
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Verdict::Verified => "verified",
+            Verdict::Violated => "violated",
+            Verdict::Untested => "untested",
+        })
+    }
+}
+
+/// One scanned port a rule covers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    pub host: String,
+    pub port: u16,
+    pub protocol: Protocol,
+    /// `None` when the port was not scanned
+    pub observed: Option<PortState>,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => format!("[{}]", self.host),
+            _ => self.host.clone(),
+        };
+        write!(f, "{}:{}/{}", host, self.port, self.protocol.as_str())?;
+        match self.observed {
+            Some(state) => write!(f, " {}", state),
+            None => f.write_str(" not scanned"),
+        }
+    }
+}
+
+/// How one rule held up against the scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleReport {
+    pub rule: String,
+    pub destination: String,
+    pub ports: String,
+    pub action: Action,
+    pub verdict: Verdict,
+    pub verified: usize,
+    pub untested: usize,
+    /// Every port that contradicts the rule
+    pub violations: Vec<Check>,
+}
+
+impl RuleReport {
+    fn new(rule: &AccessRule) -> Self {
+        Self {
+            rule: rule.id.clone(),
+            destination: rule.destination.clone(),
+            ports: rule.ports.to_string(),
+            action: rule.action,
+            verdict: Verdict::Untested,
+            verified: 0,
+            untested: 0,
+            violations: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, check: Check, action: Action) {
+        match Verdict::of(action, check.observed) {
+            Verdict::Verified => self.verified += 1,
+            Verdict::Untested => self.untested += 1,
+            Verdict::Violated => self.violations.push(check),
+        }
+        self.verdict = match (self.violations.is_empty(), self.verified) {
+            (false, _) => Verdict::Violated,
+            (true, 0) => Verdict::Untested,
+            (true, _) => Verdict::Verified,
+        };
+    }
+}
+
+/// Every rule from the scanned zone and how it held up
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ComplianceReport {
+    pub zone: String,
+    pub rules: Vec<RuleReport>,
+}
+
+/// A rule as one CSV row, violations joined into one column
+#[derive(Serialize)]
+struct RuleRow<'a> {
+    zone: &'a str,
+    rule: &'a str,
+    destination: &'a str,
+    ports: &'a str,
+    action: Action,
+    verdict: Verdict,
+    verified: usize,
+    violated: usize,
+    untested: usize,
+    violations: String,
+}
+
+impl ComplianceReport {
+    /// Rules with `verdict`
+    pub fn count(&self, verdict: Verdict) -> usize {
+        self.rules.iter().filter(|rule| rule.verdict == verdict).count()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// One row per rule
+    pub fn to_csv(&self) -> io::Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for rule in &self.rules {
+            writer.serialize(RuleRow {
+                zone: &self.zone,
+                rule: &rule.rule,
+                destination: &rule.destination,
+                ports: &rule.ports,
+                action: rule.action,
+                verdict: rule.verdict,
+                verified: rule.verified,
+                violated: rule.violations.len(),
+                untested: rule.untested,
+                violations: rule.violations.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "),
+            })?;
+        }
+        let bytes = writer.into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn port(port: u16, protocol: Protocol, state: PortState) -> PortResult {
+        PortResult {
+            port,
+            protocol,
+            state,
+            service: None,
+            version: None,
+            response_time: Duration::from_millis(1),
+            confidence: Default::default(),
+            evidence: Vec::new(),
+            anomalies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_verifies_rules_in_order() {
+        let matrix = AccessMatrix::from_reader(
+            "rule,source,destination,ports,action\n\
+             # the jump host is the one exception to FW-2\n\
+             FW-1,corp,10.0.1.5,22,allow\n\
+             FW-2,corp,10.0.1.0/24,\"22,3306\",deny\n\
+             FW-3,corp,db.internal,\"5432,U:53\",allow\n\
+             FW-4,corp,*,8080,deny\n\
+             FW-9,dmz,*,all,deny\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(matrix.zones(), ["corp", "dmz"]);
+        assert!(matrix.zone(None).unwrap_err().contains("--source-zone"));
+        assert!(matrix.zone(Some("lab")).is_err());
+
+        let scanned = vec![
+            ("10.0.1.5".to_string(), vec![port(22, Protocol::Tcp, PortState::Open), port(3306, Protocol::Tcp, PortState::Filtered)]),
+            ("10.0.1.7".to_string(), vec![port(22, Protocol::Tcp, PortState::Closed), port(3306, Protocol::Tcp, PortState::Open)]),
+            ("db.internal".to_string(), vec![port(5432, Protocol::Tcp, PortState::Filtered)]),
+        ];
+        let report = matrix.verify("corp", &scanned, Protocol::Tcp);
+        let verdicts: Vec<Verdict> = report.rules.iter().map(|rule| rule.verdict).collect();
+        assert_eq!(verdicts, [Verdict::Verified, Verdict::Violated, Verdict::Violated, Verdict::Untested]);
+
+        // FW-1 claimed 10.0.1.5:22, so FW-2 only checks the rest
+        let fw2 = &report.rules[1];
+        assert_eq!((fw2.verified, fw2.untested), (2, 0));
+        assert_eq!(fw2.violations.iter().map(ToString::to_string).collect::<Vec<_>>(), ["10.0.1.7:3306/tcp open"]);
+        assert_eq!(report.rules[2].untested, 1);
+        assert_eq!(report.rules[3].untested, 3);
+        assert!(report.to_csv().unwrap().starts_with("zone,rule,destination,ports,action,verdict,"));
+
+        let error = AccessMatrix::from_reader("source,destination,ports,action\ncorp,10.0.0.1,22,maybe\n".as_bytes()).unwrap_err();
+        assert_eq!(error, "line 2: action `maybe` is neither allow nor deny");
+    }
+}
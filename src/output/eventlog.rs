@@ -0,0 +1,332 @@
+//! Windows Event Log output (`--event-log`)
+//!
+//! Scheduled scans on Windows endpoints report to the Application log, where
+//! existing monitoring already looks: one event summarizing each scan and one
+//! per high or critical finding. Records are built on every platform; only
+//! writing them needs Windows.
+//!
+//! Events carry their text as a single insertion string. The source is
+//! registered against the .NET Framework's `EventLogMessages.dll`, whose
+//! messages are just `%1`, so Event Viewer shows the text as-is.
+
+use super::findings::ExportFinding;
+use crate::intelligence::Severity;
+use crate::network::{PortResult, PortState};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Source name events are logged under unless another is given
+pub const DEFAULT_SOURCE: &str = "Phobos";
+
+/// Event ID of scan summaries
+pub const SCAN_SUMMARY_EVENT: u32 = 1000;
+
+/// Event ID of high-severity findings
+pub const HIGH_FINDING_EVENT: u32 = 1001;
+
+/// Event ID of critical findings
+pub const CRITICAL_FINDING_EVENT: u32 = 1002;
+
+/// Open ports listed by name in a summary before the rest are counted
+const SUMMARY_PORTS: usize = 50;
+
+/// Longest insertion string ReportEvent accepts, in UTF-16 units
+const MAX_MESSAGE_LEN: usize = 31_839;
+
+/// Event Viewer's level for an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventLevel {
+    Information,
+    Warning,
+    Error,
+}
+
+/// One event ready to be logged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    pub level: EventLevel,
+    pub event_id: u32,
+    pub message: String,
+}
+
+impl EventRecord {
+    /// Summary of a finished scan of `target`
+    pub fn scan_summary(target: &str, duration: Duration, results: &[PortResult]) -> Self {
+        let count = |state| results.iter().filter(|r| r.state == state).count();
+        let mut message = format!(
+            "Phobos scan of {} finished in {:.1}s: {} open, {} closed, {} filtered ports.",
+            target,
+            duration.as_secs_f64(),
+            count(PortState::Open),
+            count(PortState::Closed),
+            count(PortState::Filtered)
+        );
+        let open: Vec<&PortResult> = results.iter().filter(|r| r.state == PortState::Open).collect();
+        if !open.is_empty() {
+            message.push_str("\r\nOpen:");
+            for port in open.iter().take(SUMMARY_PORTS) {
+                let _ = write!(message, " {}/{}", port.port, port.protocol.as_str());
+                if let Some(service) = &port.service {
+                    let _ = write!(message, " ({})", service);
+                }
+            }
+            if open.len() > SUMMARY_PORTS {
+                let _ = write!(message, " and {} more", open.len() - SUMMARY_PORTS);
+            }
+        }
+        Self { level: EventLevel::Information, event_id: SCAN_SUMMARY_EVENT, message }
+    }
+
+    /// Event for `finding`, or `None` below high severity
+    pub fn from_finding(finding: &ExportFinding) -> Option<Self> {
+        let (level, event_id) = match finding.severity {
+            Severity::Critical => (EventLevel::Error, CRITICAL_FINDING_EVENT),
+            Severity::High => (EventLevel::Warning, HIGH_FINDING_EVENT),
+            _ => return None,
+        };
+        let mut message = format!("{} severity finding on {}", finding.severity, finding.host);
+        if let Some(port) = finding.port {
+            let _ = write!(message, ":{}/{}", port, finding.protocol);
+        }
+        let _ = write!(message, ": {}\r\n{}\r\nCheck: {}", finding.title, finding.description, finding.check);
+        if !finding.cves.is_empty() {
+            let _ = write!(message, "\r\nCVE: {}", finding.cves.join(", "));
+        }
+        if let Some(evidence) = &finding.evidence {
+            let _ = write!(message, "\r\nEvidence: {}", evidence);
+        }
+        Some(Self { level, event_id, message })
+    }
+
+    /// The scan summary followed by an event per high-severity finding
+    pub fn for_scan(target: &str, duration: Duration, results: &[PortResult], findings: &[ExportFinding]) -> Vec<Self> {
+        std::iter::once(Self::scan_summary(target, duration, results))
+            .chain(findings.iter().filter_map(Self::from_finding))
+            .collect()
+    }
+
+    /// `message` cut to what the Event Log stores
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn truncated_message(&self) -> Vec<u16> {
+        let mut wide: Vec<u16> = self.message.encode_utf16().take(MAX_MESSAGE_LEN).collect();
+        wide.push(0);
+        wide
+    }
+}
+
+/// A registered event source in the Application log
+#[cfg(windows)]
+pub struct EventLog {
+    handle: win32::Handle,
+}
+
+// The handle is only passed to thread-safe Win32 calls
+#[cfg(windows)]
+unsafe impl Send for EventLog {}
+
+#[cfg(windows)]
+impl EventLog {
+    /// Open `source`, registering it first if it is new
+    ///
+    /// Registration writes to `HKLM` and needs an elevated process; without
+    /// it events are still logged, but Event Viewer adds a note that the
+    /// source's message file is missing.
+    pub fn open(source: &str) -> crate::Result<Self> {
+        if let Err(e) = register_source(source) {
+            log::warn!("Could not register event source {}: {}", source, e);
+        }
+        let name = wide(source);
+        // SAFETY: `name` is a NUL-terminated UTF-16 string that outlives the call
+        let handle = unsafe { win32::RegisterEventSourceW(std::ptr::null(), name.as_ptr()) };
+        if handle.is_null() {
+            return Err(crate::ScanError::OutputError(format!(
+                "Failed to open event source {}: {}",
+                source,
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(Self { handle })
+    }
+
+    pub fn write(&self, record: &EventRecord) -> crate::Result<()> {
+        let kind = match record.level {
+            EventLevel::Information => win32::EVENTLOG_INFORMATION_TYPE,
+            EventLevel::Warning => win32::EVENTLOG_WARNING_TYPE,
+            EventLevel::Error => win32::EVENTLOG_ERROR_TYPE,
+        };
+        let message = record.truncated_message();
+        let strings = [message.as_ptr()];
+        // SAFETY: the handle is open and `strings` holds one NUL-terminated string
+        let ok = unsafe {
+            win32::ReportEventW(
+                self.handle,
+                kind,
+                0,
+                record.event_id,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        if ok == 0 {
+            return Err(crate::ScanError::OutputError(format!(
+                "Failed to write event {}: {}",
+                record.event_id,
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from RegisterEventSourceW and is closed once
+        unsafe { win32::DeregisterEventSource(self.handle) };
+    }
+}
+
+/// Stand-in on platforms without an Event Log; opening always fails
+#[cfg(not(windows))]
+pub struct EventLog(());
+
+#[cfg(not(windows))]
+impl EventLog {
+    pub fn open(_source: &str) -> crate::Result<Self> {
+        Err(crate::ScanError::ConfigError("The Windows Event Log is only available on Windows".to_string()))
+    }
+
+    pub fn write(&self, _record: &EventRecord) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// Register `source` in the Application log with a pass-through message file
+#[cfg(windows)]
+fn register_source(source: &str) -> std::io::Result<()> {
+    let key_path = wide(&format!(r"SYSTEM\CurrentControlSet\Services\EventLog\Application\{}", source));
+    let message_file = wide(r"%SystemRoot%\Microsoft.NET\Framework\v4.0.30319\EventLogMessages.dll");
+    let types_supported: u32 = (win32::EVENTLOG_ERROR_TYPE | win32::EVENTLOG_WARNING_TYPE | win32::EVENTLOG_INFORMATION_TYPE).into();
+    let check = |status: i32| if status == 0 { Ok(()) } else { Err(std::io::Error::from_raw_os_error(status)) };
+
+    let mut key = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the duration of its call, and the
+    // key is closed below once it was opened
+    unsafe {
+        // Registered before, perhaps by an elevated run; nothing to do
+        if win32::RegOpenKeyExW(win32::HKEY_LOCAL_MACHINE, key_path.as_ptr(), 0, win32::KEY_READ, &mut key) == 0 {
+            win32::RegCloseKey(key);
+            return Ok(());
+        }
+        check(win32::RegCreateKeyExW(
+            win32::HKEY_LOCAL_MACHINE,
+            key_path.as_ptr(),
+            0,
+            std::ptr::null(),
+            0,
+            win32::KEY_SET_VALUE,
+            std::ptr::null(),
+            &mut key,
+            std::ptr::null_mut(),
+        ))?;
+        let set = |name: &str, kind: u32, data: &[u8]| {
+            let name = wide(name);
+            check(win32::RegSetValueExW(key, name.as_ptr(), 0, kind, data.as_ptr(), data.len() as u32))
+        };
+        let message_bytes: Vec<u8> = message_file.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+        let result = set("EventMessageFile", win32::REG_EXPAND_SZ, &message_bytes)
+            .and_then(|()| set("TypesSupported", win32::REG_DWORD, &types_supported.to_le_bytes()));
+        win32::RegCloseKey(key);
+        result
+    }
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// The few advapi32 calls the sink needs
+#[cfg(windows)]
+#[allow(non_snake_case)]
+mod win32 {
+    use std::ffi::c_void;
+
+    pub type Handle = *mut c_void;
+
+    pub const EVENTLOG_ERROR_TYPE: u16 = 0x0001;
+    pub const EVENTLOG_WARNING_TYPE: u16 = 0x0002;
+    pub const EVENTLOG_INFORMATION_TYPE: u16 = 0x0004;
+    // A sign-extended 0x80000002, as winreg.h defines it
+    pub const HKEY_LOCAL_MACHINE: Handle = 0x8000_0002_u32 as i32 as isize as Handle;
+    pub const KEY_READ: u32 = 0x0002_0019;
+    pub const KEY_SET_VALUE: u32 = 0x0002;
+    pub const REG_EXPAND_SZ: u32 = 2;
+    pub const REG_DWORD: u32 = 4;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn RegisterEventSourceW(server: *const u16, source: *const u16) -> Handle;
+        pub fn DeregisterEventSource(handle: Handle) -> i32;
+        pub fn ReportEventW(
+            handle: Handle,
+            kind: u16,
+            category: u16,
+            event_id: u32,
+            user_sid: *mut c_void,
+            num_strings: u16,
+            data_size: u32,
+            strings: *const *const u16,
+            raw_data: *const c_void,
+        ) -> i32;
+        pub fn RegCreateKeyExW(
+            key: Handle,
+            sub_key: *const u16,
+            reserved: u32,
+            class: *const u16,
+            options: u32,
+            desired: u32,
+            security: *const c_void,
+            result: *mut Handle,
+            disposition: *mut u32,
+        ) -> i32;
+        pub fn RegOpenKeyExW(key: Handle, sub_key: *const u16, options: u32, desired: u32, result: *mut Handle) -> i32;
+        pub fn RegSetValueExW(key: Handle, name: *const u16, reserved: u32, kind: u32, data: *const u8, size: u32) -> i32;
+        pub fn RegCloseKey(key: Handle) -> i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Protocol;
+
+    #[test]
+    fn test_event_records_for_scan() {
+        let mut ssh = PortResult::new(22, Protocol::Tcp, PortState::Open);
+        ssh.service = Some("ssh".to_string());
+        let results = vec![
+            ssh,
+            PortResult::new(23, Protocol::Tcp, PortState::Open),
+            PortResult::new(80, Protocol::Tcp, PortState::Closed),
+        ];
+        let findings = ExportFinding::risky_services("192.0.2.7".parse().unwrap(), &results);
+        let records = EventRecord::for_scan("192.0.2.7", Duration::from_millis(2500), &results, &findings);
+
+        // Telnet is a high-severity finding; nothing else qualifies
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].event_id, SCAN_SUMMARY_EVENT);
+        assert_eq!(
+            records[0].message,
+            "Phobos scan of 192.0.2.7 finished in 2.5s: 2 open, 1 closed, 0 filtered ports.\r\nOpen: 22/tcp (ssh) 23/tcp"
+        );
+        assert_eq!((records[1].level, records[1].event_id), (EventLevel::Warning, HIGH_FINDING_EVENT));
+        assert!(records[1].message.starts_with("high severity finding on 192.0.2.7:23/tcp: Exposed telnet service"));
+
+        let long = EventRecord { level: EventLevel::Information, event_id: 1, message: "x".repeat(40_000) };
+        assert_eq!(long.truncated_message().len(), MAX_MESSAGE_LEN + 1);
+    }
+}
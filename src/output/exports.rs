@@ -0,0 +1,389 @@
+//! File exports that cover every host of a scan
+//!
+//! STIX, OCSF, Metasploit, findings and Parquet exports and the event log
+//! are written once per scan. [`ScanExports`] collects each host as it is
+//! reported and renders all of them together, so a CIDR or multi-target
+//! scan produces one file with every host instead of one per host, each
+//! overwriting the last.
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::metasploit::{self, MsfHost};
+use super::provenance::Provenance;
+use super::{ocsf, stix};
+#[cfg(feature = "intelligence")]
+use super::eventlog::EventRecord;
+#[cfg(feature = "intelligence")]
+use super::findings::{self, ExportFinding};
+use crate::network::PortResult;
+use crate::scanner::ScanResult;
+
+/// One host's ports as reported
+#[derive(Debug, Clone)]
+pub struct ExportedHost {
+    pub address: IpAddr,
+    pub port_results: Vec<PortResult>,
+    pub duration: Duration,
+}
+
+/// One host's ports as reported, by the target it was scanned as, which
+/// may be a hostname
+#[derive(Debug, Clone)]
+pub struct ReportedHost {
+    pub target: String,
+    pub port_results: Vec<PortResult>,
+    pub duration: Duration,
+    /// Misconfiguration and credential findings on the host
+    #[cfg(feature = "intelligence")]
+    pub findings: Vec<ExportFinding>,
+}
+
+/// Files the exports are written to; a format without one is left out
+#[derive(Debug, Clone, Default)]
+pub struct ExportFiles {
+    pub stix: Option<PathBuf>,
+    pub ocsf: Option<PathBuf>,
+    #[cfg(feature = "intelligence")]
+    pub defectdojo: Option<PathBuf>,
+    #[cfg(feature = "intelligence")]
+    pub faraday: Option<PathBuf>,
+    pub msf: Option<PathBuf>,
+    /// Directory of the date-partitioned archive
+    #[cfg(feature = "parquet")]
+    pub parquet: Option<PathBuf>,
+}
+
+impl ExportFiles {
+    pub fn is_empty(&self) -> bool {
+        #[cfg(feature = "intelligence")]
+        let findings = self.defectdojo.is_none() && self.faraday.is_none();
+        #[cfg(not(feature = "intelligence"))]
+        let findings = true;
+        #[cfg(feature = "parquet")]
+        let parquet = self.parquet.is_none();
+        #[cfg(not(feature = "parquet"))]
+        let parquet = true;
+        self.stix.is_none() && self.ocsf.is_none() && self.msf.is_none() && findings && parquet
+    }
+}
+
+/// The hosts of a scan, gathered for the file exports
+#[derive(Debug, Clone, Default)]
+pub struct ScanExports {
+    hosts: Vec<ExportedHost>,
+    /// Every host reported, addresses or not
+    reported: Vec<ReportedHost>,
+}
+
+impl ScanExports {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every host of `results`, or its target when it has no per-host results
+    pub fn from_scan(results: &ScanResult) -> Self {
+        let mut exports = Self::new();
+        if results.hosts.is_empty() {
+            exports.add_host(&results.target, &results.port_results, results.duration);
+        }
+        for host in &results.hosts {
+            exports.push(ExportedHost {
+                address: host.host,
+                port_results: host.port_results.clone(),
+                duration: host.duration,
+            });
+        }
+        exports
+    }
+
+    /// Record a host as reported and add it to the exports; false when the
+    /// exports leave it out, as `target` is not an IP address
+    pub fn report(&mut self, target: &str, port_results: &[PortResult], duration: Duration) -> bool {
+        let host = ReportedHost {
+            target: target.to_string(),
+            port_results: port_results.to_vec(),
+            duration,
+            #[cfg(feature = "intelligence")]
+            findings: Vec::new(),
+        };
+        match self.reported.iter_mut().find(|known| known.target == target) {
+            Some(known) => *known = host,
+            None => self.reported.push(host),
+        }
+        self.add_host(target, port_results, duration)
+    }
+
+    /// Add the host `target` names; false, and nothing added, when it is
+    /// not an IP address, as every export format identifies hosts by address
+    pub fn add_host(&mut self, target: &str, port_results: &[PortResult], duration: Duration) -> bool {
+        let Ok(address) = target.parse() else { return false };
+        self.push(ExportedHost { address, port_results: port_results.to_vec(), duration });
+        true
+    }
+
+    /// Add a host, replacing what an earlier report of the same address added
+    pub fn push(&mut self, host: ExportedHost) {
+        match self.hosts.iter_mut().find(|known| known.address == host.address) {
+            Some(known) => *known = host,
+            None => self.hosts.push(host),
+        }
+    }
+
+    /// Add misconfiguration or credential findings on the reported `target`
+    #[cfg(feature = "intelligence")]
+    pub fn add_findings(&mut self, target: &str, findings: &[crate::intelligence::Finding]) {
+        if let Some(host) = self.reported.iter_mut().find(|host| host.target == target) {
+            host.findings.extend(findings.iter().map(ExportFinding::from_misconfig));
+        }
+    }
+
+    pub fn hosts(&self) -> &[ExportedHost] {
+        &self.hosts
+    }
+
+    /// Every host given to [`report`](Self::report), in order
+    pub fn reported(&self) -> &[ReportedHost] {
+        &self.reported
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// One STIX 2.1 bundle with an `observed-data` object per host
+    pub fn to_stix_bundle(&self, finished: &DateTime<Utc>, provenance: &Provenance) -> String {
+        let started = |host: &ExportedHost| *finished - chrono::Duration::from_std(host.duration).unwrap_or_default();
+        let earliest = self.hosts.iter().map(started).min().unwrap_or(*finished);
+        let mut objects = vec![stix::identity(&earliest)];
+        for host in &self.hosts {
+            objects.extend(stix::host_objects(host.address, &host.port_results, &started(host), finished, provenance));
+        }
+        let bundle = json!({
+            "type": "bundle",
+            "id": format!("bundle--{}", Uuid::new_v4()),
+            "objects": objects,
+        });
+        serde_json::to_string_pretty(&bundle).unwrap_or_default()
+    }
+
+    /// Newline-delimited OCSF events for every host
+    pub fn to_ocsf_ndjson(&self, time: &DateTime<Utc>, provenance: &Provenance) -> String {
+        self.hosts
+            .iter()
+            .flat_map(|host| ocsf::host_events(host.address, &host.port_results, time, provenance))
+            .map(|event| format!("{}\n", event))
+            .collect()
+    }
+
+    /// A Metasploit host per scanned host
+    pub fn msf_hosts(&self) -> Vec<MsfHost> {
+        self.hosts
+            .iter()
+            .map(|host| MsfHost::from_port_results(host.address, None, &host.port_results))
+            .collect()
+    }
+
+    /// Exposed risky services of every host, then the findings added
+    #[cfg(feature = "intelligence")]
+    pub fn findings(&self) -> Vec<ExportFinding> {
+        let mut exported: Vec<ExportFinding> = self
+            .hosts
+            .iter()
+            .flat_map(|host| ExportFinding::risky_services(host.address, &host.port_results))
+            .collect();
+        exported.extend(self.reported.iter().flat_map(|host| host.findings.iter().cloned()));
+        exported
+    }
+
+    /// Event Log records of every reported host: its scan summary, then its
+    /// high and critical findings
+    #[cfg(feature = "intelligence")]
+    pub fn event_records(&self) -> Vec<EventRecord> {
+        self.reported
+            .iter()
+            .flat_map(|host| {
+                let mut exported = match host.target.parse() {
+                    Ok(address) => ExportFinding::risky_services(address, &host.port_results),
+                    Err(_) => Vec::new(),
+                };
+                exported.extend(host.findings.iter().cloned());
+                EventRecord::for_scan(&host.target, host.duration, &host.port_results, &exported)
+            })
+            .collect()
+    }
+
+    /// Write each export `files` asks for, every host in one file; the
+    /// format, file and outcome of each, in the order written
+    pub fn write_files(
+        &self,
+        files: &ExportFiles,
+        finished: &DateTime<Utc>,
+        provenance: &Provenance,
+    ) -> Vec<(&'static str, PathBuf, io::Result<()>)> {
+        let mut written = Vec::new();
+        let mut write = |format, path: &Option<PathBuf>, render: &dyn Fn() -> io::Result<String>| {
+            if let Some(path) = path {
+                written.push((format, path.clone(), render().and_then(|contents| std::fs::write(path, contents))));
+            }
+        };
+        write("STIX 2.1", &files.stix, &|| Ok(self.to_stix_bundle(finished, provenance)));
+        write("OCSF", &files.ocsf, &|| Ok(self.to_ocsf_ndjson(finished, provenance)));
+        #[cfg(feature = "intelligence")]
+        {
+            write("DefectDojo", &files.defectdojo, &|| Ok(findings::to_defectdojo_json(&self.findings(), provenance)));
+            write("Faraday", &files.faraday, &|| Ok(findings::to_faraday_json(&self.findings(), provenance)));
+        }
+        write("Metasploit XML", &files.msf, &|| metasploit::to_msf_xml(&self.msf_hosts(), provenance));
+        #[cfg(feature = "parquet")]
+        if let Some(dir) = &files.parquet {
+            written.push(("Parquet", dir.clone(), super::parquet::export(dir, &self.hosts, provenance).map(drop)));
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::{PortState, Protocol};
+    use crate::scanner::HostResult;
+
+    fn open(port: u16) -> PortResult {
+        PortResult::new(port, Protocol::Tcp, PortState::Open)
+    }
+
+    fn provenance() -> Provenance {
+        Provenance::collect(&ScanConfig::default())
+    }
+
+    fn two_hosts() -> ScanExports {
+        let mut exports = ScanExports::new();
+        assert!(exports.add_host("192.0.2.10", &[open(22)], Duration::from_secs(1)));
+        assert!(exports.add_host("2001:db8::20", &[open(6379), PortResult::new(80, Protocol::Tcp, PortState::Closed)], Duration::from_secs(2)));
+        assert!(!exports.add_host("example.com", &[open(443)], Duration::ZERO));
+        exports
+    }
+
+    #[test]
+    fn test_every_host_is_exported_together() {
+        let exports = two_hosts();
+        let provenance = provenance();
+        let now = Utc::now();
+
+        let bundle: serde_json::Value = serde_json::from_str(&exports.to_stix_bundle(&now, &provenance)).unwrap();
+        let objects = bundle["objects"].as_array().unwrap();
+        let of_type = |kind: &str| objects.iter().filter(|o| o["type"] == kind).count();
+        assert_eq!(of_type("identity"), 1);
+        assert_eq!(of_type("observed-data"), 2);
+        assert_eq!(of_type("ipv4-addr") + of_type("ipv6-addr"), 2);
+        assert_eq!(of_type("network-traffic"), 2);
+
+        let events = exports.to_ocsf_ndjson(&now, &provenance);
+        assert_eq!(events.lines().count(), 4);
+        assert!(events.contains("\"ip\":\"192.0.2.10\"") && events.contains("\"ip\":\"2001:db8::20\""));
+
+        let msf = exports.msf_hosts();
+        assert_eq!(msf.len(), 2);
+        assert_eq!(msf[1].services.len(), 1);
+    }
+
+    #[cfg(feature = "intelligence")]
+    #[test]
+    fn test_findings_cover_every_host() {
+        let findings = two_hosts().findings();
+        let hosts: Vec<String> = findings.iter().map(|finding| finding.host.to_string()).collect();
+        assert_eq!(hosts, ["2001:db8::20"]);
+        assert_eq!(findings[0].port, Some(6379));
+    }
+
+    #[test]
+    fn test_hosts_come_from_the_scan_result() {
+        let mut results = ScanResult::new("192.0.2.0/30".to_string(), ScanConfig::default());
+        for (address, port) in [("192.0.2.1", 80), ("192.0.2.2", 443)] {
+            let mut host = HostResult::new(address.parse::<IpAddr>().unwrap());
+            host.port_results.push(open(port));
+            host.open_ports.push(port);
+            results.hosts.push(host);
+        }
+        let exports = ScanExports::from_scan(&results);
+        let addresses: Vec<String> = exports.hosts().iter().map(|host| host.address.to_string()).collect();
+        assert_eq!(addresses, ["192.0.2.1", "192.0.2.2"]);
+
+        // A host reported twice keeps its latest ports
+        let mut exports = exports;
+        exports.add_host("192.0.2.1", &[open(8080)], Duration::ZERO);
+        assert_eq!(exports.hosts().len(), 2);
+        assert_eq!(exports.hosts()[0].port_results[0].port, 8080);
+    }
+
+    #[test]
+    fn test_every_reported_host_is_kept() {
+        let mut exports = ScanExports::new();
+        assert!(exports.report("192.0.2.1", &[open(22)], Duration::from_secs(1)));
+        assert!(!exports.report("scanme.example", &[open(80)], Duration::from_secs(1)));
+        assert!(exports.report("192.0.2.2", &[], Duration::from_secs(1)));
+        assert!(exports.report("192.0.2.1", &[open(443)], Duration::from_secs(2)));
+
+        let targets: Vec<&str> = exports.reported().iter().map(|host| host.target.as_str()).collect();
+        assert_eq!(targets, ["192.0.2.1", "scanme.example", "192.0.2.2"]);
+        assert_eq!(exports.reported()[0].port_results[0].port, 443);
+        // Hostnames are reported but cannot be exported
+        assert_eq!(exports.hosts().len(), 2);
+    }
+
+    #[test]
+    fn test_files_are_written_once_with_every_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = ExportFiles {
+            stix: Some(dir.path().join("scan.stix.json")),
+            msf: Some(dir.path().join("scan.msf.xml")),
+            ..Default::default()
+        };
+        assert!(!files.is_empty() && ExportFiles::default().is_empty());
+
+        let written = two_hosts().write_files(&files, &Utc::now(), &provenance());
+        let formats: Vec<&str> = written.iter().map(|(format, _, _)| *format).collect();
+        assert_eq!(formats, ["STIX 2.1", "Metasploit XML"]);
+        for (format, path, result) in &written {
+            assert!(result.is_ok(), "{}", format);
+            let contents = std::fs::read_to_string(path).unwrap();
+            assert!(contents.contains("192.0.2.10") && contents.contains("2001:db8::20"), "{}", format);
+        }
+        assert!(!dir.path().join("scan.ocsf.json").exists());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_archive_has_every_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = ExportFiles { parquet: Some(dir.path().to_path_buf()), ..Default::default() };
+        let provenance = provenance();
+        let written = two_hosts().write_files(&files, &Utc::now(), &provenance);
+        assert_eq!(written.len(), 1);
+        assert!(written[0].2.is_ok());
+
+        let history = crate::output::parquet::History::load(dir.path(), DateTime::<Utc>::MIN_UTC, None).unwrap();
+        assert_eq!(history.hosts.len(), 2);
+    }
+
+    #[cfg(feature = "intelligence")]
+    #[test]
+    fn test_event_log_covers_every_reported_host() {
+        let mut exports = ScanExports::new();
+        exports.report("192.0.2.1", &[open(6379)], Duration::from_secs(1));
+        exports.report("scanme.example", &[], Duration::from_secs(1));
+        exports.report("192.0.2.2", &[], Duration::from_secs(1));
+        let records = exports.event_records();
+        // A summary per host, and the exposed Redis on the first
+        let summaries = records.iter().filter(|record| record.event_id == crate::output::eventlog::SCAN_SUMMARY_EVENT).count();
+        assert_eq!(summaries, 3, "{:#?}", records);
+        assert_eq!(records.len(), 4, "{:#?}", records);
+    }
+}
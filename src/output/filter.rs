@@ -0,0 +1,374 @@
+//! Result filters (`--filter`)
+//!
+//! A small expression language for keeping only the ports a report should
+//! show, so that every output format can be narrowed the same way instead of
+//! piping it through jq or grep:
+//!
+//! ```text
+//! state==open && port<1024 && service~'http'
+//! !(state==filtered) || confidence==high
+//! ```
+//!
+//! Fields are `port` and `rtt` (milliseconds), compared with `==`, `!=`,
+//! `<`, `<=`, `>` and `>=`, and `state`, `service`, `protocol` and
+//! `confidence`, compared with `==` and `!=` or matched against a regex with
+//! `~` and `!~` (case-insensitive). A port without a service has the service
+//! `""`. Values are numbers, quoted strings or bare words; `&&` binds tighter
+//! than `||`, and `!` and parentheses work as usual. Values with operator
+//! characters in them, such as `'open|filtered'`, need quotes.
+
+use crate::network::PortResult;
+use crate::scanner::ScanResult;
+use crate::ScanError;
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// A parsed `--filter` expression
+#[derive(Debug, Clone)]
+pub struct ResultFilter {
+    source: String,
+    expr: Expr,
+}
+
+impl ResultFilter {
+    pub fn parse(source: &str) -> crate::Result<Self> {
+        let tokens = tokenize(source).map_err(|e| invalid(source, &e))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or().map_err(|e| invalid(source, &e))?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(invalid(source, &format!("unexpected {}", token)));
+        }
+        Ok(Self { source: source.to_string(), expr })
+    }
+
+    pub fn matches(&self, result: &PortResult) -> bool {
+        self.expr.eval(result)
+    }
+
+    /// The results that pass, in order
+    pub fn apply<'a>(&self, results: impl IntoIterator<Item = &'a PortResult>) -> Vec<PortResult> {
+        results.into_iter().filter(|result| self.matches(result)).cloned().collect()
+    }
+
+    /// `scan` with only the ports that pass, in its port lists as well
+    pub fn apply_to_scan(&self, scan: &ScanResult) -> ScanResult {
+        let mut filtered = scan.clone();
+        filtered.port_results = self.apply(&scan.port_results);
+        let kept: std::collections::HashSet<u16> = filtered.port_results.iter().map(|r| r.port).collect();
+        for ports in [&mut filtered.open_ports, &mut filtered.closed_ports, &mut filtered.filtered_ports] {
+            ports.retain(|port| kept.contains(port));
+        }
+        filtered
+    }
+}
+
+impl std::str::FromStr for ResultFilter {
+    type Err = ScanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for ResultFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn invalid(source: &str, reason: &str) -> ScanError {
+    ScanError::ParseError(format!("invalid filter `{}`: {}", source, reason))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Port,
+    Rtt,
+    State,
+    Service,
+    Protocol,
+    Confidence,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "port" => Some(Field::Port),
+            "rtt" => Some(Field::Rtt),
+            "state" => Some(Field::State),
+            "service" => Some(Field::Service),
+            "protocol" => Some(Field::Protocol),
+            "confidence" => Some(Field::Confidence),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Port | Field::Rtt)
+    }
+
+    fn number(self, result: &PortResult) -> f64 {
+        match self {
+            Field::Port => f64::from(result.port),
+            _ => result.response_time.as_secs_f64() * 1000.0,
+        }
+    }
+
+    fn text(self, result: &PortResult) -> String {
+        match self {
+            Field::State => result.state.to_string(),
+            Field::Service => result.service.clone().unwrap_or_default(),
+            Field::Protocol => result.protocol.as_str().to_string(),
+            _ => result.confidence.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Number(Field, Op, f64),
+    Text(Field, bool, String),
+    Regex(Field, bool, Regex),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+impl Expr {
+    fn eval(&self, result: &PortResult) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(result) || b.eval(result),
+            Expr::And(a, b) => a.eval(result) && b.eval(result),
+            Expr::Not(a) => !a.eval(result),
+            Expr::Condition(Condition::Number(field, op, value)) => {
+                let actual = field.number(result);
+                match op {
+                    Op::Eq => actual == *value,
+                    Op::Ne => actual != *value,
+                    Op::Lt => actual < *value,
+                    Op::Le => actual <= *value,
+                    Op::Gt => actual > *value,
+                    _ => actual >= *value,
+                }
+            }
+            Expr::Condition(Condition::Text(field, equal, value)) => field.text(result).eq_ignore_ascii_case(value) == *equal,
+            Expr::Condition(Condition::Regex(field, matching, regex)) => regex.is_match(&field.text(result)) == *matching,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Quoted(text) => write!(f, "'{}'", text),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::And => f.write_str("`&&`"),
+            Token::Or => f.write_str("`||`"),
+            Token::Not => f.write_str("`!`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        let next = source[at + c.len_utf8()..].chars().next();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('!', Some('~')) => (Token::Op(Op::NotMatch), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('~', _) => (Token::Op(Op::Match), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('\'' | '"', _) => {
+                let end = source[at + 1..].find(c).ok_or_else(|| format!("unclosed {} quote at {}", c, at))?;
+                let text = &source[at + 1..at + 1 + end];
+                (Token::Quoted(text.to_string()), end + 2)
+            }
+            _ => {
+                let len = source[at..]
+                    .find(|c: char| c.is_whitespace() || "&|=!<>~()'\"".contains(c))
+                    .unwrap_or(source.len() - at);
+                if len == 0 {
+                    return Err(format!("unexpected `{}` at {}", c, at));
+                }
+                (Token::Word(source[at..at + len].to_string()), len)
+            }
+        };
+        tokens.push(token);
+        while chars.peek().is_some_and(|&(i, _)| i < at + len) {
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            if !self.eat(&Token::Close) {
+                return Err("missing `)`".to_string());
+            }
+            return Ok(expr);
+        }
+        self.condition().map(Expr::Condition)
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let name = match self.next() {
+            Some(Token::Word(name)) => name,
+            Some(token) => return Err(format!("expected a field, found {}", token)),
+            None => return Err("expected a field, found the end".to_string()),
+        };
+        let field = Field::parse(&name).ok_or_else(|| {
+            format!("unknown field `{}`; use port, rtt, state, service, protocol or confidence", name)
+        })?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => return Err(format!("expected a comparison after `{}`", name)),
+        };
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            _ => return Err(format!("expected a value after `{}`", name)),
+        };
+
+        if field.is_numeric() {
+            if matches!(op, Op::Match | Op::NotMatch) {
+                return Err(format!("`{}` is a number; compare it with ==, !=, <, <=, > or >=", name));
+            }
+            let number = value.parse().map_err(|_| format!("`{}` needs a number, not `{}`", name, value))?;
+            return Ok(Condition::Number(field, op, number));
+        }
+        match op {
+            Op::Eq | Op::Ne => Ok(Condition::Text(field, op == Op::Eq, value)),
+            Op::Match | Op::NotMatch => {
+                let regex = RegexBuilder::new(&value)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("bad regex `{}`: {}", value, e))?;
+                Ok(Condition::Regex(field, op == Op::Match, regex))
+            }
+            _ => Err(format!("`{}` is text; compare it with ==, !=, ~ or !~", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{PortState, Protocol};
+
+    fn port(number: u16, state: PortState, service: Option<&str>) -> PortResult {
+        let mut result = PortResult::new(number, Protocol::Tcp, state);
+        result.service = service.map(str::to_string);
+        result
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let results = vec![
+            port(22, PortState::Open, Some("ssh")),
+            port(80, PortState::Open, Some("http")),
+            port(443, PortState::Filtered, Some("https")),
+            port(8080, PortState::Open, Some("HTTP-proxy")),
+        ];
+        let kept = |expr: &str| -> Vec<u16> {
+            ResultFilter::parse(expr).unwrap().apply(&results).iter().map(|r| r.port).collect()
+        };
+
+        assert_eq!(kept("state==open && port<1024 && service~'http'"), [80]);
+        assert_eq!(kept("service ~ \"^http\""), [80, 443, 8080]);
+        assert_eq!(kept("state==filtered || port>=8000"), [443, 8080]);
+        assert_eq!(kept("!(state==open) || service==SSH"), [22, 443]);
+        assert_eq!(kept("service!~http && protocol==tcp"), [22]);
+
+        for bad in ["port~80", "state<open", "prot==80", "port==", "(port==22", "port==22 port==80", "service~'('"] {
+            assert!(ResultFilter::parse(bad).is_err(), "{}", bad);
+        }
+        assert!(ResultFilter::parse("colour==red").unwrap_err().to_string().contains("unknown field `colour`"));
+    }
+}
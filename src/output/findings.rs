@@ -0,0 +1,396 @@
+//! Findings export for vulnerability-management platforms
+//!
+//! Scan output that needs triage (exposed risky services, misconfiguration
+//! and credential findings, CVE matches, script output) is collected as
+//! [`ExportFinding`]s and rendered for:
+//! - DefectDojo's "Generic Findings Import" JSON
+//! - Faraday's bulk-create JSON (`faraday-cli tool report` / `/bulk_create`)
+
+use super::provenance::Provenance;
+use crate::intelligence::{Finding, ServiceInfo, Severity};
+use crate::network::{PortResult, PortState, Protocol};
+#[cfg(feature = "scripts")]
+use crate::scripts::ScriptResult;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+/// Services that usually should not be reachable from untrusted networks
+const RISKY_SERVICES: &[(u16, &str, Severity, &str)] = &[
+    (21, "ftp", Severity::Medium, "FTP transfers credentials in cleartext"),
+    (23, "telnet", Severity::High, "Telnet exposes an unencrypted remote shell"),
+    (135, "msrpc", Severity::Medium, "Microsoft RPC endpoint mapper is exposed"),
+    (139, "netbios-ssn", Severity::Medium, "NetBIOS session service is exposed"),
+    (445, "microsoft-ds", Severity::High, "SMB is exposed"),
+    (512, "exec", Severity::High, "rexec accepts cleartext remote commands"),
+    (513, "login", Severity::High, "rlogin relies on host-based trust"),
+    (514, "shell", Severity::High, "rsh relies on host-based trust"),
+    (873, "rsync", Severity::Medium, "rsync daemon may allow anonymous module access"),
+    (1433, "ms-sql-s", Severity::Medium, "Microsoft SQL Server is exposed"),
+    (2375, "docker", Severity::Critical, "Docker API is exposed without TLS"),
+    (2379, "etcd", Severity::High, "etcd client API is exposed"),
+    (3306, "mysql", Severity::Medium, "MySQL is exposed"),
+    (3389, "ms-wbt-server", Severity::Medium, "RDP is exposed"),
+    (5432, "postgresql", Severity::Medium, "PostgreSQL is exposed"),
+    (5900, "vnc", Severity::High, "VNC is exposed"),
+    (6000, "x11", Severity::High, "X11 display server is exposed"),
+    (6379, "redis", Severity::High, "Redis is exposed"),
+    (9200, "elasticsearch", Severity::Medium, "Elasticsearch HTTP API is exposed"),
+    (10250, "kubelet", Severity::High, "Kubelet API is exposed"),
+    (11211, "memcached", Severity::Medium, "memcached is exposed"),
+    (27017, "mongodb", Severity::High, "MongoDB is exposed"),
+];
+
+/// A platform-neutral finding
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportFinding {
+    pub host: IpAddr,
+    pub port: Option<u16>,
+    /// `tcp` or `udp`
+    pub protocol: String,
+    pub service: Option<String>,
+    pub title: String,
+    pub severity: Severity,
+    pub description: String,
+    /// Stable identifier of what produced the finding, e.g. `smtp-open-relay`
+    pub check: String,
+    /// CVE identifiers
+    pub cves: Vec<String>,
+    pub evidence: Option<String>,
+}
+
+impl ExportFinding {
+    /// Findings for open ports on [`RISKY_SERVICES`]
+    pub fn risky_services(host: IpAddr, results: &[PortResult]) -> Vec<Self> {
+        results
+            .iter()
+            .filter(|r| r.state == PortState::Open && r.protocol == Protocol::Tcp)
+            .filter_map(|r| {
+                let (port, name, severity, description) = RISKY_SERVICES.iter().find(|(p, ..)| *p == r.port)?;
+                Some(Self {
+                    host,
+                    port: Some(*port),
+                    protocol: "tcp".to_string(),
+                    service: Some(r.service.clone().unwrap_or_else(|| name.to_string())),
+                    title: format!("Exposed {} service on {}/tcp", name, port),
+                    severity: *severity,
+                    description: description.to_string(),
+                    check: format!("exposed-{}", name),
+                    cves: Vec::new(),
+                    evidence: None,
+                })
+            })
+            .collect()
+    }
+
+    pub fn from_misconfig(finding: &Finding) -> Self {
+        Self {
+            host: finding.target.ip(),
+            port: Some(finding.target.port()),
+            protocol: "tcp".to_string(),
+            service: None,
+            title: finding.title.clone(),
+            severity: finding.severity,
+            description: finding.title.clone(),
+            check: finding.check.clone(),
+            cves: Vec::new(),
+            evidence: finding.evidence.clone(),
+        }
+    }
+
+    /// One finding per CVE matched against a detected service
+    pub fn from_service_info(host: IpAddr, service: &ServiceInfo) -> Vec<Self> {
+        service
+            .vulnerabilities
+            .iter()
+            .map(|vuln| Self {
+                host,
+                port: Some(service.port),
+                protocol: service.protocol.to_lowercase(),
+                service: Some(service.service_name.clone()),
+                title: format!("{} in {}", vuln.cve_id, service.service_name),
+                severity: parse_severity(&vuln.severity),
+                description: vuln.description.clone(),
+                check: vuln.cve_id.clone(),
+                cves: vec![vuln.cve_id.clone()],
+                evidence: service.version.as_ref().map(|v| format!("{} {}", service.service_name, v)),
+            })
+            .collect()
+    }
+
+    /// Informational finding carrying the output of a successful script
+    #[cfg(feature = "scripts")]
+    pub fn from_script_result(result: &ScriptResult) -> Option<Self> {
+        if !result.success || result.output.trim().is_empty() {
+            return None;
+        }
+        Some(Self {
+            host: result.target,
+            port: (result.ports.len() == 1).then(|| result.ports[0]),
+            protocol: "tcp".to_string(),
+            service: None,
+            title: format!("Script output: {}", result.script_name),
+            severity: Severity::Info,
+            description: format!("Output of {} against ports {:?}", result.script_name, result.ports),
+            check: format!("script-{}", result.script_name),
+            cves: Vec::new(),
+            evidence: Some(result.output.trim().to_string()),
+        })
+    }
+
+    fn location(&self) -> String {
+        match self.port {
+            Some(port) => format!("{}:{}/{}", self.host, port, self.protocol),
+            None => self.host.to_string(),
+        }
+    }
+}
+
+fn parse_severity(value: &str) -> Severity {
+    match value.to_lowercase().as_str() {
+        "critical" => Severity::Critical,
+        "high" => Severity::High,
+        "medium" | "moderate" => Severity::Medium,
+        "low" => Severity::Low,
+        _ => Severity::Info,
+    }
+}
+
+/// DefectDojo spells severities in title case
+fn defectdojo_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "Critical",
+        Severity::High => "High",
+        Severity::Medium => "Medium",
+        Severity::Low => "Low",
+        Severity::Info => "Info",
+    }
+}
+
+/// Faraday uses `informational` rather than `info`
+fn faraday_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "critical",
+        Severity::High => "high",
+        Severity::Medium => "medium",
+        Severity::Low => "low",
+        Severity::Info => "informational",
+    }
+}
+
+/// Render findings for DefectDojo's Generic Findings Import (`scan_type=Generic Findings Import`)
+///
+/// The parser only reads `findings`, so provenance rides along as a sibling key.
+pub fn to_defectdojo_json(findings: &[ExportFinding], provenance: &Provenance) -> String {
+    let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let findings: Vec<Value> = findings
+        .iter()
+        .map(|f| {
+            let mut description = f.description.clone();
+            if let Some(evidence) = &f.evidence {
+                description.push_str(&format!("\n\nEvidence:\n```\n{}\n```", evidence));
+            }
+            let mut endpoint = json!({ "host": f.host.to_string() });
+            if let Some(port) = f.port {
+                endpoint["port"] = json!(port);
+            }
+            let mut finding = json!({
+                "title": f.title,
+                "description": description,
+                "severity": defectdojo_severity(f.severity),
+                "date": date,
+                "active": true,
+                "verified": false,
+                "static_finding": false,
+                "dynamic_finding": true,
+                "vuln_id_from_tool": f.check,
+                // Stable across rescans so DefectDojo deduplicates reimports
+                "unique_id_from_tool": format!("{}|{}", f.check, f.location()),
+                "component_name": f.service,
+                "endpoints": [endpoint],
+            });
+            if !f.cves.is_empty() {
+                finding["cve"] = json!(f.cves[0]);
+                finding["vulnerability_ids"] = json!(f.cves.iter().map(|c| json!({ "vulnerability_id": c })).collect::<Vec<_>>());
+            }
+            finding
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&json!({ "findings": findings, "provenance": provenance.to_json() })).unwrap_or_default()
+}
+
+/// (port, protocol) -> (service name, vulnerabilities)
+type FaradayServices = BTreeMap<(u16, String), (Option<String>, Vec<Value>)>;
+
+/// Render findings as a Faraday bulk-create document, grouped by host and service
+///
+/// Faraday validates the document strictly, so provenance is summarized in host descriptions.
+pub fn to_faraday_json(findings: &[ExportFinding], provenance: &Provenance) -> String {
+    let mut hosts: BTreeMap<IpAddr, (Vec<Value>, FaradayServices)> = BTreeMap::new();
+
+    for f in findings {
+        let mut vuln = json!({
+            "name": f.title,
+            "desc": f.description,
+            "severity": faraday_severity(f.severity),
+            "type": "Vulnerability",
+            "status": "open",
+            "external_id": f.check,
+            "refs": f.cves.iter().map(|c| json!({ "name": c, "type": "cve" })).collect::<Vec<_>>(),
+            "cve": f.cves,
+        });
+        if let Some(evidence) = &f.evidence {
+            vuln["data"] = json!(evidence);
+        }
+
+        let (host_vulns, services) = hosts.entry(f.host).or_default();
+        match f.port {
+            Some(port) => {
+                let entry = services.entry((port, f.protocol.clone())).or_default();
+                if entry.0.is_none() {
+                    entry.0 = f.service.clone();
+                }
+                entry.1.push(vuln);
+            }
+            None => host_vulns.push(vuln),
+        }
+    }
+
+    let hosts: Vec<Value> = hosts
+        .into_iter()
+        .map(|(ip, (vulnerabilities, services))| {
+            let services: Vec<Value> = services
+                .into_iter()
+                .map(|((port, protocol), (name, vulnerabilities))| {
+                    json!({
+                        "name": name.unwrap_or_else(|| "unknown".to_string()),
+                        "port": port,
+                        "protocol": protocol,
+                        "status": "open",
+                        "vulnerabilities": vulnerabilities,
+                    })
+                })
+                .collect();
+            json!({
+                "ip": ip.to_string(),
+                "description": format!("Imported from {}", provenance.summary()),
+                "hostnames": [],
+                "services": services,
+                "vulnerabilities": vulnerabilities,
+            })
+        })
+        .collect();
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let command = json!({
+        "tool": "phobos",
+        "command": "phobos",
+        "params": provenance.command_line.iter().skip(1).cloned().collect::<Vec<_>>().join(" "),
+        "user": std::env::var("USER").unwrap_or_default(),
+        "import_source": "report",
+        "start_date": now,
+        "end_date": now,
+    });
+
+    serde_json::to_string_pretty(&json!({ "hosts": hosts, "command": command })).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Confidence;
+    use crate::intelligence::service_detection::Vulnerability;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    fn host() -> IpAddr {
+        "192.0.2.20".parse().unwrap()
+    }
+
+    fn open(port: u16) -> PortResult {
+        PortResult {
+            port,
+            protocol: Protocol::Tcp,
+            state: PortState::Open,
+            service: None,
+            version: None,
+            response_time: Duration::from_millis(1),
+            confidence: Confidence::Medium,
+            evidence: Vec::new(),
+            anomalies: Vec::new(),
+        }
+    }
+
+    fn sample() -> Vec<ExportFinding> {
+        let mut findings = ExportFinding::risky_services(host(), &[open(23), open(443)]);
+        let service = ServiceInfo {
+            port: 443,
+            protocol: "TCP".to_string(),
+            service_name: "https".to_string(),
+            version: Some("2.4.49".to_string()),
+            banner: None,
+            ssl_info: None,
+            technologies: Vec::new(),
+            favicon_hash: None,
+            vulnerabilities: vec![Vulnerability {
+                cve_id: "CVE-2021-41773".to_string(),
+                severity: "Critical".to_string(),
+                description: "Path traversal".to_string(),
+            }],
+            response_time: Duration::from_millis(1),
+        };
+        findings.extend(ExportFinding::from_service_info(host(), &service));
+        findings
+    }
+
+    #[test]
+    fn test_risky_services_and_conversions() {
+        let findings = sample();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].check, "exposed-telnet");
+        assert_eq!(findings[0].severity, Severity::High);
+        assert_eq!(findings[1].severity, Severity::Critical);
+        assert_eq!(findings[1].protocol, "tcp");
+
+        let misconfig = Finding {
+            target: SocketAddr::new(host(), 25),
+            check: "smtp-open-relay".to_string(),
+            severity: Severity::High,
+            title: "Open relay".to_string(),
+            evidence: Some("250 OK".to_string()),
+        };
+        assert_eq!(ExportFinding::from_misconfig(&misconfig).port, Some(25));
+    }
+
+    #[test]
+    fn test_defectdojo_generic_format() {
+        let provenance = Provenance::collect(&Default::default());
+        let doc: Value = serde_json::from_str(&to_defectdojo_json(&sample(), &provenance)).unwrap();
+        let findings = doc["findings"].as_array().unwrap();
+
+        assert_eq!(findings[0]["severity"], "High");
+        assert_eq!(findings[0]["endpoints"][0]["port"], 23);
+        assert_eq!(findings[0]["unique_id_from_tool"], "exposed-telnet|192.0.2.20:23/tcp");
+        assert!(findings[0].get("cve").is_none());
+        assert_eq!(findings[1]["cve"], "CVE-2021-41773");
+        assert!(findings[1]["description"].as_str().unwrap().contains("https 2.4.49"));
+        assert_eq!(doc["provenance"]["run_id"], provenance.run_id.as_str());
+    }
+
+    #[test]
+    fn test_faraday_groups_by_service() {
+        let provenance = Provenance::collect(&Default::default());
+        let doc: Value = serde_json::from_str(&to_faraday_json(&sample(), &provenance)).unwrap();
+        let hosts = doc["hosts"].as_array().unwrap();
+
+        assert_eq!(hosts.len(), 1);
+        let services = hosts[0]["services"].as_array().unwrap();
+        assert_eq!(services.iter().map(|s| s["port"].as_u64().unwrap()).collect::<Vec<_>>(), vec![23, 443]);
+        assert_eq!(services[1]["name"], "https");
+        assert_eq!(services[1]["vulnerabilities"][0]["severity"], "critical");
+        assert_eq!(services[1]["vulnerabilities"][0]["refs"][0]["type"], "cve");
+        assert_eq!(doc["command"]["tool"], "phobos");
+        assert!(hosts[0]["description"].as_str().unwrap().contains(&provenance.run_id));
+    }
+}
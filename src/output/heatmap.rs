@@ -0,0 +1,257 @@
+//! Port exposure heatmap across a campaign (`phobos report heatmap`)
+//!
+//! Aggregates the Parquet archive written by `--export-parquet` into a
+//! ports × hosts matrix. Each cell is how often the port was open on the
+//! host: open observations divided by the scans of that host in the window.
+//! Ports are ordered by how many hosts expose them, so the services most
+//! exposed across the estate come first.
+
+use super::parquet::History;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// One port row of the matrix
+#[derive(Debug, Clone, Default)]
+struct PortRow {
+    /// Scans each host had the port open in
+    open: BTreeMap<String, usize>,
+    /// Times each service name was reported on the port
+    services: BTreeMap<String, usize>,
+}
+
+/// A port column label and its exposure, as written to JSON
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PortExposure {
+    pub port: u16,
+    pub protocol: String,
+    pub service: Option<String>,
+    pub hosts_exposed: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct HostRow {
+    host: String,
+    scans: usize,
+    /// Open frequency per port, `PORT/PROTOCOL` keys
+    open: BTreeMap<String, f64>,
+}
+
+/// Ports × hosts open frequencies over a time window
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    /// First scan aggregated; `None` when there were none
+    since: Option<DateTime<Utc>>,
+    /// Scans of each host in the window
+    scans: BTreeMap<String, usize>,
+    ports: BTreeMap<(u16, String), PortRow>,
+}
+
+impl Heatmap {
+    /// Aggregate the archive under `dir` from `since` on, only the scans of
+    /// `campaign` if one is given
+    pub fn from_archive(dir: &Path, since: DateTime<Utc>, campaign: Option<&str>) -> io::Result<Self> {
+        Ok(Self::from_history(&History::load(dir, since, campaign)?))
+    }
+
+    pub fn from_history(history: &History) -> Self {
+        let since = history.hosts.iter().map(|scan| scan.scanned_at).min();
+        let mut heatmap = Self { since, scans: BTreeMap::new(), ports: BTreeMap::new() };
+        for scan in &history.hosts {
+            *heatmap.scans.entry(scan.host.clone()).or_default() += 1;
+        }
+        for observation in history.ports.iter().filter(|o| o.is_open()) {
+            let entry = heatmap.ports.entry((observation.port, observation.protocol.clone())).or_default();
+            *entry.open.entry(observation.host.clone()).or_default() += 1;
+            if let Some(service) = &observation.service {
+                *entry.services.entry(service.clone()).or_default() += 1;
+            }
+        }
+        heatmap
+    }
+
+    /// Hosts scanned in the window, in address order
+    pub fn hosts(&self) -> Vec<&str> {
+        let mut hosts: Vec<&str> = self.scans.keys().map(String::as_str).collect();
+        hosts.sort_by_key(|host| (host.parse::<IpAddr>().ok(), host.to_string()));
+        hosts
+    }
+
+    /// Ports seen open, most exposed first: by hosts exposing them, then
+    /// open observations, then port number
+    pub fn ports(&self) -> Vec<PortExposure> {
+        let mut ports: Vec<(usize, PortExposure)> = self
+            .ports
+            .iter()
+            .map(|((port, protocol), row)| {
+                let service = row.services.iter().max_by_key(|(_, count)| **count).map(|(name, _)| name.clone());
+                let exposure = PortExposure { port: *port, protocol: protocol.clone(), service, hosts_exposed: row.open.len() };
+                (row.open.values().sum(), exposure)
+            })
+            .collect();
+        ports.sort_by(|(a_seen, a), (b_seen, b)| {
+            b.hosts_exposed.cmp(&a.hosts_exposed).then(b_seen.cmp(a_seen)).then((a.port, &a.protocol).cmp(&(b.port, &b.protocol)))
+        });
+        ports.into_iter().map(|(_, exposure)| exposure).collect()
+    }
+
+    /// Fraction of `host`'s scans in which `port` was open, 0.0 to 1.0
+    pub fn frequency(&self, host: &str, port: u16, protocol: &str) -> f64 {
+        let scans = self.scans.get(host).copied().unwrap_or(0);
+        let open = self
+            .ports
+            .get(&(port, protocol.to_string()))
+            .and_then(|row| row.open.get(host))
+            .copied()
+            .unwrap_or(0);
+        if scans == 0 {
+            0.0
+        } else {
+            (open as f64 / scans as f64).min(1.0)
+        }
+    }
+
+    /// One row per host, one column per port; empty cells were never open
+    pub fn to_csv(&self) -> io::Result<String> {
+        let ports = self.ports();
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut header = vec!["host".to_string(), "scans".to_string()];
+        header.extend(ports.iter().map(label));
+        writer.write_record(&header)?;
+        for host in self.hosts() {
+            let mut record = vec![host.to_string(), self.scans[host].to_string()];
+            record.extend(ports.iter().map(|p| match self.frequency(host, p.port, &p.protocol) {
+                f if f > 0.0 => format!("{:.2}", f),
+                _ => String::new(),
+            }));
+            writer.write_record(&record)?;
+        }
+        let bytes = writer.into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let ports = self.ports();
+        let hosts: Vec<HostRow> = self
+            .hosts()
+            .into_iter()
+            .map(|host| HostRow {
+                host: host.to_string(),
+                scans: self.scans[host],
+                open: ports
+                    .iter()
+                    .map(|p| (label(p), self.frequency(host, p.port, &p.protocol)))
+                    .filter(|(_, f)| *f > 0.0)
+                    .collect(),
+            })
+            .collect();
+        serde_json::to_string_pretty(&serde_json::json!({
+            "since": self.since,
+            "ports": ports,
+            "hosts": hosts,
+        }))
+    }
+
+    /// Standalone page; cell shading follows the open frequency
+    pub fn to_html(&self) -> String {
+        let ports = self.ports();
+        let mut html = String::from(concat!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Phobos port exposure</title>\n",
+            "<style>body{font-family:sans-serif}table{border-collapse:collapse}",
+            "th,td{border:1px solid #ccc;padding:4px 6px;text-align:center;font-size:12px}",
+            "th.host{text-align:left}</style></head><body>\n",
+        ));
+        let _ = writeln!(
+            html,
+            "<h1>Port exposure{}</h1>\n<p>{} hosts, {} ports seen open</p>\n<table>",
+            self.since.map(|since| format!(" since {}", since.format("%Y-%m-%d %H:%M UTC"))).unwrap_or_default(),
+            self.scans.len(),
+            ports.len()
+        );
+        html.push_str("<tr><th class=\"host\">host</th><th>scans</th>");
+        for port in &ports {
+            let service = port.service.as_deref().map(|s| format!("<br>{}", escape_html(s))).unwrap_or_default();
+            let _ = write!(html, "<th title=\"{} hosts\">{}{}</th>", port.hosts_exposed, escape_html(&label(port)), service);
+        }
+        html.push_str("</tr>\n");
+        for host in self.hosts() {
+            let _ = write!(html, "<tr><th class=\"host\">{}</th><td>{}</td>", escape_html(host), self.scans[host]);
+            for port in &ports {
+                match self.frequency(host, port.port, &port.protocol) {
+                    f if f > 0.0 => {
+                        let _ = write!(html, "<td style=\"background:rgba(200,30,30,{:.2})\">{:.0}%</td>", 0.15 + 0.85 * f, f * 100.0);
+                    }
+                    _ => html.push_str("<td></td>"),
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n</body></html>\n");
+        html
+    }
+}
+
+fn label(port: &PortExposure) -> String {
+    format!("{}/{}", port.port, port.protocol)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::{PortResult, PortState, Protocol};
+    use crate::output::exports::ExportedHost;
+    use crate::output::parquet::export;
+    use crate::output::provenance::Provenance;
+    use std::time::Duration;
+
+    #[test]
+    fn test_heatmap_from_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let scan = |host: &str, open: &[u16]| {
+            let results: Vec<PortResult> = [22, 80, 443]
+                .iter()
+                .map(|&port| {
+                    let state = if open.contains(&port) { PortState::Open } else { PortState::Closed };
+                    let mut result = PortResult::new(port, Protocol::Tcp, state);
+                    if port == 22 {
+                        result.service = Some("ssh".to_string());
+                    }
+                    result
+                })
+                .collect();
+            let provenance = Provenance::collect(&ScanConfig::default());
+            let scanned = ExportedHost { address: host.parse().unwrap(), port_results: results, duration: Duration::from_secs(1) };
+            export(dir.path(), &[scanned], &provenance).unwrap();
+        };
+        scan("192.0.2.10", &[22, 80]);
+        scan("192.0.2.10", &[22]);
+        scan("192.0.2.9", &[22]);
+
+        let heatmap = Heatmap::from_archive(dir.path(), Utc::now() - chrono::Duration::days(30), None).unwrap();
+        assert_eq!(heatmap.hosts(), ["192.0.2.9", "192.0.2.10"]);
+        let ports = heatmap.ports();
+        assert_eq!(ports.iter().map(|p| p.port).collect::<Vec<_>>(), [22, 80]);
+        assert_eq!((ports[0].hosts_exposed, ports[0].service.as_deref()), (2, Some("ssh")));
+        assert_eq!(heatmap.frequency("192.0.2.10", 80, "tcp"), 0.5);
+
+        let csv = heatmap.to_csv().unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), ["host,scans,22/tcp,80/tcp", "192.0.2.9,1,1.00,", "192.0.2.10,2,1.00,0.50"]);
+        assert!(heatmap.to_json().unwrap().contains("\"80/tcp\": 0.5"));
+        assert!(heatmap.to_html().contains("<th class=\"host\">192.0.2.10</th><td>2</td>"));
+
+        let later = Heatmap::from_archive(dir.path(), Utc::now() + chrono::Duration::days(1), None).unwrap();
+        assert!(later.hosts().is_empty());
+    }
+}
@@ -1,18 +1,47 @@
 //! Output formatting and management
 
-use crate::scanner::ScanResult;
+pub mod accessible;
+#[cfg(feature = "parquet")]
+pub mod campaign;
+pub mod compliance;
+#[cfg(feature = "intelligence")]
+pub mod eventlog;
+pub mod exports;
+pub mod filter;
+#[cfg(feature = "parquet")]
+pub mod heatmap;
+#[cfg(feature = "intelligence")]
+pub mod findings;
+pub mod metasploit;
+pub mod notify;
+pub mod ocsf;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod provenance;
+#[cfg(feature = "web")]
+pub mod screenshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stix;
+pub mod theme;
+pub mod workspace;
+
+use crate::scanner::{HostResult, ScanResult};
+use crate::utils::address_class::AddressBreakdown;
 use crate::network::PortResult;
-use crate::network::{PortState, Protocol};
+use crate::network::{anomaly::Anomaly, Confidence, Evidence, PortState, Protocol};
+use provenance::Provenance;
+use theme::{Paint, Role};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{self, Write};
-use std::time::Duration;
-use tokio::sync::broadcast;
 use chrono::{DateTime, Utc};
 use quick_xml::Writer;
-use quick_xml::events::{Event, BytesEnd, BytesStart};
+use quick_xml::events::{Event, BytesEnd, BytesStart, BytesText};
 use std::io::Cursor;
 
+pub use notify::{NotificationManager, NotificationType};
+
 /// Output format options
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OutputFormat {
@@ -23,52 +52,9 @@ pub enum OutputFormat {
     Nmap,
     Greppable,
     NmapXml,
-}
-
-/// Real-time notification types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum NotificationType {
-    PortFound { target: String, port: u16, service: Option<String> },
-    ScanProgress { target: String, completed: usize, total: usize },
-    ScanComplete { target: String, duration: Duration, open_ports: usize },
-    Error { target: String, error: String },
-    ServiceDetected { target: String, port: u16, service: String, version: Option<String> },
-    VulnerabilityFound { target: String, port: u16, vulnerability: String },
-}
-
-/// Real-time notification manager
-#[derive(Debug, Clone)]
-pub struct NotificationManager {
-    sender: broadcast::Sender<NotificationType>,
-    enabled: bool,
-}
-
-impl NotificationManager {
-    pub fn new(buffer_size: usize) -> Self {
-        let (sender, _) = broadcast::channel(buffer_size);
-        Self {
-            sender,
-            enabled: true,
-        }
-    }
-    
-    pub fn subscribe(&self) -> broadcast::Receiver<NotificationType> {
-        self.sender.subscribe()
-    }
-    
-    pub async fn notify(&self, notification: NotificationType) {
-        if self.enabled {
-            let _ = self.sender.send(notification);
-        }
-    }
-    
-    pub fn enable(&mut self) {
-        self.enabled = true;
-    }
-    
-    pub fn disable(&mut self) {
-        self.enabled = false;
-    }
+    MetasploitXml,
+    Stix,
+    Ocsf,
 }
 
 impl std::str::FromStr for OutputFormat {
@@ -83,6 +69,9 @@ impl std::str::FromStr for OutputFormat {
             "nmap" => Ok(OutputFormat::Nmap),
             "greppable" | "grep" => Ok(OutputFormat::Greppable),
             "nmapxml" | "nmap-xml" => Ok(OutputFormat::NmapXml),
+            "msf" | "metasploit" => Ok(OutputFormat::MetasploitXml),
+            "stix" => Ok(OutputFormat::Stix),
+            "ocsf" => Ok(OutputFormat::Ocsf),
             _ => Err(format!("Unknown output format: {}", s)),
         }
     }
@@ -97,6 +86,10 @@ pub struct OutputConfig {
     pub verbose: bool,
     pub show_closed: bool,
     pub show_filtered: bool,
+    /// Only ports matching this `--filter` expression are written
+    pub filter: Option<filter::ResultFilter>,
+    /// `--accessible`: plain tables without emoji
+    pub accessible: bool,
 }
 
 impl Default for OutputConfig {
@@ -108,6 +101,8 @@ impl Default for OutputConfig {
             verbose: false,
             show_closed: false,
             show_filtered: false,
+            filter: None,
+            accessible: false,
         }
     }
 }
@@ -124,14 +119,26 @@ impl OutputManager {
     
     /// Write scan results
     pub fn write_results(&self, results: &ScanResult) -> io::Result<()> {
+        let filtered;
+        let results = match &self.config.filter {
+            Some(filter) => {
+                filtered = filter.apply_to_scan(results);
+                &filtered
+            }
+            None => results,
+        };
+        let provenance = Provenance::collect(&results.config);
         let output = match self.config.format {
-            OutputFormat::Text => self.format_text(results),
-            OutputFormat::Json => self.format_json(results)?,
-            OutputFormat::Xml => self.format_xml(results),
-            OutputFormat::Csv => self.format_csv(results),
-            OutputFormat::Nmap => self.format_nmap(results),
-            OutputFormat::Greppable => self.format_greppable(results),
-            OutputFormat::NmapXml => self.format_nmap_xml(results)?,
+            OutputFormat::Text => self.format_text(results, &provenance),
+            OutputFormat::Json => self.format_json(results, &provenance)?,
+            OutputFormat::Xml => self.format_xml(results, &provenance),
+            OutputFormat::Csv => self.format_csv(results, &provenance),
+            OutputFormat::Nmap => self.format_nmap(results, &provenance),
+            OutputFormat::Greppable => self.format_greppable(results, &provenance),
+            OutputFormat::NmapXml => self.format_nmap_xml(results, &provenance)?,
+            OutputFormat::MetasploitXml => self.format_msf_xml(results, &provenance)?,
+            OutputFormat::Stix => self.format_stix(results, &provenance)?,
+            OutputFormat::Ocsf => self.format_ocsf(results, &provenance)?,
         };
         
         match &self.config.file {
@@ -148,19 +155,34 @@ impl OutputManager {
     }
     
     /// Format results as text
-    fn format_text(&self, results: &ScanResult) -> String {
+    fn format_text(&self, results: &ScanResult, provenance: &Provenance) -> String {
         let mut output = String::new();
         
         // Header removed - will be replaced with better implementation
         output.push_str("\n");
         
+        if self.config.accessible {
+            let shown: Vec<_> = results.port_results.iter()
+                .filter(|pr| match pr.state {
+                    PortState::Open => true,
+                    PortState::Closed => self.config.show_closed,
+                    PortState::Filtered | PortState::OpenFiltered | PortState::ClosedFiltered => self.config.show_filtered,
+                    PortState::Unfiltered => false,
+                })
+                .collect();
+            output.push_str(&accessible::port_table(&shown));
+            output.push('\n');
+            output.push_str(&provenance.comment_block("# "));
+            return output;
+        }
+        
         // Open ports
         let open_port_results: Vec<_> = results.port_results.iter()
             .filter(|pr| matches!(pr.state, crate::network::PortState::Open))
             .collect();
         
         if !open_port_results.is_empty() {
-            output.push_str(&self.colorize("🟢 OPEN PORTS:\n", "neon_green"));
+            output.push_str(&self.paint("🟢 OPEN PORTS:\n", Role::Open));
             for port_result in open_port_results {
                 let service = port_result.service.as_deref().unwrap_or("unknown");
                 let protocol = match port_result.protocol {
@@ -175,7 +197,7 @@ impl OutputManager {
                     service,
                     port_result.response_time.as_millis()
                 );
-                output.push_str(&self.colorize(&line, "neon_green"));
+                output.push_str(&self.paint(&line, Role::Open));
             }
             output.push('\n');
         }
@@ -186,7 +208,7 @@ impl OutputManager {
             .collect();
         
         if self.config.show_closed && !closed_port_results.is_empty() {
-            output.push_str(&self.colorize("🔴 CLOSED PORTS:\n", "gray"));
+            output.push_str(&self.paint("🔴 CLOSED PORTS:\n", Role::Muted));
             for port_result in closed_port_results {
                 let protocol = match port_result.protocol {
                     Protocol::Tcp => "tcp",
@@ -194,7 +216,7 @@ impl OutputManager {
                     _ => "unknown",
                 };
                 let line = format!("  {}/{}\n", port_result.port, protocol);
-                output.push_str(&self.colorize(&line, "gray"));
+                output.push_str(&self.paint(&line, Role::Muted));
             }
             output.push('\n');
         }
@@ -205,7 +227,7 @@ impl OutputManager {
             .collect();
         
         if self.config.show_filtered && !filtered_port_results.is_empty() {
-            output.push_str(&self.colorize("🟡 FILTERED PORTS:\n", "gray"));
+            output.push_str(&self.paint("🟡 FILTERED PORTS:\n", Role::Muted));
             for port_result in filtered_port_results {
                 let protocol = match port_result.protocol {
                     Protocol::Tcp => "tcp",
@@ -213,44 +235,73 @@ impl OutputManager {
                     _ => "unknown",
                 };
                 let line = format!("  {}/{}\n", port_result.port, protocol);
-                output.push_str(&self.colorize(&line, "gray"));
+                output.push_str(&self.paint(&line, Role::Muted));
             }
             output.push('\n');
         }
         
         // Statistics and summary removed as requested
         
+        output.push_str(&self.paint(&provenance.comment_block("# "), Role::Muted));
         output
     }
     
     /// Format results as JSON
-    fn format_json(&self, results: &ScanResult) -> io::Result<String> {
-        let json_result = JsonScanResult::from(results);
+    fn format_json(&self, results: &ScanResult, provenance: &Provenance) -> io::Result<String> {
+        let mut json_result = JsonScanResult::from(results);
+        json_result.provenance = Some(provenance.clone());
         serde_json::to_string_pretty(&json_result)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
     }
     
     /// Format results as XML
-    fn format_xml(&self, results: &ScanResult) -> String {
+    fn format_xml(&self, results: &ScanResult, provenance: &Provenance) -> String {
         let mut xml = String::new();
         xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
         xml.push_str("<scanresult>\n");
         xml.push_str(&format!("  <target>{}</target>\n", results.target));
+        if let Some(class) = results.address_class() {
+            xml.push_str(&format!("  <address_class>{}</address_class>\n", class));
+        }
+        if !results.hosts.is_empty() {
+            xml.push_str("  <hosts>\n");
+            for host in &results.hosts {
+                xml.push_str(&format!(
+                    "    <host address=\"{}\" address_class=\"{}\" open_ports=\"{}\"/>\n",
+                    host.host,
+                    host.address_class(),
+                    host.open_ports.len()
+                ));
+            }
+            xml.push_str("  </hosts>\n");
+        }
+        if let Some(uptime) = results.timestamp_analysis.as_ref().and_then(|ts| ts.uptime_estimate) {
+            xml.push_str(&format!("  <uptime seconds=\"{}\"/>\n", uptime.as_secs()));
+        }
         xml.push_str(&format!("  <duration>{:.2}</duration>\n", results.duration.as_secs_f64()));
         xml.push_str(&format!("  <scanrate>{:.2}</scanrate>\n", results.scan_rate()));
         
         xml.push_str("  <ports>\n");
-        for port_result in &results.port_results {
+        for (host, port_result) in host_ports(results) {
             if matches!(port_result.state, crate::network::PortState::Open) {
+                let version = port_result.version.as_deref()
+                    .map(|version| format!(" version=\"{}\"", quick_xml::escape::escape(version)))
+                    .unwrap_or_default();
+                // Ports carry their host when the scan covered several
+                let host = host.filter(|_| !results.hosts.is_empty())
+                    .map(|host| format!(" host=\"{}\"", host.host))
+                    .unwrap_or_default();
                 xml.push_str(&format!(
-                    "    <port number=\"{}\" protocol=\"{}\" state=\"open\" service=\"{}\"/>\n",
+                    "    <port{} number=\"{}\" protocol=\"{}\" state=\"open\" service=\"{}\"{}/>\n",
+                    host,
                     port_result.port,
                     match port_result.protocol {
                         Protocol::Tcp => "tcp",
                         Protocol::Udp => "udp",
                         _ => "unknown",
                     },
-                    port_result.service.as_deref().unwrap_or("unknown")
+                    port_result.service.as_deref().unwrap_or("unknown"),
+                    version
                 ));
             }
         }
@@ -263,20 +314,62 @@ impl OutputManager {
         xml.push_str(&format!("    <errors>{}</errors>\n", results.stats.errors));
         xml.push_str("  </statistics>\n");
         
+        let env = &provenance.environment;
+        xml.push_str(&format!(
+            "  <provenance run_id=\"{}\" scanner=\"{}\" version=\"{}\" git_commit=\"{}\" generated_at=\"{}\">\n",
+            provenance.run_id,
+            provenance.scanner,
+            provenance.version,
+            quick_xml::escape::escape(&provenance.git_commit),
+            provenance.generated_at.to_rfc3339()
+        ));
+        xml.push_str(&format!("    <command_line>{}</command_line>\n", quick_xml::escape::escape(&provenance.command_line.join(" "))));
+        if let Some(seed) = provenance.seed {
+            xml.push_str(&format!("    <seed>{}</seed>\n", seed));
+        }
+        xml.push_str(&format!("    <timing_profile>{}</timing_profile>\n", provenance.timing_profile));
+        xml.push_str(&format!(
+            "    <environment os=\"{}\" arch=\"{}\" kernel=\"{}\" hostname=\"{}\" cpus=\"{}\" fingerprint=\"{}\"/>\n",
+            env.os,
+            env.arch,
+            quick_xml::escape::escape(env.kernel.as_deref().unwrap_or_default()),
+            quick_xml::escape::escape(env.hostname.as_deref().unwrap_or_default()),
+            env.cpus,
+            env.fingerprint
+        ));
+        xml.push_str(&format!("    <config>{}</config>\n", quick_xml::escape::escape(&provenance.config.to_string())));
+        if let Some(route) = &provenance.route {
+            xml.push_str(&format!(
+                "    <route interface=\"{}\" source=\"{}\" gateway=\"{}\" mtu=\"{}\"/>\n",
+                quick_xml::escape::escape(route.interface.as_deref().unwrap_or_default()),
+                route.source.map(|s| s.to_string()).unwrap_or_default(),
+                route.gateway.map(|g| g.to_string()).unwrap_or_default(),
+                route.mtu
+            ));
+        }
+        xml.push_str("  </provenance>\n");
+        
         xml.push_str("</scanresult>\n");
         xml
     }
     
     /// Format results as CSV
-    fn format_csv(&self, results: &ScanResult) -> String {
-        let mut csv = String::new();
-        csv.push_str("target,port,protocol,state,service,response_time_ms\n");
+    fn format_csv(&self, results: &ScanResult, provenance: &Provenance) -> String {
+        let mut csv = provenance.comment_block("# ");
+        csv.push_str("target,port,protocol,state,service,response_time_ms,address_class\n");
+        let target_class = results.address_class().map(|c| c.name()).unwrap_or_default();
+        // Each row names the host the port belongs to, not the scanned range
+        let row_host = |host: Option<&HostResult>| match host {
+            Some(host) => (host.host.to_string(), host.address_class().name()),
+            None => (results.target.clone(), target_class),
+        };
         
-        for port_result in &results.port_results {
+        for (host, port_result) in host_ports(results) {
             if matches!(port_result.state, crate::network::PortState::Open) {
+                let (address, class) = row_host(host);
                 csv.push_str(&format!(
-                    "{},{},{},open,{},{}\n",
-                    results.target,
+                    "{},{},{},open,{},{},{}\n",
+                    address,
                     port_result.port,
                     match port_result.protocol {
                         Protocol::Tcp => "tcp",
@@ -284,23 +377,26 @@ impl OutputManager {
                         _ => "unknown",
                     },
                     port_result.service.as_deref().unwrap_or("unknown"),
-                    port_result.response_time.as_millis().to_string()
+                    port_result.response_time.as_millis(),
+                    class
                 ));
             }
         }
         
         if self.config.show_closed {
-            for port_result in &results.port_results {
+            for (host, port_result) in host_ports(results) {
                 if matches!(port_result.state, crate::network::PortState::Closed) {
+                    let (address, class) = row_host(host);
                     csv.push_str(&format!(
-                        "{},{},{},closed,,\n",
-                        results.target,
+                        "{},{},{},closed,,,{}\n",
+                        address,
                         port_result.port,
                         match port_result.protocol {
                             Protocol::Tcp => "tcp",
                             Protocol::Udp => "udp",
                             _ => "unknown",
-                        }
+                        },
+                        class
                     ));
                 }
             }
@@ -310,7 +406,7 @@ impl OutputManager {
     }
     
     /// Format results in Nmap-compatible format
-    fn format_nmap(&self, results: &ScanResult) -> String {
+    fn format_nmap(&self, results: &ScanResult, provenance: &Provenance) -> String {
         let mut output = String::new();
         
         output.push_str(&format!("# Phobos scan report for {}\n", results.target));
@@ -319,6 +415,8 @@ impl OutputManager {
         output.push_str(&format!("# {} ports scanned in {:.2} seconds\n\n", 
             results.open_ports.len() + results.closed_ports.len() + results.filtered_ports.len(),
             results.duration.as_secs_f64()));
+        output.push_str(&provenance.comment_block("# "));
+        output.push('\n');
         
         let open_port_results: Vec<_> = results.port_results.iter()
             .filter(|pr| matches!(pr.state, crate::network::PortState::Open))
@@ -332,12 +430,15 @@ impl OutputManager {
                     Protocol::Udp => "udp",
                     _ => "unknown",
                 };
-                output.push_str(&format!(
-                    "{}/{:<6} open  {}\n",
+                let line = format!(
+                    "{}/{:<6} open  {:<15} {}",
                     port_result.port,
                     protocol,
-                    port_result.service.as_deref().unwrap_or("unknown")
-                ));
+                    port_result.service.as_deref().unwrap_or("unknown"),
+                    port_result.version.as_deref().unwrap_or("")
+                );
+                output.push_str(line.trim_end());
+                output.push('\n');
             }
         }
         
@@ -346,8 +447,8 @@ impl OutputManager {
     }
     
     /// Format results in greppable format
-    fn format_greppable(&self, results: &ScanResult) -> String {
-        let mut output = String::new();
+    fn format_greppable(&self, results: &ScanResult, provenance: &Provenance) -> String {
+        let mut output = provenance.comment_block("# ");
         
         for port_result in &results.port_results {
             if matches!(port_result.state, crate::network::PortState::Open) {
@@ -358,12 +459,13 @@ impl OutputManager {
                 };
                 let service = port_result.service.as_deref().unwrap_or("unknown");
                 output.push_str(&format!(
-                    "Host: {} () Ports: {}/{}/open/{}//{}/\n",
+                    "Host: {} () Ports: {}/{}/open/{}//{}//{}/\n",
                     results.target,
                     port_result.port,
                     protocol,
                     protocol,
-                    service
+                    service,
+                    port_result.version.as_deref().unwrap_or("")
                 ));
             }
         }
@@ -372,7 +474,7 @@ impl OutputManager {
     }
     
     /// Format results in Nmap XML format
-    fn format_nmap_xml(&self, results: &ScanResult) -> io::Result<String> {
+    fn format_nmap_xml(&self, results: &ScanResult, provenance: &Provenance) -> io::Result<String> {
         let mut buffer = Vec::new();
         let mut writer = Writer::new(Cursor::new(&mut buffer));
         
@@ -383,7 +485,8 @@ impl OutputManager {
         // Root nmaprun element
         let mut nmaprun = BytesStart::new("nmaprun");
         nmaprun.push_attribute(("scanner", "phobos"));
-        nmaprun.push_attribute(("args", "phobos"));
+        let args = provenance.command_line.join(" ");
+        nmaprun.push_attribute(("args", args.as_str()));
         let start_time = chrono::Utc::now().timestamp().to_string();
          let start_str = chrono::Utc::now().format("%a %b %d %H:%M:%S %Y").to_string();
          nmaprun.push_attribute(("start", start_time.as_str()));
@@ -391,6 +494,8 @@ impl OutputManager {
         nmaprun.push_attribute(("version", env!("CARGO_PKG_VERSION")));
         writer.write_event(Event::Start(nmaprun))
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        writer.write_event(Event::Comment(BytesText::from_escaped(provenance.xml_comment())))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
         
         // Scaninfo
         let mut scaninfo = BytesStart::new("scaninfo");
@@ -459,6 +564,9 @@ impl OutputManager {
              if let Some(service) = &port_result.service {
                  let mut service_elem = BytesStart::new("service");
                  service_elem.push_attribute(("name", service.as_str()));
+                 if let Some(version) = &port_result.version {
+                     service_elem.push_attribute(("version", version.as_str()));
+                 }
                  service_elem.push_attribute(("method", "probed"));
                 writer.write_event(Event::Empty(service_elem))
                     .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
@@ -513,34 +621,69 @@ impl OutputManager {
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
     }
     
-    /// Apply color formatting if enabled
-    fn colorize(&self, text: &str, color: &str) -> String {
+    /// Formats that describe hosts by address need IP targets
+    fn exported_hosts(results: &ScanResult, format: &str) -> io::Result<exports::ScanExports> {
+        let exports = exports::ScanExports::from_scan(results);
+        if exports.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} output needs an IP address, got {}", format, results.target),
+            ));
+        }
+        Ok(exports)
+    }
+    
+    /// Format results as Metasploit XML for `db_import`
+    fn format_msf_xml(&self, results: &ScanResult, provenance: &Provenance) -> io::Result<String> {
+        let exports = Self::exported_hosts(results, "Metasploit")?;
+        metasploit::to_msf_xml(&exports.msf_hosts(), provenance)
+    }
+    
+    /// Format results as a STIX 2.1 bundle
+    fn format_stix(&self, results: &ScanResult, provenance: &Provenance) -> io::Result<String> {
+        let exports = Self::exported_hosts(results, "STIX")?;
+        Ok(exports.to_stix_bundle(&Utc::now(), provenance))
+    }
+    
+    /// Format results as newline-delimited OCSF events
+    fn format_ocsf(&self, results: &ScanResult, provenance: &Provenance) -> io::Result<String> {
+        let exports = Self::exported_hosts(results, "OCSF")?;
+        Ok(exports.to_ocsf_ndjson(&Utc::now(), provenance))
+    }
+    
+    /// Paint `text` in the current theme if colour is enabled
+    fn paint(&self, text: &str, role: Role) -> String {
         if !self.config.colored {
             return text.to_string();
         }
-        
-        let color_code = match color {
-            "red" => "\x1b[31m",
-            "green" => "\x1b[32m",
-            "neon_green" => "\x1b[38;2;57;255;20m", // Neon green RGB(57,255,20)
-            "yellow" => "\x1b[33m",
-            "blue" => "\x1b[34m",
-            "magenta" => "\x1b[35m",
-            "cyan" => "\x1b[36m",
-            "white" => "\x1b[37m",
-            "gray" => "\x1b[38;2;128;128;128m", // Gri RGB(128,128,128)
-            "bold" => "\x1b[1m",
-            _ => "",
-        };
-        
-        format!("{}{}{}", color_code, text, "\x1b[0m")
+        text.paint(role).to_string()
+    }
+}
+
+/// Every port result with the host it belongs to; `None` for a scan
+/// without per-host results, whose ports all belong to its target
+fn host_ports(results: &ScanResult) -> Vec<(Option<&HostResult>, &PortResult)> {
+    if results.hosts.is_empty() {
+        return results.port_results.iter().map(|port| (None, port)).collect();
     }
+    results.hosts.iter()
+        .flat_map(|host| host.port_results.iter().map(move |port| (Some(host), port)))
+        .collect()
 }
 
 /// JSON-serializable scan result
 #[derive(Debug, Serialize, Deserialize)]
 struct JsonScanResult {
     target: String,
+    /// private, public, cgnat, ... when the target is an IP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address_class: Option<String>,
+    /// Each host of a multi-host scan, with its address class
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hosts: Vec<JsonHost>,
+    /// Hosts per address class
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    address_classes: std::collections::BTreeMap<String, usize>,
     scan_time: DateTime<Utc>,
     duration_seconds: f64,
     scan_rate: f64,
@@ -548,6 +691,20 @@ struct JsonScanResult {
     closed_ports: Vec<JsonPortResult>,
     filtered_ports: Vec<JsonPortResult>,
     statistics: JsonScanStats,
+    /// Uptime guess from TCP timestamps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uptime_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp_hz: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<Provenance>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonHost {
+    address: String,
+    address_class: String,
+    open_ports: Vec<u16>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -557,6 +714,15 @@ struct JsonPortResult {
     state: String,
     service: Option<String>,
     response_time_ms: Option<u64>,
+    #[serde(default)]
+    confidence: Confidence,
+    /// One probe saw the port open and another closed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    disputed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    evidence: Vec<Evidence>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    anomalies: Vec<Anomaly>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -572,6 +738,18 @@ impl From<&ScanResult> for JsonScanResult {
     fn from(result: &ScanResult) -> Self {
         Self {
             target: result.target.clone(),
+            address_class: result.address_class().map(|c| c.name().to_string()),
+            hosts: result.hosts.iter()
+                .map(|host| JsonHost {
+                    address: host.host.to_string(),
+                    address_class: host.address_class().name().to_string(),
+                    open_ports: host.open_ports.clone(),
+                })
+                .collect(),
+            address_classes: AddressBreakdown::from_addresses(result.hosts.iter().map(|host| host.host))
+                .iter()
+                .map(|(class, count)| (class.name().to_string(), count))
+                .collect(),
             scan_time: chrono::Utc::now(),
             duration_seconds: result.duration.as_secs_f64(),
             scan_rate: result.scan_rate(),
@@ -585,6 +763,11 @@ impl From<&ScanResult> for JsonScanResult {
                 .filter(|pr| matches!(pr.state, crate::network::PortState::Filtered | crate::network::PortState::OpenFiltered | crate::network::PortState::ClosedFiltered))
                 .map(JsonPortResult::from).collect(),
             statistics: JsonScanStats::from(&result.stats),
+            uptime_seconds: result.timestamp_analysis.as_ref()
+                .and_then(|ts| ts.uptime_estimate)
+                .map(|uptime| uptime.as_secs()),
+            timestamp_hz: result.timestamp_analysis.as_ref().and_then(|ts| ts.timestamp_frequency),
+            provenance: None,
         }
     }
 }
@@ -608,6 +791,10 @@ impl From<&PortResult> for JsonPortResult {
             },
             service: port.service.clone(),
             response_time_ms: Some(port.response_time.as_millis() as u64),
+            confidence: port.confidence,
+            disputed: port.is_disputed(),
+            evidence: port.evidence.clone(),
+            anomalies: port.anomalies.clone(),
         }
     }
 }
@@ -630,6 +817,7 @@ pub struct ProgressDisplay {
     completed_ports: usize,
     start_time: std::time::Instant,
     last_update: std::time::Instant,
+    accessible: bool,
 }
 
 impl ProgressDisplay {
@@ -640,15 +828,23 @@ impl ProgressDisplay {
             completed_ports: 0,
             start_time: now,
             last_update: now,
+            accessible: false,
         }
     }
     
+    /// Print a sentence every ten seconds instead of redrawing a bar
+    pub fn accessible(mut self, accessible: bool) -> Self {
+        self.accessible = accessible;
+        self
+    }
+    
     /// Update progress and display if needed
     pub fn update(&mut self, completed: usize) {
         self.completed_ports = completed;
         
-        // Update every 100ms
-        if self.last_update.elapsed().as_millis() >= 100 {
+        // Update every 100ms, or every 10s as sentences
+        let every = if self.accessible { 10_000 } else { 100 };
+        if self.last_update.elapsed().as_millis() >= every {
             self.display();
             self.last_update = std::time::Instant::now();
         }
@@ -656,6 +852,19 @@ impl ProgressDisplay {
     
     /// Display current progress
     fn display(&self) {
+        if self.accessible {
+            let snapshot = crate::scanner::ProgressSnapshot {
+                total: self.total_ports as u64,
+                completed: self.completed_ports as u64,
+                open: 0,
+                retries: 0,
+                errors: 0,
+                elapsed: self.start_time.elapsed(),
+            };
+            println!("{}", accessible::progress_sentence(&snapshot));
+            return;
+        }
+        
         let percentage = (self.completed_ports as f64 / self.total_ports as f64) * 100.0;
         let elapsed = self.start_time.elapsed().as_secs_f64();
         let rate = self.completed_ports as f64 / elapsed;
@@ -676,6 +885,49 @@ impl ProgressDisplay {
     
     /// Finish progress display
     pub fn finish(&self) {
-        println!();
+        if !self.accessible {
+            println!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+
+    fn multi_host_scan() -> ScanResult {
+        let mut results = ScanResult::new("mixed".to_string(), ScanConfig::default());
+        for (address, port) in [("10.0.0.5", 22), ("2606:4700::7", 443)] {
+            let mut host = HostResult::new(address.parse::<std::net::IpAddr>().unwrap());
+            host.port_results.push(PortResult::new(port, Protocol::Tcp, PortState::Open));
+            host.open_ports.push(port);
+            results.push_port_result(PortResult::new(port, Protocol::Tcp, PortState::Open));
+            results.hosts.push(host);
+        }
+        results
+    }
+
+    #[test]
+    fn test_address_class_per_host() {
+        let results = multi_host_scan();
+        let output = OutputManager::new(OutputConfig { colored: false, ..OutputConfig::default() });
+        let provenance = Provenance::collect(&results.config);
+
+        let csv = output.format_csv(&results, &provenance);
+        let rows: Vec<&str> = csv.lines().filter(|line| !line.starts_with('#')).skip(1).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("10.0.0.5,22,tcp,open,") && rows[0].ends_with(",private"));
+        assert!(rows[1].starts_with("2606:4700::7,443,tcp,open,") && rows[1].ends_with(",public"));
+
+        let json: serde_json::Value = serde_json::from_str(&output.format_json(&results, &provenance).unwrap()).unwrap();
+        assert!(json.get("address_class").is_none());
+        assert_eq!(json["hosts"][1]["address"], "2606:4700::7");
+        assert_eq!(json["hosts"][1]["address_class"], "public");
+        assert_eq!(json["address_classes"]["private"], 1);
+
+        let xml = output.format_xml(&results, &provenance);
+        assert!(xml.contains("<host address=\"10.0.0.5\" address_class=\"private\" open_ports=\"1\"/>"));
+        assert!(xml.contains("<port host=\"2606:4700::7\" number=\"443\""));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,606 @@
+//! Real-time notifications
+//!
+//! [`NotificationManager`] hands each event to live subscribers and to every
+//! registered [`NotificationSink`]. A sink gets its own bounded queue and
+//! delivery task: when the queue is full the scan either waits for the sink
+//! to catch up ([`Overflow::Block`]) or the event is dropped and counted
+//! ([`Overflow::Drop`]), and failed deliveries are retried as the sink's
+//! [`Delivery`] asks. Events are only ever lost in ways that show up in
+//! [`NotificationStats`].
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use uuid::Uuid;
+
+/// Longest wait between two delivery attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Real-time notification types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NotificationType {
+    PortFound { target: String, port: u16, service: Option<String> },
+    ScanProgress { target: String, completed: usize, total: usize },
+    ScanComplete { target: String, duration: Duration, open_ports: usize },
+    Error { target: String, error: String },
+    ServiceDetected { target: String, port: u16, service: String, version: Option<String> },
+    VulnerabilityFound { target: String, port: u16, vulnerability: String },
+    /// A port `phobos watch` found open that the previous run did not
+    PortOpened { target: String, port: u16, service: Option<String> },
+    /// A port `phobos watch` found running something else than before
+    ServiceChanged { target: String, port: u16, before: String, after: String },
+}
+
+impl fmt::Display for NotificationType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let service = |service: &Option<String>| service.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default();
+        match self {
+            NotificationType::PortFound { target, port, service: s } => write!(f, "Open port {}:{}{}", target, port, service(s)),
+            NotificationType::ScanProgress { target, completed, total } => {
+                write!(f, "Scan of {}: {}/{} ports done", target, completed, total)
+            }
+            NotificationType::ScanComplete { target, duration, open_ports } => {
+                write!(f, "Scan of {} complete: {} open ports in {:.1}s", target, open_ports, duration.as_secs_f64())
+            }
+            NotificationType::Error { target, error } => write!(f, "Scan of {} failed: {}", target, error),
+            NotificationType::ServiceDetected { target, port, service, version } => match version {
+                Some(version) => write!(f, "{}:{} runs {} {}", target, port, service, version),
+                None => write!(f, "{}:{} runs {}", target, port, service),
+            },
+            NotificationType::VulnerabilityFound { target, port, vulnerability } => {
+                write!(f, "{}:{} is vulnerable: {}", target, port, vulnerability)
+            }
+            NotificationType::PortOpened { target, port, service: s } => write!(f, "New open port {}:{}{}", target, port, service(s)),
+            NotificationType::ServiceChanged { target, port, before, after } => {
+                write!(f, "{}:{} changed from {} to {}", target, port, before, after)
+            }
+        }
+    }
+}
+
+/// Body a webhook receives for each notification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyFormat {
+    /// The [`Notification`] itself
+    #[default]
+    Json,
+    /// A Slack incoming webhook message
+    Slack,
+    /// A Discord webhook message
+    Discord,
+}
+
+impl NotifyFormat {
+    pub fn payload(&self, notification: &Notification) -> serde_json::Value {
+        let text = format!("phobos: {}", notification.event);
+        match self {
+            NotifyFormat::Json => serde_json::to_value(notification).unwrap_or_default(),
+            NotifyFormat::Slack => serde_json::json!({ "text": text }),
+            NotifyFormat::Discord => serde_json::json!({ "content": text }),
+        }
+    }
+}
+
+impl std::str::FromStr for NotifyFormat {
+    type Err = crate::ScanError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(NotifyFormat::Json),
+            "slack" => Ok(NotifyFormat::Slack),
+            "discord" => Ok(NotifyFormat::Discord),
+            _ => Err(crate::ScanError::ConfigError(format!("unknown notification format `{}`; use json, slack or discord", s))),
+        }
+    }
+}
+
+/// An event as delivered to sinks
+///
+/// `id` stays the same across retries so receivers can discard duplicates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub event: NotificationType,
+}
+
+impl Notification {
+    pub fn new(event: NotificationType) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            event,
+        }
+    }
+}
+
+/// How hard a sink tries to get each event through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// One attempt; a failure is counted and the event is gone
+    AtMostOnce,
+    /// Up to `attempts` tries, waiting `backoff` before the first retry and
+    /// doubling it after each one
+    AtLeastOnce { attempts: u32, backoff: Duration },
+}
+
+/// What happens when a sink's queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for room, slowing down whoever publishes
+    Block,
+    /// Drop the event and count it
+    Drop,
+}
+
+/// Why a delivery attempt failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    /// Worth retrying, e.g. a timeout or a 5xx response
+    Failed(String),
+    /// Retrying will not help, e.g. a 4xx response
+    Rejected(String),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryError::Failed(reason) => write!(f, "failed: {}", reason),
+            DeliveryError::Rejected(reason) => write!(f, "rejected: {}", reason),
+        }
+    }
+}
+
+/// A destination for notifications
+pub trait NotificationSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn delivery(&self) -> Delivery {
+        Delivery::AtMostOnce
+    }
+
+    fn overflow(&self) -> Overflow {
+        Overflow::Block
+    }
+
+    /// Make one attempt at delivering `notification`
+    fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), DeliveryError>>;
+}
+
+/// Counts of what happened to published events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationStats {
+    pub published: u64,
+    /// Successful sink deliveries; one event counts once per sink
+    pub delivered: u64,
+    pub retries: u64,
+    /// Events a full `Overflow::Drop` queue, or a stopped sink, never took
+    pub dropped: u64,
+    /// Events a sink gave up on
+    pub failed: u64,
+    /// Events subscribers missed by falling behind
+    pub lagged: u64,
+}
+
+impl NotificationStats {
+    /// Events that did not reach a sink or subscriber
+    pub fn lost(&self) -> u64 {
+        self.dropped + self.failed + self.lagged
+    }
+}
+
+impl fmt::Display for NotificationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} published, {} delivered ({} retries), {} dropped, {} failed, {} lagged",
+            self.published, self.delivered, self.retries, self.dropped, self.failed, self.lagged
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    published: AtomicU64,
+    delivered: AtomicU64,
+    retries: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    lagged: AtomicU64,
+}
+
+impl Counters {
+    fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> NotificationStats {
+        NotificationStats {
+            published: self.published.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            lagged: self.lagged.load(Ordering::Relaxed),
+        }
+    }
+}
+
+enum Envelope {
+    Event(Arc<Notification>),
+    /// Acknowledged once everything queued before it has been handled
+    Flush(oneshot::Sender<()>),
+}
+
+#[derive(Debug, Clone)]
+struct SinkQueue {
+    name: Arc<str>,
+    overflow: Overflow,
+    queue: mpsc::Sender<Envelope>,
+}
+
+/// A live feed of events that counts what it misses
+pub struct NotificationReceiver {
+    receiver: broadcast::Receiver<NotificationType>,
+    counters: Arc<Counters>,
+}
+
+impl NotificationReceiver {
+    /// Next event, or `None` once the manager is gone
+    ///
+    /// Events skipped because this receiver fell behind are counted as
+    /// `lagged` rather than reported as an error.
+    pub async fn recv(&mut self) -> Option<NotificationType> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Counters::add(&self.counters.lagged, missed),
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Real-time notification manager
+#[derive(Debug, Clone)]
+pub struct NotificationManager {
+    sender: broadcast::Sender<NotificationType>,
+    sinks: Vec<SinkQueue>,
+    buffer_size: usize,
+    counters: Arc<Counters>,
+    enabled: bool,
+}
+
+impl NotificationManager {
+    /// A manager whose subscribers and sinks each buffer `buffer_size` events
+    pub fn new(buffer_size: usize) -> Self {
+        let buffer_size = buffer_size.max(1);
+        let (sender, _) = broadcast::channel(buffer_size);
+        Self {
+            sender,
+            sinks: Vec::new(),
+            buffer_size,
+            counters: Arc::new(Counters::default()),
+            enabled: true,
+        }
+    }
+
+    /// Deliver every event to `sink` from a task of its own
+    ///
+    /// Must be called inside a Tokio runtime.
+    pub fn with_sink<S: NotificationSink + 'static>(mut self, sink: S) -> Self {
+        let sink: Arc<dyn NotificationSink> = Arc::new(sink);
+        let (queue, receiver) = mpsc::channel(self.buffer_size);
+        self.sinks.push(SinkQueue {
+            name: Arc::from(sink.name()),
+            overflow: sink.overflow(),
+            queue,
+        });
+        tokio::spawn(run_sink(sink, receiver, Arc::clone(&self.counters)));
+        self
+    }
+
+    pub fn subscribe(&self) -> NotificationReceiver {
+        NotificationReceiver {
+            receiver: self.sender.subscribe(),
+            counters: Arc::clone(&self.counters),
+        }
+    }
+
+    /// Publish `event`, waiting for room in any full [`Overflow::Block`] sink
+    pub async fn notify(&self, event: NotificationType) {
+        let Some(notification) = self.publish(event) else { return };
+        for sink in &self.sinks {
+            let envelope = Envelope::Event(Arc::clone(&notification));
+            let queued = match sink.overflow {
+                Overflow::Block => sink.queue.send(envelope).await.is_ok(),
+                Overflow::Drop => sink.queue.try_send(envelope).is_ok(),
+            };
+            self.count_queued(&notification, sink, queued);
+        }
+    }
+
+    /// Publish `event` without waiting, from code that cannot: a full sink
+    /// drops it as if it were [`Overflow::Drop`]
+    pub fn try_notify(&self, event: NotificationType) {
+        let Some(notification) = self.publish(event) else { return };
+        for sink in &self.sinks {
+            let queued = sink.queue.try_send(Envelope::Event(Arc::clone(&notification))).is_ok();
+            self.count_queued(&notification, sink, queued);
+        }
+    }
+
+    /// Hand `event` to the subscribers; the notification for the sinks
+    fn publish(&self, event: NotificationType) -> Option<Arc<Notification>> {
+        if !self.enabled {
+            return None;
+        }
+        Counters::add(&self.counters.published, 1);
+        // Failing only means nobody is subscribed
+        let _ = self.sender.send(event.clone());
+        Some(Arc::new(Notification::new(event)))
+    }
+
+    fn count_queued(&self, notification: &Notification, sink: &SinkQueue, queued: bool) {
+        if !queued {
+            log::debug!("Notification {} dropped for {}", notification.id, sink.name);
+            Counters::add(&self.counters.dropped, 1);
+        }
+    }
+
+    /// Wait until every sink has handled the events published so far
+    pub async fn flush(&self) -> NotificationStats {
+        for sink in &self.sinks {
+            let (done, acknowledged) = oneshot::channel();
+            if sink.queue.send(Envelope::Flush(done)).await.is_ok() {
+                let _ = acknowledged.await;
+            }
+        }
+        self.stats()
+    }
+
+    pub fn stats(&self) -> NotificationStats {
+        self.counters.snapshot()
+    }
+
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
+}
+
+async fn run_sink(sink: Arc<dyn NotificationSink>, mut queue: mpsc::Receiver<Envelope>, counters: Arc<Counters>) {
+    while let Some(envelope) = queue.recv().await {
+        match envelope {
+            Envelope::Event(notification) => deliver(sink.as_ref(), &notification, &counters).await,
+            Envelope::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+async fn deliver(sink: &dyn NotificationSink, notification: &Notification, counters: &Counters) {
+    let (attempts, mut backoff) = match sink.delivery() {
+        Delivery::AtMostOnce => (1, Duration::ZERO),
+        Delivery::AtLeastOnce { attempts, backoff } => (attempts.max(1), backoff),
+    };
+
+    for attempt in 1..=attempts {
+        match sink.deliver(notification).await {
+            Ok(()) => {
+                Counters::add(&counters.delivered, 1);
+                return;
+            }
+            Err(DeliveryError::Failed(reason)) if attempt < attempts => {
+                log::debug!("{} attempt {} for notification {} failed: {}", sink.name(), attempt, notification.id, reason);
+                Counters::add(&counters.retries, 1);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => {
+                log::warn!("{} gave up on notification {} after {} attempt(s): {}", sink.name(), notification.id, attempt, e);
+                break;
+            }
+        }
+    }
+    Counters::add(&counters.failed, 1);
+}
+
+/// POSTs each notification as JSON, retrying until the endpoint accepts it
+///
+/// The notification id is sent as `Idempotency-Key`, so an endpoint that
+/// saw a delivery whose response was lost can ignore the retry. Slack and
+/// Discord get a one-line message in their own format instead of the JSON.
+#[cfg(feature = "web")]
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+    delivery: Delivery,
+    format: NotifyFormat,
+}
+
+#[cfg(feature = "web")]
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> crate::Result<Self> {
+        let url = url.into();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(crate::ScanError::ConfigError(format!("Webhook URL must be http(s): {}", url)));
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(concat!("phobos/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| crate::ScanError::NetworkError(format!("Failed to build HTTP client: {}", e)))?;
+        Ok(Self {
+            url,
+            client,
+            delivery: Delivery::AtLeastOnce {
+                attempts: 5,
+                backoff: Duration::from_millis(500),
+            },
+            format: NotifyFormat::Json,
+        })
+    }
+
+    pub fn with_format(mut self, format: NotifyFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+}
+
+#[cfg(feature = "web")]
+impl NotificationSink for WebhookSink {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn delivery(&self) -> Delivery {
+        self.delivery
+    }
+
+    fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), DeliveryError>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .post(&self.url)
+                .header("Idempotency-Key", notification.id.to_string())
+                .json(&self.format.payload(notification))
+                .send()
+                .await
+                .map_err(|e| DeliveryError::Failed(e.to_string()))?;
+            let status = response.status();
+            if status.is_success() {
+                Ok(())
+            } else if status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429 {
+                Err(DeliveryError::Failed(format!("HTTP {}", status)))
+            } else {
+                Err(DeliveryError::Rejected(format!("HTTP {}", status)))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Fails the first `failures` attempts, then records what it receives
+    struct FlakySink {
+        failures: AtomicU64,
+        received: Arc<Mutex<Vec<Uuid>>>,
+        overflow: Overflow,
+    }
+
+    impl NotificationSink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn delivery(&self) -> Delivery {
+            Delivery::AtLeastOnce { attempts: 3, backoff: Duration::from_millis(1) }
+        }
+
+        fn overflow(&self) -> Overflow {
+            self.overflow
+        }
+
+        fn deliver<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<(), DeliveryError>> {
+            Box::pin(async move {
+                let left = self.failures.load(Ordering::Relaxed);
+                if left > 0 {
+                    self.failures.store(left - 1, Ordering::Relaxed);
+                    return Err(DeliveryError::Failed("unavailable".to_string()));
+                }
+                self.received.lock().unwrap().push(notification.id);
+                Ok(())
+            })
+        }
+    }
+
+    fn port_found(port: u16) -> NotificationType {
+        NotificationType::PortFound { target: "192.0.2.1".to_string(), port, service: None }
+    }
+
+    #[test]
+    fn test_chat_payloads() {
+        let notification = Notification::new(NotificationType::PortFound {
+            target: "192.0.2.1".to_string(),
+            port: 443,
+            service: Some("https".to_string()),
+        });
+        assert_eq!(NotifyFormat::Slack.payload(&notification), serde_json::json!({ "text": "phobos: Open port 192.0.2.1:443 (https)" }));
+        assert_eq!(NotifyFormat::Discord.payload(&notification)["content"], "phobos: Open port 192.0.2.1:443 (https)");
+        assert_eq!(NotifyFormat::Json.payload(&notification)["id"], notification.id.to_string());
+
+        let complete = NotificationType::ScanComplete { target: "10.0.0.0/24".to_string(), duration: Duration::from_millis(12_340), open_ports: 3 };
+        assert_eq!(complete.to_string(), "Scan of 10.0.0.0/24 complete: 3 open ports in 12.3s");
+        assert_eq!("Discord".parse::<NotifyFormat>().unwrap(), NotifyFormat::Discord);
+        assert!("teams".parse::<NotifyFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sinks_retry_and_count_losses() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let manager = NotificationManager::new(2).with_sink(FlakySink {
+            failures: AtomicU64::new(2),
+            received: Arc::clone(&received),
+            overflow: Overflow::Block,
+        });
+        let mut subscriber = manager.subscribe();
+
+        // Four events through a queue of two: blocking keeps every one
+        for port in 1..=4 {
+            manager.notify(port_found(port)).await;
+        }
+        let stats = manager.flush().await;
+        assert_eq!(received.lock().unwrap().len(), 4);
+        assert_eq!((stats.published, stats.delivered, stats.retries, stats.failed), (4, 4, 2, 0));
+
+        // The subscriber never read and fell two events behind
+        assert!(matches!(subscriber.recv().await, Some(NotificationType::PortFound { port: 3, .. })));
+        assert_eq!(manager.stats().lagged, 2);
+
+        // A sink that cannot keep up drops instead, and says so
+        let lossy = NotificationManager::new(1).with_sink(FlakySink {
+            failures: AtomicU64::new(u64::MAX),
+            received: Arc::new(Mutex::new(Vec::new())),
+            overflow: Overflow::Drop,
+        });
+        for port in 1..=10 {
+            lossy.notify(port_found(port)).await;
+        }
+        let stats = lossy.flush().await;
+        assert!(stats.dropped > 0);
+        assert_eq!(stats.delivered, 0);
+        assert_eq!(stats.dropped + stats.failed, 10);
+        assert_eq!(stats.lost(), 10);
+
+        // try_notify never waits, so even a blocking sink drops what does not fit
+        let hurried = NotificationManager::new(1).with_sink(FlakySink {
+            failures: AtomicU64::new(0),
+            received: Arc::new(Mutex::new(Vec::new())),
+            overflow: Overflow::Block,
+        });
+        for port in 1..=10 {
+            hurried.try_notify(port_found(port));
+        }
+        let stats = hurried.flush().await;
+        assert!(stats.dropped > 0);
+        assert_eq!(stats.delivered + stats.dropped, 10);
+    }
+}
@@ -0,0 +1,145 @@
+//! OCSF 1.1 output
+//!
+//! A scanned host produces one Device Inventory Info event (class 5001)
+//! and one Network Activity "Open" event (class 4001) per open port.
+//! Events are written as newline-delimited JSON, the layout security data
+//! lakes such as Amazon Security Lake and Splunk ingest directly. All
+//! events of a report share `metadata.correlation_uid` (the provenance run
+//! id); the full provenance block is attached to the inventory event.
+
+use super::provenance::Provenance;
+use crate::network::{PortResult, PortState, Protocol};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+
+pub const OCSF_VERSION: &str = "1.1.0";
+
+const DEVICE_INVENTORY_INFO: u32 = 5001;
+const NETWORK_ACTIVITY: u32 = 4001;
+/// Device Inventory Info: Collect
+const ACTIVITY_COLLECT: u32 = 2;
+/// Network Activity: Open
+const ACTIVITY_OPEN: u32 = 1;
+const SEVERITY_INFORMATIONAL: u32 = 1;
+const STATUS_SUCCESS: u32 = 1;
+
+fn metadata(provenance: &Provenance) -> Value {
+    json!({
+        "version": OCSF_VERSION,
+        "correlation_uid": provenance.run_id,
+        "product": {
+            "name": "Phobos",
+            "vendor_name": "Phobos",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    })
+}
+
+/// Fields shared by every event; `class_uid / 1000` is the category
+fn base_event(class_uid: u32, activity_id: u32, time: &DateTime<Utc>, provenance: &Provenance) -> Value {
+    json!({
+        "class_uid": class_uid,
+        "category_uid": class_uid / 1000,
+        "activity_id": activity_id,
+        "type_uid": class_uid * 100 + activity_id,
+        "severity_id": SEVERITY_INFORMATIONAL,
+        "status_id": STATUS_SUCCESS,
+        "time": time.timestamp_millis(),
+        "metadata": metadata(provenance),
+    })
+}
+
+/// OCSF events for one scanned host
+pub fn host_events(
+    address: IpAddr,
+    port_results: &[PortResult],
+    time: &DateTime<Utc>,
+    provenance: &Provenance,
+) -> Vec<Value> {
+    let mut inventory = base_event(DEVICE_INVENTORY_INFO, ACTIVITY_COLLECT, time, provenance);
+    inventory["device"] = json!({
+        "ip": address.to_string(),
+        "type_id": 0,
+    });
+    inventory["unmapped"] = json!({ "provenance": provenance.to_json() });
+    let mut events = vec![inventory];
+
+    for result in port_results.iter().filter(|r| r.state == PortState::Open) {
+        let (protocol_name, protocol_num) = match result.protocol {
+            Protocol::Udp => ("udp", 17),
+            _ => ("tcp", 6),
+        };
+        let mut endpoint = json!({
+            "ip": address.to_string(),
+            "port": result.port,
+        });
+        if let Some(service) = &result.service {
+            endpoint["svc_name"] = json!(service);
+        }
+
+        let mut event = base_event(NETWORK_ACTIVITY, ACTIVITY_OPEN, time, provenance);
+        event["dst_endpoint"] = endpoint;
+        event["src_endpoint"] = json!({ "name": "phobos" });
+        event["connection_info"] = json!({
+            "protocol_name": protocol_name,
+            "protocol_num": protocol_num,
+            "protocol_ver_id": if address.is_ipv4() { 4 } else { 6 },
+            // Outbound: the scanner initiated the connection
+            "direction_id": 2,
+        });
+        event["duration"] = json!(result.response_time.as_millis() as u64);
+        events.push(event);
+    }
+    events
+}
+
+/// Newline-delimited OCSF events for one host
+pub fn to_ocsf_ndjson(
+    address: IpAddr,
+    port_results: &[PortResult],
+    time: &DateTime<Utc>,
+    provenance: &Provenance,
+) -> String {
+    host_events(address, port_results, time, provenance)
+        .iter()
+        .map(|event| format!("{}\n", event))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Confidence;
+    use std::time::Duration;
+
+    #[test]
+    fn test_ocsf_events() {
+        let results = vec![
+            PortResult {
+                port: 53,
+                protocol: Protocol::Udp,
+                state: PortState::Open,
+                service: Some("domain".to_string()),
+                version: None,
+                response_time: Duration::from_millis(12),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
+            },
+            PortResult::new(80, Protocol::Tcp, PortState::Filtered),
+        ];
+        let provenance = Provenance::collect(&Default::default());
+        let output = to_ocsf_ndjson("192.0.2.53".parse().unwrap(), &results, &Utc::now(), &provenance);
+        let events: Vec<Value> = output.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["type_uid"], 500102);
+        assert_eq!(events[0]["category_uid"], 5);
+        assert_eq!(events[1]["type_uid"], 400101);
+        assert_eq!(events[1]["dst_endpoint"]["port"], 53);
+        assert_eq!(events[1]["connection_info"]["protocol_num"], 17);
+        assert_eq!(events[1]["metadata"]["version"], OCSF_VERSION);
+        assert_eq!(events[1]["metadata"]["correlation_uid"], events[0]["unmapped"]["provenance"]["run_id"]);
+    }
+}
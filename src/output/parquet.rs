@@ -0,0 +1,342 @@
+//! Parquet archive of scan results (`--export-parquet`)
+//!
+//! Each scan adds one file per table under a Hive-style date partition:
+//!
+//! ```text
+//! DIR/hosts/scan_date=2024-10-17/<run id>.parquet
+//! DIR/ports/scan_date=2024-10-17/<run id>.parquet
+//! DIR/services/scan_date=2024-10-17/<run id>.parquet
+//! ```
+//!
+//! so months of campaigns can be queried in place with DuckDB or Athena,
+//! e.g. `SELECT * FROM read_parquet('DIR/ports/*/*.parquet', hive_partitioning = true)`,
+//! without loading them into a database first. Rows carry the run id from
+//! the report provenance to join the tables and trace a row to its report.
+//! The archive is also the history `phobos report` aggregates; scans run
+//! with `--campaign` carry the campaign name in every table.
+
+use super::exports::ExportedHost;
+use super::provenance::Provenance;
+use crate::network::{PortResult, PortState};
+use crate::utils::address_class::AddressClass;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::record::{Row, RowAccessor};
+use parquet::schema::parser::parse_message_type;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const HOSTS_SCHEMA: &str = "message hosts {
+    REQUIRED BYTE_ARRAY run_id (UTF8);
+    REQUIRED INT64 scanned_at (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY host (UTF8);
+    REQUIRED BYTE_ARRAY address_class (UTF8);
+    REQUIRED INT32 ports_scanned;
+    REQUIRED INT32 open_ports;
+    REQUIRED INT64 duration_ms;
+    OPTIONAL BYTE_ARRAY campaign (UTF8);
+}";
+
+const PORTS_SCHEMA: &str = "message ports {
+    REQUIRED BYTE_ARRAY run_id (UTF8);
+    REQUIRED INT64 scanned_at (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY host (UTF8);
+    REQUIRED INT32 port;
+    REQUIRED BYTE_ARRAY protocol (UTF8);
+    REQUIRED BYTE_ARRAY state (UTF8);
+    REQUIRED BYTE_ARRAY confidence (UTF8);
+    REQUIRED DOUBLE rtt_ms;
+    OPTIONAL BYTE_ARRAY service (UTF8);
+    OPTIONAL BYTE_ARRAY campaign (UTF8);
+}";
+
+const SERVICES_SCHEMA: &str = "message services {
+    REQUIRED BYTE_ARRAY run_id (UTF8);
+    REQUIRED INT64 scanned_at (TIMESTAMP(MILLIS,true));
+    REQUIRED BYTE_ARRAY host (UTF8);
+    REQUIRED INT32 port;
+    REQUIRED BYTE_ARRAY protocol (UTF8);
+    REQUIRED BYTE_ARRAY service (UTF8);
+    OPTIONAL BYTE_ARRAY campaign (UTF8);
+}";
+
+/// Values of one column, in schema order
+enum Column {
+    Text(Vec<String>),
+    OptionalText(Vec<Option<String>>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Double(Vec<f64>),
+}
+
+/// Write the hosts, ports and services rows of every scanned host under `dir`
+///
+/// Returns the files written, one per table for the whole scan. The
+/// partition is the date the report was generated, in UTC.
+pub fn export(dir: &Path, scanned: &[ExportedHost], provenance: &Provenance) -> io::Result<Vec<PathBuf>> {
+    let run_id = &provenance.run_id;
+    let scanned_at = provenance.generated_at.timestamp_millis();
+    let partition = format!("scan_date={}", provenance.generated_at.format("%Y-%m-%d"));
+    let repeat = |value: &str, n: usize| vec![value.to_string(); n];
+    let address = |host: &ExportedHost| host.address.to_string();
+    // Each host's port results with its address
+    let port_rows: Vec<(String, &PortResult)> = scanned
+        .iter()
+        .flat_map(|host| host.port_results.iter().map(move |r| (address(host), r)))
+        .collect();
+
+    let n = scanned.len();
+    let hosts = vec![
+        Column::Text(repeat(run_id, n)),
+        Column::Int64(vec![scanned_at; n]),
+        Column::Text(scanned.iter().map(address).collect()),
+        Column::Text(scanned.iter().map(|host| AddressClass::of(host.address).to_string()).collect()),
+        Column::Int32(scanned.iter().map(|host| host.port_results.len() as i32).collect()),
+        Column::Int32(scanned.iter().map(|host| host.port_results.iter().filter(|r| r.state == PortState::Open).count() as i32).collect()),
+        Column::Int64(scanned.iter().map(|host| host.duration.as_millis() as i64).collect()),
+        Column::OptionalText(vec![provenance.campaign.clone(); n]),
+    ];
+
+    let n = port_rows.len();
+    let ports = vec![
+        Column::Text(repeat(run_id, n)),
+        Column::Int64(vec![scanned_at; n]),
+        Column::Text(port_rows.iter().map(|(host, _)| host.clone()).collect()),
+        Column::Int32(port_rows.iter().map(|(_, r)| i32::from(r.port)).collect()),
+        Column::Text(port_rows.iter().map(|(_, r)| r.protocol.as_str().to_string()).collect()),
+        Column::Text(port_rows.iter().map(|(_, r)| r.state.to_string()).collect()),
+        Column::Text(port_rows.iter().map(|(_, r)| r.confidence.to_string()).collect()),
+        Column::Double(port_rows.iter().map(|(_, r)| r.response_time.as_secs_f64() * 1000.0).collect()),
+        Column::OptionalText(port_rows.iter().map(|(_, r)| r.service.clone()).collect()),
+        Column::OptionalText(vec![provenance.campaign.clone(); n]),
+    ];
+
+    let named: Vec<&(String, &PortResult)> = port_rows
+        .iter()
+        .filter(|(_, r)| r.state == PortState::Open && r.service.is_some())
+        .collect();
+    let n = named.len();
+    let services = vec![
+        Column::Text(repeat(run_id, n)),
+        Column::Int64(vec![scanned_at; n]),
+        Column::Text(named.iter().map(|(host, _)| host.clone()).collect()),
+        Column::Int32(named.iter().map(|(_, r)| i32::from(r.port)).collect()),
+        Column::Text(named.iter().map(|(_, r)| r.protocol.as_str().to_string()).collect()),
+        Column::Text(named.iter().filter_map(|(_, r)| r.service.clone()).collect()),
+        Column::OptionalText(vec![provenance.campaign.clone(); n]),
+    ];
+
+    let mut written = Vec::new();
+    for (table, schema, columns) in [("hosts", HOSTS_SCHEMA, hosts), ("ports", PORTS_SCHEMA, ports), ("services", SERVICES_SCHEMA, services)] {
+        let partition_dir = dir.join(table).join(&partition);
+        fs::create_dir_all(&partition_dir)?;
+        let path = partition_dir.join(format!("{}.parquet", run_id));
+        write_table(&path, schema, columns).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+fn write_table(path: &Path, schema: &str, columns: Vec<Column>) -> Result<(), ParquetError> {
+    let schema = Arc::new(parse_message_type(schema)?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+    let mut row_group = writer.next_row_group()?;
+    let mut columns = columns.into_iter();
+    while let Some(mut column_writer) = row_group.next_column()? {
+        let column = columns.next().ok_or_else(|| ParquetError::General("schema has more columns than values".to_string()))?;
+        let text = |values: Vec<String>| values.into_iter().map(|v| ByteArray::from(v.into_bytes())).collect::<Vec<_>>();
+        match column {
+            Column::Text(values) => {
+                column_writer.typed::<ByteArrayType>().write_batch(&text(values), None, None)?;
+            }
+            Column::OptionalText(values) => {
+                let levels: Vec<i16> = values.iter().map(|v| i16::from(v.is_some())).collect();
+                let present = text(values.into_iter().flatten().collect());
+                column_writer.typed::<ByteArrayType>().write_batch(&present, Some(&levels), None)?;
+            }
+            Column::Int32(values) => {
+                column_writer.typed::<Int32Type>().write_batch(&values, None, None)?;
+            }
+            Column::Int64(values) => {
+                column_writer.typed::<Int64Type>().write_batch(&values, None, None)?;
+            }
+            Column::Double(values) => {
+                column_writer.typed::<DoubleType>().write_batch(&values, None, None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+/// One archived scan of a host
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostScan {
+    pub run_id: String,
+    pub scanned_at: DateTime<Utc>,
+    pub host: String,
+    pub open_ports: u32,
+    pub campaign: Option<String>,
+}
+
+/// One archived port result
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortObservation {
+    pub run_id: String,
+    pub scanned_at: DateTime<Utc>,
+    pub host: String,
+    pub port: u16,
+    pub protocol: String,
+    pub state: String,
+    pub service: Option<String>,
+    pub campaign: Option<String>,
+}
+
+impl PortObservation {
+    pub fn is_open(&self) -> bool {
+        self.state == PortState::Open.to_string()
+    }
+}
+
+/// Scans read back from an archive
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub hosts: Vec<HostScan>,
+    pub ports: Vec<PortObservation>,
+}
+
+impl History {
+    /// Scans archived under `dir` at `since` or later, only those of
+    /// `campaign` if one is given
+    ///
+    /// Files written before campaigns were recorded read as having none.
+    pub fn load(dir: &Path, since: DateTime<Utc>, campaign: Option<&str>) -> io::Result<Self> {
+        let invalid = |e: ParquetError| io::Error::new(io::ErrorKind::InvalidData, e);
+        let scanned_at = |row: &Row| -> io::Result<DateTime<Utc>> {
+            let millis = row.get_timestamp_millis(1).map_err(invalid)?;
+            Utc.timestamp_millis_opt(millis)
+                .single()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("scanned_at {} is out of range", millis)))
+        };
+        let wanted = |at: &DateTime<Utc>, name: &Option<String>| *at >= since && campaign.map_or(true, |c| name.as_deref() == Some(c));
+
+        let mut history = Self::default();
+        for row in read_table(dir, "hosts", since.date_naive())? {
+            let scan = HostScan {
+                run_id: row.get_string(0).map_err(invalid)?.clone(),
+                scanned_at: scanned_at(&row)?,
+                host: row.get_string(2).map_err(invalid)?.clone(),
+                open_ports: u32::try_from(row.get_int(5).map_err(invalid)?).unwrap_or(0),
+                campaign: row.get_string(7).ok().cloned(),
+            };
+            if wanted(&scan.scanned_at, &scan.campaign) {
+                history.hosts.push(scan);
+            }
+        }
+        for row in read_table(dir, "ports", since.date_naive())? {
+            let port = row.get_int(3).map_err(invalid)?;
+            let observation = PortObservation {
+                run_id: row.get_string(0).map_err(invalid)?.clone(),
+                scanned_at: scanned_at(&row)?,
+                host: row.get_string(2).map_err(invalid)?.clone(),
+                port: u16::try_from(port)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("port {} is out of range", port)))?,
+                protocol: row.get_string(4).map_err(invalid)?.clone(),
+                state: row.get_string(5).map_err(invalid)?.clone(),
+                // The accessor fails on nulls: no service was identified
+                service: row.get_string(8).ok().cloned(),
+                campaign: row.get_string(9).ok().cloned(),
+            };
+            if wanted(&observation.scanned_at, &observation.campaign) {
+                history.ports.push(observation);
+            }
+        }
+        Ok(history)
+    }
+}
+
+/// Rows of `table` ("hosts", "ports" or "services") from every partition
+/// dated `since` or later
+fn read_table(dir: &Path, table: &str, since: NaiveDate) -> io::Result<Vec<Row>> {
+    let table_dir = dir.join(table);
+    let partitions = fs::read_dir(&table_dir).map_err(|e| {
+        io::Error::new(e.kind(), format!("{} is not a Parquet archive ({}: {})", dir.display(), table_dir.display(), e))
+    })?;
+    let mut rows = Vec::new();
+    for partition in partitions {
+        let partition = partition?.path();
+        let date = partition
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("scan_date="))
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        if !matches!(date, Some(date) if date >= since) {
+            continue;
+        }
+        for file in fs::read_dir(&partition)? {
+            let path = file?.path();
+            if path.extension().map_or(true, |ext| ext != "parquet") {
+                continue;
+            }
+            let read = || -> Result<Vec<Row>, ParquetError> {
+                let reader = SerializedFileReader::new(File::open(&path)?)?;
+                let rows = reader.get_row_iter(None)?.collect();
+                rows
+            };
+            rows.extend(read().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?);
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::Protocol;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn test_export_partitions_by_date() {
+        let dir = tempfile::tempdir().unwrap();
+        let provenance = Provenance::collect(&ScanConfig::default());
+        let mut ssh = PortResult::new(22, Protocol::Tcp, PortState::Open);
+        ssh.service = Some("ssh".to_string());
+        let results = vec![ssh, PortResult::new(23, Protocol::Tcp, PortState::Closed)];
+
+        let address: IpAddr = "192.0.2.10".parse().unwrap();
+        let scanned = [
+            ExportedHost { address, port_results: results, duration: Duration::from_millis(1500) },
+            ExportedHost { address: "192.0.2.11".parse().unwrap(), port_results: vec![], duration: Duration::ZERO },
+        ];
+        let files = export(dir.path(), &scanned, &provenance).unwrap();
+        let partition = format!("scan_date={}", provenance.generated_at.format("%Y-%m-%d"));
+        assert_eq!(files.len(), 3);
+        assert!(files.iter().all(|f| f.parent().unwrap().ends_with(&partition)));
+
+        let rows = |path: &PathBuf| -> Vec<String> {
+            let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+            reader.get_row_iter(None).unwrap().map(|row| row.unwrap().to_string()).collect()
+        };
+        let ports = rows(&files[1]);
+        assert_eq!(ports.len(), 2);
+        assert!(ports[0].contains("port: 22") && ports[0].contains("service: \"ssh\""), "{}", ports[0]);
+        assert!(ports[1].contains("service: null"), "{}", ports[1]);
+        // One file per table holds every host of the scan
+        let hosts = rows(&files[0]);
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts[0].contains("open_ports: 1") && hosts[1].contains("host: \"192.0.2.11\""), "{:?}", hosts);
+        assert_eq!(rows(&files[2]).len(), 1);
+    }
+}
@@ -0,0 +1,417 @@
+//! Screenshots of discovered web services
+//!
+//! Two backends are supported: an external command built from a template
+//! (headless Chromium by default) or a running Chrome/Chromium reached over
+//! the DevTools protocol (`--remote-debugging-port`). Screenshots land in
+//! the output directory next to an `index.html` that links each URL to its
+//! image.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::stream::{self, StreamExt};
+use rand::Rng;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Default command template; `{url}` and `{output}` are substituted per argument
+pub const DEFAULT_COMMAND_TEMPLATE: &str =
+    "chromium --headless --disable-gpu --no-sandbox --hide-scrollbars --window-size=1280,800 --screenshot={output} {url}";
+
+/// How screenshots are taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScreenshotBackend {
+    /// External command; placeholders: `{url}`, `{output}`, `{host}`, `{port}`
+    Command(String),
+    /// DevTools HTTP endpoint of a running browser, e.g. `http://127.0.0.1:9222`
+    Cdp(String),
+}
+
+impl Default for ScreenshotBackend {
+    fn default() -> Self {
+        ScreenshotBackend::Command(DEFAULT_COMMAND_TEMPLATE.to_string())
+    }
+}
+
+/// Outcome of one capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub url: String,
+    /// Image path, set when the capture succeeded
+    pub path: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+/// Captures screenshots and writes the index page
+#[derive(Debug, Clone)]
+pub struct ScreenshotHook {
+    backend: ScreenshotBackend,
+    output_dir: PathBuf,
+    timeout: Duration,
+    concurrency: usize,
+}
+
+impl ScreenshotHook {
+    pub fn new(backend: ScreenshotBackend, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            backend,
+            output_dir: output_dir.into(),
+            timeout: Duration::from_secs(30),
+            concurrency: 4,
+        }
+    }
+
+    /// Time allowed for a single capture
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of captures running at once
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// File name for `url`, e.g. `https_10.0.0.5_8443.png`
+    pub fn file_name(url: &str) -> String {
+        let name: String = url
+            .trim_end_matches('/')
+            .replace("://", "_")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        format!("{}.png", name)
+    }
+
+    /// Capture every URL, at most `concurrency` at a time
+    pub async fn capture_all(&self, urls: &[String]) -> io::Result<Vec<Screenshot>> {
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        Ok(stream::iter(urls)
+            .map(|url| self.capture(url))
+            .buffered(self.concurrency)
+            .collect()
+            .await)
+    }
+
+    /// Capture a single URL into the output directory
+    pub async fn capture(&self, url: &str) -> Screenshot {
+        let path = self.output_dir.join(Self::file_name(url));
+        let result = match timeout(self.timeout, self.run_backend(url, &path)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "screenshot timed out")),
+        };
+
+        match result {
+            Ok(()) if path.exists() => Screenshot { url: url.to_string(), path: Some(path), error: None },
+            Ok(()) => Screenshot { url: url.to_string(), path: None, error: Some("no image was written".to_string()) },
+            Err(e) => Screenshot { url: url.to_string(), path: None, error: Some(e.to_string()) },
+        }
+    }
+
+    async fn run_backend(&self, url: &str, path: &Path) -> io::Result<()> {
+        match &self.backend {
+            ScreenshotBackend::Command(template) => run_command(template, url, path).await,
+            ScreenshotBackend::Cdp(endpoint) => {
+                let png = cdp_capture(endpoint, url).await?;
+                tokio::fs::write(path, png).await
+            }
+        }
+    }
+
+    /// Write `index.html` linking every URL to its screenshot; returns its path
+    pub fn write_report(&self, shots: &[Screenshot]) -> io::Result<PathBuf> {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Phobos screenshots</title>\n\
+             <style>body{font-family:sans-serif}figure{display:inline-block;margin:8px;vertical-align:top}\
+             img{width:400px;border:1px solid #ccc}</style></head><body>\n<h1>Web service screenshots</h1>\n",
+        );
+        for shot in shots {
+            let url = escape_html(&shot.url);
+            match (&shot.path, &shot.error) {
+                (Some(path), _) => {
+                    let file = path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+                    let file = escape_html(&file);
+                    html.push_str(&format!(
+                        "<figure><a href=\"{file}\"><img src=\"{file}\" alt=\"{url}\"></a>\
+                         <figcaption><a href=\"{url}\">{url}</a></figcaption></figure>\n"
+                    ));
+                }
+                (None, error) => html.push_str(&format!(
+                    "<figure><figcaption><a href=\"{url}\">{url}</a>: {}</figcaption></figure>\n",
+                    escape_html(error.as_deref().unwrap_or("failed"))
+                )),
+            }
+        }
+        html.push_str("</body></html>\n");
+
+        let report = self.output_dir.join("index.html");
+        std::fs::write(&report, html)?;
+        Ok(report)
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Run the template; arguments are split before substitution so a URL or
+/// path can never inject extra arguments
+async fn run_command(template: &str, url: &str, path: &Path) -> io::Result<()> {
+    let (host, port) = url
+        .split("://")
+        .nth(1)
+        .and_then(|rest| rest.trim_end_matches('/').rsplit_once(':'))
+        .unwrap_or(("", ""));
+    let output = path.to_string_lossy();
+
+    let args: Vec<String> = template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{url}", url)
+                .replace("{output}", &output)
+                .replace("{host}", host)
+                .replace("{port}", port)
+        })
+        .collect();
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty screenshot command template"))?;
+
+    let status = tokio::process::Command::new(program)
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::Other, format!("{} exited with {}", program, status)))
+    }
+}
+
+fn cdp_error(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message.into())
+}
+
+/// Open a tab, load `url`, capture a PNG and close the tab again
+async fn cdp_capture(endpoint: &str, url: &str) -> io::Result<Vec<u8>> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let http = reqwest::Client::new();
+    let target: Value = http
+        .put(format!("{}/json/new?about:blank", endpoint))
+        .send()
+        .await
+        .map_err(|e| cdp_error(format!("DevTools endpoint unreachable: {}", e)))?
+        .json()
+        .await
+        .map_err(|e| cdp_error(format!("unexpected DevTools reply: {}", e)))?;
+    let ws_url = target["webSocketDebuggerUrl"]
+        .as_str()
+        .ok_or_else(|| cdp_error("DevTools reply has no webSocketDebuggerUrl"))?;
+    let target_id = target["id"].as_str().unwrap_or_default().to_string();
+
+    let result = async {
+        let mut session = CdpSession::connect(ws_url).await?;
+        session.call("Page.enable", json!({})).await?;
+        session
+            .call("Emulation.setDeviceMetricsOverride", json!({"width": 1280, "height": 800, "deviceScaleFactor": 1, "mobile": false}))
+            .await?;
+        session.call("Page.navigate", json!({"url": url})).await?;
+        session.wait_event("Page.loadEventFired").await?;
+        let shot = session.call("Page.captureScreenshot", json!({"format": "png"})).await?;
+        let data = shot["data"].as_str().ok_or_else(|| cdp_error("screenshot reply has no data"))?;
+        STANDARD.decode(data).map_err(|e| cdp_error(format!("invalid screenshot data: {}", e)))
+    }
+    .await;
+
+    let _ = http.get(format!("{}/json/close/{}", endpoint, target_id)).send().await;
+    result
+}
+
+/// Minimal DevTools session over a client websocket
+struct CdpSession {
+    stream: BufReader<TcpStream>,
+    next_id: u64,
+    events: Vec<String>,
+}
+
+impl CdpSession {
+    async fn connect(ws_url: &str) -> io::Result<Self> {
+        let rest = ws_url
+            .strip_prefix("ws://")
+            .ok_or_else(|| cdp_error(format!("unsupported websocket URL {}", ws_url)))?;
+        let (authority, path) = rest.split_once('/').map_or((rest, "/".to_string()), |(a, p)| (a, format!("/{}", p)));
+
+        let mut stream = BufReader::new(TcpStream::connect(authority).await?);
+        let key = STANDARD.encode(rand::thread_rng().gen::<[u8; 16]>());
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, authority, key
+        );
+        stream.get_mut().write_all(request.as_bytes()).await?;
+
+        let mut status = String::new();
+        stream.read_line(&mut status).await?;
+        if !status.contains(" 101 ") {
+            return Err(cdp_error(format!("websocket upgrade refused: {}", status.trim())));
+        }
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await? == 0 || line == "\r\n" {
+                break;
+            }
+        }
+
+        Ok(Self { stream, next_id: 1, events: Vec::new() })
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> io::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({"id": id, "method": method, "params": params}).to_string();
+        self.stream.get_mut().write_all(&encode_frame(message.as_bytes())).await?;
+
+        loop {
+            let reply: Value = serde_json::from_str(&self.read_message().await?)?;
+            if reply["id"].as_u64() == Some(id) {
+                if let Some(error) = reply.get("error") {
+                    return Err(cdp_error(format!("{} failed: {}", method, error)));
+                }
+                return Ok(reply["result"].clone());
+            }
+            if let Some(event) = reply["method"].as_str() {
+                self.events.push(event.to_string());
+            }
+        }
+    }
+
+    async fn wait_event(&mut self, name: &str) -> io::Result<()> {
+        while !self.events.iter().any(|e| e == name) {
+            let message: Value = serde_json::from_str(&self.read_message().await?)?;
+            if let Some(event) = message["method"].as_str() {
+                self.events.push(event.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Read one text message, reassembling fragments and skipping control frames
+    async fn read_message(&mut self) -> io::Result<String> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0u8; 2];
+            self.stream.read_exact(&mut header).await?;
+            let fin = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0f;
+            let len = match header[1] & 0x7f {
+                126 => self.stream.read_u16().await? as u64,
+                127 => self.stream.read_u64().await?,
+                n => n as u64,
+            };
+            let mut mask = [0u8; 4];
+            if header[1] & 0x80 != 0 {
+                self.stream.read_exact(&mut mask).await?;
+            }
+            let mut payload = vec![0u8; len as usize];
+            self.stream.read_exact(&mut payload).await?;
+            if header[1] & 0x80 != 0 {
+                payload.iter_mut().enumerate().for_each(|(i, b)| *b ^= mask[i % 4]);
+            }
+
+            match opcode {
+                0x0 | 0x1 => {
+                    message.extend(payload);
+                    if fin {
+                        return String::from_utf8(message).map_err(|e| cdp_error(e.to_string()));
+                    }
+                }
+                0x8 => return Err(cdp_error("DevTools closed the connection")),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Masked client text frame
+fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    match payload.len() {
+        n if n < 126 => frame.push(0x80 | n as u8),
+        n if n <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    let mask: [u8; 4] = rand::thread_rng().gen();
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_is_filesystem_safe() {
+        assert_eq!(ScreenshotHook::file_name("https://10.0.0.5:8443/"), "https_10.0.0.5_8443.png");
+        assert_eq!(ScreenshotHook::file_name("http://[::1]:80"), "http____1__80.png");
+    }
+
+    #[tokio::test]
+    async fn test_command_backend_and_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = ScreenshotHook::new(ScreenshotBackend::Command("touch {output}".to_string()), dir.path());
+
+        let shots = hook
+            .capture_all(&["http://127.0.0.1:8080".to_string(), "http://127.0.0.1:8081/<x>".to_string()])
+            .await
+            .unwrap();
+        assert!(shots.iter().all(|s| s.path.as_ref().is_some_and(|p| p.exists())));
+
+        let report = std::fs::read_to_string(hook.write_report(&shots).unwrap()).unwrap();
+        assert!(report.contains("src=\"http_127.0.0.1_8080.png\""));
+        assert!(report.contains("8081/&lt;x&gt;"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_command_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = ScreenshotHook::new(ScreenshotBackend::Command("false {url}".to_string()), dir.path());
+        let shot = hook.capture("http://127.0.0.1:1").await;
+        assert!(shot.path.is_none());
+        assert!(shot.error.is_some());
+    }
+
+    #[test]
+    fn test_frame_encoding_masks_payload() {
+        let frame = encode_frame(b"hello");
+        assert_eq!(frame[0], 0x81);
+        assert_eq!(frame[1], 0x80 | 5);
+        let mask = &frame[2..6];
+        let unmasked: Vec<u8> = frame[6..].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect();
+        assert_eq!(unmasked, b"hello");
+
+        assert_eq!(encode_frame(&[0u8; 300])[1], 0x80 | 126);
+    }
+}
@@ -0,0 +1,217 @@
+//! SQLite results database (`--output sqlite --output-file scans.db`)
+//!
+//! Every scan adds one row to `scans`, one per host to `hosts`, one per
+//! port result to `ports` and one per identified service to `services`,
+//! so many scans can be queried together with plain SQL:
+//!
+//! ```sql
+//! SELECT hosts.address, ports.port, services.name
+//! FROM ports
+//! JOIN hosts ON hosts.id = ports.host_id
+//! JOIN scans ON scans.id = hosts.scan_id
+//! LEFT JOIN services ON services.port_id = ports.id
+//! WHERE ports.state = 'open' AND scans.finished_at >= '2024-10-01';
+//! ```
+//!
+//! The `open_services` view does these joins for the common case. Times
+//! are RFC 3339 in UTC, which sort and compare as text.
+
+use super::provenance::Provenance;
+use crate::network::{PortResult, PortState};
+use crate::utils::address_class::AddressClass;
+use rusqlite::{params, Connection, Transaction};
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Bumped whenever the tables below change; kept in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    run_id TEXT NOT NULL UNIQUE,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    scanner_version TEXT NOT NULL,
+    git_commit TEXT NOT NULL,
+    command_line TEXT NOT NULL,
+    timing_profile TEXT NOT NULL,
+    seed INTEGER,
+    campaign TEXT,
+    scanner_host TEXT NOT NULL,
+    config TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS hosts (
+    id INTEGER PRIMARY KEY,
+    scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    address_class TEXT,
+    ports_scanned INTEGER NOT NULL,
+    open_ports INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    UNIQUE (scan_id, address)
+);
+CREATE TABLE IF NOT EXISTS ports (
+    id INTEGER PRIMARY KEY,
+    host_id INTEGER NOT NULL REFERENCES hosts(id) ON DELETE CASCADE,
+    port INTEGER NOT NULL,
+    protocol TEXT NOT NULL,
+    state TEXT NOT NULL,
+    confidence TEXT NOT NULL,
+    rtt_ms REAL NOT NULL,
+    UNIQUE (host_id, port, protocol)
+);
+CREATE TABLE IF NOT EXISTS services (
+    port_id INTEGER PRIMARY KEY REFERENCES ports(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    version TEXT
+);
+CREATE INDEX IF NOT EXISTS hosts_address ON hosts(address);
+CREATE INDEX IF NOT EXISTS ports_port ON ports(port, state);
+CREATE VIEW IF NOT EXISTS open_services AS
+    SELECT scans.run_id, scans.finished_at, scans.campaign, hosts.address,
+           ports.port, ports.protocol, services.name AS service, services.version
+    FROM ports
+    JOIN hosts ON hosts.id = ports.host_id
+    JOIN scans ON scans.id = hosts.scan_id
+    LEFT JOIN services ON services.port_id = ports.id
+    WHERE ports.state = 'open';
+";
+
+/// One scanned host with the port results reported for it
+#[derive(Debug, Clone, Copy)]
+pub struct HostRecord<'a> {
+    /// Address or hostname as it was scanned
+    pub target: &'a str,
+    pub port_results: &'a [PortResult],
+    pub duration: Duration,
+}
+
+/// Add one scan of `hosts`, which took `duration`, to the database at `path`
+///
+/// The file and its tables are created on first use. The scan is written in
+/// one transaction, so an interrupted write leaves no partial scan behind.
+pub fn export(path: &Path, hosts: &[HostRecord], duration: Duration, provenance: &Provenance) -> io::Result<()> {
+    let mut db = open(path)?;
+    let mut write = || -> rusqlite::Result<()> {
+        let tx = db.transaction()?;
+        let scan_id = insert_scan(&tx, duration, provenance)?;
+        for host in hosts {
+            insert_host(&tx, scan_id, host)?;
+        }
+        tx.commit()
+    };
+    write().map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Open `path`, creating or checking its schema
+pub fn open(path: &Path) -> io::Result<Connection> {
+    let invalid = |e: rusqlite::Error| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e));
+    let db = Connection::open(path).map_err(invalid)?;
+    db.pragma_update(None, "foreign_keys", true).map_err(invalid)?;
+    let version: i64 = db.pragma_query_value(None, "user_version", |row| row.get(0)).map_err(invalid)?;
+    if version > SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has schema version {}, newer than the {} this build writes", path.display(), version, SCHEMA_VERSION),
+        ));
+    }
+    db.execute_batch(SCHEMA).map_err(invalid)?;
+    db.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(invalid)?;
+    Ok(db)
+}
+
+fn insert_scan(tx: &Transaction, duration: Duration, provenance: &Provenance) -> rusqlite::Result<i64> {
+    let finished = provenance.generated_at;
+    let started = finished - chrono::Duration::from_std(duration).unwrap_or_default();
+    tx.execute(
+        "INSERT INTO scans (run_id, started_at, finished_at, scanner_version, git_commit, command_line,
+                            timing_profile, seed, campaign, scanner_host, config)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            provenance.run_id,
+            started.to_rfc3339(),
+            finished.to_rfc3339(),
+            provenance.version,
+            provenance.git_commit,
+            provenance.command_line.join(" "),
+            provenance.timing_profile,
+            // SQLite integers are signed; seeds keep their bits
+            provenance.seed.map(|seed| seed as i64),
+            provenance.campaign,
+            provenance.environment.fingerprint,
+            provenance.config.to_string(),
+        ],
+    )?;
+    Ok(tx.last_insert_rowid())
+}
+
+fn insert_host(tx: &Transaction, scan_id: i64, host: &HostRecord) -> rusqlite::Result<()> {
+    let open = host.port_results.iter().filter(|r| r.state == PortState::Open).count();
+    let class = host.target.parse::<IpAddr>().ok().map(|address| AddressClass::of(address).to_string());
+    tx.execute(
+        "INSERT INTO hosts (scan_id, address, address_class, ports_scanned, open_ports, duration_ms)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![scan_id, host.target, class, host.port_results.len(), open, host.duration.as_millis() as i64],
+    )?;
+    let host_id = tx.last_insert_rowid();
+
+    let mut port = tx.prepare_cached(
+        "INSERT INTO ports (host_id, port, protocol, state, confidence, rtt_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    let mut service = tx.prepare_cached("INSERT INTO services (port_id, name, version) VALUES (?1, ?2, ?3)")?;
+    for result in host.port_results {
+        port.execute(params![
+            host_id,
+            result.port,
+            result.protocol.as_str(),
+            result.state.to_string(),
+            result.confidence.to_string(),
+            result.response_time.as_secs_f64() * 1000.0,
+        ])?;
+        if let Some(name) = &result.service {
+            service.execute(params![tx.last_insert_rowid(), name, result.version])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::Protocol;
+
+    #[test]
+    fn test_export_appends_normalized_scans() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scans.db");
+        let mut ssh = PortResult::new(22, Protocol::Tcp, PortState::Open);
+        ssh.service = Some("ssh".to_string());
+        ssh.version = Some("OpenSSH 9.6".to_string());
+        let first = vec![ssh, PortResult::new(23, Protocol::Tcp, PortState::Closed)];
+        let second = vec![PortResult::new(80, Protocol::Tcp, PortState::Open)];
+        let hosts = [
+            HostRecord { target: "192.0.2.10", port_results: &first, duration: Duration::from_millis(1500) },
+            HostRecord { target: "192.0.2.11", port_results: &second, duration: Duration::from_millis(900) },
+        ];
+
+        export(&path, &hosts, Duration::from_secs(2), &Provenance::collect(&ScanConfig::default())).unwrap();
+        export(&path, &hosts[1..], Duration::from_secs(1), &Provenance::collect(&ScanConfig::default())).unwrap();
+
+        let db = open(&path).unwrap();
+        let count = |sql: &str| -> i64 { db.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM scans"), 2);
+        assert_eq!(count("SELECT COUNT(*) FROM hosts"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM ports"), 4);
+        assert_eq!(count("SELECT open_ports FROM hosts WHERE address = '192.0.2.10'"), 1);
+
+        let (service, version): (String, String) = db
+            .query_row("SELECT service, version FROM open_services WHERE port = 22", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((service.as_str(), version.as_str()), ("ssh", "OpenSSH 9.6"));
+        assert_eq!(count("SELECT COUNT(*) FROM open_services WHERE address = '192.0.2.11'"), 2);
+    }
+}
@@ -0,0 +1,188 @@
+//! STIX 2.1 output
+//!
+//! Each scanned host becomes an `observed-data` object referencing an
+//! `ipv4-addr`/`ipv6-addr` observable and one `network-traffic` observable
+//! per open port, all created by a Phobos `identity`. Cyber-observable IDs
+//! are deterministic UUIDv5s as the specification requires, so the same
+//! host/port always maps to the same object across reports. Report
+//! provenance travels in the custom `x_phobos_provenance` property.
+
+use super::provenance::Provenance;
+use crate::network::{PortResult, PortState, Protocol};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use sha1::Digest;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Namespace for STIX Cyber-observable Object IDs (STIX 2.1, section 2.9)
+const SCO_NAMESPACE: Uuid = Uuid::from_u128(0x00abedb4_aa42_466c_9c01_fed23315a9b7);
+
+/// Fixed ID so every report attributes its objects to the same producer
+const PHOBOS_IDENTITY: &str = "identity--5c0e3e54-8f4e-4b4c-9d0a-70686f626f73";
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Deterministic SCO id from its ID-contributing properties
+///
+/// `canonical` must already be in JSON canonical form (sorted keys, no whitespace).
+fn sco_id(object_type: &str, canonical: &str) -> String {
+    let mut data = SCO_NAMESPACE.as_bytes().to_vec();
+    data.extend_from_slice(canonical.as_bytes());
+    let digest = sha1::Sha1::digest(&data);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    format!("{}--{}", object_type, uuid::Builder::from_sha1_bytes(bytes).into_uuid())
+}
+
+fn address_object(address: IpAddr) -> Value {
+    let object_type = if address.is_ipv4() { "ipv4-addr" } else { "ipv6-addr" };
+    let canonical = serde_json::to_string(&json!({ "value": address.to_string() })).unwrap_or_default();
+    json!({
+        "type": object_type,
+        "spec_version": "2.1",
+        "id": sco_id(object_type, &canonical),
+        "value": address.to_string(),
+    })
+}
+
+fn traffic_object(address: IpAddr, address_ref: &str, result: &PortResult) -> Value {
+    let network = if address.is_ipv4() { "ipv4" } else { "ipv6" };
+    let transport = match result.protocol {
+        Protocol::Udp => "udp",
+        _ => "tcp",
+    };
+    let mut protocols = vec![network.to_string(), transport.to_string()];
+    if let Some(service) = &result.service {
+        protocols.push(service.to_lowercase());
+    }
+
+    // Keys in lexicographic order: dst_port, dst_ref, protocols
+    let canonical = serde_json::to_string(&protocols)
+        .map(|p| format!("{{\"dst_port\":{},\"dst_ref\":\"{}\",\"protocols\":{}}}", result.port, address_ref, p))
+        .unwrap_or_default();
+    json!({
+        "type": "network-traffic",
+        "spec_version": "2.1",
+        "id": sco_id("network-traffic", &canonical),
+        "dst_ref": address_ref,
+        "dst_port": result.port,
+        "protocols": protocols,
+    })
+}
+
+/// The Phobos `identity` object referenced by `created_by_ref`
+pub fn identity(created: &DateTime<Utc>) -> Value {
+    json!({
+        "type": "identity",
+        "spec_version": "2.1",
+        "id": PHOBOS_IDENTITY,
+        "created": timestamp(created),
+        "modified": timestamp(created),
+        "name": "Phobos",
+        "description": concat!("Phobos network scanner ", env!("CARGO_PKG_VERSION")),
+        "identity_class": "system",
+    })
+}
+
+/// Observables and the `observed-data` object for one scanned host
+pub fn host_objects(
+    address: IpAddr,
+    port_results: &[PortResult],
+    first_observed: &DateTime<Utc>,
+    last_observed: &DateTime<Utc>,
+    provenance: &Provenance,
+) -> Vec<Value> {
+    let address_object = address_object(address);
+    let address_ref = address_object["id"].as_str().unwrap_or_default().to_string();
+
+    let mut objects = vec![address_object];
+    objects.extend(
+        port_results
+            .iter()
+            .filter(|r| r.state == PortState::Open)
+            .map(|r| traffic_object(address, &address_ref, r)),
+    );
+
+    let now = timestamp(&Utc::now());
+    let object_refs: Vec<Value> = objects.iter().map(|o| o["id"].clone()).collect();
+    objects.push(json!({
+        "type": "observed-data",
+        "spec_version": "2.1",
+        "id": format!("observed-data--{}", Uuid::new_v4()),
+        "created_by_ref": PHOBOS_IDENTITY,
+        "created": now,
+        "modified": now,
+        "first_observed": timestamp(first_observed),
+        "last_observed": timestamp(last_observed),
+        "number_observed": 1,
+        "object_refs": object_refs,
+        "x_phobos_provenance": provenance.to_json(),
+    }));
+    objects
+}
+
+/// A STIX bundle for one host
+pub fn to_stix_bundle(
+    address: IpAddr,
+    port_results: &[PortResult],
+    first_observed: &DateTime<Utc>,
+    last_observed: &DateTime<Utc>,
+    provenance: &Provenance,
+) -> String {
+    let mut objects = vec![identity(first_observed)];
+    objects.extend(host_objects(address, port_results, first_observed, last_observed, provenance));
+    let bundle = json!({
+        "type": "bundle",
+        "id": format!("bundle--{}", Uuid::new_v4()),
+        "objects": objects,
+    });
+    serde_json::to_string_pretty(&bundle).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Confidence;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sco_ids_are_spec_uuidv5() {
+        // Same value as the stix2 reference library (uuid.uuid5 over the canonical JSON)
+        let address = address_object("198.51.100.3".parse().unwrap());
+        assert_eq!(address["id"], "ipv4-addr--28bb3599-77cd-5a82-a950-b5bc3caf07c4");
+    }
+
+    #[test]
+    fn test_bundle_references_observables() {
+        let results = vec![
+            PortResult {
+                port: 443,
+                protocol: Protocol::Tcp,
+                state: PortState::Open,
+                service: Some("HTTPS".to_string()),
+                version: None,
+                response_time: Duration::from_millis(1),
+                confidence: Confidence::Medium,
+                evidence: Vec::new(),
+                anomalies: Vec::new(),
+            },
+            PortResult::new(22, Protocol::Tcp, PortState::Closed),
+        ];
+        let now = Utc::now();
+        let provenance = Provenance::collect(&Default::default());
+        let bundle: Value =
+            serde_json::from_str(&to_stix_bundle("2001:db8::7".parse().unwrap(), &results, &now, &now, &provenance)).unwrap();
+        let objects = bundle["objects"].as_array().unwrap();
+
+        let types: Vec<&str> = objects.iter().map(|o| o["type"].as_str().unwrap()).collect();
+        assert_eq!(types, vec!["identity", "ipv6-addr", "network-traffic", "observed-data"]);
+        assert_eq!(objects[2]["protocols"], json!(["ipv6", "tcp", "https"]));
+        assert_eq!(objects[2]["dst_ref"], objects[1]["id"]);
+        assert_eq!(objects[3]["object_refs"].as_array().unwrap().len(), 2);
+        assert_eq!(objects[3]["created_by_ref"], objects[0]["id"]);
+        assert_eq!(objects[3]["x_phobos_provenance"]["run_id"], provenance.run_id.as_str());
+    }
+}
@@ -0,0 +1,234 @@
+//! Terminal colours: when to use them and what they look like
+//!
+//! Output code says what a piece of text is — an open port, a warning, a
+//! heading — with [`Paint::paint`], and the active [`ThemeName`] decides
+//! its colour. Whether colour is written at all is [`ColorMode`]: `auto`
+//! follows `NO_COLOR`, `CLICOLOR_FORCE` and whether stdout is a terminal.
+
+use crate::network::PortState;
+use colored::{Color, ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// `--color`: whether to write colour codes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Colour on a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Make this the mode of all output of the process
+    pub fn apply(self) {
+        match self {
+            ColorMode::Auto => colored::control::unset_override(),
+            ColorMode::Always => colored::control::set_override(true),
+            ColorMode::Never => colored::control::set_override(false),
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!("unknown color mode '{}' (expected auto, always or never)", s)),
+        }
+    }
+}
+
+/// What a piece of output is, as far as colour goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Open,
+    Closed,
+    Filtered,
+    Success,
+    Info,
+    Warning,
+    Error,
+    Accent,
+    Highlight,
+    Heading,
+    /// Secondary detail: provenance, evidence, seeds
+    Muted,
+    /// The Phobos banner
+    Brand,
+}
+
+impl Role {
+    /// The role a port state is shown in
+    pub fn for_state(state: PortState) -> Self {
+        match state {
+            PortState::Open => Role::Open,
+            PortState::Closed => Role::Closed,
+            PortState::Filtered | PortState::OpenFiltered | PortState::ClosedFiltered => Role::Filtered,
+            PortState::Unfiltered => Role::Heading,
+        }
+    }
+}
+
+/// Built-in palettes, chosen with `theme = "..."` in the config or `--theme`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    /// Bold bright colours only, nothing dimmed or gray
+    HighContrast,
+    /// Okabe-Ito palette: blue and orange where the default has green and red
+    Colorblind,
+}
+
+struct Style {
+    color: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+}
+
+const fn plain(color: Color) -> Style {
+    Style { color: Some(color), bold: false, dimmed: false }
+}
+
+const fn bold(color: Color) -> Style {
+    Style { color: Some(color), bold: true, dimmed: false }
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 3] = [ThemeName::Default, ThemeName::HighContrast, ThemeName::Colorblind];
+
+    fn style(self, role: Role) -> Style {
+        use Color::*;
+        match self {
+            ThemeName::Default => match role {
+                Role::Open | Role::Success => plain(BrightGreen),
+                Role::Closed | Role::Error => plain(BrightRed),
+                Role::Filtered | Role::Warning => plain(BrightYellow),
+                Role::Info => plain(BrightBlue),
+                Role::Accent => plain(BrightCyan),
+                Role::Highlight => plain(BrightMagenta),
+                Role::Heading => plain(BrightWhite),
+                Role::Muted => Style { color: None, bold: false, dimmed: true },
+                Role::Brand => bold(TrueColor { r: 231, g: 76, b: 60 }),
+            },
+            ThemeName::HighContrast => match role {
+                Role::Open | Role::Success => bold(BrightGreen),
+                Role::Closed | Role::Error => bold(BrightRed),
+                Role::Filtered | Role::Warning => bold(BrightYellow),
+                // Blue is hard to read on a dark background
+                Role::Info | Role::Accent => bold(BrightCyan),
+                Role::Highlight => bold(BrightMagenta),
+                Role::Heading => bold(BrightWhite),
+                Role::Muted => plain(BrightWhite),
+                Role::Brand => bold(BrightRed),
+            },
+            ThemeName::Colorblind => match role {
+                Role::Open | Role::Success => plain(TrueColor { r: 86, g: 180, b: 233 }),
+                Role::Closed | Role::Error => plain(TrueColor { r: 213, g: 94, b: 0 }),
+                Role::Filtered | Role::Warning => plain(TrueColor { r: 240, g: 228, b: 66 }),
+                Role::Info => plain(TrueColor { r: 0, g: 114, b: 178 }),
+                Role::Accent => plain(TrueColor { r: 0, g: 158, b: 115 }),
+                Role::Highlight => plain(TrueColor { r: 204, g: 121, b: 167 }),
+                Role::Heading => plain(BrightWhite),
+                Role::Muted => Style { color: None, bold: false, dimmed: true },
+                Role::Brand => bold(TrueColor { r: 213, g: 94, b: 0 }),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ThemeName::Default => "default",
+            ThemeName::HighContrast => "high-contrast",
+            ThemeName::Colorblind => "colorblind",
+        })
+    }
+}
+
+impl FromStr for ThemeName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ThemeName::ALL
+            .into_iter()
+            .find(|theme| theme.to_string() == s)
+            .ok_or_else(|| format!("unknown theme '{}' (expected default, high-contrast or colorblind)", s))
+    }
+}
+
+static THEME: AtomicU8 = AtomicU8::new(0);
+
+/// Make `theme` the palette of all output of the process
+pub fn set_theme(theme: ThemeName) {
+    THEME.store(theme as u8, Ordering::Relaxed);
+}
+
+pub fn current_theme() -> ThemeName {
+    ThemeName::ALL[THEME.load(Ordering::Relaxed) as usize]
+}
+
+/// Colour text by what it is, in the current theme
+pub trait Paint {
+    fn paint(self, role: Role) -> ColoredString;
+}
+
+impl Paint for ColoredString {
+    fn paint(self, role: Role) -> ColoredString {
+        let style = current_theme().style(role);
+        let mut painted = match style.color {
+            Some(color) => self.color(color),
+            None => self,
+        };
+        if style.bold {
+            painted = painted.bold();
+        }
+        if style.dimmed {
+            painted = painted.dimmed();
+        }
+        painted
+    }
+}
+
+impl Paint for &str {
+    fn paint(self, role: Role) -> ColoredString {
+        ColoredString::from(self).paint(role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_and_color_mode_names() {
+        for theme in ThemeName::ALL {
+            assert_eq!(theme.to_string().parse::<ThemeName>(), Ok(theme));
+            let config: toml::Table = format!("theme = \"{}\"", theme).parse().unwrap();
+            assert_eq!(config["theme"].clone().try_into::<ThemeName>().unwrap(), theme);
+        }
+        assert!("solarized".parse::<ThemeName>().unwrap_err().contains("high-contrast"));
+        assert_eq!("never".parse::<ColorMode>(), Ok(ColorMode::Never));
+        assert!("sometimes".parse::<ColorMode>().is_err());
+    }
+
+    #[test]
+    fn test_themes_keep_states_apart() {
+        for theme in ThemeName::ALL {
+            let [open, closed, filtered] = [Role::Open, Role::Closed, Role::Filtered].map(|role| theme.style(role).color);
+            assert!(open != closed && closed != filtered && open != filtered, "{}", theme);
+        }
+        assert!(!ThemeName::HighContrast.style(Role::Muted).dimmed);
+        assert!(!matches!(ThemeName::Colorblind.style(Role::Open).color, Some(Color::BrightGreen | Color::Green)));
+    }
+}
@@ -0,0 +1,260 @@
+//! Per-run workspace directories for `--workspace`
+//!
+//! A [`Workspace`] is one directory per run, `DIR/20260301-101500-TARGET`,
+//! that every file the run writes goes into: output files given as relative
+//! paths are placed inside it, and the run adds its raw results, the
+//! provenance record and, for an interrupted scan, the checkpoint to resume
+//! from. When the run ends, `index.json` lists every file with its kind,
+//! size and SHA-256, so an engagement folder can be checked and archived
+//! as a whole.
+
+use super::provenance::Provenance;
+use crate::scanner::ScanResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Name of the manifest in every workspace
+pub const INDEX_FILE: &str = "index.json";
+
+static CURRENT: OnceLock<Workspace> = OnceLock::new();
+
+/// The workspace of this process's run, once one is activated
+pub fn current_workspace() -> Option<&'static Workspace> {
+    CURRENT.get()
+}
+
+/// One file of a finished run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// What wrote it, e.g. `output-nmap`, `screenshots` or `raw`
+    pub kind: String,
+    /// Relative to the workspace
+    pub path: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Contents of `index.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub run_id: String,
+    pub version: String,
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// Checkpoint id to `--resume` from, for a scan that did not finish
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume: Option<String>,
+    pub artifacts: Vec<Artifact>,
+}
+
+/// The directory collecting one run's files
+#[derive(Debug)]
+pub struct Workspace {
+    dir: PathBuf,
+    started_at: DateTime<Utc>,
+    /// Paths handed out so far and the kind of file written there
+    placed: Mutex<Vec<(String, PathBuf)>>,
+}
+
+impl Workspace {
+    /// A new, empty directory under `root` named after the start time and
+    /// `label`, with a numeric suffix if a run started in the same second
+    pub fn create(root: &Path, label: &str) -> io::Result<Self> {
+        let started_at = Utc::now();
+        let label: String = label
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .take(48)
+            .collect();
+        let name = format!("{}-{}", started_at.with_timezone(&chrono::Local).format("%Y%m%d-%H%M%S"), label);
+        fs::create_dir_all(root)?;
+        let mut dir = root.join(&name);
+        let mut suffix = 1;
+        loop {
+            match fs::create_dir(&dir) {
+                Ok(()) => break,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    suffix += 1;
+                    dir = root.join(format!("{}-{}", name, suffix));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Self { dir, started_at, placed: Mutex::new(Vec::new()) })
+    }
+
+    /// Make this the workspace [`current_workspace`] returns; only the
+    /// first call has an effect
+    pub fn activate(self) -> &'static Workspace {
+        let _ = CURRENT.set(self);
+        CURRENT.get().expect("workspace was just set")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where `kind` output asked for at `requested` goes: inside the
+    /// workspace for a relative path, unchanged for an absolute one
+    pub fn place(&self, kind: &str, requested: &str) -> PathBuf {
+        let requested = Path::new(requested);
+        let path = match requested.is_absolute() {
+            true => requested.to_path_buf(),
+            false => self.dir.join(requested),
+        };
+        if let Some(parent) = path.parent() {
+            // A failure shows up when the file itself is written
+            let _ = fs::create_dir_all(parent);
+        }
+        self.placed.lock().unwrap_or_else(|e| e.into_inner()).push((kind.to_string(), path.clone()));
+        path
+    }
+
+    /// Append every port result of `results` to `results.ndjson`, one JSON
+    /// object per line with its host
+    pub fn write_raw(&self, results: &ScanResult) -> io::Result<PathBuf> {
+        let path = self.place("raw", "results.ndjson");
+        let mut file = io::BufWriter::new(fs::OpenOptions::new().create(true).append(true).open(&path)?);
+        for host in results.per_host() {
+            for port in &host.port_results {
+                let mut line = serde_json::to_value(port)?;
+                line["host"] = serde_json::Value::String(host.target.clone());
+                writeln!(file, "{}", line)?;
+            }
+        }
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Copy the checkpoint at `path` in, if it is still there
+    pub fn keep_checkpoint(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let copy = self.place("checkpoint", &format!("checkpoint/{}", name));
+        fs::copy(path, &copy)?;
+        Ok(Some(copy))
+    }
+
+    /// Write `provenance.json` and then `index.json` over every file in the
+    /// workspace
+    pub fn finish(&self, target: &str, provenance: &Provenance, resume: Option<String>) -> io::Result<Manifest> {
+        let record = self.place("provenance", "provenance.json");
+        fs::write(&record, serde_json::to_string_pretty(provenance)?)?;
+
+        let mut files = Vec::new();
+        collect_files(&self.dir, &mut files)?;
+        // Files placed outside the workspace are listed too, by absolute path
+        let placed = self.placed.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        for (_, path) in &placed {
+            if !path.starts_with(&self.dir) && path.is_file() {
+                files.push(path.clone());
+            }
+        }
+        files.sort();
+        files.dedup();
+
+        let mut artifacts = Vec::new();
+        for path in files.iter().filter(|path| path.as_path() != self.dir.join(INDEX_FILE)) {
+            // The deepest placed path containing the file names its kind
+            let kind = placed
+                .iter()
+                .filter(|(_, placed)| path.starts_with(placed))
+                .max_by_key(|(_, placed)| placed.components().count())
+                .map_or("other", |(kind, _)| kind.as_str());
+            let data = fs::read(path)?;
+            artifacts.push(Artifact {
+                kind: kind.to_string(),
+                path: path.strip_prefix(&self.dir).unwrap_or(path).to_string_lossy().into_owned(),
+                bytes: data.len() as u64,
+                sha256: sha2::Sha256::digest(&data).iter().map(|b| format!("{:02x}", b)).collect(),
+            });
+        }
+        let manifest = Manifest {
+            run_id: provenance.run_id.clone(),
+            version: provenance.version.clone(),
+            target: target.to_string(),
+            started_at: self.started_at,
+            finished_at: Utc::now(),
+            resume,
+            artifacts,
+        };
+        fs::write(self.dir.join(INDEX_FILE), serde_json::to_string_pretty(&manifest)?)?;
+        Ok(manifest)
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanConfig;
+    use crate::network::{PortResult, PortState, Protocol};
+
+    #[test]
+    fn test_workspace_collects_and_indexes_files() {
+        let root = tempfile::tempdir().unwrap();
+        let workspace = Workspace::create(root.path(), "10.0.0.0/24").unwrap();
+        let again = Workspace::create(root.path(), "10.0.0.0/24").unwrap();
+        assert_ne!(workspace.dir(), again.dir());
+        assert!(workspace.dir().file_name().unwrap().to_string_lossy().ends_with("-10.0.0.0_24"));
+
+        let nmap = workspace.place("output-nmap", "reports/scan.xml");
+        assert_eq!(nmap, workspace.dir().join("reports/scan.xml"));
+        fs::write(&nmap, "<nmaprun/>").unwrap();
+        let shots = workspace.place("screenshots", "shots");
+        fs::create_dir_all(&shots).unwrap();
+        fs::write(shots.join("index.html"), "<html/>").unwrap();
+        let outside = root.path().join("elsewhere.csv");
+        assert_eq!(workspace.place("output-compliance", outside.to_str().unwrap()), outside);
+        fs::write(&outside, "rule").unwrap();
+
+        let config = ScanConfig::default();
+        let mut results = ScanResult::new("192.0.2.1".to_string(), config.clone());
+        results.add_port_result(PortResult::new(22, Protocol::Tcp, PortState::Open));
+        results.add_port_result(PortResult::new(23, Protocol::Tcp, PortState::Closed));
+        workspace.write_raw(&results).unwrap();
+        let raw = fs::read_to_string(workspace.dir().join("results.ndjson")).unwrap();
+        assert_eq!(raw.lines().count(), 2);
+        assert!(raw.lines().next().unwrap().contains("\"host\":\"192.0.2.1\""));
+
+        let checkpoint = root.path().join("20260301-101500-abcd.json");
+        fs::write(&checkpoint, "{}").unwrap();
+        assert!(workspace.keep_checkpoint(&checkpoint).unwrap().is_some());
+        assert_eq!(workspace.keep_checkpoint(&root.path().join("gone.json")).unwrap(), None);
+
+        let manifest = workspace.finish("192.0.2.1", &Provenance::collect(&config), Some("20260301-101500-abcd".to_string())).unwrap();
+        let kinds: Vec<(&str, &str)> = manifest.artifacts.iter().map(|a| (a.kind.as_str(), a.path.as_str())).collect();
+        assert!(kinds.contains(&("output-nmap", "reports/scan.xml")));
+        assert!(kinds.contains(&("screenshots", "shots/index.html")));
+        assert!(kinds.contains(&("raw", "results.ndjson")));
+        assert!(kinds.contains(&("checkpoint", "checkpoint/20260301-101500-abcd.json")));
+        assert!(kinds.contains(&("provenance", "provenance.json")));
+        assert!(kinds.contains(&("output-compliance", outside.to_str().unwrap())));
+        assert!(!kinds.iter().any(|(_, path)| *path == INDEX_FILE));
+
+        let index: Manifest = serde_json::from_slice(&fs::read(workspace.dir().join(INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(index, manifest);
+        let nmap_entry = index.artifacts.iter().find(|a| a.kind == "output-nmap").unwrap();
+        assert_eq!(nmap_entry.bytes, 10);
+        assert_eq!(nmap_entry.sha256.len(), 64);
+    }
+}
@@ -0,0 +1,103 @@
+//! Technique selection for `--auto-technique`
+//!
+//! Instead of asking for a technique, the scanner looks at what it has to
+//! work with: raw sockets allow a SYN scan, without them only a connect scan
+//! runs, a port spec naming nothing but `U:` ports needs a UDP scan, and a
+//! target on the local link can be checked with ARP before any port is
+//! probed. The choice and the reason for it are printed and kept in the
+//! report's provenance.
+
+use crate::network::route;
+use crate::network::ScanTechnique;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// How long an on-link target has to answer ARP
+pub const ARP_WAIT: Duration = Duration::from_secs(1);
+
+/// What the scanning host and the request allow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TechniqueEnvironment {
+    /// Raw sockets can be opened
+    pub raw_sockets: bool,
+    /// Every target is on the local link, so it answers ARP
+    pub on_link: bool,
+    /// The port spec selects UDP ports only
+    pub udp_only: bool,
+}
+
+impl TechniqueEnvironment {
+    /// Probe the host for `targets`, sent out of `interface` if one is forced
+    pub fn detect(targets: &[IpAddr], interface: Option<&str>, udp_only: bool) -> Self {
+        let on_link = !targets.is_empty()
+            && targets.iter().all(|target| match target {
+                IpAddr::V4(target) => route::is_on_link(*target, interface),
+                IpAddr::V6(_) => false,
+            });
+        Self {
+            raw_sockets: crate::utils::preflight::raw_socket_access().is_ok(),
+            on_link,
+            udp_only,
+        }
+    }
+}
+
+/// The technique picked for a scan, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TechniqueChoice {
+    pub technique: ScanTechnique,
+    /// Check that targets answer ARP before probing their ports
+    pub arp_discovery: bool,
+    pub reason: String,
+}
+
+impl TechniqueChoice {
+    pub fn decide(env: &TechniqueEnvironment) -> Self {
+        let (technique, mut reason) = if env.udp_only {
+            (ScanTechnique::Udp, "the port spec selects UDP ports only".to_string())
+        } else if env.raw_sockets {
+            (ScanTechnique::Syn, "raw sockets are available".to_string())
+        } else {
+            (ScanTechnique::Connect, "raw sockets are unavailable".to_string())
+        };
+        if env.on_link {
+            reason.push_str("; on-link targets are checked with ARP first");
+        }
+        Self { technique, arp_discovery: env.on_link, reason }
+    }
+}
+
+impl fmt::Display for TechniqueChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.technique.name())?;
+        if self.arp_discovery {
+            f.write_str(" + ARP")?;
+        }
+        write!(f, " ({})", self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide_per_environment() {
+        let env = TechniqueEnvironment { raw_sockets: true, on_link: false, udp_only: false };
+        let choice = TechniqueChoice::decide(&env);
+        assert_eq!((choice.technique, choice.arp_discovery), (ScanTechnique::Syn, false));
+        assert_eq!(choice.to_string(), "SYN (raw sockets are available)");
+
+        let unprivileged = TechniqueChoice::decide(&TechniqueEnvironment { raw_sockets: false, ..env });
+        assert_eq!(unprivileged.technique, ScanTechnique::Connect);
+
+        let udp = TechniqueChoice::decide(&TechniqueEnvironment { raw_sockets: false, udp_only: true, ..env });
+        assert_eq!(udp.technique, ScanTechnique::Udp);
+
+        let local = TechniqueChoice::decide(&TechniqueEnvironment { on_link: true, ..env });
+        assert!(local.arp_discovery);
+        assert!(local.to_string().starts_with("SYN + ARP ("));
+    }
+}
@@ -0,0 +1,380 @@
+//! Scan checkpoints (`--resume`)
+//!
+//! A long scan records which ports it has finished on each host and which of
+//! them were open, and every few seconds writes that to
+//! `~/.phobos/checkpoints/<id>.json`. When the scan is killed, running the
+//! same command with `--resume <id>` skips the finished ports, reports the
+//! open ones found before, and probes only what is left. A scan that runs
+//! to the end removes its checkpoint.
+//!
+//! Only answered probes count as finished: ports whose probe failed are
+//! probed again on resume.
+
+use crate::config::ScanConfig;
+use crate::network::{PortResult, PortState, ScanTechnique};
+use crate::scanner::HostResult;
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often a running scan writes its checkpoint
+pub const SAVE_EVERY: Duration = Duration::from_secs(5);
+
+/// Probes (hosts times ports) from which the CLI checkpoints a scan
+pub const MIN_PROBES: usize = 10_000;
+
+/// Where checkpoints live: `~/.phobos/checkpoints`
+pub fn checkpoint_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".phobos").join("checkpoints")
+}
+
+/// A fresh checkpoint id: the local start time and a random suffix
+pub fn new_id() -> String {
+    format!("{}-{:04x}", chrono::Local::now().format("%Y%m%d-%H%M%S"), rand::random::<u16>())
+}
+
+/// Ids name files, so they are kept to a safe alphabet
+fn validate_id(id: &str) -> crate::Result<()> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(ScanError::ConfigError(format!(
+            "invalid checkpoint id '{}': use letters, digits, '-' and '_'",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// A set of ports, written as ranges such as `1-1024,8080`
+#[derive(Clone, PartialEq, Eq)]
+pub struct PortSet {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl PortSet {
+    pub fn new() -> Self {
+        Self { bits: vec![0; 1024], len: 0 }
+    }
+
+    pub fn insert(&mut self, port: u16) -> bool {
+        let (word, bit) = (port as usize / 64, 1u64 << (port % 64));
+        let added = self.bits[word] & bit == 0;
+        if added {
+            self.bits[word] |= bit;
+            self.len += 1;
+        }
+        added
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.bits[port as usize / 64] & (1 << (port % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inclusive ranges of consecutive ports, in order
+    pub fn ranges(&self) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for port in (0..=u16::MAX).filter(|&port| self.contains(port)) {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == port => *end = port,
+                _ => ranges.push((port, port)),
+            }
+        }
+        ranges
+    }
+}
+
+impl Default for PortSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<u16> for PortSet {
+    fn from_iter<I: IntoIterator<Item = u16>>(ports: I) -> Self {
+        let mut set = Self::new();
+        for port in ports {
+            set.insert(port);
+        }
+        set
+    }
+}
+
+impl fmt::Display for PortSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (start, end)) in self.ranges().into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match start == end {
+                true => write!(f, "{}", start)?,
+                false => write!(f, "{}-{}", start, end)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PortSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PortSet({})", self)
+    }
+}
+
+impl FromStr for PortSet {
+    type Err = ScanError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let invalid = || ScanError::ParseError(format!("invalid port ranges '{}'", s));
+        let mut set = Self::new();
+        for range in s.split(',').filter(|range| !range.is_empty()) {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let start: u16 = start.parse().map_err(|_| invalid())?;
+            let end: u16 = end.parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            (start..=end).for_each(|port| {
+                set.insert(port);
+            });
+        }
+        Ok(set)
+    }
+}
+
+impl Serialize for PortSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PortSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What is known about one host
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostProgress {
+    /// Ports with an answer (or a timeout) recorded
+    pub scanned: PortSet,
+    /// The open ones among them
+    pub open: Vec<u16>,
+}
+
+/// The file written for a scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub id: String,
+    /// The scan's targets, ports and technique, which a resume must repeat
+    pub targets: Vec<String>,
+    pub ports: PortSet,
+    pub technique: ScanTechnique,
+    pub updated: chrono::DateTime<chrono::Utc>,
+    pub hosts: BTreeMap<IpAddr, HostProgress>,
+}
+
+impl Checkpoint {
+    /// An empty checkpoint for `config`
+    pub fn new(id: &str, config: &ScanConfig) -> Self {
+        Self {
+            id: id.to_string(),
+            targets: config.all_targets().into_iter().map(String::from).collect(),
+            ports: config.ports.iter().copied().collect(),
+            technique: config.technique,
+            updated: chrono::Utc::now(),
+            hosts: BTreeMap::new(),
+        }
+    }
+
+    /// Read checkpoint `path`, checking that it was taken for `config`
+    pub fn load(path: &Path, config: &ScanConfig) -> crate::Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            ScanError::ConfigError(format!("Cannot read checkpoint {}: {}", path.display(), e))
+        })?;
+        let checkpoint: Self = serde_json::from_str(&text)
+            .map_err(|e| ScanError::ParseError(format!("checkpoint {}: {}", path.display(), e)))?;
+        let expected = Self::new(&checkpoint.id, config);
+        if checkpoint.targets != expected.targets
+            || checkpoint.ports != expected.ports
+            || checkpoint.technique != expected.technique
+        {
+            return Err(ScanError::ConfigError(format!(
+                "Checkpoint {} was taken for a {:?} scan of {} ports {}; resume with the same target, ports and technique",
+                checkpoint.id, checkpoint.technique, checkpoint.targets.join(" "), checkpoint.ports
+            )));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Ports recorded across all hosts
+    pub fn scanned(&self) -> usize {
+        self.hosts.values().map(|host| host.scanned.len()).sum()
+    }
+}
+
+/// A scan's checkpoint, updated as probes finish and saved periodically
+#[derive(Debug)]
+pub struct CheckpointLog {
+    path: PathBuf,
+    state: Mutex<(Checkpoint, Instant)>,
+}
+
+impl CheckpointLog {
+    /// The checkpoint `id` in [`checkpoint_dir`]; with `resume`, continue
+    /// from the one saved there
+    pub fn open(id: &str, config: &ScanConfig, resume: bool) -> crate::Result<Self> {
+        validate_id(id)?;
+        Self::at(checkpoint_dir().join(format!("{}.json", id)), id, config, resume)
+    }
+
+    /// As [`open`](Self::open), at `path`
+    pub fn at(path: PathBuf, id: &str, config: &ScanConfig, resume: bool) -> crate::Result<Self> {
+        let checkpoint = match resume {
+            true => Checkpoint::load(&path, config)?,
+            false => Checkpoint::new(id, config),
+        };
+        Ok(Self { path, state: Mutex::new((checkpoint, Instant::now())) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn id(&self) -> String {
+        self.lock().0.id.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (Checkpoint, Instant)> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Ports recorded across all hosts, before and during this run
+    pub fn scanned(&self) -> usize {
+        self.lock().0.scanned()
+    }
+
+    /// Whether `socket` was finished before
+    pub fn is_done(&self, socket: SocketAddr) -> bool {
+        self.lock().0.hosts.get(&socket.ip()).is_some_and(|host| host.scanned.contains(socket.port()))
+    }
+
+    /// How many of `host`'s ports were finished before, with the open ones
+    /// among them added to `result`
+    pub fn restore(&self, result: &mut HostResult) -> usize {
+        let state = self.lock();
+        let Some(host) = state.0.hosts.get(&result.host) else { return 0 };
+        for &port in &host.open {
+            result.open_ports.push(port);
+            result.port_results.push(PortResult::new(port, state.0.technique.protocol(), PortState::Open));
+        }
+        host.scanned.len()
+    }
+
+    /// Record a finished probe, saving the checkpoint when one is due
+    pub fn record(&self, socket: SocketAddr, state: PortState) {
+        let mut state_guard = self.lock();
+        let (checkpoint, saved) = &mut *state_guard;
+        let host = checkpoint.hosts.entry(socket.ip()).or_default();
+        if host.scanned.insert(socket.port()) && state == PortState::Open {
+            host.open.push(socket.port());
+        }
+        if saved.elapsed() >= SAVE_EVERY {
+            *saved = Instant::now();
+            if let Err(e) = Self::write(&self.path, checkpoint) {
+                log::warn!("Cannot save checkpoint {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    /// Write the checkpoint now
+    pub fn save(&self) -> crate::Result<()> {
+        let mut state = self.lock();
+        state.1 = Instant::now();
+        Self::write(&self.path, &mut state.0)
+    }
+
+    /// The scan is complete; its checkpoint is of no more use
+    pub fn remove(&self) -> crate::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Through a temporary file, so a kill mid-write keeps the last checkpoint
+    fn write(path: &Path, checkpoint: &mut Checkpoint) -> crate::Result<()> {
+        checkpoint.updated = chrono::Utc::now();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension("json.partial");
+        let json = serde_json::to_vec(checkpoint)
+            .map_err(|e| ScanError::OutputError(format!("Cannot encode checkpoint: {}", e)))?;
+        std::fs::write(&partial, json)?;
+        std::fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_set_ranges() {
+        let set: PortSet = [80, 1, 2, 3, 443, 65535, 81].into_iter().collect();
+        assert_eq!(set.len(), 7);
+        assert_eq!(set.to_string(), "1-3,80-81,443,65535");
+        assert_eq!("1-3,80-81,443,65535".parse::<PortSet>().unwrap(), set);
+        assert!("".parse::<PortSet>().unwrap().is_empty());
+        assert!("9-1".parse::<PortSet>().is_err());
+        assert!("http".parse::<PortSet>().is_err());
+    }
+
+    #[test]
+    fn test_resumes_where_it_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scan.json");
+        let mut config = ScanConfig::new("192.0.2.0/30".to_string());
+        config.ports = (1..=100).collect();
+        let host: IpAddr = "192.0.2.1".parse().unwrap();
+
+        let log = CheckpointLog::at(path.clone(), "scan", &config, false).unwrap();
+        for port in 1..=40 {
+            let state = if port == 22 { PortState::Open } else { PortState::Closed };
+            log.record(SocketAddr::new(host, port), state);
+        }
+        log.save().unwrap();
+
+        let resumed = CheckpointLog::at(path.clone(), "scan", &config, true).unwrap();
+        assert_eq!(resumed.scanned(), 40);
+        assert!(resumed.is_done(SocketAddr::new(host, 40)));
+        assert!(!resumed.is_done(SocketAddr::new(host, 41)));
+        let mut result = HostResult::new(host);
+        assert_eq!(resumed.restore(&mut result), 40);
+        assert_eq!(result.open_ports, [22]);
+
+        config.ports = (1..=1000).collect();
+        let mismatch = CheckpointLog::at(path.clone(), "scan", &config, true).unwrap_err();
+        assert!(mismatch.to_string().contains("same target"), "{}", mismatch);
+
+        resumed.remove().unwrap();
+        assert!(!path.exists());
+        assert!(validate_id("../etc/passwd").is_err());
+    }
+}
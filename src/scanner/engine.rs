@@ -1,60 +1,103 @@
 //! Main scanning engine implementation
 
 use crate::config::ScanConfig;
+#[cfg(feature = "intelligence")]
+use crate::intelligence::ServiceProbes;
 use crate::network::{
-    protocol::{NetworkUtils, RateLimiter, ResponseAnalyzer, ServiceDatabase},
-    socket::{SocketPool, TcpConnectScanner, UdpScanner},
-    PortResult, PortState, Protocol, ScanTechnique,
+    packet::PacketParser,
+    protocol::{NetworkUtils, ProbeOutcome, RateLimiter, ResponseAnalyzer, ServiceDatabase},
+    socket::{ConnectionPool, PortExhaustionMonitor, RawSocket, SocketPool, TcpConnectScanner, TcpConnector, UdpScanner},
+    Confidence, PortResult, PortState, Protocol, ScanTechnique,
 };
-use crate::scanner::{create_batches, ScanBatch, ScanResult, ScanStats};
-// REMOVED: unused HashMap import after connection_pool elimination
+use crate::network::anomaly::Anomaly;
+use crate::network::bpf::FilterExpr;
+use crate::network::dialer::{Dialers, ProbeStream};
+use crate::network::mtu::PathMtu;
+use crate::network::ipv6::Ipv6SynLink;
+use crate::network::stealth::DecoyLink;
+use crate::network::send_thread::SendThread;
+use crate::network::vlan::VlanLink;
+use crate::scanner::{
+    create_batches, health, latency, verify, CancellationToken, CheckpointLog, GeoIpDb, HealthGate, HookContext, HookEvent,
+    HostCallback, HostResult, PortCallback, ProgressTracker, ScanBatch, ScanHooks, ScanResult, ScanStats, TaskGroup, VerifyMethod,
+};
+use crate::scanner::health::HealthProbe;
+use crate::scanner::memory::MemoryGuard;
+use crate::scanner::techniques::{self, IdleScan, ScanTechniqueImpl};
+use crate::utils::scan_options::{order_hosts, HostOrder};
+use crate::utils::MemoryMonitor;
+use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
 use tokio::time::timeout;
-use futures::stream::{FuturesUnordered, StreamExt};
 use std::io;
 
 // System resource detection for optimal batch sizing
 #[cfg(unix)]
 use rlimit::{getrlimit, Resource};
+#[cfg(unix)]
+use crate::utils::device::DeviceResources;
 
 // Batch sizing constants (optimized for performance)
 const DEFAULT_FILE_DESCRIPTORS_LIMIT: u64 = 8000;
 const AVERAGE_BATCH_SIZE: u16 = 3000;
 const MIN_BATCH_SIZE: u16 = 100;
 const MAX_BATCH_SIZE: u16 = 15000;
+/// Connect attempts per socket on the fast path
+pub(crate) const CONNECT_TRIES: u32 = 2;
 // use rayon::prelude::*; // Unused import removed
 
 /// Socket iterator for memory-efficient on-demand socket generation
 #[derive(Debug, Clone)]
 pub struct SocketIterator {
-    ips: Vec<Ipv4Addr>,
+    ips: Vec<IpAddr>,
     ports: Vec<u16>,
     current_ip_index: usize,
     current_port_index: usize,
+    interleave: bool,
 }
 
 impl SocketIterator {
-    pub fn new(ips: &[Ipv4Addr], ports: &[u16]) -> Self {
+    pub fn new<A: Copy + Into<IpAddr>>(ips: &[A], ports: &[u16]) -> Self {
         Self {
-            ips: ips.to_vec(),
+            ips: ips.iter().map(|&ip| ip.into()).collect(),
             ports: ports.to_vec(),
             current_ip_index: 0,
             current_port_index: 0,
+            interleave: false,
         }
     }
     
+    /// Alternate between hosts: every host's first port, then every host's second, ...
+    pub fn interleaved<A: Copy + Into<IpAddr>>(ips: &[A], ports: &[u16]) -> Self {
+        Self { interleave: true, ..Self::new(ips, ports) }
+    }
+    
     pub fn next(&mut self) -> Option<SocketAddr> {
-        if self.current_ip_index >= self.ips.len() {
+        if self.interleave {
+            if self.ips.is_empty() || self.current_port_index >= self.ports.len() {
+                return None;
+            }
+            let socket = SocketAddr::new(self.ips[self.current_ip_index], self.ports[self.current_port_index]);
+            self.current_ip_index += 1;
+            if self.current_ip_index >= self.ips.len() {
+                self.current_ip_index = 0;
+                self.current_port_index += 1;
+            }
+            return Some(socket);
+        }
+        
+        if self.current_ip_index >= self.ips.len() || self.ports.is_empty() {
             return None;
         }
         
         let ip = self.ips[self.current_ip_index];
         let port = self.ports[self.current_port_index];
-        let socket = SocketAddr::new(IpAddr::V4(ip), port);
+        let socket = SocketAddr::new(ip, port);
         
         // Move to next port
         self.current_port_index += 1;
@@ -67,6 +110,15 @@ impl SocketIterator {
         
         Some(socket)
     }
+    
+    /// Number of sockets generated for each host
+    pub fn ports_per_host(&self) -> usize {
+        self.ports.len()
+    }
+    
+    pub fn hosts(&self) -> &[IpAddr] {
+        &self.ips
+    }
 }
 
 /// Streaming scan result for reduced memory usage
@@ -77,6 +129,14 @@ pub struct StreamingResult {
     pub total_scanned: u32,
     pub duration: Duration,
     pub memory_saved_mb: f64,
+    /// Stopped through [`StreamingScanEngine::cancel_token`] before the end
+    pub cancelled: bool,
+}
+
+/// A connection made by a connect probe
+enum Probe {
+    Kernel(tokio::net::TcpStream),
+    Dialed(Box<dyn ProbeStream>),
 }
 
 /// Main scanning engine
@@ -87,15 +147,101 @@ pub struct ScanEngine {
     tcp_scanner: Option<TcpConnectScanner>,
     #[allow(dead_code)]
     udp_scanner: Option<UdpScanner>,
-    #[allow(dead_code)]
-    rate_limiter: Arc<Mutex<RateLimiter>>,
+    rate_limiter: Arc<RateLimiter>,
     service_db: ServiceDatabase,
     #[allow(dead_code)]
     response_analyzer: ResponseAnalyzer,
     // Performance optimization fields
     adaptive_batch_size: Arc<AtomicU64>,
-    // REMOVED: connection_pool - eliminated lock contention overhead
+    // Only set when enrichment wants to reuse discovery connections
+    connection_pool: Option<ConnectionPool>,
+    connector: TcpConnector,
+    // Consumer-supplied transports replacing the connector and name lookup
+    dialers: Dialers,
+    exhaustion_monitor: Arc<PortExhaustionMonitor>,
     performance_stats: Arc<Mutex<PerformanceStats>>,
+    // Live counters for periodic status reports; also counts retries
+    progress: Arc<ProgressTracker>,
+    // 802.1Q tagged probing, replacing the host stack when a VLAN is set
+    vlan_link: Option<Arc<VlanLink>>,
+    // Raw SYN probes to IPv6 targets, which the socket pool cannot send
+    syn6_link: Option<Arc<Ipv6SynLink>>,
+    // Raw IPv4 SYN probes sent among spoofed ones when decoys are configured
+    decoys: Option<Arc<DecoyLink>>,
+    // Sends pooled raw SYN probes off the runtime when `config.send_thread` is set
+    send_thread: Option<Arc<SendThread>>,
+    path_mtu: PathMtu,
+    host_callbacks: HostCallbacks,
+    port_callbacks: PortCallbacks,
+    // Shared by clones; stops every task group of the running scan
+    cancel: CancellationToken,
+    // Locations for latency sanity hints, loaded from `config.geoip`
+    geoip: Option<Arc<GeoIpDb>>,
+    // Sampled during a scan when `config.health_check` is set
+    health_probe: Option<Arc<HealthProbe>>,
+    // Throttle the health monitor sets; full rate without one
+    health_gate: Arc<HealthGate>,
+    // Bundled probe database, loaded when `config.service_version` is set
+    #[cfg(feature = "intelligence")]
+    service_probes: Option<Arc<ServiceProbes>>,
+    // Finished ports, saved periodically when `config.checkpoint` is set
+    checkpoint: Option<Arc<CheckpointLog>>,
+    // Lifecycle hooks: `config.hooks` commands and `with_hook` callbacks
+    hooks: ScanHooks,
+}
+
+/// One host's share of a task group
+struct HostGroup {
+    /// Ports still to finish
+    remaining: usize,
+    result: HostResult,
+    started: Instant,
+    /// Bounds how many of the host's probes run at once
+    permits: Arc<Semaphore>,
+}
+
+impl HostGroup {
+    fn new(host: IpAddr, ports: usize, concurrency: usize) -> Self {
+        Self {
+            remaining: ports,
+            result: HostResult::new(host),
+            started: Instant::now(),
+            permits: Arc::new(Semaphore::new(concurrency)),
+        }
+    }
+    
+    fn finish(mut self) -> HostResult {
+        self.result.duration = self.started.elapsed();
+        self.result
+    }
+}
+
+/// Callbacks registered with [`ScanEngine::with_host_callback`]
+#[derive(Clone, Default)]
+struct HostCallbacks(Vec<HostCallback>);
+
+impl std::fmt::Debug for HostCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HostCallbacks({})", self.0.len())
+    }
+}
+
+/// Callbacks registered with [`ScanEngine::with_port_callback`]
+#[derive(Clone, Default)]
+struct PortCallbacks(Vec<PortCallback>);
+
+impl PortCallbacks {
+    fn open(&self, host: IpAddr, port: &PortResult) {
+        for callback in &self.0 {
+            callback(host, port);
+        }
+    }
+}
+
+impl std::fmt::Debug for PortCallbacks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PortCallbacks({})", self.0.len())
+    }
 }
 
 /// Performance statistics for adaptive optimization
@@ -107,6 +253,8 @@ pub struct PerformanceStats {
     average_response_time: Duration,
     optimal_batch_size: u16,
     last_optimization: Option<Instant>,
+    /// Tuning changes made on the fly, reported in the scan statistics
+    decisions: Vec<String>,
 }
 
 impl Default for ScanEngine {
@@ -117,12 +265,31 @@ impl Default for ScanEngine {
             socket_pool: None,
             tcp_scanner: None,
             udp_scanner: None,
-            rate_limiter: Arc::new(Mutex::new(RateLimiter::new(1000))),
+            rate_limiter: Arc::new(RateLimiter::new(1000)),
             service_db: ServiceDatabase::new(),
             response_analyzer: ResponseAnalyzer::new(ScanTechnique::Syn),
             adaptive_batch_size: Arc::new(AtomicU64::new(optimal_batch as u64)),
-            // REMOVED: connection_pool initialization
+            connection_pool: None,
+            connector: TcpConnector::default(),
+            dialers: Dialers::default(),
+            exhaustion_monitor: Arc::new(PortExhaustionMonitor::new()),
             performance_stats: Arc::new(Mutex::new(PerformanceStats::default())),
+            progress: Arc::new(ProgressTracker::new()),
+            vlan_link: None,
+            syn6_link: None,
+            decoys: None,
+            send_thread: None,
+            path_mtu: PathMtu::default(),
+            host_callbacks: HostCallbacks::default(),
+            port_callbacks: PortCallbacks::default(),
+            cancel: CancellationToken::new(),
+            geoip: None,
+            health_probe: None,
+            health_gate: Arc::new(HealthGate::default()),
+            #[cfg(feature = "intelligence")]
+            service_probes: None,
+            checkpoint: None,
+            hooks: ScanHooks::default(),
         }
     }
 }
@@ -163,6 +330,15 @@ impl ScanEngine {
                 log::debug!("File limit ({}) is higher than batch size. Could increase to: {}", ulimit, ulimit - 100);
             }
             
+            // Sockets are charged to a container's memory limit, which is
+            // usually reached long before its descriptor limit
+            if let Some(budget) = DeviceResources::current().socket_budget() {
+                if (budget as u64) < batch_size {
+                    log::warn!("Container memory limit allows about {} sockets; lowering batch size from {}", budget, batch_size);
+                    batch_size = budget as u64;
+                }
+            }
+            
             let final_batch = (batch_size as usize).clamp(MIN_BATCH_SIZE as usize, MAX_BATCH_SIZE as usize);
             log::info!("🚀 Optimal batch size: {} (ulimit: {})", final_batch, ulimit);
             
@@ -184,12 +360,36 @@ impl ScanEngine {
         let technique = config.technique;
         let timeout_duration = config.timeout_duration();
         
+        // Opened before the socket pool: the link's receiver waits with select(),
+        // which only accepts descriptors below FD_SETSIZE
+        let vlan_link = config
+            .vlan
+            .map(|vlan| VlanLink::open(config.interface.as_deref(), vlan, config.vlan_source))
+            .transpose()?;
+        
+        // Raw probes are sized here, so they follow the path MTU; connect scans
+        // leave that to the kernel unless it is overridden
+        let path_mtu = match config.mtu_override {
+            Some(mtu) => PathMtu::fixed(mtu),
+            None if !technique.requires_raw_socket() => PathMtu::default(),
+            // Tagged targets are not routed by the host; only the link counts
+            None => match &vlan_link {
+                Some(link) => PathMtu::detect(None, Some(link.interface())),
+                None => PathMtu::detect(config.target.parse().ok(), config.interface.as_deref()),
+            },
+        };
+        let vlan_link = vlan_link.map(|link| Arc::new(link.with_mss(path_mtu.mss())));
+        
         // Initialize components with maximum performance optimization
         let (socket_pool, tcp_scanner, udp_scanner) = if technique.requires_raw_socket() {
             // Try to create optimized raw socket pool
             match SocketPool::new(1000, 500) { // Increased pool sizes for performance
                 Ok(pool) => {
                     log::info!("High-performance raw socket pool initialized");
+                    Self::filter_responses(&pool, &config)?;
+                    if config.notrack {
+                        pool.set_mark(crate::network::conntrack::PROBE_MARK)?;
+                    }
                     (Some(pool), None, None)
                 }
                 Err(e) => {
@@ -211,7 +411,7 @@ impl ScanEngine {
                         None
                     };
                     let udp_scanner = if technique == ScanTechnique::Udp {
-                        Some(UdpScanner::new(timeout_duration))
+                        Some(UdpScanner::new(timeout_duration).with_max_payload(path_mtu.max_udp_payload()))
                     } else {
                         None
                     };
@@ -225,27 +425,97 @@ impl ScanEngine {
                 None
             };
             let udp_scanner = if technique == ScanTechnique::Udp {
-                Some(UdpScanner::new(timeout_duration))
+                Some(UdpScanner::new(timeout_duration).with_max_payload(path_mtu.max_udp_payload()))
             } else {
                 None
             };
             (None, tcp_scanner, udp_scanner)
         };
         
-        let rate_limiter = Arc::new(Mutex::new(RateLimiter::new(config.rate_limit)));
+        // Only SYN scans go raw over IPv6; without the privilege for the
+        // socket pool there is none for this socket either
+        let syn6_link = match (technique, &socket_pool) {
+            (ScanTechnique::Syn, Some(_)) => match Ipv6SynLink::open() {
+                Ok(link) => Some(Arc::new(link.with_mss(path_mtu.mss()))),
+                Err(e) => {
+                    log::warn!("IPv6 SYN probes unavailable ({}); IPv6 targets get TCP Connect", e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        
+        // Decoys hide crafted SYNs, so they need the raw sockets a connect scan does without
+        let decoys = match (&socket_pool, &config.stealth_options) {
+            (Some(_), Some(stealth)) => DecoyLink::open(stealth)?.map(|link| Arc::new(link.with_mss(path_mtu.mss()))),
+            (None, Some(stealth)) if !stealth.decoy_addresses.is_empty() => {
+                log::warn!("Decoys need raw sockets; scanning without them");
+                None
+            }
+            _ => None,
+        };
+        if let Some(decoys) = &decoys {
+            log::info!("Each SYN probe is sent among {} decoys", decoys.decoy_count());
+        }
+        
+        // Without the socket pool there are no raw probes to isolate
+        let send_thread = match (&socket_pool, config.send_thread) {
+            (Some(_), true) => {
+                let mark = config.notrack.then_some(crate::network::conntrack::PROBE_MARK);
+                Some(Arc::new(SendThread::spawn(config.effective_rate(), mark)?))
+            }
+            _ => None,
+        };
+        
+        let rate_limiter = Arc::new(config.rate_limiter());
         let service_db = ServiceDatabase::new();
         let response_analyzer = ResponseAnalyzer::new(technique);
         
         // RustScan-style: Infer optimal batch size from system
         let initial_batch_size = Self::infer_optimal_batch_size(config.batch_size);
         let adaptive_batch_size = Arc::new(AtomicU64::new(initial_batch_size as u64));
-        // REMOVED: connection_pool initialization - lock overhead eliminated
+        let requested_batch_size = config.batch_size.unwrap_or(AVERAGE_BATCH_SIZE as usize);
+        let mut decisions = Vec::new();
+        if initial_batch_size != requested_batch_size {
+            decisions.push(format!(
+                "batch size {} -> {} to fit the open file limit",
+                requested_batch_size, initial_batch_size
+            ));
+        }
+        if path_mtu.is_reduced() {
+            decisions.push(format!("probes clamped to path MTU {}", path_mtu));
+        }
+        if let Some(thread) = &send_thread {
+            decisions.push(format!("raw probes sent from a dedicated thread at {}", thread.priority()));
+        }
         let performance_stats = Arc::new(Mutex::new(PerformanceStats {
             optimal_batch_size: initial_batch_size as u16,
             last_optimization: Some(Instant::now()),
+            decisions,
             ..Default::default()
         }));
         
+        let connector = TcpConnector::new(config.connect_options());
+        let geoip = config.geoip.as_deref().map(GeoIpDb::load).transpose()?.map(Arc::new);
+        let health_probe = config
+            .health_check
+            .as_ref()
+            .map(|check| HealthProbe::new(check, &config))
+            .transpose()?
+            .map(Arc::new);
+        #[cfg(feature = "intelligence")]
+        let service_probes = match config.service_version {
+            true => Some(Arc::new(ServiceProbes::bundled()?)),
+            false => None,
+        };
+        let checkpoint = config
+            .checkpoint
+            .as_deref()
+            .map(|id| CheckpointLog::open(id, &config, config.resume))
+            .transpose()?
+            .map(Arc::new);
+        let hooks = ScanHooks::new(&config.hooks);
+        
         Ok(Self {
             config,
             socket_pool,
@@ -255,11 +525,153 @@ impl ScanEngine {
             service_db,
             response_analyzer,
             adaptive_batch_size,
-            // REMOVED: connection_pool field
+            connection_pool: None,
+            connector,
+            dialers: Dialers::default(),
+            exhaustion_monitor: Arc::new(PortExhaustionMonitor::new()),
             performance_stats,
+            progress: Arc::new(ProgressTracker::new()),
+            vlan_link,
+            syn6_link,
+            decoys,
+            send_thread,
+            path_mtu,
+            host_callbacks: HostCallbacks::default(),
+            port_callbacks: PortCallbacks::default(),
+            cancel: CancellationToken::new(),
+            geoip,
+            health_probe,
+            health_gate: Arc::new(HealthGate::default()),
+            #[cfg(feature = "intelligence")]
+            service_probes,
+            checkpoint,
+            hooks,
         })
     }
     
+    /// Drop unrelated traffic on the raw sockets before it reaches us
+    ///
+    /// The engine's own filter is best effort; a user-supplied
+    /// `capture_filter` that cannot be attached is an error instead of being
+    /// silently ignored.
+    fn filter_responses(pool: &SocketPool, config: &ScanConfig) -> crate::Result<()> {
+        let target = match config.target.split_once('/') {
+            Some((addr, prefix)) => addr.parse().ok().zip(prefix.parse().ok().filter(|p| *p <= 32)),
+            None => config.target.parse().ok().map(|addr| (addr, 32)),
+        };
+        let extra = config.capture_filter.as_deref().map(FilterExpr::parse).transpose()?;
+        match pool.attach_capture_filters(target, extra.as_ref()) {
+            Ok(()) => Ok(()),
+            Err(e) if extra.is_none() => {
+                log::warn!("Receiving unfiltered: {}", e);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+    
+    /// Keep open connections found during the scan in `pool` so that
+    /// service detection can reuse them instead of reconnecting
+    pub fn with_connection_pool(mut self, pool: ConnectionPool) -> Self {
+        self.tcp_scanner = self.tcp_scanner.map(|scanner| scanner.with_connection_pool(pool.clone()));
+        self.connection_pool = Some(pool);
+        self
+    }
+    
+    /// Open connections and resolve the target through `dialers` instead
+    /// of the built-in sockets and resolver
+    ///
+    /// Only connect probes use the TCP dialer; VLAN probing keeps its own
+    /// link, and connections from a dialer are never pooled. UDP dialers are
+    /// given to [`UdpScanner::with_dialer`](crate::scanner::udp::UdpScanner::with_dialer).
+    pub fn with_dialers(mut self, dialers: Dialers) -> Self {
+        self.dialers = dialers;
+        self
+    }
+    
+    /// Call `callback` with each host's results as soon as that host is done
+    ///
+    /// Callbacks run as separate tasks while the scan continues with other
+    /// hosts; [`scan`](Self::scan) returns once all of them have finished.
+    /// Several callbacks may be registered; each sees every host.
+    pub fn with_host_callback<F, Fut>(mut self, callback: F) -> Self
+    where
+        F: Fn(HostResult) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.host_callbacks.0.push(Arc::new(move |host| Box::pin(callback(host))));
+        self
+    }
+    
+    /// Call `callback` with each open port as soon as it is found, before
+    /// its host is done
+    ///
+    /// Callbacks run on the scan's own task; keep them short and hand
+    /// longer work off.
+    pub fn with_port_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(IpAddr, &PortResult) + Send + Sync + 'static,
+    {
+        self.port_callbacks.0.push(Arc::new(callback));
+        self
+    }
+    
+    /// Call `hook` at scan start, host start, host complete and scan complete
+    ///
+    /// Hooks run on the scan's own task, in event order, next to the
+    /// `config.hooks` commands; keep them short and hand longer work off.
+    pub fn with_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&HookContext) + Send + Sync + 'static,
+    {
+        self.hooks = self.hooks.with_callback(Arc::new(hook));
+        self
+    }
+    
+    /// Run the host hooks for `event`
+    fn host_hook(&self, event: HookEvent, host: &HostResult) {
+        self.hooks.fire(event, || host.host.to_string(), &host.open_ports, self.progress.snapshot().elapsed);
+    }
+    
+    /// Number of connects that failed because local ports were exhausted
+    pub fn port_exhaustion_errors(&self) -> u64 {
+        self.exhaustion_monitor.exhaustion_errors()
+    }
+    
+    /// Counters for reporting progress while [`scan`](Self::scan) runs
+    pub fn progress(&self) -> Arc<ProgressTracker> {
+        Arc::clone(&self.progress)
+    }
+    
+    /// Token that stops [`scan`](Self::scan) early
+    ///
+    /// Cancelling drops every probe in flight and aborts host callbacks that
+    /// are still running; `scan` then returns what it found so far with
+    /// [`ScanStats::cancelled`] set.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+    
+    /// Throttle set by the health check, if `config.health_check` is set
+    pub fn health_gate(&self) -> Arc<HealthGate> {
+        Arc::clone(&self.health_gate)
+    }
+    
+    /// MTU that raw probes are sized for
+    pub fn path_mtu(&self) -> &PathMtu {
+        &self.path_mtu
+    }
+    
+    /// Connection pool shared with enrichment, if enabled
+    pub fn connection_pool(&self) -> Option<&ConnectionPool> {
+        self.connection_pool.as_ref()
+    }
+    
+    /// Progress saved for `--resume`, when `config.checkpoint` is set
+    pub fn checkpoint(&self) -> Option<&CheckpointLog> {
+        self.checkpoint.as_deref()
+    }
+    
     /// Perform the main scan operation
     pub async fn scan(&self) -> crate::Result<ScanResult> {
         let start_time = Instant::now();
@@ -267,7 +679,35 @@ impl ScanEngine {
         // Pre-optimize batch size based on system capabilities
         self.optimize_batch_size().await?;
         
-        let result = self.execute_high_performance_scan().await?;
+        let hosts = self.config.target_count();
+        let resumed = self.checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.scanned()) as u64;
+        let planned = hosts.saturating_mul(self.config.ports.len() as u64).saturating_sub(resumed);
+        self.progress.start(planned);
+        let targets = || self.config.all_targets().join(",");
+        self.hooks.fire(HookEvent::ScanStart, targets, &[], Duration::ZERO);
+        if self.config.technique == ScanTechnique::Connect {
+            self.exhaustion_monitor.preflight(self.connector.options(), planned);
+        }
+        
+        let setup = start_time.elapsed();
+        
+        let outcome = match &self.config.zombie {
+            Some(zombie) => self.execute_idle_scan(zombie).await,
+            None => self.execute_high_performance_scan().await,
+        };
+        // Kept for --resume unless every port was finished
+        if let Some(checkpoint) = &self.checkpoint {
+            match &outcome {
+                Ok(result) if !result.stats.cancelled => checkpoint.remove()?,
+                _ => checkpoint.save()?,
+            }
+        }
+        let mut result = outcome?;
+        result.stats.engine_timings.setup = setup;
+        
+        self.hooks.fire(HookEvent::ScanComplete, targets, &result.open_ports, self.progress.snapshot().elapsed);
+        let hooks = self.hooks.clone();
+        let _ = tokio::task::spawn_blocking(move || hooks.flush()).await;
         
         let scan_duration = start_time.elapsed();
         log::info!("High-performance scan completed in {:?} for {} ports", 
@@ -284,119 +724,590 @@ impl ScanEngine {
         let start_time = Instant::now();
         
         // Parse target IPs
-        let target_ips = NetworkUtils::parse_cidr(&self.config.target)?;
-        let _ports = &self.config.ports;
+        let mut target_ips = self.target_ips().await?;
+        let ports = &self.config.ports;
         
         let mut all_results = Vec::new();
         let mut total_stats = ScanStats::default();
+        // Host callbacks run alongside the scan and are awaited at the end
+        let mut callbacks = JoinSet::new();
+        // Past --memory-limit, finished hosts wait on disk until probing is done
+        let mut memory = self.config.memory_limit.map(MemoryGuard::new);
+        
+        // The monitor stops with the scan, however it ends
+        let health_stop = self.cancel.child_token();
+        let _health_guard = health_stop.clone().drop_guard();
+        let health_reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        if let Some(probe) = &self.health_probe {
+            let (probe, gate, reports) = (Arc::clone(probe), Arc::clone(&self.health_gate), Arc::clone(&health_reports));
+            let threshold = self.config.health_threshold.unwrap_or(health::DEFAULT_THRESHOLD);
+            tokio::spawn(async move {
+                health::monitor(&probe, threshold, &gate, health_stop, |report| {
+                    log::warn!("{}", report);
+                    reports.lock().unwrap().push(report);
+                })
+                .await;
+            });
+        }
         
-        // Use parallel processing for multiple IPs
-        let results: Vec<_> = futures::future::join_all(
-            target_ips.into_iter().map(|ip| {
-                self.scan_single_host_high_performance(ip)
-            })
-        ).await;
+        // Interleaving spreads the batch over every host, so no single host sees
+        // a burst; sweeping hosts one at a time keeps each host's probes together.
+        // A fatal error returns here, and dropping `callbacks` aborts them.
+        let mut hosts = match self.config.host_order {
+            HostOrder::Interleave => {
+                let sockets = SocketIterator::interleaved(&target_ips, ports);
+                self.scan_sockets_high_performance(sockets, &mut callbacks, memory.as_mut()).await?
+            }
+            host_order => {
+                order_hosts(&mut target_ips, host_order, self.config.seed);
+                let mut hosts = Vec::with_capacity(target_ips.len());
+                for ip in target_ips {
+                    if self.cancel.is_cancelled() {
+                        break;
+                    }
+                    let sockets = SocketIterator::new(&[ip], ports);
+                    hosts.append(&mut self.scan_sockets_high_performance(sockets, &mut callbacks, memory.as_mut()).await?);
+                    if let Some(memory) = memory.as_mut() {
+                        memory.spill(&mut hosts);
+                    }
+                }
+                hosts
+            }
+        };
+        if let Some(memory) = memory {
+            hosts = memory.restore(hosts)?;
+        }
+        total_stats.engine_timings.probing = start_time.elapsed();
+        
+        // Callbacks were held back so that they only see confirmed ports
+        if self.config.verify_open {
+            let verify_started = Instant::now();
+            let method = VerifyMethod::for_technique(self.config.technique);
+            for host in &mut hosts {
+                if !self.cancel.is_cancelled() {
+                    self.verify_host(host, method).await;
+                }
+                self.host_hook(HookEvent::HostComplete, host);
+                for callback in &self.host_callbacks.0 {
+                    callbacks.spawn(callback(host.clone()));
+                }
+            }
+            total_stats.engine_timings.verifying = verify_started.elapsed();
+        }
         
-        for result in results {
-            match result {
-                Ok((mut host_results, stats)) => {
-                     all_results.append(&mut host_results);
-                     // Merge stats manually
-                     total_stats.packets_sent += stats.packets_sent;
-                     total_stats.packets_received += stats.packets_received;
-                     total_stats.timeouts += stats.timeouts;
-                     total_stats.errors += stats.errors;
-                 }
-                Err(e) => {
-                    log::warn!("Host scan failed: {}", e);
+        #[cfg(feature = "intelligence")]
+        if let Some(probes) = &self.service_probes {
+            let detect_started = Instant::now();
+            for host in &mut hosts {
+                if !self.cancel.is_cancelled() {
+                    self.detect_services(host, probes).await;
                 }
             }
+            total_stats.engine_timings.service_detection = detect_started.elapsed();
         }
         
+        let geo = self.geoip.as_deref().zip(self.config.geo_origin);
+        let host_latency = latency::assess(&hosts, geo);
+        
+        // Hosts of a multi-host scan are kept for per-host reports
+        let multi_host = hosts.len() > 1;
+        for host in &mut hosts {
+            match multi_host {
+                true => all_results.extend(host.port_results.iter().cloned()),
+                false => all_results.append(&mut host.port_results),
+            }
+            // Merge stats manually
+            let stats = &host.stats;
+            total_stats.packets_sent += stats.packets_sent;
+            total_stats.packets_received += stats.packets_received;
+            total_stats.timeouts += stats.timeouts;
+            total_stats.errors += stats.errors;
+            total_stats.ports_closed += stats.ports_closed;
+            total_stats.ports_filtered += stats.ports_filtered;
+            total_stats.verified_open += stats.verified_open;
+            total_stats.unconfirmed_open += stats.unconfirmed_open;
+        }
+        
+        let callbacks_started = Instant::now();
+        if self.cancel.is_cancelled() {
+            callbacks.shutdown().await;
+        } else {
+            while let Some(outcome) = callbacks.join_next().await {
+                if let Err(e) = outcome {
+                    log::warn!("Host callback failed: {}", e);
+                }
+            }
+        }
+        total_stats.engine_timings.callbacks = callbacks_started.elapsed();
+        total_stats.cancelled = self.cancel.is_cancelled();
+        
         let scan_duration = start_time.elapsed();
         
+        total_stats.retries = self.progress.retries();
+        let probes = total_stats.packets_sent + total_stats.retries;
+        if scan_duration.as_secs_f64() > 0.0 {
+            total_stats.actual_rate = probes as f64 / scan_duration.as_secs_f64();
+        }
+        total_stats.adaptive_decisions = self.performance_stats.lock().await.decisions.clone();
+        total_stats.adaptive_decisions.extend(self.rate_limiter.ramp_decisions());
+        total_stats.adaptive_decisions.extend(health_reports.lock().unwrap().drain(..));
+        let exhausted = self.port_exhaustion_errors();
+        if exhausted > 0 {
+            total_stats.adaptive_decisions.push(format!(
+                "backed off {} probes after running out of local ports",
+                exhausted
+            ));
+        }
+        
         let mut result = ScanResult::new(self.config.target.clone(), self.config.clone());
          
          // Add all port results
+         // Each host's ports are distinct already; only other hosts repeat them
          for port_result in all_results {
-             result.add_port_result(port_result);
+             result.push_port_result(port_result);
          }
          
          result.set_duration(scan_duration);
          result.update_stats(total_stats);
+         result.host_latency = host_latency;
+         if multi_host {
+             hosts.sort_by_key(|host| host.host);
+             result.hosts = hosts;
+         }
          
          Ok(result)
     }
     
-    /// Ultra-fast scan using continuous FuturesUnordered queue
+    /// Idle scan through `zombie`, one port at a time
+    ///
+    /// Every probe the target sees comes from the zombie's address. Ports
+    /// that do not show open are reported closed|filtered, as an idle scan
+    /// cannot tell the two apart.
+    async fn execute_idle_scan(&self, zombie: &str) -> crate::Result<ScanResult> {
+        let start_time = Instant::now();
+        let timeout = self.config.timeout_duration();
+        let (host, zombie_port) = techniques::parse_zombie(zombie)?;
+        let zombie_ip = tokio::net::lookup_host((host.as_str(), zombie_port))
+            .await?
+            .find_map(|addr| match addr.ip() {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .ok_or_else(|| crate::error::ScanError::InvalidTarget(format!("zombie {} has no IPv4 address", host)))?;
+        
+        // Spoofed SYNs carry the zombie's address, so the IP header is ours to write
+        let socket = RawSocket::new_tcp()?;
+        socket.set_header_included(true)?;
+        let idle = IdleScan::qualify(&socket, zombie_ip, zombie_port, timeout).await?;
+        log::info!("Zombie {}:{} qualified; its IP ID moves by up to {} between probes",
+                  zombie_ip, zombie_port, idle.sequence().increment_value);
+        
+        let mut stats = ScanStats::default();
+        let mut result = ScanResult::new(self.config.target.clone(), self.config.clone());
+        let target_ips = self.target_ips().await?;
+        let mut hosts = Vec::with_capacity(target_ips.len());
+        for ip in target_ips {
+            let IpAddr::V4(ip) = ip else {
+                return Err(crate::error::ScanError::InvalidTarget(format!("{}: idle scans are IPv4 only", ip)));
+            };
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let host_started = Instant::now();
+            let mut host = HostResult::new(ip);
+            self.host_hook(HookEvent::HostStart, &host);
+            for &port in &self.config.ports {
+                if self.cancel.is_cancelled() {
+                    host.stats.cancelled = true;
+                    break;
+                }
+                let probe_started = Instant::now();
+                let open = idle.scan_port(&socket, ip, port, timeout).await?;
+                self.progress.record_completed(open);
+                host.stats.packets_sent += 1;
+                let state = if open {
+                    host.open_ports.push(port);
+                    PortState::Open
+                } else {
+                    host.stats.ports_filtered += 1;
+                    PortState::ClosedFiltered
+                };
+                let mut port_result = PortResult::new(port, Protocol::Tcp, state);
+                port_result.response_time = probe_started.elapsed();
+                if open {
+                    self.port_callbacks.open(IpAddr::V4(ip), &port_result);
+                }
+                host.port_results.push(port_result.clone());
+                result.push_port_result(port_result);
+            }
+            host.duration = host_started.elapsed();
+            if !host.stats.cancelled {
+                self.host_hook(HookEvent::HostComplete, &host);
+            }
+            stats.packets_sent += host.stats.packets_sent;
+            stats.ports_filtered += host.stats.ports_filtered;
+            hosts.push(host);
+        }
+        if hosts.len() > 1 {
+            result.hosts = hosts;
+        }
+        
+        let scan_duration = start_time.elapsed();
+        stats.engine_timings.probing = scan_duration;
+        stats.cancelled = self.cancel.is_cancelled();
+        if scan_duration.as_secs_f64() > 0.0 {
+            stats.actual_rate = stats.packets_sent as f64 / scan_duration.as_secs_f64();
+        }
+        result.set_duration(scan_duration);
+        result.update_stats(stats);
+        Ok(result)
+    }
+    
+    /// Ultra-fast scan using one bounded task group as a continuous queue
     /// Optimized for full port scans with minimal overhead
-    async fn scan_single_host_high_performance(&self, target_ip: Ipv4Addr) -> crate::Result<(Vec<PortResult>, ScanStats)> {
-        let ports = &self.config.ports;
+    ///
+    /// At most the batch size of probes run at once, and at most
+    /// `host_concurrency` of them against any one host. Returns one
+    /// [`HostResult`] per host, in completion order; each is handed to the host
+    /// callbacks (spawned on `callbacks`) as soon as its last port is done.
+    /// Running out of file descriptors stops the group and is returned. On
+    /// cancellation the hosts still in progress are returned as they are,
+    /// with `stats.cancelled` set and no callbacks run. Past the `memory`
+    /// cap, finished hosts are spilled and the batch size halved.
+    async fn scan_sockets_high_performance(
+        &self,
+        mut socket_iterator: SocketIterator,
+        callbacks: &mut JoinSet<()>,
+        mut memory: Option<&mut MemoryGuard>,
+    ) -> crate::Result<Vec<HostResult>> {
         let batch_size = self.get_current_batch_size() as usize;
+        let host_limit = self.config.host_concurrency.unwrap_or(batch_size).max(1);
+        let ports_per_host = socket_iterator.ports_per_host();
         
-        // Pre-allocate for performance (avoid reallocation)
-        let estimated_open = (ports.len() / 100).max(10); // ~1% typically open
-        let mut all_results = Vec::with_capacity(estimated_open);
-        let mut stats = ScanStats::default();
+        let mut pending: HashMap<IpAddr, HostGroup> = HashMap::new();
+        let mut completed = Vec::new();
+        let mut group = TaskGroup::new(batch_size, self.cancel.clone());
         
-        // Create socket iterator for memory-efficient on-demand generation
-        let mut socket_iterator = SocketIterator::new(&[target_ip], ports);
-        let mut futures = FuturesUnordered::new();
+        log::debug!("Starting continuous queue with batch size {} ({} per host)", batch_size, host_limit);
         
-        // Fill initial batch
-        for _ in 0..batch_size {
-            if let Some(socket) = socket_iterator.next() {
-                futures.push(self.scan_socket_high_performance(socket));
-            } else {
-                break;
+        // Hosts a resumed scan finished before are done already
+        if let Some(checkpoint) = &self.checkpoint {
+            for &ip in socket_iterator.hosts() {
+                let mut host = HostResult::new(ip);
+                if checkpoint.restore(&mut host) < ports_per_host {
+                    continue;
+                }
+                self.host_hook(HookEvent::HostStart, &host);
+                if !self.config.verify_open {
+                    self.host_hook(HookEvent::HostComplete, &host);
+                    for callback in &self.host_callbacks.0 {
+                        callbacks.spawn(callback(host.clone()));
+                    }
+                }
+                completed.push(host);
             }
         }
         
-        log::debug!("Starting continuous queue with batch size {}", batch_size);
-        
-        // Key optimization: As each future completes, immediately spawn a new one
-        // This maintains constant batch size and maximizes throughput
-        while let Some(result) = futures.next().await {
-            // Spawn next socket scan to maintain batch size (hot path)
-            if let Some(socket) = socket_iterator.next() {
-                futures.push(self.scan_socket_high_performance(socket));
+        loop {
+            // Keep the group full; sockets are generated on demand for memory efficiency
+            while group.has_capacity() {
+                let Some(socket) = socket_iterator.next() else { break };
+                if self.checkpoint.as_ref().is_some_and(|checkpoint| checkpoint.is_done(socket)) {
+                    continue;
+                }
+                let ip = socket.ip();
+                let host = pending.entry(ip).or_insert_with(|| {
+                    let mut host = HostGroup::new(ip, ports_per_host, host_limit);
+                    if let Some(checkpoint) = &self.checkpoint {
+                        host.remaining -= checkpoint.restore(&mut host.result);
+                    }
+                    self.host_hook(HookEvent::HostStart, &host.result);
+                    host
+                });
+                group.spawn(self.scan_socket_tracked(socket, Arc::clone(&host.permits)));
             }
             
+            let Some((socket, result)) = group.join_next().await else { break };
+            
+            let ip = socket.ip();
+            let Some(host) = pending.get_mut(&ip) else { continue };
+            let stats = &mut host.result.stats;
+            
             // Fast path: Only track open ports for full scans
-            if let Ok(port_result) = result {
-                if port_result.state == PortState::Open {
-                    all_results.push(port_result);
-                    stats.packets_sent += 1;
-                    stats.packets_received += 1;
-                } else {
-                    // Count but don't store closed/filtered
-                    stats.packets_sent += 1;
+            match result {
+                Ok(port_result) => {
+                    self.progress.record_completed(port_result.state == PortState::Open);
+                    if let Some(checkpoint) = &self.checkpoint {
+                        checkpoint.record(socket, port_result.state);
+                    }
+                    if matches!(port_result.state, PortState::Open | PortState::Closed) {
+                        let rtt = port_result.response_time;
+                        host.result.min_rtt = Some(host.result.min_rtt.map_or(rtt, |min| min.min(rtt)));
+                    }
+                    match port_result.state {
+                        PortState::Open => {
+                            self.port_callbacks.open(ip, &port_result);
+                            host.result.open_ports.push(port_result.port);
+                            host.result.port_results.push(port_result);
+                            stats.packets_sent += 1;
+                            stats.packets_received += 1;
+                        }
+                        // Count closed/filtered, and only store them when asked to
+                        PortState::Closed | PortState::ClosedFiltered => {
+                            stats.packets_sent += 1;
+                            stats.ports_closed += 1;
+                            if self.config.keep_all_states {
+                                host.result.port_results.push(port_result);
+                            }
+                        }
+                        _ => {
+                            stats.packets_sent += 1;
+                            stats.ports_filtered += 1;
+                            if self.config.keep_all_states {
+                                host.result.port_results.push(port_result);
+                            }
+                        }
+                    }
+                }
+                Err(crate::error::ScanError::IoError(e)) if Self::out_of_descriptors(&e) => {
+                    group.shutdown();
+                    return Err(crate::error::ScanError::IoError(e));
+                }
+                Err(_) => {
+                    self.progress.record_error();
+                    stats.errors += 1;
+                }
+            }
+            
+            host.remaining -= 1;
+            if host.remaining == 0 {
+                if let Some(host) = pending.remove(&ip) {
+                    let host = host.finish();
+                    if !self.config.verify_open {
+                        self.host_hook(HookEvent::HostComplete, &host);
+                        for callback in &self.host_callbacks.0 {
+                            callbacks.spawn(callback(host.clone()));
+                        }
+                    }
+                    completed.push(host);
                 }
+            }
+            
+            if let Some(memory) = memory.as_deref_mut() {
+                if let Some(rss) = memory.over_limit() {
+                    self.relieve_memory(memory, rss, &mut group).await;
+                }
+                memory.spill(&mut completed);
+            }
+        }
+        
+        if group.is_cancelled() {
+            completed.extend(pending.into_values().map(|host| {
+                let mut host = host.finish();
+                host.stats.cancelled = true;
+                host
+            }));
+        }
+        
+        Ok(completed)
+    }
+    
+    /// Halve the batch size, down to the minimum, after RSS passed the cap
+    async fn relieve_memory<F: std::future::Future>(&self, memory: &MemoryGuard, rss: u64, group: &mut TaskGroup<F>) {
+        let before = group.limit();
+        let after = (before / 2).max(MIN_BATCH_SIZE as usize).min(before);
+        group.set_limit(after);
+        self.adaptive_batch_size.store(after as u64, Ordering::Relaxed);
+        let decision = format!(
+            "memory at {} over the {} limit: spilling finished hosts to disk, batch size {} -> {}",
+            MemoryMonitor::format_bytes(rss),
+            MemoryMonitor::format_bytes(memory.limit()),
+            before,
+            after
+        );
+        log::warn!("{}", decision);
+        self.performance_stats.lock().await.decisions.push(decision);
+    }
+    
+    /// Probe each of `host`'s open ports again with `method`
+    ///
+    /// Ports the second probe does not confirm leave `open_ports` and are
+    /// counted as closed or filtered; their results stay, with both
+    /// observations as evidence.
+    async fn verify_host(&self, host: &mut HostResult, method: VerifyMethod) {
+        let mut group = TaskGroup::new(self.get_current_batch_size() as usize, self.cancel.clone());
+        let ip = host.host;
+        let mut unchecked = host
+            .port_results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.state == PortState::Open && r.protocol == Protocol::Tcp)
+            .map(|(index, r)| (index, SocketAddr::new(ip, r.port)));
+        
+        let mut checks = Vec::new();
+        loop {
+            while group.has_capacity() {
+                let Some((index, socket)) = unchecked.next() else { break };
+                group.spawn(self.verify_socket(index, socket, method));
+            }
+            let Some(check) = group.join_next().await else { break };
+            checks.push(check);
+        }
+        
+        for (index, state) in checks {
+            let port = &mut host.port_results[index];
+            if verify::apply(port, self.config.technique, state) {
+                host.stats.verified_open += 1;
+                continue;
+            }
+            log::info!("{}:{} not confirmed by {} ({})", host.host, port.port, method.name(), state);
+            let number = port.port;
+            host.open_ports.retain(|&p| p != number);
+            host.stats.unconfirmed_open += 1;
+            if state == PortState::Closed {
+                host.stats.ports_closed += 1;
             } else {
-                stats.errors += 1;
+                host.stats.ports_filtered += 1;
+            }
+        }
+    }
+    
+    /// Name the service and version behind each of the host's open TCP ports
+    ///
+    /// Replaces the guess from the port number when a probe reply matches;
+    /// ports nothing matched keep it.
+    #[cfg(feature = "intelligence")]
+    async fn detect_services(&self, host: &mut HostResult, probes: &ServiceProbes) {
+        let mut group = TaskGroup::new(self.get_current_batch_size() as usize, self.cancel.clone());
+        let ip = host.host;
+        // Replies take longer than a SYN-ACK, even on loopback
+        let wait = self.config.timeout_duration().max(Duration::from_secs(1));
+        let mut pending = host
+            .port_results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.state == PortState::Open && r.protocol == Protocol::Tcp)
+            .map(|(index, r)| (index, SocketAddr::new(ip, r.port)));
+        
+        let mut found = Vec::new();
+        loop {
+            while group.has_capacity() {
+                let Some((index, socket)) = pending.next() else { break };
+                group.spawn(async move { (index, probes.identify(socket, wait).await) });
             }
+            let Some(detection) = group.join_next().await else { break };
+            found.push(detection);
         }
         
-        Ok((all_results, stats))
+        for (index, detection) in found {
+            let Some(detection) = detection else { continue };
+            let port = &mut host.port_results[index];
+            log::info!("{}:{} is {} {}", host.host, port.port, detection.service, detection.version.as_deref().unwrap_or(""));
+            port.service = Some(detection.service);
+            port.version = detection.version;
+        }
+    }
+    
+    /// State of `socket` according to `method`, tagged with `index`
+    async fn verify_socket(&self, index: usize, socket: SocketAddr, method: VerifyMethod) -> (usize, PortState) {
+        self.pace(socket.ip()).await;
+        let stream = match self.connect_probe(socket).await {
+            Ok(stream) => stream,
+            Err(e) => return (index, Self::classify_error(&e)),
+        };
+        let state = match method {
+            VerifyMethod::Connect => PortState::Open,
+            VerifyMethod::Banner => {
+                let mut byte = [0u8; 1];
+                let read = match stream {
+                    Probe::Kernel(stream) => timeout(verify::BANNER_WAIT, stream.peek(&mut byte)).await,
+                    Probe::Dialed(mut stream) => {
+                        use tokio::io::AsyncReadExt;
+                        timeout(verify::BANNER_WAIT, stream.read(&mut byte)).await
+                    }
+                };
+                match read {
+                    // Spoke, or stayed connected without a word: a real listener
+                    Ok(Ok(read)) if read > 0 => PortState::Open,
+                    Err(_) => PortState::Open,
+                    // Hung up or reset straight after accepting
+                    _ => PortState::Filtered,
+                }
+            }
+        };
+        (index, state)
+    }
+    
+    /// Wait for the rate limiter, and for the health check to allow probing
+    ///
+    /// A throttled scan spends several rate tokens per probe, which divides
+    /// both the global and the per-host rate.
+    async fn pace(&self, host: IpAddr) {
+        self.health_gate.wait().await;
+        for _ in 0..self.health_gate.slowdown() {
+            self.rate_limiter.acquire(host).await;
+        }
+    }
+    
+    /// Probe `socket` once one of its host's `permits` is free
+    async fn scan_socket_tracked(&self, socket: SocketAddr, permits: Arc<Semaphore>) -> (SocketAddr, crate::Result<PortResult>) {
+        // Never closed; the permit is released when the probe finishes or is dropped
+        let _permit = permits.acquire().await;
+        (socket, self.scan_socket_high_performance(socket).await)
     }
     
     /// High-performance socket scanning with minimal overhead
     /// Balanced approach: 2 tries for accuracy with minimal error handling
     async fn scan_socket_high_performance(&self, socket: SocketAddr) -> crate::Result<PortResult> {
         let port = socket.port();
-        // Validate IP version (fast path)
-        if !matches!(socket.ip(), IpAddr::V4(_)) {
-            return Err(crate::error::ScanError::ConfigError("IPv6 not supported".to_string()));
+        if self.vlan_link.is_some() && socket.is_ipv6() {
+            return Err(crate::error::ScanError::ConfigError(format!("VLAN scans are IPv4 only; cannot reach {}", socket.ip())));
         }
         
         let start_time = Instant::now();
         
         // Balanced: 2 tries for accuracy without delays
-        let tries = 2;
+        let tries = CONNECT_TRIES;
         for attempt in 1..=tries {
-            match self.connect_optimized(socket).await {
-                Ok(_) => {
-                    // Port is OPEN!
+            if attempt > 1 {
+                self.progress.record_retry();
+            }
+            self.pace(socket.ip()).await;
+            let timeout = self.config.timeout_duration();
+            let raw_state = match (socket.ip(), &self.vlan_link, &self.syn6_link) {
+                (IpAddr::V4(target), Some(link), _) => Some(link.probe_syn(target, port, timeout).await?),
+                (IpAddr::V6(target), _, Some(link)) => Some(link.probe_syn(target, port, timeout).await?),
+                (IpAddr::V4(target), None, _) => match &self.decoys {
+                    Some(link) => Some(link.probe_syn(target, port, timeout).await?),
+                    None => None,
+                },
+                _ => None,
+            };
+            if let Some(state) = raw_state {
+                let outcome = if state == PortState::Filtered { ProbeOutcome::Lost } else { ProbeOutcome::Answered };
+                self.rate_limiter.record(socket.ip(), outcome);
+                if state == PortState::Filtered && attempt < tries {
+                    continue;
+                }
+                return Ok(PortResult {
+                    port,
+                    protocol: Protocol::Tcp,
+                    state,
+                    service: if state == PortState::Open { self.service_db.get_tcp_service(port).map(|s| s.to_string()) } else { None },
+                    version: None,
+                    response_time: start_time.elapsed(),
+                    confidence: Confidence::of(state),
+                    evidence: Vec::new(),
+                    anomalies: Vec::new(),
+                });
+            }
+            match self.connect_probe(socket).await {
+                Ok(stream) => {
+                    self.rate_limiter.record(socket.ip(), ProbeOutcome::Answered);
+                    // Port is OPEN! Keep the connection for enrichment if pooling
+                    if let (Some(pool), Probe::Kernel(stream)) = (&self.connection_pool, stream) {
+                        pool.checkin(socket, stream);
+                    }
                     let response_time = start_time.elapsed();
                     let service = self.service_db.get_tcp_service(port).map(|s| s.to_string());
                     
@@ -405,15 +1316,30 @@ impl ScanEngine {
                         protocol: Protocol::Tcp,
                         state: PortState::Open,
                         service,
+                        version: None,
                         response_time,
+                        confidence: Confidence::Medium,
+                        evidence: Vec::new(),
+                        anomalies: Vec::new(),
                     });
                 }
                 Err(e) => {
                     // Critical error check
-                    if e.to_string().contains("too many open files") {
+                    if Self::out_of_descriptors(&e) {
                         return Err(crate::error::ScanError::IoError(e));
                     }
                     
+                    // Local ports ran out: the target never saw this probe,
+                    // so back off and retry instead of reporting "filtered"
+                    if self.exhaustion_monitor.record_error(&e, self.connector.options()) {
+                        if attempt == tries {
+                            return Err(crate::error::ScanError::IoError(e));
+                        }
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        continue;
+                    }
+                    self.rate_limiter.record(socket.ip(), Self::outcome_of(&e));
+                    
                     // Last attempt - classify and return
                     if attempt == tries {
                         let state = Self::classify_error(&e);
@@ -422,7 +1348,11 @@ impl ScanEngine {
                             protocol: Protocol::Tcp,
                             state,
                             service: None,
+                            version: None,
                             response_time: start_time.elapsed(),
+                            confidence: Confidence::of(state),
+                            evidence: Vec::new(),
+                            anomalies: Vec::new(),
                         });
                     }
                     // Continue to next attempt (no delay for speed)
@@ -436,27 +1366,73 @@ impl ScanEngine {
             protocol: Protocol::Tcp,
             state: PortState::Closed,
             service: None,
+            version: None,
             response_time: start_time.elapsed(),
+            confidence: Confidence::Medium,
+            evidence: Vec::new(),
+            anomalies: Vec::new(),
         })
     }
     
+    /// Addresses in `config.target`, resolving a host name through the
+    /// custom resolver if one is set
+    async fn target_ips(&self) -> crate::Result<Vec<IpAddr>> {
+        let Some(resolver) = &self.dialers.resolver else {
+            return self.config.target_addresses();
+        };
+        let mut addresses = Vec::new();
+        for target in self.config.all_targets() {
+            if let Ok(mut parsed) = NetworkUtils::parse_cidr(target) {
+                addresses.append(&mut parsed);
+                continue;
+            }
+            let resolved = resolver.resolve(target).await.map_err(|e| {
+                crate::error::ScanError::InvalidTarget(format!("Cannot resolve {}: {}", target, e))
+            })?;
+            if resolved.is_empty() {
+                return Err(crate::error::ScanError::InvalidTarget(format!("{} has no address", target)));
+            }
+            addresses.extend(resolved);
+        }
+        self.config.exclude(addresses)
+    }
+    
+    /// Connect through the custom TCP dialer if one is set, otherwise the
+    /// built-in connector
+    async fn connect_probe(&self, socket: SocketAddr) -> io::Result<Probe> {
+        match &self.dialers.tcp {
+            Some(dialer) => dialer.dial(socket, self.config.timeout_duration()).await.map(Probe::Dialed),
+            None => self.connect_optimized(socket).await.map(Probe::Kernel),
+        }
+    }
+    
     /// Simplified connection with minimal abstractions for maximum speed
     /// Optimized to reduce system calls for full port scans
     async fn connect_optimized(&self, socket: SocketAddr) -> io::Result<tokio::net::TcpStream> {
         let timeout_duration = self.config.timeout_duration();
         
-        // Direct TcpStream::connect with timeout
-        // Using ?? pattern for fast error propagation
-        timeout(
-            timeout_duration,
-            tokio::net::TcpStream::connect(socket)
-        ).await?
-        // Connection established if we got here
+        // Plain TcpStream::connect unless RST-on-close or a source port range is set
         // Stream will auto-close on drop - minimal system calls
+        self.connector.connect(socket, timeout_duration).await
+    }
+    
+    /// Out of file descriptors: every later probe would fail the same way
+    fn out_of_descriptors(error: &io::Error) -> bool {
+        error.to_string().to_lowercase().contains("too many open files")
+    }
+    
+    /// What a failed connect says about how well its host copes with the
+    /// probe rate: resets while connecting are a sign of overload
+    fn outcome_of(error: &io::Error) -> ProbeOutcome {
+        match error.kind() {
+            io::ErrorKind::ConnectionReset => ProbeOutcome::Reset,
+            _ if Self::classify_error(error) == PortState::Filtered => ProbeOutcome::Lost,
+            _ => ProbeOutcome::Answered,
+        }
     }
     
     /// Classify IO error into port state
-    fn classify_error(error: &io::Error) -> PortState {
+    pub fn classify_error(error: &io::Error) -> PortState {
         use std::io::ErrorKind;
         match error.kind() {
             ErrorKind::ConnectionRefused => PortState::Closed,
@@ -477,18 +1453,18 @@ impl ScanEngine {
     }
     
     /// Ultra-fast batch scanning with optimized connection handling (Legacy method, kept for compatibility)
-    async fn scan_batch_high_performance(&self, target_ip: Ipv4Addr, batch: ScanBatch) -> crate::Result<(Vec<PortResult>, ScanStats)> {
+    async fn scan_batch_high_performance(&self, target_ip: IpAddr, batch: ScanBatch) -> crate::Result<(Vec<PortResult>, ScanStats)> {
         let mut results = Vec::new();
         let mut stats = ScanStats::default();
         
-        // Use futures unordered for maximum concurrency
-        let mut futures = FuturesUnordered::new();
+        // The whole batch runs at once
+        let mut group = TaskGroup::new(batch.ports.len(), self.cancel.clone());
         
         for port in batch.ports {
-            futures.push(self.scan_port_high_performance(target_ip, port));
+            group.spawn(self.scan_port_high_performance(target_ip, port));
         }
         
-        while let Some(result) = futures.next().await {
+        while let Some(result) = group.join_next().await {
             match result {
                 Ok(port_result) => {
                     // Add all port results (open, closed, filtered)
@@ -502,24 +1478,26 @@ impl ScanEngine {
                 }
             }
         }
+        stats.cancelled = group.is_cancelled();
         
         Ok((results, stats))
     }
     
     /// Ultra-fast port scanning with immediate retry for maximum speed
     /// Optimized: Removed retry delays for rustscan-level performance
-    async fn scan_port_high_performance(&self, target: Ipv4Addr, port: u16) -> crate::Result<PortResult> {
+    async fn scan_port_high_performance(&self, target: IpAddr, port: u16) -> crate::Result<PortResult> {
         let start_time = Instant::now();
         // Fixed 2 retries for optimal balance between speed and accuracy
         let max_retries = 2;
         
         let mut last_state = PortState::Closed;
+        let mut anomalies = Vec::new();
         
         // OPTIMIZED: Immediate retry without delays for maximum speed
         for attempt in 1..=max_retries {
-            let state = if let Some(ref tcp_scanner) = self.tcp_scanner {
+            let (state, seen) = if let Some(ref tcp_scanner) = self.tcp_scanner {
                 // Use optimized TCP Connect scan
-                self.scan_tcp_high_performance(tcp_scanner, target, port).await?
+                (self.scan_tcp_high_performance(tcp_scanner, target, port).await?, Vec::new())
             } else if let Some(ref _socket_pool) = self.socket_pool {
                 // Use raw socket scan
                 self.scan_port_raw(target, port).await?
@@ -529,6 +1507,12 @@ impl ScanEngine {
                 ));
             };
             
+            for anomaly in seen {
+                if !anomalies.contains(&anomaly) {
+                    anomalies.push(anomaly);
+                }
+            }
+            
             // If port is open, return immediately (fast path)
             if state == PortState::Open {
                 let response_time = start_time.elapsed();
@@ -537,7 +1521,11 @@ impl ScanEngine {
                     protocol: Protocol::Tcp,
                     state: PortState::Open,
                     service: self.service_db.get_tcp_service(port).map(|s| s.to_string()),
+                    version: None,
                     response_time,
+                    confidence: Confidence::Medium,
+                    evidence: Vec::new(),
+                    anomalies,
                 });
             }
             
@@ -559,13 +1547,17 @@ impl ScanEngine {
             protocol: Protocol::Tcp,
             state: last_state,
             service: None,
+            version: None,
             response_time,
+            confidence: Confidence::of(last_state),
+            evidence: Vec::new(),
+            anomalies,
         })
     }
     
     /// Ultra-fast high-speed TCP scanning with retry-based accuracy
-    async fn scan_tcp_high_performance(&self, _tcp_scanner: &TcpConnectScanner, target: Ipv4Addr, port: u16) -> crate::Result<PortState> {
-        let socket_addr = SocketAddr::new(IpAddr::V4(target), port);
+    async fn scan_tcp_high_performance(&self, _tcp_scanner: &TcpConnectScanner, target: IpAddr, port: u16) -> crate::Result<PortState> {
+        let socket_addr = SocketAddr::new(target, port);
         
         // Speed-optimized approach: Use fast timeout, rely on retries for accuracy
         // This gives maximum speed while retry mechanism prevents port misses
@@ -635,6 +1627,12 @@ impl ScanEngine {
         };
         
         self.adaptive_batch_size.store(new_batch_size as u64, Ordering::Relaxed);
+        if new_batch_size != current_batch {
+            stats.decisions.push(format!(
+                "batch size {} -> {} (success rate {:.0}%)",
+                current_batch, new_batch_size, success_rate * 100.0
+            ));
+        }
         stats.optimal_batch_size = new_batch_size;
         stats.last_optimization = Some(Instant::now());
         
@@ -687,17 +1685,55 @@ impl ScanEngine {
             service_db: self.service_db.clone(),
             response_analyzer: self.response_analyzer.clone(),
             adaptive_batch_size: Arc::clone(&self.adaptive_batch_size),
-            // REMOVED: connection_pool clone
+            connection_pool: self.connection_pool.clone(),
+            connector: self.connector.clone(),
+            dialers: self.dialers.clone(),
+            exhaustion_monitor: Arc::clone(&self.exhaustion_monitor),
             performance_stats: Arc::clone(&self.performance_stats),
+            progress: Arc::clone(&self.progress),
+            vlan_link: self.vlan_link.clone(),
+            syn6_link: self.syn6_link.clone(),
+            decoys: self.decoys.clone(),
+            send_thread: self.send_thread.clone(),
+            path_mtu: self.path_mtu.clone(),
+            host_callbacks: self.host_callbacks.clone(),
+            port_callbacks: self.port_callbacks.clone(),
+            cancel: self.cancel.clone(),
+            geoip: self.geoip.clone(),
+            health_probe: self.health_probe.clone(),
+            health_gate: Arc::clone(&self.health_gate),
+            #[cfg(feature = "intelligence")]
+            service_probes: self.service_probes.clone(),
+            checkpoint: self.checkpoint.clone(),
+            hooks: self.hooks.clone(),
         }
     }
     
     /// Raw socket scanning implementation (requires elevated privileges)
-    /// Falls back to TCP Connect if raw sockets are not available
-    async fn scan_port_raw(&self, target: Ipv4Addr, port: u16) -> crate::Result<PortState> {
+    /// Falls back to TCP Connect if raw sockets are not available. Anomalies
+    /// are only seen in raw replies.
+    async fn scan_port_raw(&self, target: IpAddr, port: u16) -> crate::Result<(PortState, Vec<Anomaly>)> {
         // Raw socket implementation requires CAP_NET_RAW capability on Linux
         // or administrator privileges on Windows
         
+        let target = match (target, &self.syn6_link) {
+            (IpAddr::V4(target), _) => target,
+            (IpAddr::V6(target), Some(link)) => {
+                return Ok((link.probe_syn(target, port, self.config.timeout_duration()).await?, Vec::new()));
+            }
+            (IpAddr::V6(_), None) => {
+                return self.scan_tcp_high_performance(
+                    &TcpConnectScanner::new(self.config.timeout_duration()),
+                    target,
+                    port
+                ).await.map(|state| (state, Vec::new()));
+            }
+        };
+        
+        if let Some(link) = &self.vlan_link {
+            return Ok((link.probe_syn(target, port, self.config.timeout_duration()).await?, Vec::new()));
+        }
+        
         if let Some(socket_pool) = &self.socket_pool {
             // Raw socket SYN scan - Ultra-fast stealth scanning
             log::debug!("Using raw socket SYN scan for {}:{}", target, port);
@@ -709,18 +1745,22 @@ impl ScanEngine {
                     log::warn!("No TCP sockets available in pool, falling back to TCP Connect");
                     return self.scan_tcp_high_performance(
                         &TcpConnectScanner::new(self.config.timeout_duration()),
-                        target,
+                        IpAddr::V4(target),
                         port
-                    ).await;
+                    ).await.map(|state| (state, Vec::new()));
                 }
             };
             
             // Build TCP SYN packet
             let syn_packet = self.build_tcp_syn_packet(target, port)?;
             
-            // Send SYN packet using raw socket
+            // Send SYN packet using raw socket, or queue it for the send thread
             let dest_addr = SocketAddr::new(IpAddr::V4(target), port);
-            match raw_socket.send_to(&syn_packet, dest_addr) {
+            let sent = match &self.send_thread {
+                Some(thread) => thread.send_to(syn_packet, dest_addr).await,
+                None => raw_socket.send_to(&syn_packet, dest_addr),
+            };
+            match sent {
                 Ok(_) => {
                     log::trace!("SYN packet sent to {}:{}", target, port);
                     
@@ -731,19 +1771,22 @@ impl ScanEngine {
                     );
                     
                     // Try to receive response (SYN-ACK or RST)
-                    let state = self.receive_syn_response(socket_pool, target, port, response_timeout).await?;
+                    let (state, anomalies) = self.receive_syn_response(socket_pool, target, port, response_timeout).await?;
                     
                     log::debug!("Port {}:{} state: {:?}", target, port, state);
-                    Ok(state)
+                    if !anomalies.is_empty() {
+                        log::debug!("Port {}:{} reply anomalies: {:?}", target, port, anomalies);
+                    }
+                    Ok((state, anomalies))
                 }
                 Err(e) => {
                     log::warn!("Failed to send SYN packet to {}:{}: {}", target, port, e);
                     // Fallback to TCP Connect on send error
                     self.scan_tcp_high_performance(
                         &TcpConnectScanner::new(self.config.timeout_duration()),
-                        target,
+                        IpAddr::V4(target),
                         port
-                    ).await
+                    ).await.map(|state| (state, Vec::new()))
                 }
             }
         } else {
@@ -751,9 +1794,9 @@ impl ScanEngine {
             log::debug!("Raw sockets not available for port {}, using TCP Connect", port);
             self.scan_tcp_high_performance(
                 &TcpConnectScanner::new(self.config.timeout_duration()),
-                target,
+                IpAddr::V4(target),
                 port
-            ).await
+            ).await.map(|state| (state, Vec::new()))
         }
     }
     
@@ -762,8 +1805,8 @@ impl ScanEngine {
         // Simplified TCP SYN packet structure
         // In production, use a proper packet crafting library like pnet
         
-        // TCP header: 20 bytes minimum
-        let mut packet = Vec::with_capacity(20);
+        // TCP header: 20 bytes plus a 4-byte MSS option
+        let mut packet = Vec::with_capacity(24);
         
         // Source port (random high port)
         let src_port: u16 = 50000 + (port % 15000);
@@ -779,8 +1822,8 @@ impl ScanEngine {
         // Acknowledgment number (0 for SYN)
         packet.extend_from_slice(&[0, 0, 0, 0]);
         
-        // Data offset (5 32-bit words = 20 bytes) + flags (SYN = 0x02)
-        packet.push(0x50); // Data offset: 5 << 4
+        // Data offset (6 32-bit words = 24 bytes) + flags (SYN = 0x02)
+        packet.push(0x60); // Data offset: 6 << 4
         packet.push(0x02); // SYN flag
         
         // Window size (default 65535)
@@ -792,6 +1835,10 @@ impl ScanEngine {
         // Urgent pointer (0)
         packet.extend_from_slice(&[0x00, 0x00]);
         
+        // MSS option, clamped to the path MTU
+        packet.extend_from_slice(&[0x02, 0x04]);
+        packet.extend_from_slice(&self.path_mtu.mss().to_be_bytes());
+        
         Ok(packet)
     }
     
@@ -802,7 +1849,7 @@ impl ScanEngine {
         target: Ipv4Addr,
         port: u16,
         timeout_duration: Duration,
-    ) -> crate::Result<PortState> {
+    ) -> crate::Result<(PortState, Vec<Anomaly>)> {
         // Try to receive response using ICMP socket or TCP socket
         let start = tokio::time::Instant::now();
         
@@ -814,7 +1861,7 @@ impl ScanEngine {
             Some(s) => s,
             None => {
                 log::warn!("No sockets available for receiving response");
-                return Ok(PortState::Filtered);
+                return Ok((PortState::Filtered, Vec::new()));
             }
         };
         
@@ -829,8 +1876,7 @@ impl ScanEngine {
                     if let IpAddr::V4(response_ip) = addr.ip() {
                         if response_ip == target {
                             // Parse response to determine port state
-                            let state = self.parse_tcp_response(&buf[..size], port)?;
-                            return Ok(state);
+                            return self.parse_tcp_response(&buf[..size], port);
                         }
                     }
                 }
@@ -843,16 +1889,17 @@ impl ScanEngine {
         
         // Timeout: port is likely filtered
         log::trace!("Timeout waiting for response from {}:{}", target, port);
-        Ok(PortState::Filtered)
+        Ok((PortState::Filtered, Vec::new()))
     }
     
-    /// Parse TCP response packet to determine port state
-    fn parse_tcp_response(&self, packet: &[u8], expected_port: u16) -> crate::Result<PortState> {
+    /// Parse TCP response packet to determine port state, along with any
+    /// protocol anomalies in it
+    fn parse_tcp_response(&self, packet: &[u8], expected_port: u16) -> crate::Result<(PortState, Vec<Anomaly>)> {
         // Simplified TCP response parsing
         // In production, use proper packet parsing library
         
         if packet.len() < 20 {
-            return Ok(PortState::Filtered);
+            return Ok((PortState::Filtered, Vec::new()));
         }
         
         // Extract destination port from packet (offset 2-3 in TCP header)
@@ -864,7 +1911,7 @@ impl ScanEngine {
         };
         
         if packet.len() < ip_header_len + 4 {
-            return Ok(PortState::Filtered);
+            return Ok((PortState::Filtered, Vec::new()));
         }
         
         let tcp_header = &packet[ip_header_len..];
@@ -874,26 +1921,30 @@ impl ScanEngine {
         
         if src_port != expected_port {
             // Not our response
-            return Ok(PortState::Filtered);
+            return Ok((PortState::Filtered, Vec::new()));
         }
         
+        let anomalies = PacketParser::parse_tcp_response(packet)
+            .map(|reply| Anomaly::in_syn_reply(&reply))
+            .unwrap_or_default();
+        
         // Check TCP flags (offset 13 in TCP header)
         if tcp_header.len() > 13 {
             let flags = tcp_header[13];
             
             // SYN+ACK (0x12) = Port Open
             if flags & 0x12 == 0x12 {
-                return Ok(PortState::Open);
+                return Ok((PortState::Open, anomalies));
             }
             
             // RST (0x04) = Port Closed
             if flags & 0x04 != 0 {
-                return Ok(PortState::Closed);
+                return Ok((PortState::Closed, anomalies));
             }
         }
         
         // Unknown response or ICMP unreachable = Filtered
-        Ok(PortState::Filtered)
+        Ok((PortState::Filtered, anomalies))
     }
 }
 
@@ -910,27 +1961,38 @@ impl StreamingScanEngine {
         Ok(Self { base_engine })
     }
     
+    /// Token that stops [`scan_streaming`](Self::scan_streaming) early
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.base_engine.cancel_token()
+    }
+    
     /// Execute streaming scan optimized for memory usage
     pub async fn scan_streaming(&self) -> crate::Result<StreamingResult> {
-        use colored::*;
+        use crate::output::theme::{Paint, Role};
+        use colored::Colorize;
         
         let start_time = Instant::now();
         let mut open_ports = Vec::new();
         let mut total_scanned = 0u32;
         
         println!("{} {}", 
-            "[🚀] Starting memory-optimized streaming scan".bright_green().bold(),
-            format!("({} ports)", self.base_engine.config.ports.len()).bright_cyan()
+            "[🚀] Starting memory-optimized streaming scan".paint(Role::Success).bold(),
+            format!("({} ports)", self.base_engine.config.ports.len()).paint(Role::Accent)
         );
         
         // Pre-optimize for large scans
         self.base_engine.optimize_batch_size().await?;
         
         // Parse target IPs
-        let target_ips = NetworkUtils::parse_cidr(&self.base_engine.config.target)?;
+        let mut target_ips = NetworkUtils::parse_cidr(&self.base_engine.config.target)?;
+        order_hosts(&mut target_ips, self.base_engine.config.host_order, self.base_engine.config.seed);
         
-        // Process each host with memory-efficient streaming
+        // Process each host with memory-efficient streaming (one host at a time,
+        // so interleaving falls back to target order)
         for target_ip in target_ips {
+            if self.base_engine.cancel.is_cancelled() {
+                break;
+            }
             let result = self.scan_host_streaming_minimal(target_ip).await?;
             open_ports.extend(result.0);
             total_scanned += result.1;
@@ -938,7 +2000,7 @@ impl StreamingScanEngine {
             // Show progress every 5000 ports 
             if total_scanned % 5000 == 0 {
                 println!("{} {} ports scanned, {} open", 
-                    "[Streaming]".bright_blue(),
+                    "[Streaming]".paint(Role::Info),
                     total_scanned,
                     open_ports.len()
                 );
@@ -950,9 +2012,9 @@ impl StreamingScanEngine {
         let memory_saved = traditional_memory_mb * 0.8; // 80% savings from streaming
         
         println!("{} {} {}", 
-            "[✅] Streaming scan completed in".bright_green().bold(),
-            format!("{:.2}s", scan_duration.as_secs_f64()).bright_white().bold(),
-            format!("(Memory saved: {:.1}MB)", memory_saved).bright_yellow()
+            "[✅] Streaming scan completed in".paint(Role::Success).bold(),
+            format!("{:.2}s", scan_duration.as_secs_f64()).paint(Role::Heading).bold(),
+            format!("(Memory saved: {:.1}MB)", memory_saved).paint(Role::Warning)
         );
         
         Ok(StreamingResult {
@@ -961,15 +2023,17 @@ impl StreamingScanEngine {
             total_scanned,
             duration: scan_duration,
             memory_saved_mb: memory_saved,
+            cancelled: self.base_engine.cancel.is_cancelled(),
         })
     }
     
     /// Scan single host with minimal memory usage
     async fn scan_host_streaming_minimal(
         &self,
-        target_ip: Ipv4Addr
+        target_ip: IpAddr
     ) -> crate::Result<(Vec<u16>, u32)> {
-        use colored::*;
+        use crate::output::theme::{Paint, Role};
+        use colored::Colorize;
         
         let ports = &self.base_engine.config.ports;
         let current_batch_size = self.base_engine.get_current_batch_size() as usize;
@@ -983,6 +2047,9 @@ impl StreamingScanEngine {
         
         // Process batches sequentially to maintain low memory usage
         for batch in batches {
+            if self.base_engine.cancel.is_cancelled() {
+                break;
+            }
             let batch_result = self.base_engine.scan_batch_high_performance(target_ip, batch).await?;
             
             // Process results immediately and only keep open ports
@@ -993,8 +2060,8 @@ impl StreamingScanEngine {
                     open_ports.push(port_result.port);
                     // Real-time output for open ports
                     println!("{}:{} OPEN", 
-                        target_ip.to_string().bright_cyan(),
-                        port_result.port.to_string().bright_green().bold()
+                        target_ip.to_string().paint(Role::Accent),
+                        port_result.port.to_string().paint(Role::Open).bold()
                     );
                 }
             }
@@ -0,0 +1,256 @@
+//! Pre-scan cost estimation
+//!
+//! Predicts how many probes a scan will send, how much traffic that is and
+//! how long it will take, from the effective configuration alone. Nothing is
+//! sent. Bounds come from the rate limit and from concurrency against the
+//! probe timeout; when earlier scans were recorded by adaptive learning,
+//! their observed time per port gives an expected value inside those bounds.
+
+use super::engine::{ScanEngine, CONNECT_TRIES};
+use crate::adaptive::ScanStats as HistoricalScan;
+use crate::config::ScanConfig;
+use crate::network::ScanTechnique;
+use std::fmt;
+use std::time::Duration;
+
+/// Bytes on the wire for one probe, IPv4 headers included
+fn probe_bytes(technique: ScanTechnique) -> u64 {
+    match technique {
+        // SYN with the usual kernel options (MSS, SACK, timestamps, window scale)
+        ScanTechnique::Connect => 60,
+        // Empty datagram; service payloads are a few bytes more
+        ScanTechnique::Udp => 28,
+        // Crafted segment with an MSS option
+        _ => 44,
+    }
+}
+
+/// Predicted cost of a scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanEstimate {
+    pub hosts: u64,
+    pub ports: u64,
+    /// Every probe answered on the first attempt
+    pub probes_min: u64,
+    /// Every probe retried
+    pub probes_max: u64,
+    pub bytes_min: u64,
+    pub bytes_max: u64,
+    pub duration_min: Duration,
+    pub duration_max: Duration,
+    /// From comparable recorded scans, if any
+    pub duration_expected: Option<Duration>,
+    pub history_samples: usize,
+    pub rate_limit: u64,
+    pub concurrency: u64,
+    pub timeout: Duration,
+    pub timing_template: u8,
+}
+
+impl ScanEstimate {
+    /// Estimate a scan of `config` against `hosts` addresses, using `history` where it matches
+    pub fn new(config: &ScanConfig, hosts: u64, history: &[HistoricalScan]) -> Self {
+        let ports = config.ports.len() as u64;
+        let sockets = hosts * ports;
+        let tries = match config.technique {
+            ScanTechnique::Connect => CONNECT_TRIES as u64,
+            _ => 1 + config.max_retries.unwrap_or(0) as u64,
+        };
+        let probes_min = sockets;
+        let probes_max = sockets * tries;
+        let bytes = probe_bytes(config.technique);
+
+        let rate = config.effective_rate().max(1) as f64;
+        let concurrency = ScanEngine::infer_optimal_batch_size(config.batch_size).max(1) as u64;
+        let timeout = config.timeout_duration();
+        let fastest_reply = Duration::from_millis(config.min_response_time);
+
+        // Whichever of pacing and concurrency is the tighter limit
+        let bound = |probes: u64, per_probe: Duration| {
+            let paced = probes as f64 / rate;
+            let concurrent = probes as f64 * per_probe.as_secs_f64() / concurrency as f64;
+            Duration::from_secs_f64(paced.max(concurrent))
+        };
+        let duration_min = bound(probes_min, fastest_reply);
+        let duration_max = bound(probes_max, timeout);
+
+        let technique = format!("{:?}", config.technique);
+        let per_port: Vec<f64> = history
+            .iter()
+            .filter(|scan| !scan.ports_scanned.is_empty() && scan.technique_used.eq_ignore_ascii_case(&technique))
+            .map(|scan| scan.scan_duration.as_secs_f64() / scan.ports_scanned.len() as f64)
+            .collect();
+        let duration_expected = median(&per_port).map(|secs| {
+            Duration::from_secs_f64(secs * sockets as f64).clamp(duration_min, duration_max)
+        });
+
+        Self {
+            hosts,
+            ports,
+            probes_min,
+            probes_max,
+            bytes_min: probes_min * bytes,
+            bytes_max: probes_max * bytes,
+            duration_min,
+            duration_max,
+            duration_expected,
+            history_samples: per_port.len(),
+            rate_limit: config.effective_rate(),
+            concurrency,
+            timeout,
+            timing_template: config.timing_template,
+        }
+    }
+
+    /// Average outbound bandwidth in bits per second over the fastest run
+    pub fn peak_bandwidth_bps(&self) -> f64 {
+        let secs = self.duration_min.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes_min as f64 * 8.0 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// What limits the worst case: pacing or waiting on timeouts
+    pub fn bottleneck(&self) -> &'static str {
+        let paced = self.probes_max as f64 / self.rate_limit.max(1) as f64;
+        if paced >= self.duration_max.as_secs_f64() {
+            "rate limit"
+        } else {
+            "timeouts at the current concurrency"
+        }
+    }
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let mid = sorted.len() / 2;
+    Some(if sorted.len() % 2 == 0 { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn format_bits_per_second(bps: f64) -> String {
+    match bps {
+        b if b >= 1e9 => format!("{:.1} Gbit/s", b / 1e9),
+        b if b >= 1e6 => format!("{:.1} Mbit/s", b / 1e6),
+        b if b >= 1e3 => format!("{:.1} kbit/s", b / 1e3),
+        b => format!("{:.0} bit/s", b),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if duration < Duration::from_millis(1) {
+        return "<1ms".to_string();
+    }
+    if secs < 1.0 {
+        return format!("{:.0}ms", secs * 1000.0);
+    }
+    let whole = duration.as_secs();
+    match whole {
+        0..=59 => format!("{:.1}s", secs),
+        60..=3599 => format!("{}m{:02}s", whole / 60, whole % 60),
+        _ => format!("{}h{:02}m", whole / 3600, whole / 60 % 60),
+    }
+}
+
+impl fmt::Display for ScanEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Targets:      {} hosts x {} ports", self.hosts, self.ports)?;
+        writeln!(f, "Probes:       {} - {}", self.probes_min, self.probes_max)?;
+        writeln!(
+            f,
+            "Traffic out:  {} - {} (up to {})",
+            format_bytes(self.bytes_min),
+            format_bytes(self.bytes_max),
+            format_bits_per_second(self.peak_bandwidth_bps())
+        )?;
+        writeln!(
+            f,
+            "Duration:     {} - {}",
+            format_duration(self.duration_min),
+            format_duration(self.duration_max)
+        )?;
+        match self.duration_expected {
+            Some(expected) => writeln!(
+                f,
+                "Expected:     {} (from {} recorded scans)",
+                format_duration(expected),
+                self.history_samples
+            )?,
+            None => writeln!(f, "Expected:     no comparable scans recorded")?,
+        }
+        writeln!(
+            f,
+            "Settings:     T{}, {} pps, {} concurrent, {} timeout",
+            self.timing_template,
+            self.rate_limit,
+            self.concurrency,
+            format_duration(self.timeout)
+        )?;
+        writeln!(f, "Bottleneck:   {}", self.bottleneck())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_estimate_bounds_and_history() {
+        let config = ScanConfig {
+            target: "192.0.2.0".to_string(),
+            ports: (1..=1000).collect(),
+            rate_limit: 500,
+            batch_size: Some(100),
+            timeout: 1000,
+            min_response_time: 1,
+            ..Default::default()
+        };
+        let scan = |secs: u64| HistoricalScan {
+            target: "192.0.2.9".to_string(),
+            ports_scanned: (1..=100).collect(),
+            open_ports: Vec::new(),
+            scan_duration: Duration::from_secs(secs),
+            timestamp: SystemTime::now(),
+            success_rate: 1.0,
+            technique_used: "Connect".to_string(),
+            thread_count: 100,
+            timeout: Duration::from_secs(1),
+        };
+
+        let estimate = ScanEstimate::new(&config, 4, &[]);
+        assert_eq!((estimate.probes_min, estimate.probes_max), (4000, 8000));
+        assert_eq!(estimate.bytes_min, 240_000);
+        assert_eq!(estimate.duration_min, Duration::from_secs(8));
+        assert_eq!(estimate.duration_max, Duration::from_secs(80));
+        assert_eq!(estimate.bottleneck(), "timeouts at the current concurrency");
+        assert_eq!(estimate.duration_expected, None);
+
+        // 0.1s per port in history -> 400s, clamped to the worst case
+        let estimate = ScanEstimate::new(&config, 4, &[scan(5), scan(10), scan(60)]);
+        assert_eq!(estimate.history_samples, 3);
+        assert_eq!(estimate.duration_expected, Some(Duration::from_secs(80)));
+        assert!(estimate.to_string().contains("Duration:     8.0s - 1m20s"));
+    }
+}
@@ -0,0 +1,359 @@
+//! Target health monitoring (`--health-check`)
+//!
+//! A fragile network shows strain before it falls over: the gateway answers
+//! pings later, a web front end slows down. With a health check configured,
+//! the engine samples it every [`CHECK_INTERVAL`] while probing. Latency
+//! above `threshold` times the baseline of the first samples halves the
+//! probe rate, down to [`MAX_SLOWDOWN`]; repeated failures pause probing
+//! altogether. Each healthy sample undoes one halving, and the first answer
+//! after a pause resumes the scan.
+
+use crate::config::ScanConfig;
+use crate::network::icmp::IcmpPinger;
+use crate::network::route::RouteInfo;
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Time between two health samples
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long one sample may take before it counts as a failure
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Latency multiple of the baseline that counts as degraded, by default
+pub const DEFAULT_THRESHOLD: f64 = 3.0;
+
+/// Slowest the scan is throttled to, as a divisor of its rate
+pub const MAX_SLOWDOWN: u64 = 16;
+
+/// Healthy samples whose median becomes the baseline
+const BASELINE_SAMPLES: usize = 3;
+
+/// Consecutive failed samples that pause probing
+const FAILURES_TO_PAUSE: u32 = 3;
+
+/// What to watch while scanning
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthCheck {
+    /// ICMP echo to an address, or to the target's gateway when `None`
+    Ping(Option<Ipv4Addr>),
+    /// HTTP GET; any answer below 500 is healthy
+    Http(String),
+}
+
+impl FromStr for HealthCheck {
+    type Err = ScanError;
+
+    /// `gateway`, an IPv4 address to ping, or an http(s) URL
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("gateway") {
+            return Ok(HealthCheck::Ping(None));
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(HealthCheck::Http(s.to_string()));
+        }
+        s.parse().map(|addr| HealthCheck::Ping(Some(addr))).map_err(|_| {
+            ScanError::ParseError(format!("invalid health check '{}': expected gateway, an IPv4 address or a URL", s))
+        })
+    }
+}
+
+impl fmt::Display for HealthCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthCheck::Ping(None) => f.write_str("gateway"),
+            HealthCheck::Ping(Some(addr)) => write!(f, "{}", addr),
+            HealthCheck::Http(url) => f.write_str(url),
+        }
+    }
+}
+
+/// How hard the engine may probe right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthState {
+    /// The probe rate is divided by this
+    pub slowdown: u64,
+    pub paused: bool,
+}
+
+impl HealthState {
+    pub const FULL_RATE: Self = Self { slowdown: 1, paused: false };
+}
+
+/// Turns health samples into throttling decisions
+#[derive(Debug)]
+pub struct HealthMonitor {
+    threshold: f64,
+    warmup: Vec<Duration>,
+    baseline: Option<Duration>,
+    failures: u32,
+    state: HealthState,
+}
+
+impl HealthMonitor {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            warmup: Vec::with_capacity(BASELINE_SAMPLES),
+            baseline: None,
+            failures: 0,
+            state: HealthState::FULL_RATE,
+        }
+    }
+
+    /// Latency healthy samples are judged against, once known
+    pub fn baseline(&self) -> Option<Duration> {
+        self.baseline
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.state
+    }
+
+    /// Record one sample, `None` for a failed check, and return the new state
+    pub fn observe(&mut self, sample: Option<Duration>) -> HealthState {
+        let Some(latency) = sample else {
+            self.failures += 1;
+            if self.failures >= FAILURES_TO_PAUSE {
+                self.state.paused = true;
+            } else {
+                self.slow_down();
+            }
+            return self.state;
+        };
+        self.failures = 0;
+        self.state.paused = false;
+
+        let Some(baseline) = self.baseline else {
+            self.warmup.push(latency);
+            if self.warmup.len() == BASELINE_SAMPLES {
+                self.warmup.sort();
+                self.baseline = Some(self.warmup[BASELINE_SAMPLES / 2]);
+            }
+            return self.state;
+        };
+        if latency > baseline.mul_f64(self.threshold) {
+            self.slow_down();
+        } else {
+            self.state.slowdown = (self.state.slowdown / 2).max(1);
+        }
+        self.state
+    }
+
+    fn slow_down(&mut self) {
+        self.state.slowdown = (self.state.slowdown * 2).min(MAX_SLOWDOWN);
+    }
+}
+
+/// Throttle shared between the health monitor and the engine's probes
+#[derive(Debug)]
+pub struct HealthGate {
+    slowdown: AtomicU64,
+    paused: watch::Sender<bool>,
+}
+
+impl Default for HealthGate {
+    fn default() -> Self {
+        Self { slowdown: AtomicU64::new(1), paused: watch::channel(false).0 }
+    }
+}
+
+impl HealthGate {
+    pub fn set(&self, state: HealthState) {
+        self.slowdown.store(state.slowdown, Ordering::Relaxed);
+        self.paused.send_if_modified(|paused| std::mem::replace(paused, state.paused) != state.paused);
+    }
+
+    /// Rate tokens each probe must spend
+    pub fn slowdown(&self) -> u64 {
+        self.slowdown.load(Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Notified whenever probing pauses or resumes
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.paused.subscribe()
+    }
+
+    /// Wait while probing is paused
+    pub async fn wait(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives in `self`, so this only fails if it was dropped
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
+
+/// A [`HealthCheck`] ready to run, with its address resolved
+#[derive(Debug)]
+pub struct HealthProbe {
+    #[cfg_attr(not(feature = "web"), allow(dead_code))]
+    check: HealthCheck,
+    kind: ProbeKind,
+}
+
+impl fmt::Debug for ProbeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeKind::Ping { address, .. } => write!(f, "Ping({})", address),
+            #[cfg(feature = "web")]
+            ProbeKind::Http(_) => f.write_str("Http"),
+        }
+    }
+}
+
+enum ProbeKind {
+    Ping { pinger: IcmpPinger, address: Ipv4Addr },
+    #[cfg(feature = "web")]
+    Http(reqwest::Client),
+}
+
+impl HealthProbe {
+    /// Prepare `config.health_check`; pings need a raw ICMP socket, and
+    /// `gateway` falls back to the target itself when it is on-link
+    pub fn new(check: &HealthCheck, config: &ScanConfig) -> crate::Result<Self> {
+        let kind = match check {
+            HealthCheck::Ping(address) => {
+                let address = match address {
+                    Some(address) => *address,
+                    None => {
+                        let target: Option<Ipv4Addr> = config.target.split('/').next().and_then(|host| host.parse().ok());
+                        let route = RouteInfo::for_config(config);
+                        route.and_then(|route| route.gateway).or(target).ok_or_else(|| {
+                            ScanError::ConfigError(format!("No gateway known for health checks on {}", config.target))
+                        })?
+                    }
+                };
+                ProbeKind::Ping { pinger: IcmpPinger::new()?, address }
+            }
+            #[cfg(feature = "web")]
+            HealthCheck::Http(_) => ProbeKind::Http(
+                reqwest::Client::builder()
+                    .timeout(CHECK_TIMEOUT)
+                    .user_agent(concat!("phobos/", env!("CARGO_PKG_VERSION")))
+                    .build()
+                    .map_err(|e| ScanError::NetworkError(format!("Failed to build HTTP client: {}", e)))?,
+            ),
+            #[cfg(not(feature = "web"))]
+            HealthCheck::Http(_) => {
+                return Err(ScanError::ConfigError("HTTP health checks need the `web` feature".to_string()));
+            }
+        };
+        Ok(Self { check: check.clone(), kind })
+    }
+
+    /// What is being checked, with the gateway resolved
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            ProbeKind::Ping { address, .. } => format!("ping {}", address),
+            #[cfg(feature = "web")]
+            ProbeKind::Http(_) => self.check.to_string(),
+        }
+    }
+
+    /// Latency of one check, or `None` if it failed
+    pub async fn sample(&self) -> Option<Duration> {
+        match &self.kind {
+            ProbeKind::Ping { pinger, address } => {
+                let reply = pinger.ping(*address, CHECK_TIMEOUT).await;
+                reply.success.then_some(reply.rtt)
+            }
+            #[cfg(feature = "web")]
+            ProbeKind::Http(client) => {
+                let HealthCheck::Http(url) = &self.check else { return None };
+                let started = Instant::now();
+                let response = client.get(url).send().await.ok()?;
+                (!response.status().is_server_error()).then(|| started.elapsed())
+            }
+        }
+    }
+}
+
+/// Sample `probe` until `stop` fires, applying each decision to `gate`
+///
+/// Calls `report` with a description whenever probing pauses, resumes,
+/// starts throttling or returns to full rate.
+pub async fn monitor(
+    probe: &HealthProbe,
+    threshold: f64,
+    gate: &HealthGate,
+    stop: crate::scanner::CancellationToken,
+    mut report: impl FnMut(String),
+) {
+    let mut monitor = HealthMonitor::new(threshold);
+    let started = Instant::now();
+    let target = probe.describe();
+    loop {
+        let sample = tokio::select! {
+            _ = stop.cancelled() => break,
+            sample = probe.sample() => sample,
+        };
+        let before = monitor.state();
+        let after = monitor.observe(sample);
+        gate.set(after);
+        let at = started.elapsed().as_secs_f64();
+        if after.paused && !before.paused {
+            report(format!("health check: paused at {:.1}s, {} is not answering", at, target));
+        } else if before.paused && !after.paused {
+            report(format!("health check: resumed at {:.1}s at 1/{} rate", at, after.slowdown));
+        } else if before.slowdown == 1 && after.slowdown > 1 {
+            let latency = sample.map_or_else(|| "failed".to_string(), |rtt| format!("took {}ms", rtt.as_millis()));
+            report(format!("health check: throttled at {:.1}s, {} {}", at, target, latency));
+        } else if before.slowdown > 1 && after.slowdown == 1 && !after.paused {
+            report(format!("health check: back to full rate at {:.1}s", at));
+        }
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+        }
+    }
+    // Never leave probes waiting on a monitor that is gone
+    gate.set(HealthState::FULL_RATE);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitor_throttles_pauses_and_recovers() {
+        assert_eq!("gateway".parse::<HealthCheck>().unwrap(), HealthCheck::Ping(None));
+        assert_eq!("10.0.0.1".parse::<HealthCheck>().unwrap(), HealthCheck::Ping(Some(Ipv4Addr::new(10, 0, 0, 1))));
+        assert!(matches!("https://shop.example/health".parse::<HealthCheck>().unwrap(), HealthCheck::Http(_)));
+        assert!("ftp://x".parse::<HealthCheck>().is_err());
+
+        let ms = |millis| Some(Duration::from_millis(millis));
+        let mut monitor = HealthMonitor::new(DEFAULT_THRESHOLD);
+        for rtt in [10, 30, 20] {
+            assert_eq!(monitor.observe(ms(rtt)), HealthState::FULL_RATE);
+        }
+        assert_eq!(monitor.baseline(), ms(20));
+
+        // 60ms is still within three times the baseline; 61ms is not
+        assert_eq!(monitor.observe(ms(60)).slowdown, 1);
+        assert_eq!(monitor.observe(ms(61)).slowdown, 2);
+        assert_eq!(monitor.observe(ms(500)).slowdown, 4);
+
+        // Failures slow down further, then pause
+        assert_eq!(monitor.observe(None), HealthState { slowdown: 8, paused: false });
+        assert_eq!(monitor.observe(None).slowdown, 16);
+        assert!(monitor.observe(None).paused);
+        assert!(monitor.observe(None).paused);
+
+        // Recovery is gradual: resume throttled, then halve the slowdown per healthy sample
+        assert_eq!(monitor.observe(ms(20)), HealthState { slowdown: 8, paused: false });
+        for _ in 0..3 {
+            monitor.observe(ms(20));
+        }
+        assert_eq!(monitor.state(), HealthState::FULL_RATE);
+    }
+}
@@ -0,0 +1,337 @@
+//! Lifecycle hooks at scan start, host start, host complete and scan complete
+//!
+//! Commands from the `[hooks]` table or the `--on-*` flags run once per event
+//! with its context filled into `{placeholders}` and `PHOBOS_*` variables, so
+//! a script can log, open a ticket or start a follow-up per host without a
+//! fork of the scanner. Library users register callbacks instead.
+//!
+//! Commands run one at a time, in event order, on a worker thread of their
+//! own: a slow hook never holds up probing. [`ScanEngine::scan`] returns once
+//! every queued command has run.
+//!
+//! [`ScanEngine::scan`]: super::ScanEngine::scan
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Longest a hook command may run before it is killed
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Point in a scan's life that hooks run at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    ScanStart,
+    HostStart,
+    HostComplete,
+    ScanComplete,
+}
+
+impl HookEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            HookEvent::ScanStart => "scan-start",
+            HookEvent::HostStart => "host-start",
+            HookEvent::HostComplete => "host-complete",
+            HookEvent::ScanComplete => "scan-complete",
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a hook is told about the event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookContext {
+    pub event: HookEvent,
+    /// The host for host events; the scan's targets, comma-separated, otherwise
+    pub target: String,
+    /// Open ports so far, ascending: the host's for host events, those of any
+    /// host otherwise
+    pub open_ports: Vec<u16>,
+    /// Hosts finished so far
+    pub hosts_done: usize,
+    /// Time since the scan started
+    pub elapsed: Duration,
+}
+
+impl HookContext {
+    fn ports(&self) -> String {
+        self.open_ports.iter().map(u16::to_string).collect::<Vec<_>>().join(",")
+    }
+
+    /// `(placeholder, PHOBOS_* variable, value)` for every field
+    fn fields(&self) -> [(&'static str, &'static str, String); 6] {
+        [
+            ("{event}", "PHOBOS_HOOK_EVENT", self.event.to_string()),
+            ("{target}", "PHOBOS_TARGET", self.target.clone()),
+            ("{ports}", "PHOBOS_OPEN_PORTS", self.ports()),
+            ("{open}", "PHOBOS_OPEN_COUNT", self.open_ports.len().to_string()),
+            ("{hosts}", "PHOBOS_HOSTS_DONE", self.hosts_done.to_string()),
+            ("{elapsed}", "PHOBOS_ELAPSED", format!("{:.3}", self.elapsed.as_secs_f64())),
+        ]
+    }
+
+    /// Program and arguments of `template` for this event
+    ///
+    /// Arguments are split before substitution, so a target can never inject
+    /// extra arguments; `None` for an empty template.
+    pub fn render(&self, template: &str) -> Option<Vec<String>> {
+        let fields = self.fields();
+        let args: Vec<String> = template
+            .split_whitespace()
+            .map(|arg| fields.iter().fold(arg.to_string(), |arg, (placeholder, _, value)| arg.replace(placeholder, value)))
+            .collect();
+        (!args.is_empty()).then_some(args)
+    }
+}
+
+/// Commands to run at each event (`[hooks]` table); placeholders `{event}`,
+/// `{target}`, `{ports}`, `{open}`, `{hosts}` and `{elapsed}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookCommands {
+    #[serde(default)]
+    pub scan_start: Option<String>,
+    #[serde(default)]
+    pub host_start: Option<String>,
+    #[serde(default)]
+    pub host_complete: Option<String>,
+    #[serde(default)]
+    pub scan_complete: Option<String>,
+}
+
+impl HookCommands {
+    pub fn command(&self, event: HookEvent) -> Option<&str> {
+        match event {
+            HookEvent::ScanStart => self.scan_start.as_deref(),
+            HookEvent::HostStart => self.host_start.as_deref(),
+            HookEvent::HostComplete => self.host_complete.as_deref(),
+            HookEvent::ScanComplete => self.scan_complete.as_deref(),
+        }
+    }
+
+    /// Events that have a command
+    pub fn events(&self) -> Vec<HookEvent> {
+        [HookEvent::ScanStart, HookEvent::HostStart, HookEvent::HostComplete, HookEvent::ScanComplete]
+            .into_iter()
+            .filter(|&event| self.command(event).is_some())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events().is_empty()
+    }
+}
+
+/// Called with every event; runs on the scan's own task, so keep it short
+///
+/// Register with [`ScanEngine::with_hook`](super::ScanEngine::with_hook).
+pub type HookCallback = Arc<dyn Fn(&HookContext) + Send + Sync>;
+
+enum Job {
+    Run { args: Vec<String>, env: Vec<(&'static str, String)> },
+    Flush(mpsc::Sender<()>),
+}
+
+/// Worker thread running queued hook commands in order
+struct CommandRunner {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl CommandRunner {
+    fn start() -> Self {
+        let (jobs, queue) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("phobos-hooks".to_string())
+            .spawn(move || {
+                for job in queue {
+                    match job {
+                        Job::Run { args, env } => run_command(&args, env),
+                        Job::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn the hook thread");
+        Self { jobs }
+    }
+}
+
+/// Run one command to completion or [`COMMAND_TIMEOUT`]; failures are logged
+fn run_command(args: &[String], env: Vec<(&'static str, String)>) {
+    let Some((program, rest)) = args.split_first() else { return };
+    let mut child = match Command::new(program).args(rest).envs(env).stdin(Stdio::null()).stdout(Stdio::null()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::warn!("hook {} failed to start: {}", program, e);
+            return;
+        }
+    };
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return,
+            Ok(Some(status)) => return log::warn!("hook {} exited with {}", program, status),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return log::warn!("hook {} killed after {}s", program, COMMAND_TIMEOUT.as_secs());
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => return log::warn!("hook {} failed: {}", program, e),
+        }
+    }
+}
+
+/// Hook commands and callbacks of one scan
+#[derive(Clone, Default)]
+pub struct ScanHooks {
+    commands: HookCommands,
+    runner: Option<Arc<CommandRunner>>,
+    callbacks: Vec<HookCallback>,
+    /// Host-complete events since the last scan-start
+    hosts_done: Arc<AtomicUsize>,
+}
+
+impl fmt::Debug for ScanHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanHooks")
+            .field("commands", &self.commands)
+            .field("callbacks", &self.callbacks.len())
+            .finish()
+    }
+}
+
+impl ScanHooks {
+    /// Hooks running `commands`; the worker thread only starts when there are any
+    pub fn new(commands: &HookCommands) -> Self {
+        Self {
+            runner: (!commands.is_empty()).then(|| Arc::new(CommandRunner::start())),
+            commands: commands.clone(),
+            callbacks: Vec::new(),
+            hosts_done: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn with_callback(mut self, callback: HookCallback) -> Self {
+        self.callbacks.push(callback);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runner.is_none() && self.callbacks.is_empty()
+    }
+
+    /// Call every callback and queue the event's command
+    ///
+    /// `target` is only built when some hook will see it.
+    pub fn fire(&self, event: HookEvent, target: impl FnOnce() -> String, open_ports: &[u16], elapsed: Duration) {
+        if self.is_empty() {
+            return;
+        }
+        let hosts_done = match event {
+            HookEvent::ScanStart => {
+                self.hosts_done.store(0, Ordering::Relaxed);
+                0
+            }
+            HookEvent::HostComplete => self.hosts_done.fetch_add(1, Ordering::Relaxed) + 1,
+            _ => self.hosts_done.load(Ordering::Relaxed),
+        };
+        let mut open_ports = open_ports.to_vec();
+        open_ports.sort_unstable();
+        open_ports.dedup();
+        let context = HookContext { event, target: target(), open_ports, hosts_done, elapsed };
+        for callback in &self.callbacks {
+            callback(&context);
+        }
+        let (Some(runner), Some(template)) = (&self.runner, self.commands.command(event)) else { return };
+        let Some(args) = context.render(template) else { return };
+        let env = context.fields().into_iter().map(|(_, name, value)| (name, value)).collect();
+        let _ = runner.jobs.send(Job::Run { args, env });
+    }
+
+    /// Wait until every command queued so far has run
+    pub fn flush(&self) {
+        let Some(runner) = &self.runner else { return };
+        let (done, wait) = mpsc::channel();
+        if runner.jobs.send(Job::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn context(event: HookEvent) -> HookContext {
+        HookContext {
+            event,
+            target: "10.0.0.7".to_string(),
+            open_ports: vec![22, 443],
+            hosts_done: 3,
+            elapsed: Duration::from_millis(1500),
+        }
+    }
+
+    #[test]
+    fn test_render_keeps_arguments_apart() {
+        let args = context(HookEvent::HostComplete).render("notify --host {target} --ports={ports} {event} {open}").unwrap();
+        assert_eq!(args, ["notify", "--host", "10.0.0.7", "--ports=22,443", "host-complete", "2"]);
+
+        let hostile = HookContext { target: "a b;rm".to_string(), ..context(HookEvent::ScanStart) };
+        assert_eq!(hostile.render("echo {target}").unwrap(), ["echo", "a b;rm"]);
+        assert_eq!(context(HookEvent::ScanStart).render("  "), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands_run_in_order_with_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let (script, log) = (dir.path().join("hook.sh"), dir.path().join("hooks.log"));
+        std::fs::write(&script, "echo \"$PHOBOS_HOOK_EVENT $PHOBOS_TARGET $PHOBOS_OPEN_PORTS\" >> \"$1\"\n").unwrap();
+        let append = format!("sh {} {}", script.display(), log.display());
+        let commands = HookCommands {
+            host_start: Some(append.clone()),
+            host_complete: Some(append),
+            ..Default::default()
+        };
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hooks = {
+            let seen = Arc::clone(&seen);
+            ScanHooks::new(&commands).with_callback(Arc::new(move |context: &HookContext| seen.lock().unwrap().push(context.clone())))
+        };
+
+        let elapsed = Duration::ZERO;
+        hooks.fire(HookEvent::ScanStart, || "10.0.0.0/30".to_string(), &[], elapsed);
+        for host in ["10.0.0.1", "10.0.0.2"] {
+            hooks.fire(HookEvent::HostStart, || host.to_string(), &[], elapsed);
+            hooks.fire(HookEvent::HostComplete, || host.to_string(), &[22, 443], elapsed);
+        }
+        hooks.fire(HookEvent::ScanComplete, || "10.0.0.0/30".to_string(), &[22, 443], elapsed);
+        hooks.flush();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.iter().map(|c| c.event).collect::<Vec<_>>(), [
+            HookEvent::ScanStart,
+            HookEvent::HostStart,
+            HookEvent::HostComplete,
+            HookEvent::HostStart,
+            HookEvent::HostComplete,
+            HookEvent::ScanComplete,
+        ]);
+        assert_eq!(seen.iter().map(|c| c.hosts_done).collect::<Vec<_>>(), [0, 0, 1, 1, 2, 2]);
+        let written = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(written, "host-start 10.0.0.1 \nhost-complete 10.0.0.1 22,443\nhost-start 10.0.0.2 \nhost-complete 10.0.0.2 22,443\n");
+    }
+}
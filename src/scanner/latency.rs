@@ -0,0 +1,313 @@
+//! Latency sanity hints
+//!
+//! A host's minimum round trip time bounds how far away it can be: nothing
+//! answers sooner than light in fibre needs to get there and back. Hosts
+//! that answer too fast for their GeoIP location, or much faster or slower
+//! than the other hosts in their /24, are likely not what they appear to be:
+//! anycast addresses and proxies answer from nearby, while VPN endpoints,
+//! tunnels and tarpits add delay. The hints are heuristics for a human to
+//! follow up, not findings.
+
+use super::HostResult;
+use crate::utils::address_class::AddressClass;
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Distance light covers in fibre per millisecond, one way
+const FIBRE_KM_PER_MS: f64 = 200.0;
+
+/// Mean Earth radius used for great-circle distances
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A round trip this many times the fibre floor, plus [`SLOW_SLACK`], is
+/// more than routing detours explain
+const SLOW_FACTOR: u32 = 3;
+const SLOW_SLACK: Duration = Duration::from_millis(100);
+
+/// Hosts needed in a /24, besides the one judged, for a sibling comparison
+const MIN_SIBLINGS: usize = 2;
+
+/// How many times faster or slower than its siblings' median a host must be
+const SIBLING_FACTOR: u32 = 3;
+
+/// Smallest absolute gaps worth reporting, so LAN jitter stays quiet
+const SIBLING_FAST_GAP: Duration = Duration::from_millis(10);
+const SIBLING_SLOW_GAP: Duration = Duration::from_millis(50);
+
+/// A location in decimal degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GeoPoint {
+    /// Great-circle distance to `other` in kilometres
+    pub fn distance_km(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl FromStr for GeoPoint {
+    type Err = ScanError;
+
+    /// Parse `LAT,LON`, e.g. `52.52,13.40`
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let invalid = || ScanError::ConfigError(format!("invalid location '{}': expected LAT,LON in degrees", s));
+        let (lat, lon) = s.split_once(',').ok_or_else(invalid)?;
+        let latitude: f64 = lat.trim().parse().map_err(|_| invalid())?;
+        let longitude: f64 = lon.trim().parse().map_err(|_| invalid())?;
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(invalid());
+        }
+        Ok(Self { latitude, longitude })
+    }
+}
+
+/// IPv4 networks and their locations, from a GeoIP CSV
+///
+/// The CSV needs a header with `network`, `latitude` and `longitude`
+/// columns, as in MaxMind's GeoLite2 City blocks file; other columns and
+/// IPv6 networks are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct GeoIpDb {
+    /// Networks by prefix length, keyed by their masked address
+    by_prefix: Vec<HashMap<u32, GeoPoint>>,
+}
+
+impl GeoIpDb {
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let mut reader = csv::Reader::from_path(path)
+            .map_err(|e| ScanError::ConfigError(format!("cannot read GeoIP file {}: {}", path.display(), e)))?;
+        let headers = reader
+            .headers()
+            .map_err(|e| ScanError::ConfigError(format!("{}: {}", path.display(), e)))?
+            .clone();
+        let column = |name: &str| {
+            headers.iter().position(|h| h == name).ok_or_else(|| {
+                ScanError::ConfigError(format!("{}: no '{}' column", path.display(), name))
+            })
+        };
+        let (network, latitude, longitude) = (column("network")?, column("latitude")?, column("longitude")?);
+
+        let mut db = Self::default();
+        for record in reader.records() {
+            let record = record.map_err(|e| ScanError::ConfigError(format!("{}: {}", path.display(), e)))?;
+            let Some((addr, prefix)) = record.get(network).and_then(|n| n.split_once('/')) else { continue };
+            let (Ok(addr), Ok(prefix)) = (addr.parse::<Ipv4Addr>(), prefix.parse::<u8>()) else { continue };
+            let (Some(Ok(latitude)), Some(Ok(longitude))) = (
+                record.get(latitude).map(str::parse::<f64>),
+                record.get(longitude).map(str::parse::<f64>),
+            ) else {
+                continue;
+            };
+            db.insert(addr, prefix, GeoPoint { latitude, longitude });
+        }
+        Ok(db)
+    }
+
+    /// Record `network/prefix` as being at `location`
+    pub fn insert(&mut self, network: Ipv4Addr, prefix: u8, location: GeoPoint) {
+        if prefix > 32 {
+            return;
+        }
+        if self.by_prefix.is_empty() {
+            self.by_prefix = vec![HashMap::new(); 33];
+        }
+        self.by_prefix[prefix as usize].insert(u32::from(network) & mask(prefix), location);
+    }
+
+    /// Location of the most specific network containing `addr`
+    pub fn lookup(&self, addr: Ipv4Addr) -> Option<GeoPoint> {
+        let addr = u32::from(addr);
+        (0..self.by_prefix.len())
+            .rev()
+            .find_map(|prefix| self.by_prefix[prefix].get(&(addr & mask(prefix as u8))).copied())
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_prefix.iter().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+/// Why a host's latency looks wrong
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LatencyHint {
+    /// Answered sooner than light could reach its GeoIP location and back
+    FasterThanLight { distance_km: u32, floor: Duration },
+    /// Answered far slower than the distance to its GeoIP location explains
+    SlowerThanLocation { distance_km: u32, floor: Duration },
+    /// Answered much sooner than the other hosts in its /24
+    FasterThanSubnet { median: Duration },
+    /// Answered much later than the other hosts in its /24
+    SlowerThanSubnet { median: Duration },
+}
+
+impl LatencyHint {
+    /// What usually causes this
+    pub fn likely_cause(&self) -> &'static str {
+        match self {
+            LatencyHint::FasterThanLight { .. } | LatencyHint::FasterThanSubnet { .. } => {
+                "anycast, or a proxy or middlebox answering for the host"
+            }
+            LatencyHint::SlowerThanLocation { .. } | LatencyHint::SlowerThanSubnet { .. } => {
+                "a VPN endpoint, tunnel or tarpit"
+            }
+        }
+    }
+}
+
+impl fmt::Display for LatencyHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatencyHint::FasterThanLight { distance_km, floor } => write!(
+                f,
+                "faster than light allows for its GeoIP location {} km away (fibre needs {}ms)",
+                distance_km,
+                floor.as_millis()
+            ),
+            LatencyHint::SlowerThanLocation { distance_km, floor } => write!(
+                f,
+                "far slower than its GeoIP location {} km away explains (fibre needs {}ms)",
+                distance_km,
+                floor.as_millis()
+            ),
+            LatencyHint::FasterThanSubnet { median } => {
+                write!(f, "much faster than its /24 neighbours ({}ms median)", median.as_millis())
+            }
+            LatencyHint::SlowerThanSubnet { median } => {
+                write!(f, "much slower than its /24 neighbours ({}ms median)", median.as_millis())
+            }
+        }
+    }
+}
+
+/// A host's minimum round trip time and anything odd about it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostLatency {
+    pub host: IpAddr,
+    pub min_rtt: Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<LatencyHint>,
+}
+
+/// Check every host that answered against its /24 siblings and, with a
+/// GeoIP database and the scanner's own location, against physics
+///
+/// Both checks are IPv4 only: the database holds IPv4 networks, and an IPv6
+/// subnet's hosts are too sparse to compare.
+pub fn assess(hosts: &[HostResult], geo: Option<(&GeoIpDb, GeoPoint)>) -> Vec<HostLatency> {
+    let mut latencies: Vec<HostLatency> = hosts
+        .iter()
+        .filter_map(|h| h.min_rtt.map(|min_rtt| HostLatency { host: h.host, min_rtt, hints: Vec::new() }))
+        .collect();
+
+    let mut subnets: HashMap<[u8; 3], Vec<Duration>> = HashMap::new();
+    for latency in &latencies {
+        if let IpAddr::V4(host) = latency.host {
+            let [a, b, c, _] = host.octets();
+            subnets.entry([a, b, c]).or_default().push(latency.min_rtt);
+        }
+    }
+
+    for latency in &mut latencies {
+        let IpAddr::V4(host) = latency.host else { continue };
+        // Loopback and link-local hosts are where the scanner is; nothing to locate
+        if let Some((db, origin)) = geo.filter(|_| !AddressClass::of_v4(host).is_local()) {
+            if let Some(location) = db.lookup(host) {
+                latency.hints.extend(check_location(latency.min_rtt, origin.distance_km(&location)));
+            }
+        }
+        let [a, b, c, _] = host.octets();
+        let mut siblings = subnets[&[a, b, c]].clone();
+        if let Some(own) = siblings.iter().position(|&rtt| rtt == latency.min_rtt) {
+            siblings.swap_remove(own);
+        }
+        latency.hints.extend(check_siblings(latency.min_rtt, &mut siblings));
+    }
+    latencies
+}
+
+fn check_location(rtt: Duration, distance_km: f64) -> Option<LatencyHint> {
+    let floor = Duration::from_secs_f64(2.0 * distance_km / FIBRE_KM_PER_MS / 1000.0);
+    let distance_km = distance_km.round() as u32;
+    if rtt < floor {
+        Some(LatencyHint::FasterThanLight { distance_km, floor })
+    } else if rtt > floor * SLOW_FACTOR + SLOW_SLACK {
+        Some(LatencyHint::SlowerThanLocation { distance_km, floor })
+    } else {
+        None
+    }
+}
+
+fn check_siblings(rtt: Duration, siblings: &mut [Duration]) -> Option<LatencyHint> {
+    if siblings.len() < MIN_SIBLINGS {
+        return None;
+    }
+    siblings.sort_unstable();
+    let median = siblings[siblings.len() / 2];
+    if rtt * SIBLING_FACTOR < median && median - rtt >= SIBLING_FAST_GAP {
+        Some(LatencyHint::FasterThanSubnet { median })
+    } else if rtt > median * SIBLING_FACTOR && rtt - median >= SIBLING_SLOW_GAP {
+        Some(LatencyHint::SlowerThanSubnet { median })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_hints() {
+        let berlin: GeoPoint = "52.52,13.40".parse().unwrap();
+        let sydney = GeoPoint { latitude: -33.87, longitude: 151.21 };
+        assert!((berlin.distance_km(&sydney) - 16_090.0).abs() < 100.0);
+        assert!("91,0".parse::<GeoPoint>().is_err());
+
+        let mut db = GeoIpDb::default();
+        db.insert(Ipv4Addr::new(203, 0, 113, 0), 24, sydney);
+        db.insert(Ipv4Addr::new(203, 0, 113, 128), 25, berlin);
+        assert_eq!(db.lookup(Ipv4Addr::new(203, 0, 113, 200)), Some(berlin));
+        assert_eq!(db.lookup(Ipv4Addr::new(198, 51, 100, 1)), None);
+
+        let host = |last, millis| {
+            let mut host = HostResult::new(Ipv4Addr::new(203, 0, 113, last));
+            host.min_rtt = Some(Duration::from_millis(millis));
+            host
+        };
+        let hosts = [host(1, 5), host(2, 290), host(3, 300), host(4, 310), host(129, 950), HostResult::new(Ipv4Addr::new(203, 0, 113, 5))];
+        let latencies = assess(&hosts, Some((&db, berlin)));
+        assert_eq!(latencies.len(), 5);
+
+        // 5ms to "Sydney" from Berlin: too fast for the location and the subnet
+        assert!(matches!(latencies[0].hints[..], [LatencyHint::FasterThanLight { .. }, LatencyHint::FasterThanSubnet { .. }]));
+        assert!(latencies[1].hints.is_empty());
+        // 950ms to a Berlin network, from Berlin, and three times its neighbours
+        assert!(matches!(latencies[4].hints[..], [LatencyHint::SlowerThanLocation { .. }, LatencyHint::SlowerThanSubnet { .. }]));
+
+        // Without GeoIP only the siblings are compared
+        assert_eq!(assess(&hosts, None)[0].hints.len(), 1);
+    }
+}
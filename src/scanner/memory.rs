@@ -0,0 +1,189 @@
+//! Memory guardrail (`--memory-limit`)
+//!
+//! A sweep keeps every finished host's open ports until probing is done,
+//! which on a large enough range is more than the machine has. A
+//! [`MemoryGuard`] samples the process RSS while the engine works. Once it
+//! passes the cap, finished hosts go to a temporary file instead of staying
+//! in memory and the engine lowers its batch size; they are read back when
+//! probing is done.
+
+use super::HostResult;
+use crate::utils::MemoryMonitor;
+use crate::ScanError;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, Write};
+use std::time::{Duration, Instant};
+
+/// How often RSS is sampled while under the cap
+const CHECK_EVERY: Duration = Duration::from_millis(250);
+
+/// Time for a lower batch size to show in RSS before lowering it again
+const SETTLE: Duration = Duration::from_secs(5);
+
+/// Watches RSS against a cap and spills finished hosts to disk past it
+pub struct MemoryGuard {
+    limit: u64,
+    next_check: Instant,
+    spill: Option<BufWriter<File>>,
+    spilled: usize,
+    /// Writing the spill file failed; hosts stay in memory from then on
+    spill_failed: bool,
+}
+
+impl MemoryGuard {
+    /// A guard for a cap of `limit` bytes of RSS
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            next_check: Instant::now(),
+            spill: None,
+            spilled: 0,
+            spill_failed: false,
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Whether finished hosts go to disk rather than memory
+    pub fn spilling(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Hosts written to the spill file so far
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    /// The RSS, when a sample due now finds it over the cap
+    ///
+    /// The first time, this also switches to spilling. After a report the
+    /// next sample waits long enough for the engine's adaptation to show.
+    pub fn over_limit(&mut self) -> Option<u64> {
+        if Instant::now() < self.next_check {
+            return None;
+        }
+        self.check(MemoryMonitor::current_usage())
+    }
+
+    fn check(&mut self, rss: Option<u64>) -> Option<u64> {
+        let now = Instant::now();
+        self.next_check = now + CHECK_EVERY;
+        let rss = rss.filter(|&rss| rss > self.limit)?;
+        self.next_check = now + SETTLE;
+        if self.spill.is_none() && !self.spill_failed {
+            match tempfile::tempfile() {
+                Ok(file) => self.spill = Some(BufWriter::new(file)),
+                Err(e) => self.give_up_spilling(e),
+            }
+        }
+        Some(rss)
+    }
+
+    /// Move `hosts` to the spill file, once spilling
+    pub fn spill(&mut self, hosts: &mut Vec<HostResult>) {
+        let Some(file) = self.spill.as_mut() else { return };
+        let written = hosts.iter().try_for_each(|host| {
+            serde_json::to_writer(&mut *file, host)?;
+            file.write_all(b"\n")
+        });
+        match written {
+            Ok(()) => {
+                self.spilled += hosts.len();
+                hosts.clear();
+            }
+            // Hosts written before the error are still read back
+            Err(e) => self.give_up_spilling(e),
+        }
+    }
+
+    fn give_up_spilling(&mut self, e: io::Error) {
+        log::warn!("Cannot spill scan results to disk ({}); keeping them in memory", e);
+        self.spill_failed = true;
+    }
+
+    /// Spilled hosts followed by `hosts`
+    pub fn restore(self, hosts: Vec<HostResult>) -> crate::Result<Vec<HostResult>> {
+        let Some(file) = self.spill else { return Ok(hosts) };
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        file.rewind()?;
+        let mut restored = Vec::with_capacity(self.spilled + hosts.len());
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            match serde_json::from_str(&line) {
+                Ok(host) => restored.push(host),
+                // A line cut short by a failed write
+                Err(_) if self.spill_failed => break,
+                Err(e) => return Err(ScanError::ParseError(format!("spilled scan results: {}", e))),
+            }
+        }
+        restored.extend(hosts);
+        Ok(restored)
+    }
+}
+
+/// Parse a memory size such as `512M`, `1.5G` or `800K` (powers of 1024, an
+/// optional trailing `B` or `iB`); bare numbers are bytes
+pub fn parse_memory_size(input: &str) -> crate::Result<u64> {
+    let invalid = || ScanError::ParseError(format!("invalid memory size '{}': expected e.g. 512M or 2G", input));
+    let trimmed = input.trim();
+    let number = trimmed.strip_suffix("iB").or_else(|| trimmed.strip_suffix('B')).unwrap_or(trimmed);
+    let (number, multiplier) = match number.char_indices().last() {
+        Some((at, 'k' | 'K')) => (&number[..at], 1u64 << 10),
+        Some((at, 'm' | 'M')) => (&number[..at], 1 << 20),
+        Some((at, 'g' | 'G')) => (&number[..at], 1 << 30),
+        Some((at, 't' | 'T')) => (&number[..at], 1 << 40),
+        _ => (number, 1),
+    };
+    let value: f64 = number.trim().parse().map_err(|_| invalid())?;
+    let bytes = (value * multiplier as f64).round();
+    if !bytes.is_finite() || bytes < 1.0 || bytes > u64::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{PortResult, PortState, Protocol};
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("512M").unwrap(), 512 << 20);
+        assert_eq!(parse_memory_size("1.5G").unwrap(), 3 << 29);
+        assert_eq!(parse_memory_size("2GiB").unwrap(), 2 << 30);
+        assert_eq!(parse_memory_size("800kB").unwrap(), 800 << 10);
+        assert_eq!(parse_memory_size("4096").unwrap(), 4096);
+        assert!(parse_memory_size("0M").is_err());
+        assert!(parse_memory_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_spills_past_the_cap_and_restores_in_order() {
+        let host = |last_octet: u8| {
+            let mut host = HostResult::new(std::net::Ipv4Addr::new(192, 0, 2, last_octet));
+            host.open_ports.push(443);
+            host.port_results.push(PortResult::new(443, Protocol::Tcp, PortState::Open));
+            host
+        };
+        let mut guard = MemoryGuard::new(1 << 30);
+        assert_eq!(guard.check(Some(512 << 20)), None);
+        let mut finished = vec![host(1), host(2)];
+        guard.spill(&mut finished);
+        assert_eq!(finished.len(), 2, "nothing is spilled under the cap");
+
+        assert_eq!(guard.check(Some(2 << 30)), Some(2 << 30));
+        assert!(guard.spilling());
+        assert_eq!(guard.over_limit(), None, "the next sample waits for the adaptation to settle");
+        guard.spill(&mut finished);
+        assert!(finished.is_empty());
+        assert_eq!(guard.spilled(), 2);
+
+        let restored = guard.restore(vec![host(3)]).unwrap();
+        let hosts: Vec<_> = restored.iter().map(|h| h.host).collect();
+        assert_eq!(hosts, [1, 2, 3].map(|last| std::net::Ipv4Addr::new(192, 0, 2, last)));
+        assert_eq!(restored[0].port_results[0].state, PortState::Open);
+    }
+}
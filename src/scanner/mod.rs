@@ -1,16 +1,41 @@
 //! Scanner module containing the main scanning engine
 
+pub mod auto_technique;
+pub mod checkpoint;
 pub mod engine;
+pub mod estimate;
+pub mod health;
+pub mod hooks;
+pub mod latency;
+pub mod memory;
+pub mod progress;
+pub mod summary;
+pub mod task_group;
 pub mod techniques;
 pub mod udp;
+pub mod verify;
 
 use crate::config::ScanConfig;
-use crate::network::{PortResult, PortState};
+use crate::network::uptime::TimestampAnalysis;
+use crate::network::{Evidence, PortResult, PortState, ScanTechnique};
+use crate::utils::address_class::AddressClass;
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+pub use auto_technique::{TechniqueChoice, TechniqueEnvironment};
+pub use checkpoint::CheckpointLog;
 pub use engine::{ScanEngine, StreamingScanEngine};
+pub use estimate::ScanEstimate;
+pub use health::{HealthCheck, HealthGate, HealthState};
+pub use hooks::{HookCommands, HookContext, HookEvent, ScanHooks};
+pub use latency::{GeoIpDb, GeoPoint, HostLatency, LatencyHint};
+pub use progress::{ProgressEvent, ProgressSnapshot, ProgressTracker};
+pub use summary::{PhaseTimings, ScanPhase, ScanSummary};
+pub use task_group::{CancellationToken, EngineTimings, TaskGroup};
+pub use verify::VerifyMethod;
 
 /// Complete scan result containing all discovered information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +63,19 @@ pub struct ScanResult {
     
     /// Scan configuration used
     pub config: ScanConfig,
+    
+    /// Clock rate and uptime guess from TCP timestamps, when probed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_analysis: Option<TimestampAnalysis>,
+    
+    /// Minimum round trip time of each host that answered, with sanity hints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_latency: Vec<HostLatency>,
+    
+    /// Each host's share of a scan of several hosts, in address order; the
+    /// fields above add them all up
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<HostResult>,
 }
 
 impl ScanResult {
@@ -51,25 +89,125 @@ impl ScanResult {
             duration: Duration::from_secs(0),
             stats: ScanStats::default(),
             config,
+            timestamp_analysis: None,
+            host_latency: Vec::new(),
+            hosts: Vec::new(),
+        }
+    }
+    
+    /// One result per host of a multi-host scan, as a scan of that host
+    /// alone would have reported it; just this one for a single host
+    pub fn per_host(&self) -> Vec<ScanResult> {
+        if self.hosts.is_empty() {
+            return vec![self.clone()];
         }
+        let mut config = self.config.clone();
+        config.targets.clear();
+        self.hosts
+            .iter()
+            .map(|host| {
+                config.target = host.host.to_string();
+                let mut result = ScanResult::new(config.target.clone(), config.clone());
+                for port_result in &host.port_results {
+                    result.push_port_result(port_result.clone());
+                }
+                result.duration = host.duration;
+                result.stats = host.stats.clone();
+                result.host_latency = self.host_latency.iter().filter(|l| l.host == host.host).cloned().collect();
+                result
+            })
+            .collect()
+    }
+    
+    /// [`per_host`](Self::per_host) results, each with whether the host gets
+    /// a report of its own: every host when `verbose`, otherwise only those
+    /// with open ports, as Nmap does. A single host is always reported
+    pub fn host_reports(&self, verbose: bool) -> Vec<(ScanResult, bool)> {
+        let single = self.hosts.is_empty();
+        self.per_host()
+            .into_iter()
+            .map(|host| {
+                let report = single || verbose || !host.open_ports.is_empty();
+                (host, report)
+            })
+            .collect()
+    }
+    
+    /// Hosts of a multi-host scan with at least one open port
+    pub fn hosts_up(&self) -> usize {
+        self.hosts.iter().filter(|host| !host.open_ports.is_empty()).count()
     }
     
     /// Add a port result to the scan
+    ///
+    /// A port already present for the same protocol is merged with
+    /// [`PortResult::merge`] instead of being listed twice.
     pub fn add_port_result(&mut self, result: PortResult) {
-        match result.state {
-            PortState::Open => self.open_ports.push(result.port),
-            PortState::Closed => self.closed_ports.push(result.port),
-            PortState::Filtered | PortState::OpenFiltered | PortState::ClosedFiltered => {
-                self.filtered_ports.push(result.port);
+        let technique = self.config.technique;
+        self.add_port_result_from(result, technique);
+    }
+    
+    /// Add a result found by `technique` rather than the scan's own technique
+    pub fn add_port_result_from(&mut self, mut result: PortResult, technique: ScanTechnique) {
+        let existing = self
+            .port_results
+            .iter()
+            .rposition(|r| r.port == result.port && r.protocol == result.protocol);
+        let Some(index) = existing else {
+            if technique != self.config.technique && result.evidence.is_empty() {
+                result.evidence.push(Evidence { technique, state: result.state });
             }
-            PortState::Unfiltered => {
-                // Handle unfiltered ports separately if needed
+            self.push_port_result(result);
+            return;
+        };
+        
+        let (port, before) = (result.port, self.port_results[index].state);
+        self.port_results[index].merge(self.config.technique, result, technique);
+        let after = self.port_results[index].state;
+        if after != before {
+            if let Some(ports) = self.state_list(before) {
+                if let Some(at) = ports.iter().position(|&p| p == port) {
+                    ports.remove(at);
+                }
+            }
+            if let Some(ports) = self.state_list(after) {
+                ports.push(port);
             }
         }
-        
+    }
+    
+    /// Add `result` as a new entry, even if its port is already listed
+    ///
+    /// For results from several hosts, which share port numbers without
+    /// being observations of the same port.
+    pub fn push_port_result(&mut self, result: PortResult) {
+        if let Some(ports) = self.state_list(result.state) {
+            ports.push(result.port);
+        }
         self.port_results.push(result);
     }
     
+    /// Port list that results in `state` are counted in
+    fn state_list(&mut self, state: PortState) -> Option<&mut Vec<u16>> {
+        match state {
+            PortState::Open => Some(&mut self.open_ports),
+            PortState::Closed => Some(&mut self.closed_ports),
+            PortState::Filtered | PortState::OpenFiltered | PortState::ClosedFiltered => Some(&mut self.filtered_ports),
+            // Handle unfiltered ports separately if needed
+            PortState::Unfiltered => None,
+        }
+    }
+    
+    /// Ports that one probe found open and another closed
+    pub fn disputed_ports(&self) -> impl Iterator<Item = &PortResult> {
+        self.port_results.iter().filter(|r| r.is_disputed())
+    }
+    
+    /// Protocol anomalies counted over every port
+    pub fn anomaly_stats(&self) -> crate::network::anomaly::AnomalyStats {
+        crate::network::anomaly::AnomalyStats::from_results(&self.port_results)
+    }
+    
     /// Set the scan duration
     pub fn set_duration(&mut self, duration: Duration) {
         self.duration = duration;
@@ -85,6 +223,11 @@ impl ScanResult {
         self.open_ports.len() + self.closed_ports.len() + self.filtered_ports.len()
     }
     
+    /// Address range of the target, when it is an IP address
+    pub fn address_class(&self) -> Option<AddressClass> {
+        self.target.parse().ok().map(AddressClass::of)
+    }
+    
     /// Get scan rate in ports per second
     pub fn scan_rate(&self) -> f64 {
         if self.duration.as_secs_f64() > 0.0 {
@@ -103,6 +246,50 @@ impl ScanResult {
     }
 }
 
+/// Results for a single host, available as soon as its last port completes
+///
+/// Like [`ScanResult`] from the fast path, `port_results` only holds open
+/// ports; closed and filtered ones are counted in `stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostResult {
+    pub host: IpAddr,
+    pub open_ports: Vec<u16>,
+    pub port_results: Vec<PortResult>,
+    /// From the host's first probe to its last answer
+    pub duration: Duration,
+    /// Fastest answer (open or closed) from the host
+    #[serde(default)]
+    pub min_rtt: Option<Duration>,
+    pub stats: ScanStats,
+}
+
+impl HostResult {
+    pub fn new(host: impl Into<IpAddr>) -> Self {
+        Self {
+            host: host.into(),
+            open_ports: Vec::new(),
+            port_results: Vec::new(),
+            duration: Duration::ZERO,
+            min_rtt: None,
+            stats: ScanStats::default(),
+        }
+    }
+
+    pub fn address_class(&self) -> AddressClass {
+        AddressClass::of(self.host)
+    }
+}
+
+/// Called with each host's results while the rest of the scan continues
+///
+/// Register with [`ScanEngine::with_host_callback`].
+pub type HostCallback = Arc<dyn Fn(HostResult) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Called with each open port the moment it is found
+///
+/// Register with [`ScanEngine::with_port_callback`].
+pub type PortCallback = Arc<dyn Fn(IpAddr, &PortResult) + Send + Sync>;
+
 
 /// Scan statistics for performance monitoring
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -139,6 +326,42 @@ pub struct ScanStats {
     
     /// CPU usage percentage
     pub cpu_usage: f64,
+    
+    /// Closed ports counted by scanners that only keep open results
+    #[serde(default)]
+    pub ports_closed: u64,
+    
+    /// Filtered ports counted by scanners that only keep open results
+    #[serde(default)]
+    pub ports_filtered: u64,
+    
+    /// Probes sent again after a failed first attempt
+    #[serde(default)]
+    pub retries: u64,
+    
+    /// Tuning changes the engine made during the scan
+    #[serde(default)]
+    pub adaptive_decisions: Vec<String>,
+    
+    /// Time the engine spent setting up, probing and running callbacks
+    #[serde(default)]
+    pub engine_timings: EngineTimings,
+    
+    /// The scan was cancelled before every probe finished
+    #[serde(default)]
+    pub cancelled: bool,
+    
+    /// Open ports confirmed by `--verify-open`
+    #[serde(default)]
+    pub verified_open: u64,
+    
+    /// Open ports `--verify-open` could not confirm, now closed or filtered
+    #[serde(default)]
+    pub unconfirmed_open: u64,
+    
+    /// Notifications dropped or undeliverable, from `NotificationStats::lost`
+    #[serde(default)]
+    pub notifications_lost: u64,
 }
 
 impl ScanStats {
@@ -256,15 +479,15 @@ pub struct HostDiscoveryResult {
 #[derive(Debug, Clone)]
 pub struct ScanBatch {
     pub ports: Vec<u16>,
-    pub target: Ipv4Addr,
+    pub target: IpAddr,
     pub batch_id: usize,
 }
 
 impl ScanBatch {
-    pub fn new(ports: Vec<u16>, target: Ipv4Addr, batch_id: usize) -> Self {
+    pub fn new(ports: Vec<u16>, target: impl Into<IpAddr>, batch_id: usize) -> Self {
         Self {
             ports,
-            target,
+            target: target.into(),
             batch_id,
         }
     }
@@ -278,7 +501,8 @@ impl ScanBatch {
 pub type ResultCollector = std::sync::Arc<tokio::sync::Mutex<ScanResult>>;
 
 /// Create batches from port list for parallel processing
-pub fn create_batches(ports: Vec<u16>, target: Ipv4Addr, batch_size: usize) -> Vec<ScanBatch> {
+pub fn create_batches(ports: Vec<u16>, target: impl Into<IpAddr>, batch_size: usize) -> Vec<ScanBatch> {
+        let target = target.into();
         ports
             .chunks(batch_size)
             .enumerate()
@@ -0,0 +1,277 @@
+//! Live progress counters for long scans
+//!
+//! The engine updates a [`ProgressTracker`] from its hot path with relaxed
+//! atomics; a reporter (e.g. `--stats-every`) takes [`ProgressSnapshot`]s on
+//! its own schedule without slowing the scan down. With `--progress-json`
+//! snapshots and phase changes go to stderr as [`ProgressEvent`] lines.
+
+use super::ScanPhase;
+use crate::error::ScanError;
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Counters shared between the engine and progress reporters
+#[derive(Debug)]
+pub struct ProgressTracker {
+    total: AtomicU64,
+    completed: AtomicU64,
+    open: AtomicU64,
+    retries: AtomicU64,
+    errors: AtomicU64,
+    started: Mutex<Instant>,
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            completed: AtomicU64::new(0),
+            open: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            started: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset the counters for a scan of `total` probes starting now
+    pub fn start(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+        for counter in [&self.completed, &self.open, &self.retries, &self.errors] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        *self.started.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    pub fn record_completed(&self, open: bool) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        if open {
+            self.open.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            total: self.total.load(Ordering::Relaxed),
+            completed: self.completed.load(Ordering::Relaxed),
+            open: self.open.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            elapsed: self.started.lock().unwrap_or_else(|e| e.into_inner()).elapsed(),
+        }
+    }
+}
+
+/// Point-in-time view of a running scan
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressSnapshot {
+    pub total: u64,
+    pub completed: u64,
+    pub open: u64,
+    pub retries: u64,
+    pub errors: u64,
+    pub elapsed: Duration,
+}
+
+impl ProgressSnapshot {
+    pub fn percentage(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.completed as f64 / self.total as f64 * 100.0
+    }
+
+    /// Average ports completed per second so far
+    pub fn rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.completed as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Time left at the average rate so far, once there is one
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate();
+        if rate <= 0.0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.completed);
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+}
+
+fn format_hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+impl fmt::Display for ProgressSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stats: {} elapsed; {}/{} ports done ({:.2}%); {:.0} ports/s; ETC {}; {} open, {} retries, {} errors",
+            format_hms(self.elapsed),
+            self.completed,
+            self.total,
+            self.percentage(),
+            self.rate(),
+            self.eta().map_or("unknown".to_string(), format_hms),
+            self.open,
+            self.retries,
+            self.errors
+        )
+    }
+}
+
+/// One line of `--progress-json` output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    PhaseStarted {
+        phase: ScanPhase,
+    },
+    PhaseFinished {
+        phase: ScanPhase,
+        seconds: f64,
+    },
+    Progress {
+        completed: u64,
+        total: u64,
+        percent: f64,
+        /// Ports completed per second so far
+        rate: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        eta_seconds: Option<f64>,
+        open: u64,
+        retries: u64,
+        errors: u64,
+        elapsed_seconds: f64,
+    },
+}
+
+impl From<&ProgressSnapshot> for ProgressEvent {
+    fn from(snapshot: &ProgressSnapshot) -> Self {
+        ProgressEvent::Progress {
+            completed: snapshot.completed,
+            total: snapshot.total,
+            percent: snapshot.percentage(),
+            rate: snapshot.rate(),
+            eta_seconds: snapshot.eta().map(|eta| eta.as_secs_f64()),
+            open: snapshot.open,
+            retries: snapshot.retries,
+            errors: snapshot.errors,
+            elapsed_seconds: snapshot.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// Compact JSON on a single line
+impl fmt::Display for ProgressEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// Parse an interval such as `30s`, `5m`, `1h`, `30d` or `500ms`; bare numbers are seconds
+pub fn parse_interval(input: &str) -> crate::Result<Duration> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let value: u64 = number
+        .parse()
+        .map_err(|_| ScanError::ParseError(format!("invalid interval '{}': expected e.g. 30s, 5m or 1h", input)))?;
+    let duration = match unit {
+        "" | "s" => Duration::from_secs(value),
+        "ms" => Duration::from_millis(value),
+        "m" => Duration::from_secs(value * 60),
+        "h" => Duration::from_secs(value * 3600),
+        "d" => Duration::from_secs(value * 86400),
+        _ => return Err(ScanError::ParseError(format!("unknown unit '{}' in interval '{}': use ms, s, m, h or d", unit, input))),
+    };
+    if duration.is_zero() {
+        return Err(ScanError::ParseError("interval must be greater than zero".to_string()));
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_interval() {
+        let snapshot = ProgressSnapshot {
+            total: 65535,
+            completed: 16384,
+            open: 3,
+            retries: 120,
+            errors: 1,
+            elapsed: Duration::from_secs(64),
+        };
+        assert_eq!(snapshot.rate(), 256.0);
+        assert_eq!(snapshot.eta(), Some(Duration::from_secs_f64(49151.0 / 256.0)));
+        assert_eq!(
+            snapshot.to_string(),
+            "Stats: 0:01:04 elapsed; 16384/65535 ports done (25.00%); 256 ports/s; ETC 0:03:11; 3 open, 120 retries, 1 errors"
+        );
+
+        assert_eq!(parse_interval("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_interval("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_interval("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_interval("90").unwrap(), Duration::from_secs(90));
+        assert!(parse_interval("0s").is_err());
+        assert_eq!(parse_interval("30d").unwrap(), Duration::from_secs(30 * 86400));
+        assert!(parse_interval("10w").is_err());
+        assert!(parse_interval("s").is_err());
+    }
+
+    #[test]
+    fn test_progress_events_as_json() {
+        let snapshot = ProgressSnapshot {
+            total: 1000,
+            completed: 250,
+            open: 2,
+            retries: 0,
+            errors: 0,
+            elapsed: Duration::from_secs(5),
+        };
+        assert_eq!(
+            ProgressEvent::from(&snapshot).to_string(),
+            r#"{"event":"progress","completed":250,"total":1000,"percent":25.0,"rate":50.0,"eta_seconds":15.0,"open":2,"retries":0,"errors":0,"elapsed_seconds":5.0}"#
+        );
+        assert_eq!(
+            ProgressEvent::PhaseStarted { phase: ScanPhase::Scan }.to_string(),
+            r#"{"event":"phase_started","phase":"scan"}"#
+        );
+        assert_eq!(
+            ProgressEvent::PhaseFinished { phase: ScanPhase::Resolve, seconds: 0.5 }.to_string(),
+            r#"{"event":"phase_finished","phase":"resolve","seconds":0.5}"#
+        );
+        let idle = ProgressSnapshot { completed: 0, ..snapshot };
+        assert!(!ProgressEvent::from(&idle).to_string().contains("eta_seconds"));
+    }
+}
@@ -0,0 +1,256 @@
+//! End-of-scan statistics
+//!
+//! [`PhaseTimings`] records how long each stage of a run took;
+//! [`ScanSummary`] combines that with the engine's [`ScanStats`] into the
+//! block printed by `--stats`.
+
+use super::{EngineTimings, ScanResult, ScanStats};
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Stages of a run, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanPhase {
+    /// Target parsing and DNS resolution
+    Resolve,
+    /// Host discovery before the port scan
+    Discovery,
+    /// The port scan itself
+    Scan,
+    /// Post-scan checks, fingerprinting and exports
+    Enrichment,
+    /// Script execution, including the Nmap hand-off
+    Scripts,
+}
+
+impl ScanPhase {
+    pub const ALL: [ScanPhase; 5] = [
+        ScanPhase::Resolve,
+        ScanPhase::Discovery,
+        ScanPhase::Scan,
+        ScanPhase::Enrichment,
+        ScanPhase::Scripts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScanPhase::Resolve => "resolve",
+            ScanPhase::Discovery => "discovery",
+            ScanPhase::Scan => "scan",
+            ScanPhase::Enrichment => "enrichment",
+            ScanPhase::Scripts => "scripts",
+        }
+    }
+
+    /// The phase in words, for sentences such as "Starting the port scan."
+    pub fn description(self) -> &'static str {
+        match self {
+            ScanPhase::Resolve => "target resolution",
+            ScanPhase::Discovery => "host discovery",
+            ScanPhase::Scan => "the port scan",
+            ScanPhase::Enrichment => "post-scan checks",
+            ScanPhase::Scripts => "scripts and Nmap",
+        }
+    }
+}
+
+/// Accumulated wall-clock time per phase
+#[derive(Debug, Clone, Default)]
+pub struct PhaseTimings {
+    durations: Vec<(ScanPhase, Duration)>,
+}
+
+impl PhaseTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `duration` to `phase`; phases may run more than once
+    pub fn record(&mut self, phase: ScanPhase, duration: Duration) {
+        match self.durations.iter_mut().find(|(p, _)| *p == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.durations.push((phase, duration)),
+        }
+    }
+
+    /// Record the time elapsed since `started`
+    pub fn record_since(&mut self, phase: ScanPhase, started: Instant) {
+        self.record(phase, started.elapsed());
+    }
+
+    /// Time spent in `phase`, or `None` if it did not run
+    pub fn get(&self, phase: ScanPhase) -> Option<Duration> {
+        self.durations.iter().find(|(p, _)| *p == phase).map(|(_, d)| *d)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.durations.iter().map(|(_, d)| *d).sum()
+    }
+}
+
+/// Port-state counts, timing breakdown and engine behaviour for one run
+#[derive(Debug, Clone)]
+pub struct ScanSummary {
+    pub open: u64,
+    pub closed: u64,
+    pub filtered: u64,
+    pub phases: PhaseTimings,
+    /// Probes per second actually achieved during the scan phase
+    pub achieved_pps: f64,
+    /// Rate limit the scan was configured with
+    pub configured_pps: u64,
+    /// Connection attempts, retries included
+    pub probes: u64,
+    pub retries: u64,
+    pub errors: u64,
+    pub adaptive_decisions: Vec<String>,
+    pub engine: EngineTimings,
+    /// The scan was interrupted and the counts are partial
+    pub cancelled: bool,
+    pub notifications_lost: u64,
+    pub verified_open: u64,
+    pub unconfirmed_open: u64,
+}
+
+impl ScanSummary {
+    pub fn new(result: &ScanResult, phases: PhaseTimings) -> Self {
+        let stats: &ScanStats = &result.stats;
+        // The fast engine only keeps open results and counts the rest
+        let closed = (result.closed_ports.len() as u64).max(stats.ports_closed);
+        let filtered = (result.filtered_ports.len() as u64).max(stats.ports_filtered);
+        let probes = stats.packets_sent + stats.retries;
+        let achieved_pps = if stats.actual_rate > 0.0 {
+            stats.actual_rate
+        } else if result.duration.as_secs_f64() > 0.0 {
+            probes as f64 / result.duration.as_secs_f64()
+        } else {
+            0.0
+        };
+
+        Self {
+            open: result.open_ports.len() as u64,
+            closed,
+            filtered,
+            phases,
+            achieved_pps,
+            configured_pps: result.config.effective_rate(),
+            probes,
+            retries: stats.retries,
+            errors: stats.errors,
+            adaptive_decisions: stats.adaptive_decisions.clone(),
+            engine: stats.engine_timings,
+            cancelled: stats.cancelled,
+            notifications_lost: stats.notifications_lost,
+            verified_open: stats.verified_open,
+            unconfirmed_open: stats.unconfirmed_open,
+        }
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Port states:  {} open, {} closed, {} filtered ({} total)",
+            self.open,
+            self.closed,
+            self.filtered,
+            self.open + self.closed + self.filtered
+        )?;
+
+        writeln!(f, "Phases:")?;
+        for phase in ScanPhase::ALL {
+            match self.phases.get(phase) {
+                Some(duration) => writeln!(f, "  {:<12}{:>10.3}s", phase.name(), duration.as_secs_f64())?,
+                None => writeln!(f, "  {:<12}{:>11}", phase.name(), "skipped")?,
+            }
+        }
+        writeln!(f, "  {:<12}{:>10.3}s", "total", self.phases.total().as_secs_f64())?;
+
+        let utilisation = if self.configured_pps > 0 {
+            self.achieved_pps / self.configured_pps as f64 * 100.0
+        } else {
+            0.0
+        };
+        writeln!(
+            f,
+            "Rate:         {:.0} pps achieved of {} pps configured ({:.1}%)",
+            self.achieved_pps, self.configured_pps, utilisation
+        )?;
+        writeln!(f, "Probes:       {} sent ({} retries), {} errors", self.probes, self.retries, self.errors)?;
+        if self.engine.total() > Duration::ZERO {
+            writeln!(
+                f,
+                "Engine:       {:.3}s setup, {:.3}s probing, {:.3}s verifying, {:.3}s service detection, {:.3}s callbacks",
+                self.engine.setup.as_secs_f64(),
+                self.engine.probing.as_secs_f64(),
+                self.engine.verifying.as_secs_f64(),
+                self.engine.service_detection.as_secs_f64(),
+                self.engine.callbacks.as_secs_f64()
+            )?;
+        }
+        if self.verified_open + self.unconfirmed_open > 0 {
+            writeln!(
+                f,
+                "Verified:     {} of {} open ports confirmed",
+                self.verified_open,
+                self.verified_open + self.unconfirmed_open
+            )?;
+        }
+        if self.notifications_lost > 0 {
+            writeln!(f, "Notify:       {} notifications dropped or undelivered", self.notifications_lost)?;
+        }
+        if self.cancelled {
+            writeln!(f, "Stopped:      cancelled before every probe finished")?;
+        }
+
+        if self.adaptive_decisions.is_empty() {
+            writeln!(f, "Adaptive:     no changes")?;
+        } else {
+            writeln!(f, "Adaptive:")?;
+            for decision in &self.adaptive_decisions {
+                writeln!(f, "  - {}", decision)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{PortResult, PortState, Protocol};
+
+    #[test]
+    fn test_summary_counts_and_phases() {
+        let mut result = ScanResult::new("192.0.2.1".to_string(), Default::default());
+        result.add_port_result(PortResult::new(22, Protocol::Tcp, PortState::Open));
+        result.set_duration(Duration::from_secs(2));
+        result.update_stats(ScanStats {
+            packets_sent: 1000,
+            retries: 200,
+            ports_closed: 990,
+            ports_filtered: 9,
+            adaptive_decisions: vec!["batch size 3000 -> 1000 to fit the open file limit".to_string()],
+            ..Default::default()
+        });
+
+        let mut phases = PhaseTimings::new();
+        phases.record(ScanPhase::Scan, Duration::from_secs(2));
+        phases.record(ScanPhase::Enrichment, Duration::from_millis(250));
+        phases.record(ScanPhase::Enrichment, Duration::from_millis(250));
+
+        let summary = ScanSummary::new(&result, phases);
+        assert_eq!((summary.open, summary.closed, summary.filtered), (1, 990, 9));
+        assert_eq!(summary.achieved_pps, 600.0);
+        assert_eq!(summary.phases.get(ScanPhase::Enrichment), Some(Duration::from_millis(500)));
+
+        let text = summary.to_string();
+        assert!(text.contains("1 open, 990 closed, 9 filtered (1000 total)"));
+        assert!(text.contains("resolve         skipped"));
+        assert!(text.contains("total            2.500s"));
+        assert!(text.contains("- batch size 3000 -> 1000"));
+    }
+}
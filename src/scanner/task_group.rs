@@ -0,0 +1,133 @@
+//! Bounded task groups for the scan engine
+//!
+//! Every probe the engine starts belongs to a [`TaskGroup`]: at most `limit`
+//! run at once, and the group stops as soon as its [`CancellationToken`]
+//! fires. Probes are polled in place rather than spawned, so dropping or
+//! shutting down the group drops everything still in flight and no probe
+//! outlives the scan that started it.
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// A bounded set of in-flight futures that stops on cancellation
+pub struct TaskGroup<F: Future> {
+    running: FuturesUnordered<F>,
+    limit: usize,
+    cancel: CancellationToken,
+}
+
+impl<F: Future> TaskGroup<F> {
+    /// A group running at most `limit` tasks (at least one) until `cancel` fires
+    pub fn new(limit: usize, cancel: CancellationToken) -> Self {
+        Self {
+            running: FuturesUnordered::new(),
+            limit: limit.max(1),
+            cancel,
+        }
+    }
+
+    /// Whether another task may start now
+    pub fn has_capacity(&self) -> bool {
+        self.running.len() < self.limit && !self.cancel.is_cancelled()
+    }
+
+    /// Start `task`; callers check [`has_capacity`](Self::has_capacity) first
+    pub fn spawn(&mut self, task: F) {
+        debug_assert!(self.running.len() < self.limit, "task group over its limit");
+        self.running.push(task);
+    }
+
+    /// Output of the next task to finish
+    ///
+    /// Returns `None` once the group is empty or cancelled; on cancellation
+    /// the remaining tasks are dropped first.
+    pub async fn join_next(&mut self) -> Option<F::Output> {
+        if self.running.is_empty() {
+            return None;
+        }
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => {
+                self.shutdown();
+                None
+            }
+            output = self.running.next() => output,
+        }
+    }
+
+    /// Drop every task still in flight
+    pub fn shutdown(&mut self) {
+        self.running = FuturesUnordered::new();
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Change the limit (at least one); tasks over a lowered limit finish
+    /// before new ones start
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.running.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+/// Where the engine's own time went during [`ScanEngine::scan`](super::ScanEngine::scan)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineTimings {
+    /// Batch sizing and resource preflight
+    pub setup: Duration,
+    /// Probes in flight, across all task groups
+    pub probing: Duration,
+    /// Re-probing open ports for `--verify-open`
+    #[serde(default)]
+    pub verifying: Duration,
+    /// Probing open ports for their service and version (`--sV`)
+    #[serde(default)]
+    pub service_detection: Duration,
+    /// Waiting for host callbacks after the last probe
+    pub callbacks: Duration,
+}
+
+impl EngineTimings {
+    pub fn total(&self) -> Duration {
+        self.setup + self.probing + self.verifying + self.service_detection + self.callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_task_group_bounds_and_cancels() {
+        let cancel = CancellationToken::new();
+        let mut group = TaskGroup::new(2, cancel.clone());
+        group.spawn(Box::pin(async { 1u32 }) as std::pin::Pin<Box<dyn Future<Output = u32>>>);
+        group.spawn(Box::pin(std::future::pending()));
+        assert!(!group.has_capacity());
+        assert_eq!(group.join_next().await, Some(1));
+        assert!(group.has_capacity());
+
+        // The pending task would never finish; cancelling drops it
+        cancel.cancel();
+        assert!(!group.has_capacity());
+        assert_eq!(group.join_next().await, None);
+        assert!(group.is_empty());
+    }
+}
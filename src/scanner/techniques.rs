@@ -1,13 +1,15 @@
 //! Scanning technique implementations
 
 use crate::network::{
-    packet::{TcpPacketBuilder, UdpPacketBuilder},
+    ip_id::{IPIDSequence, IPIDSequenceType},
+    packet::{PacketParser, TcpPacketBuilder, UdpPacketBuilder},
     protocol::NetworkUtils,
     socket::RawSocket,
     ScanTechnique,
 };
+use crate::ScanError;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::future::Future;
 use std::pin::Pin;
 
@@ -384,6 +386,158 @@ impl ScanTechniqueImpl for UdpScan {
     }
 }
 
+/// Zombie port probed when `--zombie` names none
+pub const DEFAULT_ZOMBIE_PORT: u16 = 80;
+
+/// Zombie replies looked at to qualify it
+const ZOMBIE_SAMPLES: usize = 6;
+
+/// Time for the target's answer to reach the zombie, and the gap between
+/// qualification probes so both see the same background traffic
+const ZOMBIE_SETTLE: Duration = Duration::from_millis(300);
+
+/// Largest IP ID step between qualification probes of a usable zombie
+const MAX_ZOMBIE_STEP: u16 = 5;
+
+/// How often the receive loop checks the non-blocking raw socket
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Split a `--zombie` value, `HOST` or `HOST:PORT`, into host and port
+pub fn parse_zombie(spec: &str) -> crate::Result<(String, u16)> {
+    let invalid = || ScanError::ParseError(format!("invalid zombie '{}': expected HOST or HOST:PORT", spec));
+    let (host, port) = match spec.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+        None => (spec, DEFAULT_ZOMBIE_PORT),
+    };
+    if host.is_empty() || port == 0 {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), port))
+}
+
+/// IP-ID idle scan: the target only ever sees packets from the zombie
+///
+/// A SYN spoofed from the zombie makes an open port send the zombie a
+/// SYN/ACK, which the zombie answers with a RST, using up one IP ID; a closed
+/// port sends a RST, which the zombie ignores. Reading the zombie's IP ID
+/// before and after tells the two apart, as long as its IP ID is one global
+/// counter that little else advances. Closed and filtered ports look the
+/// same, so `scan_port` returning false means closed|filtered.
+pub struct IdleScan {
+    zombie: Ipv4Addr,
+    zombie_port: u16,
+    source: Ipv4Addr,
+    sequence: IPIDSequence,
+}
+
+impl IdleScan {
+    /// Check that `zombie` answers on `zombie_port` from a quiet incremental
+    /// IP ID counter; `socket` must send packets with their IP header
+    pub async fn qualify(socket: &RawSocket, zombie: Ipv4Addr, zombie_port: u16, timeout: Duration) -> crate::Result<Self> {
+        let (_, source) = crate::network::route::egress(zombie)
+            .ok_or_else(|| ScanError::NetworkError(format!("no route to zombie {}", zombie)))?;
+        let mut scan = Self { zombie, zombie_port, source, sequence: IPIDSequence::from_ip_ids(&[]) };
+
+        let mut ids = Vec::with_capacity(ZOMBIE_SAMPLES);
+        for sample in 0..ZOMBIE_SAMPLES {
+            if sample > 0 {
+                tokio::time::sleep(ZOMBIE_SETTLE).await;
+            }
+            ids.extend(scan.zombie_ip_id(socket, timeout).await?);
+        }
+        if ids.len() < 2 {
+            return Err(ScanError::StealthError(format!("zombie {}:{} did not answer", zombie, zombie_port)));
+        }
+        scan.sequence = IPIDSequence::from_ip_ids(&ids);
+        match scan.sequence.sequence_type {
+            IPIDSequenceType::Incremental if scan.sequence.increment_value <= MAX_ZOMBIE_STEP => Ok(scan),
+            IPIDSequenceType::Incremental => Err(ScanError::StealthError(format!(
+                "zombie {} is too busy: its IP ID moved by up to {} between probes",
+                zombie, scan.sequence.increment_value
+            ))),
+            other => Err(ScanError::StealthError(format!("zombie {} is unusable: its IP ID sequence is {:?}", zombie, other))),
+        }
+    }
+
+    /// The zombie's IP ID sequence as seen while qualifying it
+    pub fn sequence(&self) -> &IPIDSequence {
+        &self.sequence
+    }
+
+    /// Send the zombie an unsolicited SYN/ACK and read the IP ID of its RST
+    async fn zombie_ip_id(&self, socket: &RawSocket, timeout: Duration) -> crate::Result<Option<u16>> {
+        let local_port = NetworkUtils::random_source_port();
+        let probe = TcpPacketBuilder::new(self.source, self.zombie, local_port, self.zombie_port)
+            .syn()
+            .ack()
+            .seq_num(StealthUtils::random_seq_num())
+            .ack_num(StealthUtils::random_seq_num())
+            .build();
+        socket.send_to(&probe, SocketAddr::new(IpAddr::V4(self.zombie), self.zombie_port))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 1500];
+        while Instant::now() < deadline {
+            let Ok((size, _)) = socket.recv_from(&mut buf) else {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            };
+            match PacketParser::parse_tcp_response(&buf[..size]) {
+                Some(reply) if reply.source_ip == self.zombie && reply.dest_port == local_port && reply.is_rst() => {
+                    return Ok(Some(reply.ip_id));
+                }
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+
+    /// One spoofed SYN between two readings of the zombie's IP ID
+    async fn probe(&self, socket: &RawSocket, target: Ipv4Addr, port: u16, timeout: Duration) -> crate::Result<bool> {
+        let lost = || ScanError::StealthError(format!("zombie {} stopped answering", self.zombie));
+        let before = self.zombie_ip_id(socket, timeout).await?.ok_or_else(lost)?;
+        let syn = TcpPacketBuilder::new(self.zombie, target, NetworkUtils::random_source_port(), port)
+            .syn()
+            .seq_num(StealthUtils::random_seq_num())
+            .build();
+        socket.send_to(&syn, SocketAddr::new(IpAddr::V4(target), port))?;
+        tokio::time::sleep(timeout.min(ZOMBIE_SETTLE)).await;
+        let after = self.zombie_ip_id(socket, timeout).await?.ok_or_else(lost)?;
+        Ok(zombie_answered(before, after, self.sequence.increment_value))
+    }
+}
+
+/// Whether the zombie's IP ID moved by more than its usual step between two
+/// readings, i.e. it also answered the target
+fn zombie_answered(before: u16, after: u16, usual_step: u16) -> bool {
+    after.wrapping_sub(before) > usual_step
+}
+
+impl ScanTechniqueImpl for IdleScan {
+    fn scan_port<'a>(
+        &'a self,
+        socket: &'a RawSocket,
+        target: Ipv4Addr,
+        port: u16,
+        timeout: Duration,
+    ) -> Pin<Box<dyn Future<Output = crate::Result<bool>> + Send + 'a>> {
+        Box::pin(async move {
+        // Background traffic can move the counter too, so an open port has to
+        // show twice
+        for _ in 0..2 {
+            if !self.probe(socket, target, port, timeout).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+        })
+    }
+    
+    fn name(&self) -> &'static str {
+        "TCP Idle Scan"
+    }
+}
+
 /// Factory for creating scan technique implementations
 pub struct TechniqueFactory;
 
@@ -473,4 +627,26 @@ impl StealthUtils {
         
         decoys
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_zombie() {
+        assert_eq!(parse_zombie("printer.lan").unwrap(), ("printer.lan".to_string(), DEFAULT_ZOMBIE_PORT));
+        assert_eq!(parse_zombie("192.0.2.7:443").unwrap(), ("192.0.2.7".to_string(), 443));
+        assert!(parse_zombie("192.0.2.7:http").is_err());
+        assert!(parse_zombie(":80").is_err());
+    }
+
+    #[test]
+    fn test_open_port_moves_the_zombie_counter_twice() {
+        // Quiet zombie: only our own probe moves it when the port is closed
+        assert!(!zombie_answered(100, 101, 1));
+        assert!(zombie_answered(100, 102, 1));
+        assert!(zombie_answered(65535, 1, 1));
+        assert!(!zombie_answered(100, 103, 3));
+    }
+}
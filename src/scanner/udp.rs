@@ -1,10 +1,16 @@
 //! UDP scanning implementation
 
 use crate::error::ScanError;
+use crate::network::dialer::UdpDialer;
+use crate::network::mtu::{self, PathCheck, SAFE_UDP_PAYLOAD};
+use crate::network::unreachable::Unreachable;
 use crate::utils::scan_options::{ScanOptions, order_ports};
 use futures::future::join_all;
+use std::fmt;
 use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::io::Interest;
 use tokio::net::UdpSocket as TokioUdpSocket;
 use tokio::time::timeout;
 
@@ -17,18 +23,50 @@ pub enum UdpScanResult {
     Timeout,
 }
 
+/// What a UDP port's result is based on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpReason {
+    /// The service answered the probe
+    UdpResponse,
+    /// An ICMP destination unreachable came back, with its code
+    Unreachable(Unreachable),
+    /// Nothing came back before the timeout
+    NoResponse,
+    /// The probe failed locally
+    Error,
+}
+
+impl fmt::Display for UdpReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdpReason::UdpResponse => write!(f, "udp-response"),
+            UdpReason::Unreachable(kind) => write!(f, "{}", kind),
+            UdpReason::NoResponse => write!(f, "no-response"),
+            UdpReason::Error => write!(f, "error"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UdpScanResponse {
     pub port: u16,
     pub result: UdpScanResult,
     pub response_time: Duration,
     pub service: Option<String>,
+    pub reason: UdpReason,
+    /// The probe was larger than the path to the target was shown to carry,
+    /// so a silent port may be the path dropping it
+    pub path_unverified: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct UdpScanner {
     options: ScanOptions,
     payloads: UdpPayloads,
+    // Sends the probes instead of a local socket when set
+    dialer: Option<Arc<dyn UdpDialer>>,
+    // What the path to the current target carries, once checked
+    path: Option<PathCheck>,
 }
 
 impl UdpScanner {
@@ -36,22 +74,69 @@ impl UdpScanner {
         Self {
             options,
             payloads: UdpPayloads::new(),
+            dialer: None,
+            path: None,
         }
     }
 
+    /// Send every probe through `dialer` instead of a local socket
+    pub fn with_dialer(mut self, dialer: Arc<dyn UdpDialer>) -> Self {
+        self.dialer = Some(dialer);
+        self
+    }
+
     /// Scan multiple UDP ports on a target
     pub async fn scan_ports(&self, target: IpAddr, ports: Vec<u16>) -> Vec<UdpScanResponse> {
         let ordered_ports = order_ports(ports, self.options.scan_order);
         let mut results = Vec::new();
+        let scanner = self.checked_path(target, &ordered_ports).await;
 
         for chunk in ordered_ports.chunks(self.options.batch_size as usize) {
-            let chunk_results = self.scan_batch(target, chunk.to_vec()).await;
+            let chunk_results = scanner.scan_batch(target, chunk.to_vec()).await;
             results.extend(chunk_results);
         }
 
         results
     }
 
+    /// This scanner with the path to `target` checked, if any of the probes
+    /// for `ports` is too large to be sure of getting there
+    ///
+    /// A probe dropped on the way reads as a silent port, and the port
+    /// unreachable a closed one would send never comes. Probes over a limit
+    /// the check finds are swapped for the small generic one; when the check
+    /// cannot tell, they go out as they are and their results are marked.
+    async fn checked_path(&self, target: IpAddr, ports: &[u16]) -> Self {
+        let mut scanner = self.clone();
+        let largest = ports.iter().map(|&port| self.payloads.get_payload(port).len()).max().unwrap_or(0);
+        let IpAddr::V4(target_v4) = target else { return scanner };
+        // A dialer's path is not ours to probe
+        if largest <= SAFE_UDP_PAYLOAD || self.dialer.is_some() {
+            return scanner;
+        }
+        let check = mtu::check_udp_path(target_v4, largest, self.options.timeout).await;
+        match check {
+            PathCheck::Clear => log::debug!("UDP path to {}: {}", target, check),
+            _ => log::info!("UDP path to {}: {}", target, check),
+        }
+        scanner.path = Some(check);
+        scanner
+    }
+
+    /// Payload to send to `port` over the checked path, and whether the path
+    /// is unverified for it
+    fn payload(&self, port: u16) -> (Vec<u8>, bool) {
+        let payload = self.payloads.get_payload(port);
+        let fits = payload.len() <= SAFE_UDP_PAYLOAD;
+        match self.path {
+            Some(check) if !fits => match check.payload_limit() {
+                Some(limit) if payload.len() > limit => (GENERIC_PAYLOAD.to_vec(), false),
+                _ => (payload, check == PathCheck::Unanswered),
+            },
+            _ => (payload, false),
+        }
+    }
+
     /// Scan a batch of UDP ports
     async fn scan_batch(&self, target: IpAddr, ports: Vec<u16>) -> Vec<UdpScanResponse> {
         let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(self.options.threads as usize));
@@ -73,11 +158,12 @@ impl UdpScanner {
         
         let mut attempts = 0;
         let mut result = UdpScanResult::Timeout;
+        let mut reason = UdpReason::Error;
         
         while attempts < self.options.tries {
             match self.probe_port(target, port).await {
                 Ok(res) => {
-                    result = res;
+                    (result, reason) = res;
                     break;
                 }
                 Err(_) => {
@@ -91,46 +177,82 @@ impl UdpScanner {
 
         let response_time = start.elapsed();
         let service = self.identify_service(port, &result);
+        let path_unverified = self.payload(port).1 && reason == UdpReason::NoResponse;
 
         UdpScanResponse {
             port,
             result,
             response_time,
             service,
+            reason,
+            path_unverified,
         }
     }
 
     /// Probe a UDP port
-    async fn probe_port(&self, target: IpAddr, port: u16) -> Result<UdpScanResult, ScanError> {
+    async fn probe_port(&self, target: IpAddr, port: u16) -> Result<(UdpScanResult, UdpReason), ScanError> {
         let socket_addr = SocketAddr::new(target, port);
+        let (payload, _) = self.payload(port);
+        if let Some(dialer) = &self.dialer {
+            return Ok(match dialer.exchange(socket_addr, &payload, self.options.timeout).await {
+                Ok(Some(reply)) if !reply.is_empty() => (UdpScanResult::Open, UdpReason::UdpResponse),
+                Ok(_) => (UdpScanResult::OpenFiltered, UdpReason::NoResponse),
+                Err(e) => Self::refused(&e),
+            });
+        }
         let socket = TokioUdpSocket::bind("0.0.0.0:0").await
             .map_err(|e| ScanError::NetworkError(e.to_string()))?;
+        // A connected socket hears the ICMP errors its probes cause
+        socket.connect(socket_addr).await
+            .map_err(|e| ScanError::NetworkError(e.to_string()))?;
+        report_all_icmp_errors(&socket);
 
-        // Get appropriate payload for this port
-        let payload = self.payloads.get_payload(port);
-        
         // Send probe packet
-        socket.send_to(&payload, socket_addr).await
+        socket.send(&payload).await
             .map_err(|e| ScanError::NetworkError(e.to_string()))?;
 
-        // Wait for response
+        // Wait for a response, or the ICMP error the kernel queued instead
         let mut buffer = vec![0u8; 1024];
-        match timeout(self.options.timeout, socket.recv_from(&mut buffer)).await {
-            Ok(Ok((bytes, _))) => {
+        let reply = async {
+            loop {
+                let ready = socket.ready(Interest::READABLE | Interest::ERROR).await?;
+                if ready.is_error() {
+                    if let Some(e) = socket.take_error()? {
+                        return Err(e);
+                    }
+                }
+                match socket.try_recv(&mut buffer) {
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                    received => return received,
+                }
+            }
+        };
+        match timeout(self.options.timeout, reply).await {
+            Ok(Ok(bytes)) => {
                 if bytes > 0 {
-                    Ok(UdpScanResult::Open)
+                    Ok((UdpScanResult::Open, UdpReason::UdpResponse))
                 } else {
-                    Ok(UdpScanResult::OpenFiltered)
+                    Ok((UdpScanResult::OpenFiltered, UdpReason::NoResponse))
                 }
             }
-            Ok(Err(_)) => Ok(UdpScanResult::Filtered),
+            Ok(Err(e)) => Ok(Self::refused(&e)),
             Err(_) => {
                 // Timeout - could be open or filtered
-                Ok(UdpScanResult::OpenFiltered)
+                Ok((UdpScanResult::OpenFiltered, UdpReason::NoResponse))
             }
         }
     }
 
+    /// Result of a probe that failed with `error`: closed on an ICMP port
+    /// unreachable, filtered on any other unreachable code or error
+    fn refused(error: &std::io::Error) -> (UdpScanResult, UdpReason) {
+        match Unreachable::from_io_error(error) {
+            Some(Unreachable::Port) => (UdpScanResult::Closed, UdpReason::Unreachable(Unreachable::Port)),
+            Some(kind) => (UdpScanResult::Filtered, UdpReason::Unreachable(kind)),
+            None => (UdpScanResult::Filtered, UdpReason::Error),
+        }
+    }
+
     /// Identify service based on port and response
     fn identify_service(&self, port: u16, result: &UdpScanResult) -> Option<String> {
         if matches!(result, UdpScanResult::Open | UdpScanResult::OpenFiltered) {
@@ -141,6 +263,37 @@ impl UdpScanner {
     }
 }
 
+/// Have the kernel report every ICMP error to `socket`, not just port
+/// unreachable (IP_RECVERR)
+#[cfg(target_os = "linux")]
+fn report_all_icmp_errors(socket: &TokioUdpSocket) {
+    use std::os::unix::io::AsRawFd;
+
+    let enable: libc::c_int = 1;
+    // SAFETY: `enable` is valid for reads of the size passed
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_RECVERR,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        log::debug!("IP_RECVERR unavailable: {}", std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn report_all_icmp_errors(_socket: &TokioUdpSocket) {}
+
+/// Sent to ports without a service-specific payload
+const GENERIC_PAYLOAD: &[u8] = b"TEST";
+
+/// Smallest datagram a QUIC server accepts a client's first packet in
+const QUIC_DATAGRAM: usize = 1200;
+
 #[derive(Debug, Clone)]
 struct UdpPayloads {
     payloads: std::collections::HashMap<u16, Vec<u8>>,
@@ -187,11 +340,21 @@ impl UdpPayloads {
         payloads.insert(5060, b"OPTIONS sip:test@example.com SIP/2.0\r\n\r\n".to_vec());
         services.insert(5060, "sip".to_string());
 
+        // QUIC: a long header for a reserved version, which servers answer with
+        // Version Negotiation (RFC 9000, section 6). Servers drop datagrams
+        // carrying a client's first packet that are smaller than 1200 bytes.
+        let mut quic = vec![0xc0, 0x1a, 0x2a, 0x3a, 0x4a, 8];
+        quic.extend_from_slice(b"phobosqc");
+        quic.push(0);
+        quic.resize(QUIC_DATAGRAM, 0);
+        payloads.insert(443, quic);
+        services.insert(443, "quic".to_string());
+
         Self { payloads, services }
     }
 
     fn get_payload(&self, port: u16) -> Vec<u8> {
-        self.payloads.get(&port).cloned().unwrap_or_else(|| b"TEST".to_vec())
+        self.payloads.get(&port).cloned().unwrap_or_else(|| GENERIC_PAYLOAD.to_vec())
     }
 
     fn get_service_name(&self, port: u16) -> Option<String> {
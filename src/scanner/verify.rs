@@ -0,0 +1,81 @@
+//! Second-opinion checks for open ports (`--verify-open`)
+//!
+//! Middleboxes produce open ports that are not there: SYN proxies answer
+//! every SYN, and some firewalls accept a connection only to reset it. With
+//! verification on, each port the scan finds open is probed again with a
+//! different method before it is reported, and only ports both methods agree
+//! on stay open.
+
+use crate::network::{Confidence, PortResult, PortState, ScanTechnique};
+use std::time::Duration;
+
+/// How long a banner check waits for the service to speak or hang up
+pub const BANNER_WAIT: Duration = Duration::from_millis(500);
+
+/// How an open port is checked a second time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMethod {
+    /// Complete a full TCP handshake
+    Connect,
+    /// Connect, then require that the service neither resets nor closes
+    /// the connection within [`BANNER_WAIT`]
+    Banner,
+}
+
+impl VerifyMethod {
+    /// A method independent of `technique`: connect after raw probes, and a
+    /// banner read after a connect scan
+    pub fn for_technique(technique: ScanTechnique) -> Self {
+        if technique == ScanTechnique::Connect {
+            VerifyMethod::Banner
+        } else {
+            VerifyMethod::Connect
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VerifyMethod::Connect => "connect",
+            VerifyMethod::Banner => "banner read",
+        }
+    }
+}
+
+/// Record the second probe's `state` on an open `port`
+///
+/// Returns whether the port stays open. A port the second probe does not
+/// confirm takes that probe's state at low confidence, with both
+/// observations kept as evidence.
+pub fn apply(port: &mut PortResult, technique: ScanTechnique, state: PortState) -> bool {
+    let mut check = PortResult::new(port.port, port.protocol, state);
+    check.response_time = port.response_time;
+    port.merge(technique, check, ScanTechnique::Connect);
+    if state == PortState::Open {
+        return true;
+    }
+    port.state = state;
+    port.confidence = Confidence::Low;
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Protocol;
+
+    #[test]
+    fn test_apply_demotes_unconfirmed_ports() {
+        assert_eq!(VerifyMethod::for_technique(ScanTechnique::Syn), VerifyMethod::Connect);
+        assert_eq!(VerifyMethod::for_technique(ScanTechnique::Connect), VerifyMethod::Banner);
+
+        let mut confirmed = PortResult::new(22, Protocol::Tcp, PortState::Open);
+        assert!(apply(&mut confirmed, ScanTechnique::Syn, PortState::Open));
+        assert_eq!((confirmed.state, confirmed.confidence), (PortState::Open, Confidence::High));
+
+        // A SYN/ACK from a SYN proxy, refused by the real host
+        let mut proxied = PortResult::new(8080, Protocol::Tcp, PortState::Open);
+        assert!(!apply(&mut proxied, ScanTechnique::Syn, PortState::Closed));
+        assert_eq!((proxied.state, proxied.confidence), (PortState::Closed, Confidence::Low));
+        assert!(proxied.is_disputed());
+    }
+}
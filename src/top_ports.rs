@@ -1,8 +1,25 @@
 //! Port lists for targeted scanning
 
-use std::collections::HashMap;
+use crate::network::Protocol;
+use std::collections::{HashMap, HashSet};
 use once_cell::sync::Lazy;
 
+/// Most frequently open TCP ports, most common first (nmap-services frequency order)
+const TCP_FREQUENCY_RANKING: [u16; 100] = [
+    80, 23, 443, 21, 22, 25, 3389, 110, 445, 139, 143, 53, 135, 3306, 8080, 1723, 111, 995, 993, 5900,
+    1025, 587, 8888, 199, 1720, 465, 548, 113, 81, 6001, 10000, 514, 5060, 179, 1026, 2000, 8443, 8000, 32768, 554,
+    26, 1433, 49152, 2001, 515, 8008, 49154, 1027, 5666, 646, 5000, 5631, 631, 49153, 8081, 2049, 88, 79, 5800, 106,
+    2121, 1110, 49155, 6000, 513, 990, 5357, 427, 49156, 543, 544, 5101, 144, 7, 389, 8009, 3128, 444, 9999, 5009,
+    7070, 5190, 3000, 5432, 1900, 3986, 13, 1029, 9, 5051, 6646, 49157, 1028, 873, 1755, 2717, 4899, 9100, 119, 37,
+];
+
+/// Most frequently open UDP ports, most common first
+const UDP_FREQUENCY_RANKING: [u16; 50] = [
+    631, 161, 137, 123, 138, 1434, 445, 135, 67, 53, 139, 500, 68, 520, 1900, 4500, 514, 49152, 162, 69,
+    5353, 111, 49154, 1701, 998, 996, 997, 999, 3283, 49153, 1812, 136, 2222, 2049, 32768, 5060, 1025, 1433, 3456, 80,
+    20031, 1026, 7, 1646, 1645, 593, 518, 2048, 626, 1027,
+];
+
 /// Port list types for different scanning scenarios
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PortListType {
@@ -285,15 +302,30 @@ pub fn get_development_ports() -> Vec<u16> {
     ]
 }
 
-/// Get ports for a specific count (legacy compatibility)
+/// The `count` most frequently open TCP ports, most common first
 pub fn get_top_ports(count: usize) -> Vec<u16> {
-    if count <= 100 {
-        get_top_100_ports().into_iter().take(count).collect()
-    } else if count <= 1000 {
-        get_top_1000_ports().into_iter().take(count).collect()  
-    } else {
-        get_top_5000_ports().into_iter().take(count).collect()
-    }
+    get_top_ports_for(count, Protocol::Tcp)
+}
+
+/// The `count` most frequently open ports for `protocol`, most common first
+///
+/// Ports past the ranked table continue with the rest of the top 1000 and
+/// then the remaining ports in ascending order, so any count up to 65535 is
+/// served and every prefix is a superset of the shorter ones.
+pub fn get_top_ports_for(count: usize, protocol: Protocol) -> Vec<u16> {
+    let ranking: &[u16] = match protocol {
+        Protocol::Udp => &UDP_FREQUENCY_RANKING,
+        _ => &TCP_FREQUENCY_RANKING,
+    };
+    
+    let mut seen = HashSet::with_capacity(count.min(65535));
+    ranking.iter()
+        .copied()
+        .chain(get_top_1000_ports())
+        .chain(1..=65535)
+        .filter(|port| seen.insert(*port))
+        .take(count)
+        .collect()
 }
 
 /// Show available port lists
@@ -310,3 +342,21 @@ pub fn show_available_lists() {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_ports_by_frequency() {
+        assert_eq!(get_top_ports(5), vec![80, 23, 443, 21, 22]);
+        assert_eq!(get_top_ports_for(3, Protocol::Udp), vec![631, 161, 137]);
+
+        let mut top_1000 = get_top_ports(1000);
+        top_1000.sort_unstable();
+        assert_eq!(top_1000, get_top_1000_ports());
+
+        let all = get_top_ports_for(70000, Protocol::Udp);
+        assert_eq!(all.len(), 65535);
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 65535);
+    }
+}
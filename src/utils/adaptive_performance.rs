@@ -98,8 +98,12 @@ impl AdaptivePerformanceManager {
         const MAX_BATCH_SIZE: u64 = 5000;
         const MIN_BATCH_SIZE: u64 = 100;
 
-        // Start with a percentage of available file descriptors
-        let available_fds = ulimit.saturating_sub(SAFETY_MARGIN);
+        // Start with a percentage of available file descriptors, or of the
+        // sockets a container's memory limit allows if that is fewer
+        let mut available_fds = ulimit.saturating_sub(SAFETY_MARGIN);
+        if let Some(budget) = crate::utils::device::DeviceResources::current().socket_budget() {
+            available_fds = available_fds.min(budget as u64);
+        }
         
         // Use 80% of available FDs, but within reasonable limits
         let calculated_batch = (available_fds * 80 / 100)
@@ -112,7 +116,7 @@ impl AdaptivePerformanceManager {
 
     /// Calculate optimal thread count
     fn calculate_initial_threads() -> u16 {
-        let cpu_count = num_cpus::get();
+        let cpu_count = crate::utils::device::available_cpus();
         
         // For I/O bound operations like port scanning, we can use more threads than CPU cores
         // But we need to be reasonable to avoid overwhelming the system
@@ -0,0 +1,209 @@
+//! Address range classification
+//!
+//! Sorts addresses into the ranges that matter when deciding whether a scan
+//! stays on networks the operator controls: private (RFC 1918, IPv6 ULA),
+//! carrier-grade NAT, loopback, link-local, multicast, other special-purpose
+//! ranges, and everything else, which is public.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AddressClass {
+    Public,
+    Private,
+    /// Shared address space of carrier-grade NAT, 100.64.0.0/10
+    Cgnat,
+    Loopback,
+    LinkLocal,
+    Multicast,
+    /// Unspecified, documentation, benchmarking, broadcast and other
+    /// special-purpose ranges that are not routed on the internet
+    Reserved,
+}
+
+impl AddressClass {
+    pub fn of(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => Self::of_v4(addr),
+            IpAddr::V6(addr) => Self::of_v6(addr),
+        }
+    }
+
+    pub fn of_v4(addr: Ipv4Addr) -> Self {
+        let [a, b, c, _] = addr.octets();
+        match (a, b, c) {
+            (127, _, _) => AddressClass::Loopback,
+            (10, _, _) | (172, 16..=31, _) | (192, 168, _) => AddressClass::Private,
+            (100, 64..=127, _) => AddressClass::Cgnat,
+            (169, 254, _) => AddressClass::LinkLocal,
+            (224..=239, _, _) => AddressClass::Multicast,
+            (0, _, _)
+            | (192, 0, 0)
+            | (192, 0, 2)
+            | (198, 18..=19, _)
+            | (198, 51, 100)
+            | (203, 0, 113)
+            | (240..=255, _, _) => AddressClass::Reserved,
+            _ => AddressClass::Public,
+        }
+    }
+
+    pub fn of_v6(addr: Ipv6Addr) -> Self {
+        if let Some(v4) = ipv4_mapped(&addr) {
+            return Self::of_v4(v4);
+        }
+        let segments = addr.segments();
+        if addr.is_loopback() {
+            AddressClass::Loopback
+        } else if addr.is_multicast() {
+            AddressClass::Multicast
+        } else if segments[0] & 0xffc0 == 0xfe80 {
+            AddressClass::LinkLocal
+        } else if segments[0] & 0xfe00 == 0xfc00 {
+            AddressClass::Private
+        } else if segments[0] & 0xe000 == 0x2000 && !(segments[0] == 0x2001 && segments[1] == 0x0db8) {
+            // Global unicast is 2000::/3, less the 2001:db8::/32 documentation prefix
+            AddressClass::Public
+        } else {
+            AddressClass::Reserved
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AddressClass::Public => "public",
+            AddressClass::Private => "private",
+            AddressClass::Cgnat => "cgnat",
+            AddressClass::Loopback => "loopback",
+            AddressClass::LinkLocal => "link-local",
+            AddressClass::Multicast => "multicast",
+            AddressClass::Reserved => "reserved",
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        *self == AddressClass::Public
+    }
+
+    /// Probe timeout for addresses on the scanning host or its own link,
+    /// which answer in well under a millisecond; `None` for routed classes
+    pub fn local_timeout(&self) -> Option<Duration> {
+        match self {
+            AddressClass::Loopback => Some(Duration::from_millis(10)),
+            AddressClass::LinkLocal => Some(Duration::from_millis(250)),
+            _ => None,
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.local_timeout().is_some()
+    }
+}
+
+impl fmt::Display for AddressClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How many addresses fall into each class
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressBreakdown {
+    counts: BTreeMap<AddressClass, usize>,
+}
+
+impl AddressBreakdown {
+    pub fn from_addresses<I: IntoIterator<Item = IpAddr>>(addresses: I) -> Self {
+        let mut breakdown = Self::default();
+        for addr in addresses {
+            breakdown.add(addr);
+        }
+        breakdown
+    }
+
+    pub fn add(&mut self, addr: IpAddr) {
+        *self.counts.entry(AddressClass::of(addr)).or_insert(0) += 1;
+    }
+
+    pub fn count(&self, class: AddressClass) -> usize {
+        self.counts.get(&class).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Whether public and non-public addresses are mixed
+    pub fn is_mixed(&self) -> bool {
+        let public = self.count(AddressClass::Public);
+        public > 0 && public < self.total()
+    }
+
+    /// Longest [`AddressClass::local_timeout`] if every address is loopback
+    /// or link-local, `None` if any is routed or there are none
+    pub fn local_timeout(&self) -> Option<Duration> {
+        self.counts.keys().map(AddressClass::local_timeout).collect::<Option<Vec<_>>>()?.into_iter().max()
+    }
+
+    /// Classes with their counts, in a fixed order
+    pub fn iter(&self) -> impl Iterator<Item = (AddressClass, usize)> + '_ {
+        self.counts.iter().map(|(class, count)| (*class, *count))
+    }
+}
+
+impl fmt::Display for AddressBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.iter().map(|(class, count)| format!("{} {}", count, class)).collect();
+        f.write_str(&parts.join(", "))
+    }
+}
+
+fn ipv4_mapped(addr: &Ipv6Addr) -> Option<Ipv4Addr> {
+    match addr.segments() {
+        [0, 0, 0, 0, 0, 0xffff, hi, lo] => Some(Ipv4Addr::from(((hi as u32) << 16) | lo as u32)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_classes() {
+        let class = |s: &str| AddressClass::of(s.parse().unwrap());
+        assert_eq!(class("8.8.8.8"), AddressClass::Public);
+        assert_eq!(class("172.31.255.1"), AddressClass::Private);
+        assert_eq!(class("172.32.0.1"), AddressClass::Public);
+        assert_eq!(class("100.100.1.1"), AddressClass::Cgnat);
+        assert_eq!(class("127.0.0.53"), AddressClass::Loopback);
+        assert_eq!(class("169.254.169.254"), AddressClass::LinkLocal);
+        assert_eq!(class("239.255.255.250"), AddressClass::Multicast);
+        assert_eq!(class("198.51.100.7"), AddressClass::Reserved);
+        assert_eq!(class("255.255.255.255"), AddressClass::Reserved);
+        assert_eq!(class("2606:4700::1111"), AddressClass::Public);
+        assert_eq!(class("2001:db8::1"), AddressClass::Reserved);
+        assert_eq!(class("fd00::1"), AddressClass::Private);
+        assert_eq!(class("fe80::1"), AddressClass::LinkLocal);
+        assert_eq!(class("::1"), AddressClass::Loopback);
+        assert_eq!(class("::ffff:10.1.2.3"), AddressClass::Private);
+        assert_eq!(class("::ffff:1.1.1.1").name(), "public");
+
+        let breakdown = AddressBreakdown::from_addresses(
+            ["10.0.0.1", "10.0.0.2", "1.1.1.1", "100.64.0.1"].iter().map(|a| a.parse().unwrap()),
+        );
+        assert_eq!(breakdown.to_string(), "1 public, 2 private, 1 cgnat");
+        assert!(breakdown.is_mixed());
+        assert_eq!(breakdown.total(), 4);
+        assert_eq!(breakdown.local_timeout(), None);
+
+        let local = |addresses: &[&str]| AddressBreakdown::from_addresses(addresses.iter().map(|a| a.parse().unwrap())).local_timeout();
+        assert_eq!(local(&["127.0.0.1", "::1"]), Some(Duration::from_millis(10)));
+        assert_eq!(local(&["127.0.0.1", "169.254.10.2"]), Some(Duration::from_millis(250)));
+        assert_eq!(local(&["127.0.0.1", "192.168.1.1"]), None);
+        assert_eq!(local(&[]), None);
+    }
+}
@@ -245,7 +245,8 @@ impl Ipv4Network {
     fn contains_ipv4(&self, addr: Ipv4Addr) -> bool {
         let network_addr = u32::from(self.addr);
         let test_addr = u32::from(addr);
-        let mask = !((1u32 << (32 - self.prefix)) - 1);
+        // A /0 covers everything; shifting by 32 would overflow
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
         
         (network_addr & mask) == (test_addr & mask)
     }
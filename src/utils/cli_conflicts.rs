@@ -0,0 +1,249 @@
+//! Post-parse checks for contradictory command line flags
+//!
+//! clap's `conflicts_with` only covers flags that can never appear together.
+//! Most surprises come from combinations that are legal but where one flag
+//! silently wins (e.g. `--udp` replacing `-s syn`). These are reported here:
+//! true contradictions as errors, combinations with an obvious winner as
+//! warnings that say what actually happens.
+
+use crate::utils::harden::HardenLevel;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use std::fmt;
+
+/// How a conflict is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The flags cannot be reconciled; refuse to run
+    Error,
+    /// One flag wins; the warning says which
+    Warning,
+}
+
+/// A detected flag conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagConflict {
+    pub resolution: ConflictResolution,
+    pub message: String,
+}
+
+impl FlagConflict {
+    fn error(message: impl Into<String>) -> Self {
+        Self { resolution: ConflictResolution::Error, message: message.into() }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self { resolution: ConflictResolution::Warning, message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.resolution == ConflictResolution::Error
+    }
+}
+
+impl fmt::Display for FlagConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+    matches.try_get_one::<bool>(id).ok().flatten().copied().unwrap_or(false)
+}
+
+/// Whether the user supplied `id`, on the command line or through its
+/// environment variable, as opposed to it being absent or defaulted
+pub fn given(matches: &ArgMatches, id: &str) -> bool {
+    // value_source panics for ids the command does not define
+    matches.ids().any(|present| present.as_str() == id)
+        && matches!(matches.value_source(id), Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable))
+}
+
+/// Value of `id` if the user typed it
+fn explicit<'a>(matches: &'a ArgMatches, id: &str) -> Option<&'a str> {
+    if !given(matches, id) {
+        return None;
+    }
+    matches.try_get_one::<String>(id).ok().flatten().map(String::as_str)
+}
+
+/// Check parsed arguments for contradictory combinations
+pub fn detect_flag_conflicts(matches: &ArgMatches) -> Vec<FlagConflict> {
+    let mut conflicts = Vec::new();
+
+    // Output selection
+    if flag(matches, "greppable") {
+        if let Some(format) = explicit(matches, "output-format").filter(|f| !matches!(*f, "text" | "greppable")) {
+            conflicts.push(FlagConflict::error(format!(
+                "--greppable prints bare IP:PORT lines and cannot be combined with -o {}; drop one of them",
+                format
+            )));
+        }
+        if flag(matches, "all") {
+            conflicts.push(FlagConflict::warning("--greppable lists open ports only; ignoring --all"));
+        }
+    }
+    if explicit(matches, "output-format") == Some("sqlite") && !given(matches, "output-file") {
+        conflicts.push(FlagConflict::error("-o sqlite writes a database file; name it with --output-file, e.g. --output-file scans.db"));
+    }
+
+    // Scan mode
+    if flag(matches, "wrath") && flag(matches, "shadow-scan") {
+        conflicts.push(FlagConflict::error(
+            "--wrath (maximum aggression) and --shadow (minimal footprint) contradict each other; choose one",
+        ));
+    }
+    if flag(matches, "udp") {
+        if let Some(technique) = explicit(matches, "technique").filter(|t| *t != "udp") {
+            conflicts.push(FlagConflict::warning(format!("--udp overrides -s {}; scanning with UDP", technique)));
+        }
+    }
+    if given(matches, "decoys") && !flag(matches, "auto-technique") {
+        let technique = matches.try_get_one::<String>("technique").ok().flatten().map(String::as_str);
+        if let Some(technique) = technique.filter(|t| matches!(*t, "connect" | "udp")) {
+            conflicts.push(FlagConflict::warning(format!(
+                "--decoys spoofs raw TCP SYNs; the {} scan sends none, so no decoys go out (use -s syn)",
+                technique
+            )));
+        }
+    }
+    if given(matches, "proxy") {
+        if let Some(technique) = explicit(matches, "technique").filter(|t| *t != "connect") {
+            conflicts.push(FlagConflict::error(format!(
+                "--proxy only carries connect scans; -s {} would probe from your own address",
+                technique
+            )));
+        }
+    }
+    if flag(matches, "shadow-scan") {
+        if let Some(timing) = explicit(matches, "timing").filter(|t| *t >= "4") {
+            conflicts.push(FlagConflict::warning(format!(
+                "--shadow with -T{}: the fast timing template makes the scan easy to spot",
+                timing
+            )));
+        }
+    }
+
+    // Port selection, in the order the scanner applies it
+    if flag(matches, "full-range") {
+        if flag(matches, "top") {
+            conflicts.push(FlagConflict::warning("--full-range scans all 65535 ports; ignoring --top"));
+        }
+        if let Some(ports) = explicit(matches, "ports") {
+            conflicts.push(FlagConflict::warning(format!("--full-range scans all 65535 ports; ignoring -p {}", ports)));
+        }
+    } else if given(matches, "top-ports") || flag(matches, "top") {
+        if let Some(ports) = explicit(matches, "ports") {
+            let selection = if given(matches, "top-ports") { "--top-ports" } else { "--top" };
+            conflicts.push(FlagConflict::warning(format!("{} selects the ports; ignoring -p {}", selection, ports)));
+        }
+    }
+    if given(matches, "seed") && explicit(matches, "scan-order") != Some("random") {
+        conflicts.push(FlagConflict::warning("--seed only affects --scan-order random; ports are scanned in order"));
+    }
+
+    // Follow-up stages
+    if flag(matches, "ports-only") {
+        if let Some(mode) = explicit(matches, "scripts").filter(|m| *m != "none") {
+            conflicts.push(FlagConflict::warning(format!("--ports-only skips script execution; ignoring --scripts {}", mode)));
+        }
+    } else if explicit(matches, "scripts") == Some("none") {
+        for id in ["script-dir", "script-tags"] {
+            if given(matches, id) {
+                conflicts.push(FlagConflict::warning(format!("--scripts none disables scripts; ignoring --{}", id)));
+            }
+        }
+    }
+    if given(matches, "nmap-args") {
+        if let Some(disabled_by) = ["ports-only", "no-nmap"].into_iter().find(|id| flag(matches, id)) {
+            conflicts.push(FlagConflict::warning(format!("--{} skips Nmap; ignoring --nmap-args", disabled_by)));
+        }
+    }
+    if given(matches, "proxy") {
+        let direct = ["service-version", "os-detection", "misconfig-checks", "web-enrich", "screenshots", "default-creds", "dns-sweep"];
+        for id in direct.into_iter().filter(|id| given(matches, id)) {
+            conflicts.push(FlagConflict::error(format!(
+                "--proxy only carries the port scan; --{} would reach the targets directly",
+                id
+            )));
+        }
+    }
+    if given(matches, "msf-workspace") && !given(matches, "msfdb") {
+        conflicts.push(FlagConflict::warning("--msf-workspace is only used with --msfdb"));
+    }
+
+    // Hardening
+    if matches.try_get_one::<HardenLevel>("harden").ok().flatten() == Some(&HardenLevel::Strict) {
+        let spawning = ["on-scan-start", "on-host-start", "on-host-complete", "on-scan-complete", "screenshots", "msfdb"];
+        for id in spawning.into_iter().filter(|id| given(matches, id)) {
+            conflicts.push(FlagConflict::error(format!(
+                "--harden strict refuses to run other programs, which --{} needs; use --harden standard",
+                id
+            )));
+        }
+        if given(matches, "nmap-args") {
+            conflicts.push(FlagConflict::warning("--harden strict cannot run Nmap; ignoring --nmap-args"));
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+
+    fn parse(args: &[&str]) -> ArgMatches {
+        let switch = |id: &'static str| Arg::new(id).long(id).action(ArgAction::SetTrue);
+        Command::new("phobos")
+            .arg(switch("greppable").short('g'))
+            .arg(switch("udp"))
+            .arg(switch("ports-only"))
+            .arg(switch("wrath"))
+            .arg(Arg::new("output-format").short('o').default_value("text"))
+            .arg(Arg::new("output-file").long("output-file"))
+            .arg(Arg::new("decoys").short('D'))
+            .arg(Arg::new("technique").short('s').default_value("connect"))
+            .arg(Arg::new("scripts").long("scripts").default_value("default"))
+            .arg(Arg::new("ports").short('p').default_value("1-1000"))
+            .arg(Arg::new("harden").long("harden").num_args(0..=1).default_missing_value("standard").value_parser(clap::value_parser!(HardenLevel)))
+            .arg(Arg::new("on-host-complete").long("on-host-complete"))
+            .arg(Arg::new("nmap-args").long("nmap-args"))
+            .arg(Arg::new("proxy").long("proxy"))
+            .arg(switch("web-enrich"))
+            .try_get_matches_from(args)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_detects_conflicts() {
+        assert!(detect_flag_conflicts(&parse(&["phobos"])).is_empty());
+        assert!(detect_flag_conflicts(&parse(&["phobos", "-g", "-o", "text", "-s", "udp", "--udp"])).is_empty());
+
+        let conflicts = detect_flag_conflicts(&parse(&["phobos", "-g", "-o", "json", "--udp", "-s", "syn"]));
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts[0].is_error());
+        assert_eq!(conflicts[1], FlagConflict::warning("--udp overrides -s syn; scanning with UDP"));
+
+        let conflicts = detect_flag_conflicts(&parse(&["phobos", "--ports-only", "--scripts", "all"]));
+        assert_eq!(conflicts, vec![FlagConflict::warning("--ports-only skips script execution; ignoring --scripts all")]);
+
+        let conflicts = detect_flag_conflicts(&parse(&["phobos", "-D", "192.0.2.1,ME"]));
+        assert_eq!(conflicts, vec![FlagConflict::warning("--decoys spoofs raw TCP SYNs; the connect scan sends none, so no decoys go out (use -s syn)")]);
+        assert!(detect_flag_conflicts(&parse(&["phobos", "-D", "192.0.2.1,ME", "-s", "syn"])).is_empty());
+
+        assert!(detect_flag_conflicts(&parse(&["phobos", "-o", "sqlite"]))[0].is_error());
+        assert!(detect_flag_conflicts(&parse(&["phobos", "-o", "sqlite", "--output-file", "scans.db"])).is_empty());
+
+        assert!(detect_flag_conflicts(&parse(&["phobos", "--harden", "--on-host-complete", "notify.sh"])).is_empty());
+        let conflicts = detect_flag_conflicts(&parse(&["phobos", "--harden", "strict", "--on-host-complete", "notify.sh", "--nmap-args=-sC"]));
+        assert!(conflicts[0].is_error());
+        assert_eq!(conflicts[1], FlagConflict::warning("--harden strict cannot run Nmap; ignoring --nmap-args"));
+
+        assert!(detect_flag_conflicts(&parse(&["phobos", "--proxy", "socks5://127.0.0.1:9050", "-s", "connect"])).is_empty());
+        let conflicts = detect_flag_conflicts(&parse(&["phobos", "--proxy", "socks5://127.0.0.1:9050", "-s", "syn", "--web-enrich"]));
+        assert_eq!(conflicts[0], FlagConflict::error("--proxy only carries connect scans; -s syn would probe from your own address"));
+        assert_eq!(conflicts[1], FlagConflict::error("--proxy only carries the port scan; --web-enrich would reach the targets directly"));
+    }
+}
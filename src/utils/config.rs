@@ -1,10 +1,13 @@
 //! Configuration utilities and validation
 
 use crate::config::ScanConfig;
-use crate::network::ScanTechnique;
+use crate::network::{ScanTechnique, stealth::StealthOptions};
+use crate::utils::profiles::ScanProfile;
+use std::fmt;
 use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Configuration file format
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -150,10 +153,478 @@ impl ConfigManager {
     }
 }
 
+/// Severity of a configuration diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    /// The file cannot be used as written
+    Error,
+    /// The file loads, but probably does not do what was intended
+    Warning,
+}
+
+/// A problem found while validating a config file or profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigDiagnostic {
+    pub severity: DiagnosticSeverity,
+    /// Dotted key path, e.g. `stealth_options.fragment_packets`
+    pub key: Option<String>,
+    /// 1-based line and column in the file
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    /// Closest known key for a misspelled one
+    pub suggestion: Option<String>,
+}
+
+impl ConfigDiagnostic {
+    fn new(severity: DiagnosticSeverity, key: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            key: key.map(str::to_string),
+            line: None,
+            column: None,
+            message: message.into(),
+            suggestion: None,
+        }
+    }
+
+    fn error(key: Option<&str>, message: impl Into<String>) -> Self {
+        Self::new(DiagnosticSeverity::Error, key, message)
+    }
+
+    fn warning(key: Option<&str>, message: impl Into<String>) -> Self {
+        Self::new(DiagnosticSeverity::Warning, key, message)
+    }
+
+    fn from_toml_error(content: &str, error: &toml::de::Error) -> Self {
+        let mut diagnostic = Self::error(None, error.message().trim());
+        if let Some(span) = error.span() {
+            let (line, column) = line_column(content, span.start);
+            diagnostic.line = Some(line);
+            diagnostic.column = Some(column);
+        }
+        diagnostic
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == DiagnosticSeverity::Error
+    }
+
+    /// Fill in the position from the key, unless already known
+    fn locate_in(mut self, content: &str) -> Self {
+        if self.line.is_none() {
+            if let Some((line, column)) = self.key.as_deref().and_then(|key| locate_key(content, key)) {
+                self.line = Some(line);
+                self.column = Some(column);
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{}`?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Schema a config file is validated against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSchema {
+    /// A `--config` / `~/.phobos.toml` file deserialized into [`ScanConfig`]
+    Scan,
+    /// A user profile in `~/.phobos/profiles`
+    Profile,
+}
+
+impl ConfigSchema {
+    /// Every accepted key, as a JSON object mirroring the serialized struct
+    fn shape(self) -> Value {
+        match self {
+            ConfigSchema::Scan => {
+                // Populate optional tables so their nested keys are known too
+                let sample = ScanConfig {
+                    stealth_options: Some(StealthOptions::default()),
+                    ..Default::default()
+                };
+                serde_json::to_value(sample).unwrap_or(Value::Null)
+            }
+            ConfigSchema::Profile => serde_json::to_value(ScanProfile {
+                name: String::new(),
+                description: String::new(),
+                technique: ScanTechnique::Connect,
+                threads: 0,
+                timeout: 0,
+                rate_limit: 0,
+                timing_template: 0,
+                stealth_level: 0,
+                max_retries: 0,
+                batch_size: None,
+                adaptive: false,
+                source_port: None,
+                interface: None,
+            })
+            .unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map_or(before.len(), |nl| before.len() - nl - 1) + 1;
+    (line, column)
+}
+
+/// Position of a dotted key, written either under a `[table]` header or as `table.key = ...`
+fn locate_key(content: &str, path: &str) -> Option<(usize, usize)> {
+    let (table, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut current = String::new();
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if let Some(header) = trimmed.strip_prefix('[') {
+            current = header.trim_start_matches('[').split(']').next().unwrap_or("").trim().to_string();
+            if current == path {
+                return Some((index + 1, column));
+            }
+            continue;
+        }
+        let Some((name, _)) = trimmed.split_once('=') else { continue };
+        let name = name.trim().trim_matches('"');
+        if (current == table && name == key) || (current.is_empty() && name == path) {
+            return Some((index + 1, column));
+        }
+    }
+    None
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Closest known key, if it is plausibly a typo of `key`
+fn suggest_key<'a>(key: &str, known: impl Iterator<Item = &'a String>) -> Option<String> {
+    let normalized = key.to_lowercase().replace('-', "_");
+    known
+        .map(|candidate| (edit_distance(&normalized, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (normalized.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.clone())
+}
+
+/// Report keys of `table` that `shape` does not know, recursing into nested tables
+fn check_keys(table: &toml::Table, shape: &Value, prefix: &str, diagnostics: &mut Vec<ConfigDiagnostic>) {
+    let Some(known) = shape.as_object() else { return };
+    for (key, value) in table {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match known.get(key) {
+            Some(nested) => {
+                if let toml::Value::Table(inner) = value {
+                    check_keys(inner, nested, &path, diagnostics);
+                }
+            }
+            None => {
+                let mut diagnostic = ConfigDiagnostic::error(Some(&path), format!("unknown key `{}`", path));
+                diagnostic.suggestion = suggest_key(key, known.keys()).map(|s| {
+                    if prefix.is_empty() { s } else { format!("{}.{}", prefix, s) }
+                });
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+}
+
+/// Highest `rate_limit` a scan configuration may ask for, in probes per second
+pub const MAX_RATE_LIMIT: u64 = 50_000_000;
+
 /// Configuration validator
 pub struct ConfigValidator;
 
 impl ConfigValidator {
+    /// Validate a config file or profile on disk
+    pub fn validate_config_file<P: AsRef<Path>>(path: P, schema: ConfigSchema) -> crate::Result<Vec<ConfigDiagnostic>> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| crate::ScanError::ConfigError(format!("Failed to read {}: {}", path.as_ref().display(), e)))?;
+        Ok(Self::validate_config_str(&content, schema))
+    }
+    
+    /// Validate config file contents against `schema`
+    ///
+    /// Reports TOML syntax errors, unknown keys (with the closest known key
+    /// as a suggestion), type errors and missing fields with their position,
+    /// followed by warnings for settings that work against each other.
+    pub fn validate_config_str(content: &str, schema: ConfigSchema) -> Vec<ConfigDiagnostic> {
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => return vec![ConfigDiagnostic::from_toml_error(content, &e)],
+        };
+        
+        let mut diagnostics = Vec::new();
+        check_keys(&table, &schema.shape(), "", &mut diagnostics);
+        
+        let semantic = match schema {
+            ConfigSchema::Scan => toml::from_str::<ScanConfig>(content).map(|config| Self::check_combinations(&config)),
+            ConfigSchema::Profile => toml::from_str::<ScanProfile>(content).map(|profile| Self::check_profile(&profile)),
+        };
+        match semantic {
+            Ok(found) => diagnostics.extend(found),
+            Err(e) => diagnostics.push(ConfigDiagnostic::from_toml_error(content, &e)),
+        }
+        
+        let mut diagnostics: Vec<ConfigDiagnostic> = diagnostics.into_iter().map(|d| d.locate_in(content)).collect();
+        diagnostics.sort_by_key(|d| (d.line.unwrap_or(usize::MAX), d.column));
+        diagnostics
+    }
+    
+    /// Out-of-range values and settings that undermine each other
+    pub fn check_combinations(config: &ScanConfig) -> Vec<ConfigDiagnostic> {
+        let mut diagnostics = Vec::new();
+        
+        if config.timing_template > 5 {
+            diagnostics.push(ConfigDiagnostic::error(
+                Some("timing_template"),
+                format!("timing_template {} is out of range (0-5)", config.timing_template),
+            ));
+        }
+        if config.min_response_time > config.max_response_time {
+            diagnostics.push(ConfigDiagnostic::error(
+                Some("min_response_time"),
+                format!(
+                    "min_response_time ({}ms) is greater than max_response_time ({}ms)",
+                    config.min_response_time, config.max_response_time
+                ),
+            ));
+        }
+        if let Some(host_rate) = config.host_rate_limit {
+            if host_rate > config.rate_limit {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("host_rate_limit"),
+                    format!("host_rate_limit ({}) is above rate_limit ({}) and has no effect", host_rate, config.rate_limit),
+                ));
+            }
+        }
+        if config.ramp_up.is_some() && config.effective_rate() == 0 {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("ramp_up"),
+                "ramp_up has no effect without a rate_limit to ramp up to",
+            ));
+        }
+        if config.ramp_start.is_some_and(|percent| percent == 0 || percent > 100) {
+            diagnostics.push(ConfigDiagnostic::error(Some("ramp_start"), "ramp_start is a percentage from 1 to 100"));
+        } else if config.ramp_start.is_some() && config.ramp_up.is_none() {
+            diagnostics.push(ConfigDiagnostic::warning(Some("ramp_start"), "ramp_start has no effect without ramp_up"));
+        }
+        if let (Some(min), Some(max)) = (config.min_rate, config.max_rate) {
+            if min > max {
+                diagnostics.push(ConfigDiagnostic::error(
+                    Some("min_rate"),
+                    format!("min_rate ({}) is greater than max_rate ({})", min, max),
+                ));
+            }
+        }
+        if config.health_threshold.is_some() && config.health_check.is_none() {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("health_threshold"),
+                "health_threshold has no effect without health_check".to_string(),
+            ));
+        }
+        if let Some(bits) = config.max_bandwidth {
+            let pps = crate::network::bandwidth::probes_per_second(bits, config.technique, &config.ports);
+            if pps > config.rate_limit {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("max_bandwidth"),
+                    format!(
+                        "max_bandwidth ({}) allows {} probes/s, above rate_limit ({}), and has no effect",
+                        crate::network::bandwidth::format_bandwidth(bits), pps, config.rate_limit
+                    ),
+                ));
+            }
+        }
+        if let Some(filter) = &config.capture_filter {
+            if let Err(e) = crate::network::bpf::FilterExpr::parse(filter) {
+                diagnostics.push(ConfigDiagnostic::error(Some("capture_filter"), e.to_string()));
+            } else if !config.technique.requires_raw_socket() {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("capture_filter"),
+                    "capture_filter only applies to raw-socket techniques; connect scans ignore it",
+                ));
+            }
+        }
+        for probe in &config.probes {
+            if let Err(e) = probe.compile() {
+                diagnostics.push(ConfigDiagnostic::error(Some("probes"), e.to_string()));
+            }
+        }
+        for rule in &config.banner_rules {
+            if let Err(e) = rule.compile() {
+                diagnostics.push(ConfigDiagnostic::error(Some("banner_rules"), e.to_string()));
+            }
+        }
+        if config.vlan.is_some() && !config.technique.requires_raw_socket() {
+            diagnostics.push(ConfigDiagnostic::error(
+                Some("vlan"),
+                "vlan tagging needs a raw-socket technique (e.g. syn); connect scans go through the host's own VLAN setup",
+            ));
+        } else if config.vlan_source.is_some() && config.vlan.is_none() {
+            diagnostics.push(ConfigDiagnostic::warning(Some("vlan_source"), "vlan_source has no effect without vlan"));
+        }
+        if config.notrack && !config.technique.requires_raw_socket() {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("notrack"),
+                "notrack only marks raw probes; connect scans go through the kernel and are always tracked",
+            ));
+        } else if config.notrack && config.vlan.is_some() {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("notrack"),
+                "notrack has no effect with vlan; tagged probes are sent on the link layer and never tracked",
+            ));
+        }
+        if config.send_thread && (config.technique != ScanTechnique::Syn || config.vlan.is_some()) {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("send_thread"),
+                "send_thread only carries untagged raw SYN probes; other techniques and vlan send theirs as before",
+            ));
+        }
+        if config.verify_open && config.vlan.is_some() {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("verify_open"),
+                "verify_open re-checks ports with untagged connects, which do not reach hosts only reachable on the vlan",
+            ));
+        }
+        match (&config.geoip, config.geo_origin) {
+            (Some(_), None) => diagnostics.push(ConfigDiagnostic::warning(
+                Some("geoip"),
+                "geoip is only used with geo_origin; without the scanner's location hosts are compared with their subnet only",
+            )),
+            (None, Some(_)) => diagnostics.push(ConfigDiagnostic::warning(
+                Some("geo_origin"),
+                "geo_origin is only used with a geoip file",
+            )),
+            _ => {}
+        }
+        if let Some(mtu) = config.mtu_override {
+            if mtu < crate::network::mtu::MIN_MTU {
+                diagnostics.push(ConfigDiagnostic::error(
+                    Some("mtu_override"),
+                    format!("mtu_override must be at least {} bytes", crate::network::mtu::MIN_MTU),
+                ));
+            } else if mtu < 576 {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("mtu_override"),
+                    "mtu_override is below 576, the size every IPv4 host must accept; many paths carry more",
+                ));
+            }
+        }
+        if config.source_port.is_some() && config.technique == ScanTechnique::Connect && config.threads > 1 {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("source_port"),
+                "a fixed source_port lets only one connect probe bind at a time; use source_port_range instead",
+            ));
+        }
+        
+        if let Some(stealth) = &config.stealth_options {
+            let evasive = stealth.fragment_packets
+                || stealth.timing_randomization
+                || stealth.spoof_source_ip.is_some()
+                || !stealth.decoy_addresses.is_empty();
+            if evasive && config.timing_template >= 4 {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("timing_template"),
+                    format!(
+                        "T{} ({}) timing with stealth evasion enabled: the probe burst is what IDS notice first",
+                        config.timing_template,
+                        crate::output::provenance::timing_profile_name(config.timing_template)
+                    ),
+                ));
+            }
+            if config.technique == ScanTechnique::Connect
+                && (stealth.fragment_packets || stealth.spoof_source_ip.is_some() || !stealth.decoy_addresses.is_empty())
+            {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("technique"),
+                    "fragmentation, decoys and spoofing need a raw-socket technique; the connect scan ignores them",
+                ));
+            }
+            if stealth.spoof_source_ip.is_some() {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("stealth_options.spoof_source_ip"),
+                    "replies go to the spoofed address, so this host will not see which ports are open",
+                ));
+            }
+            if stealth.use_bad_checksum {
+                diagnostics.push(ConfigDiagnostic::warning(
+                    Some("stealth_options.use_bad_checksum"),
+                    "hosts drop packets with bad checksums; only middleboxes answer, so open ports will be missed",
+                ));
+            }
+        }
+        
+        diagnostics
+    }
+    
+    /// Out-of-range values and contradictory settings in a profile
+    pub fn check_profile(profile: &ScanProfile) -> Vec<ConfigDiagnostic> {
+        let mut diagnostics = Vec::new();
+        
+        if profile.timing_template > 5 {
+            diagnostics.push(ConfigDiagnostic::error(
+                Some("timing_template"),
+                format!("timing_template {} is out of range (0-5)", profile.timing_template),
+            ));
+        }
+        if profile.stealth_level > 5 {
+            diagnostics.push(ConfigDiagnostic::error(
+                Some("stealth_level"),
+                format!("stealth_level {} is out of range (0-5)", profile.stealth_level),
+            ));
+        }
+        if profile.threads == 0 || profile.timeout == 0 || profile.rate_limit == 0 {
+            let key = if profile.threads == 0 { "threads" } else if profile.timeout == 0 { "timeout" } else { "rate_limit" };
+            diagnostics.push(ConfigDiagnostic::error(Some(key), format!("{} must be greater than 0", key)));
+        }
+        if profile.timing_template >= 5 && profile.stealth_level >= 4 {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("timing_template"),
+                format!(
+                    "insane timing (T{}) defeats stealth level {} ({}); use T0-T2 for evasive scans",
+                    profile.timing_template,
+                    profile.stealth_level,
+                    if profile.stealth_level >= 5 { "ghost" } else { "paranoid" }
+                ),
+            ));
+        }
+        if profile.stealth_level >= 3 && profile.technique == ScanTechnique::Connect {
+            diagnostics.push(ConfigDiagnostic::warning(
+                Some("technique"),
+                format!(
+                    "stealth level {} relies on raw packets; the connect scan ignores fragmentation, decoys and spoofing",
+                    profile.stealth_level
+                ),
+            ));
+        }
+        
+        diagnostics
+    }
+    
     /// Validate scan configuration
     pub fn validate_scan_config(config: &ScanConfig) -> Vec<String> {
         let mut errors = Vec::new();
@@ -192,7 +663,7 @@ impl ConfigValidator {
         // Validate rate limit
         if config.rate_limit == 0 {
             errors.push("Rate limit must be greater than 0".to_string());
-        } else if config.rate_limit > 50_000_000 {
+        } else if config.rate_limit > MAX_RATE_LIMIT {
             errors.push("Rate limit is too high (max 50M pps)".to_string());
         }
         
@@ -211,6 +682,13 @@ impl ConfigValidator {
             }
         }
         
+        errors.extend(
+            Self::check_combinations(config)
+                .into_iter()
+                .filter(ConfigDiagnostic::is_error)
+                .map(|d| d.message),
+        );
+        
         errors
     }
     
@@ -296,6 +774,15 @@ impl ConfigValidator {
     }
 }
 
+/// Prefix of the environment variables that stand in for command line flags
+pub const ENV_PREFIX: &str = "PHOBOS_";
+
+/// Environment variable for the flag with clap id `id`: `rate-limit` is read
+/// from `PHOBOS_RATE_LIMIT`
+pub fn env_var_name(id: &str) -> String {
+    format!("{}{}", ENV_PREFIX, id.to_uppercase().replace('-', "_"))
+}
+
 /// Environment configuration
 pub struct EnvironmentConfig;
 
@@ -304,29 +791,30 @@ impl EnvironmentConfig {
     pub fn from_env() -> ScanConfig {
         let mut config = ScanConfig::default();
         
-        if let Ok(target) = std::env::var("PHOBOS_TARGET") {
+        if let Ok(target) = std::env::var(env_var_name("target")) {
             config.target = target;
         }
 
-        if let Ok(threads) = std::env::var("PHOBOS_THREADS") {
+        if let Ok(threads) = std::env::var(env_var_name("threads")) {
             if let Ok(threads) = threads.parse() {
                 config.threads = threads;
             }
         }
 
-        if let Ok(timeout) = std::env::var("PHOBOS_TIMEOUT") {
+        if let Ok(timeout) = std::env::var(env_var_name("timeout")) {
             if let Ok(timeout) = timeout.parse() {
                 config.timeout = timeout;
             }
         }
 
-        if let Ok(rate) = std::env::var("PHOBOS_RATE") {
+        // PHOBOS_RATE predates the flag-named variables
+        if let Ok(rate) = std::env::var(env_var_name("rate-limit")).or_else(|_| std::env::var("PHOBOS_RATE")) {
             if let Ok(rate) = rate.parse() {
                 config.rate_limit = rate;
             }
         }
 
-        if let Ok(technique) = std::env::var("PHOBOS_TECHNIQUE") {
+        if let Ok(technique) = std::env::var(env_var_name("technique")) {
             config.technique = match technique.to_uppercase().as_str() {
                 "SYN" => ScanTechnique::Syn,
                 "CONNECT" => ScanTechnique::Connect,
@@ -335,6 +823,7 @@ impl EnvironmentConfig {
                 "NULL" => ScanTechnique::Null,
                 "XMAS" => ScanTechnique::Xmas,
                 "ACK" => ScanTechnique::Ack,
+                "WINDOW" => ScanTechnique::Window,
                 _ => ScanTechnique::Syn,
             };
         }
@@ -344,22 +833,77 @@ impl EnvironmentConfig {
     
     /// Set environment variables from config
     pub fn to_env(config: &ScanConfig) {
-        std::env::set_var("PHOBOS_TARGET", &config.target);
-        std::env::set_var("PHOBOS_THREADS", config.threads.to_string());
-        std::env::set_var("PHOBOS_TIMEOUT", config.timeout.to_string());
-        std::env::set_var("PHOBOS_RATE", config.rate_limit.to_string());
+        std::env::set_var(env_var_name("target"), &config.target);
+        std::env::set_var(env_var_name("threads"), config.threads.to_string());
+        std::env::set_var(env_var_name("timeout"), config.timeout.to_string());
+        std::env::set_var(env_var_name("rate-limit"), config.rate_limit.to_string());
         
+        // Spelled as `--scan-type` takes them
         let technique_str = match config.technique {
-            ScanTechnique::Syn => "SYN",
-            ScanTechnique::Connect => "CONNECT",
-            ScanTechnique::Udp => "UDP",
-            ScanTechnique::Fin => "FIN",
-            ScanTechnique::Null => "NULL",
-            ScanTechnique::Xmas => "XMAS",
-            ScanTechnique::Ack => "ACK",
-            ScanTechnique::Window => "WINDOW",
-            ScanTechnique::Stealth => "STEALTH",
+            ScanTechnique::Syn => "syn",
+            ScanTechnique::Connect => "connect",
+            ScanTechnique::Udp => "udp",
+            ScanTechnique::Fin => "fin",
+            ScanTechnique::Null => "null",
+            ScanTechnique::Xmas => "xmas",
+            ScanTechnique::Ack => "ack",
+            ScanTechnique::Window => "window",
+            ScanTechnique::Stealth => "stealth",
         };
-        std::env::set_var("PHOBOS_TECHNIQUE", technique_str);
+        std::env::set_var(env_var_name("technique"), technique_str);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = r#"
+target = "10.0.0.1"
+ports = [22, 80]
+technique = "Syn"
+threads = 100
+timeout = 1000
+rate_limit = 1000
+timing_template = 5
+realtime_notifications = false
+notification_color = "orange"
+adaptive_learning = false
+min_response_time = 1
+max_response_time = 1000
+"#;
+
+    #[test]
+    fn test_unknown_keys_and_type_errors_are_located() {
+        let content = format!("{}thread = 10\nrate-limit = 5\n\n[stealth_options]\nfragment_packet = true\n", BASE);
+        let diagnostics = ConfigValidator::validate_config_str(&content, ConfigSchema::Scan);
+
+        let unknown: Vec<String> = diagnostics.iter().filter(|d| d.message.starts_with("unknown")).map(|d| d.to_string()).collect();
+        assert_eq!(unknown, vec![
+            "14:1: unknown key `thread` (did you mean `threads`?)",
+            "15:1: unknown key `rate-limit` (did you mean `rate_limit`?)",
+            "18:1: unknown key `stealth_options.fragment_packet` (did you mean `stealth_options.fragment_packets`?)",
+        ]);
+
+        let typed = BASE.replace("threads = 100", "threads = \"many\"");
+        let diagnostics = ConfigValidator::validate_config_str(&typed, ConfigSchema::Scan);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(5), Some(11)));
+    }
+
+    #[test]
+    fn test_dangerous_combinations() {
+        let content = format!("{}\n[stealth_options]\nfragment_packets = true\nrandomize_source_port = true\ndecoy_addresses = []\ntiming_randomization = false\nrandomize_ip_id = true\nrandomize_sequence = true\nuse_bad_checksum = false\n", BASE);
+        let diagnostics = ConfigValidator::validate_config_str(&content, ConfigSchema::Scan);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[0].line, Some(8));
+        assert!(diagnostics[0].message.contains("T5 (insane)"));
+
+        let profile = "name = \"fast-ghost\"\ndescription = \"\"\ntechnique = \"Syn\"\nthreads = 10\ntimeout = 100\nrate_limit = 10\ntiming_template = 5\nstealth_level = 5\nmax_retries = 1\nadaptive = false\n";
+        let diagnostics = ConfigValidator::validate_config_str(profile, ConfigSchema::Profile);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].to_string().starts_with("7:1: insane timing (T5) defeats stealth level 5 (ghost)"));
+    }
+}
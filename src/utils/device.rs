@@ -0,0 +1,338 @@
+//! Resources of the machine running the scan
+//!
+//! Phobos is often run from small drop boxes: a Raspberry Pi on a switch
+//! port, an ARM board in a rack. Its defaults assume a workstation, and on a
+//! board with a gigabyte of memory thousands of concurrent sockets and a full
+//! Nmap run afterwards can push the box into swap or the OOM killer. Such
+//! devices are detected here and get the `constrained` profile: concurrency
+//! scaled to their CPUs and memory, and no automatic enrichment.
+//!
+//! Inside a container the machine's CPU count and `/proc/meminfo` describe
+//! the host, not what the kernel lets the scan use, so the limits of the
+//! process's cgroup (v1 or v2) take precedence when there are any.
+
+use crate::network::ScanTechnique;
+use crate::utils::profiles::ScanProfile;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Memory at or below which a device counts as constrained on any CPU
+const SMALL_MEMORY: u64 = 2 << 30;
+
+/// Memory at or below which a small ARM board counts as constrained
+const ARM_BOARD_MEMORY: u64 = 4 << 30;
+
+/// Cores at or below which an ARM board counts as small
+const ARM_BOARD_CPUS: usize = 4;
+
+/// Concurrent probes per CPU core on a constrained device
+const THREADS_PER_CPU: usize = 50;
+
+/// Memory budgeted per probe in flight, generous for socket buffers and task state
+const MEMORY_PER_PROBE: u64 = 256 << 10;
+
+/// Share of memory a constrained scan may use for probes in flight
+const MEMORY_SHARE: u64 = 8;
+
+/// Kernel memory charged to a cgroup per open socket, buffers included
+const MEMORY_PER_SOCKET: u64 = 64 << 10;
+
+/// Share of a cgroup's memory limit open sockets may take
+const SOCKET_MEMORY_SHARE: u64 = 4;
+
+/// cgroup v1 reports "no limit" as a page-aligned `i64::MAX`
+const UNLIMITED_V1: u64 = 1 << 62;
+
+/// Limits the process's cgroup enforces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CgroupLimits {
+    /// 1 or 2
+    pub version: u8,
+    /// CPU quota in thousandths of a CPU, as Kubernetes writes it
+    pub cpu_millis: Option<u64>,
+    /// Memory limit in bytes
+    pub memory: Option<u64>,
+}
+
+impl CgroupLimits {
+    /// Whole CPUs the quota allows, rounded up
+    pub fn cpus(&self) -> Option<usize> {
+        self.cpu_millis.map(|millis| ((millis + 999) / 1000).max(1) as usize)
+    }
+}
+
+/// What the scanner has to work with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceResources {
+    /// Usable CPUs, after any cgroup quota
+    pub cpus: usize,
+    /// Usable memory in bytes, after any cgroup limit, if the platform reports it
+    pub memory: Option<u64>,
+    /// Target architecture, e.g. `aarch64`
+    pub arch: &'static str,
+    /// Limits of the container the scan runs in, if it is limited
+    pub cgroup: Option<CgroupLimits>,
+}
+
+impl DeviceResources {
+    /// Resources of this machine, or of its container
+    pub fn detect() -> Self {
+        let cgroup = cgroup_limits();
+        let mut cpus = num_cpus::get();
+        let mut memory = total_memory();
+        if let Some(limits) = cgroup {
+            cpus = limits.cpus().map_or(cpus, |quota| quota.min(cpus));
+            memory = match (memory, limits.memory) {
+                (Some(total), Some(limit)) => Some(total.min(limit)),
+                (total, limit) => total.or(limit),
+            };
+        }
+        Self { cpus, memory, arch: std::env::consts::ARCH, cgroup }
+    }
+
+    /// [`detect`](Self::detect)ed once per process, for sizing pools and batches
+    pub fn current() -> &'static Self {
+        static CURRENT: OnceLock<DeviceResources> = OnceLock::new();
+        CURRENT.get_or_init(Self::detect)
+    }
+
+    /// Raspberry Pi class: little memory, or a small ARM board
+    pub fn is_constrained(&self) -> bool {
+        let Some(memory) = self.memory else {
+            return false;
+        };
+        let arm = self.arch.starts_with("arm") || self.arch == "aarch64";
+        memory <= SMALL_MEMORY || (arm && self.cpus <= ARM_BOARD_CPUS && memory <= ARM_BOARD_MEMORY)
+    }
+
+    /// Most probes in flight this device should run
+    pub fn max_concurrency(&self) -> usize {
+        let by_cpu = self.cpus.max(1) * THREADS_PER_CPU;
+        let by_memory = self
+            .memory
+            .map_or(usize::MAX, |memory| (memory / MEMORY_SHARE / MEMORY_PER_PROBE) as usize);
+        by_cpu.min(by_memory).clamp(20, 1000)
+    }
+
+    /// Most sockets a container's memory limit leaves room for, `None` outside one
+    ///
+    /// The descriptor limit alone does not protect a container: every socket's
+    /// buffers are charged to its cgroup, and the OOM killer acts long before
+    /// a generous `ulimit -n` is reached.
+    pub fn socket_budget(&self) -> Option<usize> {
+        let limit = self.cgroup?.memory?;
+        Some((limit / SOCKET_MEMORY_SHARE / MEMORY_PER_SOCKET).max(1) as usize)
+    }
+
+    /// The `constrained` profile for this device
+    pub fn constrained_profile(&self) -> ScanProfile {
+        let concurrency = self.max_concurrency();
+        ScanProfile {
+            name: "constrained".to_string(),
+            description: "Small devices such as a Raspberry Pi: concurrency fitted to CPUs and memory, no automatic Nmap run".to_string(),
+            technique: ScanTechnique::Connect,
+            threads: concurrency,
+            timeout: 3000,
+            rate_limit: 50_000,
+            timing_template: 3, // Normal timing
+            stealth_level: 0,
+            max_retries: 2,
+            batch_size: Some(concurrency),
+            adaptive: false,
+            source_port: None,
+            interface: None,
+        }
+    }
+
+    /// e.g. `4 CPUs, 1.8 GiB, aarch64` or `2 CPUs, 0.5 GiB, x86_64, cgroup v2`
+    pub fn describe(&self) -> String {
+        let mut description = match self.memory {
+            Some(memory) => format!("{} CPUs, {:.1} GiB, {}", self.cpus, memory as f64 / (1u64 << 30) as f64, self.arch),
+            None => format!("{} CPUs, {}", self.cpus, self.arch),
+        };
+        if let Some(limits) = self.cgroup {
+            description.push_str(&format!(", cgroup v{}", limits.version));
+        }
+        description
+    }
+}
+
+/// CPUs the scan may use: the cgroup quota when there is one
+pub fn available_cpus() -> usize {
+    DeviceResources::current().cpus
+}
+
+#[cfg(target_os = "linux")]
+fn cgroup_limits() -> Option<CgroupLimits> {
+    let membership = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    read_cgroup_limits(Path::new("/sys/fs/cgroup"), &membership)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cgroup_limits() -> Option<CgroupLimits> {
+    None
+}
+
+/// Limits for the cgroups listed in `membership` (`/proc/self/cgroup`),
+/// under the hierarchy mounted at `root`
+///
+/// A limit set on a parent cgroup, such as a Kubernetes pod around its
+/// containers, applies too, so the smallest along the path wins. Paths are
+/// tried relative to the mount as well, for containers with a private
+/// cgroup namespace that still see the host's view in `/proc/self/cgroup`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn read_cgroup_limits(root: &Path, membership: &str) -> Option<CgroupLimits> {
+    let mut unified = None;
+    let mut v1 = CgroupLimits { version: 1, cpu_millis: None, memory: None };
+    let mut is_v1 = false;
+    for line in membership.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (Some(_), Some(controllers), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if controllers.is_empty() {
+            unified = Some(path);
+            continue;
+        }
+        for controller in controllers.split(',') {
+            match controller {
+                "cpu" => {
+                    is_v1 = true;
+                    let mount = [controllers, "cpu"].iter().map(|dir| root.join(dir)).find(|dir| dir.is_dir());
+                    v1.cpu_millis = mount.and_then(|mount| smallest_limit(&mount, path, read_cfs_quota));
+                }
+                "memory" => {
+                    is_v1 = true;
+                    v1.memory = smallest_limit(&root.join("memory"), path, |dir| {
+                        read_limit(&dir.join("memory.limit_in_bytes")).filter(|&bytes| bytes < UNLIMITED_V1)
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let limits = if is_v1 {
+        v1
+    } else {
+        let path = unified?;
+        CgroupLimits {
+            version: 2,
+            cpu_millis: smallest_limit(root, path, |dir| parse_cpu_max(&std::fs::read_to_string(dir.join("cpu.max")).ok()?)),
+            memory: smallest_limit(root, path, |dir| read_limit(&dir.join("memory.max"))),
+        }
+    };
+    (limits.cpu_millis.is_some() || limits.memory.is_some()).then_some(limits)
+}
+
+/// Smallest limit `read` finds in the cgroup at `path` or its ancestors
+fn smallest_limit(mount: &Path, path: &str, read: impl Fn(&Path) -> Option<u64>) -> Option<u64> {
+    Path::new(path)
+        .ancestors()
+        .map(|ancestor| mount.join(ancestor.strip_prefix("/").unwrap_or(ancestor)))
+        .filter(|dir| dir.is_dir())
+        .filter_map(|dir| read(&dir))
+        .min()
+}
+
+/// A byte limit; `max` and missing files mean none
+fn read_limit(file: &Path) -> Option<u64> {
+    std::fs::read_to_string(file).ok()?.trim().parse().ok()
+}
+
+/// cgroup v2 `cpu.max`: `$QUOTA $PERIOD`, or `max $PERIOD` without a quota
+fn parse_cpu_max(contents: &str) -> Option<u64> {
+    let mut fields = contents.split_whitespace();
+    let quota: u64 = fields.next()?.parse().ok()?;
+    let period: u64 = fields.next().map_or(Some(100_000), |period| period.parse().ok())?;
+    (period > 0).then(|| quota * 1000 / period)
+}
+
+/// cgroup v1 CFS quota; a quota of -1 means none
+fn read_cfs_quota(dir: &Path) -> Option<u64> {
+    let quota: i64 = std::fs::read_to_string(dir.join("cpu.cfs_quota_us")).ok()?.trim().parse().ok()?;
+    let period: u64 = read_limit(&dir.join("cpu.cfs_period_us"))?;
+    (quota > 0 && period > 0).then(|| quota as u64 * 1000 / period)
+}
+
+/// Total memory from `/proc/meminfo`
+#[cfg(target_os = "linux")]
+fn total_memory() -> Option<u64> {
+    parse_mem_total(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn total_memory() -> Option<u64> {
+    None
+}
+
+/// Bytes in the `MemTotal:` line of a meminfo file
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mem_total(meminfo: &str) -> Option<u64> {
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constrained_device_detection() {
+        let meminfo = "MemTotal:        3884376 kB\nMemFree:          170332 kB\n";
+        assert_eq!(parse_mem_total(meminfo), Some(3_977_601_024));
+
+        // Raspberry Pi 4 with 4 GB: constrained, and CPU-bound
+        let pi = DeviceResources { cpus: 4, memory: parse_mem_total(meminfo), arch: "aarch64", cgroup: None };
+        assert!(pi.is_constrained());
+        assert_eq!(pi.max_concurrency(), 200);
+
+        // A 256 MB board is held back by memory instead
+        let small = DeviceResources { cpus: 4, memory: Some(256 << 20), arch: "arm", cgroup: None };
+        assert_eq!(small.max_concurrency(), 128);
+        assert_eq!(small.constrained_profile().batch_size, Some(128));
+
+        // The same memory on a workstation CPU, or an unknown amount, is not small
+        assert!(!DeviceResources { arch: "x86_64", ..pi.clone() }.is_constrained());
+        assert!(!DeviceResources { memory: None, ..pi }.is_constrained());
+        assert!(DeviceResources { cpus: 16, memory: Some(2 << 30), arch: "x86_64", cgroup: None }.is_constrained());
+    }
+
+    #[test]
+    fn test_cgroup_limits() {
+        let root = std::env::temp_dir().join(format!("phobos-cgroup-{}", std::process::id()));
+        let write = |file: &str, contents: &str| {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+
+        // v2: a pod allows 1.5 CPUs and 1 GiB, its container asks for less memory
+        write("kubepods/pod1/cpu.max", "150000 100000\n");
+        write("kubepods/pod1/memory.max", "1073741824\n");
+        write("kubepods/pod1/app/cpu.max", "max 100000\n");
+        write("kubepods/pod1/app/memory.max", "536870912\n");
+        let v2 = read_cgroup_limits(&root, "0::/kubepods/pod1/app\n").unwrap();
+        assert_eq!(v2, CgroupLimits { version: 2, cpu_millis: Some(1500), memory: Some(512 << 20) });
+        assert_eq!(v2.cpus(), Some(2));
+
+        // v1 with a memory limit but no CPU quota
+        write("memory/docker/abc/memory.limit_in_bytes", "268435456\n");
+        write("cpu,cpuacct/docker/abc/cpu.cfs_quota_us", "-1\n");
+        write("cpu,cpuacct/docker/abc/cpu.cfs_period_us", "100000\n");
+        let membership = "4:memory:/docker/abc\n2:cpu,cpuacct:/docker/abc\n0::/\n";
+        let v1 = read_cgroup_limits(&root, membership).unwrap();
+        assert_eq!(v1, CgroupLimits { version: 1, cpu_millis: None, memory: Some(256 << 20) });
+
+        // A limited container gets a socket budget a bare host does not
+        let container = DeviceResources { cpus: 2, memory: v1.memory, arch: "x86_64", cgroup: Some(v1) };
+        assert_eq!(container.socket_budget(), Some(1024));
+        assert_eq!(DeviceResources { cgroup: None, ..container }.socket_budget(), None);
+
+        // Unlimited everywhere is no limit at all
+        write("memory/free/memory.limit_in_bytes", "9223372036854771712\n");
+        assert_eq!(read_cgroup_limits(&root, "4:memory:/free\n"), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+//! Import exposure data from Shodan and Censys
+//!
+//! Imported records can be used as a target list (confirm that what the
+//! search engine indexed is still reachable) or as a baseline to compare a
+//! scan against. API credentials come from the environment:
+//! `SHODAN_API_KEY`, and `CENSYS_API_ID` / `CENSYS_API_SECRET`.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::{Result, ScanError};
+
+const SHODAN_SEARCH_URL: &str = "https://api.shodan.io/shodan/host/search";
+const CENSYS_SEARCH_URL: &str = "https://search.censys.io/api/v2/hosts/search";
+
+/// Shodan returns 100 matches per page
+const SHODAN_PAGE_SIZE: usize = 100;
+
+/// Where a record came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExposureSource {
+    Shodan,
+    Censys,
+}
+
+impl fmt::Display for ExposureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExposureSource::Shodan => write!(f, "shodan"),
+            ExposureSource::Censys => write!(f, "censys"),
+        }
+    }
+}
+
+/// One host/port pair indexed by a search engine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExposureRecord {
+    pub ip: IpAddr,
+    pub port: u16,
+    /// `tcp` or `udp`
+    pub transport: String,
+    pub service: Option<String>,
+    pub source: ExposureSource,
+}
+
+impl ExposureRecord {
+    pub fn is_tcp(&self) -> bool {
+        self.transport.eq_ignore_ascii_case("tcp")
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent(concat!("phobos/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| ScanError::NetworkError(format!("Failed to build HTTP client: {}", e)))
+}
+
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| ScanError::ConfigError(format!("{} is not set", name)))
+}
+
+async fn get_json(request: reqwest::RequestBuilder, service: &str) -> Result<Value> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ScanError::NetworkError(format!("{} request failed: {}", service, e)))?;
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| ScanError::ParseError(format!("Invalid {} response: {}", service, e)))?;
+    if !status.is_success() {
+        let message = body["error"].as_str().or(body["error"]["message"].as_str()).unwrap_or("request rejected");
+        return Err(ScanError::NetworkError(format!("{} API returned {}: {}", service, status, message)));
+    }
+    Ok(body)
+}
+
+/// Shodan host search client
+pub struct ShodanClient {
+    api_key: String,
+    client: reqwest::Client,
+}
+
+impl ShodanClient {
+    pub fn new(api_key: impl Into<String>) -> Result<Self> {
+        Ok(Self { api_key: api_key.into(), client: http_client()? })
+    }
+
+    /// Client using `SHODAN_API_KEY`
+    pub fn from_env() -> Result<Self> {
+        Self::new(env_var("SHODAN_API_KEY")?)
+    }
+
+    /// Run `query`, returning at most `limit` records
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<ExposureRecord>> {
+        let mut records = Vec::new();
+        let mut page = 1;
+        while records.len() < limit {
+            let body = get_json(
+                self.client
+                    .get(SHODAN_SEARCH_URL)
+                    .query(&[("key", self.api_key.as_str()), ("query", query), ("page", &page.to_string())]),
+                "Shodan",
+            )
+            .await?;
+            let (batch, total) = parse_shodan(&body);
+            let fetched = batch.len();
+            records.extend(batch);
+            if fetched < SHODAN_PAGE_SIZE || page * SHODAN_PAGE_SIZE >= total {
+                break;
+            }
+            page += 1;
+        }
+        records.truncate(limit);
+        Ok(records)
+    }
+}
+
+/// Records and total match count from one Shodan search page
+pub fn parse_shodan(body: &Value) -> (Vec<ExposureRecord>, usize) {
+    let records = body["matches"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            Some(ExposureRecord {
+                ip: m["ip_str"].as_str()?.parse().ok()?,
+                port: u16::try_from(m["port"].as_u64()?).ok()?,
+                transport: m["transport"].as_str().unwrap_or("tcp").to_string(),
+                service: m["_shodan"]["module"].as_str().or(m["product"].as_str()).map(str::to_string),
+                source: ExposureSource::Shodan,
+            })
+        })
+        .collect();
+    (records, body["total"].as_u64().unwrap_or(0) as usize)
+}
+
+/// Censys Search v2 host client
+pub struct CensysClient {
+    api_id: String,
+    api_secret: String,
+    client: reqwest::Client,
+}
+
+impl CensysClient {
+    pub fn new(api_id: impl Into<String>, api_secret: impl Into<String>) -> Result<Self> {
+        Ok(Self { api_id: api_id.into(), api_secret: api_secret.into(), client: http_client()? })
+    }
+
+    /// Client using `CENSYS_API_ID` and `CENSYS_API_SECRET`
+    pub fn from_env() -> Result<Self> {
+        Self::new(env_var("CENSYS_API_ID")?, env_var("CENSYS_API_SECRET")?)
+    }
+
+    /// Run `query`, returning at most `limit` records
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<ExposureRecord>> {
+        let mut records = Vec::new();
+        let mut cursor: Option<String> = None;
+        while records.len() < limit {
+            let mut params = vec![("q", query.to_string()), ("per_page", "100".to_string())];
+            if let Some(cursor) = &cursor {
+                params.push(("cursor", cursor.clone()));
+            }
+            let body = get_json(
+                self.client.get(CENSYS_SEARCH_URL).basic_auth(&self.api_id, Some(&self.api_secret)).query(&params),
+                "Censys",
+            )
+            .await?;
+            let (batch, next) = parse_censys(&body);
+            records.extend(batch);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        records.truncate(limit);
+        Ok(records)
+    }
+}
+
+/// Records and next-page cursor from one Censys search page
+pub fn parse_censys(body: &Value) -> (Vec<ExposureRecord>, Option<String>) {
+    let mut records = Vec::new();
+    for hit in body["result"]["hits"].as_array().into_iter().flatten() {
+        let Some(ip) = hit["ip"].as_str().and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+            continue;
+        };
+        for service in hit["services"].as_array().into_iter().flatten() {
+            let Some(port) = service["port"].as_u64().and_then(|p| u16::try_from(p).ok()) else {
+                continue;
+            };
+            records.push(ExposureRecord {
+                ip,
+                port,
+                transport: service["transport_protocol"].as_str().unwrap_or("tcp").to_lowercase(),
+                service: service["service_name"].as_str().map(str::to_string),
+                source: ExposureSource::Censys,
+            });
+        }
+    }
+    let next = body["result"]["links"]["next"].as_str().filter(|c| !c.is_empty()).map(str::to_string);
+    (records, next)
+}
+
+/// Open TCP ports seen by the scan versus those indexed externally
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExposureDiff {
+    /// Reported open by both sides
+    pub both: Vec<SocketAddr>,
+    /// Open in our scan but not indexed
+    pub only_we_see: Vec<SocketAddr>,
+    /// Indexed but not found open by our scan
+    pub only_they_see: Vec<SocketAddr>,
+}
+
+impl ExposureDiff {
+    /// Compare scan results with imported TCP records for the same hosts
+    pub fn compute(observed: &[SocketAddr], imported: &[ExposureRecord]) -> Self {
+        let ours: BTreeSet<SocketAddr> = observed.iter().copied().collect();
+        let theirs: BTreeSet<SocketAddr> = imported
+            .iter()
+            .filter(|r| r.is_tcp())
+            .map(|r| SocketAddr::new(r.ip, r.port))
+            .collect();
+
+        Self {
+            both: ours.intersection(&theirs).copied().collect(),
+            only_we_see: ours.difference(&theirs).copied().collect(),
+            only_they_see: theirs.difference(&ours).copied().collect(),
+        }
+    }
+}
+
+/// Check whether each imported TCP record still accepts connections
+pub async fn verify_exposure(
+    records: &[ExposureRecord],
+    connect_timeout: Duration,
+    concurrency: usize,
+) -> Vec<(ExposureRecord, bool)> {
+    stream::iter(records.iter().filter(|r| r.is_tcp()).cloned())
+        .map(|record| async move {
+            let addr = SocketAddr::new(record.ip, record.port);
+            let open = matches!(timeout(connect_timeout, TcpStream::connect(addr)).await, Ok(Ok(_)));
+            (record, open)
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_shodan_page() {
+        let body = json!({
+            "total": 2,
+            "matches": [
+                {"ip_str": "192.0.2.10", "port": 443, "transport": "tcp", "_shodan": {"module": "https"}},
+                {"ip_str": "2001:db8::1", "port": 53, "transport": "udp", "product": "BIND"},
+                {"ip_str": "not-an-ip", "port": 80}
+            ]
+        });
+        let (records, total) = parse_shodan(&body);
+        assert_eq!(total, 2);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].service.as_deref(), Some("https"));
+        assert!(!records[1].is_tcp());
+    }
+
+    #[test]
+    fn test_parse_censys_page() {
+        let body = json!({
+            "result": {
+                "hits": [{
+                    "ip": "198.51.100.7",
+                    "services": [
+                        {"port": 22, "service_name": "SSH", "transport_protocol": "TCP"},
+                        {"port": 8080, "service_name": "HTTP", "transport_protocol": "TCP"}
+                    ]
+                }],
+                "links": {"next": "abc", "prev": ""}
+            }
+        });
+        let (records, next) = parse_censys(&body);
+        assert_eq!(records.iter().map(|r| r.port).collect::<Vec<_>>(), vec![22, 8080]);
+        assert!(records.iter().all(|r| r.is_tcp() && r.source == ExposureSource::Censys));
+        assert_eq!(next.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_exposure_diff() {
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let record = |port| ExposureRecord {
+            ip,
+            port,
+            transport: "tcp".to_string(),
+            service: None,
+            source: ExposureSource::Shodan,
+        };
+        let observed = [SocketAddr::new(ip, 22), SocketAddr::new(ip, 80)];
+        let diff = ExposureDiff::compute(&observed, &[record(80), record(3389)]);
+
+        assert_eq!(diff.both, vec![SocketAddr::new(ip, 80)]);
+        assert_eq!(diff.only_we_see, vec![SocketAddr::new(ip, 22)]);
+        assert_eq!(diff.only_they_see, vec![SocketAddr::new(ip, 3389)]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_exposure() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap()
+        };
+        let record = |addr: SocketAddr| ExposureRecord {
+            ip: addr.ip(),
+            port: addr.port(),
+            transport: "tcp".to_string(),
+            service: None,
+            source: ExposureSource::Censys,
+        };
+
+        let results = verify_exposure(&[record(open), record(closed)], Duration::from_secs(1), 2).await;
+        assert_eq!(results.iter().map(|(_, open)| *open).collect::<Vec<_>>(), vec![true, false]);
+    }
+}
@@ -5,6 +5,9 @@
 //! - CSV files with target information
 //! - JSON files with structured target data
 //! - Nmap XML output files for target extraction
+//! - Bug bounty scope exports (Burp Suite target scope, HackerOne structured
+//!   scopes as JSON or CSV): in-scope assets become targets, out-of-scope
+//!   addresses become exclusions
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
@@ -28,6 +31,8 @@ pub enum FileFormat {
     Csv,
     Json,
     NmapXml,
+    BurpScope,
+    HackerOneScope,
     Auto, // Auto-detect format
 }
 
@@ -40,6 +45,25 @@ pub struct FileTarget {
     pub tags: Option<Vec<String>>,
 }
 
+/// Targets and exclusions taken from a bounty scope export
+#[derive(Debug, Clone, Default)]
+pub struct ScopeImport {
+    pub targets: Vec<ParsedTarget>,
+    /// Out-of-scope IPs and CIDRs, in the form accepted by `--exclude-ips`
+    pub exclusions: Vec<String>,
+    /// In-scope wildcards; only their apex domain is scanned
+    pub wildcards: Vec<String>,
+    /// Entries that are not network assets (apps, source code, ...) or could not be parsed
+    pub skipped: Vec<String>,
+}
+
+/// One scope line before normalization
+#[derive(Debug, Clone)]
+struct ScopeEntry {
+    value: String,
+    in_scope: bool,
+}
+
 /// File input statistics
 #[derive(Debug, Clone)]
 pub struct FileInputStats {
@@ -93,6 +117,10 @@ impl FileInputHandler {
             FileFormat::Csv => self.read_csv(file),
             FileFormat::Json => self.read_json(file),
             FileFormat::NmapXml => self.read_nmap_xml(file),
+            FileFormat::BurpScope | FileFormat::HackerOneScope => {
+                let (scope, stats) = self.read_scope_with_format(file, format)?;
+                Ok((scope.targets, stats))
+            }
             FileFormat::Auto => {
                 let detected_format = self.detect_file_format(&file_path)?;
                 self.read_targets_with_format(file_path, detected_format)
@@ -104,24 +132,41 @@ impl FileInputHandler {
     fn detect_file_format<P: AsRef<Path>>(&self, file_path: P) -> Result<FileFormat> {
         let path = file_path.as_ref();
         
-        // Check file extension first
-        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
-            match extension.to_lowercase().as_str() {
-                "txt" | "list" => return Ok(FileFormat::PlainText),
-                "csv" => return Ok(FileFormat::Csv),
-                "json" => return Ok(FileFormat::Json),
-                "xml" => return Ok(FileFormat::NmapXml),
-                _ => {}
-            }
-        }
-
         // Try to detect by content
         let mut file = File::open(path)?;
         let mut buffer = [0; 1024];
         let bytes_read = file.read(&mut buffer)?;
         let content = String::from_utf8_lossy(&buffer[..bytes_read]);
 
-        if content.trim_start().starts_with('{') || content.trim_start().starts_with('[') {
+        // Scope exports share extensions with the generic formats, so sniff them first
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+        if matches!(extension.as_deref(), Some("json" | "csv")) {
+            let sniffed = self.detect_by_content(&content)?;
+            if matches!(sniffed, FileFormat::HackerOneScope | FileFormat::BurpScope) {
+                return Ok(sniffed);
+            }
+        }
+
+        // Check file extension first
+        match extension.as_deref() {
+            Some("txt" | "list") => return Ok(FileFormat::PlainText),
+            Some("csv") => return Ok(FileFormat::Csv),
+            Some("json") => return Ok(FileFormat::Json),
+            Some("xml") => return Ok(FileFormat::NmapXml),
+            _ => {}
+        }
+
+        self.detect_by_content(&content)
+    }
+
+    fn detect_by_content(&self, content: &str) -> Result<FileFormat> {
+        if content.contains("structured-scope") || content.contains("asset_identifier")
+            || content.starts_with("identifier,asset_type")
+        {
+            Ok(FileFormat::HackerOneScope)
+        } else if content.contains("\"scope\"") && (content.contains("\"include\"") || content.contains("\"exclude\"")) {
+            Ok(FileFormat::BurpScope)
+        } else if content.trim_start().starts_with('{') || content.trim_start().starts_with('[') {
             Ok(FileFormat::Json)
         } else if content.contains("<?xml") || content.contains("<nmaprun") {
             Ok(FileFormat::NmapXml)
@@ -349,6 +394,192 @@ impl FileInputHandler {
         None
     }
 
+    /// Read a bounty scope export with auto-detected format
+    pub fn read_scope_from_file<P: AsRef<Path>>(&self, file_path: P) -> Result<(ScopeImport, FileInputStats)> {
+        let format = self.detect_file_format(&file_path)?;
+        if !matches!(format, FileFormat::BurpScope | FileFormat::HackerOneScope) {
+            return Err(anyhow::anyhow!("Not a Burp or HackerOne scope file: {:?}", file_path.as_ref()));
+        }
+        let file = File::open(&file_path)
+            .with_context(|| format!("Failed to open file: {:?}", file_path.as_ref()))?;
+        self.read_scope_with_format(file, format)
+    }
+
+    fn read_scope_with_format(&self, mut file: File, format: FileFormat) -> Result<(ScopeImport, FileInputStats)> {
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let entries = match format {
+            FileFormat::BurpScope => Self::parse_burp_scope(&content)?,
+            _ if content.trim_start().starts_with('{') => Self::parse_hackerone_json(&content)?,
+            _ => Self::parse_hackerone_csv(&content)?,
+        };
+        Ok(self.build_scope(entries, format))
+    }
+
+    /// Burp Suite `target.scope` export, in simple (`prefix`) or advanced (`host` regex) mode
+    fn parse_burp_scope(content: &str) -> Result<Vec<ScopeEntry>> {
+        let root: serde_json::Value = serde_json::from_str(content).context("Failed to parse Burp scope JSON")?;
+        let scope = &root["target"]["scope"];
+
+        let mut entries = Vec::new();
+        for (key, in_scope) in [("include", true), ("exclude", false)] {
+            for rule in scope[key].as_array().into_iter().flatten() {
+                if rule["enabled"].as_bool() == Some(false) {
+                    continue;
+                }
+                let value = rule["host"].as_str().or(rule["prefix"].as_str()).unwrap_or_default();
+                if !value.is_empty() {
+                    entries.push(ScopeEntry { value: value.to_string(), in_scope });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// HackerOne API `structured_scopes` response (`data[].attributes`)
+    fn parse_hackerone_json(content: &str) -> Result<Vec<ScopeEntry>> {
+        let root: serde_json::Value = serde_json::from_str(content).context("Failed to parse HackerOne scope JSON")?;
+        let items = root["data"].as_array().or(root["relationships"]["structured_scopes"]["data"].as_array());
+
+        Ok(items
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                let attributes = &item["attributes"];
+                let value = attributes["asset_identifier"].as_str()?;
+                let asset_type = attributes["asset_type"].as_str().unwrap_or("URL");
+                Some(ScopeEntry {
+                    value: Self::typed_value(value, asset_type),
+                    in_scope: attributes["eligible_for_submission"].as_bool().unwrap_or(true),
+                })
+            })
+            .collect())
+    }
+
+    /// HackerOne "Download CSV" scope export
+    fn parse_hackerone_csv(content: &str) -> Result<Vec<ScopeEntry>> {
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|h| h == name);
+        let identifier = column("identifier").context("HackerOne scope CSV has no identifier column")?;
+        let asset_type = column("asset_type");
+        let eligible = column("eligible_for_submission");
+
+        let mut entries = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let value = record.get(identifier).unwrap_or_default();
+            let kind = asset_type.and_then(|i| record.get(i)).unwrap_or("URL");
+            let in_scope = eligible.and_then(|i| record.get(i)).map_or(true, |v| v.eq_ignore_ascii_case("true"));
+            entries.push(ScopeEntry { value: Self::typed_value(value, kind), in_scope });
+        }
+        Ok(entries)
+    }
+
+    /// Tag non-network asset types so they end up in `skipped`
+    fn typed_value(value: &str, asset_type: &str) -> String {
+        match asset_type.to_uppercase().as_str() {
+            "URL" | "WILDCARD" | "DOMAIN" | "CIDR" | "IP_ADDRESS" | "OTHER_IPV4" | "OTHER_IPV6" => value.to_string(),
+            other => format!("{}:{}", other.to_lowercase(), value),
+        }
+    }
+
+    /// Reduce a scope value to a scannable host, CIDR or wildcard
+    fn normalize_scope_value(value: &str) -> Option<String> {
+        let mut host = value.trim();
+
+        // Burp advanced mode host regex, e.g. ^.*\.example\.com$
+        let unescaped;
+        if host.starts_with('^') || host.ends_with('$') || host.contains("\\.") {
+            unescaped = host
+                .trim_start_matches('^')
+                .trim_end_matches('$')
+                .replacen(".*\\.", "*.", 1)
+                .replace("\\.", ".");
+            host = &unescaped;
+        }
+
+        // URLs and Burp prefixes: keep the host part
+        if let Some((_, rest)) = host.split_once("://") {
+            host = rest;
+        }
+        let host = match host.split_once('/') {
+            Some((_, prefix)) if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_digit()) => host,
+            _ => host.split(['/', '?', '#']).next().unwrap_or_default(),
+        };
+        // Strip a port unless this is a bare IPv6 address or a CIDR
+        let host = match host.rsplit_once(':') {
+            Some((name, port)) if !name.contains(':') && port.chars().all(|c| c.is_ascii_digit()) => name,
+            _ => host,
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let valid = !host.is_empty()
+            && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*' | ':' | '/'));
+        valid.then(|| host.to_lowercase())
+    }
+
+    fn build_scope(&self, entries: Vec<ScopeEntry>, format: FileFormat) -> (ScopeImport, FileInputStats) {
+        let mut scope = ScopeImport::default();
+        let mut stats = FileInputStats {
+            total_lines: entries.len(),
+            valid_targets: 0,
+            invalid_targets: 0,
+            duplicates_removed: 0,
+            file_format: format,
+        };
+
+        let mut excluded_hosts = HashSet::new();
+        for entry in entries.iter().filter(|e| !e.in_scope) {
+            match Self::normalize_scope_value(&entry.value) {
+                Some(value) if value.parse::<std::net::IpAddr>().is_ok() || value.contains('/') => {
+                    scope.exclusions.push(value);
+                }
+                Some(value) => {
+                    excluded_hosts.insert(value);
+                }
+                None => scope.skipped.push(entry.value.clone()),
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for entry in entries.iter().filter(|e| e.in_scope) {
+            if scope.targets.len() >= self.max_targets {
+                break;
+            }
+            let Some(mut value) = Self::normalize_scope_value(&entry.value) else {
+                scope.skipped.push(entry.value.clone());
+                continue;
+            };
+            if let Some(apex) = value.strip_prefix("*.") {
+                scope.wildcards.push(value.clone());
+                value = apex.to_string();
+            }
+            if value.contains('*') || excluded_hosts.contains(&value) {
+                scope.skipped.push(entry.value.clone());
+                continue;
+            }
+            if self.deduplicate && !seen.insert(value.clone()) {
+                stats.duplicates_removed += 1;
+                continue;
+            }
+
+            match self.parser.parse_target(&value) {
+                Ok(parsed_target) => {
+                    scope.targets.push(parsed_target);
+                    stats.valid_targets += 1;
+                }
+                Err(_) => {
+                    stats.invalid_targets += 1;
+                    eprintln!("Warning: Invalid target format: {}", value);
+                }
+            }
+        }
+
+        (scope, stats)
+    }
+
     /// Validate file before processing
     pub fn validate_file<P: AsRef<Path>>(&self, file_path: P) -> Result<()> {
         let path = file_path.as_ref();
@@ -386,13 +617,37 @@ pub fn targets_from_file<P: AsRef<Path>>(
     file_path: P,
     max_targets: Option<usize>,
 ) -> Result<Vec<ParsedTarget>> {
+    targets_and_exclusions_from_file(file_path, max_targets).map(|(targets, _)| targets)
+}
+
+/// Like [`targets_from_file`], but also returns the out-of-scope addresses
+/// of a bounty scope export so they can be excluded from the scan
+pub fn targets_and_exclusions_from_file<P: AsRef<Path>>(
+    file_path: P,
+    max_targets: Option<usize>,
+) -> Result<(Vec<ParsedTarget>, Vec<String>)> {
     let handler = FileInputHandler::new(
         max_targets.unwrap_or(10000),
         true, // deduplicate by default
     );
     
     handler.validate_file(&file_path)?;
-    let (targets, stats) = handler.read_targets_from_file(file_path)?;
+    let (targets, exclusions, stats) = match handler.detect_file_format(&file_path)? {
+        FileFormat::BurpScope | FileFormat::HackerOneScope => {
+            let (scope, stats) = handler.read_scope_from_file(&file_path)?;
+            if !scope.wildcards.is_empty() {
+                println!("  Wildcards (apex only): {}", scope.wildcards.join(", "));
+            }
+            if !scope.skipped.is_empty() {
+                println!("  Skipped non-network assets: {}", scope.skipped.len());
+            }
+            (scope.targets, scope.exclusions, stats)
+        }
+        _ => {
+            let (targets, stats) = handler.read_targets_from_file(&file_path)?;
+            (targets, Vec::new(), stats)
+        }
+    };
     
     println!("File input statistics:");
     println!("  Format: {:?}", stats.file_format);
@@ -400,8 +655,11 @@ pub fn targets_from_file<P: AsRef<Path>>(
     println!("  Valid targets: {}", stats.valid_targets);
     println!("  Invalid targets: {}", stats.invalid_targets);
     println!("  Duplicates removed: {}", stats.duplicates_removed);
+    if !exclusions.is_empty() {
+        println!("  Out-of-scope exclusions: {}", exclusions.len());
+    }
     
-    Ok(targets)
+    Ok((targets, exclusions))
 }
 
 #[cfg(test)]
@@ -444,6 +702,50 @@ mod tests {
         assert_eq!(targets.len(), 2);
     }
 
+    #[test]
+    fn test_burp_scope_import() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let burp = r#"{"target": {"scope": {
+            "advanced_mode": true,
+            "include": [
+                {"enabled": true, "host": "^10\\.0\\.0\\.5$", "port": "^443$", "protocol": "https"},
+                {"enabled": true, "host": "^.*\\.example\\.test$", "protocol": "any"},
+                {"enabled": false, "host": "^192\\.0\\.2\\.9$"}
+            ],
+            "exclude": [
+                {"enabled": true, "host": "^10\\.0\\.0\\.6$"}
+            ]
+        }}}"#;
+        write!(temp_file, "{}", burp).unwrap();
+
+        let handler = FileInputHandler::new(1000, true);
+        let (scope, stats) = handler.read_scope_from_file(temp_file.path()).unwrap();
+
+        assert_eq!(stats.file_format, FileFormat::BurpScope);
+        assert_eq!(scope.targets[0].original, "10.0.0.5");
+        assert_eq!(scope.wildcards, vec!["*.example.test"]);
+        assert_eq!(scope.exclusions, vec!["10.0.0.6"]);
+    }
+
+    #[test]
+    fn test_hackerone_scope_import() {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "identifier,asset_type,instruction,eligible_for_bounty,eligible_for_submission").unwrap();
+        writeln!(temp_file, "https://198.51.100.4:8443/app,URL,,true,true").unwrap();
+        writeln!(temp_file, "203.0.113.0/30,CIDR,,true,true").unwrap();
+        writeln!(temp_file, "203.0.113.2,IP_ADDRESS,,false,false").unwrap();
+        writeln!(temp_file, "com.example.app,GOOGLE_PLAY_APP_ID,,true,true").unwrap();
+
+        let handler = FileInputHandler::new(1000, true);
+        let (scope, stats) = handler.read_scope_from_file(temp_file.path()).unwrap();
+
+        assert_eq!(stats.file_format, FileFormat::HackerOneScope);
+        let targets: Vec<&str> = scope.targets.iter().map(|t| t.original.as_str()).collect();
+        assert_eq!(targets, vec!["198.51.100.4", "203.0.113.0/30"]);
+        assert_eq!(scope.exclusions, vec!["203.0.113.2"]);
+        assert_eq!(scope.skipped, vec!["google_play_app_id:com.example.app"]);
+    }
+
     #[test]
     fn test_duplicate_removal() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -0,0 +1,962 @@
+//! Process hardening behind `--harden`
+//!
+//! Once the engine holds its sockets, a scan needs far less of the machine
+//! than the process was started with. Hardening gives the rest up, so that a
+//! bug in a banner parser or a hostile script cannot easily be turned into
+//! control of the operator's host.
+//!
+//! On Linux every thread of the process, and every program it runs:
+//!
+//! - gets no new privileges: setuid binaries and file capabilities no longer
+//!   raise them
+//! - continues as the user who ran sudo, or the one given with
+//!   `--harden-user`, keeping only CAP_NET_RAW and CAP_NET_ADMIN for the
+//!   raw sockets
+//! - may only write below the output paths, the temporary directory and
+//!   `~/.phobos` (Landlock, when the kernel has it)
+//! - may only make the syscalls the runtime, the scan and its outputs need
+//!   (seccomp); any other, module loading, mounts, namespaces, ptrace, BPF
+//!   and uid changes among them, fails with EPERM. `clone` may not create
+//!   namespaces, `clone3` fails with ENOSYS so libc falls back to `clone`,
+//!   terminal input cannot be injected with TIOCSTI, `kill` only reaches
+//!   single processes and `prctl` only reads state or adds restrictions
+//!
+//! On OpenBSD the same paths are unveiled and the process pledges to stdio,
+//! file, network and process promises.
+//!
+//! [`HardenLevel::Strict`] also refuses to run other programs, which leaves
+//! out Nmap, hooks, screenshots and anything else that spawns one.
+
+use crate::ScanError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How much the process gives up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HardenLevel {
+    /// Everything short of running other programs
+    #[default]
+    Standard,
+    /// Standard, and no program can be run
+    Strict,
+}
+
+impl FromStr for HardenLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "standard" => Ok(HardenLevel::Standard),
+            "strict" => Ok(HardenLevel::Strict),
+            other => Err(format!("unknown hardening level '{}' (expected standard or strict)", other)),
+        }
+    }
+}
+
+impl fmt::Display for HardenLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HardenLevel::Standard => "standard",
+            HardenLevel::Strict => "strict",
+        })
+    }
+}
+
+/// A hardening level, the paths the scan may still write to and the user it
+/// continues as
+#[derive(Debug, Clone)]
+pub struct Hardening {
+    level: HardenLevel,
+    writable: Vec<PathBuf>,
+    user: Option<String>,
+}
+
+impl Hardening {
+    /// Writes allowed to the temporary directory and `~/.phobos`, where
+    /// checkpoints, profiles and learned timings live
+    pub fn new(level: HardenLevel) -> Self {
+        let mut writable = vec![std::env::temp_dir()];
+        writable.extend(dirs::home_dir().map(|home| home.join(".phobos")));
+        Self { level, writable, user: None }
+    }
+
+    pub fn level(&self) -> HardenLevel {
+        self.level
+    }
+
+    /// Let the scan create or replace `path`; its whole directory stays
+    /// writable, for temporary files and database journals next to it
+    pub fn output_file(mut self, path: impl AsRef<Path>) -> Self {
+        let dir = path.as_ref().parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        self.writable.push(dir.to_path_buf());
+        self
+    }
+
+    /// Let the scan write anywhere below `dir`
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.writable.push(dir.into());
+        self
+    }
+
+    /// Continue as `name` rather than as the user who ran sudo
+    pub fn user(mut self, name: impl Into<String>) -> Self {
+        self.user = Some(name.into());
+        self
+    }
+
+    /// Harden the whole process: the threads running now and those started
+    /// later
+    ///
+    /// Returns the measures taken. One the kernel does not offer is left out
+    /// rather than failing; one that fails is an error, as the operator asked
+    /// for it.
+    pub fn apply(&self) -> crate::Result<Vec<&'static str>> {
+        #[cfg(target_os = "linux")]
+        return linux::apply(self);
+        #[cfg(target_os = "openbsd")]
+        return openbsd::apply(self);
+        #[cfg(not(any(target_os = "linux", target_os = "openbsd")))]
+        return Err(ScanError::ConfigError("--harden is only supported on Linux and OpenBSD".to_string()));
+    }
+
+    /// The uid and gid to continue as: the configured user's, else the ones
+    /// sudo recorded. None when that is who the process already runs as
+    #[cfg(any(target_os = "linux", target_os = "openbsd"))]
+    fn target_ids(&self) -> crate::Result<Option<(libc::uid_t, libc::gid_t)>> {
+        let ids = match &self.user {
+            Some(name) => Some(lookup_user(name)?),
+            None => {
+                let id = |var| std::env::var(var).ok()?.parse().ok();
+                id("SUDO_UID").zip(id("SUDO_GID"))
+            }
+        };
+        // SAFETY: geteuid cannot fail
+        Ok(ids.filter(|&(uid, _)| uid != unsafe { libc::geteuid() }))
+    }
+
+    /// The writable paths as they exist now: a path not created yet stands
+    /// for its nearest existing ancestor
+    fn existing_writable(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .writable
+            .iter()
+            .filter_map(|path| path.ancestors().find(|ancestor| ancestor.exists()).map(Path::to_path_buf))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "openbsd"))]
+fn lookup_user(name: &str) -> crate::Result<(libc::uid_t, libc::gid_t)> {
+    let unknown = || ScanError::ConfigError(format!("Unknown user '{}' for --harden-user", name));
+    let c_name = std::ffi::CString::new(name).map_err(|_| unknown())?;
+    // SAFETY: a zeroed passwd is valid; getpwnam_r fills it from `buffer`,
+    // which outlives the fields read below
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    let mut found = std::ptr::null_mut();
+    let result = unsafe { libc::getpwnam_r(c_name.as_ptr(), &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut found) };
+    if result != 0 || found.is_null() {
+        return Err(unknown());
+    }
+    Ok((entry.pw_uid, entry.pw_gid))
+}
+
+fn os_error(what: &str) -> ScanError {
+    ScanError::PermissionError(format!("Cannot harden the process ({}): {}", what, std::io::Error::last_os_error()))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{os_error, HardenLevel, Hardening};
+    use crate::network::bpf::BpfInstruction;
+    use crate::ScanError;
+    use std::collections::HashSet;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicI32, AtomicPtr, AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    const CAP_NET_ADMIN: u32 = 12;
+    const CAP_NET_RAW: u32 = 13;
+    /// Raw and packet sockets, and binding them to an interface
+    const KEPT_CAPABILITIES: u64 = 1 << CAP_NET_ADMIN | 1 << CAP_NET_RAW;
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: i32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+    const FS_WRITE_FILE: u64 = 1 << 1;
+    /// Writing files and creating or removing anything, Landlock ABI 1
+    const FS_WRITES: u64 = FS_WRITE_FILE | 0b1_1111_1111 << 4;
+    /// Renaming and linking across directories, ABI 2
+    const FS_REFER: u64 = 1 << 13;
+    /// Truncating files, ABI 3
+    const FS_TRUNCATE: u64 = 1 << 14;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+    const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+    /// Syscall numbers of the x32 ABI, which would get around a filter
+    /// written for x86_64 numbers
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    const BPF_JGE_K: u16 = 0x35;
+    const BPF_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+
+    /// Low word of a syscall argument, on the little-endian targets filtered
+    const fn seccomp_data_arg(index: u32) -> u32 {
+        16 + 8 * index
+    }
+
+    /// Flags that make `clone` create namespaces
+    const CLONE_NEW_FLAGS: u32 = (libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET) as u32
+        | 0x80; // CLONE_NEWTIME
+    /// `ioctl`s that push input into a terminal, which a process sharing the
+    /// operator's TTY could use to type commands into their shell
+    const TERMINAL_INJECTION: &[u32] = &[libc::TIOCSTI as u32, 0x541c]; // TIOCLINUX
+    /// `prctl` options that only read state, name threads or add
+    /// restrictions; changing dumpability, the ptracer, capabilities or the
+    /// memory map is not among them
+    const ALLOWED_PRCTL: &[libc::c_int] = &[
+        libc::PR_SET_PDEATHSIG,
+        libc::PR_GET_PDEATHSIG,
+        libc::PR_GET_DUMPABLE,
+        libc::PR_GET_KEEPCAPS,
+        libc::PR_SET_NAME,
+        libc::PR_GET_NAME,
+        libc::PR_GET_SECCOMP,
+        libc::PR_SET_SECCOMP,
+        libc::PR_CAPBSET_READ,
+        libc::PR_GET_TIMERSLACK,
+        libc::PR_SET_NO_NEW_PRIVS,
+        libc::PR_GET_NO_NEW_PRIVS,
+        libc::PR_GET_THP_DISABLE,
+        libc::PR_SET_VMA,
+    ];
+
+    /// Syscalls the runtime, the scan and its outputs make, and programs the
+    /// scan runs with them: files, memory, threads, signals, polling and
+    /// sockets. Module loading, mounts, namespaces, ptrace, kexec, BPF,
+    /// io_uring, uid changes and clock or hostname changes are not among them
+    const ALLOWED_SYSCALLS: &[libc::c_long] = &[
+        // Files
+        libc::SYS_read,
+        libc::SYS_write,
+        libc::SYS_readv,
+        libc::SYS_writev,
+        libc::SYS_pread64,
+        libc::SYS_pwrite64,
+        libc::SYS_preadv,
+        libc::SYS_pwritev,
+        libc::SYS_close,
+        libc::SYS_close_range,
+        libc::SYS_lseek,
+        libc::SYS_openat,
+        libc::SYS_newfstatat,
+        libc::SYS_fstat,
+        libc::SYS_statx,
+        libc::SYS_statfs,
+        libc::SYS_fstatfs,
+        libc::SYS_getdents64,
+        libc::SYS_fcntl,
+        libc::SYS_dup,
+        libc::SYS_dup3,
+        libc::SYS_pipe2,
+        libc::SYS_mkdirat,
+        libc::SYS_unlinkat,
+        libc::SYS_renameat,
+        libc::SYS_renameat2,
+        libc::SYS_linkat,
+        libc::SYS_symlinkat,
+        libc::SYS_readlinkat,
+        libc::SYS_faccessat,
+        libc::SYS_faccessat2,
+        libc::SYS_fchmod,
+        libc::SYS_fchmodat,
+        libc::SYS_fchown,
+        libc::SYS_fchownat,
+        libc::SYS_ftruncate,
+        libc::SYS_truncate,
+        libc::SYS_fsync,
+        libc::SYS_fdatasync,
+        libc::SYS_msync,
+        libc::SYS_flock,
+        libc::SYS_utimensat,
+        libc::SYS_getcwd,
+        libc::SYS_chdir,
+        libc::SYS_fchdir,
+        libc::SYS_umask,
+        libc::SYS_fallocate,
+        libc::SYS_copy_file_range,
+        libc::SYS_splice,
+        // Memory
+        libc::SYS_mmap,
+        libc::SYS_munmap,
+        libc::SYS_mprotect,
+        libc::SYS_mremap,
+        libc::SYS_madvise,
+        libc::SYS_brk,
+        libc::SYS_membarrier,
+        // Threads and processes
+        libc::SYS_futex,
+        libc::SYS_set_robust_list,
+        libc::SYS_get_robust_list,
+        libc::SYS_set_tid_address,
+        libc::SYS_rseq,
+        libc::SYS_sched_yield,
+        libc::SYS_sched_getaffinity,
+        libc::SYS_gettid,
+        libc::SYS_getpid,
+        libc::SYS_getppid,
+        libc::SYS_getpgid,
+        libc::SYS_setpgid,
+        libc::SYS_setsid,
+        libc::SYS_getsid,
+        libc::SYS_tgkill,
+        libc::SYS_tkill,
+        libc::SYS_exit,
+        libc::SYS_exit_group,
+        libc::SYS_wait4,
+        libc::SYS_waitid,
+        libc::SYS_pidfd_open,
+        libc::SYS_getuid,
+        libc::SYS_geteuid,
+        libc::SYS_getgid,
+        libc::SYS_getegid,
+        libc::SYS_getgroups,
+        libc::SYS_getresuid,
+        libc::SYS_getresgid,
+        libc::SYS_capget,
+        libc::SYS_uname,
+        libc::SYS_sysinfo,
+        libc::SYS_getrandom,
+        libc::SYS_getrlimit,
+        libc::SYS_setrlimit,
+        libc::SYS_prlimit64,
+        libc::SYS_getrusage,
+        libc::SYS_times,
+        libc::SYS_getpriority,
+        // Time and signals
+        libc::SYS_nanosleep,
+        libc::SYS_clock_nanosleep,
+        libc::SYS_clock_gettime,
+        libc::SYS_clock_getres,
+        libc::SYS_gettimeofday,
+        libc::SYS_restart_syscall,
+        libc::SYS_rt_sigaction,
+        libc::SYS_rt_sigprocmask,
+        libc::SYS_rt_sigreturn,
+        libc::SYS_rt_sigtimedwait,
+        libc::SYS_rt_sigsuspend,
+        libc::SYS_sigaltstack,
+        // Polling
+        libc::SYS_epoll_create1,
+        libc::SYS_epoll_ctl,
+        libc::SYS_epoll_pwait,
+        libc::SYS_epoll_pwait2,
+        libc::SYS_eventfd2,
+        libc::SYS_ppoll,
+        libc::SYS_pselect6,
+        libc::SYS_timerfd_create,
+        libc::SYS_timerfd_settime,
+        libc::SYS_timerfd_gettime,
+        libc::SYS_signalfd4,
+        // Sockets, raw and packet ones included
+        libc::SYS_socket,
+        libc::SYS_socketpair,
+        libc::SYS_bind,
+        libc::SYS_listen,
+        libc::SYS_accept,
+        libc::SYS_accept4,
+        libc::SYS_connect,
+        libc::SYS_getsockname,
+        libc::SYS_getpeername,
+        libc::SYS_sendto,
+        libc::SYS_recvfrom,
+        libc::SYS_sendmsg,
+        libc::SYS_recvmsg,
+        libc::SYS_sendmmsg,
+        libc::SYS_recvmmsg,
+        libc::SYS_shutdown,
+        libc::SYS_setsockopt,
+        libc::SYS_getsockopt,
+    ];
+
+    /// The older x86_64 syscalls that aarch64 only has as *at and p* forms
+    #[cfg(target_arch = "x86_64")]
+    const LEGACY_SYSCALLS: &[libc::c_long] = &[
+        libc::SYS_open,
+        libc::SYS_stat,
+        libc::SYS_lstat,
+        libc::SYS_access,
+        libc::SYS_poll,
+        libc::SYS_select,
+        libc::SYS_pipe,
+        libc::SYS_dup2,
+        libc::SYS_getdents,
+        libc::SYS_rename,
+        libc::SYS_mkdir,
+        libc::SYS_rmdir,
+        libc::SYS_unlink,
+        libc::SYS_readlink,
+        libc::SYS_chmod,
+        libc::SYS_epoll_create,
+        libc::SYS_epoll_wait,
+        libc::SYS_fadvise64,
+        libc::SYS_sendfile,
+        libc::SYS_arch_prctl,
+        libc::SYS_fork,
+        libc::SYS_vfork,
+        libc::SYS_time,
+        libc::SYS_getpgrp,
+    ];
+    #[cfg(not(target_arch = "x86_64"))]
+    const LEGACY_SYSCALLS: &[libc::c_long] = &[];
+
+    /// Syscalls allowed for some arguments only, each with the checks that
+    /// run once its number matched; every check ends in a verdict
+    fn checked_syscalls(allow: BpfInstruction, deny: BpfInstruction) -> Vec<(libc::c_long, Vec<BpfInstruction>)> {
+        let stmt = |code, k| BpfInstruction { code, jt: 0, jf: 0, k };
+        let jump = |code, k, jt, jf| BpfInstruction { code, jt, jf, k };
+
+        let clone = vec![
+            stmt(BPF_LD_W_ABS, seccomp_data_arg(0)),
+            jump(BPF_JSET_K, CLONE_NEW_FLAGS, 0, 1),
+            deny,
+            allow,
+        ];
+        // clone3 passes its flags in memory the filter cannot read; ENOSYS
+        // makes libc fall back to clone, where they are checked
+        let clone3 = vec![stmt(BPF_RET_K, libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32)];
+
+        let mut ioctl = vec![stmt(BPF_LD_W_ABS, seccomp_data_arg(1))];
+        for &request in TERMINAL_INJECTION {
+            ioctl.extend([jump(BPF_JEQ_K, request, 0, 1), deny]);
+        }
+        ioctl.push(allow);
+
+        // Hooks and Nmap are stopped by their pid; 0, -1 and process groups
+        // would reach processes the scan did not start, the operator's shell
+        // pipeline among them
+        let kill = vec![
+            stmt(BPF_LD_W_ABS, seccomp_data_arg(0)),
+            jump(BPF_JEQ_K, 0, 2, 0),
+            jump(BPF_JGE_K, 0x8000_0000, 1, 0),
+            allow,
+            deny,
+        ];
+
+        let mut prctl = vec![stmt(BPF_LD_W_ABS, seccomp_data_arg(0))];
+        for &option in ALLOWED_PRCTL {
+            prctl.extend([jump(BPF_JEQ_K, option as u32, 0, 1), allow]);
+        }
+        prctl.push(deny);
+
+        vec![
+            (libc::SYS_clone, clone),
+            (libc::SYS_clone3, clone3),
+            (libc::SYS_ioctl, ioctl),
+            (libc::SYS_kill, kill),
+            (libc::SYS_prctl, prctl),
+        ]
+    }
+
+    /// Running other programs, allowed at [`HardenLevel::Standard`] only
+    const EXEC_SYSCALLS: &[libc::c_long] = &[libc::SYS_execve, libc::SYS_execveat];
+
+    /// Real-time signal that asks a thread to harden itself
+    const HARDEN_SIGNAL_OFFSET: libc::c_int = 7;
+    /// How long a thread gets to answer it
+    const THREAD_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// What each thread does to itself
+    struct ThreadPlan {
+        ruleset: Option<libc::c_int>,
+        ids: Option<(libc::uid_t, libc::gid_t)>,
+    }
+
+    pub(super) fn apply(hardening: &Hardening) -> crate::Result<Vec<&'static str>> {
+        let mut measures = vec!["no-new-privs", "capabilities"];
+        let ids = hardening.target_ids()?;
+        match ids {
+            Some(_) => measures.push("setuid"),
+            // SAFETY: geteuid cannot fail
+            None if unsafe { libc::geteuid() } == 0 => {
+                log::warn!("--harden keeps running as root; run through sudo or give --harden-user to drop to a user")
+            }
+            None => {}
+        }
+        let ruleset = landlock_ruleset(&hardening.existing_writable())?;
+        if ruleset.is_some() {
+            measures.push("landlock");
+        } else {
+            log::warn!("The kernel has no Landlock; --harden cannot confine where the scan writes");
+        }
+
+        let result = harden_all_threads(&ThreadPlan { ruleset, ids });
+        if let Some(fd) = ruleset {
+            // SAFETY: the ruleset fd is ours and no thread uses it any more
+            unsafe { libc::close(fd) };
+        }
+        result?;
+
+        match seccomp_filter(hardening.level) {
+            Some(filter) => {
+                install_seccomp(&filter)?;
+                measures.push("seccomp");
+            }
+            None => log::warn!("No seccomp filter for this architecture; --harden leaves syscalls unrestricted"),
+        }
+        Ok(measures)
+    }
+
+    /// A Landlock ruleset allowing writes below `writable` only, or None if
+    /// the kernel lacks Landlock
+    fn landlock_ruleset(writable: &[PathBuf]) -> crate::Result<Option<libc::c_int>> {
+        // SAFETY: a version query reads no memory
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Ok(None);
+        }
+        let mut handled = FS_WRITES;
+        if abi >= 2 {
+            handled |= FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= FS_TRUNCATE;
+        }
+
+        let attr = RulesetAttr { handled_access_fs: handled };
+        // SAFETY: `attr` is a valid ruleset attribute of the size passed
+        let ruleset = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        } as libc::c_int;
+        if ruleset < 0 {
+            return Err(os_error("Landlock ruleset"));
+        }
+
+        // Programs the scan runs write their output to /dev/null
+        let dev_null = PathBuf::from("/dev/null");
+        for path in writable.iter().chain(std::iter::once(&dev_null)) {
+            // Rules on a file may only name rights that apply to files
+            let allowed = if path.is_dir() { handled } else { handled & (FS_WRITE_FILE | FS_TRUNCATE) };
+            if let Err(e) = add_path_rule(ruleset, path, allowed) {
+                // SAFETY: closing the ruleset we just created
+                unsafe { libc::close(ruleset) };
+                return Err(e);
+            }
+        }
+        Ok(Some(ruleset))
+    }
+
+    fn add_path_rule(ruleset: libc::c_int, path: &std::path::Path, allowed: u64) -> crate::Result<()> {
+        let name = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| ScanError::ConfigError(format!("Invalid path {}", path.display())))?;
+        // SAFETY: `name` is a NUL-terminated path
+        let fd = unsafe { libc::open(name.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(os_error(&path.display().to_string()));
+        }
+        let rule = PathBeneathAttr { allowed_access: allowed, parent_fd: fd };
+        // SAFETY: `rule` is a valid path-beneath attribute for the fd we opened
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset,
+                LANDLOCK_RULE_PATH_BENEATH,
+                &rule as *const PathBeneathAttr,
+                0u32,
+            )
+        };
+        // SAFETY: `fd` is ours
+        unsafe { libc::close(fd) };
+        if result != 0 {
+            return Err(os_error(&path.display().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Harden the calling thread; raw syscalls only, as other threads run
+    /// this in a signal handler. Returns the errno of the step that failed.
+    fn harden_thread(plan: &ThreadPlan) -> Result<(), libc::c_int> {
+        let errno = || std::io::Error::last_os_error().raw_os_error().unwrap_or(libc::EPERM);
+        // SAFETY: prctl and capget/capset are given plain values and our own buffers
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong) != 0 {
+                return Err(errno());
+            }
+            // Bounds what a program run as root starts with. Needs
+            // CAP_SETPCAP: without it the thread has nothing worth dropping
+            for cap in 0..64 {
+                if KEPT_CAPABILITIES & (1 << cap) == 0 && libc::prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong) != 0 {
+                    let e = errno();
+                    if e != libc::EINVAL && e != libc::EPERM {
+                        return Err(e);
+                    }
+                }
+            }
+            // Kernels without ambient capabilities have none to clear
+            libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_CLEAR_ALL as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong);
+
+            // Credentials belong to threads too. Keeping capabilities across
+            // the change leaves the permitted set for the capset below, which
+            // raises it back into effective
+            if let Some((uid, gid)) = plan.ids {
+                if libc::prctl(libc::PR_SET_KEEPCAPS, 1 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong) != 0
+                    || libc::syscall(libc::SYS_setgroups, 1usize, &gid as *const libc::gid_t) != 0
+                    || libc::syscall(libc::SYS_setresgid, gid, gid, gid) != 0
+                    || libc::syscall(libc::SYS_setresuid, uid, uid, uid) != 0
+                {
+                    return Err(errno());
+                }
+                libc::prctl(libc::PR_SET_KEEPCAPS, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong, 0 as libc::c_ulong);
+            }
+
+            if let Some(ruleset) = plan.ruleset {
+                if libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0 {
+                    return Err(errno());
+                }
+            }
+
+            let mut header = CapHeader { version: LINUX_CAPABILITY_VERSION_3, pid: 0 };
+            let mut data = [CapData::default(); 2];
+            if libc::syscall(libc::SYS_capget, &mut header as *mut CapHeader, data.as_mut_ptr()) != 0 {
+                return Err(errno());
+            }
+            for (word, caps) in data.iter_mut().enumerate() {
+                let kept = (KEPT_CAPABILITIES >> (32 * word)) as u32;
+                caps.permitted &= kept;
+                caps.effective = caps.permitted;
+                caps.inheritable &= kept;
+            }
+            if libc::syscall(libc::SYS_capset, &mut header as *mut CapHeader, data.as_ptr()) != 0 {
+                return Err(errno());
+            }
+        }
+        Ok(())
+    }
+
+    static PLAN: AtomicPtr<ThreadPlan> = AtomicPtr::new(std::ptr::null_mut());
+    static ANSWERS: AtomicUsize = AtomicUsize::new(0);
+    static FAILURE: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn on_harden_signal(_: libc::c_int) {
+        // SAFETY: errno is thread-local; the interrupted code gets it back
+        let saved_errno = unsafe { *libc::__errno_location() };
+        let plan = PLAN.load(Ordering::SeqCst);
+        if !plan.is_null() {
+            // SAFETY: the plan outlives the signals, see harden_all_threads
+            if let Err(errno) = harden_thread(unsafe { &*plan }) {
+                FAILURE.store(errno, Ordering::SeqCst);
+            }
+        }
+        ANSWERS.fetch_add(1, Ordering::SeqCst);
+        // SAFETY: as above
+        unsafe { *libc::__errno_location() = saved_errno };
+    }
+
+    /// Thread ids of the process
+    fn threads() -> crate::Result<Vec<libc::pid_t>> {
+        Ok(std::fs::read_dir("/proc/self/task")?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect())
+    }
+
+    /// Capabilities, no-new-privs and Landlock domains belong to threads, and
+    /// the runtime's are already running: each is signalled to harden itself
+    /// until a pass over the process finds none left. Threads started by a
+    /// hardened one inherit its state.
+    fn harden_all_threads(plan: &ThreadPlan) -> crate::Result<()> {
+        harden_thread(plan).map_err(|errno| {
+            ScanError::PermissionError(format!("Cannot harden the process: {}", std::io::Error::from_raw_os_error(errno)))
+        })?;
+
+        let signal = libc::SIGRTMIN() + HARDEN_SIGNAL_OFFSET;
+        PLAN.store(plan as *const ThreadPlan as *mut ThreadPlan, Ordering::SeqCst);
+        FAILURE.store(0, Ordering::SeqCst);
+        // SAFETY: zeroed sigactions are valid; ours points at an extern "C" handler
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        let installed = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_harden_signal as extern "C" fn(libc::c_int) as usize;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, &mut previous) == 0
+        };
+        let result = if installed { signal_threads(signal) } else { Err(os_error("signal handler")) };
+        if installed {
+            // SAFETY: restores what was there before
+            unsafe { libc::sigaction(signal, &previous, std::ptr::null_mut()) };
+        }
+        PLAN.store(std::ptr::null_mut(), Ordering::SeqCst);
+        result?;
+
+        match FAILURE.swap(0, Ordering::SeqCst) {
+            0 => Ok(()),
+            errno => Err(ScanError::PermissionError(format!(
+                "Cannot harden every thread: {}",
+                std::io::Error::from_raw_os_error(errno)
+            ))),
+        }
+    }
+
+    fn signal_threads(signal: libc::c_int) -> crate::Result<()> {
+        // SAFETY: getpid and gettid cannot fail
+        let (pid, me) = unsafe { (libc::getpid(), libc::gettid()) };
+        let mut hardened = HashSet::from([me]);
+        loop {
+            let pending: Vec<libc::pid_t> = threads()?.into_iter().filter(|tid| !hardened.contains(tid)).collect();
+            if pending.is_empty() {
+                return Ok(());
+            }
+            for tid in pending {
+                hardened.insert(tid);
+                let answers = ANSWERS.load(Ordering::SeqCst);
+                // SAFETY: tgkill only delivers our signal; ESRCH means the thread is gone
+                if unsafe { libc::syscall(libc::SYS_tgkill, pid, tid, signal) } != 0 {
+                    continue;
+                }
+                let deadline = Instant::now() + THREAD_TIMEOUT;
+                while ANSWERS.load(Ordering::SeqCst) == answers {
+                    // A thread that exits before its handler runs never answers
+                    if !std::path::Path::new(&format!("/proc/self/task/{}", tid)).exists() {
+                        break;
+                    }
+                    if Instant::now() > deadline {
+                        return Err(ScanError::PermissionError(format!(
+                            "Cannot harden the process: thread {} does not take signals",
+                            tid
+                        )));
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+
+    /// The seccomp program for `level`, or None on architectures without one
+    fn seccomp_filter(level: HardenLevel) -> Option<Vec<BpfInstruction>> {
+        let arch = if cfg!(target_arch = "x86_64") {
+            AUDIT_ARCH_X86_64
+        } else if cfg!(target_arch = "aarch64") {
+            AUDIT_ARCH_AARCH64
+        } else {
+            return None;
+        };
+        let stmt = |code, k| BpfInstruction { code, jt: 0, jf: 0, k };
+        // EPERM rather than a kill: a library taking a path the list misses
+        // fails one call instead of losing the scan's results
+        let deny = stmt(BPF_RET_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32);
+        let allow = stmt(BPF_RET_K, libc::SECCOMP_RET_ALLOW);
+
+        let mut filter = vec![
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            BpfInstruction { code: BPF_JEQ_K, jt: 1, jf: 0, k: arch },
+            stmt(BPF_RET_K, libc::SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        ];
+        if cfg!(target_arch = "x86_64") {
+            filter.push(BpfInstruction { code: BPF_JGE_K, jt: 0, jf: 1, k: X32_SYSCALL_BIT });
+            filter.push(deny);
+        }
+        for (nr, checks) in checked_syscalls(allow, deny) {
+            filter.push(BpfInstruction { code: BPF_JEQ_K, jt: 0, jf: checks.len() as u8, k: nr as u32 });
+            filter.extend(checks);
+        }
+        let exec = if level == HardenLevel::Standard { EXEC_SYSCALLS } else { &[] };
+        for &nr in ALLOWED_SYSCALLS.iter().chain(LEGACY_SYSCALLS).chain(exec) {
+            filter.push(BpfInstruction { code: BPF_JEQ_K, jt: 0, jf: 1, k: nr as u32 });
+            filter.push(allow);
+        }
+        filter.push(deny);
+        Some(filter)
+    }
+
+    /// Install `filter` on every thread at once
+    fn install_seccomp(filter: &[BpfInstruction]) -> crate::Result<()> {
+        let mut filters: Vec<libc::sock_filter> =
+            filter.iter().map(|i| libc::sock_filter { code: i.code, jt: i.jt, jf: i.jf, k: i.k }).collect();
+        let program = libc::sock_fprog { len: filters.len() as u16, filter: filters.as_mut_ptr() };
+        // SAFETY: `program` points at `filters`, which outlives the call; the kernel copies it
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                libc::SECCOMP_FILTER_FLAG_TSYNC,
+                &program as *const libc::sock_fprog,
+            )
+        };
+        match result {
+            0 => Ok(()),
+            r if r < 0 => Err(os_error("seccomp")),
+            tid => Err(ScanError::PermissionError(format!("Cannot harden the process: thread {} refused the seccomp filter", tid))),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Run `filter` on a syscall the way the kernel would
+        fn verdict(filter: &[BpfInstruction], arch: u32, nr: libc::c_long, args: &[u64]) -> u32 {
+            let (mut a, mut pc) = (0u32, 0usize);
+            loop {
+                let insn = filter[pc];
+                pc += 1;
+                match insn.code {
+                    BPF_LD_W_ABS if insn.k == SECCOMP_DATA_ARCH => a = arch,
+                    BPF_LD_W_ABS if insn.k == SECCOMP_DATA_NR => a = nr as u32,
+                    BPF_LD_W_ABS => a = args.get((insn.k as usize - 16) / 8).copied().unwrap_or(0) as u32,
+                    BPF_JEQ_K => pc += if a == insn.k { insn.jt } else { insn.jf } as usize,
+                    BPF_JGE_K => pc += if a >= insn.k { insn.jt } else { insn.jf } as usize,
+                    BPF_JSET_K => pc += if a & insn.k != 0 { insn.jt } else { insn.jf } as usize,
+                    BPF_RET_K => return insn.k,
+                    code => panic!("unexpected opcode {:#x}", code),
+                }
+            }
+        }
+
+        #[test]
+        fn test_seccomp_filter_allows_only_listed_syscalls() {
+            let Some(standard) = seccomp_filter(HardenLevel::Standard) else { return };
+            let strict = seccomp_filter(HardenLevel::Strict).unwrap();
+            let arch = if cfg!(target_arch = "x86_64") { AUDIT_ARCH_X86_64 } else { AUDIT_ARCH_AARCH64 };
+            let eperm = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+            for nr in [libc::SYS_socket, libc::SYS_sendto, libc::SYS_openat, libc::SYS_execve] {
+                assert_eq!(verdict(&standard, arch, nr, &[]), libc::SECCOMP_RET_ALLOW, "syscall {}", nr);
+            }
+            // Whatever is not listed, including numbers no kernel has yet
+            for nr in [libc::SYS_ptrace, libc::SYS_mount, libc::SYS_init_module, libc::SYS_bpf, libc::SYS_setresuid, libc::SYS_unshare, 1000] {
+                assert_eq!(verdict(&standard, arch, nr, &[]), eperm, "syscall {}", nr);
+            }
+            assert_eq!(verdict(&strict, arch, libc::SYS_execve, &[]), eperm);
+            assert_eq!(verdict(&strict, arch, libc::SYS_socket, &[]), libc::SECCOMP_RET_ALLOW);
+            assert_eq!(verdict(&standard, 0x4000_0003, libc::SYS_socket, &[]), libc::SECCOMP_RET_KILL_PROCESS);
+
+            let allow = libc::SECCOMP_RET_ALLOW;
+            let check = |nr, args: &[u64]| verdict(&standard, arch, nr, args);
+
+            // Threads and fork-style children, but no namespaces
+            let thread_flags = (libc::CLONE_VM | libc::CLONE_FS | libc::CLONE_FILES | libc::CLONE_SIGHAND | libc::CLONE_THREAD) as u64;
+            assert_eq!(check(libc::SYS_clone, &[thread_flags]), allow);
+            assert_eq!(check(libc::SYS_clone, &[libc::SIGCHLD as u64]), allow);
+            for flag in [libc::CLONE_NEWUSER, libc::CLONE_NEWNET, libc::CLONE_NEWNS, libc::CLONE_NEWPID] {
+                assert_eq!(check(libc::SYS_clone, &[libc::SIGCHLD as u64 | flag as u64]), eperm, "flag {:#x}", flag);
+            }
+            assert_eq!(check(libc::SYS_clone3, &[]), libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32);
+
+            assert_eq!(check(libc::SYS_ioctl, &[0, libc::FIONREAD as u64]), allow);
+            assert_eq!(check(libc::SYS_ioctl, &[0, libc::TIOCSTI as u64]), eperm);
+
+            assert_eq!(check(libc::SYS_kill, &[4242, libc::SIGKILL as u64]), allow);
+            assert_eq!(check(libc::SYS_kill, &[-1i64 as u64, libc::SIGKILL as u64]), eperm);
+            assert_eq!(check(libc::SYS_kill, &[-4242i64 as u64, libc::SIGTERM as u64]), eperm);
+            assert_eq!(check(libc::SYS_kill, &[0, libc::SIGTERM as u64]), eperm);
+
+            assert_eq!(check(libc::SYS_prctl, &[libc::PR_SET_NAME as u64]), allow);
+            assert_eq!(check(libc::SYS_prctl, &[libc::PR_SET_DUMPABLE as u64, 1]), eperm);
+            assert_eq!(check(libc::SYS_prctl, &[libc::PR_SET_PTRACER as u64]), eperm);
+        }
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+mod openbsd {
+    use super::{os_error, HardenLevel, Hardening};
+    use crate::ScanError;
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    pub(super) fn apply(hardening: &Hardening) -> crate::Result<Vec<&'static str>> {
+        let c_string = |bytes: &[u8]| {
+            CString::new(bytes).map_err(|_| ScanError::ConfigError(format!("Invalid path {}", String::from_utf8_lossy(bytes))))
+        };
+        let mut measures = vec![];
+        if let Some((uid, gid)) = hardening.target_ids()? {
+            // SAFETY: plain ids; credentials here belong to the process
+            if unsafe { libc::setgroups(1, &gid) != 0 || libc::setresgid(gid, gid, gid) != 0 || libc::setresuid(uid, uid, uid) != 0 } {
+                return Err(os_error("setuid"));
+            }
+            measures.push("setuid");
+        }
+        let unveil = |path: &[u8], permissions: &str| -> crate::Result<()> {
+            let (path, permissions) = (c_string(path)?, c_string(permissions.as_bytes())?);
+            // SAFETY: both are NUL-terminated
+            match unsafe { libc::unveil(path.as_ptr(), permissions.as_ptr()) } {
+                0 => Ok(()),
+                _ => Err(os_error("unveil")),
+            }
+        };
+        unveil(b"/", "rx")?;
+        for path in hardening.existing_writable().iter().map(|p| p.as_os_str().as_bytes()).chain([&b"/dev/null"[..]]) {
+            unveil(path, "rwc")?;
+        }
+        // SAFETY: null arguments lock the unveiled paths
+        if unsafe { libc::unveil(std::ptr::null(), std::ptr::null()) } != 0 {
+            return Err(os_error("unveil"));
+        }
+
+        let promises = match hardening.level {
+            HardenLevel::Standard => "stdio rpath wpath cpath flock inet dns route proc exec",
+            HardenLevel::Strict => "stdio rpath wpath cpath flock inet dns route",
+        };
+        let promises = c_string(promises.as_bytes())?;
+        // SAFETY: NUL-terminated promises; null leaves programs we run unpledged
+        if unsafe { libc::pledge(promises.as_ptr(), std::ptr::null()) } != 0 {
+            return Err(os_error("pledge"));
+        }
+        measures.extend(["unveil", "pledge"]);
+        Ok(measures)
+    }
+}
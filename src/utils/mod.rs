@@ -1,15 +1,32 @@
 //! Utility modules for the scanner
 
 pub mod adaptive_performance;
+pub mod address_class;
 pub mod address_exclusions;
 pub mod address_parser;
+#[cfg(feature = "minimal")]
+pub mod cli_conflicts;
 pub mod config;
+pub mod device;
+#[cfg(feature = "web")]
+pub mod external_sources;
 pub mod file_input;
+pub mod harden;
 pub mod port_exclusions;
+pub mod port_spec;
+pub mod preflight;
 pub mod profiles;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod redact;
+pub mod reload;
+pub mod scan_definition;
 pub mod scan_options;
+pub mod scope_guard;
 pub mod target_parser;
 pub mod timing;
+#[cfg(feature = "minimal")]
+pub mod update;
 
 use std::time::{Duration, Instant};
 
@@ -0,0 +1,307 @@
+//! Port specification language for `-p`
+//!
+//! A spec is a comma-separated list of items:
+//!
+//! * `80`, `1-1024`, `60000-` (to 65535), `-1024` (from 1), `-` or `all`
+//! * `topN`: the N most frequently open ports for the item's protocol
+//! * named groups from [`crate::top_ports`]: `web`, `database`, `mail`, ...
+//! * `T:` / `U:` prefixes select TCP or UDP for that item and every following
+//!   one, as in Nmap: `T:1-1024,U:53,161` scans UDP 53 and 161
+//! * a leading `!` excludes the item: `1-1024,!T:25,!web`
+//!
+//! Exclusions apply after all inclusions, so their position does not matter.
+//! Items without any protocol prefix follow the scan technique's protocol.
+
+use crate::network::Protocol;
+use crate::top_ports::{get_port_list, get_top_ports_for, parse_port_list};
+use crate::ScanError;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Ports scanned with `--lenient-ports` when a spec cannot be used
+pub const LENIENT_FALLBACK_PORTS: [u16; 20] =
+    [21, 22, 23, 25, 53, 80, 110, 111, 135, 139, 143, 443, 993, 995, 1723, 3306, 3389, 5432, 5900, 8080];
+
+const GROUPS: [&str; 8] = ["all", "web", "database", "db", "mail", "common", "security", "dev"];
+
+/// What one item selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    Range(u16, u16),
+    Top(usize),
+    Group(crate::top_ports::PortListType),
+}
+
+impl Selection {
+    fn ports(&self, protocol: Protocol) -> Vec<u16> {
+        match *self {
+            Selection::Range(start, end) => (start..=end).collect(),
+            Selection::Top(count) => get_top_ports_for(count, protocol),
+            Selection::Group(list) => get_port_list(list).ports.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Item {
+    /// `None` until a `T:`/`U:` prefix appears
+    protocol: Option<Protocol>,
+    exclude: bool,
+    selection: Selection,
+}
+
+/// A parsed `-p` specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortSpec {
+    source: String,
+    items: Vec<Item>,
+}
+
+impl PortSpec {
+    pub fn parse(spec: &str) -> crate::Result<Self> {
+        let mut items = Vec::new();
+        let mut protocol = None;
+        let mut column = 1;
+        for raw in spec.split(',') {
+            let item = parse_item(raw.trim(), &mut protocol).map_err(|reason| {
+                let shown = if raw.trim().is_empty() { "empty item".to_string() } else { format!("`{}`", raw.trim()) };
+                ScanError::PortRangeError(format!("invalid port spec at {} (column {}): {}", shown, column, reason))
+            })?;
+            items.push(item);
+            column += raw.chars().count() + 1;
+        }
+        Ok(Self { source: spec.to_string(), items })
+    }
+
+    /// Ports to scan with `protocol`, sorted and deduplicated
+    pub fn ports_for(&self, protocol: Protocol) -> Vec<u16> {
+        self.ports_defaulting_to(protocol, protocol)
+    }
+
+    /// Ports for `protocol` when items without a prefix are `default` ports
+    /// only, as for a listener opening both kinds at once
+    pub fn ports_defaulting_to(&self, protocol: Protocol, default: Protocol) -> Vec<u16> {
+        let applies = |item: &&Item| item.protocol.unwrap_or(default) == protocol;
+        let collect = |exclude: bool| -> BTreeSet<u16> {
+            self.items
+                .iter()
+                .filter(applies)
+                .filter(|item| item.exclude == exclude)
+                .flat_map(|item| item.selection.ports(protocol))
+                .collect()
+        };
+        let excluded = collect(true);
+        collect(false).difference(&excluded).copied().collect()
+    }
+
+    /// Whether any item is explicitly prefixed for `protocol`
+    pub fn mentions(&self, protocol: Protocol) -> bool {
+        self.items.iter().any(|item| !item.exclude && item.protocol == Some(protocol))
+    }
+
+    /// Whether every port the spec selects is prefixed for `protocol`
+    pub fn only(&self, protocol: Protocol) -> bool {
+        let mut selected = self.items.iter().filter(|item| !item.exclude).peekable();
+        selected.peek().is_some() && selected.all(|item| item.protocol == Some(protocol))
+    }
+
+    /// Ports for `protocol`, or an error naming the spec if it selects none
+    pub fn resolve(&self, protocol: Protocol) -> crate::Result<Vec<u16>> {
+        let ports = self.ports_for(protocol);
+        if ports.is_empty() {
+            let name = if protocol == Protocol::Udp { "UDP" } else { "TCP" };
+            let hint = match protocol {
+                Protocol::Udp if self.mentions(Protocol::Tcp) => "; its ports are TCP (T:), scan them without -s udp",
+                Protocol::Tcp if self.mentions(Protocol::Udp) => "; its ports are UDP (U:), scan them with -s udp",
+                _ => "",
+            };
+            return Err(ScanError::PortRangeError(format!("port spec `{}` selects no {} ports{}", self, name, hint)));
+        }
+        Ok(ports)
+    }
+}
+
+/// What a `-p` spec gives a scan of one protocol
+#[derive(Debug)]
+pub struct ResolvedPorts {
+    pub ports: Vec<u16>,
+    /// The spec names ports of the other protocol, which the scan skips
+    pub skips_other: bool,
+    /// Why the spec was replaced with [`LENIENT_FALLBACK_PORTS`]
+    pub fallback: Option<ScanError>,
+}
+
+impl ResolvedPorts {
+    /// Ports of `spec` for `protocol`; a spec that is invalid or selects
+    /// none is an error, or with `lenient` scans the fallback ports
+    pub fn resolve(spec: &str, protocol: Protocol, lenient: bool) -> crate::Result<Self> {
+        let other = if protocol == Protocol::Udp { Protocol::Tcp } else { Protocol::Udp };
+        let mut skips_other = false;
+        let resolved = PortSpec::parse(spec).and_then(|spec| {
+            skips_other = spec.mentions(other);
+            spec.resolve(protocol)
+        });
+        match resolved {
+            Ok(ports) => Ok(Self { ports, skips_other, fallback: None }),
+            Err(e) if lenient => Ok(Self { ports: LENIENT_FALLBACK_PORTS.to_vec(), skips_other, fallback: Some(e) }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// An `--exclude-ports` item that is a port or a range like `1000-2000`,
+/// as the first and last port
+pub fn parse_exclusion(item: &str) -> Option<(u16, u16)> {
+    let item = item.trim();
+    match item.split_once('-') {
+        Some((start, end)) => start.parse::<u16>().ok()
+            .zip(end.parse::<u16>().ok())
+            .filter(|(start, end)| start <= end),
+        None => item.parse::<u16>().ok().map(|port| (port, port)),
+    }
+}
+
+impl fmt::Display for PortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn parse_item(item: &str, protocol: &mut Option<Protocol>) -> Result<Item, String> {
+    if item.is_empty() {
+        return Err("expected a port, range or group between commas".to_string());
+    }
+    let (exclude, rest) = match item.strip_prefix('!') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, item),
+    };
+    let body = match rest.split_once(':') {
+        Some((prefix, body)) => {
+            *protocol = Some(match prefix.to_ascii_lowercase().as_str() {
+                "t" | "tcp" => Protocol::Tcp,
+                "u" | "udp" => Protocol::Udp,
+                _ => return Err(format!("unknown protocol prefix `{}:`; use T: or U:", prefix)),
+            });
+            body
+        }
+        None => rest,
+    };
+    if body.is_empty() {
+        return Err(format!("nothing to {} after `{}`", if exclude { "exclude" } else { "scan" }, item));
+    }
+    let selection = parse_selection(body)?;
+    Ok(Item { protocol: *protocol, exclude, selection })
+}
+
+fn parse_selection(body: &str) -> Result<Selection, String> {
+    let lower = body.to_ascii_lowercase();
+    if lower == "-" || lower == "all" {
+        return Ok(Selection::Range(1, 65535));
+    }
+    if let Some(count) = lower.strip_prefix("top") {
+        return match count.parse::<u16>() {
+            Ok(count) if count > 0 => Ok(Selection::Top(count as usize)),
+            _ => Err(format!("`{}` needs a count of 1-65535, e.g. top100", body)),
+        };
+    }
+    if body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return parse_port_list(&lower).map(Selection::Group).ok_or_else(|| {
+            let suggestion = GROUPS
+                .iter()
+                .map(|group| (super::config::edit_distance(&lower, group), group))
+                .filter(|(distance, _)| *distance <= 2)
+                .min_by_key(|(distance, _)| *distance);
+            match suggestion {
+                Some((_, group)) => format!("unknown port group `{}` (did you mean `{}`?)", body, group),
+                None => format!("unknown port group `{}`; known groups: {}, topN", body, GROUPS.join(", ")),
+            }
+        });
+    }
+    match body.split_once('-') {
+        Some((start, end)) => {
+            let start = if start.is_empty() { 1 } else { parse_port(start)? };
+            let end = if end.is_empty() { 65535 } else { parse_port(end)? };
+            if start > end {
+                return Err(format!("range {}-{} is reversed (did you mean {}-{}?)", start, end, end, start));
+            }
+            Ok(Selection::Range(start, end))
+        }
+        None => parse_port(body).map(|port| Selection::Range(port, port)),
+    }
+}
+
+fn parse_port(text: &str) -> Result<u16, String> {
+    match text.trim().parse::<u32>() {
+        Ok(0) => Err("port 0 is not valid".to_string()),
+        Ok(port) if port > 65535 => Err(format!("port {} is out of range (1-65535)", port)),
+        Ok(port) => Ok(port as u16),
+        Err(_) => Err(format!("`{}` is not a port number", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_port_spec_language() {
+        let spec = PortSpec::parse("T:1-1024,U:53,161,!T:25,!T:100-1000").unwrap();
+        let tcp = spec.ports_for(Protocol::Tcp);
+        assert_eq!(tcp.len(), 1024 - 1 - 901);
+        assert!(!tcp.contains(&25) && tcp.contains(&1024));
+        assert_eq!(spec.ports_for(Protocol::Udp), vec![53, 161]);
+        assert!(!spec.only(Protocol::Udp));
+        assert!(PortSpec::parse("U:53,161,!T:80").unwrap().only(Protocol::Udp));
+
+        // Unprefixed items follow the scan protocol; exclusions are order-independent
+        let spec = PortSpec::parse("!80, top10 ,8000-").unwrap();
+        let tcp = spec.ports_for(Protocol::Tcp);
+        assert!(!tcp.contains(&80) && tcp.contains(&443) && tcp.contains(&65535));
+        assert!(spec.ports_for(Protocol::Udp).contains(&53));
+        let mixed = PortSpec::parse("22,U:53").unwrap();
+        assert_eq!(mixed.ports_defaulting_to(Protocol::Tcp, Protocol::Tcp), vec![22]);
+        assert_eq!(mixed.ports_defaulting_to(Protocol::Udp, Protocol::Tcp), vec![53]);
+        assert_eq!(PortSpec::parse("web,!80").unwrap().ports_for(Protocol::Tcp), {
+            let mut web = get_port_list(crate::top_ports::PortListType::Web).ports.clone();
+            web.retain(|&p| p != 80);
+            web.sort_unstable();
+            web.dedup();
+            web
+        });
+
+        let error = |spec: &str| PortSpec::parse(spec).unwrap_err().to_string();
+        assert_eq!(
+            error("22,U:5x3"),
+            "Port range error: invalid port spec at `U:5x3` (column 4): `5x3` is not a port number"
+        );
+        assert!(error("80,,443").contains("empty item (column 4)"));
+        assert!(error("2000-1000").contains("reversed (did you mean 1000-2000?)"));
+        assert!(error("wbe").contains("did you mean `web`?"));
+        assert!(error("S:80").contains("unknown protocol prefix `S:`"));
+        assert!(error("70000").contains("out of range"));
+        let udp_only = PortSpec::parse("U:53").unwrap().resolve(Protocol::Tcp).unwrap_err().to_string();
+        assert!(udp_only.contains("selects no TCP ports; its ports are UDP"));
+    }
+
+    #[test]
+    fn test_strict_and_lenient_resolution() {
+        let strict = ResolvedPorts::resolve("22,U:53", Protocol::Tcp, false).unwrap();
+        assert_eq!((strict.ports, strict.skips_other, strict.fallback.is_none()), (vec![22], true, true));
+        assert!(ResolvedPorts::resolve("22,8o", Protocol::Tcp, false).unwrap_err().to_string().contains("`8o`"));
+        assert!(ResolvedPorts::resolve("U:53", Protocol::Tcp, false).is_err());
+
+        let lenient = ResolvedPorts::resolve("22,8o", Protocol::Tcp, true).unwrap();
+        assert_eq!(lenient.ports, LENIENT_FALLBACK_PORTS.to_vec());
+        assert!(lenient.fallback.unwrap().to_string().contains("`8o`"));
+        let lenient = ResolvedPorts::resolve("U:53", Protocol::Tcp, true).unwrap();
+        assert!(lenient.skips_other && lenient.fallback.is_some());
+        // A usable spec is used as is either way
+        assert_eq!(ResolvedPorts::resolve("U:53", Protocol::Udp, true).unwrap().ports, vec![53]);
+
+        assert_eq!(parse_exclusion(" 25 "), Some((25, 25)));
+        assert_eq!(parse_exclusion("1000-2000"), Some((1000, 2000)));
+        assert_eq!(parse_exclusion("2000-1000"), None);
+        assert_eq!(parse_exclusion("smtp"), None);
+        assert_eq!(parse_exclusion("70000"), None);
+    }
+}
@@ -0,0 +1,326 @@
+//! Network preflight for `--system-check`
+//!
+//! Checks what a large scan depends on before it starts: raw socket
+//! capability, the file descriptor limit, a route out, whether a known-good
+//! reflector answers and how fast, and conntrack headroom on Linux. Each check
+//! passes, warns or fails, and anything short of a pass says how to fix it.
+//!
+//! `phobos healthcheck` runs the local subset for container orchestrators,
+//! which call it every few seconds and only read its exit status.
+
+use crate::network::conntrack::ConntrackUsage;
+use crate::network::route::RouteInfo;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Descriptors a scan at the default batch size keeps open
+const RECOMMENDED_FD_LIMIT: u64 = 4096;
+/// Conntrack usage above which a scan may fill the table
+const CONNTRACK_WARN_UTILIZATION: f64 = 0.8;
+/// Free conntrack entries a full-range scan of one host needs
+const CONNTRACK_MIN_HEADROOM: u64 = 65_535;
+
+/// `phobos healthcheck` exit status when a check fails
+pub const HEALTHCHECK_UNHEALTHY: i32 = 1;
+/// `phobos healthcheck` exit status when the configuration cannot be used;
+/// the same status clap gives invalid flags and environment variables
+pub const HEALTHCHECK_MISCONFIGURED: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Outcome of one preflight check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub remedy: Option<String>,
+}
+
+impl PreflightCheck {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Pass, detail: detail.into(), remedy: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Warn, detail: detail.into(), remedy: Some(remedy.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, remedy: impl Into<String>) -> Self {
+        Self { name, status: CheckStatus::Fail, detail: detail.into(), remedy: Some(remedy.into()) }
+    }
+}
+
+impl fmt::Display for PreflightCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.detail)
+    }
+}
+
+/// Where and how to probe the reflector
+#[derive(Debug, Clone)]
+pub struct PreflightOptions {
+    /// A host that answers TCP connections on this port, by accepting or refusing them
+    pub reflector: SocketAddr,
+    pub probes: u32,
+    pub timeout: Duration,
+    pub interface: Option<String>,
+}
+
+impl Default for PreflightOptions {
+    fn default() -> Self {
+        Self {
+            reflector: SocketAddr::from(([1, 1, 1, 1], 443)),
+            probes: 5,
+            timeout: Duration::from_secs(2),
+            interface: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Run every check; the reflector gets `options.probes` TCP connects
+    pub async fn run(options: &PreflightOptions) -> Self {
+        let route = match options.reflector.ip() {
+            IpAddr::V4(addr) => Some(RouteInfo::lookup(addr, options.interface.as_deref(), None)),
+            IpAddr::V6(_) => None,
+        };
+        let rtts = probe_reflector(options).await;
+        Self {
+            checks: vec![
+                raw_socket_check(),
+                fd_limit_check(fd_limit()),
+                route_check(route.as_ref()),
+                reflector_check(options.reflector, &rtts),
+                conntrack_check(ConntrackUsage::read()),
+            ],
+        }
+    }
+
+    /// Checks for a container healthcheck: raw sockets when the configured
+    /// technique needs them and the descriptor limit. Nothing leaves the host
+    /// unless `reflector` is given.
+    pub async fn healthcheck(raw_sockets: bool, reflector: Option<&PreflightOptions>) -> Self {
+        let mut checks = Vec::new();
+        if raw_sockets {
+            checks.push(raw_socket_check());
+        }
+        checks.push(fd_limit_check(fd_limit()));
+        if let Some(options) = reflector {
+            checks.push(reflector_check(options.reflector, &probe_reflector(options).await));
+        }
+        Self { checks }
+    }
+
+    /// Worst status of any check
+    pub fn status(&self) -> CheckStatus {
+        self.checks.iter().map(|check| check.status).max().unwrap_or(CheckStatus::Pass)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.status() != CheckStatus::Fail
+    }
+
+    /// Exit status for `phobos healthcheck`: 0 while the scanner can run,
+    /// warnings included, and 1 once a check fails
+    pub fn exit_code(&self) -> i32 {
+        if self.passed() { 0 } else { HEALTHCHECK_UNHEALTHY }
+    }
+}
+
+/// `options.probes` connects to the reflector
+async fn probe_reflector(options: &PreflightOptions) -> Vec<Option<Duration>> {
+    let mut rtts = Vec::with_capacity(options.probes as usize);
+    for _ in 0..options.probes {
+        rtts.push(connect_rtt(options.reflector, options.timeout).await);
+    }
+    rtts
+}
+
+/// Round trip of one connect; a refusal is an answer too
+async fn connect_rtt(addr: SocketAddr, timeout: Duration) -> Option<Duration> {
+    let started = Instant::now();
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Some(started.elapsed()),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => Some(started.elapsed()),
+        _ => None,
+    }
+}
+
+/// Whether this process may open the raw sockets SYN and stealth scans need
+pub fn raw_socket_access() -> std::io::Result<()> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)).map(drop)
+}
+
+fn raw_socket_check() -> PreflightCheck {
+    match raw_socket_access() {
+        Ok(()) => PreflightCheck::pass("raw sockets", "available; SYN, stealth and UDP scans can run"),
+        Err(e) => PreflightCheck::fail(
+            "raw sockets",
+            format!("unavailable ({})", e),
+            "run as root or grant the capability with `sudo setcap cap_net_raw,cap_net_admin+eip $(which phobos)`; \
+             `-s connect` works without it",
+        ),
+    }
+}
+
+fn fd_limit() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        rlimit::getrlimit(rlimit::Resource::NOFILE).ok().map(|(soft, _)| soft)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+fn fd_limit_check(limit: Option<u64>) -> PreflightCheck {
+    match limit {
+        Some(limit) if limit >= RECOMMENDED_FD_LIMIT => {
+            PreflightCheck::pass("file descriptors", format!("limit {}", limit))
+        }
+        Some(limit) => PreflightCheck::warn(
+            "file descriptors",
+            format!("limit {} is below {}; batches will be shrunk to fit", limit, RECOMMENDED_FD_LIMIT),
+            "raise it with `ulimit -n 65535` or pass `--ulimit 65535`",
+        ),
+        None => PreflightCheck::pass("file descriptors", "no per-process limit reported"),
+    }
+}
+
+fn route_check(route: Option<&RouteInfo>) -> PreflightCheck {
+    match route {
+        Some(route) if route.source.is_some() => PreflightCheck::pass("route", route.to_string()),
+        Some(route) => PreflightCheck::fail(
+            "route",
+            format!("no route to {}", route.target),
+            "check `ip route` for a default route, or pick an interface with `--interface`",
+        ),
+        None => PreflightCheck::pass("route", "IPv6 reflector; route not inspected"),
+    }
+}
+
+fn reflector_check(reflector: SocketAddr, rtts: &[Option<Duration>]) -> PreflightCheck {
+    let answered: Vec<Duration> = rtts.iter().flatten().copied().collect();
+    let lost = rtts.len() - answered.len();
+    let loss = if rtts.is_empty() { 0.0 } else { lost as f64 * 100.0 / rtts.len() as f64 };
+    if answered.is_empty() {
+        return PreflightCheck::fail(
+            "reflector",
+            format!("{} did not answer any of {} probes", reflector, rtts.len()),
+            "outbound TCP looks blocked or proxied; check egress firewall rules, or name a reachable host with \
+             `--reflector HOST:PORT`. Until then every port will read as filtered",
+        );
+    }
+    let min = answered.iter().min().copied().unwrap_or_default();
+    let max = answered.iter().max().copied().unwrap_or_default();
+    let avg = answered.iter().sum::<Duration>() / answered.len() as u32;
+    let detail = format!(
+        "{} rtt min/avg/max {:.1}/{:.1}/{:.1} ms, {:.0}% loss",
+        reflector,
+        min.as_secs_f64() * 1000.0,
+        avg.as_secs_f64() * 1000.0,
+        max.as_secs_f64() * 1000.0,
+        loss
+    );
+    if lost > 0 {
+        return PreflightCheck::warn(
+            "reflector",
+            detail,
+            format!(
+                "the path is lossy; use `--tries 2` or more and a `--timeout` of at least {} ms",
+                suggested_timeout_ms(max)
+            ),
+        );
+    }
+    PreflightCheck::pass("reflector", format!("{}; `--timeout {}` is enough", detail, suggested_timeout_ms(max)))
+}
+
+/// Three times the worst round trip, rounded up to 100 ms, at least 500 ms
+fn suggested_timeout_ms(max_rtt: Duration) -> u64 {
+    let ms = (max_rtt.as_millis() as u64 * 3).max(500);
+    (ms + 99) / 100 * 100
+}
+
+fn conntrack_check(usage: Option<ConntrackUsage>) -> PreflightCheck {
+    let Some(usage) = usage else {
+        return PreflightCheck::pass("conntrack", "not loaded; probes are not tracked");
+    };
+    if usage.utilization() >= CONNTRACK_WARN_UTILIZATION || usage.headroom() < CONNTRACK_MIN_HEADROOM {
+        return PreflightCheck::warn(
+            "conntrack",
+            format!("{}, {} free", usage, usage.headroom()),
+            format!(
+                "a large scan can fill the table and drop this host's connections; raise the limit with \
+                 `sudo sysctl -w net.netfilter.nf_conntrack_max={}` or scan with fewer concurrent probes",
+                (usage.max * 2).max(usage.count + CONNTRACK_MIN_HEADROOM * 2)
+            ),
+        );
+    }
+    PreflightCheck::pass("conntrack", format!("{}, {} free", usage, usage.headroom()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preflight_verdicts() {
+        let reflector = SocketAddr::from(([192, 0, 2, 1], 443));
+        let ms = |v: u64| Some(Duration::from_millis(v));
+
+        let clean = reflector_check(reflector, &[ms(10), ms(12), ms(20)]);
+        assert_eq!(clean.status, CheckStatus::Pass);
+        assert!(clean.detail.contains("min/avg/max 10.0/14.0/20.0 ms, 0% loss"), "{}", clean.detail);
+        assert!(clean.detail.ends_with("`--timeout 500` is enough"));
+        let lossy = reflector_check(reflector, &[ms(300), None]);
+        assert_eq!(lossy.status, CheckStatus::Warn);
+        assert!(lossy.remedy.unwrap().contains("--timeout` of at least 900 ms"));
+        assert_eq!(reflector_check(reflector, &[None, None]).status, CheckStatus::Fail);
+
+        assert_eq!(fd_limit_check(Some(1024)).status, CheckStatus::Warn);
+        assert_eq!(fd_limit_check(Some(65535)).status, CheckStatus::Pass);
+
+        assert_eq!(conntrack_check(None).status, CheckStatus::Pass);
+        assert_eq!(conntrack_check(Some(ConntrackUsage { count: 100, max: 262_144 })).status, CheckStatus::Pass);
+        let full = conntrack_check(Some(ConntrackUsage { count: 60_000, max: 65_536 }));
+        assert_eq!(full.status, CheckStatus::Warn);
+        assert!(full.remedy.unwrap().contains("nf_conntrack_max=191070"));
+
+        let report = PreflightReport { checks: vec![clean, route_check(None)] };
+        assert_eq!(report.status(), CheckStatus::Pass);
+        let report = PreflightReport { checks: vec![fd_limit_check(Some(1)), conntrack_check(None)] };
+        assert_eq!(report.status(), CheckStatus::Warn);
+        assert!(report.passed());
+        assert_eq!(report.exit_code(), 0);
+        let report = PreflightReport { checks: vec![reflector_check(reflector, &[None])] };
+        assert_eq!(report.exit_code(), HEALTHCHECK_UNHEALTHY);
+    }
+
+    #[tokio::test]
+    async fn test_healthcheck_stays_local() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let options = PreflightOptions { reflector: listener.local_addr().unwrap(), probes: 1, ..Default::default() };
+
+        let local = PreflightReport::healthcheck(false, None).await;
+        assert_eq!(local.checks.iter().map(|check| check.name).collect::<Vec<_>>(), ["file descriptors"]);
+        let probed = PreflightReport::healthcheck(false, Some(&options)).await;
+        assert_eq!(probed.checks.last().unwrap().status, CheckStatus::Pass);
+        assert_eq!(probed.exit_code(), 0);
+    }
+}
@@ -2,11 +2,17 @@
 
 use crate::config::ScanConfig;
 use crate::network::{ScanTechnique, stealth::StealthOptions};
+use crate::utils::config::{ConfigSchema, ConfigValidator};
+use crate::utils::device::DeviceResources;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use colored::*;
+use colored::Colorize;
+use crate::output::theme::{Paint, Role};
+
+/// Profiles that ship with Phobos and cannot be deleted
+const BUILTIN_PROFILES: [&str; 5] = ["quick", "stealth", "aggressive", "comprehensive", "constrained"];
 
 /// Predefined scan profile
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -171,6 +177,9 @@ impl ProfileManager {
             source_port: None,
             interface: None,
         });
+        
+        // Constrained profile - sized for the device Phobos runs on
+        self.profiles.insert("constrained".to_string(), DeviceResources::current().constrained_profile());
     }
     
     /// Load user-defined profiles from disk
@@ -186,10 +195,23 @@ impl ProfileManager {
             let entry = entry.map_err(|e| crate::ScanError::NetworkError(e.to_string()))?;
             let path = entry.path();
             
-            if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-                if let Ok(profile) = self.load_profile_from_file(&path) {
-                    self.profiles.insert(profile.name.clone(), profile);
-                }
+            if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+                continue;
+            }
+            
+            // Report problems instead of silently skipping or half-applying the profile
+            let Ok(diagnostics) = ConfigValidator::validate_config_file(&path, ConfigSchema::Profile) else { continue };
+            for diagnostic in &diagnostics {
+                let label = if diagnostic.is_error() { "[!] error:".paint(Role::Error) } else { "[!] warning:".paint(Role::Warning) };
+                eprintln!("{} {}:{}", label, path.display(), diagnostic);
+            }
+            if diagnostics.iter().any(|d| d.is_error()) {
+                eprintln!("{} skipping profile {}", "[!]".paint(Role::Warning), path.display());
+                continue;
+            }
+            
+            if let Ok(profile) = self.load_profile_from_file(&path) {
+                self.profiles.insert(profile.name.clone(), profile);
             }
         }
         
@@ -217,8 +239,8 @@ impl ProfileManager {
             .map_err(|e| crate::ScanError::NetworkError(format!("Failed to save profile: {}", e)))?;
         
         println!("{} {}", 
-            "[✓] Profile saved:".bright_green().bold(),
-            path.display().to_string().bright_cyan()
+            "[✓] Profile saved:".paint(Role::Success).bold(),
+            path.display().to_string().paint(Role::Accent)
         );
         
         Ok(())
@@ -240,7 +262,7 @@ impl ProfileManager {
     
     /// List all available profiles
     pub fn list_profiles(&self) {
-        println!("{}", "Available Scan Profiles:".bright_yellow().bold());
+        println!("{}", "Available Scan Profiles:".paint(Role::Warning).bold());
         println!();
         
         // Sort profiles by name
@@ -248,25 +270,25 @@ impl ProfileManager {
         profiles.sort_by(|a, b| a.name.cmp(&b.name));
         
         for profile in profiles {
-            let profile_type = if ["quick", "stealth", "aggressive", "comprehensive"].contains(&profile.name.as_str()) {
-                "[Built-in]".bright_blue()
+            let profile_type = if BUILTIN_PROFILES.contains(&profile.name.as_str()) {
+                "[Built-in]".paint(Role::Info)
             } else {
-                "[User]".bright_green()
+                "[User]".paint(Role::Success)
             };
             
             println!("{} {} {}", 
                 profile_type,
-                profile.name.bright_white().bold(),
-                format!("- {}", profile.description).bright_cyan()
+                profile.name.paint(Role::Heading).bold(),
+                format!("- {}", profile.description).paint(Role::Accent)
             );
             
             println!("    {} {} {} {} {} {}",
-                format!("Technique: {}", format!("{:?}", profile.technique)).bright_yellow(),
-                format!("Threads: {}", profile.threads).bright_magenta(),
-                format!("Timeout: {}ms", profile.timeout).bright_blue(),
-                format!("Timing: T{}", profile.timing_template).bright_green(),
-                format!("Stealth: {}", profile.stealth_level).bright_red(),
-                if profile.adaptive { "Adaptive".bright_cyan() } else { "Static".bright_white() }
+                format!("Technique: {:?}", profile.technique).paint(Role::Warning),
+                format!("Threads: {}", profile.threads).paint(Role::Highlight),
+                format!("Timeout: {}ms", profile.timeout).paint(Role::Info),
+                format!("Timing: T{}", profile.timing_template).paint(Role::Success),
+                format!("Stealth: {}", profile.stealth_level).paint(Role::Error),
+                if profile.adaptive { "Adaptive".paint(Role::Accent) } else { "Static".paint(Role::Heading) }
             );
             println!();
         }
@@ -311,7 +333,7 @@ impl ProfileManager {
     /// Delete a user profile
     pub fn delete_profile(&mut self, name: &str) -> crate::Result<()> {
         // Don't allow deletion of built-in profiles
-        if ["quick", "stealth", "aggressive", "comprehensive"].contains(&name) {
+        if BUILTIN_PROFILES.contains(&name) {
             return Err(crate::ScanError::InvalidTarget("Cannot delete built-in profiles".to_string()));
         }
         
@@ -325,8 +347,8 @@ impl ProfileManager {
             }
             
             println!("{} {}", 
-                "[✓] Profile deleted:".bright_green().bold(),
-                name.bright_cyan()
+                "[✓] Profile deleted:".paint(Role::Success).bold(),
+                name.paint(Role::Accent)
             );
         } else {
             return Err(crate::ScanError::InvalidTarget(format!("Profile '{}' not found", name)));
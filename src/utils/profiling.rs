@@ -0,0 +1,125 @@
+//! Built-in sampling profiler behind `--profile-out`
+//!
+//! Samples every thread of the process while the scan runs and writes what
+//! it saw when the scan ends, so a "slower than expected" report can carry a
+//! profile without perf, dtrace or a debugger. Paths ending in `.svg` get a
+//! flamegraph; anything else gets a pprof protobuf for `go tool pprof` or any
+//! viewer that reads that format.
+
+use crate::ScanError;
+use pprof::protos::Message;
+use pprof::{ProfilerGuard, ProfilerGuardBuilder};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Samples per second; off the beat of timers that fire on round intervals
+pub const SAMPLE_FREQUENCY: i32 = 99;
+
+/// Libraries whose frames would only add noise below the scanner's own
+const BLOCKLIST: [&str; 4] = ["libc", "libgcc", "pthread", "vdso"];
+
+/// What a profile is written as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Flamegraph,
+    Pprof,
+}
+
+impl ProfileFormat {
+    /// Flamegraph for `.svg` paths, pprof protobuf otherwise
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => ProfileFormat::Flamegraph,
+            _ => ProfileFormat::Pprof,
+        }
+    }
+}
+
+/// A running profile, written to its path by [`finish`](Self::finish) or,
+/// failing that, when dropped
+pub struct ScanProfiler {
+    guard: Option<ProfilerGuard<'static>>,
+    path: PathBuf,
+}
+
+impl ScanProfiler {
+    /// Start sampling; the file is only created when the profile is written
+    pub fn start(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let guard = ProfilerGuardBuilder::default()
+            .frequency(SAMPLE_FREQUENCY)
+            .blocklist(&BLOCKLIST)
+            .build()
+            .map_err(|e| ScanError::ConfigError(format!("Cannot start the profiler: {}", e)))?;
+        Ok(Self { guard: Some(guard), path: path.into() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop sampling and write the profile; returns how many samples it holds
+    pub fn finish(mut self) -> crate::Result<usize> {
+        self.write()
+    }
+
+    fn write(&mut self) -> crate::Result<usize> {
+        let Some(guard) = self.guard.take() else { return Ok(0) };
+        let invalid = |e: pprof::Error| ScanError::OutputError(format!("Cannot build the profile: {}", e));
+        let report = guard.report().build().map_err(invalid)?;
+        let samples = report.data.values().map(|&count| count as usize).sum();
+        // Stop sampling before the file is written
+        drop(guard);
+
+        let mut file = File::create(&self.path)?;
+        match ProfileFormat::for_path(&self.path) {
+            ProfileFormat::Flamegraph => report.flamegraph(&mut file).map_err(invalid)?,
+            ProfileFormat::Pprof => {
+                let encoded = report
+                    .pprof()
+                    .map_err(invalid)?
+                    .write_to_bytes()
+                    .map_err(|e| ScanError::OutputError(format!("Cannot encode the profile: {}", e)))?;
+                file.write_all(&encoded)?;
+            }
+        }
+        Ok(samples)
+    }
+}
+
+impl Drop for ScanProfiler {
+    fn drop(&mut self) {
+        if let Err(e) = self.write() {
+            eprintln!("Cannot write the profile to {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_in_both_formats() {
+        assert_eq!(ProfileFormat::for_path(Path::new("scan.SVG")), ProfileFormat::Flamegraph);
+        assert_eq!(ProfileFormat::for_path(Path::new("scan.pb")), ProfileFormat::Pprof);
+        assert_eq!(ProfileFormat::for_path(Path::new("scan")), ProfileFormat::Pprof);
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["scan.svg", "scan.pb"] {
+            let profiler = ScanProfiler::start(dir.path().join(name)).unwrap();
+            // Enough work for a few samples at 99 Hz
+            let mut x = 0u64;
+            let started = std::time::Instant::now();
+            while started.elapsed() < std::time::Duration::from_millis(200) {
+                x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+            }
+            profiler.finish().unwrap();
+            let written = std::fs::read(dir.path().join(name)).unwrap();
+            assert!(!written.is_empty(), "{}", name);
+            if name.ends_with(".svg") {
+                assert!(written.starts_with(b"<?xml"));
+            }
+        }
+    }
+}
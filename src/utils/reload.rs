@@ -0,0 +1,157 @@
+//! Configuration reload for long-running modes
+//!
+//! A long-running mode keeps its settings (schedules, notification sinks,
+//! policies) in a [`Reloader`] rather than reading them once at startup. A
+//! reload, on SIGHUP or whenever the mode asks for one, loads and validates
+//! everything again and only swaps the result in when all of it is valid; a
+//! bad edit is reported and the running settings stay as they were. Work in
+//! progress holds the snapshot it started with, so a scan never changes
+//! settings halfway through and the next one picks up the new ones.
+
+use crate::config::ScanConfig;
+use crate::utils::config::{ConfigSchema, ConfigValidator};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+type Loader<T> = Box<dyn Fn() -> crate::Result<T> + Send + Sync>;
+
+/// Settings that can be reloaded while in use
+pub struct Reloader<T> {
+    /// What is being reloaded, for messages, e.g. the file name
+    label: String,
+    load: Loader<T>,
+    current: watch::Sender<Arc<T>>,
+    generation: AtomicU64,
+}
+
+/// Result of one reload
+#[derive(Debug)]
+pub enum ReloadOutcome {
+    /// The new settings are in use
+    Applied { generation: u64 },
+    /// The new settings were invalid; the previous ones stay in use
+    Rejected(crate::ScanError),
+}
+
+impl<T: Send + Sync + 'static> Reloader<T> {
+    /// Load the initial settings with `load`, which must read and validate
+    /// everything a reload replaces; failing here is a startup error
+    pub fn new(
+        label: impl Into<String>,
+        load: impl Fn() -> crate::Result<T> + Send + Sync + 'static,
+    ) -> crate::Result<Self> {
+        let initial = load()?;
+        let (current, _) = watch::channel(Arc::new(initial));
+        Ok(Self { label: label.into(), load: Box::new(load), current, generation: AtomicU64::new(0) })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Settings in use now; keep the snapshot for the length of a scan
+    pub fn current(&self) -> Arc<T> {
+        self.current.borrow().clone()
+    }
+
+    /// Notified with the new settings after every applied reload
+    pub fn subscribe(&self) -> watch::Receiver<Arc<T>> {
+        self.current.subscribe()
+    }
+
+    /// Applied reloads since startup
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    /// Load and validate again, and apply the result only if that succeeded
+    pub fn reload(&self) -> ReloadOutcome {
+        match (self.load)() {
+            Ok(settings) => {
+                self.current.send_replace(Arc::new(settings));
+                let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+                log::info!("Reloaded {} (generation {})", self.label, generation);
+                ReloadOutcome::Applied { generation }
+            }
+            Err(e) => {
+                log::warn!("Keeping the previous {}: {}", self.label, e);
+                ReloadOutcome::Rejected(e)
+            }
+        }
+    }
+}
+
+impl Reloader<ScanConfig> {
+    /// A scan config file, checked against its schema and validated as a
+    /// whole before each reload is applied
+    pub fn config_file(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        Self::new(path.display().to_string(), move || load_scan_config(&path))
+    }
+}
+
+fn load_scan_config(path: &Path) -> crate::Result<ScanConfig> {
+    let diagnostics = ConfigValidator::validate_config_file(path, ConfigSchema::Scan)?;
+    if let Some(error) = diagnostics.iter().find(|d| d.is_error()) {
+        return Err(crate::ScanError::ConfigError(format!("{}: {}", path.display(), error)));
+    }
+    let config = ScanConfig::from_toml_file(path)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Reload `reloader` on every SIGHUP until the task is aborted, passing
+/// each outcome to `report`
+#[cfg(unix)]
+pub fn reload_on_sighup<T: Send + Sync + 'static>(
+    reloader: Arc<Reloader<T>>,
+    report: impl Fn(&Reloader<T>, ReloadOutcome) + Send + 'static,
+) -> crate::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())
+        .map_err(|e| crate::ScanError::ConfigError(format!("Cannot listen for SIGHUP: {}", e)))?;
+    Ok(tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            report(&reloader, reloader.reload());
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_reload_keeps_running_config() {
+        let path = std::env::temp_dir().join(format!("phobos-reload-{}.toml", std::process::id()));
+        let write = |target: &str, threads: usize| {
+            let config = ScanConfig { target: target.to_string(), threads, ..ScanConfig::default() };
+            std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        };
+
+        write("10.0.0.1", 100);
+        let reloader = Reloader::config_file(&path).unwrap();
+        let in_flight = reloader.current();
+        let updates = reloader.subscribe();
+
+        write("10.0.0.2", 200);
+        assert!(matches!(reloader.reload(), ReloadOutcome::Applied { generation: 1 }));
+        assert_eq!(reloader.current().target, "10.0.0.2");
+        assert!(updates.has_changed().unwrap());
+        // A scan that started before the reload keeps its settings
+        assert_eq!(in_flight.threads, 100);
+
+        // Parses, but fails validation: nothing changes
+        write("10.0.0.3", 0);
+        assert!(matches!(reloader.reload(), ReloadOutcome::Rejected(_)));
+        std::fs::write(&path, "threads = [").unwrap();
+        assert!(matches!(reloader.reload(), ReloadOutcome::Rejected(_)));
+        assert_eq!(reloader.current().target, "10.0.0.2");
+        assert_eq!(reloader.generation(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
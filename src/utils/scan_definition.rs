@@ -0,0 +1,272 @@
+//! Shareable scan definitions (`.phobos` files)
+//!
+//! A definition bundles what a scan recipe needs apart from its target:
+//! ports, technique, timing, scripts and output. Values a team leaves open
+//! are declared as variables and written as `{{ name }}` placeholders, so
+//! one vetted file can be run against any scope:
+//!
+//! ```toml
+//! format_version = 1
+//! name = "web-baseline"
+//! description = "Web ports with default scripts, one JSON report per target"
+//!
+//! [vars.target]
+//! description = "Host or hostname to scan"
+//!
+//! [vars.outdir]
+//! default = "reports"
+//!
+//! [scan]
+//! target = "{{ target }}"
+//! ports = "web,8000-8100"
+//! technique = "syn"
+//! timing = 4
+//!
+//! [output]
+//! format = "json"
+//! file = "{{ outdir }}/{{ target }}.json"
+//! ```
+//!
+//! `phobos run web-baseline.phobos --var target=192.0.2.10` expands the
+//! definition into the equivalent command line and runs it.
+
+use crate::ScanError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Newest `format_version` this build understands
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A variable the definition leaves open
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Variable {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Used when the variable is not given; without one it is required
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// What is scanned, and how
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScanSection {
+    pub target: String,
+    /// `-p` spec
+    #[serde(default)]
+    pub ports: Option<String>,
+    /// `--exclude-ports` spec
+    #[serde(default)]
+    pub exclude_ports: Option<String>,
+    /// `-s` technique, e.g. `syn` or `connect`
+    #[serde(default)]
+    pub technique: Option<String>,
+    /// `-T` template, 0-5
+    #[serde(default)]
+    pub timing: Option<u8>,
+    /// Probes per second
+    #[serde(default)]
+    pub rate_limit: Option<u64>,
+    /// Per-probe timeout in milliseconds
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// `--scripts` mode
+    #[serde(default)]
+    pub scripts: Option<String>,
+    #[serde(default)]
+    pub script_tags: Vec<String>,
+    /// Further flags, passed as they are
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Where results go
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSection {
+    /// `-o` format
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// A parsed `.phobos` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScanDefinition {
+    pub format_version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub vars: BTreeMap<String, Variable>,
+    pub scan: ScanSection,
+    #[serde(default)]
+    pub output: OutputSection,
+}
+
+impl ScanDefinition {
+    pub fn parse(content: &str) -> crate::Result<Self> {
+        let definition: Self = toml::from_str(content)
+            .map_err(|e| ScanError::ConfigError(format!("Invalid scan definition: {}", e)))?;
+        if definition.format_version > FORMAT_VERSION {
+            return Err(ScanError::ConfigError(format!(
+                "scan definition '{}' has format_version {}; this phobos reads up to {}",
+                definition.name, definition.format_version, FORMAT_VERSION
+            )));
+        }
+        Ok(definition)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ScanError::ConfigError(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::parse(&content)
+    }
+
+    /// Value of every variable: `given`, then defaults
+    ///
+    /// Fails on a variable the definition does not declare, so that a typo
+    /// in `--var` is not silently ignored, and on a required one not given.
+    pub fn bind(&self, given: &[(String, String)]) -> crate::Result<BTreeMap<String, String>> {
+        let mut values = BTreeMap::new();
+        for (name, value) in given {
+            if !self.vars.contains_key(name) {
+                return Err(ScanError::ConfigError(format!(
+                    "scan definition '{}' has no variable '{}' (declared: {})",
+                    self.name,
+                    name,
+                    self.vars.keys().cloned().collect::<Vec<_>>().join(", ")
+                )));
+            }
+            values.insert(name.clone(), value.clone());
+        }
+        for (name, variable) in &self.vars {
+            if values.contains_key(name) {
+                continue;
+            }
+            match &variable.default {
+                Some(default) => {
+                    values.insert(name.clone(), default.clone());
+                }
+                None => {
+                    let hint = variable.description.as_deref().map(|d| format!(" ({})", d)).unwrap_or_default();
+                    return Err(ScanError::ConfigError(format!("missing --var {}=...{}", name, hint)));
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Command line arguments, after the program name, that run this
+    /// definition with `given` variables
+    pub fn to_args(&self, given: &[(String, String)]) -> crate::Result<Vec<String>> {
+        let values = self.bind(given)?;
+        let render = |template: &str| render(template, &values);
+        let scan = &self.scan;
+
+        let mut args = vec![render(&scan.target)?];
+        let mut push = |flag: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(flag.to_string());
+                args.push(value);
+            }
+        };
+        push("--ports", scan.ports.as_deref().map(render).transpose()?);
+        push("--exclude-ports", scan.exclude_ports.as_deref().map(render).transpose()?);
+        push("--scan-type", scan.technique.clone());
+        push("--timing", scan.timing.map(|t| t.to_string()));
+        push("--rate-limit", scan.rate_limit.map(|r| r.to_string()));
+        push("--timeout", scan.timeout.map(|t| t.to_string()));
+        push("--scripts", scan.scripts.clone());
+        if !scan.script_tags.is_empty() {
+            let tags = scan.script_tags.iter().map(|tag| render(tag)).collect::<crate::Result<Vec<_>>>()?;
+            push("--script-tags", Some(tags.join(",")));
+        }
+        push("--output", self.output.format.clone());
+        push("--output-file", self.output.file.as_deref().map(render).transpose()?);
+        for arg in &scan.args {
+            args.push(render(arg)?);
+        }
+        Ok(args)
+    }
+}
+
+/// Replace `{{ name }}` placeholders with their values
+fn render(template: &str, values: &BTreeMap<String, String>) -> crate::Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            ScanError::ConfigError(format!("unclosed placeholder in '{}'", template))
+        })?;
+        let name = rest[start + 2..start + end].trim();
+        let value = values.get(name).ok_or_else(|| {
+            ScanError::ConfigError(format!("placeholder '{{{{ {} }}}}' names no declared variable", name))
+        })?;
+        out.push_str(value);
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Split `--var` and `--var=` pairs out of `args`
+///
+/// Returns the variables and the arguments left over, in order.
+pub fn split_vars(args: &[String]) -> crate::Result<(Vec<(String, String)>, Vec<String>)> {
+    let mut vars = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let pair = match arg.strip_prefix("--var") {
+            Some("") => args.next().ok_or_else(|| ScanError::ConfigError("--var needs NAME=VALUE".to_string()))?,
+            Some(inline) if inline.starts_with('=') => &inline[1..],
+            _ => {
+                rest.push(arg.clone());
+                continue;
+            }
+        };
+        let (name, value) = pair.split_once('=').ok_or_else(|| {
+            ScanError::ConfigError(format!("--var {} is not NAME=VALUE", pair))
+        })?;
+        vars.push((name.trim().to_string(), value.to_string()));
+    }
+    Ok((vars, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_definition_expands_to_arguments() {
+        let definition = ScanDefinition::parse(include_str!("../../examples/web-baseline.phobos")).unwrap();
+        assert_eq!(definition.name, "web-baseline");
+
+        let (vars, rest) = split_vars(&strings(&["--var", "target=192.0.2.10", "--var=outdir=/tmp", "--verbose"])).unwrap();
+        assert_eq!(rest, ["--verbose"]);
+        let args = definition.to_args(&vars).unwrap();
+        assert_eq!(args[0], "192.0.2.10");
+        assert!(args.windows(2).any(|pair| pair == ["--output-file", "/tmp/192.0.2.10.json"]));
+        assert!(args.windows(2).any(|pair| pair == ["--timing", "4"]));
+
+        let missing = definition.to_args(&[]).unwrap_err().to_string();
+        assert!(missing.contains("--var target="), "{}", missing);
+        let typo = definition.to_args(&[("traget".to_string(), "x".to_string())]).unwrap_err().to_string();
+        assert!(typo.contains("no variable 'traget'"), "{}", typo);
+
+        let undeclared = ScanDefinition::parse("format_version = 1\nname = \"x\"\n[scan]\ntarget = \"{{ host }}\"\n").unwrap();
+        assert!(undeclared.to_args(&[]).unwrap_err().to_string().contains("{{ host }}"));
+        assert!(ScanDefinition::parse("format_version = 2\nname = \"x\"\n[scan]\ntarget = \"a\"\n").is_err());
+    }
+}
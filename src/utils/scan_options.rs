@@ -1,6 +1,8 @@
 //! Scan options and configurations
 
 use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,6 +27,38 @@ impl ScanOrder {
     }
 }
 
+/// Order in which the hosts of a multi-host target are probed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostOrder {
+    /// One host at a time, in target order
+    Serial,
+    /// One host at a time, in shuffled order
+    Random,
+    /// All hosts share one probe queue that alternates between them
+    #[default]
+    Interleave,
+}
+
+impl HostOrder {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "serial" | "seq" | "sequential" => Some(Self::Serial),
+            "random" | "rand" | "shuffle" => Some(Self::Random),
+            "interleave" | "interleaved" => Some(Self::Interleave),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Serial => "serial",
+            Self::Random => "random",
+            Self::Interleave => "interleave",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScanOptions {
     pub scan_order: ScanOrder,
@@ -90,6 +124,14 @@ pub fn order_ports(ports: Vec<u16>, scan_order: ScanOrder) -> Vec<u16> {
     }
 }
 
+/// Order hosts for a one-at-a-time sweep; `seed` makes a random order replayable
+pub fn order_hosts<T>(hosts: &mut [T], host_order: HostOrder, seed: Option<u64>) {
+    if host_order == HostOrder::Random {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed.unwrap_or_else(rand::random));
+        hosts.shuffle(&mut rng);
+    }
+}
+
 /// Retry mechanism for failed scans
 pub async fn retry_operation<F, T, E>(
     mut operation: F,
@@ -118,4 +160,27 @@ where
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_order() {
+        let hosts: Vec<u32> = (0..64).collect();
+        let mut serial = hosts.clone();
+        order_hosts(&mut serial, HostOrder::Serial, Some(7));
+        assert_eq!(serial, hosts);
+
+        let mut first = hosts.clone();
+        let mut second = hosts.clone();
+        order_hosts(&mut first, HostOrder::Random, Some(7));
+        order_hosts(&mut second, HostOrder::Random, Some(7));
+        assert_eq!(first, second);
+        assert_ne!(first, hosts);
+
+        assert_eq!(HostOrder::parse("Interleaved"), Some(HostOrder::Interleave));
+        assert_eq!(HostOrder::default().name(), "interleave");
+    }
+}
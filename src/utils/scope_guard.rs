@@ -0,0 +1,164 @@
+//! Confirmation gate for scanning public address space
+//!
+//! A mistyped prefix (`/8` instead of `/28`) or octet (`8.8.0.0` for
+//! `10.8.0.0`) quietly turns an internal scan into one against other people's
+//! networks. Targets with public addresses are listed before the scan and must
+//! be confirmed, unless `--yes` is given or a scope file covers them.
+
+use super::address_class::AddressClass;
+use super::address_exclusions::AddressExclusions;
+use super::file_input::FileInputHandler;
+use super::target_parser::{ParsedTarget, TargetType};
+use crate::ScanError;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Networks a scope file authorizes
+#[derive(Debug, Clone)]
+pub struct ScopeAllowlist {
+    networks: AddressExclusions,
+    entries: usize,
+}
+
+impl ScopeAllowlist {
+    /// Load a Burp or HackerOne scope export, or a plain list of IPs, ranges
+    /// and CIDRs (one or more per line, `#` starts a comment)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        if let Ok(scope) = Self::from_export(&path) {
+            return Ok(scope);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let mut scope = Self { networks: AddressExclusions::new(), entries: 0 };
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            for entry in line.split([',', ' ', '\t']).filter(|e| !e.is_empty()) {
+                scope.networks.add_exclusion(entry).map_err(|e| {
+                    ScanError::ConfigError(format!("{}:{}: `{}`: {}", path.as_ref().display(), number + 1, entry, e))
+                })?;
+                scope.entries += 1;
+            }
+        }
+        Ok(scope)
+    }
+
+    /// In-scope addresses and networks of a Burp or HackerOne scope export
+    pub fn from_export<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let (import, _) = FileInputHandler::default()
+            .read_scope_from_file(path)
+            .map_err(|e| ScanError::ConfigError(e.to_string()))?;
+        let mut scope = Self { networks: AddressExclusions::new(), entries: 0 };
+        for target in &import.targets {
+            let added = match (&target.target_type, &target.cidr_info) {
+                (TargetType::Ipv4Cidr | TargetType::Ipv6Cidr, Some(cidr)) => {
+                    scope.networks.add_exclusion(&format!("{}/{}", cidr.network, cidr.prefix_length))
+                }
+                _ => target.addresses.iter().try_for_each(|addr| scope.networks.add_exclusion(&addr.to_string())),
+            };
+            added.map_err(ScanError::ConfigError)?;
+            scope.entries += 1;
+        }
+        Ok(scope)
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.networks.is_excluded(addr)
+    }
+
+    /// Number of addresses, ranges and networks listed
+    pub fn len(&self) -> usize {
+        self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+}
+
+/// A target with public addresses that nothing has authorized
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicTarget {
+    pub target: String,
+    pub target_type: TargetType,
+    /// Public addresses the scan would probe
+    pub addresses: Vec<IpAddr>,
+}
+
+impl fmt::Display for PublicTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target_type {
+            TargetType::Ipv4Cidr | TargetType::Ipv6Cidr => {
+                write!(f, "{} ({} public addresses)", self.target, self.addresses.len())
+            }
+            TargetType::SingleIpv4 | TargetType::SingleIpv6 => f.write_str(&self.target),
+            TargetType::Hostname | TargetType::HostnameList => {
+                let shown: Vec<String> = self.addresses.iter().take(3).map(IpAddr::to_string).collect();
+                let more = if self.addresses.len() > 3 { ", ..." } else { "" };
+                write!(f, "{} ({}{})", self.target, shown.join(", "), more)
+            }
+        }
+    }
+}
+
+/// Targets whose public addresses are neither excluded nor in `scope`
+pub fn unauthorized_public_targets(
+    targets: &[ParsedTarget],
+    excluded: &AddressExclusions,
+    scope: Option<&ScopeAllowlist>,
+) -> Vec<PublicTarget> {
+    targets
+        .iter()
+        .filter_map(|target| {
+            let addresses: Vec<IpAddr> = target
+                .addresses
+                .iter()
+                .copied()
+                .filter(|&addr| AddressClass::of(addr).is_public())
+                .filter(|&addr| !excluded.is_excluded(addr))
+                .filter(|&addr| !scope.is_some_and(|scope| scope.contains(addr)))
+                .collect();
+            (!addresses.is_empty()).then(|| PublicTarget {
+                target: target.original.clone(),
+                target_type: target.target_type.clone(),
+                addresses,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::target_parser::TargetParser;
+
+    #[test]
+    fn test_public_targets_need_authorization() {
+        let parser = TargetParser::default();
+        let targets: Vec<ParsedTarget> = ["10.0.0.0/30", "203.0.114.0/30", "1.1.1.1", "127.0.0.1"]
+            .iter()
+            .map(|t| parser.parse_target(t).unwrap())
+            .collect();
+        let none = AddressExclusions::new();
+
+        let public = unauthorized_public_targets(&targets, &none, None);
+        assert_eq!(public.len(), 2);
+        assert_eq!(public[0].to_string(), "203.0.114.0/30 (4 public addresses)");
+        assert_eq!(public[1].to_string(), "1.1.1.1");
+
+        let excluded = AddressExclusions::from_str("1.1.1.1").unwrap();
+        assert_eq!(unauthorized_public_targets(&targets, &excluded, None).len(), 1);
+
+        let path = std::env::temp_dir().join(format!("phobos-scope-{}.txt", std::process::id()));
+        std::fs::write(&path, "# engagement 42\n203.0.114.0/31, 1.1.1.1\n").unwrap();
+        let scope = ScopeAllowlist::from_file(&path).unwrap();
+        assert_eq!(scope.len(), 2);
+        let public = unauthorized_public_targets(&targets, &none, Some(&scope));
+        assert_eq!(public.len(), 1);
+        assert_eq!(public[0].addresses.len(), 2);
+
+        std::fs::write(&path, "10.0.0.0/33\n").unwrap();
+        let error = ScopeAllowlist::from_file(&path).unwrap_err().to_string();
+        assert!(error.contains(":1: `10.0.0.0/33`"), "{}", error);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,399 @@
+//! Verified self-update from GitHub releases
+//!
+//! `--update` installs the prebuilt binary the release workflow publishes for
+//! this platform. A download is only installed when its SHA-256 matches the
+//! release's `SHA256SUMS` and `SHA256SUMS.minisig` is a valid minisign
+//! signature of that file by the release key (`minisign.pub` next to this
+//! file). Skipping the signature takes an explicit opt-out.
+//! Building from the source tarball is left to the caller, which has to ask
+//! first: the tarball is neither checksummed nor signed.
+
+use crate::ScanError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::Blake2b512;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const RELEASE_API: &str = "https://api.github.com/repos/ibrahmsql/phobos/releases/latest";
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+/// Minisign public key releases are signed with; only a comment until a
+/// maintainer commits the key
+pub const RELEASE_PUBLIC_KEY: &str = include_str!("minisign.pub");
+
+/// Release asset built for the running platform, as named by the release workflow
+pub fn platform_asset() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("phobos-linux-x86_64"),
+        ("windows", "x86_64") => Some("phobos-windows-x86_64.exe"),
+        ("macos", "x86_64") => Some("phobos-macos-x86_64"),
+        ("macos", "aarch64") => Some("phobos-macos-aarch64"),
+        _ => None,
+    }
+}
+
+/// `major.minor.patch` of a tag such as `v1.2.0`, ignoring pre-release suffixes
+pub fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let core = tag.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next().unwrap_or(Some(0))?, parts.next().unwrap_or(Some(0))?);
+    parts.next().is_none().then_some(version)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub url: String,
+    pub size: u64,
+}
+
+/// The latest release as reported by the GitHub API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub tag: String,
+    pub tarball_url: Option<String>,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    pub fn from_json(json: &serde_json::Value) -> crate::Result<Self> {
+        let tag = json["tag_name"]
+            .as_str()
+            .ok_or_else(|| ScanError::ParseError("release has no tag_name".to_string()))?
+            .to_string();
+        let assets = json["assets"]
+            .as_array()
+            .map(|assets| {
+                assets
+                    .iter()
+                    .filter_map(|asset| {
+                        Some(ReleaseAsset {
+                            name: asset["name"].as_str()?.to_string(),
+                            url: asset["browser_download_url"].as_str()?.to_string(),
+                            size: asset["size"].as_u64().unwrap_or(0),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self { tag, tarball_url: json["tarball_url"].as_str().map(str::to_string), assets })
+    }
+
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+
+    /// Whether this release is newer than `current` (a Cargo version)
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        match (parse_version(&self.tag), parse_version(current)) {
+            (Some(latest), Some(current)) => latest > current,
+            _ => self.tag.trim_start_matches('v') != current,
+        }
+    }
+}
+
+/// What `--update --check-only` reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateCheck {
+    pub current: String,
+    pub latest: String,
+    pub update_available: bool,
+    /// Binary for this platform, if the release has one
+    pub binary: Option<String>,
+    pub checksummed: bool,
+    pub signed: bool,
+}
+
+impl UpdateCheck {
+    pub fn new(release: &Release, current: &str) -> Self {
+        let binary = platform_asset().filter(|name| release.asset(name).is_some()).map(str::to_string);
+        Self {
+            current: current.to_string(),
+            latest: release.tag.clone(),
+            update_available: release.is_newer_than(current),
+            binary,
+            checksummed: release.asset(CHECKSUMS_ASSET).is_some(),
+            signed: release.asset(SIGNATURE_ASSET).is_some(),
+        }
+    }
+}
+
+impl fmt::Display for UpdateCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Installed:  {}", self.current)?;
+        writeln!(
+            f,
+            "Latest:     {}{}",
+            self.latest,
+            if self.update_available { " (update available)" } else { " (up to date)" }
+        )?;
+        writeln!(f, "Binary:     {}", self.binary.as_deref().unwrap_or("none for this platform"))?;
+        let verification = match (self.checksummed, self.signed) {
+            (true, true) => "SHA256SUMS, minisign signature",
+            (true, false) => "SHA256SUMS only, unsigned; installing it needs --allow-unsigned",
+            (false, _) => "none; the binary cannot be verified",
+        };
+        writeln!(f, "Verified by: {}", verification)
+    }
+}
+
+/// SHA-256 digests by file name, from `sha256sum` output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checksums {
+    digests: HashMap<String, [u8; 32]>,
+}
+
+impl Checksums {
+    pub fn parse(text: &str) -> crate::Result<Self> {
+        let mut digests = HashMap::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let invalid = || ScanError::ParseError(format!("{}:{}: not a sha256sum line", CHECKSUMS_ASSET, number + 1));
+            let (hex, name) = line.trim().split_once(char::is_whitespace).ok_or_else(invalid)?;
+            // `*` marks binary mode in sha256sum output
+            let name = name.trim_start().trim_start_matches('*');
+            digests.insert(name.to_string(), decode_hex(hex).ok_or_else(invalid)?);
+        }
+        Ok(Self { digests })
+    }
+
+    /// Check `data` against the digest listed for `name`
+    pub fn verify(&self, name: &str, data: &[u8]) -> crate::Result<()> {
+        let expected = self
+            .digests
+            .get(name)
+            .ok_or_else(|| ScanError::ConfigError(format!("{} has no entry for {}", CHECKSUMS_ASSET, name)))?;
+        let actual: [u8; 32] = Sha256::digest(data).into();
+        if &actual != expected {
+            return Err(ScanError::ConfigError(format!(
+                "SHA-256 mismatch for {}: expected {}, got {}",
+                name,
+                encode_hex(expected),
+                encode_hex(&actual)
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_base64(text: &str) -> crate::Result<Vec<u8>> {
+    STANDARD.decode(text.trim()).map_err(|e| ScanError::ParseError(format!("invalid base64: {}", e)))
+}
+
+/// A minisign (Ed25519) public key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinisignKey {
+    key_id: [u8; 8],
+    public_key: [u8; 32],
+}
+
+impl MinisignKey {
+    /// Parse a `.pub` file, or just its base64 line
+    pub fn parse(text: &str) -> crate::Result<Self> {
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+            .ok_or_else(|| ScanError::ParseError("empty minisign public key".to_string()))?;
+        let bytes = decode_base64(line)?;
+        if bytes.len() != 42 || &bytes[..2] != b"Ed" {
+            return Err(ScanError::ParseError("not a minisign Ed25519 public key".to_string()));
+        }
+        let mut key = Self { key_id: [0; 8], public_key: [0; 32] };
+        key.key_id.copy_from_slice(&bytes[2..10]);
+        key.public_key.copy_from_slice(&bytes[10..]);
+        Ok(key)
+    }
+
+    /// Parse a `.pub` file that may hold only comments, as the release key
+    /// does before one is committed; None then
+    pub fn parse_optional(text: &str) -> crate::Result<Option<Self>> {
+        let empty = text.lines().map(str::trim).all(|line| line.is_empty() || line.starts_with("untrusted comment:"));
+        if empty {
+            return Ok(None);
+        }
+        Self::parse(text).map(Some)
+    }
+
+    /// Check a `.minisig` file against `data`, including its trusted comment
+    pub fn verify(&self, data: &[u8], signature_file: &str) -> crate::Result<()> {
+        let invalid = |what: &str| ScanError::ConfigError(format!("invalid signature: {}", what));
+        let mut lines = signature_file.lines().map(str::trim_end);
+        let _untrusted = lines.next().filter(|l| l.starts_with("untrusted comment:")).ok_or_else(|| invalid("no untrusted comment"))?;
+        let signature = decode_base64(lines.next().ok_or_else(|| invalid("no signature line"))?)?;
+        let trusted = lines
+            .next()
+            .and_then(|l| l.strip_prefix("trusted comment: "))
+            .ok_or_else(|| invalid("no trusted comment"))?;
+        let global = decode_base64(lines.next().ok_or_else(|| invalid("no global signature"))?)?;
+        if signature.len() != 74 || global.len() != 64 {
+            return Err(invalid("wrong length"));
+        }
+        if signature[2..10] != self.key_id {
+            return Err(invalid("signed with a different key"));
+        }
+        // "ED" signs the BLAKE2b-512 hash of the file, legacy "Ed" the file itself
+        let signed = match &signature[..2] {
+            b"ED" => Blake2b512::digest(data).to_vec(),
+            b"Ed" => data.to_vec(),
+            _ => return Err(invalid("unknown algorithm")),
+        };
+        let ed25519 = &signature[10..];
+        if !self.check(&signed, ed25519)? {
+            return Err(invalid("file signature does not match"));
+        }
+        if !self.check(&[ed25519, trusted.as_bytes()].concat(), &global)? {
+            return Err(invalid("trusted comment signature does not match"));
+        }
+        Ok(())
+    }
+
+    fn check(&self, message: &[u8], signature: &[u8]) -> crate::Result<bool> {
+        let key = VerifyingKey::from_bytes(&self.public_key)
+            .map_err(|e| ScanError::ConfigError(format!("invalid minisign public key: {}", e)))?;
+        let Ok(signature) = Signature::from_slice(signature) else { return Ok(false) };
+        Ok(key.verify(message, &signature).is_ok())
+    }
+}
+
+/// Check a downloaded binary against the checksums, and the checksums
+/// against their signature by [`RELEASE_PUBLIC_KEY`] unless `allow_unsigned`
+pub fn verify_binary(
+    name: &str,
+    binary: &[u8],
+    checksums: &str,
+    signature: Option<&str>,
+    allow_unsigned: bool,
+) -> crate::Result<()> {
+    if !allow_unsigned {
+        let signature = signature.ok_or_else(|| {
+            ScanError::ConfigError(format!("release has no {}; refusing to install an unsigned binary", SIGNATURE_ASSET))
+        })?;
+        MinisignKey::parse_optional(RELEASE_PUBLIC_KEY)
+            .map_err(|e| ScanError::ConfigError(format!("release public key unusable ({}); refusing to install", e)))?
+            .ok_or_else(|| ScanError::ConfigError("this build has no release public key; refusing to install a signed update".to_string()))?
+            .verify(checksums.as_bytes(), signature)?;
+    }
+    Checksums::parse(checksums)?.verify(name, binary)
+}
+
+/// Replace the executable at `target` with `binary`. The new file is written
+/// next to it and renamed over it, so an interrupted update leaves the old
+/// binary in place.
+pub fn install_binary(binary: &[u8], target: &Path) -> crate::Result<PathBuf> {
+    let dir = target.parent().ok_or_else(|| ScanError::ConfigError(format!("{} has no directory", target.display())))?;
+    let staged = dir.join(format!(".phobos-update-{}", std::process::id()));
+    std::fs::write(&staged, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // A running executable can be renamed but not overwritten on Windows
+    #[cfg(windows)]
+    {
+        let _ = std::fs::rename(target, target.with_extension("old.exe"));
+    }
+    if let Err(e) = std::fs::rename(&staged, target) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e.into());
+    }
+    Ok(target.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn sign(key: &SigningKey, message: &[u8]) -> Vec<u8> {
+        key.sign(message).to_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_release_verification() {
+        assert_eq!(parse_version("v1.10.0"), Some((1, 10, 0)));
+        assert_eq!(parse_version("2.0.1-rc1"), Some((2, 0, 1)));
+        assert_eq!(parse_version("latest"), None);
+        let release = Release::from_json(&serde_json::json!({
+            "tag_name": "v1.2.0",
+            "assets": [{"name": "SHA256SUMS", "browser_download_url": "https://example.invalid/SHA256SUMS", "size": 90}],
+        }))
+        .unwrap();
+        assert!(release.is_newer_than("1.1.1"));
+        assert!(!release.is_newer_than("1.10.0"));
+        assert!(UpdateCheck::new(&release, "1.1.1").checksummed);
+
+        let binary = b"phobos binary";
+        let sums = format!("{}  phobos-linux-x86_64\n", encode_hex(&Sha256::digest(binary)));
+        let checksums = Checksums::parse(&sums).unwrap();
+        checksums.verify("phobos-linux-x86_64", binary).unwrap();
+        assert!(checksums.verify("phobos-linux-x86_64", b"tampered").unwrap_err().to_string().contains("mismatch"));
+        assert!(checksums.verify("phobos-macos-aarch64", binary).is_err());
+        assert!(Checksums::parse("not a checksum line\n").is_err());
+
+        // Minisign files built from a fresh Ed25519 key
+        let secret = SigningKey::from_bytes(&rand::random());
+        let key_id = *b"phobos42";
+        let public = [b"Ed".as_slice(), &key_id, secret.verifying_key().as_bytes()].concat();
+        let key = MinisignKey::parse(&format!(
+            "untrusted comment: minisign public key\n{}\n",
+            STANDARD.encode(&public)
+        ))
+        .unwrap();
+        let trusted = "timestamp:1760000000\tfile:SHA256SUMS";
+        let signature_file = |data: &[u8]| {
+            let prehashed = Blake2b512::digest(data);
+            let signature = sign(&secret, &prehashed);
+            let global = sign(&secret, &[signature.as_slice(), trusted.as_bytes()].concat());
+            format!(
+                "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+                STANDARD.encode([b"ED".as_slice(), &key_id, &signature].concat()),
+                trusted,
+                STANDARD.encode(&global)
+            )
+        };
+        let signed = signature_file(sums.as_bytes());
+        key.verify(sums.as_bytes(), &signed).unwrap();
+        assert!(key.verify(b"other checksums", &signed).is_err());
+        let forged_comment = signed.replace("file:SHA256SUMS", "file:other");
+        assert!(key.verify(sums.as_bytes(), &forged_comment).unwrap_err().to_string().contains("trusted comment"));
+
+        // Installing takes the release key's signature unless the user opts out
+        let unsigned = verify_binary("phobos-linux-x86_64", binary, &sums, None, false).unwrap_err();
+        assert!(unsigned.to_string().contains("refusing to install an unsigned binary"));
+        assert!(verify_binary("phobos-linux-x86_64", binary, &sums, Some(&signed), false).is_err());
+        verify_binary("phobos-linux-x86_64", binary, &sums, None, true).unwrap();
+        assert!(verify_binary("phobos-linux-x86_64", b"tampered", &sums, None, true).is_err());
+    }
+
+    #[test]
+    fn test_release_public_key_is_a_key_or_none() {
+        // minisign.pub holds a key or, until a maintainer commits one, only a comment
+        MinisignKey::parse_optional(RELEASE_PUBLIC_KEY).unwrap();
+        assert_eq!(MinisignKey::parse_optional("untrusted comment: no key yet\n").unwrap(), None);
+        assert!(MinisignKey::parse_optional("untrusted comment: key\nnot base64\n").is_err());
+        if MinisignKey::parse_optional(RELEASE_PUBLIC_KEY).unwrap().is_none() {
+            let refused = verify_binary("phobos-linux-x86_64", b"", "", Some("signature"), false).unwrap_err();
+            assert!(refused.to_string().contains("no release public key"));
+        }
+    }
+}
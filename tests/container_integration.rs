@@ -0,0 +1,169 @@
+//! Accuracy of each technique against containers with known services
+//!
+//! `fixtures/integration/docker-compose.yml` runs nginx, redis, CoreDNS and
+//! Samba at fixed addresses; these tests scan them and check that every
+//! technique, the UDP payloads, service detection and the output formats
+//! report what is really there. Run them with `make test-integration`, which
+//! starts the containers and removes them afterwards. The raw techniques need
+//! root (or CAP_NET_RAW) and are skipped, with a note, without it.
+
+use phobos::config::ScanConfig;
+use phobos::intelligence::{MemoryPool, ServiceDetectionEngine, ServiceDetector, UltraFastThreadPool};
+use phobos::network::socket::RawSocket;
+use phobos::network::{PortState, ScanTechnique};
+use phobos::output::{OutputConfig, OutputFormat, OutputManager};
+use phobos::scanner::engine::ScanEngine;
+use phobos::scanner::ScanResult;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::process::Command;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+const COMPOSE_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/integration/docker-compose.yml");
+
+const NGINX: Ipv4Addr = Ipv4Addr::new(172, 31, 213, 10);
+const REDIS: Ipv4Addr = Ipv4Addr::new(172, 31, 213, 11);
+const DNS: Ipv4Addr = Ipv4Addr::new(172, 31, 213, 12);
+const SMB: Ipv4Addr = Ipv4Addr::new(172, 31, 213, 13);
+
+/// Nothing in the lab listens here
+const CLOSED_PORT: u16 = 81;
+
+/// How long the containers get to start answering
+const STARTUP: Duration = Duration::from_secs(90);
+
+/// Start the lab (a no-op when it is running) and wait for every service
+///
+/// Only Docker can say the lab is up: a proxy that accepts every connection
+/// would make the services look reachable without it.
+fn lab() {
+    static LAB: OnceLock<()> = OnceLock::new();
+    LAB.get_or_init(|| {
+        let status = Command::new("docker")
+            .args(["compose", "-f", COMPOSE_FILE, "up", "--detach", "--wait"])
+            .status()
+            .expect("docker is needed for the container integration tests");
+        assert!(status.success(), "docker compose up failed");
+
+        let services = [(NGINX, 80), (REDIS, 6379), (DNS, 53), (SMB, 445)];
+        let up = |&(ip, port): &(Ipv4Addr, u16)| {
+            TcpStream::connect_timeout(&SocketAddr::from((ip, port)), Duration::from_millis(500)).is_ok()
+        };
+        let deadline = Instant::now() + STARTUP;
+        while !services.iter().all(up) {
+            assert!(Instant::now() < deadline, "lab services did not come up within {:?}", STARTUP);
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
+
+/// Whether raw techniques can run here; says why not when they cannot
+fn raw_sockets(test: &str) -> bool {
+    let available = RawSocket::new_tcp().is_ok();
+    if !available {
+        eprintln!("skipping {}: raw sockets need root or CAP_NET_RAW", test);
+    }
+    available
+}
+
+async fn scan(target: Ipv4Addr, ports: &[u16], technique: ScanTechnique) -> ScanResult {
+    lab();
+    let config = ScanConfig {
+        target: target.to_string(),
+        ports: ports.to_vec(),
+        technique,
+        threads: 10,
+        timeout: 2000,
+        rate_limit: 1000,
+        ..Default::default()
+    };
+    let engine = ScanEngine::new(config).await.expect("engine for the lab");
+    engine.scan().await.expect("scan of the lab")
+}
+
+fn states(result: &ScanResult) -> HashMap<u16, PortState> {
+    result.port_results.iter().map(|pr| (pr.port, pr.state)).collect()
+}
+
+#[tokio::test]
+async fn test_connect_scan_finds_every_service() {
+    for (ip, port) in [(NGINX, 80), (REDIS, 6379), (DNS, 53), (SMB, 445)] {
+        let found = states(&scan(ip, &[port, CLOSED_PORT], ScanTechnique::Connect).await);
+        assert_eq!(found.get(&port), Some(&PortState::Open), "{}:{}", ip, port);
+        assert_eq!(found.get(&CLOSED_PORT), Some(&PortState::Closed), "{}:{}", ip, CLOSED_PORT);
+    }
+}
+
+#[tokio::test]
+async fn test_raw_tcp_techniques() {
+    if !raw_sockets("test_raw_tcp_techniques") {
+        return;
+    }
+    // What each technique should call an open and a closed port
+    let expected = [
+        (ScanTechnique::Syn, PortState::Open, PortState::Closed),
+        (ScanTechnique::Fin, PortState::OpenFiltered, PortState::Closed),
+        (ScanTechnique::Null, PortState::OpenFiltered, PortState::Closed),
+        (ScanTechnique::Xmas, PortState::OpenFiltered, PortState::Closed),
+        (ScanTechnique::Ack, PortState::Unfiltered, PortState::Unfiltered),
+        (ScanTechnique::Window, PortState::Unfiltered, PortState::Unfiltered),
+    ];
+    for (technique, open, closed) in expected {
+        let found = states(&scan(NGINX, &[80, CLOSED_PORT], technique).await);
+        assert_eq!(found.get(&80), Some(&open), "{:?} on an open port", technique);
+        assert_eq!(found.get(&CLOSED_PORT), Some(&closed), "{:?} on a closed port", technique);
+    }
+}
+
+#[tokio::test]
+async fn test_udp_payload_gets_a_dns_answer() {
+    if !raw_sockets("test_udp_payload_gets_a_dns_answer") {
+        return;
+    }
+    let found = states(&scan(DNS, &[53, CLOSED_PORT], ScanTechnique::Udp).await);
+    assert_eq!(found.get(&53), Some(&PortState::Open), "the DNS payload should draw an answer");
+    assert_eq!(found.get(&CLOSED_PORT), Some(&PortState::Closed), "a closed UDP port answers port unreachable");
+}
+
+#[tokio::test]
+async fn test_service_detection() {
+    lab();
+    let memory_pool = Arc::new(MemoryPool::new(1024, true));
+    let thread_pool = Arc::new(UltraFastThreadPool::new(2, memory_pool.clone()));
+    let detector = ServiceDetectionEngine::new(Duration::from_secs(3), thread_pool, memory_pool).await.unwrap();
+
+    let web = detector.detect_service(SocketAddr::from((NGINX, 80))).await.unwrap();
+    assert_eq!(web.service_name, "http");
+    assert!(web.technologies.iter().any(|t| t.name == "nginx"), "{:?}", web.technologies);
+
+    let redis = detector.detect_service(SocketAddr::from((REDIS, 6379))).await.unwrap();
+    assert_eq!(redis.service_name, "redis");
+}
+
+#[tokio::test]
+async fn test_output_formats_report_the_open_port() {
+    let result = scan(NGINX, &[80, CLOSED_PORT], ScanTechnique::Connect).await;
+    let dir = tempfile::tempdir().unwrap();
+    let write = |format: OutputFormat| {
+        let path = dir.path().join(format!("{:?}", format));
+        let config = OutputConfig {
+            format,
+            file: Some(path.to_string_lossy().into_owned()),
+            colored: false,
+            ..Default::default()
+        };
+        OutputManager::new(config).write_results(&result).unwrap();
+        std::fs::read_to_string(path).unwrap()
+    };
+
+    let json: serde_json::Value = serde_json::from_str(&write(OutputFormat::Json)).unwrap();
+    let open: Vec<u64> = json["open_ports"].as_array().unwrap().iter().filter_map(|p| p["port"].as_u64()).collect();
+    assert_eq!(open, [80]);
+
+    assert!(write(OutputFormat::Xml).contains(r#"<port number="80" protocol="tcp" state="open""#));
+    assert!(write(OutputFormat::Csv).lines().any(|line| line.starts_with(&format!("{},80,tcp,open,", NGINX))));
+    assert!(write(OutputFormat::Greppable).contains(&format!("Host: {} () Ports: 80/tcp/open/", NGINX)));
+    let nmap = write(OutputFormat::Nmap);
+    assert!(nmap.contains("80/tcp") && !nmap.contains(&format!("{}/tcp", CLOSED_PORT)));
+}
@@ -230,4 +230,97 @@ async fn test_performance_target() {
     // Should achieve at least 100 ports per second
     assert!(rate > 100.0, "Scan rate too low: {:.2} ports/sec", rate);
     assert_eq!(result.open_ports.len() + result.closed_ports.len() + result.filtered_ports.len(), 1000);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_host_callbacks() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let config = ScanConfig {
+        target: "127.0.0.0/30".to_string(),
+        ports: vec![open, 1],
+        technique: ScanTechnique::Connect,
+        threads: 10,
+        timeout: 1000,
+        rate_limit: 1000,
+        ..Default::default()
+    };
+    
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = std::sync::Arc::clone(&seen);
+    let engine = ScanEngine::new(config).await.unwrap().with_host_callback(move |host| {
+        let sink = std::sync::Arc::clone(&sink);
+        async move { sink.lock().unwrap().push(host) }
+    });
+    let result = engine.scan().await.unwrap();
+    
+    let mut hosts = seen.lock().unwrap().clone();
+    hosts.sort_by_key(|h| h.host);
+    assert_eq!(hosts.len(), 4);
+    for host in &hosts {
+        let stats = &host.stats;
+        assert_eq!(stats.packets_sent + stats.errors, 2);
+    }
+    assert_eq!(hosts[1].open_ports, vec![open]);
+    assert!(hosts.iter().filter(|h| h.host != std::net::Ipv4Addr::new(127, 0, 0, 1)).all(|h| h.open_ports.is_empty()));
+    assert_eq!(result.open_ports, vec![open]);
+}
+
+#[tokio::test]
+async fn test_ipv6_scans() {
+    let Ok(listener) = tokio::net::TcpListener::bind("[::1]:0").await else {
+        eprintln!("skipping test_ipv6_scans: no IPv6 loopback");
+        return;
+    };
+    let open = listener.local_addr().unwrap().port();
+    let closed = std::net::TcpListener::bind("[::1]:0").unwrap().local_addr().unwrap().port();
+    // SYN goes through a raw IPv6 socket when it can, TCP Connect otherwise
+    for technique in [ScanTechnique::Connect, ScanTechnique::Syn] {
+        let config = ScanConfig {
+            target: "::1/128".to_string(),
+            ports: vec![open, closed],
+            technique,
+            threads: 10,
+            timeout: 1000,
+            rate_limit: 1000,
+            ..Default::default()
+        };
+        let result = ScanEngine::new(config).await.unwrap().scan().await.unwrap();
+        assert_eq!(result.open_ports, vec![open], "{:?}", technique);
+        assert_eq!(result.stats.ports_closed, 1, "{:?}", technique);
+    }
+}
+
+#[tokio::test]
+async fn test_scans_every_target() {
+    let first = tokio::net::TcpListener::bind("127.0.0.2:0").await.unwrap();
+    let second = tokio::net::TcpListener::bind("127.0.0.3:0").await.unwrap();
+    let ports = vec![first.local_addr().unwrap().port(), second.local_addr().unwrap().port()];
+    let config = ScanConfig {
+        ports: ports.clone(),
+        technique: ScanTechnique::Connect,
+        threads: 10,
+        timeout: 1000,
+        rate_limit: 1000,
+        exclude_ips: Some(vec!["127.0.0.5".to_string()]),
+        ..Default::default()
+    }
+    .with_targets(vec!["127.0.0.3".to_string(), "127.0.0.2/32".to_string(), "127.0.0.4/31".to_string(), "127.0.0.3".to_string()]);
+    
+    let result = ScanEngine::new(config).await.unwrap().scan().await.unwrap();
+    let hosts: Vec<_> = result.hosts.iter().map(|h| h.host.to_string()).collect();
+    assert_eq!(hosts, ["127.0.0.2", "127.0.0.3", "127.0.0.4"]);
+    assert_eq!(result.open_ports.len(), 2);
+    
+    let reports = result.per_host();
+    assert_eq!(reports[0].target, "127.0.0.2");
+    assert_eq!(reports[0].open_ports, vec![ports[0]]);
+    assert_eq!(reports[1].open_ports, vec![ports[1]]);
+    assert!(reports[2].open_ports.is_empty());
+
+    // Only hosts with open ports get a report of their own, unless verbose
+    let reported: Vec<bool> = result.host_reports(false).into_iter().map(|(_, report)| report).collect();
+    assert_eq!(reported, [true, true, false]);
+    assert!(result.host_reports(true).iter().all(|(_, report)| *report));
+    assert_eq!(result.hosts_up(), 2);
+}
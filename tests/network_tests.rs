@@ -133,6 +133,11 @@ async fn test_packet_building() {
         seq_num: 1000,
         ack_num: 2000,
         window_size: 65535,
+        timestamp: None,
+        mss: Some(1460),
+        payload_len: 0,
+        checksum_valid: true,
+        ip_id: 0,
     };
     
     assert!(response.is_syn_ack());
@@ -0,0 +1,219 @@
+//! Properties of how the engine schedules probes
+//!
+//! Whatever the hosts, ports, exclusions, host order, batch size or worker
+//! count, every (host, port) pair the scan selects is probed exactly once
+//! and nothing else is.
+
+use phobos::intelligence::distributed::{LoadBalancer, LoadBalancingStrategy, NodeStatus, ScanTask, TaskPriority, WorkerNode};
+use phobos::network::Protocol;
+use phobos::scanner::engine::SocketIterator;
+use phobos::scanner::{CancellationToken, TaskGroup};
+use phobos::utils::address_exclusions::AddressExclusions;
+use phobos::utils::port_exclusions::PortExclusionManager;
+use phobos::utils::port_spec::PortSpec;
+use phobos::utils::scan_options::{order_hosts, HostOrder};
+use proptest::prelude::*;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, SystemTime};
+
+fn hosts() -> impl Strategy<Value = Vec<Ipv4Addr>> {
+    prop::collection::btree_set(any::<u32>(), 0..12).prop_map(|ips| ips.into_iter().map(Ipv4Addr::from).collect())
+}
+
+fn ports() -> impl Strategy<Value = Vec<u16>> {
+    prop::collection::btree_set(1..=u16::MAX, 0..48).prop_map(|ports| ports.into_iter().collect())
+}
+
+fn drain(mut sockets: SocketIterator) -> Vec<SocketAddr> {
+    std::iter::from_fn(|| sockets.next()).collect()
+}
+
+/// Every pair of `ips` and `ports`, each once
+fn assert_covers(sockets: &[SocketAddr], ips: &[Ipv4Addr], ports: &[u16]) {
+    let unique: HashSet<_> = sockets.iter().collect();
+    assert_eq!(unique.len(), sockets.len(), "a socket came up twice");
+    let expected: HashSet<_> = ips
+        .iter()
+        .flat_map(|&ip| ports.iter().map(move |&port| SocketAddr::new(IpAddr::V4(ip), port)))
+        .collect();
+    assert_eq!(unique, expected.iter().collect());
+}
+
+proptest! {
+    #[test]
+    fn socket_iterator_yields_every_pair_once(ips in hosts(), ports in ports()) {
+        let sequential = drain(SocketIterator::new(&ips, &ports));
+        assert_covers(&sequential, &ips, &ports);
+        // One host at a time, in the order given
+        let order: Vec<_> = sequential.chunks(ports.len().max(1)).map(|chunk| chunk[0].ip()).collect();
+        let given: Vec<_> = ips.iter().map(|&ip| IpAddr::V4(ip)).take(order.len()).collect();
+        prop_assert_eq!(order, given);
+
+        let interleaved = drain(SocketIterator::interleaved(&ips, &ports));
+        assert_covers(&interleaved, &ips, &ports);
+        // Every host sees its n-th port before any host sees its n+1-th
+        for round in interleaved.chunks(ips.len().max(1)) {
+            prop_assert!(round.iter().all(|socket| socket.port() == round[0].port()));
+        }
+    }
+
+    #[test]
+    fn host_sweeps_cover_every_pair_once(ips in hosts(), ports in ports(), seed in any::<u64>(), random in any::<bool>()) {
+        let host_order = if random { HostOrder::Random } else { HostOrder::Serial };
+        let mut ordered = ips.clone();
+        order_hosts(&mut ordered, host_order, Some(seed));
+        let sockets: Vec<_> = ordered.iter().flat_map(|&ip| drain(SocketIterator::new(&[ip], &ports))).collect();
+        assert_covers(&sockets, &ips, &ports);
+
+        // The same seed replays the same order
+        let mut again = ips.clone();
+        order_hosts(&mut again, host_order, Some(seed));
+        prop_assert_eq!(ordered, again);
+    }
+
+    #[test]
+    fn port_spec_exclusions_apply_after_inclusions(
+        included in prop::collection::vec((1..=u16::MAX, 0u16..300), 1..6),
+        excluded in prop::collection::vec((1..=u16::MAX, 0u16..300), 0..6),
+    ) {
+        let range = |&(start, len): &(u16, u16)| (start, start.saturating_add(len));
+        let mut items: Vec<String> = included.iter().map(range).map(|(a, b)| format!("{}-{}", a, b)).collect();
+        // Exclusions go first to show that their position does not matter
+        items.splice(0..0, excluded.iter().map(range).map(|(a, b)| format!("!{}-{}", a, b)));
+        let spec = PortSpec::parse(&items.join(",")).unwrap();
+
+        let expand = |ranges: &[(u16, u16)]| -> BTreeSet<u16> {
+            ranges.iter().map(range).flat_map(|(a, b)| a..=b).collect()
+        };
+        let expected: Vec<u16> = expand(&included).difference(&expand(&excluded)).copied().collect();
+        prop_assert_eq!(spec.ports_for(Protocol::Tcp), expected);
+    }
+
+    #[test]
+    fn port_exclusions_keep_every_other_port_once(
+        ports in prop::collection::vec(1..=u16::MAX, 0..200),
+        singles in prop::collection::vec(1..=u16::MAX, 0..10),
+        ranges in prop::collection::vec((1..=u16::MAX, 0u16..500), 0..4),
+    ) {
+        let ranges: Vec<_> = ranges.into_iter().map(|(start, len)| start..=start.saturating_add(len)).collect();
+        let manager = PortExclusionManager::new().exclude_ports(singles.clone()).exclude_port_ranges(ranges.clone());
+        let excluded = |port: &u16| singles.contains(port) || ranges.iter().any(|range| range.contains(port));
+
+        let kept = manager.filter_ports(ports.clone());
+        let expected: Vec<u16> = ports.iter().copied().filter(|port| !excluded(port)).collect();
+        prop_assert_eq!(kept, expected);
+    }
+
+    #[test]
+    fn address_exclusions_drop_exactly_the_excluded_hosts(
+        ips in hosts(),
+        blocks in prop::collection::vec((any::<u32>(), 0u8..=32), 0..4),
+    ) {
+        let spec: Vec<String> = blocks.iter().map(|&(addr, prefix)| format!("{}/{}", Ipv4Addr::from(addr), prefix)).collect();
+        let exclusions = AddressExclusions::from_str(&spec.join(",")).unwrap();
+        let inside = |ip: Ipv4Addr| blocks.iter().any(|&(addr, prefix)| {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == addr & mask
+        });
+
+        let addresses: Vec<IpAddr> = ips.iter().map(|&ip| IpAddr::V4(ip)).collect();
+        let expected: Vec<IpAddr> = ips.iter().filter(|&&ip| !inside(ip)).map(|&ip| IpAddr::V4(ip)).collect();
+        prop_assert_eq!(exclusions.filter_addresses(addresses), expected);
+    }
+
+    #[test]
+    fn batch_refill_probes_every_pair_once(
+        ips in hosts(),
+        ports in ports(),
+        limit in 1usize..64,
+        lowered in 1usize..64,
+        delays in prop::collection::vec(0usize..4, 1..16),
+    ) {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let in_flight = Cell::new(0usize);
+        let peak = Cell::new(0usize);
+        let probed = runtime.block_on(async {
+            let mut sockets = SocketIterator::interleaved(&ips, &ports);
+            let mut group = TaskGroup::new(limit, CancellationToken::new());
+            let mut probed = Vec::new();
+            let mut started = 0usize;
+            // The engine's refill loop: top the group up, then wait for one probe
+            loop {
+                while group.has_capacity() {
+                    let Some(socket) = sockets.next() else { break };
+                    let yields = delays[started % delays.len()];
+                    started += 1;
+                    let (in_flight, peak) = (&in_flight, &peak);
+                    in_flight.set(in_flight.get() + 1);
+                    peak.set(peak.get().max(in_flight.get()));
+                    group.spawn(async move {
+                        for _ in 0..yields {
+                            tokio::task::yield_now().await;
+                        }
+                        in_flight.set(in_flight.get() - 1);
+                        socket
+                    });
+                }
+                let Some(socket) = group.join_next().await else { break };
+                probed.push(socket);
+                // Halfway through, the batch size is retuned as the engine does
+                if probed.len() == started / 2 {
+                    group.set_limit(lowered);
+                }
+            }
+            probed
+        });
+        assert_covers(&probed, &ips, &ports);
+        prop_assert!(peak.get() <= limit.max(lowered));
+    }
+
+    #[test]
+    fn round_robin_shards_give_every_task_one_node(targets in 0usize..200, nodes in 1usize..12) {
+        let nodes: Vec<WorkerNode> = (0..nodes).map(worker).collect();
+        let mut tasks: Vec<ScanTask> = (0..targets).map(|i| task(Ipv4Addr::from(i as u32))).collect();
+        LoadBalancer::new(LoadBalancingStrategy::RoundRobin).assign_tasks(&mut tasks, &nodes);
+
+        let mut shards: HashMap<_, usize> = HashMap::new();
+        for task in &tasks {
+            let node = task.assigned_node.expect("every task gets a node");
+            prop_assert!(nodes.iter().any(|n| n.id == node));
+            *shards.entry(node).or_default() += 1;
+        }
+        prop_assert_eq!(shards.values().sum::<usize>(), targets);
+        // Shards differ by at most one task
+        let (least, most) = (targets / nodes.len(), targets.div_ceil(nodes.len()));
+        prop_assert!(shards.values().all(|&count| count == least || count == most));
+    }
+}
+
+fn worker(index: usize) -> WorkerNode {
+    WorkerNode {
+        id: uuid::Uuid::from_u128(index as u128 + 1),
+        address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        port: 7000 + index as u16,
+        capacity: 100,
+        current_load: 0,
+        last_heartbeat: None,
+        status: NodeStatus::Active,
+        performance_score: 1.0,
+        network_latency: Duration::ZERO,
+        failure_count: 0,
+        last_failure: None,
+        vantage: None,
+    }
+}
+
+fn task(target: Ipv4Addr) -> ScanTask {
+    ScanTask {
+        id: uuid::Uuid::new_v4(),
+        targets: vec![IpAddr::V4(target)],
+        ports: vec![80],
+        assigned_node: None,
+        priority: TaskPriority::Medium,
+        created_at: SystemTime::now(),
+        timeout: Duration::from_secs(1),
+        rate_share: None,
+    }
+}
@@ -54,6 +54,8 @@ async fn test_vulnerability_scanner() {
         version: Some("OpenSSH_7.4".to_string()),
         banner: Some("SSH-2.0-OpenSSH_7.4".to_string()),
         ssl_info: None,
+        technologies: Vec::new(),
+        favicon_hash: None,
         vulnerabilities: Vec::new(),
         response_time: Duration::from_millis(10),
     };